@@ -1,16 +1,53 @@
-use clap::{crate_description, crate_name, App, Arg};
+use clap::{crate_description, crate_name, value_t, value_t_or_exit, App, Arg};
 use console::style;
 use solana_clap_utils::input_validators::is_keypair;
 use solana_core::{
-    archiver::Archiver,
+    archiver::{Archiver, ArchiverBuilder, RedemptionPolicy, StorageFundingConfig},
+    archiver_client::StorageTurnSource,
+    archiver_proof_log::{ProofReport, PROOF_LOG_FILENAME},
+    archiver_storage::{LocalFileBackend, MmapBackend, ObjectStoreBackend, StorageBackend},
     cluster_info::{Node, VALIDATOR_PORT_RANGE},
     contact_info::ContactInfo,
 };
 use solana_sdk::{
     commitment_config::CommitmentConfig,
-    signature::{read_keypair_file, Keypair, KeypairUtil},
+    signature::{mlock_keypair, read_keypair_file, Keypair, KeypairUtil},
 };
-use std::{net::SocketAddr, path::PathBuf, process::exit, sync::Arc};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::exit,
+    sync::Arc,
+    time::Duration,
+};
+
+fn u64_validator(value: String) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|e| format!("{:?}", e))
+}
+
+fn nice_validator(value: String) -> Result<(), String> {
+    match value.parse::<i32>() {
+        Ok(nice) if nice >= -20 && nice <= 19 => Ok(()),
+        Ok(nice) => Err(format!("nice level {} is outside -20..19", nice)),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+// A directory, `mmap:DIRECTORY` or the http(s) URL of an object store bucket
+fn storage_backend_from_str(segment_storage: &str) -> Arc<dyn StorageBackend> {
+    if segment_storage.starts_with("http://") || segment_storage.starts_with("https://") {
+        Arc::new(ObjectStoreBackend::new(segment_storage))
+    } else if segment_storage.starts_with("mmap:") {
+        Arc::new(MmapBackend::new(Path::new(
+            &segment_storage["mmap:".len()..],
+        )))
+    } else {
+        Arc::new(LocalFileBackend::new(Path::new(segment_storage)))
+    }
+}
 
 fn main() {
     solana_logger::setup();
@@ -33,7 +70,7 @@ fn main() {
                 .long("entrypoint")
                 .value_name("HOST:PORT")
                 .takes_value(true)
-                .required(true)
+                .required_unless("proof_report")
                 .validator(solana_net_utils::is_host_port)
                 .help("Rendezvous with the cluster at this entry point"),
         )
@@ -52,14 +89,179 @@ fn main() {
                 .long("storage-keypair")
                 .value_name("PATH")
                 .takes_value(true)
-                .required(true)
+                .required_unless("proof_report")
                 .validator(is_keypair)
                 .help("File containing the storage account keypair"),
         )
+        .arg(
+            Arg::with_name("storage_account_funding")
+                .long("storage-account-funding")
+                .value_name("LAMPORTS")
+                .takes_value(true)
+                .default_value("1")
+                .validator(u64_validator)
+                .help("Lamports to fund the storage account with when it is created"),
+        )
+        .arg(
+            Arg::with_name("storage_account_top_up_threshold")
+                .long("storage-account-top-up-threshold")
+                .value_name("LAMPORTS")
+                .takes_value(true)
+                .validator(u64_validator)
+                .help("Top up the storage account from the fee payer keypair whenever its balance drops below this many lamports"),
+        )
+        .arg(
+            Arg::with_name("redeem_min_validations")
+                .long("redeem-min-validations")
+                .value_name("COUNT")
+                .takes_value(true)
+                .default_value("1")
+                .validator(u64_validator)
+                .help("Only claim storage rewards once this many valid proofs are pending"),
+        )
+        .arg(
+            Arg::with_name("redeem_min_interval")
+                .long("redeem-min-interval")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .default_value("0")
+                .validator(u64_validator)
+                .help("Minimum number of seconds between storage reward claims"),
+        )
+        .arg(
+            Arg::with_name("redeem_min_reward")
+                .long("redeem-min-reward")
+                .value_name("LAMPORTS")
+                .takes_value(true)
+                .default_value("0")
+                .validator(u64_validator)
+                .help("Only claim storage rewards once at least this many lamports are estimated to be pending"),
+        )
+        .arg(
+            Arg::with_name("resume")
+                .long("resume")
+                .takes_value(false)
+                .help("Keep proving the segment replicated by a previous run when it is still intact in the ledger directory"),
+        )
+        .arg(
+            Arg::with_name("segment_storage")
+                .long("segment-storage")
+                .value_name("DIR|mmap:DIR|URL")
+                .takes_value(true)
+                .help("Where to keep the encrypted segment: a directory, a directory read through memory maps, or the http(s) URL of an S3-compatible bucket accepting unsigned requests [default: the ledger directory]"),
+        )
+        .arg(
+            Arg::with_name("verify_shreds")
+                .long("verify-shreds")
+                .takes_value(false)
+                .help("Verify the signatures of downloaded shreds against the segment's leader schedule and drop invalid ones"),
+        )
+        .arg(
+            Arg::with_name("segment_download_timeout")
+                .long("segment-download-timeout")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .default_value("300")
+                .validator(u64_validator)
+                .help("Select another segment when no slot of the segment being downloaded completes for this many seconds"),
+        )
+        .arg(
+            Arg::with_name("fee_payer_keypair")
+                .long("fee-payer-keypair")
+                .value_name("PATH")
+                .takes_value(true)
+                .validator(is_keypair)
+                .help("File containing the keypair that pays for mining proofs and storage account top-ups [default: the identity keypair]"),
+        )
+        .arg(
+            Arg::with_name("storage_quota")
+                .long("storage-quota")
+                .value_name("BYTES")
+                .takes_value(true)
+                .validator(u64_validator)
+                .help("Evict the oldest proven segments to keep the replicated segments' shreds and encrypted copies within this many bytes [default: unlimited]"),
+        )
+        .arg(
+            Arg::with_name("lock_keypair_memory")
+                .long("lock-keypair-memory")
+                .takes_value(false)
+                .help("Lock the memory holding the identity, storage and fee payer keypairs so they are never swapped to disk"),
+        )
+        .arg(
+            Arg::with_name("storage_turn_source")
+                .long("storage-turn-source")
+                .value_name("SOURCE")
+                .takes_value(true)
+                .possible_values(&["rpc", "gossip"])
+                .default_value("rpc")
+                .help("Where to learn storage turns from: polling an RPC node, or the turn staked validators advertise in gossip with RPC as a fallback"),
+        )
+        .arg(
+            Arg::with_name("bind_address")
+                .long("bind-address")
+                .value_name("HOST")
+                .takes_value(true)
+                .validator(|value| solana_net_utils::parse_host(&value).map(|_| ()))
+                .help("Bind every socket to this address, to pick the interface of a multi-homed host [default: all interfaces, IPv6 and IPv4 if the public address is IPv6]"),
+        )
+        .arg(
+            Arg::with_name("direct_rpc")
+                .long("direct-rpc")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .requires("direct_tpu")
+                .validator(solana_net_utils::is_host_port)
+                .help("Send RPC requests to this address instead of discovering the cluster's RPC nodes through gossip, requires --direct-tpu"),
+        )
+        .arg(
+            Arg::with_name("direct_tpu")
+                .long("direct-tpu")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .requires("direct_rpc")
+                .validator(solana_net_utils::is_host_port)
+                .help("Send transactions to this address instead of discovering the cluster's nodes through gossip, requires --direct-rpc"),
+        )
+        .arg(
+            Arg::with_name("compute_threads")
+                .long("compute-threads")
+                .value_name("N")
+                .takes_value(true)
+                .validator(u64_validator)
+                .help("Number of threads encrypting and sampling the segment [default: one per core]"),
+        )
+        .arg(
+            Arg::with_name("nice")
+                .long("nice")
+                .value_name("LEVEL")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .validator(nice_validator)
+                .help("Run the threads encrypting and sampling the segment at this nice level, from -20 to 19, to leave the CPU to a validator on the same host (Linux only)"),
+        )
+        .arg(
+            Arg::with_name("proof_report")
+                .long("proof-report")
+                .takes_value(false)
+                .help("Summarize the proofs recorded in the ledger directory per segment and exit"),
+        )
         .get_matches();
 
     let ledger_path = PathBuf::from(matches.value_of("ledger").unwrap());
 
+    if matches.is_present("proof_report") {
+        let entries = Archiver::read_proof_history(&ledger_path).unwrap_or_else(|err| {
+            eprintln!(
+                "Unable to read proof log {:?}: {}",
+                ledger_path.join(PROOF_LOG_FILENAME),
+                err
+            );
+            exit(1);
+        });
+        print!("{}", ProofReport::new(&entries));
+        return;
+    }
+
     let keypair = if let Some(identity) = matches.value_of("identity") {
         read_keypair_file(identity).unwrap_or_else(|err| {
             eprintln!("{}: Unable to open keypair file: {}", err, identity);
@@ -77,6 +279,16 @@ fn main() {
         Keypair::new()
     };
 
+    let storage_funding = StorageFundingConfig {
+        initial_lamports: value_t_or_exit!(matches, "storage_account_funding", u64),
+        top_up_threshold: value_t!(matches, "storage_account_top_up_threshold", u64).ok(),
+    };
+    let redemption_policy = RedemptionPolicy {
+        min_validations: value_t_or_exit!(matches, "redeem_min_validations", u64),
+        min_interval: Duration::from_secs(value_t_or_exit!(matches, "redeem_min_interval", u64)),
+        min_reward_lamports: value_t_or_exit!(matches, "redeem_min_reward", u64),
+    };
+
     let entrypoint_addr = matches
         .value_of("entrypoint")
         .map(|entrypoint| {
@@ -91,8 +303,19 @@ fn main() {
         addr.set_ip(solana_net_utils::get_public_ip_addr(&entrypoint_addr).unwrap());
         addr
     };
-    let node =
-        Node::new_archiver_with_external_ip(&keypair.pubkey(), &gossip_addr, VALIDATOR_PORT_RANGE);
+    let node = match matches.value_of("bind_address") {
+        Some(bind_address) => Node::new_archiver_with_bind_ip(
+            &keypair.pubkey(),
+            &gossip_addr,
+            VALIDATOR_PORT_RANGE,
+            solana_net_utils::parse_host(bind_address).unwrap(),
+        ),
+        None => Node::new_archiver_with_external_ip(
+            &keypair.pubkey(),
+            &gossip_addr,
+            VALIDATOR_PORT_RANGE,
+        ),
+    };
 
     println!(
         "{} version {} (branch={}, commit={})",
@@ -108,16 +331,93 @@ fn main() {
         gossip_addr
     );
 
+    let lock_keypair_memory = matches.is_present("lock_keypair_memory");
+    let lock_keypair = |keypair: &Arc<Keypair>| {
+        if lock_keypair_memory {
+            if let Err(err) = mlock_keypair(keypair) {
+                eprintln!(
+                    "Unable to lock keypair {} memory: {}",
+                    keypair.pubkey(),
+                    err
+                );
+            }
+        }
+    };
+    let keypair = Arc::new(keypair);
+    let storage_keypair = Arc::new(storage_keypair);
+    lock_keypair(&keypair);
+    lock_keypair(&storage_keypair);
+
     let entrypoint_info = ContactInfo::new_gossip_entry_point(&entrypoint_addr);
-    let archiver = Archiver::new(
+    let mut builder = ArchiverBuilder::new(
         &ledger_path,
         node,
         entrypoint_info,
-        Arc::new(keypair),
-        Arc::new(storage_keypair),
-        CommitmentConfig::recent(),
+        keypair,
+        storage_keypair,
     )
-    .unwrap();
+    .client_commitment(CommitmentConfig::recent())
+    .storage_funding(storage_funding)
+    .redemption_policy(redemption_policy)
+    .verify_shreds(matches.is_present("verify_shreds"))
+    .segment_download_timeout(Duration::from_secs(value_t_or_exit!(
+        matches,
+        "segment_download_timeout",
+        u64
+    )));
+    if let Some(fee_payer_keypair) = matches.value_of("fee_payer_keypair") {
+        let fee_payer = read_keypair_file(fee_payer_keypair).unwrap_or_else(|err| {
+            eprintln!(
+                "{}: Unable to open keypair file: {}",
+                err, fee_payer_keypair
+            );
+            exit(1);
+        });
+        let fee_payer = Arc::new(fee_payer);
+        lock_keypair(&fee_payer);
+        builder = builder.fee_payer(fee_payer);
+    }
+    let storage_turn_source = match matches.value_of("storage_turn_source").unwrap() {
+        "gossip" => StorageTurnSource::Gossip,
+        _ => StorageTurnSource::Rpc,
+    };
+    builder = builder.storage_turn_source(storage_turn_source);
+    if let (Some(direct_rpc), Some(direct_tpu)) = (
+        matches.value_of("direct_rpc"),
+        matches.value_of("direct_tpu"),
+    ) {
+        let parse = |address: &str| {
+            solana_net_utils::parse_host_port(address).unwrap_or_else(|err| {
+                eprintln!("failed to parse address {}: {}", address, err);
+                exit(1);
+            })
+        };
+        builder = builder.direct_connect(parse(direct_rpc), parse(direct_tpu));
+    }
+    if let Ok(compute_threads) = value_t!(matches, "compute_threads", usize) {
+        builder = builder.compute_threads(compute_threads);
+    }
+    if let Ok(nice) = value_t!(matches, "nice", i32) {
+        builder = builder.compute_nice(nice);
+    }
+    if let Ok(storage_quota) = value_t!(matches, "storage_quota", u64) {
+        builder = builder.storage_quota(storage_quota);
+    }
+    if let Some(segment_storage) = matches.value_of("segment_storage") {
+        builder = builder.storage_backend(storage_backend_from_str(segment_storage));
+    }
+    let archiver = if matches.is_present("resume") {
+        builder.resume()
+    } else {
+        builder.build()
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("Failed to start archiver: {}", err);
+        exit(1);
+    });
 
-    archiver.join();
+    if let Err(err) = archiver.join() {
+        eprintln!("Archiver stopped with an error: {}", err);
+        exit(1);
+    }
 }
@@ -8,7 +8,9 @@ use num_traits::FromPrimitive;
 use serde_json::{self, json, Value};
 use solana_budget_api::budget_instruction::{self, BudgetError};
 use solana_clap_utils::{input_parsers::*, input_validators::*};
-use solana_client::{client_error::ClientError, rpc_client::RpcClient};
+use solana_client::{
+    airdrop::request_and_confirm_airdrop_with, client_error::ClientError, rpc_client::RpcClient,
+};
 #[cfg(not(test))]
 use solana_drone::drone::request_airdrop_transaction;
 #[cfg(test)]
@@ -23,6 +25,7 @@ use solana_sdk::{
     loader_instruction,
     message::Message,
     native_token::lamports_to_sol,
+    packet::PACKET_DATA_SIZE,
     pubkey::Pubkey,
     signature::{Keypair, KeypairUtil, Signature},
     system_instruction::SystemError,
@@ -36,16 +39,19 @@ use std::{
     fs::File,
     io::{Read, Write},
     net::{IpAddr, SocketAddr},
-    thread::sleep,
     time::Duration,
     {error, fmt},
 };
 
-const USERDATA_CHUNK_SIZE: usize = 229; // Keep program chunks under PACKET_DATA_SIZE
-
-#[derive(Debug)]
 pub struct KeypairEq(Keypair);
 
+// `Keypair`'s own `Debug` prints its secret key
+impl fmt::Debug for KeypairEq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeypairEq({})", self.pubkey())
+    }
+}
+
 impl From<Keypair> for KeypairEq {
     fn from(keypair: Keypair) -> Self {
         Self(keypair)
@@ -100,6 +106,7 @@ pub enum CliCommand {
     },
     DeactivateStake(Pubkey),
     DelegateStake(Pubkey, Pubkey, bool),
+    MergeStake(Pubkey, Pubkey),
     RedeemVoteCredits(Pubkey, Pubkey),
     ShowStakeHistory {
         use_lamports_unit: bool,
@@ -108,7 +115,14 @@ pub enum CliCommand {
         pubkey: Pubkey,
         use_lamports_unit: bool,
     },
+    SplitStake {
+        stake_account_pubkey: Pubkey,
+        split_stake_account: KeypairEq,
+        lamports: u64,
+        deactivate: bool,
+    },
     StakeAuthorize(Pubkey, Pubkey, StakeAuthorize),
+    StakeSetLockup(Pubkey, Lockup),
     WithdrawStake(Pubkey, Pubkey, u64),
     // Storage Commands
     CreateStorageAccount {
@@ -146,6 +160,7 @@ pub enum CliCommand {
         span: Option<u64>,
     },
     VoteAuthorize(Pubkey, Pubkey, VoteAuthorize),
+    VoteRotateAuthorized(Pubkey, Pubkey, VoteAuthorize),
     // Wallet Commands
     Address,
     Airdrop {
@@ -280,6 +295,8 @@ pub fn parse_command(matches: &ArgMatches<'_>) -> Result<CliCommandInfo, Box<dyn
         ("delegate-stake", Some(matches)) => parse_stake_delegate_stake(matches),
         ("withdraw-stake", Some(matches)) => parse_stake_withdraw_stake(matches),
         ("deactivate-stake", Some(matches)) => parse_stake_deactivate_stake(matches),
+        ("split-stake", Some(matches)) => parse_split_stake(matches),
+        ("merge-stake", Some(matches)) => parse_merge_stake(matches),
         ("stake-authorize-staker", Some(matches)) => {
             parse_stake_authorize(matches, StakeAuthorize::Staker)
         }
@@ -287,6 +304,7 @@ pub fn parse_command(matches: &ArgMatches<'_>) -> Result<CliCommandInfo, Box<dyn
             parse_stake_authorize(matches, StakeAuthorize::Withdrawer)
         }
         ("redeem-vote-credits", Some(matches)) => parse_redeem_vote_credits(matches),
+        ("stake-set-lockup", Some(matches)) => parse_stake_set_lockup(matches),
         ("show-stake-account", Some(matches)) => parse_show_stake_account(matches),
         ("show-stake-history", Some(matches)) => parse_show_stake_history(matches),
         // Storage Commands
@@ -318,6 +336,12 @@ pub fn parse_command(matches: &ArgMatches<'_>) -> Result<CliCommandInfo, Box<dyn
         ("vote-authorize-withdrawer", Some(matches)) => {
             parse_vote_authorize(matches, VoteAuthorize::Withdrawer)
         }
+        ("vote-rotate-voter", Some(matches)) => {
+            parse_vote_rotate_authorized(matches, VoteAuthorize::Voter)
+        }
+        ("vote-rotate-withdrawer", Some(matches)) => {
+            parse_vote_rotate_authorized(matches, VoteAuthorize::Withdrawer)
+        }
         ("show-vote-account", Some(matches)) => parse_vote_get_account_command(matches),
         ("uptime", Some(matches)) => parse_vote_uptime_command(matches),
         // Wallet Commands
@@ -611,6 +635,15 @@ fn process_show_account(
     Ok("".to_string())
 }
 
+// Largest piece of a program whose write transaction still fits in a packet
+fn max_program_chunk_size(payer_pubkey: &Pubkey, program_pubkey: &Pubkey) -> usize {
+    let instruction = loader_instruction::write(program_pubkey, &bpf_loader::id(), 0, vec![]);
+    let message = Message::new_with_payer(vec![instruction], Some(payer_pubkey));
+    let empty_write_size = bincode::serialized_size(&Transaction::new_unsigned(message)).unwrap();
+    // the compact length of the instruction data grows by up to 2 bytes as the chunk fills
+    PACKET_DATA_SIZE - empty_write_size as usize - 2
+}
+
 fn process_deploy(
     rpc_client: &RpcClient,
     config: &CliConfig,
@@ -639,14 +672,15 @@ fn process_deploy(
     );
     messages.push(&create_account_tx.message);
     let signers = [&config.keypair, &program_id];
+    let chunk_size = max_program_chunk_size(&config.keypair.pubkey(), &program_id.pubkey());
     let write_transactions: Vec<_> = program_data
-        .chunks(USERDATA_CHUNK_SIZE)
+        .chunks(chunk_size)
         .zip(0..)
         .map(|(chunk, i)| {
             let instruction = loader_instruction::write(
                 &program_id.pubkey(),
                 &bpf_loader::id(),
-                (i * USERDATA_CHUNK_SIZE) as u32,
+                (i * chunk_size) as u32,
                 chunk.to_vec(),
             );
             let message = Message::new_with_payer(vec![instruction], Some(&signers[0].pubkey()));
@@ -669,8 +703,17 @@ fn process_deploy(
     log_instruction_custom_error::<SystemError>(result)
         .map_err(|_| CliError::DynamicProgramError("Program allocate space failed".to_string()))?;
 
-    trace!("Writing program data");
-    rpc_client.send_and_confirm_transactions(write_transactions, &signers)?;
+    // Writes are all sent before any is confirmed, and those that don't land are re-signed with
+    // a fresh blockhash and sent again
+    trace!(
+        "Writing program data in {} chunks",
+        write_transactions.len()
+    );
+    rpc_client
+        .send_and_confirm_transactions(write_transactions, &signers)
+        .map_err(|err| {
+            CliError::DynamicProgramError(format!("Program write transactions failed: {}", err))
+        })?;
 
     trace!("Finalizing program account");
     rpc_client
@@ -919,6 +962,14 @@ pub fn process_command(config: &CliConfig) -> ProcessResult {
                 *force,
             )
         }
+        CliCommand::MergeStake(stake_account_pubkey, source_stake_account_pubkey) => {
+            process_merge_stake(
+                &rpc_client,
+                config,
+                &stake_account_pubkey,
+                &source_stake_account_pubkey,
+            )
+        }
         CliCommand::RedeemVoteCredits(stake_account_pubkey, vote_account_pubkey) => {
             process_redeem_vote_credits(
                 &rpc_client,
@@ -939,6 +990,19 @@ pub fn process_command(config: &CliConfig) -> ProcessResult {
         CliCommand::ShowStakeHistory { use_lamports_unit } => {
             process_show_stake_history(&rpc_client, config, *use_lamports_unit)
         }
+        CliCommand::SplitStake {
+            stake_account_pubkey,
+            split_stake_account,
+            lamports,
+            deactivate,
+        } => process_split_stake(
+            &rpc_client,
+            config,
+            &stake_account_pubkey,
+            split_stake_account,
+            *lamports,
+            *deactivate,
+        ),
         CliCommand::StakeAuthorize(
             stake_account_pubkey,
             new_authorized_pubkey,
@@ -950,6 +1014,9 @@ pub fn process_command(config: &CliConfig) -> ProcessResult {
             &new_authorized_pubkey,
             *stake_authorize,
         ),
+        CliCommand::StakeSetLockup(stake_account_pubkey, lockup) => {
+            process_stake_set_lockup(&rpc_client, config, &stake_account_pubkey, lockup)
+        }
 
         CliCommand::WithdrawStake(stake_account_pubkey, destination_account_pubkey, lamports) => {
             process_withdraw_stake(
@@ -1043,6 +1110,17 @@ pub fn process_command(config: &CliConfig) -> ProcessResult {
                 *vote_authorize,
             )
         }
+        CliCommand::VoteRotateAuthorized(
+            vote_account_pubkey,
+            new_authorized_pubkey,
+            vote_authorize,
+        ) => process_vote_rotate_authorized(
+            &rpc_client,
+            config,
+            &vote_account_pubkey,
+            &new_authorized_pubkey,
+            *vote_authorize,
+        ),
         CliCommand::Uptime {
             pubkey: vote_account_pubkey,
             aggregate,
@@ -1129,63 +1207,21 @@ pub fn process_command(config: &CliConfig) -> ProcessResult {
     }
 }
 
-// Quick and dirty Keypair that assumes the client will do retries but not update the
-// blockhash. If the client updates the blockhash, the signature will be invalid.
-struct DroneKeypair {
-    transaction: Transaction,
-}
-
-impl DroneKeypair {
-    fn new_keypair(
-        drone_addr: &SocketAddr,
-        to_pubkey: &Pubkey,
-        lamports: u64,
-        blockhash: Hash,
-    ) -> Result<Self, Box<dyn error::Error>> {
-        let transaction = request_airdrop_transaction(drone_addr, to_pubkey, lamports, blockhash)?;
-        Ok(Self { transaction })
-    }
-
-    fn airdrop_transaction(&self) -> Transaction {
-        self.transaction.clone()
-    }
-}
-
-impl KeypairUtil for DroneKeypair {
-    fn new() -> Self {
-        unimplemented!();
-    }
-
-    /// Return the public key of the keypair used to sign votes
-    fn pubkey(&self) -> Pubkey {
-        self.transaction.message().account_keys[0]
-    }
-
-    fn sign_message(&self, _msg: &[u8]) -> Signature {
-        self.transaction.signatures[0]
-    }
-}
-
 pub fn request_and_confirm_airdrop(
     rpc_client: &RpcClient,
     drone_addr: &SocketAddr,
     to_pubkey: &Pubkey,
     lamports: u64,
 ) -> ProcessResult {
-    let (blockhash, _fee_calculator) = rpc_client.get_recent_blockhash()?;
-    let keypair = {
-        let mut retries = 5;
-        loop {
-            let result = DroneKeypair::new_keypair(drone_addr, to_pubkey, lamports, blockhash);
-            if result.is_ok() || retries == 0 {
-                break result;
-            }
-            retries -= 1;
-            sleep(Duration::from_secs(1));
-        }
-    }?;
-    let mut tx = keypair.airdrop_transaction();
-    let result = rpc_client.send_and_confirm_transaction(&mut tx, &[&keypair]);
+    let result = request_and_confirm_airdrop_with(
+        rpc_client,
+        &[*drone_addr],
+        to_pubkey,
+        lamports,
+        CommitmentConfig::default(),
+        request_airdrop_transaction,
+    )
+    .map(|signature| signature.to_string());
     log_instruction_custom_error::<SystemError>(result)
 }
 
@@ -1250,7 +1286,7 @@ pub fn app<'ab, 'v>(name: &str, about: &'ab str, version: &'v str) -> App<'ab, '
                         .value_name("PATH TO BPF PROGRAM")
                         .takes_value(true)
                         .required(true)
-                        .help("/path/to/program.o"),
+                        .help("/path/to/program.so"),
                 ),
         )
         .stake_subcommands()
@@ -1852,6 +1888,14 @@ mod tests {
         let signature = process_command(&config);
         assert_eq!(signature.unwrap(), SIGNATURE.to_string());
 
+        config.command = CliCommand::VoteRotateAuthorized(
+            bob_pubkey,
+            new_authorized_pubkey,
+            VoteAuthorize::Withdrawer,
+        );
+        let signature = process_command(&config);
+        assert_eq!(signature.unwrap(), SIGNATURE.to_string());
+
         let bob_keypair = Keypair::new();
         let bob_pubkey = bob_keypair.pubkey();
         let custodian = Pubkey::new_rand();
@@ -1876,6 +1920,23 @@ mod tests {
         let signature = process_command(&config);
         assert_eq!(signature.unwrap(), SIGNATURE.to_string());
 
+        let stake_pubkey = Pubkey::new_rand();
+        config.command = CliCommand::StakeSetLockup(
+            stake_pubkey,
+            Lockup {
+                slot: 42,
+                custodian: Pubkey::new_rand(),
+            },
+        );
+        let signature = process_command(&config);
+        assert_eq!(signature.unwrap(), SIGNATURE.to_string());
+
+        let stake_pubkey = Pubkey::new_rand();
+        let source_stake_pubkey = Pubkey::new_rand();
+        config.command = CliCommand::MergeStake(stake_pubkey, source_stake_pubkey);
+        let signature = process_command(&config);
+        assert_eq!(signature.unwrap(), SIGNATURE.to_string());
+
         config.command = CliCommand::GetSlot;
         assert_eq!(process_command(&config).unwrap(), "0");
 
@@ -2013,6 +2074,10 @@ mod tests {
         config.command = CliCommand::VoteAuthorize(bob_pubkey, bob_pubkey, VoteAuthorize::Voter);
         assert!(process_command(&config).is_err());
 
+        config.command =
+            CliCommand::VoteRotateAuthorized(bob_pubkey, bob_pubkey, VoteAuthorize::Voter);
+        assert!(process_command(&config).is_err());
+
         config.command = CliCommand::GetSlot;
         assert!(process_command(&config).is_err());
 
@@ -2053,6 +2118,23 @@ mod tests {
         assert!(process_command(&config).is_err());
     }
 
+    #[test]
+    fn test_max_program_chunk_size() {
+        let payer = Pubkey::new_rand();
+        let program_id = Pubkey::new_rand();
+        let chunk_size = max_program_chunk_size(&payer, &program_id);
+
+        let instruction = loader_instruction::write(
+            &program_id,
+            &bpf_loader::id(),
+            std::u32::MAX,
+            vec![0xff; chunk_size],
+        );
+        let message = Message::new_with_payer(vec![instruction], Some(&payer));
+        let transaction = Transaction::new_unsigned(message);
+        assert!(bincode::serialized_size(&transaction).unwrap() as usize <= PACKET_DATA_SIZE);
+    }
+
     #[test]
     fn test_cli_deploy() {
         solana_logger::setup();
@@ -5,10 +5,11 @@ use crate::cli::{
 use clap::{App, Arg, ArgMatches, SubCommand};
 use console::style;
 use solana_clap_utils::{input_parsers::*, input_validators::*};
-use solana_client::rpc_client::RpcClient;
+use solana_client::{rpc_client::RpcClient, rpc_request::RpcEpochInfo};
 use solana_sdk::signature::Keypair;
 use solana_sdk::{
     account_utils::State,
+    clock::Epoch,
     pubkey::Pubkey,
     signature::KeypairUtil,
     system_instruction::SystemError,
@@ -21,7 +22,7 @@ use solana_sdk::{
 use solana_stake_api::stake_state::Meta;
 use solana_stake_api::{
     stake_instruction::{self, StakeError},
-    stake_state::{Authorized, Lockup, StakeAuthorize, StakeState},
+    stake_state::{Authorized, Lockup, Stake, StakeAuthorize, StakeState},
 };
 use solana_vote_api::vote_state::VoteState;
 use std::ops::Deref;
@@ -177,6 +178,72 @@ impl StakeSubCommands for App<'_, '_> {
                         .help("Stake account to be deactivated.")
                 )
         )
+        .subcommand(
+            SubCommand::with_name("split-stake")
+                .about("Split some of the lamports and stake of a stake account into a new stake account")
+                .arg(
+                    Arg::with_name("stake_account_pubkey")
+                        .index(1)
+                        .value_name("STAKE ACCOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey_or_keypair)
+                        .help("Stake account to be split")
+                )
+                .arg(
+                    Arg::with_name("split_stake_account")
+                        .index(2)
+                        .value_name("SPLIT STAKE ACCOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_keypair)
+                        .help("Keypair of the new stake account to receive the split-off amount")
+                )
+                .arg(
+                    Arg::with_name("amount")
+                        .index(3)
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The amount to move into the new stake account (default unit SOL)")
+                )
+                .arg(
+                    Arg::with_name("unit")
+                        .index(4)
+                        .value_name("UNIT")
+                        .takes_value(true)
+                        .possible_values(&["SOL", "lamports"])
+                        .help("Specify unit to use for request")
+                )
+                .arg(
+                    Arg::with_name("deactivate")
+                        .long("deactivate")
+                        .takes_value(false)
+                        .help("Deactivate the split-off stake, leaving the rest delegated")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("merge-stake")
+                .about("Merge all lamports and stake of one stake account into another")
+                .arg(
+                    Arg::with_name("stake_account_pubkey")
+                        .index(1)
+                        .value_name("STAKE ACCOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey_or_keypair)
+                        .help("Stake account to merge into")
+                )
+                .arg(
+                    Arg::with_name("source_stake_account_pubkey")
+                        .index(2)
+                        .value_name("SOURCE STAKE ACCOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey_or_keypair)
+                        .help("Stake account to be merged and emptied")
+                )
+        )
         .subcommand(
             SubCommand::with_name("withdraw-stake")
                 .about("Withdraw the unstaked lamports from the stake account")
@@ -215,6 +282,35 @@ impl StakeSubCommands for App<'_, '_> {
                         .help("Specify unit to use for request")
                 )
            )
+        .subcommand(
+            SubCommand::with_name("stake-set-lockup")
+                .about("Replace the lockup of the stake account")
+                .arg(
+                    Arg::with_name("stake_account_pubkey")
+                        .index(1)
+                        .value_name("STAKE ACCOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey_or_keypair)
+                        .help("Stake account whose lockup to replace")
+                )
+                .arg(
+                    Arg::with_name("lockup")
+                        .long("lockup")
+                        .value_name("SLOT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The slot height at which this account will be available for withdrawal")
+                )
+                .arg(
+                    Arg::with_name("custodian")
+                        .long("custodian")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .validator(is_pubkey_or_keypair)
+                        .help("Identity of the new custodian (can withdraw before lockup expires)")
+                )
+        )
         .subcommand(
             SubCommand::with_name("redeem-vote-credits")
                 .about("Redeem credits in the stake account")
@@ -334,6 +430,33 @@ pub fn parse_stake_deactivate_stake(matches: &ArgMatches<'_>) -> Result<CliComma
     })
 }
 
+pub fn parse_split_stake(matches: &ArgMatches<'_>) -> Result<CliCommandInfo, CliError> {
+    let stake_account_pubkey = pubkey_of(matches, "stake_account_pubkey").unwrap();
+    let split_stake_account = keypair_of(matches, "split_stake_account").unwrap();
+    let lamports = amount_of(matches, "amount", "unit").expect("Invalid amount");
+    let deactivate = matches.is_present("deactivate");
+
+    Ok(CliCommandInfo {
+        command: CliCommand::SplitStake {
+            stake_account_pubkey,
+            split_stake_account: split_stake_account.into(),
+            lamports,
+            deactivate,
+        },
+        require_keypair: true,
+    })
+}
+
+pub fn parse_merge_stake(matches: &ArgMatches<'_>) -> Result<CliCommandInfo, CliError> {
+    let stake_account_pubkey = pubkey_of(matches, "stake_account_pubkey").unwrap();
+    let source_stake_account_pubkey = pubkey_of(matches, "source_stake_account_pubkey").unwrap();
+
+    Ok(CliCommandInfo {
+        command: CliCommand::MergeStake(stake_account_pubkey, source_stake_account_pubkey),
+        require_keypair: true,
+    })
+}
+
 pub fn parse_stake_withdraw_stake(matches: &ArgMatches<'_>) -> Result<CliCommandInfo, CliError> {
     let stake_account_pubkey = pubkey_of(matches, "stake_account_pubkey").unwrap();
     let destination_account_pubkey = pubkey_of(matches, "destination_account_pubkey").unwrap();
@@ -349,6 +472,17 @@ pub fn parse_stake_withdraw_stake(matches: &ArgMatches<'_>) -> Result<CliCommand
     })
 }
 
+pub fn parse_stake_set_lockup(matches: &ArgMatches<'_>) -> Result<CliCommandInfo, CliError> {
+    let stake_account_pubkey = pubkey_of(matches, "stake_account_pubkey").unwrap();
+    let slot = value_of(&matches, "lockup").unwrap();
+    let custodian = pubkey_of(matches, "custodian").unwrap_or_default();
+
+    Ok(CliCommandInfo {
+        command: CliCommand::StakeSetLockup(stake_account_pubkey, Lockup { custodian, slot }),
+        require_keypair: true,
+    })
+}
+
 pub fn parse_show_stake_account(matches: &ArgMatches<'_>) -> Result<CliCommandInfo, CliError> {
     let stake_account_pubkey = pubkey_of(matches, "stake_account_pubkey").unwrap();
     let use_lamports_unit = matches.is_present("lamports");
@@ -480,6 +614,93 @@ pub fn process_deactivate_stake_account(
     log_instruction_custom_error::<StakeError>(result)
 }
 
+pub fn process_split_stake(
+    rpc_client: &RpcClient,
+    config: &CliConfig,
+    stake_account_pubkey: &Pubkey,
+    split_stake_account: &Keypair,
+    lamports: u64,
+    deactivate: bool,
+) -> ProcessResult {
+    let split_stake_account_pubkey = split_stake_account.pubkey();
+    check_unique_pubkeys(
+        (stake_account_pubkey, "stake_account_pubkey".to_string()),
+        (
+            &split_stake_account_pubkey,
+            "split_stake_account_pubkey".to_string(),
+        ),
+    )?;
+
+    if rpc_client.get_account(&split_stake_account_pubkey).is_ok() {
+        return Err(CliError::BadParameter(format!(
+            "Unable to split stake. Stake account already exists: {}",
+            split_stake_account_pubkey
+        ))
+        .into());
+    }
+
+    let (recent_blockhash, fee_calculator) = rpc_client.get_recent_blockhash()?;
+    let ixs = if deactivate {
+        stake_instruction::deactivate_stake_partial(
+            &config.keypair.pubkey(),
+            stake_account_pubkey,
+            &config.keypair.pubkey(),
+            lamports,
+            &split_stake_account_pubkey,
+        )
+    } else {
+        stake_instruction::split_from(
+            &config.keypair.pubkey(),
+            stake_account_pubkey,
+            &config.keypair.pubkey(),
+            lamports,
+            &split_stake_account_pubkey,
+        )
+    };
+
+    let mut tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&config.keypair.pubkey()),
+        &[&config.keypair, split_stake_account],
+        recent_blockhash,
+    );
+    check_account_for_fee(rpc_client, config, &fee_calculator, &tx.message)?;
+    let result =
+        rpc_client.send_and_confirm_transaction(&mut tx, &[&config.keypair, split_stake_account]);
+    log_instruction_custom_error::<StakeError>(result)
+}
+
+pub fn process_merge_stake(
+    rpc_client: &RpcClient,
+    config: &CliConfig,
+    stake_account_pubkey: &Pubkey,
+    source_stake_account_pubkey: &Pubkey,
+) -> ProcessResult {
+    check_unique_pubkeys(
+        (stake_account_pubkey, "stake_account_pubkey".to_string()),
+        (
+            source_stake_account_pubkey,
+            "source_stake_account_pubkey".to_string(),
+        ),
+    )?;
+
+    let (recent_blockhash, fee_calculator) = rpc_client.get_recent_blockhash()?;
+    let ixs = vec![stake_instruction::merge(
+        stake_account_pubkey,
+        source_stake_account_pubkey,
+        &config.keypair.pubkey(),
+    )];
+    let mut tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&config.keypair.pubkey()),
+        &[&config.keypair],
+        recent_blockhash,
+    );
+    check_account_for_fee(rpc_client, config, &fee_calculator, &tx.message)?;
+    let result = rpc_client.send_and_confirm_transaction(&mut tx, &[&config.keypair]);
+    log_instruction_custom_error::<StakeError>(result)
+}
+
 pub fn process_withdraw_stake(
     rpc_client: &RpcClient,
     config: &CliConfig,
@@ -507,6 +728,29 @@ pub fn process_withdraw_stake(
     log_instruction_custom_error::<StakeError>(result)
 }
 
+pub fn process_stake_set_lockup(
+    rpc_client: &RpcClient,
+    config: &CliConfig,
+    stake_account_pubkey: &Pubkey,
+    lockup: &Lockup,
+) -> ProcessResult {
+    let (recent_blockhash, fee_calculator) = rpc_client.get_recent_blockhash()?;
+    let ixs = vec![stake_instruction::set_lockup(
+        stake_account_pubkey,
+        lockup,
+        &config.keypair.pubkey(), // custodian while locked up, withdrawer after
+    )];
+    let mut tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&config.keypair.pubkey()),
+        &[&config.keypair],
+        recent_blockhash,
+    );
+    check_account_for_fee(rpc_client, config, &fee_calculator, &tx.message)?;
+    let result = rpc_client.send_and_confirm_transaction(&mut tx, &[&config.keypair]);
+    log_instruction_custom_error::<StakeError>(result)
+}
+
 pub fn process_redeem_vote_credits(
     rpc_client: &RpcClient,
     config: &CliConfig,
@@ -542,13 +786,24 @@ pub fn process_show_stake_account(
         )
         .into());
     }
+    let epoch_info = rpc_client.get_epoch_info()?;
     fn show_authorized(authorized: &Authorized) {
         println!("authorized staker: {}", authorized.staker);
-        println!("authorized withdrawer: {}", authorized.staker);
+        println!("authorized withdrawer: {}", authorized.withdrawer);
     }
-    fn show_lockup(lockup: &Lockup) {
+    fn show_lockup(lockup: &Lockup, epoch_info: &RpcEpochInfo) {
         println!("lockup slot: {}", lockup.slot);
         println!("lockup custodian: {}", lockup.custodian);
+        if lockup.slot > epoch_info.absolute_slot {
+            let slots_remaining = lockup.slot - epoch_info.absolute_slot;
+            println!(
+                "lockup expires in: {} slots (~{} epochs)",
+                slots_remaining,
+                slots_remaining / epoch_info.slots_in_epoch
+            );
+        } else {
+            println!("lockup expired");
+        }
     }
     match stake_account.state() {
         Ok(StakeState::Stake(
@@ -557,6 +812,14 @@ pub fn process_show_stake_account(
             },
             stake,
         )) => {
+            let stake_history_account = rpc_client.get_account(&stake_history::id())?;
+            let stake_history =
+                StakeHistory::from_account(&stake_history_account).ok_or_else(|| {
+                    CliError::RpcRequestError("Failed to deserialize stake history".to_string())
+                })?;
+            let (effective, activating, deactivating) =
+                stake.stake_activating_and_deactivating(epoch_info.epoch, Some(&stake_history));
+
             println!(
                 "total stake: {}",
                 build_balance_message(stake_account.lamports, use_lamports_unit, true)
@@ -570,9 +833,31 @@ pub fn process_show_stake_account(
                 println!("delegated voter pubkey: {}", stake.voter_pubkey);
             }
             println!(
-                "stake activates starting from epoch: {}",
-                stake.activation_epoch
+                "activation state: {}",
+                activation_state(&stake, epoch_info.epoch, effective, activating)
+            );
+            println!(
+                "active stake: {}",
+                build_balance_message(effective, use_lamports_unit, true)
             );
+            if activating > 0 {
+                println!(
+                    "activating stake: {}",
+                    build_balance_message(activating, use_lamports_unit, true)
+                );
+            }
+            if deactivating > 0 {
+                println!(
+                    "deactivating stake: {}",
+                    build_balance_message(deactivating, use_lamports_unit, true)
+                );
+            }
+            if stake.activation_epoch < std::u64::MAX {
+                println!(
+                    "stake activates starting from epoch: {}",
+                    stake.activation_epoch
+                );
+            }
             if stake.deactivation_epoch < std::u64::MAX {
                 println!(
                     "stake deactivates starting from epoch: {}",
@@ -580,7 +865,7 @@ pub fn process_show_stake_account(
                 );
             }
             show_authorized(&authorized);
-            show_lockup(&lockup);
+            show_lockup(&lockup, &epoch_info);
             Ok("".to_string())
         }
         Ok(StakeState::RewardsPool) => Ok("Stake account is a rewards pool".to_string()),
@@ -590,7 +875,7 @@ pub fn process_show_stake_account(
         })) => {
             println!("Stake account is undelegated");
             show_authorized(&authorized);
-            show_lockup(&lockup);
+            show_lockup(&lockup, &epoch_info);
             Ok("".to_string())
         }
         Err(err) => Err(CliError::RpcRequestError(format!(
@@ -601,6 +886,23 @@ pub fn process_show_stake_account(
     }
 }
 
+// where a delegated stake is in its warmup/cooldown cycle at the given epoch
+fn activation_state(stake: &Stake, epoch: Epoch, effective: u64, activating: u64) -> &'static str {
+    if epoch >= stake.deactivation_epoch {
+        if effective > 0 {
+            "deactivating"
+        } else {
+            "inactive"
+        }
+    } else if activating > 0 {
+        "activating"
+    } else if effective > 0 {
+        "active"
+    } else {
+        "inactive"
+    }
+}
+
 pub fn process_show_stake_history(
     rpc_client: &RpcClient,
     _config: &CliConfig,
@@ -709,7 +1011,7 @@ pub fn process_delegate_stake(
 mod tests {
     use super::*;
     use crate::cli::{app, parse_command};
-    use solana_sdk::signature::write_keypair;
+    use solana_sdk::signature::{read_keypair_file, write_keypair};
     use tempfile::NamedTempFile;
 
     fn make_tmp_file() -> (String, NamedTempFile) {
@@ -892,5 +1194,108 @@ mod tests {
                 require_keypair: true
             }
         );
+
+        // Test SplitStake Subcommand
+        let (split_keypair_file, mut tmp_file) = make_tmp_file();
+        let split_stake_account_keypair = Keypair::new();
+        write_keypair(&split_stake_account_keypair, tmp_file.as_file_mut()).unwrap();
+        let test_split_stake = test_commands.clone().get_matches_from(vec![
+            "test",
+            "split-stake",
+            &stake_pubkey_string,
+            &split_keypair_file,
+            "42",
+            "lamports",
+        ]);
+        assert_eq!(
+            parse_command(&test_split_stake).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::SplitStake {
+                    stake_account_pubkey: stake_pubkey,
+                    split_stake_account: split_stake_account_keypair.into(),
+                    lamports: 42,
+                    deactivate: false,
+                },
+                require_keypair: true
+            }
+        );
+        let split_stake_account_keypair = read_keypair_file(&split_keypair_file).unwrap();
+        let test_split_stake = test_commands.clone().get_matches_from(vec![
+            "test",
+            "split-stake",
+            "--deactivate",
+            &stake_pubkey_string,
+            &split_keypair_file,
+            "42",
+            "lamports",
+        ]);
+        assert_eq!(
+            parse_command(&test_split_stake).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::SplitStake {
+                    stake_account_pubkey: stake_pubkey,
+                    split_stake_account: split_stake_account_keypair.into(),
+                    lamports: 42,
+                    deactivate: true,
+                },
+                require_keypair: true
+            }
+        );
+
+        // Test MergeStake Subcommand
+        let test_merge_stake = test_commands.clone().get_matches_from(vec![
+            "test",
+            "merge-stake",
+            &stake_pubkey_string,
+            &stake_account_string,
+        ]);
+        assert_eq!(
+            parse_command(&test_merge_stake).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::MergeStake(stake_pubkey, stake_account_pubkey),
+                require_keypair: true
+            }
+        );
+
+        // Test StakeSetLockup Subcommand
+        let test_set_lockup = test_commands.clone().get_matches_from(vec![
+            "test",
+            "stake-set-lockup",
+            &stake_pubkey_string,
+            "--lockup",
+            "43",
+            "--custodian",
+            &custodian_string,
+        ]);
+        assert_eq!(
+            parse_command(&test_set_lockup).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::StakeSetLockup(
+                    stake_pubkey,
+                    Lockup {
+                        slot: 43,
+                        custodian,
+                    }
+                ),
+                require_keypair: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_activation_state() {
+        let stake = Stake {
+            activation_epoch: 1,
+            ..Stake::default()
+        };
+        assert_eq!(activation_state(&stake, 1, 0, 42), "activating");
+        assert_eq!(activation_state(&stake, 2, 42, 0), "active");
+
+        let stake = Stake {
+            deactivation_epoch: 3,
+            ..stake
+        };
+        assert_eq!(activation_state(&stake, 3, 42, 0), "deactivating");
+        assert_eq!(activation_state(&stake, 4, 0, 0), "inactive");
     }
 }
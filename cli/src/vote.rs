@@ -12,7 +12,7 @@ use solana_sdk::{
 };
 use solana_vote_api::{
     vote_instruction::{self, VoteError},
-    vote_state::{VoteAuthorize, VoteInit, VoteState},
+    vote_state::{AuthorizedRotations, VoteAuthorize, VoteInit, VoteState},
 };
 
 pub trait VoteSubCommands {
@@ -110,6 +110,50 @@ impl VoteSubCommands for App<'_, '_> {
                         .help("New withdrawer to authorize"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("vote-rotate-voter")
+                .about("Rotate the vote signing keypair of the given vote account at the start of the next epoch")
+                .arg(
+                    Arg::with_name("vote_account_pubkey")
+                        .index(1)
+                        .value_name("VOTE ACCOUNT PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey_or_keypair)
+                        .help("Vote account in which to rotate the authorized voter"),
+                )
+                .arg(
+                    Arg::with_name("new_authorized_pubkey")
+                        .index(2)
+                        .value_name("NEW VOTER PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey_or_keypair)
+                        .help("Vote signer to rotate to"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("vote-rotate-withdrawer")
+                .about("Rotate the withdraw signing keypair of the given vote account at the start of the next epoch")
+                .arg(
+                    Arg::with_name("vote_account_pubkey")
+                        .index(1)
+                        .value_name("VOTE ACCOUNT PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey_or_keypair)
+                        .help("Vote account in which to rotate the authorized withdrawer"),
+                )
+                .arg(
+                    Arg::with_name("new_authorized_pubkey")
+                        .index(2)
+                        .value_name("NEW WITHDRAWER PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey_or_keypair)
+                        .help("Withdrawer to rotate to"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("show-vote-account")
                 .about("Show the contents of a vote account")
@@ -193,6 +237,23 @@ pub fn parse_vote_authorize(
     })
 }
 
+pub fn parse_vote_rotate_authorized(
+    matches: &ArgMatches<'_>,
+    vote_authorize: VoteAuthorize,
+) -> Result<CliCommandInfo, CliError> {
+    let vote_account_pubkey = pubkey_of(matches, "vote_account_pubkey").unwrap();
+    let new_authorized_pubkey = pubkey_of(matches, "new_authorized_pubkey").unwrap();
+
+    Ok(CliCommandInfo {
+        command: CliCommand::VoteRotateAuthorized(
+            vote_account_pubkey,
+            new_authorized_pubkey,
+            vote_authorize,
+        ),
+        require_keypair: true,
+    })
+}
+
 pub fn parse_vote_get_account_command(
     matches: &ArgMatches<'_>,
 ) -> Result<CliCommandInfo, CliError> {
@@ -244,7 +305,7 @@ pub fn process_create_vote_account(
         (&vote_account_pubkey, "vote_account_pubkey".to_string()),
     )?;
     let required_balance =
-        rpc_client.get_minimum_balance_for_rent_exemption(VoteState::size_of())?;
+        rpc_client.get_minimum_balance_for_rent_exemption(VoteState::account_size())?;
     let lamports = if required_balance > 0 {
         required_balance
     } else {
@@ -285,7 +346,37 @@ pub fn process_vote_authorize(
         (new_authorized_pubkey, "new_authorized_pubkey".to_string()),
     )?;
     let (recent_blockhash, fee_calculator) = rpc_client.get_recent_blockhash()?;
-    let ixs = vec![vote_instruction::authorize(
+    let ixs = vec![vote_instruction::authorize_with_clock(
+        vote_account_pubkey,      // vote account to update
+        &config.keypair.pubkey(), // current authorized voter
+        new_authorized_pubkey,    // new vote signer/withdrawer
+        vote_authorize,           // vote or withdraw
+    )];
+
+    let mut tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&config.keypair.pubkey()),
+        &[&config.keypair],
+        recent_blockhash,
+    );
+    check_account_for_fee(rpc_client, config, &fee_calculator, &tx.message)?;
+    let result = rpc_client.send_and_confirm_transaction(&mut tx, &[&config.keypair]);
+    log_instruction_custom_error::<VoteError>(result)
+}
+
+pub fn process_vote_rotate_authorized(
+    rpc_client: &RpcClient,
+    config: &CliConfig,
+    vote_account_pubkey: &Pubkey,
+    new_authorized_pubkey: &Pubkey,
+    vote_authorize: VoteAuthorize,
+) -> ProcessResult {
+    check_unique_pubkeys(
+        (vote_account_pubkey, "vote_account_pubkey".to_string()),
+        (new_authorized_pubkey, "new_authorized_pubkey".to_string()),
+    )?;
+    let (recent_blockhash, fee_calculator) = rpc_client.get_recent_blockhash()?;
+    let ixs = vec![vote_instruction::rotate_authorized(
         vote_account_pubkey,      // vote account to update
         &config.keypair.pubkey(), // current authorized voter
         new_authorized_pubkey,    // new vote signer/withdrawer
@@ -331,6 +422,8 @@ pub fn process_show_vote_account(
     use_lamports_unit: bool,
 ) -> ProcessResult {
     let (vote_account, vote_state) = get_vote_account(rpc_client, vote_account_pubkey)?;
+    // accounts created before rotations existed have none
+    let rotations = AuthorizedRotations::from(&vote_account).unwrap_or_default();
 
     let epoch_schedule = rpc_client.get_epoch_schedule()?;
 
@@ -344,6 +437,18 @@ pub fn process_show_vote_account(
         "authorized withdrawer: {}",
         vote_state.authorized_withdrawer
     );
+    if let Some((pubkey, epoch)) = rotations.pending_authorized(VoteAuthorize::Voter) {
+        println!(
+            "pending authorized voter: {} (from epoch {})",
+            pubkey, epoch
+        );
+    }
+    if let Some((pubkey, epoch)) = rotations.pending_authorized(VoteAuthorize::Withdrawer) {
+        println!(
+            "pending authorized withdrawer: {} (from epoch {})",
+            pubkey, epoch
+        );
+    }
     println!("credits: {}", vote_state.credits());
     println!(
         "commission: {}%",
@@ -375,6 +480,21 @@ pub fn process_show_vote_account(
             );
         }
     }
+    if rotations.authorized_history().next().is_some() {
+        println!("authorized signer history:");
+        for rotation in rotations.authorized_history() {
+            println!(
+                "- epoch: {}\n  {}: {} -> {}",
+                rotation.epoch,
+                match rotation.vote_authorize {
+                    VoteAuthorize::Voter => "voter",
+                    VoteAuthorize::Withdrawer => "withdrawer",
+                },
+                rotation.prior,
+                rotation.authorized,
+            );
+        }
+    }
     Ok("".to_string())
 }
 
@@ -461,6 +581,24 @@ mod tests {
             }
         );
 
+        let test_rotate_withdrawer = test_commands.clone().get_matches_from(vec![
+            "test",
+            "vote-rotate-withdrawer",
+            &pubkey_string,
+            &pubkey_string,
+        ]);
+        assert_eq!(
+            parse_command(&test_rotate_withdrawer).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::VoteRotateAuthorized(
+                    pubkey,
+                    pubkey,
+                    VoteAuthorize::Withdrawer
+                ),
+                require_keypair: true
+            }
+        );
+
         let (keypair_file, mut tmp_file) = make_tmp_file();
         let keypair = Keypair::new();
         write_keypair(&keypair, tmp_file.as_file_mut()).unwrap();
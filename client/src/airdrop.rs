@@ -0,0 +1,200 @@
+//! The `airdrop` module requests lamports from a drone and confirms that they landed.
+
+use crate::{client_error::ClientError, rpc_client::RpcClient};
+use log::*;
+use solana_drone::drone::request_airdrop_transaction;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, hash::Hash, pubkey::Pubkey, signature::Signature,
+    transaction::Transaction,
+};
+use std::{io, net::SocketAddr, thread::sleep, time::Duration};
+
+/// Number of passes made over the drone addresses before giving up
+pub const AIRDROP_REQUEST_ATTEMPTS: usize = 5;
+/// Number of times the airdrop transaction is sent before giving up on confirmation
+pub const AIRDROP_SEND_ATTEMPTS: usize = 3;
+
+/// Requests an airdrop of `lamports` to `to_pubkey`, trying each of `drone_addrs` in turn,
+/// then sends the drone's transaction and waits for it to reach `commitment_config`
+pub fn request_and_confirm_airdrop(
+    rpc_client: &RpcClient,
+    drone_addrs: &[SocketAddr],
+    to_pubkey: &Pubkey,
+    lamports: u64,
+    commitment_config: CommitmentConfig,
+) -> Result<Signature, ClientError> {
+    request_and_confirm_airdrop_with(
+        rpc_client,
+        drone_addrs,
+        to_pubkey,
+        lamports,
+        commitment_config,
+        request_airdrop_transaction,
+    )
+}
+
+/// Same as `request_and_confirm_airdrop`, with the drone request supplied by the caller
+pub fn request_and_confirm_airdrop_with<F>(
+    rpc_client: &RpcClient,
+    drone_addrs: &[SocketAddr],
+    to_pubkey: &Pubkey,
+    lamports: u64,
+    commitment_config: CommitmentConfig,
+    request_transaction: F,
+) -> Result<Signature, ClientError>
+where
+    F: Fn(&SocketAddr, &Pubkey, u64, Hash) -> io::Result<Transaction>,
+{
+    if drone_addrs.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no drone address given").into());
+    }
+    let (blockhash, _fee_calculator) = rpc_client
+        .get_recent_blockhash_with_commitment(commitment_config.clone())?
+        .value;
+    let transaction = request_from_drones(
+        drone_addrs,
+        to_pubkey,
+        lamports,
+        blockhash,
+        request_transaction,
+    )?;
+    let signature = transaction.signatures[0];
+
+    for _ in 0..AIRDROP_SEND_ATTEMPTS {
+        rpc_client.send_transaction(&transaction)?;
+        if rpc_client
+            .poll_for_signature_with_commitment(&signature, commitment_config.clone())
+            .is_ok()
+        {
+            return match rpc_client
+                .get_signature_status_with_commitment(&signature.to_string(), commitment_config)?
+            {
+                Some(Err(err)) => Err(err.into()),
+                _ => Ok(signature),
+            };
+        }
+        warn!("airdrop {} not confirmed, resending", signature);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!("airdrop {} was not confirmed", signature),
+    )
+    .into())
+}
+
+fn request_from_drones<F>(
+    drone_addrs: &[SocketAddr],
+    to_pubkey: &Pubkey,
+    lamports: u64,
+    blockhash: Hash,
+    request_transaction: F,
+) -> io::Result<Transaction>
+where
+    F: Fn(&SocketAddr, &Pubkey, u64, Hash) -> io::Result<Transaction>,
+{
+    let mut last_err = None;
+    for attempt in 0..AIRDROP_REQUEST_ATTEMPTS {
+        if attempt > 0 {
+            sleep(Duration::from_secs(1));
+        }
+        for drone_addr in drone_addrs {
+            match request_transaction(drone_addr, to_pubkey, lamports, blockhash) {
+                Ok(transaction) => return Ok(transaction),
+                Err(err) => {
+                    info!("airdrop request to {} failed: {:?}", drone_addr, err);
+                    last_err = Some(err);
+                }
+            }
+        }
+    }
+    Err(last_err.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        signature::{Keypair, KeypairUtil},
+        system_transaction,
+    };
+    use std::cell::RefCell;
+
+    fn mock_drone(
+        fail_addr: SocketAddr,
+        requests: &RefCell<Vec<SocketAddr>>,
+    ) -> impl Fn(&SocketAddr, &Pubkey, u64, Hash) -> io::Result<Transaction> + '_ {
+        move |drone_addr, to_pubkey, lamports, blockhash| {
+            requests.borrow_mut().push(*drone_addr);
+            if *drone_addr == fail_addr {
+                Err(io::Error::new(io::ErrorKind::Other, "Airdrop failed"))
+            } else {
+                Ok(system_transaction::transfer(
+                    &Keypair::new(),
+                    to_pubkey,
+                    lamports,
+                    blockhash,
+                ))
+            }
+        }
+    }
+
+    #[test]
+    fn test_request_and_confirm_airdrop_falls_back() {
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        let bad_drone = "127.0.0.1:1".parse().unwrap();
+        let good_drone = "127.0.0.1:2".parse().unwrap();
+        let requests = RefCell::new(vec![]);
+        let signature = request_and_confirm_airdrop_with(
+            &rpc_client,
+            &[bad_drone, good_drone],
+            &Pubkey::new_rand(),
+            50,
+            CommitmentConfig::recent(),
+            mock_drone(bad_drone, &requests),
+        );
+        assert!(signature.is_ok());
+        assert_eq!(*requests.borrow(), vec![bad_drone, good_drone]);
+    }
+
+    #[test]
+    fn test_request_and_confirm_airdrop_errors() {
+        let drone = "127.0.0.1:1".parse().unwrap();
+        let requests = RefCell::new(vec![]);
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        assert!(request_and_confirm_airdrop_with(
+            &rpc_client,
+            &[],
+            &Pubkey::new_rand(),
+            50,
+            CommitmentConfig::recent(),
+            mock_drone(drone, &requests),
+        )
+        .is_err());
+
+        let rpc_client = RpcClient::new_mock("fails".to_string());
+        assert!(request_and_confirm_airdrop_with(
+            &rpc_client,
+            &[drone],
+            &Pubkey::new_rand(),
+            50,
+            CommitmentConfig::recent(),
+            mock_drone(drone, &requests),
+        )
+        .is_err());
+        assert!(requests.borrow().is_empty());
+
+        let rpc_client = RpcClient::new_mock("account_in_use".to_string());
+        let result = request_and_confirm_airdrop_with(
+            &rpc_client,
+            &[drone],
+            &Pubkey::new_rand(),
+            50,
+            CommitmentConfig::recent(),
+            mock_drone("127.0.0.1:2".parse().unwrap(), &requests),
+        );
+        assert!(match result {
+            Err(ClientError::TransactionError(_)) => true,
+            _ => false,
+        });
+    }
+}
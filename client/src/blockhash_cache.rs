@@ -0,0 +1,187 @@
+//! The `blockhash_cache` module keeps recent blockhashes along with the last slot in which each
+//! of them is valid, so clients can sign many transactions with one blockhash and only re-sign
+//! once it has actually expired.
+
+use crate::rpc_client::RpcClient;
+use log::*;
+use solana_sdk::{
+    clock::{Slot, DEFAULT_TICKS_PER_SECOND, DEFAULT_TICKS_PER_SLOT},
+    fee_calculator::FeeCalculator,
+    hash::Hash,
+};
+use std::{
+    collections::HashMap,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread::{sleep, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How long the cluster slot observed by the cache is trusted before it is fetched again
+const SLOT_REFRESH_INTERVAL: Duration =
+    Duration::from_millis(1000 * DEFAULT_TICKS_PER_SLOT / DEFAULT_TICKS_PER_SECOND);
+
+#[derive(Default)]
+struct CacheState {
+    /// fee calculator and last valid slot of every blockhash handed out that hasn't expired
+    blockhashes: HashMap<Hash, (FeeCalculator, Slot)>,
+    latest: Option<Hash>,
+    slot: Slot,
+    slot_updated: Option<Instant>,
+}
+
+impl CacheState {
+    fn is_valid(&self, blockhash: &Hash) -> bool {
+        self.blockhashes
+            .get(blockhash)
+            .map(|(_, last_valid_slot)| self.slot <= *last_valid_slot)
+            .unwrap_or(false)
+    }
+
+    fn update_slot(&mut self, slot: Slot) {
+        self.slot = self.slot.max(slot);
+        self.slot_updated = Some(Instant::now());
+        let current_slot = self.slot;
+        self.blockhashes
+            .retain(|_, (_, last_valid_slot)| current_slot <= *last_valid_slot);
+    }
+}
+
+pub struct BlockhashCache {
+    rpc_client: Arc<RpcClient>,
+    state: RwLock<CacheState>,
+}
+
+impl BlockhashCache {
+    pub fn new(rpc_client: Arc<RpcClient>) -> Self {
+        Self {
+            rpc_client,
+            state: RwLock::new(CacheState::default()),
+        }
+    }
+
+    /// Spawn a thread that refreshes `cache` every `interval` until `exit` is set, so callers
+    /// rarely have to wait on RPC for a blockhash
+    pub fn start_refresh(
+        cache: &Arc<Self>,
+        interval: Duration,
+        exit: &Arc<AtomicBool>,
+    ) -> JoinHandle<()> {
+        let cache = cache.clone();
+        let exit = exit.clone();
+        Builder::new()
+            .name("solana-blockhash-cache".to_string())
+            .spawn(move || {
+                while !exit.load(Ordering::Relaxed) {
+                    if let Err(err) = cache.refresh() {
+                        warn!("blockhash cache refresh failed: {:?}", err);
+                    }
+                    sleep(interval);
+                }
+            })
+            .unwrap()
+    }
+
+    /// Fetch the cluster's current blockhash and slot
+    pub fn refresh(&self) -> io::Result<()> {
+        let (blockhash, fee_calculator, last_valid_slot) = self.rpc_client.get_fees()?;
+        let slot = self.rpc_client.get_slot()?;
+        let mut state = self.state.write().unwrap();
+        state
+            .blockhashes
+            .insert(blockhash, (fee_calculator, last_valid_slot));
+        state.latest = Some(blockhash);
+        state.update_slot(slot);
+        Ok(())
+    }
+
+    /// Return the most recent blockhash, fetching a new one only if the cached one expired
+    pub fn get_blockhash(&self) -> io::Result<(Hash, FeeCalculator)> {
+        self.refresh_slot_if_stale()?;
+        if let Some(blockhash) = self.cached_blockhash() {
+            return Ok(blockhash);
+        }
+        self.refresh()?;
+        self.cached_blockhash().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "cluster returned an expired blockhash",
+            )
+        })
+    }
+
+    /// Whether transactions signed with `blockhash` can still be processed. Blockhashes that
+    /// weren't handed out by this cache are reported as expired.
+    pub fn is_blockhash_valid(&self, blockhash: &Hash) -> io::Result<bool> {
+        self.refresh_slot_if_stale()?;
+        Ok(self.state.read().unwrap().is_valid(blockhash))
+    }
+
+    fn cached_blockhash(&self) -> Option<(Hash, FeeCalculator)> {
+        let state = self.state.read().unwrap();
+        state
+            .latest
+            .filter(|blockhash| state.is_valid(blockhash))
+            .map(|blockhash| (blockhash, state.blockhashes[&blockhash].0.clone()))
+    }
+
+    fn refresh_slot_if_stale(&self) -> io::Result<()> {
+        let stale = self
+            .state
+            .read()
+            .unwrap()
+            .slot_updated
+            .map(|updated| updated.elapsed() >= SLOT_REFRESH_INTERVAL)
+            .unwrap_or(true);
+        if stale {
+            let slot = self.rpc_client.get_slot()?;
+            self.state.write().unwrap().update_slot(slot);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_rpc_client_request::PUBKEY;
+
+    #[test]
+    fn test_get_blockhash() {
+        let cache = BlockhashCache::new(Arc::new(RpcClient::new_mock("succeeds".to_string())));
+        let expected_blockhash: Hash = PUBKEY.parse().unwrap();
+        assert!(!cache.is_blockhash_valid(&expected_blockhash).unwrap());
+
+        let (blockhash, _fee_calculator) = cache.get_blockhash().unwrap();
+        assert_eq!(blockhash, expected_blockhash);
+        assert!(cache.is_blockhash_valid(&blockhash).unwrap());
+        assert!(!cache.is_blockhash_valid(&Hash::default()).unwrap());
+
+        let cache = BlockhashCache::new(Arc::new(RpcClient::new_mock("fails".to_string())));
+        assert!(cache.get_blockhash().is_err());
+    }
+
+    #[test]
+    fn test_cache_state_expiry() {
+        let mut state = CacheState::default();
+        let blockhash = Hash::new(&[1; 32]);
+        state
+            .blockhashes
+            .insert(blockhash, (FeeCalculator::default(), 10));
+        state.latest = Some(blockhash);
+
+        state.update_slot(10);
+        assert!(state.is_valid(&blockhash));
+
+        // the observed slot never moves backwards
+        state.update_slot(5);
+        assert_eq!(state.slot, 10);
+
+        state.update_slot(11);
+        assert!(!state.is_valid(&blockhash));
+        assert!(state.blockhashes.is_empty());
+    }
+}
@@ -9,6 +9,8 @@ pub enum ClientError {
     RpcError(rpc_request::RpcError),
     SerdeJson(serde_json::error::Error),
     TransactionError(TransactionError),
+    /// A wait didn't complete before its deadline, with the last state seen
+    Timeout(String),
 }
 
 impl fmt::Display for ClientError {
@@ -0,0 +1,277 @@
+//! The `leader_tpu_cache` module follows the cluster's leader schedule and the TPU addresses
+//! the cluster nodes advertise in gossip, both fetched over RPC, so clients can send
+//! transactions straight to the next few leaders instead of relying on one node to forward
+//! them.
+
+use crate::rpc_client::RpcClient;
+use bincode::serialize;
+use log::*;
+use solana_sdk::{
+    clock::{Slot, DEFAULT_TICKS_PER_SECOND, DEFAULT_TICKS_PER_SLOT},
+    pubkey::Pubkey,
+    transaction::Transaction,
+};
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread::{sleep, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Expected time between two slots, used to estimate the current slot between refreshes
+const SLOT_DURATION: Duration =
+    Duration::from_millis(1000 * DEFAULT_TICKS_PER_SLOT / DEFAULT_TICKS_PER_SECOND);
+
+/// How long the cluster slot observed by the cache is trusted before it is fetched again
+const SLOT_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct LeaderTpuState {
+    /// Slot of the first leader of `leaders`, the first slot of the epoch
+    first_slot: Slot,
+    /// The leader of every slot of the epoch
+    leaders: Vec<Pubkey>,
+    tpu_addresses: HashMap<Pubkey, SocketAddr>,
+    slot: Slot,
+    slot_updated: Option<Instant>,
+}
+
+impl LeaderTpuState {
+    /// The slot the cluster is likely at, counting the slots that passed since it was observed
+    fn estimated_slot(&self) -> Slot {
+        let elapsed = self
+            .slot_updated
+            .map(|updated| updated.elapsed().as_millis() / SLOT_DURATION.as_millis())
+            .unwrap_or(0);
+        self.slot + elapsed as Slot
+    }
+
+    fn covers(&self, slot: Slot) -> bool {
+        slot >= self.first_slot && slot < self.first_slot + self.leaders.len() as Slot
+    }
+
+    /// The first `fanout` distinct leaders from `slot` on, in schedule order
+    fn upcoming_leaders(&self, slot: Slot, fanout: usize) -> Vec<Pubkey> {
+        let mut upcoming: Vec<Pubkey> = vec![];
+        if !self.covers(slot) {
+            return upcoming;
+        }
+        for leader in &self.leaders[(slot - self.first_slot) as usize..] {
+            if upcoming.len() == fanout {
+                break;
+            }
+            if !upcoming.contains(leader) {
+                upcoming.push(*leader);
+            }
+        }
+        upcoming
+    }
+
+    fn leader_tpu_addresses(&self, slot: Slot, fanout: usize) -> Vec<SocketAddr> {
+        self.upcoming_leaders(slot, fanout)
+            .iter()
+            .filter_map(|leader| self.tpu_addresses.get(leader).cloned())
+            .collect()
+    }
+}
+
+pub struct LeaderTpuCache {
+    rpc_client: Arc<RpcClient>,
+    /// How many upcoming leaders transactions are sent to
+    fanout: usize,
+    socket: UdpSocket,
+    state: RwLock<LeaderTpuState>,
+}
+
+impl LeaderTpuCache {
+    pub fn new(rpc_client: Arc<RpcClient>, fanout: usize) -> io::Result<Self> {
+        Ok(Self {
+            rpc_client,
+            fanout,
+            socket: UdpSocket::bind("0.0.0.0:0")?,
+            state: RwLock::new(LeaderTpuState::default()),
+        })
+    }
+
+    /// Spawn a thread that refreshes `cache` every `interval` until `exit` is set, so sending
+    /// rarely has to wait on RPC
+    pub fn start_refresh(
+        cache: &Arc<Self>,
+        interval: Duration,
+        exit: &Arc<AtomicBool>,
+    ) -> JoinHandle<()> {
+        let cache = cache.clone();
+        let exit = exit.clone();
+        Builder::new()
+            .name("solana-leader-tpu-cache".to_string())
+            .spawn(move || {
+                while !exit.load(Ordering::Relaxed) {
+                    if let Err(err) = cache.refresh() {
+                        warn!("leader TPU cache refresh failed: {:?}", err);
+                    }
+                    sleep(interval);
+                }
+            })
+            .unwrap()
+    }
+
+    /// Fetch the leader schedule of the current epoch and the TPU addresses of the cluster nodes
+    pub fn refresh(&self) -> io::Result<()> {
+        let epoch_info = self.rpc_client.get_epoch_info()?;
+        let leaders = self
+            .rpc_client
+            .get_leader_schedule()?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no leader schedule"))?
+            .iter()
+            .map(|leader| {
+                leader.parse().map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid leader {}: {:?}", leader, err),
+                    )
+                })
+            })
+            .collect::<io::Result<Vec<Pubkey>>>()?;
+        let tpu_addresses = self
+            .rpc_client
+            .get_cluster_nodes()?
+            .into_iter()
+            .filter_map(|node| Some((node.pubkey.parse().ok()?, node.tpu?)))
+            .collect();
+
+        let mut state = self.state.write().unwrap();
+        state.first_slot = epoch_info.absolute_slot - epoch_info.slot_index;
+        state.leaders = leaders;
+        state.tpu_addresses = tpu_addresses;
+        state.slot = epoch_info.absolute_slot;
+        state.slot_updated = Some(Instant::now());
+        Ok(())
+    }
+
+    /// The TPU addresses of the next `fanout` leaders that advertise one, fetching the leader
+    /// schedule again once the cluster moved past it
+    pub fn leader_tpu_addresses(&self) -> io::Result<Vec<SocketAddr>> {
+        self.refresh_slot_if_stale()?;
+        let needs_refresh = {
+            let state = self.state.read().unwrap();
+            !state.covers(state.estimated_slot())
+        };
+        if needs_refresh {
+            self.refresh()?;
+        }
+        let state = self.state.read().unwrap();
+        Ok(state.leader_tpu_addresses(state.estimated_slot(), self.fanout))
+    }
+
+    /// Send `transaction` to the TPU of each upcoming leader, returns how many leaders it was
+    /// sent to
+    pub fn send_transaction(&self, transaction: &Transaction) -> io::Result<usize> {
+        let tpu_addresses = self.leader_tpu_addresses()?;
+        if tpu_addresses.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "no TPU address known for the upcoming leaders",
+            ));
+        }
+        let buf = serialize(transaction)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+        let mut sent = 0;
+        for tpu_address in &tpu_addresses {
+            match self.socket.send_to(&buf, tpu_address) {
+                Ok(_) => sent += 1,
+                Err(err) => debug!("send to leader TPU {} failed: {:?}", tpu_address, err),
+            }
+        }
+        if sent == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "sending to every upcoming leader failed",
+            ));
+        }
+        Ok(sent)
+    }
+
+    fn refresh_slot_if_stale(&self) -> io::Result<()> {
+        let stale = self
+            .state
+            .read()
+            .unwrap()
+            .slot_updated
+            .map(|updated| updated.elapsed() >= SLOT_REFRESH_INTERVAL)
+            .unwrap_or(true);
+        if stale {
+            let slot = self.rpc_client.get_slot()?;
+            let mut state = self.state.write().unwrap();
+            state.slot = slot;
+            state.slot_updated = Some(Instant::now());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_rpc_client_request::{PUBKEY, TPU_ADDRESS};
+    use solana_sdk::{
+        hash::Hash,
+        signature::{Keypair, KeypairUtil},
+        system_transaction,
+    };
+
+    #[test]
+    fn test_upcoming_leaders() {
+        let leader0 = Pubkey::new_rand();
+        let leader1 = Pubkey::new_rand();
+        let leader2 = Pubkey::new_rand();
+        let tpu_address: SocketAddr = "127.0.0.1:8003".parse().unwrap();
+        let state = LeaderTpuState {
+            first_slot: 10,
+            leaders: vec![leader0, leader0, leader1, leader1, leader0, leader2],
+            tpu_addresses: vec![(leader1, tpu_address)].into_iter().collect(),
+            ..LeaderTpuState::default()
+        };
+
+        assert!(state.upcoming_leaders(9, 2).is_empty());
+        assert!(state.upcoming_leaders(16, 2).is_empty());
+        assert_eq!(state.upcoming_leaders(10, 2), vec![leader0, leader1]);
+        assert_eq!(
+            state.upcoming_leaders(11, 5),
+            vec![leader0, leader1, leader2]
+        );
+        assert_eq!(state.upcoming_leaders(14, 1), vec![leader0]);
+
+        // Leaders that don't advertise a TPU are skipped
+        assert_eq!(state.leader_tpu_addresses(10, 2), vec![tpu_address]);
+        assert!(state.leader_tpu_addresses(14, 2).is_empty());
+    }
+
+    #[test]
+    fn test_send_transaction() {
+        let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+        let cache = LeaderTpuCache::new(rpc_client, 2).unwrap();
+        assert_eq!(
+            cache.leader_tpu_addresses().unwrap(),
+            vec![TPU_ADDRESS.parse::<SocketAddr>().unwrap()]
+        );
+        {
+            let state = cache.state.read().unwrap();
+            assert_eq!(state.first_slot, 0);
+            assert_eq!(state.leaders.len(), 32);
+            assert_eq!(state.leaders[0], PUBKEY.parse().unwrap());
+        }
+
+        let keypair = Keypair::new();
+        let tx = system_transaction::transfer(&keypair, &keypair.pubkey(), 1, Hash::default());
+        assert_eq!(cache.send_transaction(&tx).unwrap(), 1);
+
+        let rpc_client = Arc::new(RpcClient::new_mock("fails".to_string()));
+        let cache = LeaderTpuCache::new(rpc_client, 2).unwrap();
+        assert!(cache.send_transaction(&tx).is_err());
+    }
+}
@@ -1,8 +1,13 @@
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate solana_metrics;
 
+pub mod airdrop;
+pub mod blockhash_cache;
 pub mod client_error;
 mod generic_rpc_client_request;
+pub mod leader_tpu_cache;
 pub mod mock_rpc_client_request;
 pub mod perf_utils;
 pub mod rpc_client;
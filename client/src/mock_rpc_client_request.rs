@@ -1,4 +1,4 @@
-use crate::rpc_request::{Response, RpcResponseContext};
+use crate::rpc_request::{Response, RpcContactInfo, RpcEpochInfo, RpcFees, RpcResponseContext};
 use crate::{
     client_error::ClientError, generic_rpc_client_request::GenericRpcClientRequest,
     rpc_request::RpcRequest,
@@ -11,6 +11,8 @@ use solana_sdk::{
 };
 
 pub const PUBKEY: &str = "7RoSF9fUmdphVCpabEoefH81WwrW7orsWonXWqTXkKV8";
+/// TPU address of the `PUBKEY` node in `getClusterNodes` responses
+pub const TPU_ADDRESS: &str = "127.0.0.1:1027";
 pub const SIGNATURE: &str =
     "43yNSFC6fYTuPgTNFFhF4axw7AfWxB2BPdurme8yrsWEYwm8299xh8n6TAHjGymiSub1XtyxTNyd9GBfY2hxoBw8";
 
@@ -61,6 +63,15 @@ impl GenericRpcClientRequest for MockRpcClientRequest {
                     serde_json::to_value(FeeCalculator::default()).unwrap(),
                 ),
             })?,
+            RpcRequest::GetFees => serde_json::to_value(Response {
+                context: RpcResponseContext { slot: 1 },
+                value: serde_json::to_value(RpcFees {
+                    blockhash: PUBKEY.to_string(),
+                    fee_calculator: FeeCalculator::default(),
+                    last_valid_slot: 42,
+                })
+                .unwrap(),
+            })?,
             RpcRequest::GetSignatureStatus => {
                 let response: Option<transaction::Result<()>> = if self.url == "account_in_use" {
                     Some(Err(TransactionError::AccountInUse))
@@ -71,6 +82,40 @@ impl GenericRpcClientRequest for MockRpcClientRequest {
                 };
                 serde_json::to_value(response).unwrap()
             }
+            RpcRequest::GetSignatureStatuses => {
+                let signatures = params
+                    .as_ref()
+                    .and_then(|params| params.as_array())
+                    .map_or(0, |signatures| signatures.len());
+                let response: Vec<Option<transaction::Result<()>>> = if self.url == "sig_not_found"
+                {
+                    vec![None; signatures]
+                } else {
+                    vec![Some(Ok(())); signatures]
+                };
+                serde_json::to_value(response).unwrap()
+            }
+            RpcRequest::GetEpochInfo => serde_json::to_value(RpcEpochInfo {
+                epoch: 0,
+                slot_index: 2,
+                slots_in_epoch: 32,
+                absolute_slot: 2,
+            })?,
+            RpcRequest::GetLeaderSchedule => serde_json::to_value(vec![PUBKEY; 32])?,
+            RpcRequest::GetClusterNodes => serde_json::to_value(vec![RpcContactInfo {
+                pubkey: PUBKEY.to_string(),
+                gossip: None,
+                tvu: None,
+                tvu_forwards: None,
+                repair: None,
+                tpu: TPU_ADDRESS.parse().ok(),
+                tpu_forwards: None,
+                storage: None,
+                rpc: None,
+                rpc_pubsub: None,
+                shred_version: 0,
+                version: None,
+            }])?,
             RpcRequest::GetTransactionCount => Value::Number(Number::from(1234)),
             RpcRequest::GetSlot => Value::Number(Number::from(0)),
             RpcRequest::SendTransaction => Value::String(SIGNATURE.to_string()),
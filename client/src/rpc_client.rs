@@ -1,10 +1,14 @@
 use crate::rpc_request::{Response, RpcResponse};
 use crate::{
+    blockhash_cache::BlockhashCache,
     client_error::ClientError,
     generic_rpc_client_request::GenericRpcClientRequest,
     mock_rpc_client_request::MockRpcClientRequest,
     rpc_client_request::RpcClientRequest,
-    rpc_request::{RpcContactInfo, RpcEpochInfo, RpcRequest, RpcVersionInfo, RpcVoteAccountStatus},
+    rpc_request::{
+        RpcContactInfo, RpcEpochInfo, RpcEpochRewards, RpcFees, RpcRequest, RpcVersionInfo,
+        RpcVoteAccountStatus,
+    },
 };
 use bincode::serialize;
 use log::*;
@@ -28,10 +32,32 @@ use std::{
     time::{Duration, Instant},
 };
 
+// How often the `wait_for_*` helpers poll the node
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 pub struct RpcClient {
     client: Box<dyn GenericRpcClientRequest + Send + Sync>,
 }
 
+// Call `check` until it returns a value or `deadline` passes, `check` describes why it isn't
+// done yet, the last description is returned in `ClientError::Timeout`
+fn poll_until_deadline<T, F>(deadline: Instant, mut check: F) -> Result<T, ClientError>
+where
+    F: FnMut() -> Result<T, String>,
+{
+    loop {
+        let state = match check() {
+            Ok(value) => return Ok(value),
+            Err(state) => state,
+        };
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(ClientError::Timeout(state));
+        }
+        sleep(WAIT_POLL_INTERVAL.min(deadline - now));
+    }
+}
+
 impl RpcClient {
     pub fn new(url: String) -> Self {
         Self {
@@ -132,6 +158,32 @@ impl RpcClient {
         Ok(result)
     }
 
+    /// Fetch the statuses of many signatures in a single request, the result is in the same
+    /// order as `signatures`
+    pub fn get_signature_statuses(
+        &self,
+        signatures: &[String],
+    ) -> Result<Vec<Option<transaction::Result<()>>>, ClientError> {
+        self.get_signature_statuses_with_commitment(signatures, CommitmentConfig::default())
+    }
+
+    pub fn get_signature_statuses_with_commitment(
+        &self,
+        signatures: &[String],
+        commitment_config: CommitmentConfig,
+    ) -> Result<Vec<Option<transaction::Result<()>>>, ClientError> {
+        let params = json!(signatures);
+        let signature_statuses = self.client.send(
+            &RpcRequest::GetSignatureStatuses,
+            Some(params),
+            5,
+            commitment_config.ok(),
+        )?;
+        let result: Vec<Option<transaction::Result<()>>> =
+            serde_json::from_value(signature_statuses)?;
+        Ok(result)
+    }
+
     pub fn get_slot(&self) -> io::Result<Slot> {
         self.get_slot_with_commitment(CommitmentConfig::default())
     }
@@ -158,6 +210,29 @@ impl RpcClient {
         })
     }
 
+    /// Wait until the node reaches `slot`, returns the node's slot
+    pub fn wait_for_slot(&self, slot: Slot, deadline: Instant) -> Result<Slot, ClientError> {
+        self.wait_for_slot_with_commitment(slot, deadline, CommitmentConfig::default())
+    }
+
+    pub fn wait_for_slot_with_commitment(
+        &self,
+        slot: Slot,
+        deadline: Instant,
+        commitment_config: CommitmentConfig,
+    ) -> Result<Slot, ClientError> {
+        poll_until_deadline(deadline, || {
+            match self.get_slot_with_commitment(commitment_config.clone()) {
+                Ok(current_slot) if current_slot >= slot => Ok(current_slot),
+                Ok(current_slot) => Err(format!(
+                    "waiting for slot {}, node is at slot {}",
+                    slot, current_slot
+                )),
+                Err(err) => Err(format!("waiting for slot {}: {}", slot, err)),
+            }
+        })
+    }
+
     pub fn get_vote_accounts(&self) -> io::Result<RpcVoteAccountStatus> {
         let response = self
             .client
@@ -234,6 +309,27 @@ impl RpcClient {
         })
     }
 
+    /// The leader of every slot of the current epoch, as base-58 pubkey strings. `None` if the
+    /// node has no leader schedule for the epoch.
+    pub fn get_leader_schedule(&self) -> io::Result<Option<Vec<String>>> {
+        let response = self
+            .client
+            .send(&RpcRequest::GetLeaderSchedule, None, 0, None)
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("GetLeaderSchedule request failure: {:?}", err),
+                )
+            })?;
+
+        serde_json::from_value(response).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("GetLeaderSchedule parse failure: {}", err),
+            )
+        })
+    }
+
     pub fn get_inflation(&self) -> io::Result<Inflation> {
         let response = self
             .client
@@ -253,6 +349,25 @@ impl RpcClient {
         })
     }
 
+    pub fn get_epoch_rewards(&self) -> io::Result<Option<RpcEpochRewards>> {
+        let response = self
+            .client
+            .send(&RpcRequest::GetEpochRewards, None, 0, None)
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("GetEpochRewards request failure: {:?}", err),
+                )
+            })?;
+
+        serde_json::from_value(response).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("GetEpochRewards parse failure: {}", err),
+            )
+        })
+    }
+
     pub fn get_version(&self) -> io::Result<RpcVersionInfo> {
         let response = self
             .client
@@ -276,6 +391,30 @@ impl RpcClient {
         &self,
         transaction: &mut Transaction,
         signer_keys: &[&T],
+    ) -> Result<String, ClientError> {
+        self.send_and_confirm_transaction_with_cache(transaction, signer_keys, None)
+    }
+
+    /// Like `send_and_confirm_transaction`, but a transaction that doesn't land is re-signed
+    /// with a blockhash from `blockhash_cache` once its blockhash has expired
+    pub fn send_and_confirm_transaction_with_blockhash_cache<T: KeypairUtil>(
+        &self,
+        transaction: &mut Transaction,
+        signer_keys: &[&T],
+        blockhash_cache: &BlockhashCache,
+    ) -> Result<String, ClientError> {
+        self.send_and_confirm_transaction_with_cache(
+            transaction,
+            signer_keys,
+            Some(blockhash_cache),
+        )
+    }
+
+    fn send_and_confirm_transaction_with_cache<T: KeypairUtil>(
+        &self,
+        transaction: &mut Transaction,
+        signer_keys: &[&T],
+        blockhash_cache: Option<&BlockhashCache>,
     ) -> Result<String, ClientError> {
         let mut send_retries = 20;
         loop {
@@ -307,6 +446,9 @@ impl RpcClient {
                     Err(_) => 0,
                 }
             } else {
+                if let Some(blockhash_cache) = blockhash_cache {
+                    Self::resign_if_expired(transaction, signer_keys, blockhash_cache)?;
+                }
                 send_retries - 1
             };
             if send_retries == 0 {
@@ -320,9 +462,33 @@ impl RpcClient {
     }
 
     pub fn send_and_confirm_transactions<T: KeypairUtil>(
+        &self,
+        transactions: Vec<Transaction>,
+        signer_keys: &[&T],
+    ) -> Result<(), Box<dyn error::Error>> {
+        self.send_and_confirm_transactions_with_cache(transactions, signer_keys, None)
+    }
+
+    /// Like `send_and_confirm_transactions`, but failed transactions are only re-signed, with a
+    /// blockhash from `blockhash_cache`, once their blockhash has expired
+    pub fn send_and_confirm_transactions_with_blockhash_cache<T: KeypairUtil>(
+        &self,
+        transactions: Vec<Transaction>,
+        signer_keys: &[&T],
+        blockhash_cache: &BlockhashCache,
+    ) -> Result<(), Box<dyn error::Error>> {
+        self.send_and_confirm_transactions_with_cache(
+            transactions,
+            signer_keys,
+            Some(blockhash_cache),
+        )
+    }
+
+    fn send_and_confirm_transactions_with_cache<T: KeypairUtil>(
         &self,
         mut transactions: Vec<Transaction>,
         signer_keys: &[&T],
+        blockhash_cache: Option<&BlockhashCache>,
     ) -> Result<(), Box<dyn error::Error>> {
         let mut send_retries = 5;
         loop {
@@ -377,6 +543,16 @@ impl RpcClient {
             send_retries -= 1;
 
             // Re-sign any failed transactions with a new blockhash and retry
+            if let Some(blockhash_cache) = blockhash_cache {
+                transactions = transactions_signatures
+                    .into_iter()
+                    .map(|(mut transaction, _)| {
+                        Self::resign_if_expired(&mut transaction, signer_keys, blockhash_cache)
+                            .map(|_| transaction)
+                    })
+                    .collect::<io::Result<_>>()?;
+                continue;
+            }
             let (blockhash, _fee_calculator) =
                 self.get_new_blockhash(&transactions_signatures[0].0.message().recent_blockhash)?;
             transactions = transactions_signatures
@@ -389,6 +565,18 @@ impl RpcClient {
         }
     }
 
+    fn resign_if_expired<T: KeypairUtil>(
+        tx: &mut Transaction,
+        signer_keys: &[&T],
+        blockhash_cache: &BlockhashCache,
+    ) -> io::Result<()> {
+        if !blockhash_cache.is_blockhash_valid(&tx.message().recent_blockhash)? {
+            let (blockhash, _fee_calculator) = blockhash_cache.get_blockhash()?;
+            tx.sign(signer_keys, blockhash);
+        }
+        Ok(())
+    }
+
     pub fn resign_transaction<T: KeypairUtil>(
         &self,
         tx: &mut Transaction,
@@ -659,6 +847,52 @@ impl RpcClient {
         })
     }
 
+    pub fn get_fees(&self) -> io::Result<(Hash, FeeCalculator, Slot)> {
+        Ok(self
+            .get_fees_with_commitment(CommitmentConfig::default())?
+            .value)
+    }
+
+    pub fn get_fees_with_commitment(
+        &self,
+        commitment_config: CommitmentConfig,
+    ) -> RpcResponse<(Hash, FeeCalculator, Slot)> {
+        let response = self
+            .client
+            .send(&RpcRequest::GetFees, None, 0, commitment_config.ok())
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("GetFees request failure: {:?}", err),
+                )
+            })?;
+
+        let Response {
+            context,
+            value:
+                RpcFees {
+                    blockhash,
+                    fee_calculator,
+                    last_valid_slot,
+                },
+        } = serde_json::from_value::<Response<RpcFees>>(response).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("GetFees parse failure: {:?}", err),
+            )
+        })?;
+        let blockhash = blockhash.parse().map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("GetFees hash parse failure: {:?}", err),
+            )
+        })?;
+        Ok(Response {
+            context,
+            value: (blockhash, fee_calculator, last_valid_slot),
+        })
+    }
+
     pub fn get_new_blockhash(&self, blockhash: &Hash) -> io::Result<(Hash, FeeCalculator)> {
         let mut num_retries = 0;
         let start = Instant::now();
@@ -750,6 +984,43 @@ impl RpcClient {
         )
     }
 
+    /// Wait until the balance of `pubkey` is at least `lamports`, returns the balance
+    pub fn wait_for_balance_at_least(
+        &self,
+        pubkey: &Pubkey,
+        lamports: u64,
+        deadline: Instant,
+    ) -> Result<u64, ClientError> {
+        self.wait_for_balance_at_least_with_commitment(
+            pubkey,
+            lamports,
+            deadline,
+            CommitmentConfig::default(),
+        )
+    }
+
+    pub fn wait_for_balance_at_least_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        lamports: u64,
+        deadline: Instant,
+        commitment_config: CommitmentConfig,
+    ) -> Result<u64, ClientError> {
+        poll_until_deadline(deadline, || {
+            match self.get_balance_with_commitment(pubkey, commitment_config.clone()) {
+                Ok(balance) if balance.value >= lamports => Ok(balance.value),
+                Ok(balance) => Err(format!(
+                    "waiting for {} lamports in {}, balance is {}",
+                    lamports, pubkey, balance.value
+                )),
+                Err(err) => Err(format!(
+                    "waiting for {} lamports in {}: {}",
+                    lamports, pubkey, err
+                )),
+            }
+        })
+    }
+
     pub fn wait_for_balance_with_commitment(
         &self,
         pubkey: &Pubkey,
@@ -990,7 +1261,10 @@ mod tests {
         system_transaction,
         transaction::TransactionError,
     };
-    use std::{sync::mpsc::channel, thread};
+    use std::{
+        sync::{mpsc::channel, Arc},
+        thread,
+    };
 
     #[test]
     fn test_send() {
@@ -1090,6 +1364,36 @@ mod tests {
         assert_eq!(balance.unwrap().as_u64().unwrap(), 5);
     }
 
+    #[test]
+    fn test_wait_for_slot_and_balance() {
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        // the mock is at slot 0 and every balance is 50 lamports
+        assert_eq!(rpc_client.wait_for_slot(0, deadline).unwrap(), 0);
+        let pubkey = Pubkey::new_rand();
+        assert_eq!(
+            rpc_client
+                .wait_for_balance_at_least(&pubkey, 50, deadline)
+                .unwrap(),
+            50
+        );
+
+        let deadline = Instant::now() + Duration::from_millis(10);
+        match rpc_client.wait_for_slot(1, deadline) {
+            Err(ClientError::Timeout(state)) => {
+                assert_eq!(state, "waiting for slot 1, node is at slot 0")
+            }
+            result => panic!("expected a timeout, got {:?}", result),
+        }
+        match rpc_client.wait_for_balance_at_least(&pubkey, 51, deadline) {
+            Err(ClientError::Timeout(state)) => assert_eq!(
+                state,
+                format!("waiting for 51 lamports in {}, balance is 50", pubkey)
+            ),
+            result => panic!("expected a timeout, got {:?}", result),
+        }
+    }
+
     #[test]
     fn test_send_transaction() {
         let rpc_client = RpcClient::new_mock("succeeds".to_string());
@@ -1139,6 +1443,18 @@ mod tests {
         assert_eq!(status, Some(Err(TransactionError::AccountInUse)));
     }
 
+    #[test]
+    fn test_get_signature_statuses() {
+        let signatures = vec!["good_signature".to_string(), "other_signature".to_string()];
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        let statuses = rpc_client.get_signature_statuses(&signatures).unwrap();
+        assert_eq!(statuses, vec![Some(Ok(())), Some(Ok(()))]);
+
+        let rpc_client = RpcClient::new_mock("sig_not_found".to_string());
+        let statuses = rpc_client.get_signature_statuses(&signatures).unwrap();
+        assert_eq!(statuses, vec![None, None]);
+    }
+
     #[test]
     fn test_send_and_confirm_transaction() {
         let rpc_client = RpcClient::new_mock("succeeds".to_string());
@@ -1158,6 +1474,16 @@ mod tests {
         let rpc_client = RpcClient::new_mock("fails".to_string());
         let result = rpc_client.send_and_confirm_transaction(&mut tx, &[&key]);
         assert!(result.is_err());
+
+        let blockhash_cache =
+            BlockhashCache::new(Arc::new(RpcClient::new_mock("succeeds".to_string())));
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        let result = rpc_client.send_and_confirm_transaction_with_blockhash_cache(
+            &mut tx,
+            &[&key],
+            &blockhash_cache,
+        );
+        result.unwrap();
     }
 
     #[test]
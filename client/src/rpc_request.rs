@@ -3,6 +3,7 @@ use serde_json::{json, Value};
 use solana_sdk::{
     clock::{Epoch, Slot},
     commitment_config::CommitmentConfig,
+    fee_calculator::FeeCalculator,
     hash::Hash,
     transaction::{Result, Transaction},
 };
@@ -36,15 +37,72 @@ pub struct RpcTransactionStatus {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct RpcContactInfo {
     /// Pubkey of the node as a base-58 string
     pub pubkey: String,
     /// Gossip port
     pub gossip: Option<SocketAddr>,
+    /// Tvu port
+    pub tvu: Option<SocketAddr>,
+    /// Tvu forwards port
+    pub tvu_forwards: Option<SocketAddr>,
+    /// Repair port
+    pub repair: Option<SocketAddr>,
     /// Tpu port
     pub tpu: Option<SocketAddr>,
+    /// Tpu forwards port
+    pub tpu_forwards: Option<SocketAddr>,
+    /// Storage port
+    pub storage: Option<SocketAddr>,
     /// JSON RPC port
     pub rpc: Option<SocketAddr>,
+    /// JSON RPC pubsub port
+    pub rpc_pubsub: Option<SocketAddr>,
+    /// Version of the shreds the node produces and accepts
+    #[serde(default)]
+    pub shred_version: u16,
+    /// Software version of the node, if known
+    pub version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcGossipStats {
+    /// Number of nodes in the gossip table, including this one
+    pub num_nodes: usize,
+
+    /// Identities gossiped by more than one node
+    pub duplicate_identities: Vec<RpcDuplicateIdentity>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcDuplicateIdentity {
+    /// The duplicated node pubkey, as base-58 encoded string
+    pub pubkey: String,
+
+    /// The nodes claiming the pubkey, most recently updated first
+    pub nodes: Vec<RpcDuplicateIdentityNode>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcDuplicateIdentityNode {
+    /// Gossip address the pubkey was advertised from
+    pub gossip: SocketAddr,
+
+    /// Tpu address advertised along with it
+    pub tpu: SocketAddr,
+
+    /// Tvu address advertised along with it
+    pub tvu: SocketAddr,
+
+    /// Oldest wallclock, in milliseconds, seen from this address
+    pub first_wallclock: u64,
+
+    /// Newest wallclock, in milliseconds, seen from this address
+    pub last_wallclock: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -63,6 +121,143 @@ pub struct RpcEpochInfo {
     pub absolute_slot: Slot,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcFees {
+    /// A recent blockhash, as a base-58 string
+    pub blockhash: String,
+
+    /// The fee schedule for transactions using the blockhash
+    pub fee_calculator: FeeCalculator,
+
+    /// The last slot in which transactions using the blockhash will be processed
+    pub last_valid_slot: Slot,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSegmentConfig {
+    /// The number of slots in a storage segment
+    pub slots_per_segment: u64,
+
+    /// The epoch the segment size was read in
+    pub epoch: Epoch,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcEpochRewards {
+    /// The epoch the rewards were computed for
+    pub epoch: Epoch,
+
+    /// Lamports of inflation set aside for validators and their stakers
+    pub validator_rewards: u64,
+
+    /// Stake-weighted vote credits claimed by the epoch
+    pub validator_points: u64,
+
+    /// Lamports paid per validator point
+    pub validator_point_value: f64,
+
+    /// Lamports of inflation set aside for storage accounts
+    pub storage_rewards: u64,
+
+    /// Storage credits claimed by the epoch
+    pub storage_points: u64,
+
+    /// Lamports paid per storage point
+    pub storage_point_value: f64,
+
+    /// Commission each vote account can redeem from the stakes delegated to it
+    pub vote_accounts: Vec<RpcAccountReward>,
+
+    /// Rewards each stake account can redeem, after commission
+    pub stake_accounts: Vec<RpcAccountReward>,
+
+    /// Rewards each storage account earned for its credits
+    pub storage_accounts: Vec<RpcAccountReward>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAccountReward {
+    /// The rewarded account, as base-58 encoded string
+    pub pubkey: String,
+
+    /// Reward, in lamports
+    pub lamports: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAccountLockContention {
+    /// The contended account, as base-58 encoded string
+    pub pubkey: String,
+
+    /// Number of transactions the account's lock kept from being processed
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcStakeAccount {
+    /// One of `uninitialized`, `initialized`, `delegated` or `rewardsPool`
+    pub state: String,
+
+    /// Lamports held by the stake account, delegated or not
+    pub lamports: u64,
+
+    /// Authorities and lockup of initialized and delegated stakes
+    pub meta: Option<RpcStakeMeta>,
+
+    /// Delegation of delegated stakes
+    pub delegation: Option<RpcStakeDelegation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcStakeMeta {
+    /// Lamports that must stay in the account to keep it rent exempt
+    pub rent_exempt_reserve: u64,
+
+    /// Authority to delegate, deactivate, split and merge the stake, as base-58 encoded string
+    pub staker: String,
+
+    /// Authority to withdraw from the account, as base-58 encoded string
+    pub withdrawer: String,
+
+    /// Slot until which withdrawals may only go to the custodian
+    pub lockup_slot: Slot,
+
+    /// The lockup's custodian, as base-58 encoded string
+    pub custodian: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcStakeDelegation {
+    /// The vote account the stake is delegated to, as base-58 encoded string
+    pub voter_pubkey: String,
+
+    /// Delegated lamports
+    pub stake: u64,
+
+    /// Epoch the stake was delegated in
+    pub activation_epoch: Epoch,
+
+    /// Epoch the stake was deactivated in, None while it's not deactivated
+    pub deactivation_epoch: Option<Epoch>,
+
+    /// Delegated lamports in effect in the current epoch
+    pub active_stake: u64,
+
+    /// Delegated lamports still warming up
+    pub activating_stake: u64,
+
+    /// Delegated lamports cooling down
+    pub deactivating_stake: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "kebab-case")]
 pub struct RpcVersionInfo {
@@ -108,21 +303,30 @@ pub enum RpcRequest {
     DeregisterNode,
     ValidatorExit,
     GetAccountInfo,
+    GetAccountLockContention,
     GetBalance,
     GetClusterNodes,
     GetEpochInfo,
+    GetEpochRewards,
     GetEpochSchedule,
+    GetFees,
     GetGenesisHash,
+    GetGossipStats,
     GetInflation,
+    GetLeaderSchedule,
     GetNumBlocksSinceSignatureConfirmation,
     GetProgramAccounts,
     GetRecentBlockhash,
     GetSignatureStatus,
+    GetSignatureStatuses,
     GetSlot,
     GetSlotLeader,
+    GetStakeAccount,
     GetStorageTurn,
     GetStorageTurnRate,
     GetSlotsPerSegment,
+    GetSegmentConfig,
+    GetSegmentLeaders,
     GetStoragePubkeysForSlot,
     GetTransactionCount,
     GetVersion,
@@ -131,6 +335,7 @@ pub enum RpcRequest {
     RequestAirdrop,
     SendTransaction,
     SignVote,
+    SimulateTransaction,
     GetMinimumBalanceForRentExemption,
 }
 
@@ -147,23 +352,32 @@ impl RpcRequest {
             RpcRequest::DeregisterNode => "deregisterNode",
             RpcRequest::ValidatorExit => "validatorExit",
             RpcRequest::GetAccountInfo => "getAccountInfo",
+            RpcRequest::GetAccountLockContention => "getAccountLockContention",
             RpcRequest::GetBalance => "getBalance",
             RpcRequest::GetClusterNodes => "getClusterNodes",
             RpcRequest::GetEpochInfo => "getEpochInfo",
+            RpcRequest::GetEpochRewards => "getEpochRewards",
             RpcRequest::GetEpochSchedule => "getEpochSchedule",
+            RpcRequest::GetFees => "getFees",
             RpcRequest::GetGenesisHash => "getGenesisHash",
+            RpcRequest::GetGossipStats => "getGossipStats",
             RpcRequest::GetInflation => "getInflation",
+            RpcRequest::GetLeaderSchedule => "getLeaderSchedule",
             RpcRequest::GetNumBlocksSinceSignatureConfirmation => {
                 "getNumBlocksSinceSignatureConfirmation"
             }
             RpcRequest::GetProgramAccounts => "getProgramAccounts",
             RpcRequest::GetRecentBlockhash => "getRecentBlockhash",
             RpcRequest::GetSignatureStatus => "getSignatureStatus",
+            RpcRequest::GetSignatureStatuses => "getSignatureStatuses",
             RpcRequest::GetSlot => "getSlot",
             RpcRequest::GetSlotLeader => "getSlotLeader",
+            RpcRequest::GetStakeAccount => "getStakeAccount",
             RpcRequest::GetStorageTurn => "getStorageTurn",
             RpcRequest::GetStorageTurnRate => "getStorageTurnRate",
             RpcRequest::GetSlotsPerSegment => "getSlotsPerSegment",
+            RpcRequest::GetSegmentConfig => "getSegmentConfig",
+            RpcRequest::GetSegmentLeaders => "getSegmentLeaders",
             RpcRequest::GetStoragePubkeysForSlot => "getStoragePubkeysForSlot",
             RpcRequest::GetTransactionCount => "getTransactionCount",
             RpcRequest::GetVersion => "getVersion",
@@ -172,6 +386,7 @@ impl RpcRequest {
             RpcRequest::RequestAirdrop => "requestAirdrop",
             RpcRequest::SendTransaction => "sendTransaction",
             RpcRequest::SignVote => "signVote",
+            RpcRequest::SimulateTransaction => "simulateTransaction",
             RpcRequest::GetMinimumBalanceForRentExemption => "getMinimumBalanceForRentExemption",
         };
         let mut request = json!({
@@ -235,6 +450,14 @@ mod tests {
         let request = test_request.build_request_json(1, None, None);
         assert_eq!(request["method"], "getEpochInfo");
 
+        let test_request = RpcRequest::GetEpochRewards;
+        let request = test_request.build_request_json(1, None, None);
+        assert_eq!(request["method"], "getEpochRewards");
+
+        let test_request = RpcRequest::GetFees;
+        let request = test_request.build_request_json(1, None, None);
+        assert_eq!(request["method"], "getFees");
+
         let test_request = RpcRequest::GetInflation;
         let request = test_request.build_request_json(1, None, None);
         assert_eq!(request["method"], "getInflation");
@@ -3,6 +3,7 @@
 //! messages to the network directly. The binary encoding of its messages are
 //! unstable and may change in future releases.
 
+use crate::blockhash_cache::BlockhashCache;
 use crate::rpc_client::RpcClient;
 use crate::rpc_request::Response;
 use bincode::{serialize_into, serialized_size};
@@ -29,11 +30,67 @@ use std::{
     net::{SocketAddr, UdpSocket},
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
-        RwLock,
+        mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+        Arc, RwLock,
     },
+    thread::Builder,
     time::{Duration, Instant},
 };
 
+/// Maximum number of serialized transactions waiting to be written to the TPU socket
+pub const EGRESS_QUEUE_SIZE: usize = 10_000;
+
+/// Bounded queue drained by a dedicated thread, so that `async_send_transaction` never
+/// blocks the caller on socket backpressure
+struct EgressQueue {
+    sender: SyncSender<(Vec<u8>, SocketAddr)>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl EgressQueue {
+    fn new(transactions_socket: &UdpSocket) -> Self {
+        let socket = transactions_socket
+            .try_clone()
+            .expect("clone transactions socket");
+        let (sender, receiver) = sync_channel(EGRESS_QUEUE_SIZE);
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let thread_dropped = dropped.clone();
+        // The thread exits once the owning ThinClient, and with it the sender, is dropped
+        Builder::new()
+            .name("solana-thin-client-egress".to_string())
+            .spawn(move || Self::run(&socket, &receiver, &thread_dropped))
+            .unwrap();
+        Self { sender, dropped }
+    }
+
+    fn run(socket: &UdpSocket, receiver: &Receiver<(Vec<u8>, SocketAddr)>, dropped: &AtomicUsize) {
+        for (buf, addr) in receiver.iter() {
+            if let Err(err) = socket.send_to(&buf, &addr) {
+                debug!("thin client egress send to {} failed: {:?}", addr, err);
+                dropped.fetch_add(1, Ordering::Relaxed);
+                inc_new_counter_warn!("thin_client-egress_send_failed", 1);
+            }
+        }
+    }
+
+    /// Queue `buf` for `addr`. When the queue is full the packet is dropped, as it would be
+    /// by any other congested hop on its way to the TPU.
+    fn send(&self, buf: Vec<u8>, addr: SocketAddr) -> io::Result<()> {
+        match self.sender.try_send((buf, addr)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                inc_new_counter_warn!("thin_client-egress_queue_full", 1);
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "thin client egress thread exited",
+            )),
+        }
+    }
+}
+
 struct ClientOptimizer {
     cur_index: AtomicUsize,
     experiment_index: AtomicUsize,
@@ -119,6 +176,8 @@ pub struct ThinClient {
     tpu_addrs: Vec<SocketAddr>,
     rpc_clients: Vec<RpcClient>,
     optimizer: ClientOptimizer,
+    blockhash_cache: Option<Arc<BlockhashCache>>,
+    egress: EgressQueue,
 }
 
 impl ThinClient {
@@ -148,10 +207,12 @@ impl ThinClient {
         rpc_client: RpcClient,
     ) -> Self {
         Self {
+            egress: EgressQueue::new(&transactions_socket),
             transactions_socket,
             tpu_addrs: vec![tpu_addr],
             rpc_clients: vec![rpc_client],
             optimizer: ClientOptimizer::new(0),
+            blockhash_cache: None,
         }
     }
 
@@ -167,12 +228,26 @@ impl ThinClient {
         let optimizer = ClientOptimizer::new(rpc_clients.len());
         Self {
             tpu_addrs,
+            egress: EgressQueue::new(&transactions_socket),
             transactions_socket,
             rpc_clients,
             optimizer,
+            blockhash_cache: None,
         }
     }
 
+    /// Use `blockhash_cache` when retrying transactions, so they are only re-signed once their
+    /// blockhash has expired
+    pub fn set_blockhash_cache(&mut self, blockhash_cache: Arc<BlockhashCache>) {
+        self.blockhash_cache = Some(blockhash_cache);
+    }
+
+    /// Number of transactions sent with `async_send_transaction` that never left this client,
+    /// either because the egress queue was full or the socket write failed
+    pub fn egress_dropped_count(&self) -> usize {
+        self.egress.dropped.load(Ordering::Relaxed)
+    }
+
     fn tpu_addr(&self) -> &SocketAddr {
         &self.tpu_addrs[self.optimizer.best()]
     }
@@ -243,7 +318,19 @@ impl ThinClient {
                 }
             }
             info!("{} tries failed transfer to {}", x, self.tpu_addr());
-            let (blockhash, _fee_calculator) = self.rpc_client().get_recent_blockhash()?;
+            let (blockhash, _fee_calculator) = match &self.blockhash_cache {
+                Some(blockhash_cache) => {
+                    if blockhash_cache
+                        .is_blockhash_valid(&transaction.message().recent_blockhash)?
+                    {
+                        // the transaction can still land as is, re-signing would only risk
+                        // paying twice
+                        continue;
+                    }
+                    blockhash_cache.get_blockhash()?
+                }
+                None => self.rpc_client().get_recent_blockhash()?,
+            };
             transaction.sign(keypairs, blockhash);
         }
         Err(io::Error::new(
@@ -558,8 +645,7 @@ impl AsyncClient for ThinClient {
         serialize_into(&mut wr, &transaction)
             .expect("serialize Transaction in pub fn transfer_signed");
         assert!(buf.len() < PACKET_DATA_SIZE);
-        self.transactions_socket
-            .send_to(&buf[..], &self.tpu_addr())?;
+        self.egress.send(buf, *self.tpu_addr())?;
         Ok(transaction.signatures[0])
     }
     fn async_send_message(
@@ -593,8 +679,14 @@ impl AsyncClient for ThinClient {
     }
 }
 
+// A socket of the same address family as `tpu` to send it transactions from
+fn transactions_socket(tpu: &SocketAddr, range: (u16, u16)) -> (u16, UdpSocket) {
+    let ip = solana_net_utils::unspecified_ip_for(&tpu.ip());
+    solana_net_utils::bind_in_range_with_ip(ip, range).unwrap()
+}
+
 pub fn create_client((rpc, tpu): (SocketAddr, SocketAddr), range: (u16, u16)) -> ThinClient {
-    let (_, transactions_socket) = solana_net_utils::bind_in_range(range).unwrap();
+    let (_, transactions_socket) = transactions_socket(&tpu, range);
     ThinClient::new(rpc, tpu, transactions_socket)
 }
 
@@ -603,7 +695,7 @@ pub fn create_client_with_timeout(
     range: (u16, u16),
     timeout: Duration,
 ) -> ThinClient {
-    let (_, transactions_socket) = solana_net_utils::bind_in_range(range).unwrap();
+    let (_, transactions_socket) = transactions_socket(&tpu, range);
     ThinClient::new_socket_with_timeout(rpc, tpu, transactions_socket, timeout)
 }
 
@@ -630,4 +722,23 @@ mod tests {
         optimizer.report(optimizer.best(), std::u64::MAX);
         assert_eq!(optimizer.best(), NUM_CLIENTS - 2);
     }
+
+    #[test]
+    fn test_egress_queue() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let egress = EgressQueue::new(&socket);
+
+        egress
+            .send(vec![1, 2, 3], receiver.local_addr().unwrap())
+            .unwrap();
+        let mut buf = [0; 8];
+        let (len, from) = receiver.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], &[1, 2, 3]);
+        assert_eq!(from, socket.local_addr().unwrap());
+        assert_eq!(egress.dropped.load(Ordering::Relaxed), 0);
+    }
 }
@@ -1,122 +1,813 @@
 use crate::{
-    chacha::{chacha_cbc_encrypt_ledger, CHACHA_BLOCK_SIZE},
+    archiver_client::{ArchiverClusterClient, GossipClusterClient, StorageTurnSource},
+    archiver_proof_log::{ProofLog, ProofLogEntry, PROOF_LOG_FILENAME},
+    archiver_segment_transfer::{self, shred_range_packets, SegmentManifest},
+    archiver_storage::{LocalFileBackend, ObjectReader, SegmentObject, StorageBackend},
+    chacha::{
+        chacha_cbc_encrypt_ledger, chacha_corrupted_chunks, chacha_reencrypt_chunks,
+        chacha_tag_file, chacha_verify_tags_from, CHACHA_BLOCK_SIZE, CHACHA_CHUNK_SLOTS,
+        CHACHA_TAG_CHUNK_SIZE,
+    },
     cluster_info::{ClusterInfo, Node, VALIDATOR_PORT_RANGE},
     contact_info::ContactInfo,
     gossip_service::GossipService,
     packet::{limited_deserialize, PACKET_DATA_SIZE},
-    repair_service,
-    repair_service::{RepairService, RepairSlotRange, RepairStrategy},
-    result::{Error, Result},
+    repair_peers::RepairPeers,
+    repair_service::{RepairSlotRange, RepairStrategy},
+    result::{Error, GossipError, Result, RpcError, SetupError, ShutdownError, StorageError},
     shred_fetch_stage::ShredFetchStage,
+    sigverify_shreds::SegmentShredSigVerifier,
     sigverify_stage::{DisabledSigVerifier, SigVerifyStage},
     storage_stage::NUM_STORAGE_SAMPLES,
-    streamer::{receiver, responder, PacketReceiver},
-    window_service::WindowService,
+    streamer::{decoding_receiver, receiver, responder, PacketReceiver},
+    thread_affinity::set_thread_nice,
+    thread_registry,
+    window_service::{window_channel, WindowService, DEFAULT_MAX_FUTURE_SHREDS},
+    wire_format,
 };
-use crossbeam_channel::unbounded;
+use crossbeam_channel::Sender as CrossbeamSender;
 use ed25519_dalek;
-use rand::{thread_rng, Rng, SeedableRng};
+use memmap::Mmap;
+use rand::{Rng, SeedableRng};
 use rand_chacha::ChaChaRng;
-use solana_client::{rpc_client::RpcClient, rpc_request::RpcRequest, thin_client::ThinClient};
+use rayon::{prelude::*, ThreadPool};
+use solana_client::rpc_request::{RpcRequest, RpcSegmentConfig};
 use solana_ledger::{
-    blocktree::Blocktree, leader_schedule_cache::LeaderScheduleCache, shred::Shred,
+    blocktree::{Blocktree, DownloadManifest},
+    leader_schedule_cache::LeaderScheduleCache,
 };
-use solana_net_utils::bind_in_range;
+use solana_measure::measure::Measure;
+use solana_net_utils::{bind_in_range_with_ip, unspecified_ip_for};
 use solana_perf::packet::Packets;
 use solana_perf::recycler::Recycler;
+use solana_rayon_threadlimit::get_thread_count;
 use solana_sdk::packet::Packet;
 use solana_sdk::{
     account_utils::State,
-    client::{AsyncClient, SyncClient},
-    clock::{get_complete_segment_from_slot, get_segment_from_slot, Slot},
+    clock::{get_complete_segment_from_slot, get_segment_from_slot, Epoch, Slot},
     commitment_config::CommitmentConfig,
     hash::{Hash, Hasher},
+    instruction::Instruction,
     message::Message,
+    pubkey::Pubkey,
     signature::{Keypair, KeypairUtil, Signature},
+    system_instruction,
+    sysvar::{
+        rewards::{self, Rewards},
+        Sysvar,
+    },
     timing::timestamp,
     transaction::Transaction,
     transport::TransportError,
 };
 use solana_storage_api::{
-    storage_contract::StorageContract,
+    storage_contract::{ProofStatus, StorageContract},
     storage_instruction::{self, StorageAccountType},
 };
 use std::{
-    fs::File,
-    io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom},
+    any::Any,
+    collections::{BTreeMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
     mem::size_of,
     net::{SocketAddr, UdpSocket},
+    ops::Range,
     path::{Path, PathBuf},
     result,
     sync::atomic::{AtomicBool, Ordering},
-    sync::mpsc::{channel, Receiver, Sender},
-    sync::{Arc, RwLock},
-    thread::{sleep, spawn, JoinHandle},
-    time::Duration,
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    sync::{Arc, Mutex, RwLock},
+    thread::{self, sleep, JoinHandle},
+    time::{Duration, Instant},
 };
 
-static ENCRYPTED_FILENAME: &str = "ledger.enc";
-
 #[derive(Serialize, Deserialize)]
 pub enum ArchiverRequest {
     GetSlotHeight(SocketAddr),
+    /// Answered with the archiver's `ArchiverStatus`
+    GetStatus(SocketAddr),
+    /// Answered with the `SegmentManifest` of the segment being stored
+    GetSegmentManifest(SocketAddr),
+    /// Answered with up to `SHRED_RANGE_WINDOW` data shreds of `slot` starting at
+    /// `start_index`, one shred per packet
+    GetShredRange {
+        from: SocketAddr,
+        slot: Slot,
+        start_index: u64,
+        count: u64,
+    },
+}
+
+impl ArchiverRequest {
+    /// The bincode encoding requests are sent with, every archiver decodes it
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    /// The compact wire encoding, only archivers that decode it can be sent it
+    pub fn to_compact_bytes(&self) -> Result<Vec<u8>> {
+        Ok(wire_format::archiver_request_to_bytes(self)?)
+    }
+
+    /// Decode a received request, in either encoding
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if wire_format::is_compact(data) {
+            Ok(wire_format::decode_archiver_request(data)?)
+        } else {
+            Ok(limited_deserialize(data)?)
+        }
+    }
+}
+
+/// What an archiver is storing and how its proofs are doing, for remote health checks
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ArchiverStatus {
+    /// First slot of the segment being stored
+    pub segment_start_slot: Slot,
+    pub slots_per_segment: u64,
+    /// Mining proofs whose transactions were confirmed
+    pub proofs_submitted: u64,
+    /// Transaction signature of the last confirmed mining proof
+    pub last_proof_signature: Option<Signature>,
+    /// Size in bytes of the encrypted segment
+    pub encrypted_file_size: u64,
+    /// Estimated lamports of the storage rewards claimed so far
+    pub rewards_claimed: u64,
 }
 
+/// How each stage of an archiver ended, by stage name
+type StageResults = Vec<(&'static str, thread::Result<()>)>;
+
 pub struct Archiver {
-    thread_handles: Vec<JoinHandle<()>>,
+    t_archiver: JoinHandle<StageResults>,
+    // disconnects once the archiver thread is done, so it can be waited on with a deadline
+    finished: Receiver<()>,
     exit: Arc<AtomicBool>,
 }
 
+/// Progress of an archiver, fed to the subscriber given to `ArchiverBuilder::event_sender`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArchiverEvent {
+    /// A segment starting at `slot` was picked for replication
+    SegmentSelected { slot: Slot, slots_per_segment: u64 },
+    /// Another slot of the segment starting at `slot` finished downloading
+    DownloadProgress {
+        slot: Slot,
+        slots_downloaded: u64,
+        slots_per_segment: u64,
+    },
+    /// The segment starting at `slot` was encrypted into `bytes` bytes
+    EncryptionDone { slot: Slot, bytes: u64 },
+    /// The transaction of a mining proof for the segment starting at `slot` was confirmed
+    ProofSubmitted { slot: Slot, signature: Signature },
+    /// Storage rewards were claimed from `storage_accounts` accounts
+    RewardClaimed {
+        storage_accounts: usize,
+        estimated_lamports: u64,
+    },
+    /// Something went wrong, the archiver keeps running unless it's been told to exit
+    Error(String),
+}
+
+/// How the archiver funds its storage account, it's created from the archiver keypair and
+/// topped up by the fee payer
+#[derive(Clone, Debug)]
+pub struct StorageFundingConfig {
+    /// Lamports the storage account is created with
+    pub initial_lamports: u64,
+    /// When set, the storage account is topped back up to the larger of `initial_lamports` and
+    /// this threshold whenever its balance drops below it
+    pub top_up_threshold: Option<u64>,
+}
+
+impl Default for StorageFundingConfig {
+    fn default() -> Self {
+        Self {
+            initial_lamports: 1,
+            top_up_threshold: None,
+        }
+    }
+}
+
+/// When the archiver claims its storage rewards. Every claim costs a transaction fee, so
+/// redemption waits until all of the thresholds are met.
+#[derive(Clone, Debug)]
+pub struct RedemptionPolicy {
+    /// Valid proofs that must be pending before claiming
+    pub min_validations: u64,
+    /// Minimum time between claims
+    pub min_interval: Duration,
+    /// Estimated reward, in lamports, that must be pending before claiming
+    pub min_reward_lamports: u64,
+}
+
+impl Default for RedemptionPolicy {
+    fn default() -> Self {
+        Self {
+            min_validations: 1,
+            min_interval: Duration::from_secs(0),
+            min_reward_lamports: 0,
+        }
+    }
+}
+
+impl RedemptionPolicy {
+    fn should_redeem(&self, valid_proofs: u64, estimated_reward: u64) -> bool {
+        valid_proofs >= self.min_validations.max(1) && estimated_reward >= self.min_reward_lamports
+    }
+}
+
+fn count_valid_proofs(validations: &BTreeMap<u64, BTreeMap<Pubkey, Vec<ProofStatus>>>) -> u64 {
+    validations
+        .values()
+        .flat_map(|validators| validators.values().flatten())
+        .filter(|status| **status == ProofStatus::Valid)
+        .count() as u64
+}
+
+/// Proof log entries for the validations not in `logged_validations` yet, one per segment and
+/// validator, adding them to it
+fn validated_proof_entries(
+    validations: &BTreeMap<u64, BTreeMap<Pubkey, Vec<ProofStatus>>>,
+    storage_point_value: f64,
+    logged_validations: &mut HashSet<(u64, Pubkey)>,
+) -> Vec<ProofLogEntry> {
+    let mut entries = vec![];
+    for (segment, validators) in validations {
+        for (validator, statuses) in validators {
+            if !logged_validations.insert((*segment, *validator)) {
+                continue;
+            }
+            let count = |proof_status| {
+                statuses
+                    .iter()
+                    .filter(|status| **status == proof_status)
+                    .count() as u64
+            };
+            let valid = count(ProofStatus::Valid);
+            let invalid = count(ProofStatus::NotValid);
+            entries.push(ProofLogEntry::new_validated(
+                *segment,
+                *validator,
+                valid,
+                invalid,
+                (valid as f64 * storage_point_value) as u64,
+            ));
+        }
+    }
+    entries
+}
+
+/// Disk space an archiver uses for one segment it replicated
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+struct SegmentUsage {
+    start_slot: Slot,
+    slots_per_segment: u64,
+    // estimated bytes of the segment's shreds in the blocktree
+    blocktree_bytes: u64,
+    // encrypted segment and tags, only the segment being proven still has them
+    encrypted_bytes: u64,
+    // a mining proof of the segment was confirmed
+    proven: bool,
+}
+
+impl SegmentUsage {
+    fn bytes(&self) -> u64 {
+        self.blocktree_bytes + self.encrypted_bytes
+    }
+}
+
+/// How many times the proof of a sampling round is submitted before it's given up on
+const MAX_PROOF_SUBMIT_ATTEMPTS: u32 = 3;
+
+/// Most proofs an archiver keeps waiting to be submitted, the oldest are dropped first
+const MAX_PENDING_PROOFS: usize = 16;
+
+/// The proof of one sampling round, until it's confirmed on chain
+#[derive(Clone, Debug, PartialEq)]
+struct PendingProof {
+    slot: Slot,
+    segment: u64,
+    sha_state: Hash,
+    signature: Signature,
+    blockhash: Hash,
+    // submissions that failed so far
+    attempts: u32,
+}
+
+impl PendingProof {
+    fn new(meta: &ArchiverMeta) -> Self {
+        Self {
+            slot: meta.slot,
+            segment: get_segment_from_slot(meta.slot, meta.slots_per_segment),
+            sha_state: meta.sha_state,
+            signature: meta.signature,
+            blockhash: meta.blockhash,
+            attempts: 0,
+        }
+    }
+
+    fn instruction(&self, storage_pubkey: &Pubkey) -> Instruction {
+        storage_instruction::mining_proof(
+            storage_pubkey,
+            self.sha_state,
+            self.segment,
+            self.signature,
+            self.blockhash,
+        )
+    }
+}
+
+/// Split `proofs` into consecutive ranges, each submitted by one message paid by `fee_payer`
+/// whose signed transaction fits in a packet
+fn mining_proof_batches(
+    storage_pubkey: &Pubkey,
+    fee_payer: &Pubkey,
+    proofs: &[PendingProof],
+) -> Vec<(Range<usize>, Message)> {
+    let new_message = |range: &Range<usize>| {
+        let instructions = proofs[range.clone()]
+            .iter()
+            .map(|proof| proof.instruction(storage_pubkey))
+            .collect();
+        Message::new_with_payer(instructions, Some(fee_payer))
+    };
+    let fits = |message: &Message| {
+        // unsigned transactions hold placeholders for every required signature
+        let transaction = Transaction::new_unsigned(message.clone());
+        bincode::serialized_size(&transaction).unwrap() <= PACKET_DATA_SIZE as u64
+    };
+
+    let mut batches = vec![];
+    let mut start = 0;
+    while start < proofs.len() {
+        let mut range = start..start + 1;
+        let mut message = new_message(&range);
+        while range.end < proofs.len() {
+            let next_range = start..range.end + 1;
+            let next_message = new_message(&next_range);
+            if !fits(&next_message) {
+                break;
+            }
+            range = next_range;
+            message = next_message;
+        }
+        start = range.end;
+        batches.push((range, message));
+    }
+    batches
+}
+
+/// Indexes of the `segments` to evict, oldest proven segments first, so that `required` more
+/// bytes fit within `quota`. The last segment is the one being proven, it's only a candidate
+/// when `replacing_current`, in which case its encrypted copy is about to be overwritten
+/// anyway. `None` if evicting every candidate isn't enough.
+fn segments_to_evict(
+    segments: &[SegmentUsage],
+    quota: u64,
+    required: u64,
+    replacing_current: bool,
+) -> Option<Vec<usize>> {
+    let mut used = segments.iter().map(SegmentUsage::bytes).sum::<u64>() + required;
+    let num_candidates = if replacing_current {
+        used -= segments
+            .last()
+            .map(|segment| segment.encrypted_bytes)
+            .unwrap_or(0);
+        segments.len()
+    } else {
+        segments.len().saturating_sub(1)
+    };
+    let mut evicted = vec![];
+    for (i, segment) in segments[..num_candidates].iter().enumerate() {
+        if used <= quota {
+            break;
+        }
+        if segment.proven {
+            used -= segment.blocktree_bytes;
+            evicted.push(i);
+        }
+    }
+    if used <= quota {
+        Some(evicted)
+    } else {
+        None
+    }
+}
+
+/// Estimated bytes the shreds of the `slots_per_segment` slots from `start_slot` take in
+/// `blocktree`
+fn segment_blocktree_bytes(blocktree: &Blocktree, start_slot: Slot, slots_per_segment: u64) -> u64 {
+    (start_slot..start_slot + slots_per_segment)
+        .filter_map(|slot| blocktree.meta(slot).ok().and_then(|meta| meta))
+        .map(|meta| meta.received * PACKET_DATA_SIZE as u64)
+        .sum()
+}
+
+/// Throughput of handling `bytes` in `duration_ms`, counting a sub-millisecond run as one
+/// millisecond
+fn bytes_per_sec(bytes: usize, duration_ms: u64) -> f64 {
+    bytes as f64 / (duration_ms.max(1) as f64 / 1000.0)
+}
+
+// The backend `ArchiverMeta` keeps the encrypted segment in, the working directory by default
+#[derive(Clone)]
+struct SegmentBackend(Arc<dyn StorageBackend>);
+
+impl Default for SegmentBackend {
+    fn default() -> Self {
+        SegmentBackend(Arc::new(LocalFileBackend::new(Path::new(""))))
+    }
+}
+
+/// The rayon pool an archiver encrypts and samples its segment on, away from the threads
+/// receiving shreds
+struct ComputePool(Arc<ThreadPool>);
+
+impl ComputePool {
+    /// A pool of `num_threads` threads, each running at `nice` if set
+    fn new(num_threads: usize, nice: Option<i32>) -> Result<Self> {
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|i| format!("archiver-compute-{}", i))
+            .start_handler(move |_| {
+                if let Some(nice) = nice {
+                    if let Err(err) = set_thread_nice(nice) {
+                        warn!("unable to set archiver compute thread nice level: {}", err);
+                    }
+                }
+            })
+            .build()
+            .map_err(|err| io::Error::new(ErrorKind::Other, err.to_string()))?;
+        Ok(ComputePool(Arc::new(thread_pool)))
+    }
+}
+
+impl Default for ComputePool {
+    fn default() -> Self {
+        ComputePool::new(get_thread_count(), None).unwrap()
+    }
+}
+
 // Shared Archiver Meta struct used internally
 #[derive(Default)]
 struct ArchiverMeta {
     slot: Slot,
     slots_per_segment: u64,
-    ledger_path: PathBuf,
+    // epoch in which `slots_per_segment` was last confirmed
+    segment_epoch: Epoch,
+    repair_slot_range: Arc<RwLock<RepairSlotRange>>,
     signature: Signature,
+    // where the segment is encrypted to before it's handed to `storage_backend`
     ledger_data_file_encrypted: PathBuf,
+    ledger_data_file_tags: PathBuf,
+    storage_backend: SegmentBackend,
     sampling_offsets: Vec<u64>,
     blockhash: Hash,
     sha_state: Hash,
     num_chacha_blocks: usize,
     client_commitment: CommitmentConfig,
+    storage_funding: StorageFundingConfig,
+    redemption_policy: RedemptionPolicy,
+    last_redemption: Option<Instant>,
+    proof_log: Option<Arc<ProofLog>>,
+    // validations already recorded in `proof_log`, by segment and validator
+    logged_validations: HashSet<(u64, Pubkey)>,
+    // where the `ArchiverState` is saved
+    state_path: PathBuf,
+    // answered to `ArchiverRequest::GetStatus`
+    status: Arc<RwLock<ArchiverStatus>>,
+    // verifies downloaded shreds against the leaders of the segment, if enabled
+    shred_verifier: Option<SegmentShredSigVerifier>,
+    // how long the segment download may go without completing a slot before another
+    // segment is selected
+    segment_download_timeout: Duration,
+    // pays proof fees and storage account top-ups instead of the archiver keypair, if set
+    fee_payer: Option<Arc<Keypair>>,
+    // subscriber to the archiver's progress
+    event_sender: Option<CrossbeamSender<ArchiverEvent>>,
+    // bytes the segments held may use, unlimited if not set
+    storage_quota: Option<u64>,
+    // segments held in the blocktree, oldest first, the last one is being proven
+    segments: Vec<SegmentUsage>,
+    // proofs of the sampling rounds not confirmed on chain yet, oldest first
+    pending_proofs: Vec<PendingProof>,
+    // runs the segment encryption and sampling
+    compute_pool: ComputePool,
+    sample_config: SampleConfig,
+}
+
+impl ArchiverMeta {
+    // A subscriber that went away or fell behind misses the event, it never holds up the
+    // archiver
+    fn send_event(&self, event: ArchiverEvent) {
+        if let Some(event_sender) = &self.event_sender {
+            let _ = event_sender.try_send(event);
+        }
+    }
 }
 
-pub(crate) fn sample_file(in_path: &Path, sample_offsets: &[u64]) -> io::Result<Hash> {
-    let in_file = File::open(in_path)?;
-    let metadata = in_file.metadata()?;
-    let mut buffer_file = BufReader::new(in_file);
+/// How long an archiver waits for the next slot of its segment before giving up on it
+pub const DEFAULT_SEGMENT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Name of the file under the ledger directory the `ArchiverState` is saved to
+pub const ARCHIVER_STATE_FILENAME: &str = "archiver_state.bin";
+
+/// The part of `ArchiverMeta` needed to pick the proofs back up after a restart, without
+/// downloading and encrypting a segment again
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct ArchiverState {
+    storage_pubkey: Pubkey,
+    slot: Slot,
+    slots_per_segment: u64,
+    segment_epoch: Epoch,
+    signature: Signature,
+    blockhash: Hash,
+    sampling_offsets: Vec<u64>,
+    sha_state: Hash,
+    num_chacha_blocks: usize,
+    segments: Vec<SegmentUsage>,
+}
+
+impl ArchiverState {
+    fn new(meta: &ArchiverMeta, storage_pubkey: &Pubkey) -> Self {
+        Self {
+            storage_pubkey: *storage_pubkey,
+            slot: meta.slot,
+            slots_per_segment: meta.slots_per_segment,
+            segment_epoch: meta.segment_epoch,
+            signature: meta.signature,
+            blockhash: meta.blockhash,
+            sampling_offsets: meta.sampling_offsets.clone(),
+            sha_state: meta.sha_state,
+            num_chacha_blocks: meta.num_chacha_blocks,
+            segments: meta.segments.clone(),
+        }
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path)?;
+        Ok(bincode::deserialize(&data)?)
+    }
+
+    // write to a temporary file first so a crash never leaves a partial state behind
+    fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, bincode::serialize(self)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Check the state belongs to `storage_pubkey`, is for the current segment size and that
+    /// the encrypted segment it describes is still intact in `storage_backend`
+    fn verify(
+        &self,
+        storage_pubkey: &Pubkey,
+        slots_per_segment: u64,
+        storage_backend: &dyn StorageBackend,
+    ) -> Result<()> {
+        if self.storage_pubkey != *storage_pubkey {
+            return Err(StorageError::StateMismatch(format!(
+                "saved for storage account {}",
+                self.storage_pubkey
+            ))
+            .into());
+        }
+        if self.slots_per_segment != slots_per_segment {
+            return Err(StorageError::StateMismatch(format!(
+                "saved for {} slots per segment, the cluster now uses {}",
+                self.slots_per_segment, slots_per_segment
+            ))
+            .into());
+        }
+        let num_chacha_blocks =
+            storage_backend.size(SegmentObject::Segment)? as usize / CHACHA_BLOCK_SIZE;
+        if self.num_chacha_blocks == 0 || num_chacha_blocks != self.num_chacha_blocks {
+            return Err(StorageError::StateMismatch(format!(
+                "expected {} encrypted blocks in {}, found {}",
+                self.num_chacha_blocks,
+                SegmentObject::Segment.name(),
+                num_chacha_blocks
+            ))
+            .into());
+        }
+        let offsets = sampling_offsets(&self.blockhash, self.num_chacha_blocks);
+        verify_segment_tags(storage_backend, &sampled_chunk_indexes(&offsets))?;
+        Ok(())
+    }
+}
+
+/// Below this many samples the file is read on the calling thread
+const MIN_PARALLEL_SAMPLES: usize = 64;
+
+/// The offsets an archiver samples from a segment of `num_chacha_blocks` encrypted blocks, seeded
+/// by the storage `blockhash`
+pub fn sampling_offsets(blockhash: &Hash, num_chacha_blocks: usize) -> Vec<u64> {
+    let mut rng_seed = [0u8; 32];
+    rng_seed.copy_from_slice(&blockhash.as_ref());
+    let mut rng = ChaChaRng::from_seed(rng_seed);
+    (0..NUM_STORAGE_SAMPLES)
+        .map(|_| rng.gen_range(0, num_chacha_blocks) as u64)
+        .collect()
+}
+
+/// How `sample_file_with()` reads the samples of a file, the hash is the same either way
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampleConfig {
+    /// Read the samples in file order instead of the order they were requested in
+    pub sort_offsets: bool,
+    /// Have the kernel read every sample in the background before they're read, so the disk
+    /// can schedule them together. Linux only.
+    pub prefetch: bool,
+}
+
+impl Default for SampleConfig {
+    fn default() -> Self {
+        Self {
+            sort_offsets: true,
+            prefetch: true,
+        }
+    }
+}
+
+/// Hash the 32-byte samples of `in_path` at `sample_offsets`, the mining hash of a storage proof
+pub fn sample_file(in_path: &Path, sample_offsets: &[u64]) -> io::Result<Hash> {
+    sample_file_with(in_path, sample_offsets, SampleConfig::default())
+}
+
+/// `sample_file()` reading the samples through a memory map of `in_path` as `config` says
+pub fn sample_file_with(
+    in_path: &Path,
+    sample_offsets: &[u64],
+    config: SampleConfig,
+) -> io::Result<Hash> {
+    let file = File::open(in_path)?;
+    check_sample_offsets(file.metadata()?.len(), sample_offsets)?;
+    let map = unsafe { Mmap::map(&file)? };
+    if config.prefetch {
+        prefetch_samples(&file, &map, sample_offsets);
+    }
 
-    let mut hasher = Hasher::default();
     let sample_size = size_of::<Hash>();
-    let sample_size64 = sample_size as u64;
-    let mut buf = vec![0; sample_size];
+    let mut read_order: Vec<usize> = (0..sample_offsets.len()).collect();
+    if config.sort_offsets {
+        read_order.sort_by_key(|i| sample_offsets[*i]);
+    }
+    let mut samples = vec![vec![0; sample_size]; sample_offsets.len()];
+    for i in read_order {
+        let start = sample_offsets[i] as usize * sample_size;
+        trace!("sampling @ {} ", sample_offsets[i]);
+        samples[i].copy_from_slice(&map[start..start + sample_size]);
+    }
+    let mut hasher = Hasher::default();
+    for sample in &samples {
+        hasher.hash(sample);
+    }
+    Ok(hasher.result())
+}
 
-    let file_len = metadata.len();
-    if file_len < sample_size64 {
-        return Err(io::Error::new(ErrorKind::Other, "file too short!"));
+// Start reading the samples in the background. Random access advice keeps each page fault from
+// reading ahead around its sample, the samples themselves are all queued with the disk at once.
+#[cfg(target_os = "linux")]
+fn prefetch_samples(file: &File, map: &Mmap, sample_offsets: &[u64]) {
+    use std::os::unix::io::AsRawFd;
+    let sample_size = size_of::<Hash>() as u64;
+    unsafe {
+        libc::madvise(
+            map.as_ptr() as *mut libc::c_void,
+            map.len(),
+            libc::MADV_RANDOM,
+        );
     }
     for offset in sample_offsets {
-        if *offset > (file_len - sample_size64) / sample_size64 {
-            return Err(io::Error::new(ErrorKind::Other, "offset too large"));
-        }
-        buffer_file.seek(SeekFrom::Start(*offset * sample_size64))?;
-        trace!("sampling @ {} ", *offset);
-        match buffer_file.read(&mut buf) {
-            Ok(size) => {
-                assert_eq!(size, buf.len());
-                hasher.hash(&buf);
-            }
-            Err(e) => {
-                warn!("Error sampling file");
-                return Err(e);
-            }
+        unsafe {
+            libc::posix_fadvise(
+                file.as_raw_fd(),
+                (offset * sample_size) as libc::off_t,
+                sample_size as libc::off_t,
+                libc::POSIX_FADV_WILLNEED,
+            );
         }
     }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn prefetch_samples(_file: &File, _map: &Mmap, _sample_offsets: &[u64]) {}
+
+/// Same as `sample_file_with()`, for the encrypted segment kept in `storage_backend`. Segments
+/// kept on the local filesystem are memory mapped, others are read through the backend.
+pub fn sample_segment(
+    storage_backend: &dyn StorageBackend,
+    sample_offsets: &[u64],
+    config: SampleConfig,
+) -> io::Result<Hash> {
+    if let Some(path) = storage_backend.local_path(SegmentObject::Segment) {
+        return sample_file_with(&path, sample_offsets, config);
+    }
+    let file_len = storage_backend.size(SegmentObject::Segment)?;
+    sample_from(
+        || ObjectReader::new(storage_backend, SegmentObject::Segment),
+        file_len,
+        sample_offsets,
+    )
+}
+
+// Fail if a sample at `sample_offsets` doesn't fit in `file_len` bytes
+fn check_sample_offsets(file_len: u64, sample_offsets: &[u64]) -> io::Result<()> {
+    let sample_size64 = size_of::<Hash>() as u64;
+    if file_len < sample_size64 {
+        return Err(io::Error::new(ErrorKind::Other, "file too short!"));
+    }
+    if sample_offsets
+        .iter()
+        .any(|offset| *offset > (file_len - sample_size64) / sample_size64)
+    {
+        return Err(io::Error::new(ErrorKind::Other, "offset too large"));
+    }
+    Ok(())
+}
 
+// Sample `file_len` bytes of data read through the readers returned by `open`, one per thread
+fn sample_from<R, F>(open: F, file_len: u64, sample_offsets: &[u64]) -> io::Result<Hash>
+where
+    R: Read + Seek,
+    F: Fn() -> io::Result<R> + Sync,
+{
+    let sample_size = size_of::<Hash>();
+    let sample_size64 = sample_size as u64;
+    check_sample_offsets(file_len, sample_offsets)?;
+
+    // Read the samples in offset order, split across threads, then hash them in the order
+    // they were requested so the result matches a sequential pass over `sample_offsets`
+    let mut sorted_indexes: Vec<usize> = (0..sample_offsets.len()).collect();
+    sorted_indexes.sort_by_key(|i| sample_offsets[*i]);
+    let read_samples = |indexes: &[usize]| -> io::Result<Vec<(usize, Vec<u8>)>> {
+        let mut buffer_file = open()?;
+        indexes
+            .iter()
+            .map(|i| {
+                let mut buf = vec![0; sample_size];
+                buffer_file.seek(SeekFrom::Start(sample_offsets[*i] * sample_size64))?;
+                trace!("sampling @ {} ", sample_offsets[*i]);
+                buffer_file.read_exact(&mut buf).map_err(|e| {
+                    warn!("Error sampling file");
+                    e
+                })?;
+                Ok((*i, buf))
+            })
+            .collect()
+    };
+    let partitions = if sorted_indexes.len() < MIN_PARALLEL_SAMPLES {
+        vec![read_samples(&sorted_indexes)?]
+    } else {
+        let chunk_size = (sorted_indexes.len() + rayon::current_num_threads() - 1)
+            / rayon::current_num_threads();
+        sorted_indexes
+            .par_chunks(chunk_size)
+            .map(read_samples)
+            .collect::<io::Result<Vec<_>>>()?
+    };
+
+    let mut samples = vec![vec![]; sample_offsets.len()];
+    for (i, buf) in partitions.into_iter().flatten() {
+        samples[i] = buf;
+    }
+    let mut hasher = Hasher::default();
+    for sample in &samples {
+        hasher.hash(sample);
+    }
     Ok(hasher.result())
 }
 
+/// Check the tags of the given chunks of the encrypted segment kept in `storage_backend`
+fn verify_segment_tags(
+    storage_backend: &dyn StorageBackend,
+    chunk_indexes: &[u64],
+) -> io::Result<()> {
+    chacha_verify_tags_from(
+        &mut ObjectReader::new(storage_backend, SegmentObject::Segment)?,
+        &mut ObjectReader::new(storage_backend, SegmentObject::Tags)?,
+        chunk_indexes,
+    )
+}
+
+/// Return the tagged chunks of the encrypted segment read by `sample_file()`
+fn sampled_chunk_indexes(sample_offsets: &[u64]) -> Vec<u64> {
+    let sample_size64 = size_of::<Hash>() as u64;
+    let chunk_size64 = CHACHA_TAG_CHUNK_SIZE as u64;
+    let mut chunk_indexes: Vec<_> = sample_offsets
+        .iter()
+        .flat_map(|offset| {
+            let start = offset * sample_size64;
+            let end = start + sample_size64 - 1;
+            vec![start / chunk_size64, end / chunk_size64]
+        })
+        .collect();
+    chunk_indexes.sort();
+    chunk_indexes.dedup();
+    chunk_indexes
+}
+
 fn get_slot_from_signature(
     signature: &ed25519_dalek::Signature,
     storage_turn: u64,
@@ -133,24 +824,84 @@ fn get_slot_from_signature(
     segment_index * slots_per_segment
 }
 
+/// Join the archiver stages once exit was signalled, a stage panicking doesn't stop the others
+/// from being joined
+fn join_stages(
+    request_processor: Vec<JoinHandle<()>>,
+    fetch_stage: ShredFetchStage,
+    gossip_service: GossipService,
+    window_service: Option<WindowService>,
+) -> StageResults {
+    let mut results: StageResults = request_processor
+        .into_iter()
+        .map(|t| ("request processor", t.join()))
+        .collect();
+    results.push(("fetch", fetch_stage.join()));
+    results.push(("gossip", gossip_service.join()));
+    if let Some(window_service) = window_service {
+        results.push(("window", window_service.join()));
+    }
+    results
+}
+
+/// Fold the outcome of the archiver thread and the stages it joined into one result
+fn stage_results(archiver: thread::Result<StageResults>) -> Result<()> {
+    let stages = archiver.unwrap_or_else(|err| vec![("archiver", Err(err))]);
+    let panicked: Vec<_> = stages
+        .into_iter()
+        .filter_map(|(stage, result)| {
+            result
+                .err()
+                .map(|err| (stage.to_string(), panic_message(&*err)))
+        })
+        .collect();
+    if panicked.is_empty() {
+        Ok(())
+    } else {
+        Err(ShutdownError::StagesPanicked(panicked).into())
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
 fn create_request_processor(
     socket: UdpSocket,
     exit: &Arc<AtomicBool>,
     slot_receiver: Receiver<u64>,
+    status: Arc<RwLock<ArchiverStatus>>,
+    blocktree: Arc<Blocktree>,
 ) -> Vec<JoinHandle<()>> {
     let mut thread_handles = vec![];
     let (s_reader, r_reader) = channel();
+    let (s_request, r_request) = channel();
     let (s_responder, r_responder) = channel();
     let storage_socket = Arc::new(socket);
     let recycler = Recycler::default();
     let t_receiver = receiver(storage_socket.clone(), exit, s_reader, recycler, "archiver");
     thread_handles.push(t_receiver);
 
+    let t_typed_receiver = decoding_receiver(
+        r_reader,
+        exit,
+        s_request,
+        "archiver-request",
+        ArchiverRequest::decode,
+    );
+    thread_handles.push(t_typed_receiver);
+
     let t_responder = responder("archiver-responder", storage_socket.clone(), r_responder);
     thread_handles.push(t_responder);
 
     let exit = exit.clone();
-    let t_processor = spawn(move || {
+    let t_processor = thread_registry::spawn("archiver-request", Some(exit.clone()), move || {
         let slot = poll_for_slot(slot_receiver, &exit);
 
         loop {
@@ -158,25 +909,47 @@ fn create_request_processor(
                 break;
             }
 
-            let packets = r_reader.recv_timeout(Duration::from_secs(1));
-
-            if let Ok(packets) = packets {
-                for packet in &packets.packets {
-                    let req: result::Result<ArchiverRequest, Box<bincode::ErrorKind>> =
-                        limited_deserialize(&packet.data[..packet.meta.size]);
-                    match req {
-                        Ok(ArchiverRequest::GetSlotHeight(from)) => {
+            if let Ok(requests) = r_request.recv_timeout(Duration::from_secs(1)) {
+                for (request, _addr) in requests {
+                    match request {
+                        ArchiverRequest::GetSlotHeight(from) => {
                             let packet = Packet::from_data(&from, slot);
                             let _ = s_responder.send(Packets::new(vec![packet]));
                         }
-                        Err(e) => {
-                            info!("invalid request: {:?}", e);
+                        ArchiverRequest::GetStatus(from) => {
+                            let packet = Packet::from_data(&from, &*status.read().unwrap());
+                            let _ = s_responder.send(Packets::new(vec![packet]));
+                        }
+                        ArchiverRequest::GetSegmentManifest(from) => {
+                            let manifest = {
+                                let status = status.read().unwrap();
+                                SegmentManifest::new(
+                                    &blocktree,
+                                    status.segment_start_slot,
+                                    status.slots_per_segment,
+                                )
+                            };
+                            let packet = Packet::from_data(&from, manifest);
+                            let _ = s_responder.send(Packets::new(vec![packet]));
+                        }
+                        ArchiverRequest::GetShredRange {
+                            from,
+                            slot,
+                            start_index,
+                            count,
+                        } => {
+                            let packets =
+                                shred_range_packets(&blocktree, slot, start_index, count, &from);
+                            if !packets.packets.is_empty() {
+                                let _ = s_responder.send(packets);
+                            }
                         }
                     }
                 }
             }
         }
-    });
+    })
+    .unwrap();
     thread_handles.push(t_processor);
     thread_handles
 }
@@ -193,38 +966,263 @@ fn poll_for_slot(receiver: Receiver<u64>, exit: &Arc<AtomicBool>) -> u64 {
     }
 }
 
-impl Archiver {
-    /// Returns a Result that contains an archiver on success
-    ///
+/// Builds an `Archiver`, the cluster client, blocktree and storage backend can be replaced to
+/// embed an archiver with a custom transport or to run it against a mock cluster
+pub struct ArchiverBuilder {
+    ledger_path: PathBuf,
+    node: Node,
+    cluster_entrypoint: ContactInfo,
+    keypair: Arc<Keypair>,
+    storage_keypair: Arc<Keypair>,
+    client_commitment: CommitmentConfig,
+    storage_funding: StorageFundingConfig,
+    redemption_policy: RedemptionPolicy,
+    cluster_client: Option<Arc<dyn ArchiverClusterClient>>,
+    blocktree: Option<Arc<Blocktree>>,
+    storage_backend: Option<Arc<dyn StorageBackend>>,
+    proof_log_path: Option<PathBuf>,
+    verify_shreds: bool,
+    segment_download_timeout: Duration,
+    fee_payer: Option<Arc<Keypair>>,
+    event_sender: Option<CrossbeamSender<ArchiverEvent>>,
+    storage_quota: Option<u64>,
+    storage_turn_source: StorageTurnSource,
+    compute_threads: Option<usize>,
+    compute_nice: Option<i32>,
+    sample_config: SampleConfig,
+    direct_connect: Option<(SocketAddr, SocketAddr)>,
+}
+
+impl ArchiverBuilder {
     /// # Arguments
     /// * `ledger_path` - path to where the ledger will be stored.
-    /// Causes panic if none
     /// * `node` - The archiver node
     /// * `cluster_entrypoint` - ContactInfo representing an entry into the network
     /// * `keypair` - Keypair for this archiver
-    #[allow(clippy::new_ret_no_self)]
+    /// * `storage_keypair` - Keypair of the archiver's storage account
     pub fn new(
         ledger_path: &Path,
         node: Node,
         cluster_entrypoint: ContactInfo,
         keypair: Arc<Keypair>,
         storage_keypair: Arc<Keypair>,
-        client_commitment: CommitmentConfig,
-    ) -> Result<Self> {
+    ) -> Self {
+        Self {
+            ledger_path: ledger_path.to_path_buf(),
+            node,
+            cluster_entrypoint,
+            keypair,
+            storage_keypair,
+            client_commitment: CommitmentConfig::default(),
+            storage_funding: StorageFundingConfig::default(),
+            redemption_policy: RedemptionPolicy::default(),
+            cluster_client: None,
+            blocktree: None,
+            storage_backend: None,
+            proof_log_path: None,
+            verify_shreds: false,
+            segment_download_timeout: DEFAULT_SEGMENT_DOWNLOAD_TIMEOUT,
+            fee_payer: None,
+            event_sender: None,
+            storage_quota: None,
+            storage_turn_source: StorageTurnSource::default(),
+            compute_threads: None,
+            compute_nice: None,
+            sample_config: SampleConfig::default(),
+            direct_connect: None,
+        }
+    }
+
+    pub fn client_commitment(mut self, client_commitment: CommitmentConfig) -> Self {
+        self.client_commitment = client_commitment;
+        self
+    }
+
+    /// How the storage account is funded
+    pub fn storage_funding(mut self, storage_funding: StorageFundingConfig) -> Self {
+        self.storage_funding = storage_funding;
+        self
+    }
+
+    /// When storage rewards are claimed
+    pub fn redemption_policy(mut self, redemption_policy: RedemptionPolicy) -> Self {
+        self.redemption_policy = redemption_policy;
+        self
+    }
+
+    /// Use `cluster_client` for RPC requests and transactions instead of discovering the
+    /// cluster through the entrypoint and picking peers from gossip
+    pub fn cluster_client(mut self, cluster_client: Arc<dyn ArchiverClusterClient>) -> Self {
+        self.cluster_client = Some(cluster_client);
+        self
+    }
+
+    /// Download the segment into `blocktree` instead of opening one at the ledger path
+    pub fn blocktree(mut self, blocktree: Arc<Blocktree>) -> Self {
+        self.blocktree = Some(blocktree);
+        self
+    }
+
+    /// Keep the encrypted segment in `storage_backend` instead of the ledger directory
+    pub fn storage_backend(mut self, storage_backend: Arc<dyn StorageBackend>) -> Self {
+        self.storage_backend = Some(storage_backend);
+        self
+    }
+
+    /// Record submitted proofs and their validations at `proof_log_path` instead of the
+    /// ledger directory
+    pub fn proof_log(mut self, proof_log_path: &Path) -> Self {
+        self.proof_log_path = Some(proof_log_path.to_path_buf());
+        self
+    }
+
+    /// Verify the signatures of downloaded shreds against the leader schedule of the segment
+    /// and drop invalid ones, instead of storing every shred peers send
+    pub fn verify_shreds(mut self, verify_shreds: bool) -> Self {
+        self.verify_shreds = verify_shreds;
+        self
+    }
+
+    /// Select another segment when no slot of the one being downloaded completes within
+    /// `segment_download_timeout`
+    pub fn segment_download_timeout(mut self, segment_download_timeout: Duration) -> Self {
+        self.segment_download_timeout = segment_download_timeout;
+        self
+    }
+
+    /// Pay the fees of mining proofs and the storage account top-ups from `fee_payer`, so the
+    /// archiver keypair only signs the storage account setup and reward claims
+    pub fn fee_payer(mut self, fee_payer: Arc<Keypair>) -> Self {
+        self.fee_payer = Some(fee_payer);
+        self
+    }
+
+    /// Feed `ArchiverEvent`s to `event_sender` as the archiver makes progress, for embedders
+    /// observing it. Events a bounded channel has no room for are dropped.
+    pub fn event_sender(mut self, event_sender: CrossbeamSender<ArchiverEvent>) -> Self {
+        self.event_sender = Some(event_sender);
+        self
+    }
+
+    /// Keep the blocktree shreds and encrypted copies of the segments replicated so far
+    /// within `storage_quota` bytes, by evicting the oldest proven segments before a new one
+    /// is accepted
+    pub fn storage_quota(mut self, storage_quota: u64) -> Self {
+        self.storage_quota = Some(storage_quota);
+        self
+    }
+
+    /// Send RPC requests to `rpc_addr` and transactions to `tpu_addr` instead of discovering
+    /// the cluster through the entrypoint first, for a single local validator. Ignored when a
+    /// `cluster_client` is supplied.
+    pub fn direct_connect(mut self, rpc_addr: SocketAddr, tpu_addr: SocketAddr) -> Self {
+        self.direct_connect = Some((rpc_addr, tpu_addr));
+        self
+    }
+
+    /// Learn storage turns from `storage_turn_source`. Ignored when a `cluster_client` is
+    /// supplied.
+    pub fn storage_turn_source(mut self, storage_turn_source: StorageTurnSource) -> Self {
+        self.storage_turn_source = storage_turn_source;
+        self
+    }
+
+    /// Encrypt and sample the segment on `compute_threads` threads, instead of one per core
+    pub fn compute_threads(mut self, compute_threads: usize) -> Self {
+        self.compute_threads = Some(compute_threads);
+        self
+    }
+
+    /// Run the threads encrypting and sampling the segment at nice level `compute_nice`, so
+    /// the archiver yields the CPU to a validator on the same host. Linux only.
+    pub fn compute_nice(mut self, compute_nice: i32) -> Self {
+        self.compute_nice = Some(compute_nice);
+        self
+    }
+
+    /// How the samples of a segment kept on the local filesystem are read
+    pub fn sample_config(mut self, sample_config: SampleConfig) -> Self {
+        self.sample_config = sample_config;
+        self
+    }
+
+    /// Join the cluster and start replicating, returns once the storage account is set up
+    pub fn build(self) -> Result<Archiver> {
+        self.start(false)
+    }
+
+    /// Like `build`, but continue proving the segment saved under the ledger directory by a
+    /// previous run when it is still intact, instead of downloading and encrypting a new one
+    pub fn resume(self) -> Result<Archiver> {
+        self.start(true)
+    }
+
+    fn start(self, resume: bool) -> Result<Archiver> {
+        let Self {
+            ledger_path,
+            node,
+            cluster_entrypoint,
+            keypair,
+            storage_keypair,
+            client_commitment,
+            storage_funding,
+            redemption_policy,
+            cluster_client,
+            blocktree,
+            storage_backend,
+            proof_log_path,
+            verify_shreds,
+            segment_download_timeout,
+            fee_payer,
+            event_sender,
+            storage_quota,
+            storage_turn_source,
+            compute_threads,
+            compute_nice,
+            sample_config,
+            direct_connect,
+        } = self;
         let exit = Arc::new(AtomicBool::new(false));
+        let compute_pool = ComputePool::new(
+            compute_threads.unwrap_or_else(get_thread_count),
+            compute_nice,
+        )?;
 
         info!("Archiver: id: {}", keypair.pubkey());
         info!("Creating cluster info....");
         let mut cluster_info = ClusterInfo::new(node.info.clone(), keypair.clone());
         cluster_info.set_entrypoint(cluster_entrypoint.clone());
+        cluster_info.push_archiver_info(0);
         let cluster_info = Arc::new(RwLock::new(cluster_info));
 
         // Note for now, this ledger will not contain any of the existing entries
         // in the ledger located at ledger_path, and will only append on newly received
         // entries after being passed to window_service
-        let blocktree = Arc::new(
-            Blocktree::open(ledger_path).expect("Expected to be able to open database ledger"),
-        );
+        let blocktree = match blocktree {
+            Some(blocktree) => blocktree,
+            None => Arc::new(Blocktree::open(&ledger_path).map_err(SetupError::OpenLedger)?),
+        };
+        let storage_backend =
+            storage_backend.unwrap_or_else(|| Arc::new(LocalFileBackend::new(&ledger_path)));
+        // backends that don't keep the segment locally get it encrypted in the ledger directory
+        let staging_path = |object: SegmentObject| {
+            storage_backend
+                .local_path(object)
+                .unwrap_or_else(|| ledger_path.join(object.name()))
+        };
+        let ledger_data_file_encrypted = staging_path(SegmentObject::Segment);
+        let ledger_data_file_tags = staging_path(SegmentObject::Tags);
+        let proof_log_path = proof_log_path.unwrap_or_else(|| ledger_path.join(PROOF_LOG_FILENAME));
+        let proof_log = match ProofLog::open(&proof_log_path) {
+            Ok(proof_log) => Some(Arc::new(proof_log)),
+            Err(err) => {
+                warn!(
+                    "unable to open proof log {:?}, proofs won't be recorded: {:?}",
+                    proof_log_path, err
+                );
+                None
+            }
+        };
 
         let gossip_service = GossipService::new(
             &cluster_info,
@@ -234,25 +1232,48 @@ impl Archiver {
             &exit,
         );
 
-        info!("Connecting to the cluster via {:?}", cluster_entrypoint);
-        let (nodes, _) =
-            match crate::gossip_service::discover_cluster(&cluster_entrypoint.gossip, 1) {
-                Ok(nodes_and_archivers) => nodes_and_archivers,
-                Err(e) => {
-                    //shutdown services before exiting
-                    exit.store(true, Ordering::Relaxed);
-                    gossip_service.join()?;
-                    return Err(Error::from(e));
-                }
-            };
-        let client = crate::gossip_service::get_client(&nodes);
+        let cluster_client = match (cluster_client, direct_connect) {
+            (Some(cluster_client), _) => cluster_client,
+            (None, Some((rpc_addr, tpu_addr))) => {
+                info!(
+                    "Connecting directly to rpc {} and tpu {}",
+                    rpc_addr, tpu_addr
+                );
+                Arc::new(
+                    GossipClusterClient::new_direct(cluster_info.clone(), rpc_addr, tpu_addr)
+                        .storage_turn_source(storage_turn_source),
+                )
+            }
+            (None, None) => {
+                info!("Connecting to the cluster via {:?}", cluster_entrypoint);
+                let (nodes, _) =
+                    match crate::gossip_service::discover_cluster(&cluster_entrypoint.gossip, 1) {
+                        Ok(nodes_and_archivers) => nodes_and_archivers,
+                        Err(e) => {
+                            //shutdown services before exiting
+                            exit.store(true, Ordering::Relaxed);
+                            gossip_service.join()?;
+                            return Err(GossipError::DiscoveryFailed {
+                                entrypoint: cluster_entrypoint.gossip,
+                                reason: e.to_string(),
+                            }
+                            .into());
+                        }
+                    };
+                Arc::new(
+                    GossipClusterClient::new(cluster_info.clone(), nodes)
+                        .storage_turn_source(storage_turn_source),
+                )
+            }
+        };
 
         info!("Setting up mining account...");
-        if let Err(e) = Self::setup_mining_account(
-            &client,
+        if let Err(e) = Archiver::setup_mining_account(
+            cluster_client.as_ref(),
             &keypair,
             &storage_keypair,
             client_commitment.clone(),
+            &storage_funding,
         ) {
             //shutdown services before exiting
             exit.store(true, Ordering::Relaxed);
@@ -278,107 +1299,248 @@ impl Archiver {
             &exit,
         );
         let (slot_sender, slot_receiver) = channel();
-        let request_processor =
-            create_request_processor(node.sockets.storage.unwrap(), &exit, slot_receiver);
+        let status = Arc::new(RwLock::new(ArchiverStatus::default()));
+        let request_processor = create_request_processor(
+            node.sockets.storage.unwrap(),
+            &exit,
+            slot_receiver,
+            status.clone(),
+            blocktree.clone(),
+        );
 
+        let (finished_sender, finished) = channel();
         let t_archiver = {
             let exit = exit.clone();
             let node_info = node.info.clone();
             let mut meta = ArchiverMeta {
-                ledger_path: ledger_path.to_path_buf(),
+                ledger_data_file_encrypted,
+                ledger_data_file_tags,
+                storage_backend: SegmentBackend(storage_backend),
                 client_commitment,
+                storage_funding,
+                redemption_policy,
+                proof_log,
+                state_path: ledger_path.join(ARCHIVER_STATE_FILENAME),
+                status,
+                shred_verifier: if verify_shreds {
+                    Some(SegmentShredSigVerifier::default())
+                } else {
+                    None
+                },
+                segment_download_timeout,
+                fee_payer,
+                event_sender,
+                storage_quota,
+                compute_pool,
+                sample_config,
                 ..ArchiverMeta::default()
             };
-            spawn(move || {
+            thread_registry::spawn("archiver", Some(exit.clone()), move || {
+                let _finished_sender = finished_sender;
+                let resumed = resume
+                    && match Archiver::resume_state(
+                        &mut meta,
+                        cluster_client.as_ref(),
+                        &storage_keypair.pubkey(),
+                    ) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            warn!("unable to resume, replicating a new segment: {}", e);
+                            false
+                        }
+                    };
                 // setup archiver
-                let window_service = match Self::setup(
+                let window_service = match Archiver::setup(
                     &mut meta,
                     cluster_info.clone(),
+                    cluster_client.as_ref(),
                     &blocktree,
                     &exit,
                     &node_info,
                     &storage_keypair,
                     repair_socket,
                     shred_fetch_receiver,
-                    slot_sender,
+                    &slot_sender,
+                    resumed,
                 ) {
                     Ok(window_service) => window_service,
                     Err(e) => {
                         //shutdown services before exiting
                         error!("setup failed {:?}; archiver thread exiting...", e);
+                        meta.send_event(ArchiverEvent::Error(format!("setup failed: {:?}", e)));
                         exit.store(true, Ordering::Relaxed);
-                        request_processor
-                            .into_iter()
-                            .for_each(|t| t.join().unwrap());
-                        fetch_stage.join().unwrap();
-                        gossip_service.join().unwrap();
-                        return;
+                        return join_stages(request_processor, fetch_stage, gossip_service, None);
                     }
                 };
 
                 info!("setup complete");
                 // run archiver
-                Self::run(
+                Archiver::run(
                     &mut meta,
                     &blocktree,
                     cluster_info,
+                    cluster_client.as_ref(),
                     &keypair,
                     &storage_keypair,
+                    &node_info,
+                    &slot_sender,
                     &exit,
+                    resumed,
                 );
                 // wait until exit
-                request_processor
-                    .into_iter()
-                    .for_each(|t| t.join().unwrap());
-                fetch_stage.join().unwrap();
-                gossip_service.join().unwrap();
-                window_service.join().unwrap()
+                join_stages(
+                    request_processor,
+                    fetch_stage,
+                    gossip_service,
+                    Some(window_service),
+                )
             })
+            .unwrap()
         };
 
-        Ok(Self {
-            thread_handles: vec![t_archiver],
+        Ok(Archiver {
+            t_archiver,
+            finished,
             exit,
         })
     }
+}
+
+impl Archiver {
+    /// Returns a Result that contains an archiver on success, see `ArchiverBuilder` to
+    /// replace the cluster client, blocktree or storage backend
+    ///
+    /// # Arguments
+    /// * `ledger_path` - path to where the ledger will be stored.
+    /// Causes panic if none
+    /// * `node` - The archiver node
+    /// * `cluster_entrypoint` - ContactInfo representing an entry into the network
+    /// * `keypair` - Keypair for this archiver
+    /// * `storage_funding` - How the storage account is funded from `keypair`
+    /// * `redemption_policy` - When storage rewards are claimed
+    #[allow(clippy::new_ret_no_self, clippy::too_many_arguments)]
+    pub fn new(
+        ledger_path: &Path,
+        node: Node,
+        cluster_entrypoint: ContactInfo,
+        keypair: Arc<Keypair>,
+        storage_keypair: Arc<Keypair>,
+        client_commitment: CommitmentConfig,
+        storage_funding: StorageFundingConfig,
+        redemption_policy: RedemptionPolicy,
+    ) -> Result<Self> {
+        ArchiverBuilder::new(
+            ledger_path,
+            node,
+            cluster_entrypoint,
+            keypair,
+            storage_keypair,
+        )
+        .client_commitment(client_commitment)
+        .storage_funding(storage_funding)
+        .redemption_policy(redemption_policy)
+        .build()
+    }
+
+    /// Same as `new`, but picks the proofs of a previous run back up if the segment it saved
+    /// under `ledger_path` is still intact, see `ArchiverBuilder::resume`
+    #[allow(clippy::too_many_arguments)]
+    pub fn resume(
+        ledger_path: &Path,
+        node: Node,
+        cluster_entrypoint: ContactInfo,
+        keypair: Arc<Keypair>,
+        storage_keypair: Arc<Keypair>,
+        client_commitment: CommitmentConfig,
+        storage_funding: StorageFundingConfig,
+        redemption_policy: RedemptionPolicy,
+    ) -> Result<Self> {
+        ArchiverBuilder::new(
+            ledger_path,
+            node,
+            cluster_entrypoint,
+            keypair,
+            storage_keypair,
+        )
+        .client_commitment(client_commitment)
+        .storage_funding(storage_funding)
+        .redemption_policy(redemption_policy)
+        .resume()
+    }
 
+    #[allow(clippy::too_many_arguments)]
     fn run(
         meta: &mut ArchiverMeta,
         blocktree: &Arc<Blocktree>,
         cluster_info: Arc<RwLock<ClusterInfo>>,
+        cluster_client: &dyn ArchiverClusterClient,
         archiver_keypair: &Arc<Keypair>,
         storage_keypair: &Arc<Keypair>,
+        node_info: &ContactInfo,
+        slot_sender: &Sender<u64>,
         exit: &Arc<AtomicBool>,
+        resumed: bool,
     ) {
-        // encrypt segment
-        Self::encrypt_ledger(meta, blocktree).expect("ledger encrypt not successful");
-        let enc_file_path = meta.ledger_data_file_encrypted.clone();
+        if !resumed {
+            // encrypt segment
+            Self::encrypt_ledger(meta, blocktree).expect("ledger encrypt not successful");
+            Self::save_state(meta, &storage_keypair.pubkey());
+        } else if meta.segments.last().map(|segment| segment.start_slot) != Some(meta.slot) {
+            Self::record_segment_usage(meta, blocktree);
+        }
+        Self::update_segment_status(meta);
         // do replicate
         loop {
             if exit.load(Ordering::Relaxed) {
                 break;
             }
 
-            // TODO check if more segments are available - based on space constraints
+            if let Err(e) = Self::evict_segments(meta, blocktree, 0, false) {
+                warn!("{}", e);
+            }
             Self::create_sampling_offsets(meta);
-            let sampling_offsets = &meta.sampling_offsets;
-            meta.sha_state =
-                match Self::sample_file_to_create_mining_hash(&enc_file_path, sampling_offsets) {
-                    Ok(hash) => hash,
-                    Err(err) => {
-                        warn!("Error sampling file, exiting: {:?}", err);
-                        break;
-                    }
-                };
+            if let Err(err) = Self::repair_sampled_chunks(meta, blocktree) {
+                warn!("Error repairing the segment, exiting: {:?}", err);
+                meta.send_event(ArchiverEvent::Error(format!(
+                    "unable to repair the segment: {:?}",
+                    err
+                )));
+                break;
+            }
+            meta.sha_state = match Self::sample_file_to_create_mining_hash(meta) {
+                Ok(hash) => hash,
+                Err(err) => {
+                    warn!("Error sampling file, exiting: {:?}", err);
+                    meta.send_event(ArchiverEvent::Error(format!(
+                        "unable to sample the segment: {:?}",
+                        err
+                    )));
+                    break;
+                }
+            };
 
-            Self::submit_mining_proof(meta, &cluster_info, archiver_keypair, storage_keypair);
+            let proofs_submitted = meta.status.read().unwrap().proofs_submitted;
+            if meta.pending_proofs.len() >= MAX_PENDING_PROOFS {
+                warn!("too many pending mining proofs, dropping the oldest");
+                meta.pending_proofs.remove(0);
+            }
+            let proof = PendingProof::new(meta);
+            meta.pending_proofs.push(proof);
+            Self::submit_mining_proof(meta, cluster_client, archiver_keypair, storage_keypair);
+            if meta.status.read().unwrap().proofs_submitted > proofs_submitted {
+                if let Some(segment) = meta.segments.last_mut() {
+                    segment.proven = true;
+                }
+            }
+            Self::save_state(meta, &storage_keypair.pubkey());
 
             // TODO make this a lot more frequent by picking a "new" blockhash instead of picking a storage blockhash
             // prep the next proof
             let (storage_blockhash, _) = match Self::poll_for_blockhash_and_slot(
-                &cluster_info,
+                cluster_client,
                 meta.slots_per_segment,
                 &meta.blockhash,
+                &meta.client_commitment,
                 exit,
             ) {
                 Ok(blockhash_and_slot) => blockhash_and_slot,
@@ -391,111 +1553,295 @@ impl Archiver {
                 }
             };
             meta.blockhash = storage_blockhash;
+            Self::save_state(meta, &storage_keypair.pubkey());
             Self::redeem_rewards(
-                &cluster_info,
+                meta,
+                cluster_client,
                 archiver_keypair,
-                storage_keypair,
-                meta.client_commitment.clone(),
+                &[storage_keypair.pubkey()],
             );
+
+            // The segment size can change across epochs, check for it at every turn
+            match cluster_client.get_segment_config(meta.client_commitment.clone()) {
+                Ok(segment_config)
+                    if segment_config.slots_per_segment != meta.slots_per_segment =>
+                {
+                    if let Err(e) = Self::restart_segment_cycle(
+                        meta,
+                        segment_config,
+                        blocktree,
+                        &cluster_info,
+                        cluster_client,
+                        storage_keypair,
+                        node_info,
+                        slot_sender,
+                        exit,
+                    ) {
+                        warn!("Error restarting the segment cycle, exiting: {:?}", e);
+                        break;
+                    }
+                    Self::save_state(meta, &storage_keypair.pubkey());
+                    Self::update_segment_status(meta);
+                }
+                Ok(segment_config) => meta.segment_epoch = segment_config.epoch,
+                Err(e) => warn!("unable to refresh segment size configuration: {}", e),
+            }
         }
         exit.store(true, Ordering::Relaxed);
     }
 
-    fn redeem_rewards(
+    /// Replicate a new segment after the cluster changed `slots_per_segment`: pick and
+    /// download a segment of the new size, then encrypt it for the following proofs
+    #[allow(clippy::too_many_arguments)]
+    fn restart_segment_cycle(
+        meta: &mut ArchiverMeta,
+        segment_config: RpcSegmentConfig,
+        blocktree: &Arc<Blocktree>,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
-        archiver_keypair: &Arc<Keypair>,
+        cluster_client: &dyn ArchiverClusterClient,
         storage_keypair: &Arc<Keypair>,
-        client_commitment: CommitmentConfig,
+        node_info: &ContactInfo,
+        slot_sender: &Sender<u64>,
+        exit: &Arc<AtomicBool>,
+    ) -> Result<()> {
+        info!(
+            "slots per segment changed from {} (epoch {}) to {} (epoch {}), restarting segment cycle",
+            meta.slots_per_segment,
+            meta.segment_epoch,
+            segment_config.slots_per_segment,
+            segment_config.epoch
+        );
+        datapoint_info!(
+            "archiver-segment_config_changed",
+            ("old_slots_per_segment", meta.slots_per_segment, i64),
+            (
+                "new_slots_per_segment",
+                segment_config.slots_per_segment,
+                i64
+            ),
+            ("epoch", segment_config.epoch, i64)
+        );
+        // the new segment is estimated to take as much space per slot as the current one
+        let required = meta
+            .segments
+            .last()
+            .map(|segment| {
+                segment.bytes() * segment_config.slots_per_segment
+                    / segment.slots_per_segment.max(1)
+            })
+            .unwrap_or(0);
+        Self::evict_segments(meta, blocktree, required, true)?;
+        meta.slots_per_segment = segment_config.slots_per_segment;
+        meta.segment_epoch = segment_config.epoch;
+        Self::select_segment(
+            meta,
+            cluster_client,
+            storage_keypair,
+            &Hash::default(),
+            exit,
+        )?;
+        Self::update_segment_leaders(meta, cluster_client)?;
+        slot_sender.send(meta.slot)?;
+        meta.repair_slot_range
+            .write()
+            .unwrap()
+            .set_range(meta.slot, meta.slot + meta.slots_per_segment);
+        Self::download_segment(
+            meta,
+            blocktree,
+            cluster_info,
+            cluster_client,
+            storage_keypair,
+            node_info,
+            slot_sender,
+            exit,
+        )?;
+        Self::encrypt_ledger(meta, blocktree)
+    }
+
+    /// Claim the rewards of every storage account in `storage_pubkeys` that satisfies the
+    /// redemption policy, batching the claims into a single transaction
+    fn redeem_rewards(
+        meta: &mut ArchiverMeta,
+        cluster_client: &dyn ArchiverClusterClient,
+        archiver_keypair: &Arc<Keypair>,
+        storage_pubkeys: &[Pubkey],
     ) {
-        let nodes = cluster_info.read().unwrap().tvu_peers();
-        let client = crate::gossip_service::get_client(&nodes);
+        let policy = &meta.redemption_policy;
+        if let Some(last_redemption) = meta.last_redemption {
+            if last_redemption.elapsed() < policy.min_interval {
+                return;
+            }
+        }
 
-        if let Ok(Some(account)) =
-            client.get_account_with_commitment(&storage_keypair.pubkey(), client_commitment.clone())
-        {
-            if let Ok(StorageContract::ArchiverStorage { validations, .. }) = account.state() {
-                if !validations.is_empty() {
-                    let ix = storage_instruction::claim_reward(
-                        &archiver_keypair.pubkey(),
-                        &storage_keypair.pubkey(),
-                    );
-                    let message =
-                        Message::new_with_payer(vec![ix], Some(&archiver_keypair.pubkey()));
-                    if let Err(e) = client.send_message(&[&archiver_keypair], message) {
-                        error!("unable to redeem reward, tx failed: {:?}", e);
-                    } else {
-                        info!(
-                            "collected mining rewards: Account balance {:?}",
-                            client.get_balance_with_commitment(
-                                &archiver_keypair.pubkey(),
-                                client_commitment.clone()
-                            )
-                        );
+        let client_commitment = meta.client_commitment.clone();
+
+        // the point value is also needed to record the rewards of claimed proofs
+        let storage_point_value =
+            match cluster_client.get_account(&rewards::id(), client_commitment.clone()) {
+                Ok(Some(account)) => Rewards::from_account(&account)
+                    .map(|rewards| rewards.storage_point_value)
+                    .unwrap_or(0.0),
+                _ if policy.min_reward_lamports > 0 => {
+                    info!("Redeem mining reward: No rewards sysvar found");
+                    return;
+                }
+                _ => 0.0,
+            };
+
+        let logged_validations = &mut meta.logged_validations;
+        let mut validated_proofs = vec![];
+        let mut claimed_proofs = 0;
+        let mut claimed_reward = 0;
+        let instructions: Vec<_> = storage_pubkeys
+            .iter()
+            .filter(|storage_pubkey| {
+                match cluster_client.get_account(storage_pubkey, client_commitment.clone()) {
+                    Ok(Some(account)) => match account.state() {
+                        Ok(StorageContract::ArchiverStorage {
+                            validations,
+                            credits,
+                            ..
+                        }) => {
+                            let valid_proofs = count_valid_proofs(&validations);
+                            let credits = credits.redeemable + credits.current_epoch + valid_proofs;
+                            let estimated_reward = (credits as f64 * storage_point_value) as u64;
+                            // validations are recorded whether or not their reward is claimed
+                            validated_proofs.extend(validated_proof_entries(
+                                &validations,
+                                storage_point_value,
+                                logged_validations,
+                            ));
+                            let should_redeem =
+                                policy.should_redeem(valid_proofs, estimated_reward);
+                            if should_redeem {
+                                claimed_proofs += valid_proofs;
+                                claimed_reward += estimated_reward;
+                            }
+                            should_redeem
+                        }
+                        _ => false,
+                    },
+                    _ => {
+                        info!("Redeem mining reward: No account data found");
+                        false
                     }
                 }
-            }
+            })
+            .map(|storage_pubkey| {
+                storage_instruction::claim_reward(&archiver_keypair.pubkey(), storage_pubkey)
+            })
+            .collect();
+        for entry in &validated_proofs {
+            Self::log_proof(meta, entry);
+        }
+        if instructions.is_empty() {
+            return;
+        }
+
+        meta.last_redemption = Some(Instant::now());
+        let num_claims = instructions.len();
+        let message = Message::new_with_payer(instructions, Some(&archiver_keypair.pubkey()));
+        if let Err(e) = cluster_client.send_message(&[&archiver_keypair], message) {
+            error!("unable to redeem reward, tx failed: {:?}", e);
+            datapoint_error!(
+                "archiver-rewards_redeem_failed",
+                ("storage_accounts", num_claims, i64),
+                ("error", e.to_string(), String)
+            );
+            meta.send_event(ArchiverEvent::Error(format!(
+                "unable to redeem rewards: {}",
+                e
+            )));
         } else {
-            info!("Redeem mining reward: No account data found");
+            meta.status.write().unwrap().rewards_claimed += claimed_reward;
+            datapoint_info!(
+                "archiver-rewards_redeemed",
+                ("storage_accounts", num_claims, i64),
+                ("proofs", claimed_proofs, i64),
+                ("estimated_lamports", claimed_reward, i64)
+            );
+            meta.send_event(ArchiverEvent::RewardClaimed {
+                storage_accounts: num_claims,
+                estimated_lamports: claimed_reward,
+            });
+            info!(
+                "collected mining rewards from {} storage accounts: Account balance {:?}",
+                num_claims,
+                cluster_client.get_balance(&archiver_keypair.pubkey(), client_commitment)
+            );
         }
     }
 
     // Find a segment to replicate and download it.
+    #[allow(clippy::too_many_arguments)]
     fn setup(
         meta: &mut ArchiverMeta,
         cluster_info: Arc<RwLock<ClusterInfo>>,
+        cluster_client: &dyn ArchiverClusterClient,
         blocktree: &Arc<Blocktree>,
         exit: &Arc<AtomicBool>,
         node_info: &ContactInfo,
         storage_keypair: &Arc<Keypair>,
         repair_socket: Arc<UdpSocket>,
         shred_fetch_receiver: PacketReceiver,
-        slot_sender: Sender<u64>,
+        slot_sender: &Sender<u64>,
+        resumed: bool,
     ) -> Result<(WindowService)> {
-        let slots_per_segment =
-            match Self::get_segment_config(&cluster_info, meta.client_commitment.clone()) {
-                Ok(slots_per_segment) => slots_per_segment,
-                Err(e) => {
-                    error!("unable to get segment size configuration, exiting...");
-                    //shutdown services before exiting
-                    exit.store(true, Ordering::Relaxed);
-                    return Err(e);
-                }
-            };
-        let (segment_blockhash, segment_slot) = match Self::poll_for_segment(
-            &cluster_info,
-            slots_per_segment,
-            &Hash::default(),
-            exit,
-        ) {
-            Ok(blockhash_and_slot) => blockhash_and_slot,
-            Err(e) => {
+        if !resumed {
+            let segment_config =
+                match cluster_client.get_segment_config(meta.client_commitment.clone()) {
+                    Ok(segment_config) => segment_config,
+                    Err(e) => {
+                        error!("unable to get segment size configuration, exiting...");
+                        //shutdown services before exiting
+                        exit.store(true, Ordering::Relaxed);
+                        return Err(e);
+                    }
+                };
+            meta.slots_per_segment = segment_config.slots_per_segment;
+            meta.segment_epoch = segment_config.epoch;
+            if let Err(e) = Self::select_segment(
+                meta,
+                cluster_client,
+                storage_keypair,
+                &Hash::default(),
+                exit,
+            ) {
                 //shutdown services before exiting
                 exit.store(true, Ordering::Relaxed);
                 return Err(e);
             }
-        };
-        let signature = storage_keypair.sign(segment_blockhash.as_ref());
-        let slot = get_slot_from_signature(&signature, segment_slot, slots_per_segment);
-        info!("replicating slot: {}", slot);
-        slot_sender.send(slot)?;
-        meta.slot = slot;
-        meta.slots_per_segment = slots_per_segment;
-        meta.signature = Signature::new(&signature.to_bytes());
-        meta.blockhash = segment_blockhash;
+        }
+        if let Err(e) = Self::update_segment_leaders(meta, cluster_client) {
+            error!("unable to get the leaders of the segment, exiting...");
+            //shutdown services before exiting
+            exit.store(true, Ordering::Relaxed);
+            return Err(e);
+        }
+        slot_sender.send(meta.slot)?;
 
-        let mut repair_slot_range = RepairSlotRange::default();
-        repair_slot_range.end = slot + slots_per_segment;
-        repair_slot_range.start = slot;
+        meta.repair_slot_range
+            .write()
+            .unwrap()
+            .set_range(meta.slot, meta.slot + meta.slots_per_segment);
 
         let (retransmit_sender, _) = channel();
 
-        let (verified_sender, verified_receiver) = unbounded();
-
-        let _sigverify_stage = SigVerifyStage::new(
-            shred_fetch_receiver,
-            verified_sender.clone(),
-            DisabledSigVerifier::default(),
-        );
+        let (verified_sender, verified_receiver) = window_channel();
+
+        let _sigverify_stage = match &meta.shred_verifier {
+            Some(shred_verifier) => SigVerifyStage::new(
+                shred_fetch_receiver,
+                verified_sender.clone(),
+                shred_verifier.clone(),
+            ),
+            None => SigVerifyStage::new(
+                shred_fetch_receiver,
+                verified_sender.clone(),
+                DisabledSigVerifier::default(),
+            ),
+        };
 
         let window_service = WindowService::new(
             blocktree.clone(),
@@ -504,277 +1850,832 @@ impl Archiver {
             retransmit_sender,
             repair_socket,
             &exit,
-            RepairStrategy::RepairRange(repair_slot_range),
+            RepairStrategy::RepairRange(meta.repair_slot_range.clone()),
             &Arc::new(LeaderScheduleCache::default()),
+            DEFAULT_MAX_FUTURE_SHREDS,
             |_, _, _, _| true,
         );
-        info!("waiting for ledger download");
-        Self::wait_for_segment_download(
-            slot,
-            slots_per_segment,
-            &blocktree,
-            &exit,
-            &node_info,
-            cluster_info,
-        );
+        if !resumed {
+            info!("waiting for ledger download");
+            if let Err(e) = Self::download_segment(
+                meta,
+                blocktree,
+                &cluster_info,
+                cluster_client,
+                storage_keypair,
+                node_info,
+                slot_sender,
+                exit,
+            ) {
+                error!("unable to select another segment, exiting...");
+                //shutdown services before exiting
+                exit.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
         Ok(window_service)
     }
 
-    fn wait_for_segment_download(
+    /// Point the shred verifier, if any, at the leaders of the segment starting at `meta.slot`
+    fn update_segment_leaders(
+        meta: &ArchiverMeta,
+        cluster_client: &dyn ArchiverClusterClient,
+    ) -> Result<()> {
+        if let Some(shred_verifier) = &meta.shred_verifier {
+            let leaders =
+                cluster_client.get_segment_leaders(meta.slot, meta.client_commitment.clone())?;
+            shred_verifier.set_segment_leaders(meta.slot, &leaders);
+        }
+        Ok(())
+    }
+
+    /// Restore the state saved by a previous run into `meta` if it still matches the storage
+    /// account, the cluster's segment size and the encrypted segment on disk
+    fn resume_state(
+        meta: &mut ArchiverMeta,
+        cluster_client: &dyn ArchiverClusterClient,
+        storage_pubkey: &Pubkey,
+    ) -> Result<()> {
+        let state = ArchiverState::load(&meta.state_path)?;
+        let segment_config = cluster_client.get_segment_config(meta.client_commitment.clone())?;
+        state.verify(
+            storage_pubkey,
+            segment_config.slots_per_segment,
+            meta.storage_backend.0.as_ref(),
+        )?;
+        info!(
+            "resuming proofs of slot {} with blockhash {}",
+            state.slot, state.blockhash
+        );
+        meta.slot = state.slot;
+        meta.slots_per_segment = state.slots_per_segment;
+        meta.segment_epoch = segment_config.epoch;
+        meta.signature = state.signature;
+        meta.blockhash = state.blockhash;
+        meta.sampling_offsets = state.sampling_offsets;
+        meta.sha_state = state.sha_state;
+        meta.num_chacha_blocks = state.num_chacha_blocks;
+        meta.segments = state.segments;
+        Ok(())
+    }
+
+    fn update_segment_status(meta: &ArchiverMeta) {
+        let mut status = meta.status.write().unwrap();
+        status.segment_start_slot = meta.slot;
+        status.slots_per_segment = meta.slots_per_segment;
+        status.encrypted_file_size = (meta.num_chacha_blocks * CHACHA_BLOCK_SIZE) as u64;
+    }
+
+    fn save_state(meta: &ArchiverMeta, storage_pubkey: &Pubkey) {
+        if let Err(e) = ArchiverState::new(meta, storage_pubkey).save(&meta.state_path) {
+            warn!(
+                "unable to save archiver state to {:?}: {:?}",
+                meta.state_path, e
+            );
+        }
+    }
+
+    // Wait for a complete segment of `meta.slots_per_segment` slots in a turn other than
+    // `previous_blockhash` and pick the slot to replicate from the storage signature of its
+    // turn blockhash
+    fn select_segment(
+        meta: &mut ArchiverMeta,
+        cluster_client: &dyn ArchiverClusterClient,
+        storage_keypair: &Arc<Keypair>,
+        previous_blockhash: &Hash,
+        exit: &Arc<AtomicBool>,
+    ) -> Result<()> {
+        let (segment_blockhash, segment_slot) = Self::poll_for_segment(
+            cluster_client,
+            meta.slots_per_segment,
+            previous_blockhash,
+            &meta.client_commitment,
+            exit,
+        )?;
+        let signature = storage_keypair.sign(segment_blockhash.as_ref());
+        let slot = get_slot_from_signature(&signature, segment_slot, meta.slots_per_segment);
+        info!("replicating slot: {}", slot);
+        datapoint_info!(
+            "archiver-segment_selected",
+            ("slot", slot, i64),
+            ("slots_per_segment", meta.slots_per_segment, i64),
+            ("turn_slot", segment_slot, i64)
+        );
+        meta.send_event(ArchiverEvent::SegmentSelected {
+            slot,
+            slots_per_segment: meta.slots_per_segment,
+        });
+        meta.slot = slot;
+        meta.signature = Signature::new(&signature.to_bytes());
+        meta.blockhash = segment_blockhash;
+        Ok(())
+    }
+
+    /// Wait for the window service to download the segment at `meta.slot`. Whenever the
+    /// download stalls, poll for the next storage turn, derive a segment from its blockhash and
+    /// point the window service's repair at it instead
+    #[allow(clippy::too_many_arguments)]
+    fn download_segment(
+        meta: &mut ArchiverMeta,
+        blocktree: &Arc<Blocktree>,
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        cluster_client: &dyn ArchiverClusterClient,
+        storage_keypair: &Arc<Keypair>,
+        node_info: &ContactInfo,
+        slot_sender: &Sender<u64>,
+        exit: &Arc<AtomicBool>,
+    ) -> Result<()> {
+        let mut download_time = Measure::start("archiver-segment_download");
+        let mut stalls = 0;
+        while !Self::wait_for_segment_download(
+            meta.slot,
+            meta.slots_per_segment,
+            blocktree,
+            meta.segment_download_timeout,
+            exit,
+            |slots_downloaded| {
+                meta.send_event(ArchiverEvent::DownloadProgress {
+                    slot: meta.slot,
+                    slots_downloaded,
+                    slots_per_segment: meta.slots_per_segment,
+                })
+            },
+        ) {
+            stalls += 1;
+            let stalled_slot = meta.slot;
+            let previous_blockhash = meta.blockhash;
+            Self::select_segment(
+                meta,
+                cluster_client,
+                storage_keypair,
+                &previous_blockhash,
+                exit,
+            )?;
+            if meta.slot == stalled_slot {
+                info!("the next turn selected the same segment, retrying it");
+            }
+            datapoint_info!(
+                "archiver-segment_download_stalled",
+                ("stalled_slot", stalled_slot, i64),
+                ("slot", meta.slot, i64)
+            );
+            Self::update_segment_leaders(meta, cluster_client)?;
+            slot_sender.send(meta.slot)?;
+            meta.repair_slot_range
+                .write()
+                .unwrap()
+                .set_range(meta.slot, meta.slot + meta.slots_per_segment);
+        }
+        download_time.stop();
+        if !exit.load(Ordering::Relaxed) {
+            datapoint_info!(
+                "archiver-segment_downloaded",
+                ("slot", meta.slot, i64),
+                ("slots_per_segment", meta.slots_per_segment, i64),
+                ("duration_ms", download_time.as_ms(), i64),
+                ("stalls", stalls, i64)
+            );
+        }
+        Self::leave_data_plane(node_info, cluster_info);
+        Ok(())
+    }
+
+    /// Wait until every slot of the segment starting at `start_slot` is full, returns false
+    /// if the download stalled: no further slot completed within `timeout`. `on_progress` is
+    /// called with the number of full slots whenever another one completes. Progress is kept in
+    /// the segment's download manifest in `blocktree`, so after a restart the slots that were
+    /// already complete are skipped
+    fn wait_for_segment_download<F: FnMut(u64)>(
         start_slot: Slot,
         slots_per_segment: u64,
-        blocktree: &Arc<Blocktree>,
+        blocktree: &Blocktree,
+        timeout: Duration,
         exit: &Arc<AtomicBool>,
-        node_info: &ContactInfo,
-        cluster_info: Arc<RwLock<ClusterInfo>>,
-    ) {
+        mut on_progress: F,
+    ) -> bool {
         info!(
             "window created, waiting for ledger download starting at slot {:?}",
             start_slot
         );
-        let mut current_slot = start_slot;
-        'outer: loop {
-            while blocktree.is_full(current_slot) {
-                current_slot += 1;
-                info!("current slot: {}", current_slot);
-                if current_slot >= start_slot + slots_per_segment {
-                    break 'outer;
+        let mut manifest = match blocktree.download_manifest(start_slot) {
+            Ok(Some(manifest)) if manifest.slots_per_segment() == slots_per_segment => {
+                info!(
+                    "resuming download of slot {}, {} slots already complete",
+                    start_slot,
+                    manifest.num_complete()
+                );
+                manifest
+            }
+            _ => DownloadManifest::new(start_slot, slots_per_segment),
+        };
+        Self::save_download_manifest(blocktree, &manifest);
+        let mut last_progress = Instant::now();
+        loop {
+            let mut progressed = false;
+            for slot in start_slot..manifest.end_slot() {
+                if !manifest.is_slot_complete(slot) && blocktree.is_full(slot) {
+                    manifest.set_slot_complete(slot);
+                    progressed = true;
+                    on_progress(manifest.num_complete());
+                    info!("completed slot: {}", slot);
                 }
             }
+            if progressed {
+                last_progress = Instant::now();
+                Self::save_download_manifest(blocktree, &manifest);
+            }
+            if manifest.is_complete() {
+                info!("Done receiving entries from window_service");
+                return true;
+            }
             if exit.load(Ordering::Relaxed) {
-                break;
+                return true;
+            }
+            if last_progress.elapsed() >= timeout {
+                warn!(
+                    "segment download stalled at slot {:?}, nothing completed in {:?}",
+                    manifest.first_incomplete_slot(),
+                    timeout
+                );
+                return false;
             }
             sleep(Duration::from_secs(1));
         }
+    }
 
-        info!("Done receiving entries from window_service");
+    // the manifest only saves work on a restart, the download goes on without it
+    fn save_download_manifest(blocktree: &Blocktree, manifest: &DownloadManifest) {
+        if let Err(e) = blocktree.put_download_manifest(manifest) {
+            warn!(
+                "unable to save the download manifest of slot {}: {:?}",
+                manifest.start_slot, e
+            );
+        }
+    }
 
+    fn leave_data_plane(node_info: &ContactInfo, cluster_info: &Arc<RwLock<ClusterInfo>>) {
         // Remove archiver from the data plane
         let mut contact_info = node_info.clone();
-        contact_info.tvu = "0.0.0.0:0".parse().unwrap();
+        contact_info.tvu = ContactInfo::unspecified_addr();
         contact_info.wallclock = timestamp();
         {
             let mut cluster_info_w = cluster_info.write().unwrap();
             cluster_info_w.insert_self(contact_info);
+            cluster_info_w.push_archiver_info(1);
         }
     }
 
     fn encrypt_ledger(meta: &mut ArchiverMeta, blocktree: &Arc<Blocktree>) -> Result<()> {
-        meta.ledger_data_file_encrypted = meta.ledger_path.join(ENCRYPTED_FILENAME);
-
+        let mut encrypt_time = Measure::start("archiver-encrypt_segment");
         {
             let mut ivec = [0u8; 64];
             ivec.copy_from_slice(&meta.signature.as_ref());
 
-            let num_encrypted_bytes = chacha_cbc_encrypt_ledger(
-                blocktree,
-                meta.slot,
-                meta.slots_per_segment,
+            let (slot, slots_per_segment) = (meta.slot, meta.slots_per_segment);
+            let (encrypted_path, tags_path) = (
                 &meta.ledger_data_file_encrypted,
-                &mut ivec,
-            )?;
+                &meta.ledger_data_file_tags,
+            );
+            let num_encrypted_bytes = meta.compute_pool.0.install(|| {
+                let num_encrypted_bytes = chacha_cbc_encrypt_ledger(
+                    blocktree,
+                    slot,
+                    slots_per_segment,
+                    CHACHA_CHUNK_SLOTS,
+                    encrypted_path,
+                    &ivec,
+                )?;
+                chacha_tag_file(encrypted_path, tags_path)?;
+                Ok::<_, io::Error>(num_encrypted_bytes)
+            })?;
 
             meta.num_chacha_blocks = num_encrypted_bytes / CHACHA_BLOCK_SIZE;
         }
+        encrypt_time.stop();
 
         info!(
             "Done encrypting the ledger: {:?}",
             meta.ledger_data_file_encrypted
         );
+        let encrypted_bytes = meta.num_chacha_blocks * CHACHA_BLOCK_SIZE;
+        datapoint_info!(
+            "archiver-segment_encrypted",
+            ("slot", meta.slot, i64),
+            ("bytes", encrypted_bytes, i64),
+            ("duration_ms", encrypt_time.as_ms(), i64),
+            (
+                "bytes_per_sec",
+                bytes_per_sec(encrypted_bytes, encrypt_time.as_ms()),
+                f64
+            )
+        );
+        meta.send_event(ArchiverEvent::EncryptionDone {
+            slot: meta.slot,
+            bytes: encrypted_bytes as u64,
+        });
+        Self::store_segment(meta)?;
+        Self::record_segment_usage(meta, blocktree);
         Ok(())
     }
 
-    fn create_sampling_offsets(meta: &mut ArchiverMeta) {
-        meta.sampling_offsets.clear();
-        let mut rng_seed = [0u8; 32];
-        rng_seed.copy_from_slice(&meta.blockhash.as_ref());
-        let mut rng = ChaChaRng::from_seed(rng_seed);
-        for _ in 0..NUM_STORAGE_SAMPLES {
-            meta.sampling_offsets
-                .push(rng.gen_range(0, meta.num_chacha_blocks) as u64);
-        }
-    }
-
-    fn sample_file_to_create_mining_hash(
-        enc_file_path: &Path,
-        sampling_offsets: &[u64],
-    ) -> Result<(Hash)> {
-        let sha_state = sample_file(enc_file_path, sampling_offsets)?;
-        info!("sampled sha_state: {}", sha_state);
-        Ok(sha_state)
+    // Account for the segment at `meta.slot` as the one being proven, the encrypted copies of
+    // the previous segments were overwritten by its own
+    fn record_segment_usage(meta: &mut ArchiverMeta, blocktree: &Blocktree) {
+        let storage_backend = &meta.storage_backend.0;
+        let encrypted_bytes = [SegmentObject::Segment, SegmentObject::Tags]
+            .iter()
+            .map(|object| storage_backend.size(*object).unwrap_or(0))
+            .sum();
+        let usage = SegmentUsage {
+            start_slot: meta.slot,
+            slots_per_segment: meta.slots_per_segment,
+            blocktree_bytes: segment_blocktree_bytes(blocktree, meta.slot, meta.slots_per_segment),
+            encrypted_bytes,
+            proven: false,
+        };
+        meta.segments
+            .retain(|segment| segment.start_slot != usage.start_slot);
+        meta.segments
+            .iter_mut()
+            .for_each(|segment| segment.encrypted_bytes = 0);
+        datapoint_info!(
+            "archiver-storage_usage",
+            ("segments", meta.segments.len() + 1, i64),
+            (
+                "bytes",
+                meta.segments.iter().map(SegmentUsage::bytes).sum::<u64>() + usage.bytes(),
+                i64
+            ),
+            ("quota", meta.storage_quota.unwrap_or(0), i64)
+        );
+        meta.segments.push(usage);
     }
 
-    fn setup_mining_account(
-        client: &ThinClient,
-        keypair: &Keypair,
-        storage_keypair: &Keypair,
-        client_commitment: CommitmentConfig,
+    /// Evict the oldest proven segments, purging their slots from the blocktree, until
+    /// `required` more bytes fit within the storage quota. See `segments_to_evict()`.
+    fn evict_segments(
+        meta: &mut ArchiverMeta,
+        blocktree: &Blocktree,
+        required: u64,
+        replacing_current: bool,
     ) -> Result<()> {
-        // make sure archiver has some balance
-        info!("checking archiver keypair...");
-        if client.poll_balance_with_timeout_and_commitment(
-            &keypair.pubkey(),
-            &Duration::from_millis(100),
-            &Duration::from_secs(5),
-            client_commitment.clone(),
-        )? == 0
-        {
-            return Err(
-                io::Error::new(io::ErrorKind::Other, "keypair account has no balance").into(),
+        let quota = match meta.storage_quota {
+            Some(quota) => quota,
+            None => return Ok(()),
+        };
+        let evicted = segments_to_evict(&meta.segments, quota, required, replacing_current)
+            .ok_or_else(|| StorageError::QuotaExceeded {
+                quota,
+                used: meta.segments.iter().map(SegmentUsage::bytes).sum(),
+                required,
+            })?;
+        for i in evicted.into_iter().rev() {
+            let segment = meta.segments.remove(i);
+            info!(
+                "evicting segment at slot {}, freeing {} bytes",
+                segment.start_slot,
+                segment.bytes()
+            );
+            blocktree.purge_slots(
+                segment.start_slot,
+                Some(segment.start_slot + segment.slots_per_segment - 1),
+            );
+            datapoint_info!(
+                "archiver-segment_evicted",
+                ("slot", segment.start_slot, i64),
+                ("bytes", segment.bytes(), i64)
             );
         }
+        Ok(())
+    }
 
-        info!("checking storage account keypair...");
-        // check if the storage account exists
-        let balance = client
-            .poll_get_balance_with_commitment(&storage_keypair.pubkey(), client_commitment.clone());
-        if balance.is_err() || balance.unwrap() == 0 {
-            let blockhash =
-                match client.get_recent_blockhash_with_commitment(client_commitment.clone()) {
-                    Ok((blockhash, _)) => blockhash,
-                    Err(_) => {
-                        return Err(Error::IO(<io::Error>::new(
-                            io::ErrorKind::Other,
-                            "unable to get recent blockhash, can't submit proof",
-                        )));
-                    }
-                };
+    // Hand the encrypted segment to the storage backend, staging files the backend doesn't
+    // keep in place are removed once stored
+    fn store_segment(meta: &ArchiverMeta) -> Result<()> {
+        let storage_backend = &meta.storage_backend.0;
+        let objects = [
+            (SegmentObject::Segment, &meta.ledger_data_file_encrypted),
+            (SegmentObject::Tags, &meta.ledger_data_file_tags),
+        ];
+        for (object, path) in &objects {
+            storage_backend.store(*object, path)?;
+            if storage_backend.local_path(*object).as_ref() != Some(*path) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn create_sampling_offsets(meta: &mut ArchiverMeta) {
+        meta.sampling_offsets = sampling_offsets(&meta.blockhash, meta.num_chacha_blocks);
+    }
+
+    // Check the chunks about to be sampled against their tags, re-encrypting the CBC streams
+    // of the corrupted ones. A segment kept outside the local filesystem can't be patched and
+    // is encrypted again whole.
+    fn repair_sampled_chunks(meta: &mut ArchiverMeta, blocktree: &Arc<Blocktree>) -> Result<()> {
+        let storage_backend = meta.storage_backend.0.clone();
+        let chunk_indexes = sampled_chunk_indexes(&meta.sampling_offsets);
+        let corrupted = meta.compute_pool.0.install(|| {
+            chacha_corrupted_chunks(
+                &mut ObjectReader::new(storage_backend.as_ref(), SegmentObject::Segment)?,
+                &mut ObjectReader::new(storage_backend.as_ref(), SegmentObject::Tags)?,
+                &chunk_indexes,
+            )
+        })?;
+        if corrupted.is_empty() {
+            return Ok(());
+        }
+        warn!(
+            "{} corrupted chunks in the segment at slot {}: {:?}",
+            corrupted.len(),
+            meta.slot,
+            corrupted
+        );
+
+        let in_place = storage_backend.local_path(SegmentObject::Segment).as_ref()
+            == Some(&meta.ledger_data_file_encrypted);
+        datapoint_info!(
+            "archiver-segment_repaired",
+            ("slot", meta.slot, i64),
+            ("chunks", corrupted.len(), i64),
+            ("in_place", in_place, bool)
+        );
+        if !in_place {
+            return Self::encrypt_ledger(meta, blocktree);
+        }
+        let mut ivec = [0u8; CHACHA_BLOCK_SIZE];
+        ivec.copy_from_slice(&meta.signature.as_ref());
+        let (slot, slots_per_segment) = (meta.slot, meta.slots_per_segment);
+        let encrypted_path = &meta.ledger_data_file_encrypted;
+        let tags_path = &meta.ledger_data_file_tags;
+        let streams = meta.compute_pool.0.install(|| {
+            let streams = chacha_reencrypt_chunks(
+                blocktree,
+                slot,
+                slots_per_segment,
+                CHACHA_CHUNK_SLOTS,
+                &ivec,
+                &corrupted,
+                &mut OpenOptions::new().write(true).open(encrypted_path)?,
+            )?;
+            chacha_tag_file(encrypted_path, tags_path)?;
+            Ok::<_, io::Error>(streams)
+        })?;
+        info!("re-encrypted streams {:?} of the segment", streams);
+        Self::store_segment(meta)
+    }
+
+    fn sample_file_to_create_mining_hash(meta: &ArchiverMeta) -> Result<(Hash)> {
+        let mut sample_time = Measure::start("archiver-sample_segment");
+        // catch a corrupted segment here rather than with a failed proof
+        let chunk_indexes = sampled_chunk_indexes(&meta.sampling_offsets);
+        let storage_backend = meta.storage_backend.0.as_ref();
+        let sampling_offsets = &meta.sampling_offsets;
+        let sample_config = meta.sample_config;
+        let sha_state = meta.compute_pool.0.install(|| {
+            verify_segment_tags(storage_backend, &chunk_indexes)?;
+            sample_segment(storage_backend, sampling_offsets, sample_config)
+        })?;
+        sample_time.stop();
+        info!("sampled sha_state: {}", sha_state);
+        datapoint_info!(
+            "archiver-segment_sampled",
+            ("slot", meta.slot, i64),
+            ("samples", meta.sampling_offsets.len(), i64),
+            ("duration_ms", sample_time.as_ms(), i64)
+        );
+        Ok(sha_state)
+    }
+
+    fn setup_mining_account(
+        client: &dyn ArchiverClusterClient,
+        keypair: &Keypair,
+        storage_keypair: &Keypair,
+        client_commitment: CommitmentConfig,
+        storage_funding: &StorageFundingConfig,
+    ) -> Result<()> {
+        // make sure archiver has some balance
+        info!("checking archiver keypair...");
+        let archiver_balance = client.poll_balance(
+            &keypair.pubkey(),
+            Duration::from_secs(5),
+            client_commitment.clone(),
+        )?;
+        if archiver_balance == 0 {
+            return Err(StorageError::AccountNotFunded(keypair.pubkey()).into());
+        }
+
+        info!("checking storage account keypair...");
+        // check if the storage account exists
+        let balance = client.poll_balance(
+            &storage_keypair.pubkey(),
+            Duration::from_secs(1),
+            client_commitment.clone(),
+        );
+        if balance.is_err() || balance.unwrap() == 0 {
+            if archiver_balance < storage_funding.initial_lamports {
+                return Err(StorageError::AccountNotFunded(keypair.pubkey()).into());
+            }
+            let blockhash = match client.get_recent_blockhash(client_commitment.clone()) {
+                Ok((blockhash, _)) => blockhash,
+                Err(err) => {
+                    return Err(RpcError::RequestFailed {
+                        request: format!("{:?}", RpcRequest::GetRecentBlockhash),
+                        reason: format!("{:?}", err),
+                    }
+                    .into());
+                }
+            };
 
             let ix = storage_instruction::create_storage_account(
                 &keypair.pubkey(),
                 &keypair.pubkey(),
                 &storage_keypair.pubkey(),
-                1,
+                storage_funding.initial_lamports,
                 StorageAccountType::Archiver,
             );
             let tx = Transaction::new_signed_instructions(&[keypair], ix, blockhash);
             let signature = client.async_send_transaction(tx)?;
             client
-                .poll_for_signature_with_commitment(&signature, client_commitment.clone())
-                .map_err(|err| match err {
-                    TransportError::IoError(e) => e,
-                    TransportError::TransactionError(_) => io::Error::new(
-                        ErrorKind::Other,
-                        "setup_mining_account: signature not found",
-                    ),
+                .poll_for_signature(&signature, client_commitment.clone())
+                .map_err(|err| StorageError::AccountCreationFailed {
+                    account: storage_keypair.pubkey(),
+                    reason: match err {
+                        TransportError::IoError(e) => e.to_string(),
+                        TransportError::TransactionError(e) => format!("{:?}", e),
+                    },
                 })?;
         }
         Ok(())
     }
 
+    /// Submit every pending proof, batching as many mining proof instructions into each
+    /// transaction as fit in a packet. Confirmed proofs and those that failed
+    /// `MAX_PROOF_SUBMIT_ATTEMPTS` times are no longer pending.
     fn submit_mining_proof(
-        meta: &ArchiverMeta,
-        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        meta: &mut ArchiverMeta,
+        client: &dyn ArchiverClusterClient,
         archiver_keypair: &Arc<Keypair>,
         storage_keypair: &Arc<Keypair>,
     ) {
         // No point if we've got no storage account...
-        let nodes = cluster_info.read().unwrap().tvu_peers();
-        let client = crate::gossip_service::get_client(&nodes);
-        let storage_balance = client.poll_get_balance_with_commitment(
+        let storage_balance = client.poll_balance(
             &storage_keypair.pubkey(),
+            Duration::from_secs(1),
             meta.client_commitment.clone(),
         );
-        if storage_balance.is_err() || storage_balance.unwrap() == 0 {
-            error!("Unable to submit mining proof, no storage account");
-            return;
-        }
+        let storage_balance = match storage_balance {
+            Ok(balance) if balance > 0 => balance,
+            _ => {
+                Self::funding_alert(&storage_keypair.pubkey(), 0, "no storage account");
+                return;
+            }
+        };
         // ...or no lamports for fees
-        let balance = client.poll_get_balance_with_commitment(
-            &archiver_keypair.pubkey(),
+        let fee_payer = meta.fee_payer.as_ref().unwrap_or(archiver_keypair);
+        let balance = client.poll_balance(
+            &fee_payer.pubkey(),
+            Duration::from_secs(1),
             meta.client_commitment.clone(),
         );
-        if balance.is_err() || balance.unwrap() == 0 {
-            error!("Unable to submit mining proof, insufficient Archiver Account balance");
-            return;
-        }
+        let mut balance = match balance {
+            Ok(balance) if balance > 0 => balance,
+            _ => {
+                Self::funding_alert(&fee_payer.pubkey(), 0, "no lamports for fees");
+                return;
+            }
+        };
 
-        let blockhash =
-            match client.get_recent_blockhash_with_commitment(meta.client_commitment.clone()) {
-                Ok((blockhash, _)) => blockhash,
+        let (blockhash, fee_calculator) =
+            match client.get_recent_blockhash(meta.client_commitment.clone()) {
+                Ok(blockhash_and_fees) => blockhash_and_fees,
                 Err(_) => {
                     error!("unable to get recent blockhash, can't submit proof");
                     return;
                 }
             };
-        let instruction = storage_instruction::mining_proof(
+
+        if let Some(top_up_threshold) = meta.storage_funding.top_up_threshold {
+            if storage_balance < top_up_threshold {
+                let target = meta.storage_funding.initial_lamports.max(top_up_threshold);
+                balance = Self::top_up_storage_account(
+                    client,
+                    fee_payer,
+                    &storage_keypair.pubkey(),
+                    target - storage_balance,
+                    balance,
+                    &blockhash,
+                    fee_calculator.lamports_per_signature,
+                );
+            }
+        }
+
+        // each transaction is signed by both the fee payer and storage keypairs
+        let proof_fee = fee_calculator.lamports_per_signature * 2;
+        let batches = mining_proof_batches(
             &storage_keypair.pubkey(),
-            meta.sha_state,
-            get_segment_from_slot(meta.slot, meta.slots_per_segment),
-            Signature::new(&meta.signature.as_ref()),
-            meta.blockhash,
-        );
-        let message = Message::new_with_payer(vec![instruction], Some(&archiver_keypair.pubkey()));
-        let mut transaction = Transaction::new(
-            &[archiver_keypair.as_ref(), storage_keypair.as_ref()],
-            message,
-            blockhash,
+            &fee_payer.pubkey(),
+            &meta.pending_proofs,
         );
-        if let Err(err) = client.send_and_confirm_transaction(
-            &[&archiver_keypair, &storage_keypair],
-            &mut transaction,
-            10,
-            0,
-        ) {
-            error!("Error: {:?}; while sending mining proof", err);
+        let mut confirmed = vec![false; meta.pending_proofs.len()];
+        for (range, message) in batches {
+            if balance < proof_fee {
+                Self::funding_alert(
+                    &fee_payer.pubkey(),
+                    balance,
+                    "insufficient balance for proof fees",
+                );
+                break;
+            }
+            balance -= proof_fee;
+            let num_proofs = range.len();
+            let mut transaction = Transaction::new(
+                &[fee_payer.as_ref(), storage_keypair.as_ref()],
+                message,
+                blockhash,
+            );
+            let result = client
+                .send_and_confirm_transaction(&[&fee_payer, &storage_keypair], &mut transaction, 10)
+                .map_err(|err| {
+                    error!("Error: {:?}; while sending mining proofs", err);
+                    err.to_string()
+                });
+            match &result {
+                Ok(transaction_signature) => {
+                    let mut status = meta.status.write().unwrap();
+                    status.proofs_submitted += num_proofs as u64;
+                    status.last_proof_signature = Some(*transaction_signature);
+                    datapoint_info!(
+                        "archiver-proof_submitted",
+                        ("slot", meta.pending_proofs[range.end - 1].slot, i64),
+                        ("num_proofs", num_proofs, i64),
+                        ("signature", transaction_signature.to_string(), String)
+                    );
+                }
+                Err(err) => {
+                    datapoint_error!(
+                        "archiver-proof_submit_failed",
+                        ("slot", meta.pending_proofs[range.end - 1].slot, i64),
+                        ("num_proofs", num_proofs, i64),
+                        ("error", err.clone(), String)
+                    );
+                    meta.send_event(ArchiverEvent::Error(format!(
+                        "unable to submit {} mining proofs: {}",
+                        num_proofs, err
+                    )));
+                }
+            }
+            for index in range {
+                let proof = &meta.pending_proofs[index];
+                if let Ok(transaction_signature) = &result {
+                    confirmed[index] = true;
+                    meta.send_event(ArchiverEvent::ProofSubmitted {
+                        slot: proof.slot,
+                        signature: *transaction_signature,
+                    });
+                }
+                Self::log_proof(
+                    meta,
+                    &ProofLogEntry::new_submitted(
+                        proof.segment,
+                        proof.sha_state,
+                        proof.signature,
+                        proof.blockhash,
+                        result.clone(),
+                    ),
+                );
+            }
+        }
+
+        let mut confirmed = confirmed.into_iter();
+        meta.pending_proofs.retain(|_| !confirmed.next().unwrap());
+        for proof in meta.pending_proofs.iter_mut() {
+            proof.attempts += 1;
+        }
+        let pending_proofs = meta.pending_proofs.len();
+        meta.pending_proofs
+            .retain(|proof| proof.attempts < MAX_PROOF_SUBMIT_ATTEMPTS);
+        if meta.pending_proofs.len() < pending_proofs {
+            warn!(
+                "dropped {} mining proofs after {} attempts",
+                pending_proofs - meta.pending_proofs.len(),
+                MAX_PROOF_SUBMIT_ATTEMPTS
+            );
         }
     }
 
-    pub fn close(self) {
+    fn log_proof(meta: &ArchiverMeta, entry: &ProofLogEntry) {
+        if let Some(proof_log) = &meta.proof_log {
+            if let Err(err) = proof_log.append(entry) {
+                warn!(
+                    "unable to record proof in {:?}: {:?}",
+                    proof_log.path(),
+                    err
+                );
+            }
+        }
+    }
+
+    /// Transfer `lamports` from the fee payer into the storage account, returning the fee
+    /// payer's remaining balance
+    fn top_up_storage_account(
+        client: &dyn ArchiverClusterClient,
+        fee_payer: &Keypair,
+        storage_pubkey: &Pubkey,
+        lamports: u64,
+        balance: u64,
+        blockhash: &Hash,
+        lamports_per_signature: u64,
+    ) -> u64 {
+        if balance < lamports + lamports_per_signature {
+            Self::funding_alert(
+                &fee_payer.pubkey(),
+                balance,
+                "insufficient balance to top up the storage account",
+            );
+            return balance;
+        }
+        let ix = system_instruction::transfer(&fee_payer.pubkey(), storage_pubkey, lamports);
+        let mut transaction =
+            Transaction::new_signed_instructions(&[fee_payer], vec![ix], *blockhash);
+        match client.send_and_confirm_transaction(&[fee_payer], &mut transaction, 10) {
+            Ok(_) => {
+                info!(
+                    "topped up storage account {} with {} lamports",
+                    storage_pubkey, lamports
+                );
+                datapoint_info!(
+                    "archiver-storage_top_up",
+                    ("account", storage_pubkey.to_string(), String),
+                    ("lamports", lamports, i64)
+                );
+                balance - lamports - lamports_per_signature
+            }
+            Err(err) => {
+                error!(
+                    "unable to top up storage account {}: {:?}",
+                    storage_pubkey, err
+                );
+                balance
+            }
+        }
+    }
+
+    fn funding_alert(account: &Pubkey, balance: u64, reason: &str) {
+        error!(
+            "Unable to submit mining proof, {}: account {} balance {}",
+            reason, account, balance
+        );
+        datapoint_error!(
+            "archiver-funding_alert",
+            ("account", account.to_string(), String),
+            ("balance", balance, i64),
+            ("reason", reason.to_string(), String)
+        );
+    }
+
+    /// Signal exit and wait for every stage to stop, see `join`
+    pub fn close(self) -> Result<()> {
         self.exit.store(true, Ordering::Relaxed);
         self.join()
     }
 
-    pub fn join(self) {
-        for handle in self.thread_handles {
-            handle.join().unwrap();
-        }
+    /// Signal exit and wait up to `deadline` for every stage to stop, see `join_with_deadline`
+    pub fn close_with_deadline(self, deadline: Duration) -> Result<()> {
+        self.exit.store(true, Ordering::Relaxed);
+        self.join_with_deadline(deadline)
     }
 
-    fn get_segment_config(
-        cluster_info: &Arc<RwLock<ClusterInfo>>,
-        client_commitment: CommitmentConfig,
-    ) -> result::Result<u64, Error> {
-        let rpc_peers = {
-            let cluster_info = cluster_info.read().unwrap();
-            cluster_info.rpc_peers()
-        };
-        debug!("rpc peers: {:?}", rpc_peers);
-        if !rpc_peers.is_empty() {
-            let rpc_client = {
-                let node_index = thread_rng().gen_range(0, rpc_peers.len());
-                RpcClient::new_socket(rpc_peers[node_index].rpc)
-            };
-            Ok(rpc_client
-                .send(
-                    &RpcRequest::GetSlotsPerSegment,
-                    None,
-                    0,
-                    Some(client_commitment),
-                )
-                .map_err(|err| {
-                    warn!("Error while making rpc request {:?}", err);
-                    Error::IO(io::Error::new(ErrorKind::Other, "rpc error"))
-                })?
-                .as_u64()
-                .unwrap())
-        } else {
-            Err(io::Error::new(io::ErrorKind::Other, "No RPC peers...".to_string()).into())
+    /// Wait for every stage to stop. Fails with `ShutdownError::StagesPanicked` naming the
+    /// stages that panicked.
+    pub fn join(self) -> Result<()> {
+        stage_results(self.t_archiver.join())
+    }
+
+    /// Like `join`, but fails with `ShutdownError::DeadlineExceeded` if the stages are still
+    /// running after `deadline`. They are left running detached in that case.
+    pub fn join_with_deadline(self, deadline: Duration) -> Result<()> {
+        match self.finished.recv_timeout(deadline) {
+            Err(RecvTimeoutError::Timeout) => Err(ShutdownError::DeadlineExceeded(deadline).into()),
+            _ => self.join(),
         }
     }
 
     /// Waits until the first segment is ready, and returns the current segment
     fn poll_for_segment(
-        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        cluster_client: &dyn ArchiverClusterClient,
         slots_per_segment: u64,
         previous_blockhash: &Hash,
+        commitment: &CommitmentConfig,
         exit: &Arc<AtomicBool>,
     ) -> result::Result<(Hash, u64), Error> {
         loop {
             let (blockhash, turn_slot) = Self::poll_for_blockhash_and_slot(
-                cluster_info,
+                cluster_client,
                 slots_per_segment,
                 previous_blockhash,
+                commitment,
                 exit,
             )?;
             if get_complete_segment_from_slot(turn_slot, slots_per_segment).is_some() {
@@ -783,214 +2684,109 @@ impl Archiver {
         }
     }
 
-    /// Poll for a different blockhash and associated max_slot than `previous_blockhash`
+    /// Poll for a different committed blockhash and associated turn slot than `previous_blockhash`
     fn poll_for_blockhash_and_slot(
-        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        cluster_client: &dyn ArchiverClusterClient,
         slots_per_segment: u64,
         previous_blockhash: &Hash,
+        commitment: &CommitmentConfig,
         exit: &Arc<AtomicBool>,
     ) -> result::Result<(Hash, u64), Error> {
         info!("waiting for the next turn...");
         loop {
-            let rpc_peers = {
-                let cluster_info = cluster_info.read().unwrap();
-                cluster_info.rpc_peers()
-            };
-            debug!("rpc peers: {:?}", rpc_peers);
-            if !rpc_peers.is_empty() {
-                let rpc_client = {
-                    let node_index = thread_rng().gen_range(0, rpc_peers.len());
-                    RpcClient::new_socket(rpc_peers[node_index].rpc)
-                };
-                let response = rpc_client
-                    .send(&RpcRequest::GetStorageTurn, None, 0, None)
-                    .map_err(|err| {
-                        warn!("Error while making rpc request {:?}", err);
-                        Error::IO(io::Error::new(ErrorKind::Other, "rpc error"))
-                    })?;
-                let (storage_blockhash, turn_slot) =
-                    serde_json::from_value::<((String, u64))>(response).map_err(|err| {
-                        io::Error::new(
-                            io::ErrorKind::Other,
-                            format!("Couldn't parse response: {:?}", err),
-                        )
-                    })?;
-                let turn_blockhash = storage_blockhash.parse().map_err(|err| {
-                    io::Error::new(
-                        io::ErrorKind::Other,
-                        format!(
-                            "Blockhash parse failure: {:?} on {:?}",
-                            err, storage_blockhash
-                        ),
-                    )
-                })?;
-                if turn_blockhash != *previous_blockhash {
-                    info!("turn slot: {}", turn_slot);
-                    if get_segment_from_slot(turn_slot, slots_per_segment) != 0 {
-                        return Ok((turn_blockhash, turn_slot));
+            match cluster_client.get_storage_turn(commitment.clone()) {
+                Ok((turn_blockhash, turn_slot)) => {
+                    if turn_blockhash != *previous_blockhash {
+                        info!("turn slot: {}", turn_slot);
+                        if get_segment_from_slot(turn_slot, slots_per_segment) != 0 {
+                            return Ok((turn_blockhash, turn_slot));
+                        }
                     }
                 }
+                // keep waiting for the cluster to show up in gossip
+                Err(Error::RpcError(RpcError::NoPeers)) => (),
+                Err(e) => return Err(e),
             }
             if exit.load(Ordering::Relaxed) {
-                return Err(Error::IO(io::Error::new(
-                    ErrorKind::Other,
-                    "exit signalled...",
-                )));
+                return Err(SetupError::Exited.into());
             }
             sleep(Duration::from_secs(5));
         }
     }
 
-    /// Ask an archiver to populate a given blocktree with its segment.
-    /// Return the slot at the start of the archiver's segment
+    /// Ask an archiver to populate a given blocktree with its segment over its storage socket.
+    /// Return the slot at the start of the archiver's segment. How the archiver answers is
+    /// recorded in `repair_peers`, usually `ClusterInfo::repair_peer_scores`.
     ///
     /// It is recommended to use a temporary blocktree for this since the download will not verify
     /// shreds received and might impact the chaining of shreds across slots
     pub fn download_from_archiver(
-        cluster_info: &Arc<RwLock<ClusterInfo>>,
         archiver_info: &ContactInfo,
         blocktree: &Arc<Blocktree>,
-        slots_per_segment: u64,
-    ) -> Result<(u64)> {
-        // Create a client which downloads from the archiver and see that it
-        // can respond with shreds.
-        let start_slot = Self::get_archiver_segment_slot(archiver_info.storage_addr);
-        info!("Archiver download: start at {}", start_slot);
-
-        let exit = Arc::new(AtomicBool::new(false));
-        let (s_reader, r_reader) = channel();
-        let repair_socket = Arc::new(bind_in_range(VALIDATOR_PORT_RANGE).unwrap().1);
-        let t_receiver = receiver(
-            repair_socket.clone(),
-            &exit,
-            s_reader.clone(),
-            Recycler::default(),
-            "archiver_reeciver",
-        );
-        let id = cluster_info.read().unwrap().id();
-        info!(
-            "Sending repair requests from: {} to: {}",
-            cluster_info.read().unwrap().my_data().id,
-            archiver_info.gossip
-        );
-        let repair_slot_range = RepairSlotRange {
-            start: start_slot,
-            end: start_slot + slots_per_segment,
-        };
-        // try for upto 180 seconds //TODO needs tuning if segments are huge
-        for _ in 0..120 {
-            // Strategy used by archivers
-            let repairs = RepairService::generate_repairs_in_range(
-                blocktree,
-                repair_service::MAX_REPAIR_LENGTH,
-                &repair_slot_range,
-            );
-            //iter over the repairs and send them
-            if let Ok(repairs) = repairs {
-                let reqs: Vec<_> = repairs
-                    .into_iter()
-                    .filter_map(|repair_request| {
-                        cluster_info
-                            .read()
-                            .unwrap()
-                            .map_repair_request(&repair_request)
-                            .map(|result| ((archiver_info.gossip, result), repair_request))
-                            .ok()
-                    })
-                    .collect();
-
-                for ((to, req), repair_request) in reqs {
-                    if let Ok(local_addr) = repair_socket.local_addr() {
-                        datapoint_info!(
-                            "archiver_download",
-                            ("repair_request", format!("{:?}", repair_request), String),
-                            ("to", to.to_string(), String),
-                            ("from", local_addr.to_string(), String),
-                            ("id", id.to_string(), String)
-                        );
-                    }
-                    repair_socket
-                        .send_to(&req, archiver_info.gossip)
-                        .unwrap_or_else(|e| {
-                            error!("{} repair req send_to({}) error {:?}", id, to, e);
-                            0
-                        });
-                }
-            }
-            let res = r_reader.recv_timeout(Duration::new(1, 0));
-            if let Ok(mut packets) = res {
-                while let Ok(mut more) = r_reader.try_recv() {
-                    packets.packets.append_pinned(&mut more.packets);
-                }
-                let shreds: Vec<Shred> = packets
-                    .packets
-                    .into_iter()
-                    .filter_map(|p| Shred::new_from_serialized_shred(p.data.to_vec()).ok())
-                    .collect();
-                blocktree.insert_shreds(shreds, None, false)?;
-            }
-            // check if all the slots in the segment are complete
-            if Self::segment_complete(start_slot, slots_per_segment, blocktree) {
-                break;
-            }
-            sleep(Duration::from_millis(500));
-        }
-        exit.store(true, Ordering::Relaxed);
-        t_receiver.join().unwrap();
-
-        // check if all the slots in the segment are complete
-        if !Self::segment_complete(start_slot, slots_per_segment, blocktree) {
-            return Err(
-                io::Error::new(ErrorKind::Other, "Unable to download the full segment").into(),
-            );
-        }
-        Ok(start_slot)
+        repair_peers: &Mutex<RepairPeers>,
+    ) -> Result<Slot> {
+        let manifest =
+            archiver_segment_transfer::download_segment(archiver_info, blocktree, repair_peers)?;
+        Ok(manifest.start_slot)
     }
 
-    fn segment_complete(
-        start_slot: Slot,
-        slots_per_segment: u64,
-        blocktree: &Arc<Blocktree>,
-    ) -> bool {
-        for slot in start_slot..(start_slot + slots_per_segment) {
-            if !blocktree.is_full(slot) {
-                return false;
-            }
-        }
-        true
+    /// Pick one of `archivers` to download from, favoring those `repair_peers` scores best
+    pub fn select_archiver<'a>(
+        archivers: &'a [ContactInfo],
+        repair_peers: &Mutex<RepairPeers>,
+    ) -> Option<&'a ContactInfo> {
+        let peers: Vec<_> = archivers
+            .iter()
+            .map(|archiver| (archiver.id, archiver.storage_addr))
+            .collect();
+        let (id, _) = repair_peers.lock().unwrap().select_peer(&peers)?;
+        archivers.iter().find(|archiver| archiver.id == id)
     }
 
-    fn get_archiver_segment_slot(to: SocketAddr) -> u64 {
-        let (_port, socket) = bind_in_range(VALIDATOR_PORT_RANGE).unwrap();
-        socket
-            .set_read_timeout(Some(Duration::from_secs(5)))
-            .unwrap();
-
-        let req = ArchiverRequest::GetSlotHeight(socket.local_addr().unwrap());
-        let serialized_req = bincode::serialize(&req).unwrap();
-        for _ in 0..10 {
-            socket.send_to(&serialized_req, to).unwrap();
-            let mut buf = [0; 1024];
+    /// Ask the archiver listening on storage socket `to` for its `ArchiverStatus`, `None` if
+    /// it doesn't answer
+    pub fn get_archiver_status(to: SocketAddr) -> Option<ArchiverStatus> {
+        let (_port, socket) =
+            bind_in_range_with_ip(unspecified_ip_for(&to.ip()), VALIDATOR_PORT_RANGE).ok()?;
+        socket.set_read_timeout(Some(Duration::from_secs(1))).ok()?;
+
+        let req = ArchiverRequest::GetStatus(socket.local_addr().ok()?);
+        let serialized_req = req.to_bytes();
+        for _ in 0..5 {
+            socket.send_to(&serialized_req, to).ok()?;
+            let mut buf = [0; PACKET_DATA_SIZE];
             if let Ok((size, _addr)) = socket.recv_from(&mut buf) {
                 // Ignore bad packet and try again
-                if let Ok(slot) = bincode::config()
+                if let Ok(status) = bincode::config()
                     .limit(PACKET_DATA_SIZE as u64)
                     .deserialize(&buf[..size])
                 {
-                    return slot;
+                    return Some(status);
                 }
             }
-            sleep(Duration::from_millis(500));
         }
-        panic!("Couldn't get segment slot from archiver!");
+        None
+    }
+
+    /// Read back the proofs an archiver recorded in its proof log under `ledger_path`, see
+    /// `ArchiverBuilder::proof_log` for archivers that log elsewhere
+    pub fn read_proof_history(ledger_path: &Path) -> io::Result<Vec<ProofLogEntry>> {
+        ProofLog::read(&ledger_path.join(PROOF_LOG_FILENAME))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::{create_dir_all, remove_file};
+    use solana_ledger::{blocktree::make_many_slot_entries, get_tmp_ledger_path};
+    use solana_net_utils::bind_in_range;
+    use solana_sdk::{
+        account::Account, fee_calculator::FeeCalculator, system_instruction::SystemInstruction,
+        system_program, transport::Result as TransportResult,
+    };
+    use std::fs::{create_dir_all, remove_dir_all, remove_file};
     use std::io::Write;
+    use std::sync::Mutex;
 
     fn tmp_file_path(name: &str) -> PathBuf {
         use std::env;
@@ -1028,6 +2824,33 @@ mod tests {
         let res = res.unwrap();
         assert_eq!(res, ref_hash);
 
+        // Samples are hashed in the order requested, whichever path reads them
+        let reversed: Vec<_> = samples.iter().rev().cloned().collect();
+        let mut hasher = Hasher::default();
+        reversed
+            .iter()
+            .for_each(|_| hasher.hash(&string.as_bytes().repeat(4)));
+        assert_eq!(sample_file(&in_path, &reversed).unwrap(), hasher.result());
+
+        // Neither the read order nor prefetching changes the hash, nor reading without a map
+        for sort_offsets in &[false, true] {
+            for prefetch in &[false, true] {
+                let config = SampleConfig {
+                    sort_offsets: *sort_offsets,
+                    prefetch: *prefetch,
+                };
+                assert_eq!(
+                    sample_file_with(&in_path, &reversed, config).unwrap(),
+                    hasher.result()
+                );
+            }
+        }
+        let file_len = fs::metadata(&in_path).unwrap().len();
+        assert_eq!(
+            sample_from(|| File::open(&in_path), file_len, &reversed).unwrap(),
+            hasher.result()
+        );
+
         // Sample just past the end
         assert!(sample_file(&in_path, &[num_samples]).is_err());
         remove_file(&in_path).unwrap();
@@ -1048,6 +2871,645 @@ mod tests {
         remove_file(in_path).unwrap();
     }
 
+    #[test]
+    fn test_sampled_chunk_indexes() {
+        let samples_per_chunk = (CHACHA_TAG_CHUNK_SIZE / size_of::<Hash>()) as u64;
+        assert!(sampled_chunk_indexes(&[]).is_empty());
+        assert_eq!(
+            sampled_chunk_indexes(&[0, 1, samples_per_chunk - 1]),
+            vec![0]
+        );
+        assert_eq!(
+            sampled_chunk_indexes(&[3 * samples_per_chunk, 0, samples_per_chunk]),
+            vec![0, 1, 3]
+        );
+    }
+
+    #[test]
+    fn test_compute_pool() {
+        let compute_pool = ComputePool::new(2, Some(19)).unwrap();
+        assert_eq!(compute_pool.0.current_num_threads(), 2);
+        let thread_name = compute_pool
+            .0
+            .install(|| std::thread::current().name().map(str::to_string));
+        assert!(thread_name.unwrap().starts_with("archiver-compute-"));
+
+        // Lowering a thread's priority needs no privileges
+        #[cfg(target_os = "linux")]
+        {
+            let nice = compute_pool.0.install(|| {
+                let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+                unsafe { libc::getpriority(libc::PRIO_PROCESS, tid) }
+            });
+            assert_eq!(nice, 19);
+        }
+    }
+
+    #[test]
+    fn test_stage_results() {
+        assert!(stage_results(Ok(vec![("gossip", Ok(()))])).is_ok());
+
+        let panicked = thread::spawn(|| panic!("window failed")).join();
+        let stages = vec![("gossip", Ok(())), ("window", panicked)];
+        assert_matches!(
+            stage_results(Ok(stages)),
+            Err(Error::ShutdownError(ShutdownError::StagesPanicked(ref stages)))
+                if stages == &vec![("window".to_string(), "window failed".to_string())]
+        );
+
+        let panicked = thread::spawn(|| -> StageResults { panic!("{}", 42) }).join();
+        assert_matches!(
+            stage_results(panicked),
+            Err(Error::ShutdownError(ShutdownError::StagesPanicked(ref stages)))
+                if stages == &vec![("archiver".to_string(), "42".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_redemption_policy() {
+        let mut validations = BTreeMap::new();
+        assert_eq!(count_valid_proofs(&validations), 0);
+        let mut segment = BTreeMap::new();
+        segment.insert(
+            Pubkey::new_rand(),
+            vec![
+                ProofStatus::Valid,
+                ProofStatus::NotValid,
+                ProofStatus::Valid,
+            ],
+        );
+        segment.insert(Pubkey::new_rand(), vec![ProofStatus::Skipped]);
+        validations.insert(0, segment);
+        assert_eq!(count_valid_proofs(&validations), 2);
+
+        let policy = RedemptionPolicy::default();
+        assert!(!policy.should_redeem(0, 0));
+        assert!(policy.should_redeem(1, 0));
+
+        let policy = RedemptionPolicy {
+            min_validations: 3,
+            min_reward_lamports: 100,
+            ..RedemptionPolicy::default()
+        };
+        assert!(!policy.should_redeem(2, 1_000));
+        assert!(!policy.should_redeem(3, 99));
+        assert!(policy.should_redeem(3, 100));
+
+        // Validations are logged once each, even if none of them are valid
+        let mut validations = BTreeMap::new();
+        let mut segment = BTreeMap::new();
+        let validator = Pubkey::new_rand();
+        segment.insert(validator, vec![ProofStatus::NotValid]);
+        validations.insert(1, segment);
+        assert_eq!(count_valid_proofs(&validations), 0);
+        let mut logged_validations = HashSet::new();
+        assert_eq!(
+            validated_proof_entries(&validations, 1.0, &mut logged_validations)
+                .iter()
+                .map(|entry| match entry {
+                    ProofLogEntry::Validated {
+                        segment,
+                        validator,
+                        valid,
+                        invalid,
+                        ..
+                    } => (*segment, *validator, *valid, *invalid),
+                    _ => panic!("not a validation"),
+                })
+                .collect::<Vec<_>>(),
+            vec![(1, validator, 0, 1)]
+        );
+        assert!(validated_proof_entries(&validations, 1.0, &mut logged_validations).is_empty());
+    }
+
+    #[test]
+    fn test_segments_to_evict() {
+        let segment = |start_slot, proven| SegmentUsage {
+            start_slot,
+            slots_per_segment: 8,
+            blocktree_bytes: 100,
+            encrypted_bytes: 0,
+            proven,
+        };
+        let mut segments = vec![
+            segment(0, true),
+            segment(8, false),
+            segment(16, true),
+            segment(24, true),
+        ];
+        segments[3].encrypted_bytes = 100;
+
+        assert_eq!(segments_to_evict(&segments, 500, 0, false), Some(vec![]));
+        // the unproven segment is kept
+        assert_eq!(
+            segments_to_evict(&segments, 500, 200, false),
+            Some(vec![0, 2])
+        );
+        // so is the segment being proven, unless it's replaced
+        assert_eq!(segments_to_evict(&segments, 500, 300, false), None);
+        assert_eq!(
+            segments_to_evict(&segments, 500, 300, true),
+            Some(vec![0, 2])
+        );
+        assert_eq!(
+            segments_to_evict(&segments, 500, 400, true),
+            Some(vec![0, 2, 3])
+        );
+        assert_eq!(segments_to_evict(&segments, 500, 500, true), None);
+    }
+
+    #[test]
+    fn test_evict_segments() {
+        let ledger_path = get_tmp_ledger_path!();
+        let blocktree = Blocktree::open(&ledger_path).unwrap();
+        let (shreds, _) = make_many_slot_entries(0, 16, 10);
+        blocktree.insert_shreds(shreds, None, false).unwrap();
+
+        let mut meta = ArchiverMeta {
+            slots_per_segment: 8,
+            ..ArchiverMeta::default()
+        };
+        Archiver::record_segment_usage(&mut meta, &blocktree);
+        meta.segments[0].proven = true;
+        meta.slot = 8;
+        Archiver::record_segment_usage(&mut meta, &blocktree);
+        assert_eq!(meta.segments.len(), 2);
+        assert!(meta.segments[0].blocktree_bytes > 0);
+
+        // without a quota nothing is evicted
+        Archiver::evict_segments(&mut meta, &blocktree, std::u64::MAX / 2, false).unwrap();
+        assert_eq!(meta.segments.len(), 2);
+
+        meta.storage_quota = Some(meta.segments[1].bytes());
+        Archiver::evict_segments(&mut meta, &blocktree, 0, false).unwrap();
+        assert_eq!(meta.segments.len(), 1);
+        assert_eq!(meta.segments[0].start_slot, 8);
+        assert!(blocktree.meta(0).unwrap().is_none());
+        assert!(blocktree.is_full(8));
+
+        // the segment being proven can't be evicted to make room
+        assert_matches!(
+            Archiver::evict_segments(&mut meta, &blocktree, 1, false),
+            Err(Error::StorageError(StorageError::QuotaExceeded { .. }))
+        );
+
+        drop(blocktree);
+        Blocktree::destroy(&ledger_path).unwrap();
+    }
+
+    struct MockClusterClient {
+        balance: u64,
+        storage_turns: Mutex<Vec<(Hash, Slot)>>,
+        sent_transactions: Mutex<Vec<Transaction>>,
+    }
+
+    impl ArchiverClusterClient for MockClusterClient {
+        fn get_segment_config(
+            &self,
+            _commitment: CommitmentConfig,
+        ) -> result::Result<RpcSegmentConfig, Error> {
+            Err(RpcError::NoPeers.into())
+        }
+
+        fn get_storage_turn(
+            &self,
+            _commitment: CommitmentConfig,
+        ) -> result::Result<(Hash, Slot), Error> {
+            self.storage_turns
+                .lock()
+                .unwrap()
+                .pop()
+                .ok_or_else(|| RpcError::NoPeers.into())
+        }
+
+        fn get_segment_leaders(
+            &self,
+            _slot: Slot,
+            _commitment: CommitmentConfig,
+        ) -> result::Result<Vec<Pubkey>, Error> {
+            Err(RpcError::NoPeers.into())
+        }
+
+        fn get_account(
+            &self,
+            _pubkey: &Pubkey,
+            _commitment: CommitmentConfig,
+        ) -> TransportResult<Option<Account>> {
+            Ok(None)
+        }
+
+        fn get_balance(
+            &self,
+            _pubkey: &Pubkey,
+            _commitment: CommitmentConfig,
+        ) -> TransportResult<u64> {
+            Ok(self.balance)
+        }
+
+        fn poll_balance(
+            &self,
+            _pubkey: &Pubkey,
+            _timeout: Duration,
+            _commitment: CommitmentConfig,
+        ) -> io::Result<u64> {
+            Ok(self.balance)
+        }
+
+        fn get_recent_blockhash(
+            &self,
+            _commitment: CommitmentConfig,
+        ) -> TransportResult<(Hash, FeeCalculator)> {
+            Ok((Hash::default(), FeeCalculator::default()))
+        }
+
+        fn async_send_transaction(&self, transaction: Transaction) -> io::Result<Signature> {
+            Ok(transaction.signatures[0])
+        }
+
+        fn poll_for_signature(
+            &self,
+            _signature: &Signature,
+            _commitment: CommitmentConfig,
+        ) -> TransportResult<()> {
+            Ok(())
+        }
+
+        fn send_and_confirm_transaction(
+            &self,
+            _keypairs: &[&Keypair],
+            transaction: &mut Transaction,
+            _tries: usize,
+        ) -> io::Result<Signature> {
+            self.sent_transactions
+                .lock()
+                .unwrap()
+                .push(transaction.clone());
+            Ok(transaction.signatures[0])
+        }
+
+        fn send_message(
+            &self,
+            _keypairs: &[&Keypair],
+            _message: Message,
+        ) -> TransportResult<Signature> {
+            Ok(Signature::default())
+        }
+    }
+
+    #[test]
+    fn test_download_segment_reselects_stalled_segment() {
+        use solana_ledger::blocktree_processor::fill_blocktree_slot_with_ticks;
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let blocktree_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Arc::new(Blocktree::open(&blocktree_path).unwrap());
+            let mut last_entry_hash = Hash::default();
+            for slot in 0..16 {
+                last_entry_hash = fill_blocktree_slot_with_ticks(
+                    &blocktree,
+                    5,
+                    slot,
+                    slot.saturating_sub(1),
+                    last_entry_hash,
+                );
+            }
+            // no peer serves slots past 16
+            assert!(!Archiver::wait_for_segment_download(
+                100,
+                2,
+                &blocktree,
+                Duration::from_millis(0),
+                &exit,
+                |_| panic!("no slot of the segment is downloaded"),
+            ));
+            assert_eq!(
+                blocktree.download_manifest(100).unwrap(),
+                Some(DownloadManifest::new(100, 2))
+            );
+
+            let turn_blockhash = Hash::new(&[1; 32]);
+            let client = MockClusterClient {
+                balance: 0,
+                storage_turns: Mutex::new(vec![(turn_blockhash, 16)]),
+                sent_transactions: Mutex::new(vec![]),
+            };
+            let (event_sender, event_receiver) = crossbeam_channel::unbounded();
+            let mut meta = ArchiverMeta {
+                slot: 100,
+                slots_per_segment: 2,
+                event_sender: Some(event_sender),
+                ..ArchiverMeta::default()
+            };
+            let node_info = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
+            let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+                node_info.clone(),
+            )));
+            let (slot_sender, slot_receiver) = channel();
+            Archiver::download_segment(
+                &mut meta,
+                &blocktree,
+                &cluster_info,
+                &client,
+                &Arc::new(Keypair::new()),
+                &node_info,
+                &slot_sender,
+                &exit,
+            )
+            .unwrap();
+
+            assert!(meta.slot < 16);
+            assert_eq!(meta.blockhash, turn_blockhash);
+            assert_eq!(slot_receiver.try_recv().unwrap(), meta.slot);
+            assert_eq!(meta.repair_slot_range.read().unwrap().start, meta.slot);
+            assert_eq!(
+                cluster_info.read().unwrap().my_data().tvu,
+                ContactInfo::unspecified_addr()
+            );
+            assert_eq!(
+                event_receiver.try_iter().collect::<Vec<_>>(),
+                vec![
+                    ArchiverEvent::SegmentSelected {
+                        slot: meta.slot,
+                        slots_per_segment: 2
+                    },
+                    ArchiverEvent::DownloadProgress {
+                        slot: meta.slot,
+                        slots_downloaded: 1,
+                        slots_per_segment: 2
+                    },
+                    ArchiverEvent::DownloadProgress {
+                        slot: meta.slot,
+                        slots_downloaded: 2,
+                        slots_per_segment: 2
+                    },
+                ]
+            );
+            assert!(blocktree
+                .download_manifest(meta.slot)
+                .unwrap()
+                .unwrap()
+                .is_complete());
+
+            // a restarted download skips the slots its manifest already has complete
+            let mut manifest = DownloadManifest::new(100, 2);
+            manifest.set_slot_complete(100);
+            manifest.set_slot_complete(101);
+            blocktree.put_download_manifest(&manifest).unwrap();
+            assert!(Archiver::wait_for_segment_download(
+                100,
+                2,
+                &blocktree,
+                Duration::from_millis(0),
+                &exit,
+                |_| panic!("no slot completes again"),
+            ));
+        }
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
+    #[test]
+    fn test_mock_cluster_client() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let previous_blockhash = Hash::default();
+        let turn_blockhash = Hash::new(&[1; 32]);
+        let client = MockClusterClient {
+            balance: 0,
+            storage_turns: Mutex::new(vec![(turn_blockhash, 16)]),
+            sent_transactions: Mutex::new(vec![]),
+        };
+        assert_eq!(
+            Archiver::poll_for_blockhash_and_slot(
+                &client,
+                8,
+                &previous_blockhash,
+                &CommitmentConfig::default(),
+                &exit,
+            )
+            .unwrap(),
+            (turn_blockhash, 16)
+        );
+
+        let keypair = Keypair::new();
+        let storage_keypair = Keypair::new();
+        match Archiver::setup_mining_account(
+            &client,
+            &keypair,
+            &storage_keypair,
+            CommitmentConfig::recent(),
+            &StorageFundingConfig::default(),
+        ) {
+            Err(Error::StorageError(StorageError::AccountNotFunded(pubkey))) => {
+                assert_eq!(pubkey, keypair.pubkey())
+            }
+            _ => panic!("expected an unfunded archiver account"),
+        }
+    }
+
+    #[test]
+    fn test_submit_mining_proof_fee_payer() {
+        let client = MockClusterClient {
+            balance: 10,
+            storage_turns: Mutex::new(vec![]),
+            sent_transactions: Mutex::new(vec![]),
+        };
+        let archiver_keypair = Arc::new(Keypair::new());
+        let storage_keypair = Arc::new(Keypair::new());
+        let fee_payer = Arc::new(Keypair::new());
+        let mut meta = ArchiverMeta {
+            slots_per_segment: 8,
+            fee_payer: Some(fee_payer.clone()),
+            ..ArchiverMeta::default()
+        };
+        meta.pending_proofs.push(PendingProof::new(&meta));
+        Archiver::submit_mining_proof(&mut meta, &client, &archiver_keypair, &storage_keypair);
+
+        let sent_transactions = client.sent_transactions.lock().unwrap();
+        assert_eq!(sent_transactions.len(), 1);
+        let account_keys = &sent_transactions[0].message.account_keys;
+        assert_eq!(account_keys[0], fee_payer.pubkey());
+        assert!(!account_keys.contains(&archiver_keypair.pubkey()));
+        assert_eq!(meta.status.read().unwrap().proofs_submitted, 1);
+        assert!(meta.pending_proofs.is_empty());
+    }
+
+    #[test]
+    fn test_bytes_per_sec() {
+        assert_eq!(bytes_per_sec(1000, 500), 2000.0);
+        assert_eq!(bytes_per_sec(0, 500), 0.0);
+        // a run too fast to measure doesn't divide by zero
+        assert_eq!(bytes_per_sec(1000, 0), 1_000_000.0);
+    }
+
+    #[test]
+    fn test_top_up_storage_account() {
+        let archiver_keypair = Arc::new(Keypair::new());
+        let storage_keypair = Arc::new(Keypair::new());
+        // the mock reports the same balance for the storage account and the fee payer
+        let submit_proof = |balance| {
+            let client = MockClusterClient {
+                balance,
+                storage_turns: Mutex::new(vec![]),
+                sent_transactions: Mutex::new(vec![]),
+            };
+            let mut meta = ArchiverMeta {
+                slots_per_segment: 8,
+                storage_funding: StorageFundingConfig {
+                    initial_lamports: 10,
+                    top_up_threshold: Some(20),
+                },
+                ..ArchiverMeta::default()
+            };
+            meta.pending_proofs.push(PendingProof::new(&meta));
+            Archiver::submit_mining_proof(&mut meta, &client, &archiver_keypair, &storage_keypair);
+            assert!(meta.pending_proofs.is_empty());
+            client.sent_transactions.into_inner().unwrap()
+        };
+
+        // below the threshold, the storage account is topped up to it before the proof is sent
+        let sent_transactions = submit_proof(10);
+        assert_eq!(sent_transactions.len(), 2);
+        let message = &sent_transactions[0].message;
+        assert_eq!(message.account_keys[0], archiver_keypair.pubkey());
+        assert_eq!(message.account_keys[1], storage_keypair.pubkey());
+        assert_eq!(
+            bincode::deserialize::<SystemInstruction>(&message.instructions[0].data).unwrap(),
+            SystemInstruction::Transfer { lamports: 10 }
+        );
+
+        // an already funded storage account isn't topped up
+        let sent_transactions = submit_proof(30);
+        assert_eq!(sent_transactions.len(), 1);
+        assert!(!sent_transactions[0]
+            .message
+            .account_keys
+            .contains(&system_program::id()));
+
+        // the fee payer can't afford the top up
+        let client = MockClusterClient {
+            balance: 5,
+            storage_turns: Mutex::new(vec![]),
+            sent_transactions: Mutex::new(vec![]),
+        };
+        let balance = Archiver::top_up_storage_account(
+            &client,
+            &archiver_keypair,
+            &storage_keypair.pubkey(),
+            10,
+            5,
+            &Hash::default(),
+            0,
+        );
+        assert_eq!(balance, 5);
+        assert!(client.sent_transactions.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_archiver_events() {
+        let client = MockClusterClient {
+            balance: 10,
+            storage_turns: Mutex::new(vec![]),
+            sent_transactions: Mutex::new(vec![]),
+        };
+        let (event_sender, event_receiver) = crossbeam_channel::unbounded();
+        let mut meta = ArchiverMeta {
+            slot: 8,
+            slots_per_segment: 8,
+            event_sender: Some(event_sender),
+            ..ArchiverMeta::default()
+        };
+        meta.pending_proofs.push(PendingProof::new(&meta));
+        let proof_slot = meta.pending_proofs[0].slot;
+        Archiver::submit_mining_proof(
+            &mut meta,
+            &client,
+            &Arc::new(Keypair::new()),
+            &Arc::new(Keypair::new()),
+        );
+        let signature = client.sent_transactions.lock().unwrap()[0].signatures[0];
+        assert_eq!(
+            event_receiver.try_iter().collect::<Vec<_>>(),
+            vec![ArchiverEvent::ProofSubmitted {
+                slot: proof_slot,
+                signature
+            }]
+        );
+
+        // a subscriber that fell behind or went away misses events without blocking
+        let (event_sender, event_receiver) = crossbeam_channel::bounded(1);
+        meta.event_sender = Some(event_sender);
+        meta.send_event(ArchiverEvent::Error("first".to_string()));
+        meta.send_event(ArchiverEvent::Error("second".to_string()));
+        assert_eq!(
+            event_receiver.try_iter().collect::<Vec<_>>(),
+            vec![ArchiverEvent::Error("first".to_string())]
+        );
+        drop(event_receiver);
+        meta.send_event(ArchiverEvent::Error("third".to_string()));
+    }
+
+    #[test]
+    fn test_submit_batched_mining_proofs() {
+        let storage_pubkey = Pubkey::new_rand();
+        let fee_payer = Pubkey::new_rand();
+        let mut meta = ArchiverMeta {
+            slots_per_segment: 8,
+            ..ArchiverMeta::default()
+        };
+        let proofs: Vec<_> = (0..20)
+            .map(|i| {
+                meta.slot = 8 * (i + 1);
+                meta.sha_state = Hash::new(&[i as u8; 32]);
+                PendingProof::new(&meta)
+            })
+            .collect();
+
+        assert!(mining_proof_batches(&storage_pubkey, &fee_payer, &[]).is_empty());
+        let batches = mining_proof_batches(&storage_pubkey, &fee_payer, &proofs[..3]);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].0, 0..3);
+        assert_eq!(batches[0].1.instructions.len(), 3);
+
+        // batches only split when a transaction would outgrow a packet
+        let batches = mining_proof_batches(&storage_pubkey, &fee_payer, &proofs);
+        assert!(batches.len() > 1);
+        let mut next_start = 0;
+        for (range, message) in &batches {
+            assert_eq!(range.start, next_start);
+            next_start = range.end;
+            assert_eq!(message.instructions.len(), range.len());
+            let transaction = Transaction::new_unsigned(message.clone());
+            assert!(bincode::serialized_size(&transaction).unwrap() <= PACKET_DATA_SIZE as u64);
+        }
+        assert_eq!(next_start, proofs.len());
+        let (first_range, first_message) = &batches[0];
+        let mut instructions: Vec<_> = proofs[first_range.clone()]
+            .iter()
+            .map(|proof| proof.instruction(&storage_pubkey))
+            .collect();
+        instructions.push(proofs[first_range.end].instruction(&storage_pubkey));
+        let oversized =
+            Transaction::new_unsigned(Message::new_with_payer(instructions, Some(&fee_payer)));
+        assert!(bincode::serialized_size(&oversized).unwrap() > PACKET_DATA_SIZE as u64);
+        assert_eq!(first_message.account_keys[0], fee_payer);
+
+        // every pending proof is submitted, in as many transactions as batches
+        let client = MockClusterClient {
+            balance: 100,
+            storage_turns: Mutex::new(vec![]),
+            sent_transactions: Mutex::new(vec![]),
+        };
+        meta.pending_proofs = proofs;
+        let keypair = Arc::new(Keypair::new());
+        Archiver::submit_mining_proof(&mut meta, &client, &keypair, &Arc::new(Keypair::new()));
+        assert_eq!(
+            client.sent_transactions.lock().unwrap().len(),
+            batches.len()
+        );
+        assert_eq!(meta.status.read().unwrap().proofs_submitted, 20);
+        assert!(meta.pending_proofs.is_empty());
+    }
+
     #[test]
     fn test_sample_file_missing_file() {
         let in_path = tmp_file_path("test_sample_file_that_doesnt_exist.txt");
@@ -1055,4 +3517,160 @@ mod tests {
         let res = sample_file(&in_path, &samples);
         assert!(res.is_err());
     }
+
+    #[test]
+    fn test_archiver_state() {
+        let storage_pubkey = Pubkey::new_rand();
+        let meta = ArchiverMeta {
+            slot: 42,
+            slots_per_segment: 16,
+            signature: Signature::new(&[7; 64]),
+            blockhash: Hash::new(&[1; 32]),
+            sampling_offsets: vec![3, 5],
+            sha_state: Hash::new(&[2; 32]),
+            num_chacha_blocks: 10,
+            segments: vec![SegmentUsage {
+                start_slot: 42,
+                slots_per_segment: 16,
+                blocktree_bytes: 1024,
+                encrypted_bytes: 512,
+                proven: true,
+            }],
+            ..ArchiverMeta::default()
+        };
+        let state = ArchiverState::new(&meta, &storage_pubkey);
+
+        let state_path = tmp_file_path("test_archiver_state");
+        state.save(&state_path).unwrap();
+        assert_eq!(ArchiverState::load(&state_path).unwrap(), state);
+
+        let segment_dir = tmp_file_path("test_archiver_state_segment");
+        create_dir_all(&segment_dir).unwrap();
+        let storage_backend = LocalFileBackend::new(&segment_dir);
+        let segment_path = storage_backend.local_path(SegmentObject::Segment).unwrap();
+        let verify = |storage_pubkey, slots_per_segment| match state.verify(
+            storage_pubkey,
+            slots_per_segment,
+            &storage_backend,
+        ) {
+            Err(Error::StorageError(StorageError::StateMismatch(_))) => true,
+            _ => false,
+        };
+        assert!(verify(&Pubkey::new_rand(), 16));
+        assert!(verify(&storage_pubkey, 32));
+        // the encrypted segment is gone
+        assert!(!verify(&storage_pubkey, 16));
+        assert!(state.verify(&storage_pubkey, 16, &storage_backend).is_err());
+
+        // an encrypted segment of the wrong size
+        File::create(&segment_path)
+            .unwrap()
+            .write_all(&[0; CHACHA_BLOCK_SIZE])
+            .unwrap();
+        assert!(verify(&storage_pubkey, 16));
+
+        remove_dir_all(&segment_dir).unwrap();
+        remove_file(&state_path).unwrap();
+    }
+
+    #[test]
+    fn test_archiver_status_request() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (_port, socket) = bind_in_range(VALIDATOR_PORT_RANGE).unwrap();
+        let addr = socket.local_addr().unwrap();
+        let (slot_sender, slot_receiver) = channel();
+        let status = Arc::new(RwLock::new(ArchiverStatus::default()));
+        let ledger_path = get_tmp_ledger_path!();
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let request_processor =
+            create_request_processor(socket, &exit, slot_receiver, status.clone(), blocktree);
+        slot_sender.send(16).unwrap();
+
+        let expected_status = ArchiverStatus {
+            segment_start_slot: 16,
+            slots_per_segment: 8,
+            proofs_submitted: 2,
+            last_proof_signature: Some(Signature::new(&[1; 64])),
+            encrypted_file_size: CHACHA_BLOCK_SIZE as u64,
+            rewards_claimed: 10,
+        };
+        *status.write().unwrap() = expected_status.clone();
+        assert_eq!(Archiver::get_archiver_status(addr), Some(expected_status));
+
+        exit.store(true, Ordering::Relaxed);
+        request_processor
+            .into_iter()
+            .for_each(|t| t.join().unwrap());
+        Blocktree::destroy(&ledger_path).unwrap();
+    }
+
+    #[test]
+    fn test_archiver_segment_transfer() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (_port, socket) = bind_in_range(VALIDATOR_PORT_RANGE).unwrap();
+        let addr = socket.local_addr().unwrap();
+        let (slot_sender, slot_receiver) = channel();
+        let slots_per_segment = 4;
+        let status = Arc::new(RwLock::new(ArchiverStatus {
+            segment_start_slot: 0,
+            slots_per_segment,
+            ..ArchiverStatus::default()
+        }));
+
+        let source_path = get_tmp_ledger_path!();
+        let source = Arc::new(Blocktree::open(&source_path).unwrap());
+        let (shreds, _) = make_many_slot_entries(0, slots_per_segment, 500);
+        source.insert_shreds(shreds, None, false).unwrap();
+        let request_processor =
+            create_request_processor(socket, &exit, slot_receiver, status, source.clone());
+        slot_sender.send(0).unwrap();
+
+        // Start from a partial copy of the segment, the download resumes from the missing shreds
+        let ledger_path = get_tmp_ledger_path!();
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let (shreds, _) = make_many_slot_entries(0, 2, 500);
+        let num_partial = shreds.len() / 3;
+        blocktree
+            .insert_shreds(shreds[..num_partial].to_vec(), None, false)
+            .unwrap();
+        assert!(!blocktree.is_full(0));
+
+        let info = ContactInfo {
+            id: Pubkey::new_rand(),
+            storage_addr: addr,
+            ..ContactInfo::default()
+        };
+        let repair_peers = Mutex::new(RepairPeers::default());
+        let unknown_score = repair_peers.lock().unwrap().score(&info.id);
+        assert_eq!(
+            Archiver::download_from_archiver(&info, &blocktree, &repair_peers).unwrap(),
+            0
+        );
+        // the archiver answered, so it's preferred over ones never downloaded from
+        assert!(repair_peers.lock().unwrap().score(&info.id) > unknown_score);
+        assert_eq!(
+            Archiver::select_archiver(&[], &repair_peers).map(|archiver| archiver.id),
+            None
+        );
+        assert_eq!(
+            Archiver::select_archiver(&[info.clone()], &repair_peers).map(|archiver| archiver.id),
+            Some(info.id)
+        );
+        for slot in 0..slots_per_segment {
+            assert!(blocktree.is_full(slot));
+            assert_eq!(
+                blocktree.get_slot_entries(slot, 0, None).unwrap(),
+                source.get_slot_entries(slot, 0, None).unwrap()
+            );
+        }
+
+        exit.store(true, Ordering::Relaxed);
+        request_processor
+            .into_iter()
+            .for_each(|t| t.join().unwrap());
+        drop(source);
+        drop(blocktree);
+        Blocktree::destroy(&source_path).unwrap();
+        Blocktree::destroy(&ledger_path).unwrap();
+    }
 }
@@ -0,0 +1,530 @@
+//! The `archiver_client` module defines the cluster access an archiver needs, so an archiver
+//! can be embedded with a custom transport or run against a mock cluster, along with the
+//! default implementation that finds RPC and TPU peers through gossip.
+
+use crate::{
+    cluster_info::ClusterInfo,
+    contact_info::ContactInfo,
+    crds_value::StorageTurn,
+    gossip_service::get_client,
+    result::{Error, RpcError},
+    weighted_shuffle::{random_seed, sorted_stakes_with_index, weighted_best},
+};
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_request::{RpcRequest, RpcSegmentConfig},
+    thin_client::ThinClient,
+};
+use solana_runtime::storage_utils::select_storage_turn;
+use solana_sdk::{
+    account::Account,
+    client::{AsyncClient, SyncClient},
+    clock::Slot,
+    commitment_config::CommitmentConfig,
+    fee_calculator::FeeCalculator,
+    hash::Hash,
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    transaction::Transaction,
+    transport::Result as TransportResult,
+};
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    io,
+    net::SocketAddr,
+    result,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+/// The cluster queries and transactions an archiver makes
+pub trait ArchiverClusterClient: Send + Sync {
+    fn get_segment_config(
+        &self,
+        commitment: CommitmentConfig,
+    ) -> result::Result<RpcSegmentConfig, Error>;
+
+    /// The blockhash and slot of the storage turn committed on chain by the cluster's
+    /// validators, fails with `RpcError::NoPeers` while the cluster can't be reached yet
+    fn get_storage_turn(&self, commitment: CommitmentConfig)
+        -> result::Result<(Hash, Slot), Error>;
+
+    /// The leader of every slot of the segment containing `slot`, starting with its first slot
+    fn get_segment_leaders(
+        &self,
+        slot: Slot,
+        commitment: CommitmentConfig,
+    ) -> result::Result<Vec<Pubkey>, Error>;
+
+    fn get_account(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> TransportResult<Option<Account>>;
+
+    fn get_balance(&self, pubkey: &Pubkey, commitment: CommitmentConfig) -> TransportResult<u64>;
+
+    /// Poll for the balance of `pubkey` until `timeout` elapses
+    fn poll_balance(
+        &self,
+        pubkey: &Pubkey,
+        timeout: Duration,
+        commitment: CommitmentConfig,
+    ) -> io::Result<u64>;
+
+    fn get_recent_blockhash(
+        &self,
+        commitment: CommitmentConfig,
+    ) -> TransportResult<(Hash, FeeCalculator)>;
+
+    fn async_send_transaction(&self, transaction: Transaction) -> io::Result<Signature>;
+
+    fn poll_for_signature(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentConfig,
+    ) -> TransportResult<()>;
+
+    /// Send `transaction`, re-signing it with `keypairs` and a new blockhash up to `tries`
+    /// times until it is confirmed
+    fn send_and_confirm_transaction(
+        &self,
+        keypairs: &[&Keypair],
+        transaction: &mut Transaction,
+        tries: usize,
+    ) -> io::Result<Signature>;
+
+    /// Sign `message` with a recent blockhash, send it and wait for its confirmation
+    fn send_message(&self, keypairs: &[&Keypair], message: Message) -> TransportResult<Signature>;
+}
+
+/// Where `GossipClusterClient` learns the current storage turn from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StorageTurnSource {
+    /// Ask a randomly picked RPC peer for every poll
+    Rpc,
+    /// Use the turn advertised in gossip by the validators with the most stake at their
+    /// latest root, and only ask an RPC peer for the turn while no staked validator
+    /// advertises one. Node stakes are fetched from an RPC peer. The requested commitment
+    /// is not applied to gossiped turns, which are always rooted.
+    Gossip,
+}
+
+impl Default for StorageTurnSource {
+    fn default() -> Self {
+        StorageTurnSource::Rpc
+    }
+}
+
+/// How long the node stakes weighing gossiped storage turns are reused before they are
+/// fetched again, they only change at epoch boundaries
+const NODE_STAKES_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The turn advertised by the validators with the most stake, see `select_storage_turn()`.
+/// Turns from unstaked nodes are ignored, anyone can join gossip under many identities.
+fn select_gossip_storage_turn(
+    turns: &[StorageTurn],
+    node_stakes: &HashMap<Pubkey, u64>,
+) -> Option<(Hash, Slot)> {
+    select_storage_turn(turns.iter().map(|turn| {
+        let stake = node_stakes.get(&turn.from).cloned().unwrap_or(0);
+        (turn.blockhash, turn.slot, stake)
+    }))
+}
+
+/// Sends RPC requests and transactions to randomly picked peers found through gossip
+pub struct GossipClusterClient {
+    cluster_info: Arc<RwLock<ClusterInfo>>,
+    // nodes found when joining the cluster, used until gossip has discovered peers itself
+    entrypoint_nodes: Vec<ContactInfo>,
+    // the only node used for RPC and transactions, when not picking peers from gossip
+    direct_node: Option<ContactInfo>,
+    storage_turn_source: StorageTurnSource,
+    // node stakes of the current epoch and when they were fetched
+    node_stakes: RwLock<Option<(Instant, HashMap<Pubkey, u64>)>>,
+}
+
+impl GossipClusterClient {
+    pub fn new(cluster_info: Arc<RwLock<ClusterInfo>>, entrypoint_nodes: Vec<ContactInfo>) -> Self {
+        Self {
+            cluster_info,
+            entrypoint_nodes,
+            direct_node: None,
+            storage_turn_source: StorageTurnSource::default(),
+            node_stakes: RwLock::new(None),
+        }
+    }
+
+    /// A client that always sends RPC requests to `rpc_addr` and transactions to `tpu_addr`,
+    /// without discovering the cluster first
+    pub fn new_direct(
+        cluster_info: Arc<RwLock<ClusterInfo>>,
+        rpc_addr: SocketAddr,
+        tpu_addr: SocketAddr,
+    ) -> Self {
+        Self {
+            cluster_info,
+            entrypoint_nodes: vec![],
+            direct_node: Some(ContactInfo {
+                rpc: rpc_addr,
+                tpu: tpu_addr,
+                ..ContactInfo::default()
+            }),
+            storage_turn_source: StorageTurnSource::default(),
+            node_stakes: RwLock::new(None),
+        }
+    }
+
+    pub fn storage_turn_source(mut self, storage_turn_source: StorageTurnSource) -> Self {
+        self.storage_turn_source = storage_turn_source;
+        self
+    }
+
+    fn thin_client(&self) -> ThinClient {
+        if let Some(direct_node) = &self.direct_node {
+            return get_client(&[direct_node.clone()]);
+        }
+        let nodes = self.cluster_info.read().unwrap().tvu_peers();
+        if nodes.is_empty() {
+            get_client(&self.entrypoint_nodes)
+        } else {
+            get_client(&nodes)
+        }
+    }
+
+    fn rpc_client(&self) -> result::Result<RpcClient, Error> {
+        Ok(RpcClient::new_socket(self.rpc_addr()?))
+    }
+
+    fn rpc_addr(&self) -> result::Result<SocketAddr, Error> {
+        if let Some(direct_node) = &self.direct_node {
+            return Ok(direct_node.rpc);
+        }
+        let rpc_peers = self.cluster_info.read().unwrap().rpc_peers();
+        debug!("rpc peers: {:?}", rpc_peers);
+        if rpc_peers.is_empty() {
+            return Err(RpcError::NoPeers.into());
+        }
+        // archivers don't track stakes, so every peer is weighted the same
+        let ids: Vec<_> = rpc_peers.iter().map(|peer| peer.id).collect();
+        let stakes_and_index = sorted_stakes_with_index::<RandomState>(&ids, None);
+        let node_index = weighted_best(&stakes_and_index, random_seed());
+        Ok(rpc_peers[node_index].rpc)
+    }
+
+    /// The activated stake of every voting node, refreshed from an RPC peer once the cached
+    /// stakes are older than `NODE_STAKES_REFRESH_INTERVAL`
+    fn node_stakes(&self) -> result::Result<HashMap<Pubkey, u64>, Error> {
+        if let Some((fetched, node_stakes)) = &*self.node_stakes.read().unwrap() {
+            if fetched.elapsed() < NODE_STAKES_REFRESH_INTERVAL {
+                return Ok(node_stakes.clone());
+            }
+        }
+        let vote_accounts = self.rpc_client()?.get_vote_accounts().map_err(|err| {
+            warn!("Error while fetching vote accounts {:?}", err);
+            RpcError::RequestFailed {
+                request: format!("{:?}", RpcRequest::GetVoteAccounts),
+                reason: format!("{:?}", err),
+            }
+        })?;
+        let mut node_stakes = HashMap::new();
+        for vote_account in vote_accounts
+            .current
+            .iter()
+            .chain(vote_accounts.delinquent.iter())
+        {
+            if let Ok(node_pubkey) = vote_account.node_pubkey.parse() {
+                *node_stakes.entry(node_pubkey).or_insert(0) += vote_account.activated_stake;
+            }
+        }
+        *self.node_stakes.write().unwrap() = Some((Instant::now(), node_stakes.clone()));
+        Ok(node_stakes)
+    }
+
+    fn send(
+        &self,
+        request: &RpcRequest,
+        params: Option<serde_json::Value>,
+        commitment: Option<CommitmentConfig>,
+    ) -> result::Result<serde_json::Value, Error> {
+        Ok(self
+            .rpc_client()?
+            .send(request, params, 0, commitment)
+            .map_err(|err| {
+                warn!("Error while making rpc request {:?}", err);
+                RpcError::RequestFailed {
+                    request: format!("{:?}", request),
+                    reason: format!("{:?}", err),
+                }
+            })?)
+    }
+}
+
+impl ArchiverClusterClient for GossipClusterClient {
+    fn get_segment_config(
+        &self,
+        commitment: CommitmentConfig,
+    ) -> result::Result<RpcSegmentConfig, Error> {
+        let request = RpcRequest::GetSegmentConfig;
+        let response = self.send(&request, None, Some(commitment))?;
+        Ok(
+            serde_json::from_value(response).map_err(|err| RpcError::InvalidResponse {
+                request: format!("{:?}", request),
+                reason: format!("Couldn't parse response: {:?}", err),
+            })?,
+        )
+    }
+
+    fn get_storage_turn(
+        &self,
+        commitment: CommitmentConfig,
+    ) -> result::Result<(Hash, Slot), Error> {
+        if self.storage_turn_source == StorageTurnSource::Gossip {
+            let turns = self.cluster_info.read().unwrap().storage_turns();
+            if !turns.is_empty() {
+                match self.node_stakes() {
+                    Ok(node_stakes) => {
+                        if let Some(turn) = select_gossip_storage_turn(&turns, &node_stakes) {
+                            inc_new_counter_debug!("archiver-storage_turn-gossip", 1);
+                            return Ok(turn);
+                        }
+                    }
+                    Err(err) => debug!("no node stakes to weigh gossiped turns: {:?}", err),
+                }
+            }
+            debug!("no staked storage turn in gossip, asking an rpc peer");
+            inc_new_counter_info!("archiver-storage_turn-rpc_fallback", 1);
+        }
+        let request = RpcRequest::GetStorageTurn;
+        let response = self.send(&request, None, Some(commitment))?;
+        let (storage_blockhash, turn_slot) = serde_json::from_value::<(String, u64)>(response)
+            .map_err(|err| RpcError::InvalidResponse {
+                request: format!("{:?}", request),
+                reason: format!("Couldn't parse response: {:?}", err),
+            })?;
+        let turn_blockhash =
+            storage_blockhash
+                .parse()
+                .map_err(|err| RpcError::InvalidResponse {
+                    request: format!("{:?}", request),
+                    reason: format!(
+                        "Blockhash parse failure: {:?} on {:?}",
+                        err, storage_blockhash
+                    ),
+                })?;
+        Ok((turn_blockhash, turn_slot))
+    }
+
+    fn get_segment_leaders(
+        &self,
+        slot: Slot,
+        commitment: CommitmentConfig,
+    ) -> result::Result<Vec<Pubkey>, Error> {
+        let request = RpcRequest::GetSegmentLeaders;
+        let response = self.send(&request, Some(json!(slot)), Some(commitment))?;
+        let invalid_response = |reason: String| RpcError::InvalidResponse {
+            request: format!("{:?}", request),
+            reason,
+        };
+        serde_json::from_value::<Vec<String>>(response)
+            .map_err(|err| invalid_response(format!("Couldn't parse response: {:?}", err)))?
+            .iter()
+            .map(|leader| {
+                leader.parse().map_err(|err| {
+                    Error::from(invalid_response(format!(
+                        "Pubkey parse failure: {:?} on {:?}",
+                        err, leader
+                    )))
+                })
+            })
+            .collect()
+    }
+
+    fn get_account(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> TransportResult<Option<Account>> {
+        self.thin_client()
+            .get_account_with_commitment(pubkey, commitment)
+    }
+
+    fn get_balance(&self, pubkey: &Pubkey, commitment: CommitmentConfig) -> TransportResult<u64> {
+        self.thin_client()
+            .get_balance_with_commitment(pubkey, commitment)
+    }
+
+    fn poll_balance(
+        &self,
+        pubkey: &Pubkey,
+        timeout: Duration,
+        commitment: CommitmentConfig,
+    ) -> io::Result<u64> {
+        self.thin_client().poll_balance_with_timeout_and_commitment(
+            pubkey,
+            &Duration::from_millis(100),
+            &timeout,
+            commitment,
+        )
+    }
+
+    fn get_recent_blockhash(
+        &self,
+        commitment: CommitmentConfig,
+    ) -> TransportResult<(Hash, FeeCalculator)> {
+        self.thin_client()
+            .get_recent_blockhash_with_commitment(commitment)
+    }
+
+    fn async_send_transaction(&self, transaction: Transaction) -> io::Result<Signature> {
+        self.thin_client().async_send_transaction(transaction)
+    }
+
+    fn poll_for_signature(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentConfig,
+    ) -> TransportResult<()> {
+        self.thin_client()
+            .poll_for_signature_with_commitment(signature, commitment)
+    }
+
+    fn send_and_confirm_transaction(
+        &self,
+        keypairs: &[&Keypair],
+        transaction: &mut Transaction,
+        tries: usize,
+    ) -> io::Result<Signature> {
+        self.thin_client()
+            .send_and_confirm_transaction(keypairs, transaction, tries, 0)
+    }
+
+    fn send_message(&self, keypairs: &[&Keypair], message: Message) -> TransportResult<Signature> {
+        self.thin_client().send_message(keypairs, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crds_value::{CrdsData, CrdsValue};
+    use solana_sdk::{hash::hash, signature::KeypairUtil, timing::timestamp};
+
+    fn storage_turn(blockhash: Hash, slot: Slot) -> StorageTurn {
+        StorageTurn::new(Pubkey::new_rand(), blockhash, slot, timestamp())
+    }
+
+    #[test]
+    fn test_select_gossip_storage_turn() {
+        let mut node_stakes = HashMap::new();
+        assert_eq!(select_gossip_storage_turn(&[], &node_stakes), None);
+        let turn = storage_turn(Hash::default(), 8);
+        node_stakes.insert(turn.from, 1);
+        assert_eq!(select_gossip_storage_turn(&[turn], &node_stakes), None);
+
+        let (hash0, hash1) = (hash(&[0]), hash(&[1]));
+        let mut turns = vec![storage_turn(hash0, 8), storage_turn(hash1, 16)];
+        // unstaked nodes aren't counted
+        assert_eq!(select_gossip_storage_turn(&turns, &node_stakes), None);
+        node_stakes.insert(turns[0].from, 2);
+        node_stakes.insert(turns[1].from, 2);
+        // the latest turn wins a tie
+        assert_eq!(
+            select_gossip_storage_turn(&turns, &node_stakes),
+            Some((hash1, 16))
+        );
+        // many unstaked nodes can't outvote a staked one
+        turns.extend((0..8).map(|_| storage_turn(hash0, 8)));
+        assert_eq!(
+            select_gossip_storage_turn(&turns, &node_stakes),
+            Some((hash1, 16))
+        );
+        node_stakes.insert(turns[2].from, 1);
+        assert_eq!(
+            select_gossip_storage_turn(&turns, &node_stakes),
+            Some((hash0, 8))
+        );
+    }
+
+    #[test]
+    fn test_direct_client() {
+        let keypair = Keypair::new();
+        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+            ContactInfo::new_localhost(&keypair.pubkey(), timestamp()),
+        )));
+        let client = GossipClusterClient::new(cluster_info.clone(), vec![]);
+        assert_matches!(client.rpc_addr(), Err(Error::RpcError(RpcError::NoPeers)));
+
+        // A direct client needs no rpc peer in gossip
+        let rpc_addr = socketaddr!("127.0.0.1:8899");
+        let tpu_addr = socketaddr!("127.0.0.1:8003");
+        let client = GossipClusterClient::new_direct(cluster_info, rpc_addr, tpu_addr);
+        assert_eq!(client.rpc_addr().unwrap(), rpc_addr);
+        assert_eq!(
+            client
+                .direct_node
+                .as_ref()
+                .unwrap()
+                .valid_client_facing_addr(),
+            Some((rpc_addr, tpu_addr))
+        );
+    }
+
+    #[test]
+    fn test_gossip_storage_turn_source() {
+        let keypair = Keypair::new();
+        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+            ContactInfo::new_localhost(&keypair.pubkey(), timestamp()),
+        )));
+        let client = GossipClusterClient::new(cluster_info.clone(), vec![])
+            .storage_turn_source(StorageTurnSource::Gossip);
+
+        // without a turn in gossip the client falls back to rpc, and there is no rpc peer
+        assert_matches!(
+            client.get_storage_turn(CommitmentConfig::default()),
+            Err(Error::RpcError(RpcError::NoPeers))
+        );
+
+        let validator_keypair = Keypair::new();
+        let blockhash = hash(&[1]);
+        let value = CrdsValue::new_signed(
+            CrdsData::StorageTurn(StorageTurn::new(
+                validator_keypair.pubkey(),
+                blockhash,
+                16,
+                timestamp(),
+            )),
+            &validator_keypair,
+        );
+        cluster_info
+            .write()
+            .unwrap()
+            .gossip
+            .crds
+            .insert(value, timestamp())
+            .unwrap();
+        // the turn of an unstaked node isn't used
+        *client.node_stakes.write().unwrap() = Some((Instant::now(), HashMap::new()));
+        assert_matches!(
+            client.get_storage_turn(CommitmentConfig::default()),
+            Err(Error::RpcError(RpcError::NoPeers))
+        );
+        let node_stakes = vec![(validator_keypair.pubkey(), 1)].into_iter().collect();
+        *client.node_stakes.write().unwrap() = Some((Instant::now(), node_stakes));
+        assert_eq!(
+            client
+                .get_storage_turn(CommitmentConfig::default())
+                .unwrap(),
+            (blockhash, 16)
+        );
+
+        // the rpc source ignores gossip
+        let client = GossipClusterClient::new(cluster_info, vec![])
+            .storage_turn_source(StorageTurnSource::Rpc);
+        assert_matches!(
+            client.get_storage_turn(CommitmentConfig::default()),
+            Err(Error::RpcError(RpcError::NoPeers))
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! The `archiver_proof_log` module keeps a local history of the mining proofs an archiver
+//! submitted and of the validations and rewards they earned, and summarizes it per segment so
+//! operators can tell when their proofs go unvalidated.
+
+use solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Signature, timing::timestamp};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Name of the proof log in the archiver's ledger directory
+pub const PROOF_LOG_FILENAME: &str = "proof_log.jsonl";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ProofLogEntry {
+    /// A mining proof was submitted, `result` is the error if its transaction didn't confirm
+    Submitted {
+        timestamp: u64,
+        segment: u64,
+        sha_state: Hash,
+        signature: Signature,
+        /// The blockhash the proof sampled the segment with, missing from older logs
+        #[serde(default)]
+        blockhash: Hash,
+        transaction_signature: Option<Signature>,
+        result: Result<(), String>,
+    },
+    /// The storage account's proofs for `segment` were validated by `validator`, whether or not
+    /// their reward was claimed
+    Validated {
+        timestamp: u64,
+        segment: u64,
+        validator: Pubkey,
+        valid: u64,
+        invalid: u64,
+        estimated_reward: u64,
+    },
+}
+
+impl ProofLogEntry {
+    pub fn segment(&self) -> u64 {
+        match self {
+            ProofLogEntry::Submitted { segment, .. } => *segment,
+            ProofLogEntry::Validated { segment, .. } => *segment,
+        }
+    }
+
+    pub fn new_submitted(
+        segment: u64,
+        sha_state: Hash,
+        signature: Signature,
+        blockhash: Hash,
+        transaction_result: Result<Signature, String>,
+    ) -> Self {
+        let (transaction_signature, result) = match transaction_result {
+            Ok(transaction_signature) => (Some(transaction_signature), Ok(())),
+            Err(err) => (None, Err(err)),
+        };
+        ProofLogEntry::Submitted {
+            timestamp: timestamp(),
+            segment,
+            sha_state,
+            signature,
+            blockhash,
+            transaction_signature,
+            result,
+        }
+    }
+
+    pub fn new_validated(
+        segment: u64,
+        validator: Pubkey,
+        valid: u64,
+        invalid: u64,
+        estimated_reward: u64,
+    ) -> Self {
+        ProofLogEntry::Validated {
+            timestamp: timestamp(),
+            segment,
+            validator,
+            valid,
+            invalid,
+            estimated_reward,
+        }
+    }
+}
+
+/// Appends entries to a file, one JSON object per line
+pub struct ProofLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl ProofLog {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, entry: &ProofLogEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&line)?;
+        file.flush()
+    }
+
+    /// Read back every entry of the log at `path`, a partially written last line is skipped
+    pub fn read(path: &Path) -> io::Result<Vec<ProofLogEntry>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut entries = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => warn!("skipping unreadable proof log entry: {:?}", err),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Proof history of a single segment
+#[derive(Default, Debug, PartialEq)]
+pub struct SegmentProofReport {
+    pub submitted: u64,
+    pub failed: u64,
+    pub valid: u64,
+    pub invalid: u64,
+    pub estimated_reward: u64,
+    pub validators: BTreeSet<Pubkey>,
+}
+
+impl SegmentProofReport {
+    /// Fraction of the confirmed proofs that were found valid
+    pub fn acceptance_rate(&self) -> f64 {
+        let confirmed = self.submitted - self.failed;
+        if confirmed == 0 {
+            0.0
+        } else {
+            self.valid as f64 / confirmed as f64
+        }
+    }
+}
+
+#[derive(Default, Debug, PartialEq)]
+pub struct ProofReport {
+    pub segments: BTreeMap<u64, SegmentProofReport>,
+}
+
+impl ProofReport {
+    pub fn new(entries: &[ProofLogEntry]) -> Self {
+        let mut segments: BTreeMap<u64, SegmentProofReport> = BTreeMap::new();
+        for entry in entries {
+            let report = segments.entry(entry.segment()).or_default();
+            match entry {
+                ProofLogEntry::Submitted { result, .. } => {
+                    report.submitted += 1;
+                    if result.is_err() {
+                        report.failed += 1;
+                    }
+                }
+                ProofLogEntry::Validated {
+                    validator,
+                    valid,
+                    invalid,
+                    estimated_reward,
+                    ..
+                } => {
+                    report.valid += valid;
+                    report.invalid += invalid;
+                    report.estimated_reward += estimated_reward;
+                    report.validators.insert(*validator);
+                }
+            }
+        }
+        Self { segments }
+    }
+
+    /// Segments whose confirmed proofs were never validated by anyone
+    pub fn unvalidated_segments(&self) -> Vec<u64> {
+        self.segments
+            .iter()
+            .filter(|(_, report)| report.submitted > report.failed && report.validators.is_empty())
+            .map(|(segment, _)| *segment)
+            .collect()
+    }
+}
+
+impl fmt::Display for ProofReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>10} {:>10} {:>8} {:>8} {:>8} {:>10} {:>12} {:>10}",
+            "segment",
+            "submitted",
+            "failed",
+            "valid",
+            "invalid",
+            "accepted",
+            "reward",
+            "validators"
+        )?;
+        for (segment, report) in &self.segments {
+            writeln!(
+                f,
+                "{:>10} {:>10} {:>8} {:>8} {:>8} {:>9.1}% {:>12} {:>10}",
+                segment,
+                report.submitted,
+                report.failed,
+                report.valid,
+                report.invalid,
+                report.acceptance_rate() * 100.0,
+                report.estimated_reward,
+                report.validators.len()
+            )?;
+        }
+        let unvalidated = self.unvalidated_segments();
+        if !unvalidated.is_empty() {
+            writeln!(f, "never validated: {:?}", unvalidated)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    #[test]
+    fn test_proof_log() {
+        let out_dir = env::var("FARF_DIR").unwrap_or_else(|_| "farf".to_string());
+        fs::create_dir_all(&out_dir).unwrap();
+        let path = PathBuf::from(format!("{}/test_proof_log-{}", out_dir, Pubkey::new_rand()));
+        let validator = Pubkey::new_rand();
+
+        let entries = vec![
+            ProofLogEntry::new_submitted(
+                1,
+                Hash::default(),
+                Signature::default(),
+                Hash::default(),
+                Ok(Signature::default()),
+            ),
+            ProofLogEntry::new_submitted(
+                1,
+                Hash::default(),
+                Signature::default(),
+                Hash::default(),
+                Err("timeout".to_string()),
+            ),
+            ProofLogEntry::new_submitted(
+                1,
+                Hash::default(),
+                Signature::default(),
+                Hash::default(),
+                Ok(Signature::default()),
+            ),
+            ProofLogEntry::new_validated(1, validator, 1, 1, 10),
+            ProofLogEntry::new_submitted(
+                2,
+                Hash::default(),
+                Signature::default(),
+                Hash::default(),
+                Ok(Signature::default()),
+            ),
+        ];
+        {
+            let proof_log = ProofLog::open(&path).unwrap();
+            entries[..2]
+                .iter()
+                .for_each(|entry| proof_log.append(entry).unwrap());
+        }
+        // reopening appends
+        let proof_log = ProofLog::open(&path).unwrap();
+        entries[2..]
+            .iter()
+            .for_each(|entry| proof_log.append(entry).unwrap());
+        assert_eq!(ProofLog::read(&path).unwrap(), entries);
+
+        // Entries written before blockhashes were recorded still read back
+        let entry: ProofLogEntry = serde_json::from_str(
+            &serde_json::to_string(&entries[0])
+                .unwrap()
+                .replace(&format!(",\"blockhash\":\"{}\"", Hash::default()), ""),
+        )
+        .unwrap();
+        assert_eq!(entry, entries[0]);
+
+        let report = ProofReport::new(&entries);
+        let segment = &report.segments[&1];
+        assert_eq!(segment.submitted, 3);
+        assert_eq!(segment.failed, 1);
+        assert_eq!(segment.estimated_reward, 10);
+        assert_eq!(segment.acceptance_rate(), 0.5);
+        assert_eq!(report.unvalidated_segments(), vec![2]);
+        assert!(report.to_string().contains("never validated: [2]"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
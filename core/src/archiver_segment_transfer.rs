@@ -0,0 +1,264 @@
+//! The `archiver_segment_transfer` module lets archivers replicate segments from each other
+//! over the storage socket. The serving archiver answers `ArchiverRequest::GetSegmentManifest`
+//! and `ArchiverRequest::GetShredRange`, the downloading one fetches each slot in windows of
+//! shreds starting at the first shred its blocktree is missing, so an interrupted download
+//! resumes where it stopped.
+
+use crate::{
+    archiver::ArchiverRequest,
+    cluster_info::VALIDATOR_PORT_RANGE,
+    contact_info::ContactInfo,
+    packet::PACKET_DATA_SIZE,
+    repair_peers::RepairPeers,
+    result::{Result, StorageError},
+};
+use solana_ledger::{blocktree::Blocktree, shred::Shred};
+use solana_net_utils::{bind_in_range_with_ip, unspecified_ip_for};
+use solana_perf::packet::Packets;
+use solana_sdk::{clock::Slot, packet::Packet};
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Most shreds an archiver sends in answer to a single `GetShredRange` request
+pub const SHRED_RANGE_WINDOW: u64 = 64;
+
+/// How long the downloader waits for a window of shreds before asking again
+const SHRED_RANGE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Consecutive requests that may go unanswered before a download gives up
+const MAX_STALLED_REQUESTS: usize = 10;
+
+/// Describes the segment an archiver is storing, answered to
+/// `ArchiverRequest::GetSegmentManifest`
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SegmentManifest {
+    /// First slot of the segment
+    pub start_slot: Slot,
+    pub slots_per_segment: u64,
+    /// Number of slots of the segment the archiver has every shred of
+    pub complete_slots: u64,
+}
+
+impl SegmentManifest {
+    pub fn new(blocktree: &Blocktree, start_slot: Slot, slots_per_segment: u64) -> Self {
+        let complete_slots = (start_slot..start_slot + slots_per_segment)
+            .filter(|slot| blocktree.is_full(*slot))
+            .count() as u64;
+        Self {
+            start_slot,
+            slots_per_segment,
+            complete_slots,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete_slots == self.slots_per_segment
+    }
+
+    fn slots(&self) -> std::ops::Range<Slot> {
+        self.start_slot..self.start_slot + self.slots_per_segment
+    }
+}
+
+/// Answers a `GetShredRange` request with the data shreds of `slot` starting at `start_index`,
+/// one shred per packet, stopping at the first shred the blocktree doesn't have
+pub fn shred_range_packets(
+    blocktree: &Blocktree,
+    slot: Slot,
+    start_index: u64,
+    count: u64,
+    dest: &SocketAddr,
+) -> Packets {
+    let count = count.min(SHRED_RANGE_WINDOW);
+    let packets = (start_index..start_index.saturating_add(count))
+        .map(|index| blocktree.get_data_shred(slot, index))
+        .take_while(|shred| match shred {
+            Ok(Some(_)) => true,
+            _ => false,
+        })
+        .filter_map(|shred| shred.ok().and_then(|shred| shred))
+        .map(|data| {
+            let mut packet = Packet::default();
+            packet.meta.size = data.len();
+            packet.meta.set_addr(dest);
+            packet.data[..data.len()].copy_from_slice(&data);
+            packet
+        })
+        .collect();
+    Packets::new(packets)
+}
+
+/// Ask the archiver listening on storage socket `to` for its `SegmentManifest`, `None` if it
+/// doesn't answer
+pub fn request_segment_manifest(to: SocketAddr) -> Option<SegmentManifest> {
+    let (_port, socket) =
+        bind_in_range_with_ip(unspecified_ip_for(&to.ip()), VALIDATOR_PORT_RANGE).ok()?;
+    socket.set_read_timeout(Some(SHRED_RANGE_TIMEOUT)).ok()?;
+
+    let req = ArchiverRequest::GetSegmentManifest(socket.local_addr().ok()?);
+    let serialized_req = req.to_bytes();
+    for _ in 0..MAX_STALLED_REQUESTS {
+        socket.send_to(&serialized_req, to).ok()?;
+        let mut buf = [0; PACKET_DATA_SIZE];
+        if let Ok((size, _addr)) = socket.recv_from(&mut buf) {
+            // Ignore bad packet and try again
+            if let Ok(manifest) = bincode::config()
+                .limit(PACKET_DATA_SIZE as u64)
+                .deserialize(&buf[..size])
+            {
+                return Some(manifest);
+            }
+        }
+    }
+    None
+}
+
+/// Download the segment stored by `archiver_info` into `blocktree`. Shreds already in
+/// `blocktree` are not requested again. How the archiver answers is recorded in `repair_peers`.
+/// Returns the manifest of the downloaded segment.
+///
+/// Shreds are inserted without signature verification, so it is recommended to use a temporary
+/// blocktree for this
+pub fn download_segment(
+    archiver_info: &ContactInfo,
+    blocktree: &Blocktree,
+    repair_peers: &Mutex<RepairPeers>,
+) -> Result<SegmentManifest> {
+    let to = archiver_info.storage_addr;
+    let manifest = request_segment_manifest(to).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("archiver {} didn't answer the segment manifest request", to),
+        )
+    })?;
+    info!("Archiver download: {:?} from {}", manifest, to);
+    if !manifest.is_complete() {
+        warn!(
+            "archiver {} only has {}/{} slots of its segment",
+            to, manifest.complete_slots, manifest.slots_per_segment
+        );
+    }
+
+    let (_port, socket) =
+        bind_in_range_with_ip(unspecified_ip_for(&to.ip()), VALIDATOR_PORT_RANGE)?;
+    let from = socket.local_addr()?;
+    let mut stalled_requests = 0;
+    for slot in manifest.slots() {
+        loop {
+            let next_index = match blocktree.meta(slot)? {
+                Some(meta) if meta.is_full() => break,
+                Some(meta) => meta.consumed,
+                None => 0,
+            };
+            let req = ArchiverRequest::GetShredRange {
+                from,
+                slot,
+                start_index: next_index,
+                count: SHRED_RANGE_WINDOW,
+            };
+            socket.send_to(&req.to_bytes(), to)?;
+            repair_peers
+                .lock()
+                .unwrap()
+                .record_request(archiver_info.id, to);
+
+            let shreds = receive_shred_window(&socket, slot, SHRED_RANGE_WINDOW as usize)?;
+            if shreds.is_empty() {
+                stalled_requests += 1;
+                if stalled_requests >= MAX_STALLED_REQUESTS {
+                    return Err(StorageError::SegmentIncomplete {
+                        start_slot: manifest.start_slot,
+                        slots_per_segment: manifest.slots_per_segment,
+                    }
+                    .into());
+                }
+                continue;
+            }
+            stalled_requests = 0;
+            repair_peers.lock().unwrap().record_response(&to);
+            datapoint_debug!(
+                "archiver-segment-transfer",
+                ("slot", slot as i64, i64),
+                ("start_index", next_index as i64, i64),
+                ("num_shreds", shreds.len() as i64, i64),
+            );
+            blocktree.insert_shreds(shreds, None, false)?;
+        }
+    }
+    Ok(manifest)
+}
+
+/// Collect the data shreds of `slot` until `window` of them or the last shred of the slot
+/// arrived, or the window timed out
+fn receive_shred_window(socket: &UdpSocket, slot: Slot, window: usize) -> Result<Vec<Shred>> {
+    let deadline = Instant::now() + SHRED_RANGE_TIMEOUT;
+    let mut shreds = vec![];
+    let mut buf = [0; PACKET_DATA_SIZE];
+    while shreds.len() < window {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        socket.set_read_timeout(Some(deadline - now))?;
+        match socket.recv_from(&mut buf) {
+            Ok((size, _addr)) => {
+                if let Ok(shred) = Shred::new_from_serialized_shred(buf[..size].to_vec()) {
+                    if shred.slot() == slot && shred.is_data() {
+                        let last_in_slot = shred.last_in_slot();
+                        shreds.push(shred);
+                        if last_in_slot {
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                break
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(shreds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_ledger::{blocktree::make_slot_entries, get_tmp_ledger_path};
+
+    #[test]
+    fn test_shred_range_packets() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&ledger_path).unwrap();
+            let (shreds, _) = make_slot_entries(1, 0, 2000);
+            let num_shreds = shreds.len() as u64;
+            assert!(num_shreds > SHRED_RANGE_WINDOW);
+            blocktree.insert_shreds(shreds, None, false).unwrap();
+            let dest = socketaddr!("127.0.0.1:1234");
+
+            // The window caps how many shreds are answered
+            let packets = shred_range_packets(&blocktree, 1, 0, std::u64::MAX, &dest);
+            assert_eq!(packets.packets.len() as u64, SHRED_RANGE_WINDOW);
+            let packets = shred_range_packets(&blocktree, 1, num_shreds - 2, 10, &dest);
+            assert_eq!(packets.packets.len(), 2);
+            let shred = Shred::new_from_serialized_shred(packets.packets[1].data.to_vec()).unwrap();
+            assert_eq!(shred.index() as u64, num_shreds - 1);
+            assert_eq!(packets.packets[1].meta.addr(), dest);
+            assert!(shred_range_packets(&blocktree, 2, 0, 10, &dest)
+                .packets
+                .is_empty());
+
+            let manifest = SegmentManifest::new(&blocktree, 0, 2);
+            assert_eq!(manifest.complete_slots, 1);
+            assert!(!manifest.is_complete());
+        }
+        Blocktree::destroy(&ledger_path).unwrap();
+    }
+}
@@ -0,0 +1,299 @@
+//! The `archiver_storage` module defines where an archiver keeps the encrypted copy of its
+//! segment. The segment is encrypted to local files that are handed to a `StorageBackend`,
+//! mining proofs then sample it back through the backend.
+
+use crate::object_store::ObjectStore;
+use memmap::Mmap;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+/// Name of the encrypted segment
+pub const ENCRYPTED_FILENAME: &str = "ledger.enc";
+/// Name of the integrity tags of the encrypted segment
+pub const ENCRYPTED_TAGS_FILENAME: &str = "ledger.enc.tags";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SegmentObject {
+    /// The encrypted segment
+    Segment,
+    /// The integrity tags of the encrypted segment
+    Tags,
+}
+
+impl SegmentObject {
+    pub fn name(self) -> &'static str {
+        match self {
+            SegmentObject::Segment => ENCRYPTED_FILENAME,
+            SegmentObject::Tags => ENCRYPTED_TAGS_FILENAME,
+        }
+    }
+}
+
+/// Where an archiver keeps its encrypted segment
+pub trait StorageBackend: Send + Sync {
+    /// Where `object` is kept on the local filesystem, if it is. The segment is encrypted
+    /// straight to this path instead of to a staging file.
+    fn local_path(&self, object: SegmentObject) -> Option<PathBuf>;
+
+    /// Replace `object` with the contents of the file at `path`, called every time the
+    /// segment is encrypted, even when `path` is the object's `local_path()`
+    fn store(&self, object: SegmentObject, path: &Path) -> io::Result<()>;
+
+    /// Size of `object` in bytes
+    fn size(&self, object: SegmentObject) -> io::Result<u64>;
+
+    /// Fill `buf` with the bytes of `object` starting at `offset`
+    fn read_at(&self, object: SegmentObject, offset: u64, buf: &mut [u8]) -> io::Result<()>;
+}
+
+fn copy_into(path: &Path, destination: &Path) -> io::Result<()> {
+    if path != destination {
+        fs::copy(path, destination)?;
+    }
+    Ok(())
+}
+
+/// Keeps the encrypted segment as files in a directory, the ledger directory by default
+pub struct LocalFileBackend {
+    dir: PathBuf,
+}
+
+impl LocalFileBackend {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn path(&self, object: SegmentObject) -> PathBuf {
+        self.dir.join(object.name())
+    }
+}
+
+impl StorageBackend for LocalFileBackend {
+    fn local_path(&self, object: SegmentObject) -> Option<PathBuf> {
+        Some(self.path(object))
+    }
+
+    fn store(&self, object: SegmentObject, path: &Path) -> io::Result<()> {
+        copy_into(path, &self.path(object))
+    }
+
+    fn size(&self, object: SegmentObject) -> io::Result<u64> {
+        Ok(fs::metadata(self.path(object))?.len())
+    }
+
+    fn read_at(&self, object: SegmentObject, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let mut file = File::open(self.path(object))?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(buf)
+    }
+}
+
+/// Keeps the encrypted segment as files in a directory like `LocalFileBackend`, but reads
+/// them through memory maps, for a directory on a fast local device
+pub struct MmapBackend {
+    dir: PathBuf,
+    maps: RwLock<HashMap<SegmentObject, Arc<Mmap>>>,
+}
+
+impl MmapBackend {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            maps: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn path(&self, object: SegmentObject) -> PathBuf {
+        self.dir.join(object.name())
+    }
+
+    fn map(&self, object: SegmentObject) -> io::Result<Arc<Mmap>> {
+        if let Some(map) = self.maps.read().unwrap().get(&object) {
+            return Ok(map.clone());
+        }
+        // The map is dropped by `store()` before the file is read again, the archiver doesn't
+        // sample while it encrypts a new segment
+        let map = Arc::new(unsafe { Mmap::map(&File::open(self.path(object))?)? });
+        self.maps.write().unwrap().insert(object, map.clone());
+        Ok(map)
+    }
+}
+
+impl StorageBackend for MmapBackend {
+    fn local_path(&self, object: SegmentObject) -> Option<PathBuf> {
+        Some(self.path(object))
+    }
+
+    fn store(&self, object: SegmentObject, path: &Path) -> io::Result<()> {
+        self.maps.write().unwrap().remove(&object);
+        copy_into(path, &self.path(object))
+    }
+
+    fn size(&self, object: SegmentObject) -> io::Result<u64> {
+        Ok(fs::metadata(self.path(object))?.len())
+    }
+
+    fn read_at(&self, object: SegmentObject, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let map = self.map(object)?;
+        let start = offset as usize;
+        match start.checked_add(buf.len()) {
+            Some(end) if end <= map.len() => {
+                buf.copy_from_slice(&map[start..end]);
+                Ok(())
+            }
+            _ => Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("read past the end of {}", object.name()),
+            )),
+        }
+    }
+}
+
+/// Keeps the encrypted segment in an S3-compatible object store and reads it back with ranged
+/// GETs, see `ObjectStore` for how requests are authorized
+pub struct ObjectStoreBackend {
+    store: ObjectStore,
+}
+
+impl ObjectStoreBackend {
+    /// `url` is the bucket, and optionally a key prefix, the objects are stored under,
+    /// e.g. `https://s3.amazonaws.com/my-bucket/archiver-1`
+    pub fn new(url: &str) -> Self {
+        Self {
+            store: ObjectStore::new(url),
+        }
+    }
+}
+
+impl StorageBackend for ObjectStoreBackend {
+    fn local_path(&self, _object: SegmentObject) -> Option<PathBuf> {
+        None
+    }
+
+    fn store(&self, object: SegmentObject, path: &Path) -> io::Result<()> {
+        self.store.put_file(object.name(), path)
+    }
+
+    fn size(&self, object: SegmentObject) -> io::Result<u64> {
+        self.store.size(object.name())
+    }
+
+    fn read_at(&self, object: SegmentObject, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.store.read_at(object.name(), offset, buf)
+    }
+}
+
+/// Reads an object of a `StorageBackend` through `Read` and `Seek`
+pub struct ObjectReader<'a> {
+    backend: &'a dyn StorageBackend,
+    object: SegmentObject,
+    size: u64,
+    position: u64,
+}
+
+impl<'a> ObjectReader<'a> {
+    pub fn new(backend: &'a dyn StorageBackend, object: SegmentObject) -> io::Result<Self> {
+        Ok(Self {
+            backend,
+            object,
+            size: backend.size(object)?,
+            position: 0,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl<'a> Read for ObjectReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = (buf.len() as u64).min(self.size.saturating_sub(self.position)) as usize;
+        if len > 0 {
+            self.backend
+                .read_at(self.object, self.position, &mut buf[..len])?;
+            self.position += len as u64;
+        }
+        Ok(len)
+    }
+}
+
+impl<'a> Seek for ObjectReader<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, delta) = match pos {
+            SeekFrom::Start(position) => (position, 0),
+            SeekFrom::End(delta) => (self.size, delta),
+            SeekFrom::Current(delta) => (self.position, delta),
+        };
+        let position = if delta >= 0 {
+            base.checked_add(delta as u64)
+        } else {
+            base.checked_sub(delta.wrapping_neg() as u64)
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "seek to an invalid position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::pubkey::Pubkey;
+    use std::{env, io::Write};
+
+    #[test]
+    fn test_local_backends() {
+        let out_dir = env::var("FARF_DIR").unwrap_or_else(|_| "farf".to_string());
+        let dir = PathBuf::from(format!(
+            "{}/test_local_backends-{}",
+            out_dir,
+            Pubkey::new_rand()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let staging_path = dir.join("staging");
+        let data: Vec<u8> = (0..=255).collect();
+        File::create(&staging_path)
+            .unwrap()
+            .write_all(&data)
+            .unwrap();
+
+        let backends: Vec<Box<dyn StorageBackend>> = vec![
+            Box::new(LocalFileBackend::new(&dir)),
+            Box::new(MmapBackend::new(&dir)),
+        ];
+        for backend in &backends {
+            let object = SegmentObject::Segment;
+            assert_eq!(
+                backend.local_path(object),
+                Some(dir.join(ENCRYPTED_FILENAME))
+            );
+            backend.store(object, &staging_path).unwrap();
+            assert_eq!(backend.size(object).unwrap(), data.len() as u64);
+
+            let mut buf = [0; 4];
+            backend.read_at(object, 10, &mut buf).unwrap();
+            assert_eq!(buf, [10, 11, 12, 13]);
+            assert!(backend.read_at(object, 254, &mut buf).is_err());
+            assert!(backend.read_at(SegmentObject::Tags, 0, &mut buf).is_err());
+
+            let mut reader = ObjectReader::new(backend.as_ref(), object).unwrap();
+            assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 254);
+            assert_eq!(reader.read(&mut buf).unwrap(), 2);
+            assert_eq!(buf[..2], [254, 255]);
+            assert_eq!(reader.read(&mut buf).unwrap(), 0);
+            assert!(reader.seek(SeekFrom::Current(-300)).is_err());
+            fs::remove_file(dir.join(ENCRYPTED_FILENAME)).unwrap();
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -7,6 +7,8 @@ use crate::{
     poh_recorder::{PohRecorder, PohRecorderError, WorkingBankEntry},
     poh_service::PohService,
     result::{Error, Result},
+    thread_affinity::{self, ThreadRole},
+    transaction_trace::{self, TraceStage},
 };
 use crossbeam_channel::{Receiver as CrossbeamReceiver, RecvTimeoutError};
 use itertools::Itertools;
@@ -26,10 +28,12 @@ use solana_sdk::{
     poh_config::PohConfig,
     pubkey::Pubkey,
     timing::{duration_as_ms, timestamp},
-    transaction::{self, Transaction, TransactionError},
+    transaction::{self, SanitizedTransaction, Transaction, TransactionError},
 };
 use std::{
-    cmp, env,
+    cmp,
+    convert::TryFrom,
+    env,
     net::UdpSocket,
     sync::atomic::AtomicBool,
     sync::mpsc::Receiver,
@@ -50,6 +54,10 @@ pub const NUM_THREADS: u32 = 4;
 
 const TOTAL_BUFFERED_PACKETS: usize = 500_000;
 
+/// Number of slots forwarded packets are held on to, in case this node becomes leader
+/// before they land in a block
+pub const DEFAULT_HOLDING_HORIZON_SLOTS: u64 = 2 * FORWARD_TRANSACTIONS_TO_LEADER_AT_SLOT_OFFSET;
+
 const MAX_NUM_TRANSACTIONS_PER_BATCH: usize = 128;
 
 /// Stores the stage's thread handle and output receiver.
@@ -64,6 +72,80 @@ pub enum BufferedPacketsDecision {
     Hold,
 }
 
+/// Packets that have been forwarded to the leader, kept around so this node can still
+/// include them if it becomes leader within the holding horizon
+#[derive(Default)]
+pub struct HeldPackets {
+    /// (slot the batch was forwarded at, fee weight of the batch, batch)
+    batches: Vec<(Slot, u64, PacketsAndOffsets)>,
+}
+
+impl HeldPackets {
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.batches.len()
+    }
+
+    /// Fees scale with the number of signatures, so that's what held batches are
+    /// weighed by when one has to go
+    fn fee_weight((msgs, packet_indexes): &PacketsAndOffsets) -> u64 {
+        packet_indexes
+            .iter()
+            .filter_map(|i| {
+                let packet = &msgs.packets[*i];
+                limited_deserialize::<Transaction>(&packet.data[0..packet.meta.size]).ok()
+            })
+            .map(|tx| u64::from(tx.message.header.num_required_signatures))
+            .sum()
+    }
+
+    /// Hold on to a batch forwarded at `slot`.  Once `batch_limit` batches are held, the
+    /// batch with the lowest fee weight is evicted, the oldest one among equals.  Returns
+    /// the number of batches dropped
+    pub fn hold(&mut self, slot: Slot, batch: PacketsAndOffsets, batch_limit: usize) -> usize {
+        if batch.1.is_empty() {
+            return 0;
+        }
+        let fee_weight = Self::fee_weight(&batch);
+        if self.batches.len() >= batch_limit {
+            let lowest = self
+                .batches
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (_, weight, _))| *weight)
+                .map(|(i, (_, weight, _))| (i, *weight));
+            match lowest {
+                Some((i, weight)) if weight <= fee_weight => {
+                    self.batches.remove(i);
+                }
+                // nothing held pays less than the new batch, or there's no room at all
+                _ => return 1,
+            }
+            self.batches.push((slot, fee_weight, batch));
+            return 1;
+        }
+        self.batches.push((slot, fee_weight, batch));
+        0
+    }
+
+    /// Drop the batches that were forwarded more than `horizon` slots before
+    /// `current_slot`, returns the number of batches dropped
+    pub fn purge_expired(&mut self, current_slot: Slot, horizon: u64) -> usize {
+        let len = self.batches.len();
+        self.batches
+            .retain(|(slot, _, _)| slot.saturating_add(horizon) >= current_slot);
+        len - self.batches.len()
+    }
+
+    /// Take all the held batches, oldest first
+    pub fn take(&mut self) -> UnprocessedPackets {
+        self.batches.drain(..).map(|(_, _, batch)| batch).collect()
+    }
+}
+
 impl BankingStage {
     /// Create the stage using `bank`. Exit when `verified_receiver` is dropped.
     #[allow(clippy::new_ret_no_self)]
@@ -90,6 +172,7 @@ impl BankingStage {
         num_threads: u32,
     ) -> Self {
         let batch_limit = TOTAL_BUFFERED_PACKETS / ((num_threads - 1) as usize * PACKETS_PER_BATCH);
+        let holding_horizon = Self::holding_horizon();
         // Single thread to generate entries from many banks.
         // This thread talks to poh_service and broadcasts the entries once they have been recorded.
         // Once an entry has been recorded, its blockhash is registered with the bank.
@@ -110,6 +193,7 @@ impl BankingStage {
                 Builder::new()
                     .name("solana-banking-stage-tx".to_string())
                     .spawn(move || {
+                        thread_affinity::apply(ThreadRole::Banking);
                         Self::process_loop(
                             my_pubkey,
                             &verified_receiver,
@@ -119,6 +203,7 @@ impl BankingStage {
                             enable_forwarding,
                             i,
                             batch_limit,
+                            holding_horizon,
                         );
                     })
                     .unwrap()
@@ -267,16 +352,19 @@ impl BankingStage {
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn process_buffered_packets(
         my_pubkey: &Pubkey,
         socket: &std::net::UdpSocket,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         buffered_packets: &mut Vec<PacketsAndOffsets>,
+        held_packets: &mut HeldPackets,
         enable_forwarding: bool,
         batch_limit: usize,
+        holding_horizon: u64,
     ) -> Result<()> {
-        let (leader_at_slot_offset, poh_has_bank, would_be_leader) = {
+        let (leader_at_slot_offset, poh_has_bank, would_be_leader, current_slot) = {
             let poh = poh_recorder.lock().unwrap();
             (
                 poh.leader_after_n_slots(FORWARD_TRANSACTIONS_TO_LEADER_AT_SLOT_OFFSET),
@@ -284,9 +372,13 @@ impl BankingStage {
                 poh.would_be_leader(
                     (FORWARD_TRANSACTIONS_TO_LEADER_AT_SLOT_OFFSET - 1) * DEFAULT_TICKS_PER_SLOT,
                 ),
+                poh.tick_height() / poh.ticks_per_slot(),
             )
         };
 
+        let expired = held_packets.purge_expired(current_slot, holding_horizon);
+        inc_new_counter_debug!("banking_stage-expired_held_batches", expired);
+
         let decision = Self::consume_or_forward_packets(
             my_pubkey,
             leader_at_slot_offset,
@@ -296,6 +388,8 @@ impl BankingStage {
 
         match decision {
             BufferedPacketsDecision::Consume => {
+                // Give the forwarded packets that are still held another chance
+                buffered_packets.append(&mut held_packets.take());
                 let mut unprocessed = Self::consume_buffered_packets(
                     my_pubkey,
                     poh_recorder,
@@ -326,7 +420,11 @@ impl BankingStage {
                                 &leader_addr,
                                 &buffered_packets,
                             );
-                            buffered_packets.clear();
+                            let dropped: usize = buffered_packets
+                                .drain(..)
+                                .map(|batch| held_packets.hold(current_slot, batch, batch_limit))
+                                .sum();
+                            inc_new_counter_debug!("banking_stage-dropped_held_batches", dropped);
                             Ok(())
                         })
                     })
@@ -339,6 +437,7 @@ impl BankingStage {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn process_loop(
         my_pubkey: Pubkey,
         verified_receiver: &CrossbeamReceiver<Vec<Packets>>,
@@ -348,19 +447,23 @@ impl BankingStage {
         enable_forwarding: bool,
         id: u32,
         batch_limit: usize,
+        holding_horizon: u64,
     ) {
         let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
         let mut buffered_packets = vec![];
+        let mut held_packets = HeldPackets::default();
         loop {
-            if !buffered_packets.is_empty() {
+            if !buffered_packets.is_empty() || !held_packets.is_empty() {
                 Self::process_buffered_packets(
                     &my_pubkey,
                     &socket,
                     poh_recorder,
                     cluster_info,
                     &mut buffered_packets,
+                    &mut held_packets,
                     enable_forwarding,
                     batch_limit,
+                    holding_horizon,
                 )
                 .unwrap_or_else(|_| buffered_packets.clear());
             }
@@ -415,19 +518,36 @@ impl BankingStage {
         )
     }
 
-    /// Convert the transactions from a blob of binary data to a vector of transactions
+    /// Number of slots forwarded packets are held on to, see `HeldPackets`
+    pub fn holding_horizon() -> u64 {
+        env::var("SOLANA_BANKING_HOLDING_HORIZON")
+            .map(|x| x.parse().unwrap_or(DEFAULT_HOLDING_HORIZON_SLOTS))
+            .unwrap_or(DEFAULT_HOLDING_HORIZON_SLOTS)
+    }
+
+    /// Convert the transactions from a blob of binary data to a vector of transactions,
+    /// malformed transactions are rejected here and come out as `None`
     fn deserialize_transactions(p: &Packets) -> Vec<Option<Transaction>> {
         p.packets
             .iter()
-            .map(|x| limited_deserialize(&x.data[0..x.meta.size]).ok())
+            .map(|x| {
+                limited_deserialize::<Transaction>(&x.data[0..x.meta.size])
+                    .ok()
+                    .and_then(|tx| SanitizedTransaction::try_from(tx).ok())
+                    .map(SanitizedTransaction::into_inner)
+            })
             .collect()
     }
 
+    /// Record the committable transactions in PoH. Their account data growth is reserved under
+    /// the PoH lock, so the leader reserves it in the same order replay does: the ledger order.
     #[allow(clippy::match_wild_err_arm)]
     fn record_transactions(
+        bank: &Bank,
         bank_slot: Slot,
         txs: &[Transaction],
-        results: &[transaction::Result<()>],
+        results: &mut [transaction::Result<()>],
+        data_deltas: &[i64],
         poh: &Arc<Mutex<PohRecorder>>,
     ) -> (Result<usize>, Vec<usize>) {
         let mut processed_generation = Measure::start("record::process_generation");
@@ -450,17 +570,22 @@ impl BankingStage {
         // unlock all the accounts with errors which are filtered by the above `filter_map`
         if !processed_transactions.is_empty() {
             inc_new_counter_warn!("banking_stage-record_transactions", num_to_commit);
+            transaction_trace::record_transactions(TraceStage::Banking, &processed_transactions);
 
             let mut hash_time = Measure::start("record::hash");
             let hash = hash_transactions(&processed_transactions[..]);
             hash_time.stop();
 
             let mut poh_record = Measure::start("record::poh_record");
+            let mut poh = poh.lock().unwrap();
+            // transactions failing for account data growth are still recorded and charged fees
+            let (reserved, _) = bank.reserve_accounts_data_growth(None, results, data_deltas);
             // record and unlock will unlock all the successful transactions
-            let res = poh
-                .lock()
-                .unwrap()
-                .record(bank_slot, hash, processed_transactions);
+            let res = poh.record(bank_slot, hash, processed_transactions);
+            if res.is_err() {
+                bank.release_accounts_data_growth(reserved);
+            }
+            drop(poh);
 
             match res {
                 Ok(()) => (),
@@ -490,7 +615,7 @@ impl BankingStage {
         // TODO: Banking stage threads should be prioritized to complete faster then this queue
         // expires.
         let txs = batch.transactions();
-        let (mut loaded_accounts, results, mut retryable_txs, tx_count, signature_count) =
+        let (mut loaded_accounts, mut results, data_deltas, mut retryable_txs, _, signature_count) =
             bank.load_and_execute_transactions(batch, MAX_PROCESSING_AGE);
         load_execute_time.stop();
 
@@ -498,7 +623,7 @@ impl BankingStage {
 
         let mut record_time = Measure::start("record_time");
         let (num_to_commit, retryable_record_txs) =
-            Self::record_transactions(bank.slot(), txs, &results, poh);
+            Self::record_transactions(bank, bank.slot(), txs, &mut results, &data_deltas, poh);
         retryable_txs.extend(retryable_record_txs);
         if num_to_commit.is_err() {
             return (num_to_commit, retryable_txs);
@@ -510,11 +635,13 @@ impl BankingStage {
         let num_to_commit = num_to_commit.unwrap();
 
         if num_to_commit != 0 {
+            let tx_count = results.iter().filter(|result| result.is_ok()).count() as u64;
             bank.commit_transactions(
                 txs,
                 None,
                 &mut loaded_accounts,
                 &results,
+                &data_deltas,
                 tx_count,
                 signature_count,
             );
@@ -541,30 +668,54 @@ impl BankingStage {
         poh: &Arc<Mutex<PohRecorder>>,
         chunk_offset: usize,
     ) -> (Result<usize>, Vec<usize>) {
-        let mut lock_time = Measure::start("lock_time");
+        let mut num_committed = 0;
+        let mut retryable_txs = vec![];
+        let mut lock_us = 0;
+        let mut unlock_us = 0;
+        let mut batch_offset = chunk_offset;
         // Once accounts are locked, other threads cannot encode transactions that will modify the
-        // same account state
-        let batch = bank.prepare_batch(txs, None);
-        lock_time.stop();
-
-        let (result, mut retryable_txs) =
-            Self::process_and_record_transactions_locked(bank, poh, &batch);
-        retryable_txs.iter_mut().for_each(|x| *x += chunk_offset);
-
-        let mut unlock_time = Measure::start("unlock_time");
-        // Once the accounts are new transactions can enter the pipeline to process them
-        drop(batch);
-        unlock_time.stop();
+        // same account state. Each batch only takes its locks once the previous one is committed.
+        let mut batches = bank.prepare_sanitized_batches(txs);
+        loop {
+            let mut lock_time = Measure::start("lock_time");
+            let batch = match batches.next() {
+                Some(batch) => batch,
+                None => break,
+            };
+            lock_time.stop();
+            lock_us += lock_time.as_us();
+            let batch_len = batch.transactions().len();
+
+            let (result, batch_retryable_txs) =
+                Self::process_and_record_transactions_locked(bank, poh, &batch);
+            retryable_txs.extend(batch_retryable_txs.into_iter().map(|x| x + batch_offset));
+
+            let mut unlock_time = Measure::start("unlock_time");
+            // Once the accounts are new transactions can enter the pipeline to process them
+            drop(batch);
+            unlock_time.stop();
+            unlock_us += unlock_time.as_us();
+
+            match result {
+                Ok(num_to_commit) => num_committed += num_to_commit,
+                Err(e) => {
+                    // The batches that weren't attempted can all be retried
+                    retryable_txs.extend(batch_offset + batch_len..chunk_offset + txs.len());
+                    return (Err(e), retryable_txs);
+                }
+            }
+            batch_offset += batch_len;
+        }
 
         debug!(
             "bank: {} lock: {}us unlock: {}us txs_len: {}",
             bank.slot(),
-            lock_time.as_us(),
-            unlock_time.as_us(),
+            lock_us,
+            unlock_us,
             txs.len(),
         );
 
-        (result, retryable_txs)
+        (Ok(num_committed), retryable_txs)
     }
 
     /// Sends transactions to the bank.
@@ -975,6 +1126,7 @@ mod tests {
     use itertools::Itertools;
     use solana_ledger::entry::{Entry, EntrySlice};
     use solana_ledger::get_tmp_ledger_path;
+    use solana_sdk::hash::Hash;
     use solana_sdk::instruction::InstructionError;
     use solana_sdk::signature::{Keypair, KeypairUtil};
     use solana_sdk::system_transaction;
@@ -1318,10 +1470,13 @@ mod tests {
             ];
 
             let mut results = vec![Ok(()), Ok(())];
+            let data_deltas = vec![0, 0];
             let _ = BankingStage::record_transactions(
+                &bank,
                 bank.slot(),
                 &transactions,
-                &results,
+                &mut results,
+                &data_deltas,
                 &poh_recorder,
             );
             let (_bank, (entry, _tick_height)) = entry_receiver.recv().unwrap();
@@ -1333,9 +1488,11 @@ mod tests {
                 InstructionError::new_result_with_negative_lamports(),
             ));
             let (res, retryable) = BankingStage::record_transactions(
+                &bank,
                 bank.slot(),
                 &transactions,
-                &results,
+                &mut results,
+                &data_deltas,
                 &poh_recorder,
             );
             res.unwrap();
@@ -1346,9 +1503,11 @@ mod tests {
             // Other TransactionErrors should not be recorded
             results[0] = Err(TransactionError::AccountNotFound);
             let (res, retryable) = BankingStage::record_transactions(
+                &bank,
                 bank.slot(),
                 &transactions,
-                &results,
+                &mut results,
+                &data_deltas,
                 &poh_recorder,
             );
             res.unwrap();
@@ -1360,9 +1519,11 @@ mod tests {
             // record_transactions should throw MaxHeightReached and return the set of retryable
             // txs
             let (res, retryable) = BankingStage::record_transactions(
+                &bank,
                 bank.slot() + 1,
                 &transactions,
-                &results,
+                &mut results,
+                &data_deltas,
                 &poh_recorder,
             );
             assert_matches!(
@@ -1688,7 +1849,7 @@ mod tests {
     }
 
     #[test]
-    fn test_bank_process_and_record_transactions_account_in_use() {
+    fn test_bank_process_and_record_transactions_conflicting() {
         solana_logger::setup();
         let GenesisConfigInfo {
             genesis_config,
@@ -1735,8 +1896,11 @@ mod tests {
                 0,
             );
 
-            assert!(result.is_ok());
-            assert_eq!(unprocessed.len(), 1);
+            // the second transfer waits for the first one's batch instead of being retried
+            assert_eq!(result.unwrap(), 2);
+            assert!(unprocessed.is_empty());
+            assert_eq!(bank.get_balance(&pubkey), 1);
+            assert_eq!(bank.get_balance(&pubkey1), 1);
         }
         Blocktree::destroy(&ledger_path).unwrap();
     }
@@ -1778,6 +1942,62 @@ mod tests {
             .collect_vec();
     }
 
+    #[test]
+    fn test_deserialize_transactions() {
+        let keypair = Keypair::new();
+        let tx = system_transaction::transfer(&keypair, &Pubkey::new_rand(), 1, Hash::default());
+        let mut tx_readonly_payer = tx.clone();
+        tx_readonly_payer
+            .message
+            .header
+            .num_readonly_signed_accounts = 1;
+        let mut tx_loaded_twice = tx.clone();
+        tx_loaded_twice.message.account_keys[1] = keypair.pubkey();
+        let packets = to_packets(&[tx.clone(), tx_readonly_payer, tx_loaded_twice])
+            .pop()
+            .unwrap();
+
+        // malformed transactions never reach the bank
+        assert_eq!(
+            BankingStage::deserialize_transactions(&packets),
+            vec![Some(tx), None, None]
+        );
+    }
+
+    #[test]
+    fn test_held_packets() {
+        let keypair = Keypair::new();
+        let batch = |num_txs: usize| {
+            let tx =
+                system_transaction::transfer(&keypair, &Pubkey::new_rand(), 1, Hash::default());
+            let txs = vec![tx; num_txs];
+            let packets = to_packets(&txs).pop().unwrap();
+            (packets, (0..num_txs).collect_vec())
+        };
+        let mut held_packets = HeldPackets::default();
+
+        assert_eq!(held_packets.hold(0, batch(1), 2), 0);
+        assert_eq!(held_packets.hold(0, (Packets::default(), vec![]), 2), 0);
+        assert_eq!(held_packets.hold(1, batch(2), 2), 0);
+        assert_eq!(held_packets.len(), 2);
+
+        // full, the cheapest batch makes room
+        assert_eq!(held_packets.hold(2, batch(2), 2), 1);
+        assert_eq!(held_packets.len(), 2);
+        assert!(held_packets.batches.iter().all(|(slot, _, _)| *slot > 0));
+
+        // full, and the new batch is the cheapest
+        assert_eq!(held_packets.hold(3, batch(1), 2), 1);
+        assert!(held_packets.batches.iter().all(|(slot, _, _)| *slot < 3));
+
+        assert_eq!(held_packets.purge_expired(3, 2), 0);
+        assert_eq!(held_packets.purge_expired(4, 2), 1);
+        let taken = held_packets.take();
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].1.len(), 2);
+        assert!(held_packets.is_empty());
+    }
+
     #[test]
     fn test_process_transactions_returns_unprocessed_txs() {
         solana_logger::setup();
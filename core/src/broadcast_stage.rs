@@ -1,6 +1,8 @@
 //! A stage to broadcast data from a leader node to validators
+use self::broadcast_duplicate_shreds_run::BroadcastDuplicateShredsRun;
 use self::broadcast_fake_shreds_run::BroadcastFakeShredsRun;
 use self::fail_entry_verification_broadcast_run::FailEntryVerificationBroadcastRun;
+use self::fail_slot_broadcast_run::FailSlotBroadcastRun;
 use self::standard_broadcast_run::StandardBroadcastRun;
 use crate::cluster_info::{ClusterInfo, ClusterInfoError};
 use crate::poh_recorder::WorkingBankEntry;
@@ -15,9 +17,11 @@ use std::sync::{Arc, RwLock};
 use std::thread::{self, Builder, JoinHandle};
 use std::time::Instant;
 
+mod broadcast_duplicate_shreds_run;
 mod broadcast_fake_shreds_run;
 pub(crate) mod broadcast_utils;
 mod fail_entry_verification_broadcast_run;
+mod fail_slot_broadcast_run;
 mod standard_broadcast_run;
 
 pub const NUM_THREADS: u32 = 10;
@@ -32,6 +36,10 @@ pub enum BroadcastStageType {
     Standard,
     FailEntryVerification,
     BroadcastFakeShreds,
+    /// Withhold the last shreds of every slot, forcing validators to repair them
+    FailSlot,
+    /// Send half of the peers a conflicting version of the last data shred of each batch
+    BroadcastDuplicateShreds,
 }
 
 impl BroadcastStageType {
@@ -74,6 +82,24 @@ impl BroadcastStageType {
                 blocktree,
                 BroadcastFakeShredsRun::new(0, shred_version),
             ),
+
+            BroadcastStageType::FailSlot => BroadcastStage::new(
+                sock,
+                cluster_info,
+                receiver,
+                exit_sender,
+                blocktree,
+                FailSlotBroadcastRun::new(shred_version),
+            ),
+
+            BroadcastStageType::BroadcastDuplicateShreds => BroadcastStage::new(
+                sock,
+                cluster_info,
+                receiver,
+                exit_sender,
+                blocktree,
+                BroadcastDuplicateShredsRun::new(shred_version),
+            ),
         }
     }
 }
@@ -0,0 +1,89 @@
+use super::*;
+use solana_ledger::entry::Entry;
+use solana_ledger::shred::{Shredder, RECOMMENDED_FEC_RATE};
+use solana_sdk::hash::hash;
+
+/// Broadcasts every shred, except that the first half of the peers receive a conflicting
+/// version of the last data shred of each batch, signed by this leader
+pub(super) struct BroadcastDuplicateShredsRun {
+    shred_version: u16,
+}
+
+impl BroadcastDuplicateShredsRun {
+    pub(super) fn new(shred_version: u16) -> Self {
+        Self { shred_version }
+    }
+}
+
+impl BroadcastRun for BroadcastDuplicateShredsRun {
+    fn run(
+        &mut self,
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        receiver: &Receiver<WorkingBankEntry>,
+        sock: &UdpSocket,
+        blocktree: &Arc<Blocktree>,
+    ) -> Result<()> {
+        // 1) Pull entries from banking stage
+        let receive_results = broadcast_utils::recv_slot_entries(receiver)?;
+        let bank = receive_results.bank.clone();
+        let last_tick_height = receive_results.last_tick_height;
+        let is_last_in_slot = last_tick_height == bank.max_tick_height();
+
+        // 2) Convert entries to shreds + generate coding shreds
+        let keypair = cluster_info.read().unwrap().keypair.clone();
+        let next_shred_index = blocktree
+            .meta(bank.slot())
+            .expect("Database error")
+            .map(|meta| meta.consumed)
+            .unwrap_or(0) as u32;
+
+        let shredder = Shredder::new(
+            bank.slot(),
+            bank.parent().unwrap().slot(),
+            RECOMMENDED_FEC_RATE,
+            keypair.clone(),
+            (bank.tick_height() % bank.ticks_per_slot()) as u8,
+            self.shred_version,
+        )
+        .expect("Expected to create a new shredder");
+
+        let (data_shreds, coding_shreds, _) =
+            shredder.entries_to_shreds(&receive_results.entries, is_last_in_slot, next_shred_index);
+
+        // Shred an entry nobody produced into the index of the last data shred
+        let duplicate_shred = data_shreds.last().and_then(|last_shred| {
+            let fake_entry = Entry::new(&hash(&last_shred.payload), 0, vec![]);
+            shredder
+                .entries_to_shreds(&[fake_entry], is_last_in_slot, last_shred.index())
+                .0
+                .into_iter()
+                .next()
+        });
+
+        blocktree.insert_shreds(data_shreds.clone(), None, true)?;
+
+        // 3) Start broadcast step
+        let peers = cluster_info.read().unwrap().tvu_peers();
+        let num_duplicate_peers = peers.len() / 2;
+        for (i, peer) in peers.iter().enumerate() {
+            for shred in data_shreds.iter().chain(coding_shreds.iter()) {
+                let shred = match &duplicate_shred {
+                    Some(duplicate_shred)
+                        if i < num_duplicate_peers
+                            && shred.is_data()
+                            && duplicate_shred.index() == shred.index() =>
+                    {
+                        duplicate_shred
+                    }
+                    _ => shred,
+                };
+                sock.send_to(&shred.payload, &peer.tvu)?;
+            }
+        }
+        if duplicate_shred.is_some() && num_duplicate_peers > 0 {
+            inc_new_counter_info!("broadcast_stage-duplicate_shreds-sent", num_duplicate_peers);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use super::*;
+use solana_ledger::shred::{Shredder, RECOMMENDED_FEC_RATE};
+
+/// Stores every shred but never broadcasts the final batch of a slot, so validators can only
+/// complete the slot by repairing it from this leader
+pub(super) struct FailSlotBroadcastRun {
+    shred_version: u16,
+}
+
+impl FailSlotBroadcastRun {
+    pub(super) fn new(shred_version: u16) -> Self {
+        Self { shred_version }
+    }
+}
+
+impl BroadcastRun for FailSlotBroadcastRun {
+    fn run(
+        &mut self,
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        receiver: &Receiver<WorkingBankEntry>,
+        sock: &UdpSocket,
+        blocktree: &Arc<Blocktree>,
+    ) -> Result<()> {
+        // 1) Pull entries from banking stage
+        let receive_results = broadcast_utils::recv_slot_entries(receiver)?;
+        let bank = receive_results.bank.clone();
+        let last_tick_height = receive_results.last_tick_height;
+
+        // 2) Convert entries to shreds + generate coding shreds
+        let keypair = cluster_info.read().unwrap().keypair.clone();
+        let next_shred_index = blocktree
+            .meta(bank.slot())
+            .expect("Database error")
+            .map(|meta| meta.consumed)
+            .unwrap_or(0) as u32;
+
+        let shredder = Shredder::new(
+            bank.slot(),
+            bank.parent().unwrap().slot(),
+            RECOMMENDED_FEC_RATE,
+            keypair.clone(),
+            (bank.tick_height() % bank.ticks_per_slot()) as u8,
+            self.shred_version,
+        )
+        .expect("Expected to create a new shredder");
+
+        let (data_shreds, coding_shreds, _) = shredder.entries_to_shreds(
+            &receive_results.entries,
+            last_tick_height == bank.max_tick_height(),
+            next_shred_index,
+        );
+
+        let all_shreds = data_shreds
+            .into_iter()
+            .chain(coding_shreds.into_iter())
+            .collect::<Vec<_>>();
+        let all_seeds: Vec<[u8; 32]> = all_shreds.iter().map(|s| s.seed()).collect();
+        let all_shred_bufs: Vec<Vec<u8>> = all_shreds.iter().map(|s| s.payload.clone()).collect();
+        blocktree
+            .insert_shreds(all_shreds, None, true)
+            .expect("Failed to insert shreds in blocktree");
+
+        // 3) Withhold the end of the slot, coding shreds included so it can't be recovered
+        if last_tick_height == bank.max_tick_height() {
+            warn!("withholding the last shreds of slot {}", bank.slot());
+            inc_new_counter_info!("broadcast_stage-fail_slot-withheld", all_shred_bufs.len());
+            return Ok(());
+        }
+
+        // 4) Start broadcast step
+        let bank_epoch = bank.get_leader_schedule_epoch(bank.slot());
+        let stakes = staking_utils::staked_nodes_at_epoch(&bank, bank_epoch);
+
+        // Broadcast data
+        cluster_info.read().unwrap().broadcast_shreds(
+            sock,
+            all_shred_bufs,
+            &all_seeds,
+            stakes.as_ref(),
+        )?;
+
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
 use super::broadcast_utils::{self, ReceiveResults};
 use super::*;
 use crate::broadcast_stage::broadcast_utils::UnfinishedSlotInfo;
+use crate::transaction_trace::{self, TraceStage};
 use solana_ledger::entry::Entry;
-use solana_ledger::shred::{Shred, Shredder, RECOMMENDED_FEC_RATE, SHRED_TICK_REFERENCE_MASK};
+use solana_ledger::shred::{
+    Shred, Shredder, StreamingShredder, RECOMMENDED_FEC_RATE, SHRED_TICK_REFERENCE_MASK,
+};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Keypair;
 use solana_sdk::timing::duration_as_us;
@@ -32,6 +35,7 @@ impl BroadcastStats {
 pub(super) struct StandardBroadcastRun {
     stats: BroadcastStats,
     unfinished_slot: Option<UnfinishedSlotInfo>,
+    streaming_shredder: Option<StreamingShredder>,
     current_slot_and_parent: Option<(u64, u64)>,
     slot_broadcast_start: Option<Instant>,
     keypair: Arc<Keypair>,
@@ -43,6 +47,7 @@ impl StandardBroadcastRun {
         Self {
             stats: BroadcastStats::default(),
             unfinished_slot: None,
+            streaming_shredder: None,
             current_slot_and_parent: None,
             slot_broadcast_start: None,
             keypair,
@@ -85,40 +90,82 @@ impl StandardBroadcastRun {
     fn entries_to_shreds(
         &mut self,
         blocktree: &Blocktree,
-        entries: &[Entry],
+        entries: Vec<Entry>,
         is_slot_end: bool,
         reference_tick: u8,
     ) -> (Vec<Shred>, Vec<Shred>) {
         let (slot, parent_slot) = self.current_slot_and_parent.unwrap();
-        let shredder = Shredder::new(
-            slot,
-            parent_slot,
-            RECOMMENDED_FEC_RATE,
-            self.keypair.clone(),
-            reference_tick,
-            self.shred_version,
-        )
-        .expect("Expected to create a new shredder");
-
-        let next_shred_index = self
-            .unfinished_slot
-            .map(|s| s.next_shred_index)
-            .unwrap_or_else(|| {
-                blocktree
-                    .meta(slot)
-                    .expect("Database error")
-                    .map(|meta| meta.consumed)
-                    .unwrap_or(0) as u32
-            });
+        let is_current_slot = self
+            .streaming_shredder
+            .as_ref()
+            .map(|shredder| shredder.slot() == slot)
+            .unwrap_or(false);
+        if !is_current_slot {
+            // Any entries still buffered for an interrupted slot are dropped along with it
+            let shredder = Shredder::new(
+                slot,
+                parent_slot,
+                RECOMMENDED_FEC_RATE,
+                self.keypair.clone(),
+                reference_tick,
+                self.shred_version,
+            )
+            .expect("Expected to create a new shredder");
+
+            let next_shred_index = self
+                .unfinished_slot
+                .map(|s| s.next_shred_index)
+                .unwrap_or_else(|| {
+                    blocktree
+                        .meta(slot)
+                        .expect("Database error")
+                        .map(|meta| meta.consumed)
+                        .unwrap_or(0) as u32
+                });
+            self.streaming_shredder = Some(StreamingShredder::new(shredder, next_shred_index));
+        }
 
-        let (data_shreds, coding_shreds, new_next_shred_index) =
-            shredder.entries_to_shreds(entries, is_slot_end, next_shred_index);
+        let mut streaming_shredder = self.streaming_shredder.take().unwrap();
+        streaming_shredder.set_reference_tick(reference_tick);
+
+        // Full FEC blocks are emitted as soon as enough entries arrive. The remainder is held
+        // back until a tick, so transaction entries pack into full shreds without delaying
+        // transmission by more than a tick.
+        let ends_with_tick = entries.last().map(|e| e.is_tick()).unwrap_or(false);
+        let mut data_shreds = vec![];
+        let mut coding_shreds = vec![];
+        for entry in entries {
+            let (data, coding) = streaming_shredder.push(entry);
+            data_shreds.extend(data);
+            coding_shreds.extend(coding);
+        }
 
-        self.unfinished_slot = Some(UnfinishedSlotInfo {
-            next_shred_index: new_next_shred_index,
-            slot,
-            parent: parent_slot,
-        });
+        let (data, coding) = if is_slot_end {
+            let next_shred_index = streaming_shredder.next_shred_index();
+            let (data, coding) = streaming_shredder.finish();
+            let next_shred_index = next_shred_index + data.len() as u32;
+            self.unfinished_slot = Some(UnfinishedSlotInfo {
+                next_shred_index,
+                slot,
+                parent: parent_slot,
+            });
+            (data, coding)
+        } else {
+            let shreds = if ends_with_tick {
+                streaming_shredder.flush()
+            } else {
+                (vec![], vec![])
+            };
+            self.unfinished_slot = Some(UnfinishedSlotInfo {
+                next_shred_index: streaming_shredder.next_shred_index(),
+                slot,
+                parent: parent_slot,
+            });
+            self.streaming_shredder = Some(streaming_shredder);
+            shreds
+        };
+        data_shreds.extend(data);
+        coding_shreds.extend(coding);
 
         (data_shreds, coding_shreds)
     }
@@ -160,9 +207,10 @@ impl StandardBroadcastRun {
             self.check_for_interrupted_slot(bank.ticks_per_slot() as u8);
 
         // 2) Convert entries to shreds and coding shreds
+        let traced_signatures = transaction_trace::sampled_signatures(&receive_results.entries);
         let (mut data_shreds, coding_shreds) = self.entries_to_shreds(
             blocktree,
-            &receive_results.entries,
+            receive_results.entries,
             last_tick_height == bank.max_tick_height(),
             (bank.tick_height() % bank.ticks_per_slot()) as u8,
         );
@@ -190,6 +238,7 @@ impl StandardBroadcastRun {
             stakes.as_ref(),
             sock,
         )?;
+        transaction_trace::record_signatures(TraceStage::Broadcast, &traced_signatures);
 
         self.update_broadcast_stats(BroadcastStats {
             shredding_elapsed: duration_as_us(&to_shreds_elapsed),
@@ -1,8 +1,15 @@
+use rayon::{prelude::*, ThreadPool};
 use solana_ledger::blocktree::Blocktree;
+use solana_perf::perf_libs;
+use solana_rayon_threadlimit::get_thread_count;
 use solana_sdk::clock::Slot;
+use solana_sdk::hash::{hash, hashv, Hash};
+use std::cell::RefCell;
 use std::fs::File;
 use std::io;
-use std::io::{BufWriter, Write};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::mem::size_of;
+use std::ops::Range;
 use std::path::Path;
 use std::sync::Arc;
 
@@ -10,20 +17,53 @@ pub use solana_chacha_sys::chacha_cbc_encrypt;
 
 pub const CHACHA_BLOCK_SIZE: usize = 64;
 pub const CHACHA_KEY_SIZE: usize = 32;
+pub const CHACHA_TAG_SIZE: usize = size_of::<Hash>();
+/// Number of encrypted bytes covered by each integrity tag
+pub const CHACHA_TAG_CHUNK_SIZE: usize = 8 * 1024;
+/// Slots of a segment encrypted as one CBC stream by `chacha_cbc_encrypt_ledger()`,
+/// archivers and the validators checking their proofs must agree on it
+pub const CHACHA_CHUNK_SLOTS: u64 = 16;
 
-pub fn chacha_cbc_encrypt_ledger(
+/// Chunk streams `chacha_cbc_encrypt_ledger()` hands to the GPU in one call
+const CHACHA_GPU_BATCH_STREAMS: u64 = 64;
+
+/// Bytes of plaintext `chacha_cbc_encrypt_stream()` encrypts at a time, a whole number of
+/// blocks so the ciphertext doesn't depend on how the input is split
+const CHACHA_STREAM_BUFFER_SIZE: usize = 8 * 1024;
+
+const CHACHA_CHUNK_IV_DOMAIN: &[u8] = b"solana-ledger-chunk-iv";
+
+thread_local!(static PAR_THREAD_POOL: RefCell<ThreadPool> = RefCell::new(rayon::ThreadPoolBuilder::new()
+                    .num_threads(get_thread_count())
+                    .build()
+                    .unwrap()));
+
+/// Call `f` with each chunk of a segment's data shreds in the order an archiver encrypts them,
+/// the last chunk of each read padded to a key size boundary. Returns the number of bytes read.
+pub fn for_each_segment_chunk<F>(
     blocktree: &Arc<Blocktree>,
     start_slot: Slot,
     slots_per_segment: u64,
-    out_path: &Path,
-    ivec: &mut [u8; CHACHA_BLOCK_SIZE],
-) -> io::Result<usize> {
-    let mut out_file =
-        BufWriter::new(File::create(out_path).expect("Can't open ledger encrypted data file"));
+    f: F,
+) -> io::Result<usize>
+where
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    for_each_slot_range_chunk(blocktree, start_slot, start_slot + slots_per_segment, f)
+}
+
+// Same as `for_each_segment_chunk()`, over the slots from `start_slot` through `last_slot`
+fn for_each_slot_range_chunk<F>(
+    blocktree: &Arc<Blocktree>,
+    start_slot: Slot,
+    last_slot: Slot,
+    mut f: F,
+) -> io::Result<usize>
+where
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
     const BUFFER_SIZE: usize = 8 * 1024;
     let mut buffer = [0; BUFFER_SIZE];
-    let mut encrypted_buffer = [0; BUFFER_SIZE];
-    let key = [0; CHACHA_KEY_SIZE];
     let mut total_size = 0;
     let mut current_slot = start_slot;
     let mut start_index = 0;
@@ -31,7 +71,7 @@ pub fn chacha_cbc_encrypt_ledger(
         match blocktree.get_data_shreds(current_slot, start_index, std::u64::MAX, &mut buffer) {
             Ok((last_index, mut size)) => {
                 debug!(
-                    "chacha: encrypting slice: {} num_shreds: {} data_len: {}",
+                    "chacha: reading slice: {} num_shreds: {} data_len: {}",
                     current_slot,
                     last_index.saturating_sub(start_index),
                     size
@@ -39,7 +79,7 @@ pub fn chacha_cbc_encrypt_ledger(
                 debug!("read {} bytes", size);
 
                 if size == 0 {
-                    if current_slot.saturating_sub(start_slot) < slots_per_segment {
+                    if current_slot < last_slot {
                         current_slot += 1;
                         start_index = 0;
                         continue;
@@ -54,16 +94,12 @@ pub fn chacha_cbc_encrypt_ledger(
                 }
                 total_size += size;
 
-                chacha_cbc_encrypt(&buffer[..size], &mut encrypted_buffer[..size], &key, ivec);
-                if let Err(res) = out_file.write(&encrypted_buffer[..size]) {
-                    warn!("Error writing file! {:?}", res);
-                    return Err(res);
-                }
+                f(&buffer[..size])?;
 
                 start_index = last_index + 1;
             }
             Err(e) => {
-                info!("Error encrypting file: {:?}", e);
+                info!("Error reading segment: {:?}", e);
                 break;
             }
         }
@@ -71,20 +107,474 @@ pub fn chacha_cbc_encrypt_ledger(
     Ok(total_size)
 }
 
+/// Write the plaintext of a segment to `out_path`, exactly the bytes
+/// `chacha_cbc_encrypt_ledger()` encrypts
+pub fn export_segment(
+    blocktree: &Arc<Blocktree>,
+    start_slot: Slot,
+    slots_per_segment: u64,
+    out_path: &Path,
+) -> io::Result<usize> {
+    let mut out_file = BufWriter::new(File::create(out_path)?);
+    let total_size = for_each_segment_chunk(blocktree, start_slot, slots_per_segment, |chunk| {
+        out_file.write_all(chunk)
+    })?;
+    out_file.flush()?;
+    Ok(total_size)
+}
+
+/// Encrypt everything read from `reader` to `writer` as a single CBC stream starting from
+/// `ivec`, without a blocktree, e.g. a segment written by `export_segment()`. The input is zero
+/// padded to a key size boundary the way blocktree reads are, returns the number of bytes
+/// written.
+pub fn chacha_cbc_encrypt_stream<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    ivec: &mut [u8; CHACHA_BLOCK_SIZE],
+) -> io::Result<usize> {
+    let total_size = for_each_encrypted_stream_chunk(&mut reader, ivec, |encrypted| {
+        writer.write_all(encrypted)
+    })?;
+    writer.flush()?;
+    Ok(total_size)
+}
+
+/// The counterpart of `chacha_cbc_encrypt_stream()`. The ChaCha block function isn't
+/// invertible so a CBC stream can't be decrypted, instead `plaintext` is encrypted again and
+/// checked to match `encrypted` byte for byte, which also has to end with it.
+pub fn chacha_cbc_verify_stream<P: Read, E: Read>(
+    mut plaintext: P,
+    mut encrypted: E,
+    ivec: &mut [u8; CHACHA_BLOCK_SIZE],
+) -> io::Result<()> {
+    let mut expected = [0; CHACHA_STREAM_BUFFER_SIZE];
+    let mut offset = 0;
+    for_each_encrypted_stream_chunk(&mut plaintext, ivec, |chunk| {
+        let size = read_chunk(&mut encrypted, &mut expected[..chunk.len()])?;
+        if expected[..size] != *chunk {
+            return Err(stream_mismatch(offset));
+        }
+        offset += size;
+        Ok(())
+    })?;
+    if read_chunk(&mut encrypted, &mut expected[..1])? != 0 {
+        return Err(stream_mismatch(offset));
+    }
+    Ok(())
+}
+
+fn stream_mismatch(offset: usize) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!(
+            "encrypted stream differs from its plaintext after byte {}",
+            offset
+        ),
+    )
+}
+
+// Call `f` with each chunk of the encryption of `reader`, see `chacha_cbc_encrypt_stream()`
+fn for_each_encrypted_stream_chunk<R, F>(
+    reader: &mut R,
+    ivec: &mut [u8; CHACHA_BLOCK_SIZE],
+    mut f: F,
+) -> io::Result<usize>
+where
+    R: Read,
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    let mut buffer = [0; CHACHA_STREAM_BUFFER_SIZE];
+    let mut encrypted_buffer = [0; CHACHA_STREAM_BUFFER_SIZE];
+    let key = [0; CHACHA_KEY_SIZE];
+    let mut total_size = 0;
+    loop {
+        let mut size = read_chunk(reader, &mut buffer)?;
+        if size == 0 {
+            break;
+        }
+        if size < CHACHA_STREAM_BUFFER_SIZE {
+            // only the last read is short, round it to the nearest key_size boundary
+            let padded_size = (size + CHACHA_KEY_SIZE - 1) & !(CHACHA_KEY_SIZE - 1);
+            for byte in &mut buffer[size..padded_size] {
+                *byte = 0;
+            }
+            size = padded_size;
+        }
+        chacha_cbc_encrypt(&buffer[..size], &mut encrypted_buffer[..size], &key, ivec);
+        f(&encrypted_buffer[..size])?;
+        total_size += size;
+    }
+    Ok(total_size)
+}
+
+/// The ivec the CBC stream of chunk `chunk_index` of a segment starts from, the first chunk
+/// starts from the segment's `ivec` itself
+pub fn chacha_chunk_ivec(
+    ivec: &[u8; CHACHA_BLOCK_SIZE],
+    chunk_index: u64,
+) -> [u8; CHACHA_BLOCK_SIZE] {
+    if chunk_index == 0 {
+        return *ivec;
+    }
+    let mut chunk_ivec = [0; CHACHA_BLOCK_SIZE];
+    let half = CHACHA_BLOCK_SIZE / 2;
+    for (i, part) in chunk_ivec.chunks_mut(half).enumerate() {
+        let hash = hashv(&[
+            CHACHA_CHUNK_IV_DOMAIN,
+            ivec,
+            &chunk_index.to_le_bytes(),
+            &[i as u8],
+        ]);
+        part.copy_from_slice(&hash.as_ref()[..half]);
+    }
+    chunk_ivec
+}
+
+// The CPU half of `chacha_cbc_encrypt_ledger()`, the chunk streams are encrypted on a thread
+// pool
+fn encrypt_ledger_cpu(
+    blocktree: &Arc<Blocktree>,
+    start_slot: Slot,
+    slots_per_segment: u64,
+    slots_per_chunk: u64,
+    out_path: &Path,
+    ivec: &[u8; CHACHA_BLOCK_SIZE],
+) -> io::Result<usize> {
+    let last_slot = start_slot + slots_per_segment;
+    let slots_per_chunk = slots_per_chunk.max(1);
+    let num_chunks = (last_slot - start_slot) / slots_per_chunk + 1;
+    let encrypt_chunk = |chunk_index: u64| -> io::Result<Vec<u8>> {
+        let (chunk_start, chunk_last) =
+            chunk_slot_range(start_slot, last_slot, slots_per_chunk, chunk_index);
+        encrypt_chunk_stream(blocktree, chunk_start, chunk_last, ivec, chunk_index)
+    };
+
+    let encrypt_batch = |batch: Range<u64>| -> Vec<io::Result<Vec<u8>>> {
+        batch
+            .into_par_iter()
+            .map(|chunk_index| encrypt_chunk(chunk_index))
+            .collect()
+    };
+
+    let mut out_file = BufWriter::new(File::create(out_path)?);
+    let mut total_size = 0;
+    // a batch of chunks per thread is held in memory at a time
+    let mut write_batches = |batch_size: u64,
+                             encrypt_batch: &dyn Fn(Range<u64>) -> Vec<io::Result<Vec<u8>>>|
+     -> io::Result<()> {
+        let mut batch_start = 0;
+        while batch_start < num_chunks {
+            let batch_end = (batch_start + batch_size).min(num_chunks);
+            for chunk in encrypt_batch(batch_start..batch_end) {
+                let chunk = chunk?;
+                out_file.write_all(&chunk)?;
+                total_size += chunk.len();
+            }
+            batch_start = batch_end;
+        }
+        Ok(())
+    };
+    if rayon::current_thread_index().is_some() {
+        // the caller already runs on a rayon pool, e.g. an archiver's compute pool, stay on it
+        write_batches(rayon::current_num_threads() as u64, &encrypt_batch)?;
+    } else {
+        PAR_THREAD_POOL.with(|thread_pool| {
+            let thread_pool = thread_pool.borrow();
+            write_batches(thread_pool.current_num_threads() as u64, &|batch| {
+                thread_pool.install(|| encrypt_batch(batch))
+            })
+        })?;
+    }
+    out_file.flush()?;
+    Ok(total_size)
+}
+
+// Encrypt the slots from `chunk_start` through `chunk_last` as chunk `chunk_index` of a segment
+fn encrypt_chunk_stream(
+    blocktree: &Arc<Blocktree>,
+    chunk_start: Slot,
+    chunk_last: Slot,
+    ivec: &[u8; CHACHA_BLOCK_SIZE],
+    chunk_index: u64,
+) -> io::Result<Vec<u8>> {
+    let mut encrypted = vec![];
+    let mut encrypted_buffer = [0; 8 * 1024];
+    let key = [0; CHACHA_KEY_SIZE];
+    let mut chunk_ivec = chacha_chunk_ivec(ivec, chunk_index);
+    for_each_slot_range_chunk(blocktree, chunk_start, chunk_last, |chunk| {
+        let size = chunk.len();
+        chacha_cbc_encrypt(chunk, &mut encrypted_buffer[..size], &key, &mut chunk_ivec);
+        encrypted.extend_from_slice(&encrypted_buffer[..size]);
+        Ok(())
+    })?;
+    Ok(encrypted)
+}
+
+// The first and last slot of chunk `chunk_index` of the slots from `start_slot` through
+// `last_slot`
+fn chunk_slot_range(
+    start_slot: Slot,
+    last_slot: Slot,
+    slots_per_chunk: u64,
+    chunk_index: u64,
+) -> (Slot, Slot) {
+    let chunk_start = start_slot + chunk_index * slots_per_chunk;
+    let chunk_last = chunk_start
+        .saturating_add(slots_per_chunk - 1)
+        .min(last_slot);
+    (chunk_start, chunk_last)
+}
+
+/// Encrypt a segment as independent CBC streams of `slots_per_chunk` slots each, the way
+/// archivers store it and validators check their proofs. Every stream starts from
+/// `chacha_chunk_ivec()` of its index and they're written to `out_path` in slot order, so the
+/// plaintext and the sampling offsets don't depend on `slots_per_chunk`, and a segment that
+/// fits in one chunk is a single stream from `ivec`.
+///
+/// The streams are encrypted many at a time on the GPU when perf-libs with chacha support is
+/// loaded and on a thread pool otherwise, with the same output. A batch the GPU fails to
+/// encrypt is redone on the CPU.
+pub fn chacha_cbc_encrypt_ledger(
+    blocktree: &Arc<Blocktree>,
+    start_slot: Slot,
+    slots_per_segment: u64,
+    slots_per_chunk: u64,
+    out_path: &Path,
+    ivec: &[u8; CHACHA_BLOCK_SIZE],
+) -> io::Result<usize> {
+    let encrypt_many = match perf_libs::api().and_then(|api| api.chacha_cbc_encrypt_many.as_ref()) {
+        Some(encrypt_many) => encrypt_many,
+        None => {
+            return encrypt_ledger_cpu(
+                blocktree,
+                start_slot,
+                slots_per_segment,
+                slots_per_chunk,
+                out_path,
+                ivec,
+            )
+        }
+    };
+    let last_slot = start_slot + slots_per_segment;
+    let slots_per_chunk = slots_per_chunk.max(1);
+    let num_chunks = (last_slot - start_slot) / slots_per_chunk + 1;
+    let key = [0; CHACHA_KEY_SIZE];
+
+    let mut out_file = BufWriter::new(File::create(out_path)?);
+    let mut total_size = 0;
+    let mut batch_start = 0;
+    // a batch of chunks is held in memory at a time
+    while batch_start < num_chunks {
+        let batch_end = (batch_start + CHACHA_GPU_BATCH_STREAMS).min(num_chunks);
+        let mut plaintext = vec![];
+        let mut piece_lens = vec![];
+        let mut num_pieces = vec![];
+        let mut ivecs = vec![];
+        for chunk_index in batch_start..batch_end {
+            let (chunk_start, chunk_last) =
+                chunk_slot_range(start_slot, last_slot, slots_per_chunk, chunk_index);
+            let mut chunk_pieces = 0;
+            // every blocktree read is a piece, the ciphertext depends on where they end
+            for_each_slot_range_chunk(blocktree, chunk_start, chunk_last, |piece| {
+                plaintext.extend_from_slice(piece);
+                piece_lens.push(piece.len() as u32);
+                chunk_pieces += 1;
+                Ok(())
+            })?;
+            num_pieces.push(chunk_pieces);
+            ivecs.extend_from_slice(&chacha_chunk_ivec(ivec, chunk_index));
+        }
+        let num_streams = num_pieces.len();
+        let keys = vec![0; num_streams * CHACHA_KEY_SIZE];
+        let mut encrypted = vec![0; plaintext.len()];
+
+        let res = unsafe {
+            (encrypt_many)(
+                plaintext.as_ptr(),
+                encrypted.as_mut_ptr(),
+                piece_lens.as_ptr(),
+                num_pieces.as_ptr(),
+                num_streams as u32,
+                keys.as_ptr(),
+                ivecs.as_mut_ptr(),
+                1,
+            )
+        };
+        if res != 0 {
+            inc_new_counter_debug!("chacha-gpu_encrypt_failures", 1);
+            let mut encrypted_buffer = [0; 8 * 1024];
+            let mut piece_lens = piece_lens.iter();
+            let mut offset = 0;
+            for (chunk_index, chunk_pieces) in (batch_start..batch_end).zip(&num_pieces) {
+                let mut chunk_ivec = chacha_chunk_ivec(ivec, chunk_index);
+                for piece_len in piece_lens.by_ref().take(*chunk_pieces as usize) {
+                    let piece = offset..offset + *piece_len as usize;
+                    let size = piece.len();
+                    chacha_cbc_encrypt(
+                        &plaintext[piece.clone()],
+                        &mut encrypted_buffer[..size],
+                        &key,
+                        &mut chunk_ivec,
+                    );
+                    encrypted[piece].copy_from_slice(&encrypted_buffer[..size]);
+                    offset += size;
+                }
+            }
+        }
+        out_file.write_all(&encrypted)?;
+        total_size += encrypted.len();
+        batch_start = batch_end;
+    }
+    out_file.flush()?;
+    Ok(total_size)
+}
+
+/// Write one integrity tag per `CHACHA_TAG_CHUNK_SIZE` chunk of `enc_path` to `tag_path`,
+/// returns the number of chunks tagged. A tag is the SHA-256 of its chunk: it catches a segment
+/// corrupted on disk, but isn't keyed, so it says nothing about who wrote the chunk.
+pub fn chacha_tag_file(enc_path: &Path, tag_path: &Path) -> io::Result<usize> {
+    let mut enc_file = BufReader::new(File::open(enc_path)?);
+    let mut tag_file = BufWriter::new(File::create(tag_path)?);
+    let mut buffer = [0; CHACHA_TAG_CHUNK_SIZE];
+    let mut num_chunks = 0;
+    loop {
+        let size = read_chunk(&mut enc_file, &mut buffer)?;
+        if size == 0 {
+            break;
+        }
+        tag_file.write_all(hash(&buffer[..size]).as_ref())?;
+        num_chunks += 1;
+    }
+    tag_file.flush()?;
+    Ok(num_chunks)
+}
+
+/// Check the tags of the given chunks of `enc_path` against those stored in `tag_path`
+pub fn chacha_verify_tags(
+    enc_path: &Path,
+    tag_path: &Path,
+    chunk_indexes: &[u64],
+) -> io::Result<()> {
+    chacha_verify_tags_from(
+        &mut BufReader::new(File::open(enc_path)?),
+        &mut BufReader::new(File::open(tag_path)?),
+        chunk_indexes,
+    )
+}
+
+/// Same as `chacha_verify_tags()`, reading the encrypted segment and its tags from any source
+pub fn chacha_verify_tags_from<E: Read + Seek, T: Read + Seek>(
+    enc_file: &mut E,
+    tag_file: &mut T,
+    chunk_indexes: &[u64],
+) -> io::Result<()> {
+    match chacha_corrupted_chunks(enc_file, tag_file, chunk_indexes)?.first() {
+        Some(chunk_index) => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("encrypted ledger chunk {} failed verification", chunk_index),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Return which of the given chunks of an encrypted segment don't match their tags, see
+/// `chacha_tag_file()`. A chunk past the end of the tags is an error rather than corrupted.
+pub fn chacha_corrupted_chunks<E: Read + Seek, T: Read + Seek>(
+    enc_file: &mut E,
+    tag_file: &mut T,
+    chunk_indexes: &[u64],
+) -> io::Result<Vec<u64>> {
+    let mut buffer = [0; CHACHA_TAG_CHUNK_SIZE];
+    let mut tag = [0; CHACHA_TAG_SIZE];
+    let mut corrupted = vec![];
+    for chunk_index in chunk_indexes {
+        tag_file.seek(SeekFrom::Start(chunk_index * CHACHA_TAG_SIZE as u64))?;
+        tag_file.read_exact(&mut tag)?;
+
+        enc_file.seek(SeekFrom::Start(chunk_index * CHACHA_TAG_CHUNK_SIZE as u64))?;
+        let size = read_chunk(enc_file, &mut buffer)?;
+        if size == 0 || hash(&buffer[..size]).as_ref() != tag {
+            warn!("chacha: tag mismatch for chunk {}", chunk_index);
+            corrupted.push(*chunk_index);
+        }
+    }
+    Ok(corrupted)
+}
+
+/// Re-encrypt, in place, the CBC streams of a segment encrypted by `chacha_cbc_encrypt_ledger()`
+/// that overlap any of the tagged chunks `chunk_indexes`, leaving the rest of `enc_file` alone.
+/// Returns the indexes of the streams re-encrypted, the tags must be written again afterwards.
+#[allow(clippy::too_many_arguments)]
+pub fn chacha_reencrypt_chunks<F: Write + Seek>(
+    blocktree: &Arc<Blocktree>,
+    start_slot: Slot,
+    slots_per_segment: u64,
+    slots_per_chunk: u64,
+    ivec: &[u8; CHACHA_BLOCK_SIZE],
+    chunk_indexes: &[u64],
+    enc_file: &mut F,
+) -> io::Result<Vec<u64>> {
+    let last_slot = start_slot + slots_per_segment;
+    let slots_per_chunk = slots_per_chunk.max(1);
+    let num_streams = (last_slot - start_slot) / slots_per_chunk + 1;
+    let tag_chunk_size = CHACHA_TAG_CHUNK_SIZE as u64;
+    let mut reencrypted = vec![];
+    let mut offset = 0;
+    for stream_index in 0..num_streams {
+        let (stream_start, stream_last) =
+            chunk_slot_range(start_slot, last_slot, slots_per_chunk, stream_index);
+        // reading the stream is much cheaper than encrypting it
+        let size = for_each_slot_range_chunk(blocktree, stream_start, stream_last, |_| Ok(()))?;
+        let end = offset + size as u64;
+        if chunk_indexes.iter().any(|chunk_index| {
+            chunk_index * tag_chunk_size < end && (chunk_index + 1) * tag_chunk_size > offset
+        }) {
+            let encrypted =
+                encrypt_chunk_stream(blocktree, stream_start, stream_last, ivec, stream_index)?;
+            enc_file.seek(SeekFrom::Start(offset))?;
+            enc_file.write_all(&encrypted)?;
+            reencrypted.push(stream_index);
+        }
+        offset = end;
+    }
+    enc_file.flush()?;
+    Ok(reencrypted)
+}
+
+// Fill `buffer` from `reader`, only returning short at the end of the file
+fn read_chunk<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut size = 0;
+    while size < buffer.len() {
+        match reader.read(&mut buffer[size..]) {
+            Ok(0) => break,
+            Ok(n) => size += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(size)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::chacha::chacha_cbc_encrypt_ledger;
+    use crate::chacha::{
+        chacha_cbc_encrypt, chacha_cbc_encrypt_ledger, chacha_cbc_encrypt_stream,
+        chacha_cbc_verify_stream, chacha_chunk_ivec, chacha_corrupted_chunks,
+        chacha_reencrypt_chunks, chacha_tag_file, chacha_verify_tags, encrypt_ledger_cpu,
+        export_segment, for_each_segment_chunk, for_each_slot_range_chunk, CHACHA_KEY_SIZE,
+        CHACHA_STREAM_BUFFER_SIZE, CHACHA_TAG_CHUNK_SIZE, CHACHA_TAG_SIZE,
+    };
     use crate::gen_keys::GenKeys;
     use solana_ledger::blocktree::Blocktree;
-    use solana_ledger::entry::Entry;
+    use solana_ledger::entry::{create_ticks, Entry};
     use solana_ledger::get_tmp_ledger_path;
     use solana_sdk::hash::{hash, Hash, Hasher};
     use solana_sdk::pubkey::Pubkey;
     use solana_sdk::signature::KeypairUtil;
     use solana_sdk::system_transaction;
     use std::fs::remove_file;
-    use std::fs::File;
-    use std::io::Read;
+    use std::fs::{File, OpenOptions};
+    use std::io::{Read, Seek, SeekFrom, Write};
     use std::sync::Arc;
 
     fn make_tiny_deterministic_test_entries(num: usize) -> Vec<Entry> {
@@ -153,12 +643,19 @@ mod tests {
             )
             .unwrap();
 
-        let mut key = hex!(
+        let key = hex!(
             "abcd1234abcd1234abcd1234abcd1234 abcd1234abcd1234abcd1234abcd1234
                             abcd1234abcd1234abcd1234abcd1234 abcd1234abcd1234abcd1234abcd1234"
         );
-        chacha_cbc_encrypt_ledger(&blocktree, 0, slots_per_segment as u64, &out_path, &mut key)
-            .unwrap();
+        chacha_cbc_encrypt_ledger(
+            &blocktree,
+            0,
+            slots_per_segment as u64,
+            std::u64::MAX,
+            &out_path,
+            &key,
+        )
+        .unwrap();
         let mut out_file = File::open(&out_path).unwrap();
         let mut buf = vec![];
         let size = out_file.read_to_end(&mut buf).unwrap();
@@ -173,4 +670,527 @@ mod tests {
         assert_eq!(hasher.result(), golden);
         remove_file(&out_path).unwrap();
     }
+
+    #[test]
+    fn test_export_segment() {
+        solana_logger::setup();
+        let ledger_path = get_tmp_ledger_path!();
+        let ticks_per_slot = 16;
+        let slots_per_segment = 32;
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let out_path = tmp_file_path("test_export_segment");
+        let enc_path = tmp_file_path("test_export_segment_encrypted");
+
+        let seed = [2u8; 32];
+        let mut rnd = GenKeys::new(seed);
+        let keypair = rnd.gen_keypair();
+
+        let entries = make_tiny_deterministic_test_entries(slots_per_segment);
+        blocktree
+            .write_entries(
+                0,
+                0,
+                0,
+                ticks_per_slot,
+                None,
+                true,
+                &Arc::new(keypair),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        let size = export_segment(&blocktree, 0, slots_per_segment as u64, &out_path).unwrap();
+        let mut exported = vec![];
+        File::open(&out_path)
+            .unwrap()
+            .read_to_end(&mut exported)
+            .unwrap();
+        assert_eq!(exported.len(), size);
+
+        // the export holds exactly the chunks that get encrypted
+        let mut chunks = vec![];
+        for_each_segment_chunk(&blocktree, 0, slots_per_segment as u64, |chunk| {
+            chunks.extend_from_slice(chunk);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(exported, chunks);
+
+        let key = [7u8; 64];
+        let encrypted_size = chacha_cbc_encrypt_ledger(
+            &blocktree,
+            0,
+            slots_per_segment as u64,
+            std::u64::MAX,
+            &enc_path,
+            &key,
+        )
+        .unwrap();
+        assert_eq!(encrypted_size, size);
+
+        remove_file(&out_path).unwrap();
+        remove_file(&enc_path).unwrap();
+    }
+
+    #[test]
+    fn test_tag_file() {
+        solana_logger::setup();
+        let ledger_path = get_tmp_ledger_path!();
+        let ticks_per_slot = 16;
+        let slots_per_segment = 32;
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let out_path = tmp_file_path("test_tag_file");
+        let tag_path = tmp_file_path("test_tag_file_tags");
+
+        let seed = [2u8; 32];
+        let mut rnd = GenKeys::new(seed);
+        let keypair = rnd.gen_keypair();
+
+        let entries = make_tiny_deterministic_test_entries(slots_per_segment);
+        blocktree
+            .write_entries(
+                0,
+                0,
+                0,
+                ticks_per_slot,
+                None,
+                true,
+                &Arc::new(keypair),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        let ivec = [7u8; 64];
+        let size =
+            chacha_cbc_encrypt_ledger(&blocktree, 0, slots_per_segment as u64, 8, &out_path, &ivec)
+                .unwrap();
+        let num_chunks = chacha_tag_file(&out_path, &tag_path).unwrap();
+        assert_eq!(
+            num_chunks,
+            (size + CHACHA_TAG_CHUNK_SIZE - 1) / CHACHA_TAG_CHUNK_SIZE
+        );
+        assert_eq!(
+            File::open(&tag_path).unwrap().metadata().unwrap().len(),
+            (num_chunks * CHACHA_TAG_SIZE) as u64
+        );
+        let chunks: Vec<_> = (0..num_chunks as u64).collect();
+        assert!(chacha_verify_tags(&out_path, &tag_path, &chunks).is_ok());
+
+        // a chunk past the end fails
+        assert!(chacha_verify_tags(&out_path, &tag_path, &[num_chunks as u64]).is_err());
+
+        // corrupt a byte in the last chunk
+        let mut buf = vec![];
+        File::open(&out_path)
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        {
+            let mut out_file = OpenOptions::new().write(true).open(&out_path).unwrap();
+            let offset = size as u64 - 1;
+            out_file.seek(SeekFrom::Start(offset)).unwrap();
+            out_file.write_all(&[!buf[size - 1]]).unwrap();
+        }
+        assert!(chacha_verify_tags(&out_path, &tag_path, &chunks).is_err());
+        if num_chunks > 1 {
+            assert!(chacha_verify_tags(&out_path, &tag_path, &[0]).is_ok());
+        }
+
+        remove_file(&out_path).unwrap();
+        remove_file(&tag_path).unwrap();
+    }
+
+    #[test]
+    fn test_encrypt_ledger_chunked() {
+        solana_logger::setup();
+        let ledger_path = get_tmp_ledger_path!();
+        let ticks_per_slot = 16;
+        let slots_per_segment = 32;
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let chunked_path = tmp_file_path("test_encrypt_ledger_chunked");
+
+        let seed = [2u8; 32];
+        let mut rnd = GenKeys::new(seed);
+        let keypair = rnd.gen_keypair();
+
+        // ticks fill every slot of the segment
+        let entries = create_ticks(
+            ticks_per_slot * slots_per_segment as u64,
+            0,
+            Hash::default(),
+        );
+        blocktree
+            .write_entries(
+                0,
+                0,
+                0,
+                ticks_per_slot,
+                None,
+                true,
+                &Arc::new(keypair),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        let ivec = [7u8; 64];
+        assert_eq!(chacha_chunk_ivec(&ivec, 0), ivec);
+        assert_ne!(chacha_chunk_ivec(&ivec, 1)[..], ivec[..]);
+        assert_ne!(
+            chacha_chunk_ivec(&ivec, 1)[..],
+            chacha_chunk_ivec(&ivec, 2)[..]
+        );
+
+        let read = |path| {
+            let mut buf = vec![];
+            File::open(path).unwrap().read_to_end(&mut buf).unwrap();
+            buf
+        };
+        // the whole segment as a single CBC stream
+        let mut serial = vec![];
+        let mut serial_ivec = ivec;
+        let mut encrypted_buffer = [0; 8 * 1024];
+        for_each_segment_chunk(&blocktree, 0, slots_per_segment as u64, |chunk| {
+            let size = chunk.len();
+            chacha_cbc_encrypt(
+                chunk,
+                &mut encrypted_buffer[..size],
+                &[0; CHACHA_KEY_SIZE],
+                &mut serial_ivec,
+            );
+            serial.extend_from_slice(&encrypted_buffer[..size]);
+            Ok(())
+        })
+        .unwrap();
+        let size = serial.len();
+
+        // a segment that fits in one chunk is a single stream
+        assert_eq!(
+            chacha_cbc_encrypt_ledger(
+                &blocktree,
+                0,
+                slots_per_segment as u64,
+                std::u64::MAX,
+                &chunked_path,
+                &ivec,
+            )
+            .unwrap(),
+            size
+        );
+        assert_eq!(read(&chunked_path), serial);
+
+        // only the first chunk continues the segment's ivec, the rest restart from their own
+        let slots_per_chunk = 4;
+        assert_eq!(
+            chacha_cbc_encrypt_ledger(
+                &blocktree,
+                0,
+                slots_per_segment as u64,
+                slots_per_chunk,
+                &chunked_path,
+                &ivec,
+            )
+            .unwrap(),
+            size
+        );
+        let chunked = read(&chunked_path);
+        let first_chunk_size =
+            for_each_slot_range_chunk(&blocktree, 0, slots_per_chunk - 1, |_| Ok(())).unwrap();
+        assert!(first_chunk_size > 0 && first_chunk_size < size);
+        assert_eq!(chunked[..first_chunk_size], serial[..first_chunk_size]);
+        assert_ne!(chunked[first_chunk_size..], serial[first_chunk_size..]);
+
+        remove_file(&chunked_path).unwrap();
+    }
+
+    #[test]
+    fn test_encrypt_ledger_gpu() {
+        solana_logger::setup();
+        let ledger_path = get_tmp_ledger_path!();
+        let ticks_per_slot = 16;
+        let slots_per_segment = 32;
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let chunked_path = tmp_file_path("test_encrypt_ledger_gpu_chunked");
+        let gpu_path = tmp_file_path("test_encrypt_ledger_gpu");
+
+        let seed = [2u8; 32];
+        let mut rnd = GenKeys::new(seed);
+        let keypair = rnd.gen_keypair();
+        let entries = create_ticks(
+            ticks_per_slot * slots_per_segment as u64,
+            0,
+            Hash::default(),
+        );
+        blocktree
+            .write_entries(
+                0,
+                0,
+                0,
+                ticks_per_slot,
+                None,
+                true,
+                &Arc::new(keypair),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        let read = |path| {
+            let mut buf = vec![];
+            File::open(path).unwrap().read_to_end(&mut buf).unwrap();
+            buf
+        };
+        let ivec = [7u8; 64];
+        // the same ciphertext whether or not a GPU is present, more chunks than a batch too
+        for slots_per_chunk in &[1, 4, std::u64::MAX] {
+            let size = encrypt_ledger_cpu(
+                &blocktree,
+                0,
+                slots_per_segment as u64,
+                *slots_per_chunk,
+                &chunked_path,
+                &ivec,
+            )
+            .unwrap();
+            assert_eq!(
+                chacha_cbc_encrypt_ledger(
+                    &blocktree,
+                    0,
+                    slots_per_segment as u64,
+                    *slots_per_chunk,
+                    &gpu_path,
+                    &ivec,
+                )
+                .unwrap(),
+                size
+            );
+            assert_eq!(read(&gpu_path), read(&chunked_path));
+        }
+
+        remove_file(&chunked_path).unwrap();
+        remove_file(&gpu_path).unwrap();
+    }
+
+    #[test]
+    fn test_reencrypt_chunks() {
+        solana_logger::setup();
+        let ledger_path = get_tmp_ledger_path!();
+        let ticks_per_slot = 16;
+        let slots_per_segment = 32;
+        let slots_per_chunk = 4;
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let out_path = tmp_file_path("test_reencrypt_chunks");
+        let tag_path = tmp_file_path("test_reencrypt_chunks.tags");
+
+        let seed = [2u8; 32];
+        let mut rnd = GenKeys::new(seed);
+        let keypair = rnd.gen_keypair();
+        let entries = create_ticks(
+            ticks_per_slot * slots_per_segment as u64,
+            0,
+            Hash::default(),
+        );
+        blocktree
+            .write_entries(
+                0,
+                0,
+                0,
+                ticks_per_slot,
+                None,
+                true,
+                &Arc::new(keypair),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        let ivec = [7u8; 64];
+        chacha_cbc_encrypt_ledger(
+            &blocktree,
+            0,
+            slots_per_segment as u64,
+            slots_per_chunk,
+            &out_path,
+            &ivec,
+        )
+        .unwrap();
+        let num_chunks = chacha_tag_file(&out_path, &tag_path).unwrap() as u64;
+        assert!(num_chunks > 2);
+
+        let mut original = vec![];
+        File::open(&out_path)
+            .unwrap()
+            .read_to_end(&mut original)
+            .unwrap();
+        let corrupted_chunks = |chunk_indexes: &[u64]| {
+            chacha_corrupted_chunks(
+                &mut File::open(&out_path).unwrap(),
+                &mut File::open(&tag_path).unwrap(),
+                chunk_indexes,
+            )
+        };
+        let all_chunks: Vec<_> = (0..num_chunks).collect();
+        assert_eq!(corrupted_chunks(&all_chunks).unwrap(), Vec::<u64>::new());
+
+        // corrupt a byte in the middle of the segment
+        let corrupted_chunk = num_chunks / 2;
+        let offset = corrupted_chunk as usize * CHACHA_TAG_CHUNK_SIZE + 1;
+        {
+            let mut file = OpenOptions::new().write(true).open(&out_path).unwrap();
+            file.seek(SeekFrom::Start(offset as u64)).unwrap();
+            file.write_all(&[!original[offset]]).unwrap();
+        }
+        assert_eq!(
+            corrupted_chunks(&all_chunks).unwrap(),
+            vec![corrupted_chunk]
+        );
+        assert_eq!(
+            corrupted_chunks(&[0, corrupted_chunk]).unwrap(),
+            vec![corrupted_chunk]
+        );
+        assert_eq!(corrupted_chunks(&[0]).unwrap(), Vec::<u64>::new());
+        // past the end of the tags
+        assert!(corrupted_chunks(&[num_chunks]).is_err());
+
+        // only the streams overlapping the corrupted chunk are encrypted again
+        let reencrypted = chacha_reencrypt_chunks(
+            &blocktree,
+            0,
+            slots_per_segment as u64,
+            slots_per_chunk,
+            &ivec,
+            &[corrupted_chunk],
+            &mut OpenOptions::new().write(true).open(&out_path).unwrap(),
+        )
+        .unwrap();
+        assert!(!reencrypted.is_empty());
+        assert!((reencrypted.len() as u64) < slots_per_segment / slots_per_chunk);
+        assert_eq!(corrupted_chunks(&all_chunks).unwrap(), Vec::<u64>::new());
+        let mut repaired = vec![];
+        File::open(&out_path)
+            .unwrap()
+            .read_to_end(&mut repaired)
+            .unwrap();
+        assert_eq!(repaired, original);
+
+        remove_file(&out_path).unwrap();
+        remove_file(&tag_path).unwrap();
+    }
+
+    #[test]
+    fn test_encrypt_stream() {
+        let ivec = [3u8; 64];
+        for &len in &[
+            0,
+            1,
+            64,
+            100,
+            CHACHA_STREAM_BUFFER_SIZE,
+            3 * CHACHA_STREAM_BUFFER_SIZE + 17,
+        ] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let mut encrypted = vec![];
+            let mut stream_ivec = ivec;
+            let size = chacha_cbc_encrypt_stream(&plaintext[..], &mut encrypted, &mut stream_ivec)
+                .unwrap();
+            let padded_size = (len + CHACHA_KEY_SIZE - 1) / CHACHA_KEY_SIZE * CHACHA_KEY_SIZE;
+            assert_eq!(size, padded_size);
+            assert_eq!(encrypted.len(), size);
+
+            // the stream is one CBC stream over the padded input, however it was read
+            let mut padded = plaintext.clone();
+            padded.resize(padded_size, 0);
+            let mut expected = vec![0; padded_size + 64];
+            let mut expected_ivec = ivec;
+            chacha_cbc_encrypt(
+                &padded,
+                &mut expected[..padded_size],
+                &[0; CHACHA_KEY_SIZE],
+                &mut expected_ivec,
+            );
+            assert_eq!(encrypted[..], expected[..padded_size]);
+            assert_eq!(stream_ivec, expected_ivec);
+
+            assert!(
+                chacha_cbc_verify_stream(&plaintext[..], &encrypted[..], &mut ivec.clone()).is_ok()
+            );
+            if len > 0 {
+                let mut tampered = encrypted.clone();
+                tampered[len / 2] ^= 1;
+                assert!(
+                    chacha_cbc_verify_stream(&plaintext[..], &tampered[..], &mut ivec.clone())
+                        .is_err()
+                );
+                assert!(chacha_cbc_verify_stream(
+                    &plaintext[..],
+                    &encrypted[..size - 1],
+                    &mut ivec.clone()
+                )
+                .is_err());
+                assert!(
+                    chacha_cbc_verify_stream(&plaintext[..], &encrypted[..], &mut [4u8; 64])
+                        .is_err()
+                );
+            }
+            let mut extended = encrypted.clone();
+            extended.push(0);
+            assert!(
+                chacha_cbc_verify_stream(&plaintext[..], &extended[..], &mut ivec.clone()).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_encrypt_exported_segment_stream() {
+        solana_logger::setup();
+        let ledger_path = get_tmp_ledger_path!();
+        let ticks_per_slot = 16;
+        let slots_per_segment = 32;
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let out_path = tmp_file_path("test_encrypt_exported_segment_stream");
+
+        let seed = [2u8; 32];
+        let mut rnd = GenKeys::new(seed);
+        let keypair = rnd.gen_keypair();
+        let entries = make_tiny_deterministic_test_entries(slots_per_segment);
+        blocktree
+            .write_entries(
+                0,
+                0,
+                0,
+                ticks_per_slot,
+                None,
+                true,
+                &Arc::new(keypair),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        // an exported segment is encrypted and verified without opening the ledger again
+        let size = export_segment(&blocktree, 0, slots_per_segment as u64, &out_path).unwrap();
+        drop(blocktree);
+        let ivec = [7u8; 64];
+        let mut encrypted = vec![];
+        assert_eq!(
+            chacha_cbc_encrypt_stream(
+                File::open(&out_path).unwrap(),
+                &mut encrypted,
+                &mut ivec.clone()
+            )
+            .unwrap(),
+            size
+        );
+        chacha_cbc_verify_stream(
+            File::open(&out_path).unwrap(),
+            &encrypted[..],
+            &mut ivec.clone(),
+        )
+        .unwrap();
+
+        remove_file(&out_path).unwrap();
+        Blocktree::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
 }
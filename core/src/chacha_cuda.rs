@@ -1,6 +1,6 @@
 // Module used by validators to approve storage mining proofs in parallel using the GPU
 
-use crate::chacha::{CHACHA_BLOCK_SIZE, CHACHA_KEY_SIZE};
+use crate::chacha::{chacha_chunk_ivec, CHACHA_BLOCK_SIZE, CHACHA_KEY_SIZE};
 use solana_ledger::blocktree::Blocktree;
 use solana_perf::perf_libs;
 use solana_sdk::hash::Hash;
@@ -10,12 +10,14 @@ use std::sync::Arc;
 
 // Encrypt a file with multiple starting IV states, determined by ivecs.len()
 //
-// Then sample each block at the offsets provided by samples argument with sha256
-// and return the vec of sha states
+// Each key's stream restarts from its `chacha_chunk_ivec()` every `slots_per_chunk` slots, like
+// `chacha_cbc_encrypt_ledger()`. Then sample each block at the offsets provided by
+// samples argument with sha256 and return the vec of sha states
 pub fn chacha_cbc_encrypt_file_many_keys(
     blocktree: &Arc<Blocktree>,
     segment: u64,
     slots_per_segment: u64,
+    slots_per_chunk: u64,
     ivecs: &mut [u8],
     samples: &[u64],
 ) -> io::Result<Vec<Hash>> {
@@ -42,6 +44,9 @@ pub fn chacha_cbc_encrypt_file_many_keys(
     let start_slot = current_slot;
     let mut total_size = 0;
     let mut time: f32 = 0.0;
+    let slots_per_chunk = slots_per_chunk.max(1);
+    let segment_ivecs = ivecs.to_vec();
+    let mut chunk_index = 0;
     unsafe {
         (api.chacha_init_sha_state)(int_sha_states.as_mut_ptr(), num_keys as u32);
     }
@@ -70,6 +75,19 @@ pub fn chacha_cbc_encrypt_file_many_keys(
                     size = (size + CHACHA_KEY_SIZE - 1) & !(CHACHA_KEY_SIZE - 1);
                 }
 
+                let slot_chunk_index = (current_slot - start_slot) / slots_per_chunk;
+                if slot_chunk_index != chunk_index {
+                    chunk_index = slot_chunk_index;
+                    for (ivec, segment_ivec) in ivecs
+                        .chunks_mut(CHACHA_BLOCK_SIZE)
+                        .zip(segment_ivecs.chunks(CHACHA_BLOCK_SIZE))
+                    {
+                        let mut segment_ivec_array = [0; CHACHA_BLOCK_SIZE];
+                        segment_ivec_array.copy_from_slice(segment_ivec);
+                        ivec.copy_from_slice(&chacha_chunk_ivec(&segment_ivec_array, chunk_index));
+                    }
+                }
+
                 unsafe {
                     (api.chacha_cbc_encrypt_many_sample)(
                         buffer[..size].as_ptr(),
@@ -152,28 +170,33 @@ mod tests {
 
         let out_path = Path::new("test_chacha_encrypt_file_many_keys_single_output.txt.enc");
 
-        let samples = [0];
         let mut ivecs = hex!(
             "abcd1234abcd1234abcd1234abcd1234 abcd1234abcd1234abcd1234abcd1234
                               abcd1234abcd1234abcd1234abcd1234 abcd1234abcd1234abcd1234abcd1234"
         );
 
-        let mut cpu_iv = ivecs.clone();
+        // one chunk per slot, so the stream restarts at the second slot
+        let slots_per_chunk = 1;
         chacha_cbc_encrypt_ledger(
             &blocktree,
             0,
             slots_per_segment as u64,
+            slots_per_chunk,
             out_path,
-            &mut cpu_iv,
+            &ivecs,
         )
         .unwrap();
 
+        // sample both ends of the segment, in the first and in the last chunk
+        let num_blocks = std::fs::metadata(out_path).unwrap().len() / CHACHA_BLOCK_SIZE as u64;
+        let samples = [0, num_blocks - 1];
         let ref_hash = sample_file(&out_path, &samples).unwrap();
 
         let hashes = chacha_cbc_encrypt_file_many_keys(
             &blocktree,
             0,
             slots_per_segment as u64,
+            slots_per_chunk,
             &mut ivecs,
             &samples,
         )
@@ -227,8 +250,9 @@ mod tests {
                 &blocktree.clone(),
                 0,
                 DEFAULT_SLOTS_PER_SEGMENT,
+                std::u64::MAX,
                 out_path,
-                &mut ivec,
+                &ivec,
             )
             .unwrap();
 
@@ -245,6 +269,7 @@ mod tests {
             &blocktree,
             0,
             DEFAULT_SLOTS_PER_SEGMENT,
+            std::u64::MAX,
             &mut ivecs,
             &samples,
         )
@@ -272,6 +297,7 @@ mod tests {
             &blocktree,
             0,
             DEFAULT_SLOTS_PER_SEGMENT,
+            std::u64::MAX,
             &mut keys,
             &samples,
         )
@@ -16,29 +16,35 @@ use crate::packet::limited_deserialize;
 use crate::streamer::{PacketReceiver, PacketSender};
 use crate::{
     contact_info::ContactInfo,
+    crds_duplicate_identity::IdentitySighting,
     crds_gossip::CrdsGossip,
     crds_gossip_error::CrdsGossipError,
-    crds_gossip_pull::{CrdsFilter, CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS},
-    crds_value::{self, CrdsData, CrdsValue, CrdsValueLabel, EpochSlots, Vote},
+    crds_gossip_pull::{CrdsFilter, CrdsTimeouts, CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS},
+    crds_value::{
+        self, ArchiverInfo, CrdsData, CrdsValue, CrdsValueLabel, EpochSlots, StorageTurn, Vote,
+    },
     packet::{Packet, PACKET_DATA_SIZE},
+    repair_peers::RepairPeers,
     repair_service::RepairType,
     result::{Error, Result},
     sendmmsg::{multicast, send_mmsg},
-    weighted_shuffle::{weighted_best, weighted_shuffle},
+    weighted_shuffle::{sorted_stakes_with_index, stake_weighted_shuffle, weighted_best},
+    wire_format::{self, encode_repair, repair_to_bytes},
 };
 use bincode::{serialize, serialized_size};
 use core::cmp;
-use itertools::Itertools;
 use rand::{thread_rng, Rng};
 use solana_ledger::{bank_forks::BankForks, blocktree::Blocktree, staking_utils};
 use solana_metrics::{datapoint_debug, inc_new_counter_debug, inc_new_counter_error};
 use solana_net_utils::{
-    bind_common, bind_common_in_range, bind_in_range, find_available_port_in_range,
-    multi_bind_in_range, PortRange,
+    bind_common_in_range, bind_common_in_range_with_ip, bind_common_with_ip, bind_in_range,
+    bind_in_range_with_ip, find_available_port_in_range, multi_bind_in_range_with_ip,
+    unspecified_ip_for, PortRange,
 };
-use solana_perf::packet::{to_packets_with_destination, Packets};
+use solana_perf::packet::{split_into_packet_payloads, to_packets_with_destination, Packets};
 use solana_sdk::{
     clock::Slot,
+    hash::Hash,
     pubkey::Pubkey,
     signature::{Keypair, KeypairUtil, Signable, Signature},
     timing::{duration_as_ms, timestamp},
@@ -51,7 +57,7 @@ use std::{
     fmt,
     net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket},
     sync::atomic::{AtomicBool, Ordering},
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
     thread::{sleep, Builder, JoinHandle},
     time::{Duration, Instant},
 };
@@ -65,12 +71,18 @@ pub const GOSSIP_SLEEP_MILLIS: u64 = 100;
 
 /// the number of slots to respond with when responding to `Orphan` requests
 pub const MAX_ORPHAN_REPAIR_RESPONSES: usize = 10;
+/// the number of slot hashes to respond with when responding to `AncestorHashes` requests
+pub const MAX_ANCESTOR_HASHES: usize = 25;
+/// the number of `AncestorHashes` responses kept until repair takes them
+const MAX_PENDING_ANCESTOR_HASHES: usize = 64;
 /// The maximum size of a bloom filter
-pub const MAX_BLOOM_SIZE: usize = 1030;
+pub const MAX_BLOOM_SIZE: usize = 1020;
+/// Number of sockets bound to each tvu port by default
+pub const DEFAULT_TVU_RECEIVE_SOCKETS: usize = 8;
 /// The maximum size of a protocol payload
 const MAX_PROTOCOL_PAYLOAD_SIZE: u64 = PACKET_DATA_SIZE as u64 - MAX_PROTOCOL_HEADER_SIZE;
 /// The largest protocol header size
-const MAX_PROTOCOL_HEADER_SIZE: u64 = 202;
+const MAX_PROTOCOL_HEADER_SIZE: u64 = 212;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum ClusterInfoError {
@@ -87,6 +99,14 @@ pub struct ClusterInfo {
     pub(crate) keypair: Arc<Keypair>,
     /// The network entrypoint
     entrypoint: Option<ContactInfo>,
+    /// `AncestorHashes` responses not yet taken by repair, keyed by the requested slot, with
+    /// the address they were received from
+    ancestor_hashes: HashMap<Slot, (SocketAddr, Vec<(Slot, Hash)>)>,
+    /// Scores of the peers shreds are requested from, shared by repair and archiver downloads
+    repair_peer_scores: Arc<Mutex<RepairPeers>>,
+    /// Send repair requests in the compact wire format rather than bincode, only for clusters
+    /// where every node decodes it
+    compact_repair: bool,
 }
 
 #[derive(Default, Clone)]
@@ -167,7 +187,7 @@ struct PullData {
 // TODO These messages should go through the gpu pipeline for spam filtering
 #[derive(Serialize, Deserialize, Debug)]
 #[allow(clippy::large_enum_variant)]
-enum Protocol {
+pub(crate) enum Protocol {
     /// Gossip protocol messages
     PullRequest(CrdsFilter, CrdsValue),
     PullResponse(Pubkey, Vec<CrdsValue>),
@@ -179,6 +199,10 @@ enum Protocol {
     RequestWindowIndex(ContactInfo, u64, u64),
     RequestHighestWindowIndex(ContactInfo, u64, u64),
     RequestOrphan(ContactInfo, u64),
+    RequestAncestorHashes(ContactInfo, u64),
+    /// The hashes of a slot and its ancestors, newest first, sent to the gossip port of the
+    /// node that requested them
+    AncestorHashes(Pubkey, u64, Vec<(u64, Hash)>),
 }
 
 impl ClusterInfo {
@@ -192,6 +216,9 @@ impl ClusterInfo {
             gossip: CrdsGossip::default(),
             keypair,
             entrypoint: None,
+            ancestor_hashes: HashMap::new(),
+            repair_peer_scores: Arc::new(Mutex::new(RepairPeers::default())),
+            compact_repair: false,
         };
         let id = contact_info.id;
         me.gossip.set_self(&id);
@@ -326,6 +353,56 @@ impl ClusterInfo {
             .process_push_message(&self.id(), vec![entry], now);
     }
 
+    /// Advertise this node as an archiver holding `num_segments` segments, served from
+    /// its storage address
+    pub fn push_archiver_info(&mut self, num_segments: u64) {
+        let now = timestamp();
+        let storage_addr = self.my_data().storage_addr;
+        let entry = CrdsValue::new_signed(
+            CrdsData::ArchiverInfo(ArchiverInfo::new(
+                self.id(),
+                storage_addr,
+                num_segments,
+                now,
+            )),
+            &self.keypair,
+        );
+        self.gossip
+            .process_push_message(&self.id(), vec![entry], now);
+    }
+
+    /// Advertise the storage turn committed on chain at this node's latest root
+    pub fn push_storage_turn(&mut self, blockhash: Hash, slot: Slot) {
+        let now = timestamp();
+        let entry = CrdsValue::new_signed(
+            CrdsData::StorageTurn(StorageTurn::new(self.id(), blockhash, slot, now)),
+            &self.keypair,
+        );
+        self.gossip
+            .process_push_message(&self.id(), vec![entry], now);
+    }
+
+    /// The storage turns advertised by other nodes
+    pub fn storage_turns(&self) -> Vec<StorageTurn> {
+        let me = self.id();
+        self.gossip
+            .crds
+            .table
+            .values()
+            .filter_map(|x| x.value.storage_turn())
+            .filter(|x| x.from != me)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_archiver_info_for_node(&self, pubkey: &Pubkey) -> Option<&ArchiverInfo> {
+        self.gossip
+            .crds
+            .table
+            .get(&CrdsValueLabel::ArchiverInfo(*pubkey))
+            .and_then(|x| x.value.archiver_info())
+    }
+
     pub fn push_vote(&mut self, tower_index: usize, vote: Transaction) {
         let now = timestamp();
         let vote = Vote::new(&self.id(), vote, now);
@@ -407,6 +484,17 @@ impl ClusterInfo {
         self.gossip.purge(now);
     }
 
+    /// How long values of each type received from other nodes are kept
+    pub fn set_crds_timeouts(&mut self, crds_timeouts: CrdsTimeouts) {
+        self.gossip.pull.crds_timeouts = crds_timeouts;
+    }
+
+    /// Whether repair requests are sent in the compact wire format, they are bincode encoded
+    /// by default so nodes that only decode bincode can still serve them
+    pub fn set_compact_repair(&mut self, compact_repair: bool) {
+        self.compact_repair = compact_repair;
+    }
+
     pub fn rpc_peers(&self) -> Vec<ContactInfo> {
         let me = self.my_data().id;
         self.gossip
@@ -434,6 +522,11 @@ impl ClusterInfo {
             .collect()
     }
 
+    /// Identities gossiped from more than one address, with every address seen for them
+    pub fn duplicate_identities(&self) -> Vec<(Pubkey, Vec<IdentitySighting>)> {
+        self.gossip.duplicate_identities.duplicates()
+    }
+
     pub fn gossip_peers(&self) -> Vec<ContactInfo> {
         let me = self.my_data().id;
         self.gossip
@@ -455,23 +548,24 @@ impl ClusterInfo {
             .table
             .values()
             .filter_map(|x| x.value.contact_info())
-            .filter(|x| ContactInfo::is_valid_address(&x.tvu))
+            .filter(|x| x.is_valid_tvu_peer())
             .filter(|x| !ClusterInfo::is_archiver(x))
             .filter(|x| x.id != me)
             .cloned()
             .collect()
     }
 
-    /// all peers that have a valid storage addr
+    /// all peers that advertise themselves as archivers with a valid storage addr
     pub fn storage_peers(&self) -> Vec<ContactInfo> {
         let me = self.my_data().id;
         self.gossip
             .crds
             .table
             .values()
-            .filter_map(|x| x.value.contact_info())
+            .filter_map(|x| x.value.archiver_info())
+            .filter(|x| x.from != me)
             .filter(|x| ContactInfo::is_valid_address(&x.storage_addr))
-            .filter(|x| x.id != me)
+            .filter_map(|x| self.get_contact_info_for_node(&x.from))
             .cloned()
             .collect()
     }
@@ -485,8 +579,7 @@ impl ClusterInfo {
             .values()
             .filter_map(|x| x.value.contact_info())
             .filter(|x| x.id != me)
-            .filter(|x| ContactInfo::is_valid_address(&x.tvu))
-            .filter(|x| ContactInfo::is_valid_address(&x.tvu_forwards))
+            .filter(|x| x.is_valid_retransmit_peer())
             .cloned()
             .collect()
     }
@@ -497,7 +590,7 @@ impl ClusterInfo {
         ClusterInfo::tvu_peers(self)
             .into_iter()
             .filter(|x| x.id != me)
-            .filter(|x| ContactInfo::is_valid_address(&x.gossip))
+            .filter(|x| x.is_valid_repair_peer())
             .collect()
     }
 
@@ -517,36 +610,8 @@ impl ClusterInfo {
         peers: &[ContactInfo],
         stakes: Option<&HashMap<Pubkey, u64, S>>,
     ) -> Vec<(u64, usize)> {
-        let stakes_and_index: Vec<_> = peers
-            .iter()
-            .enumerate()
-            .map(|(i, c)| {
-                // For stake weighted shuffle a valid weight is atleast 1. Weight 0 is
-                // assumed to be missing entry. So let's make sure stake weights are atleast 1
-                let stake = 1.max(stakes.map_or(1, |stakes| *stakes.get(&c.id).unwrap_or(&1)));
-                (stake, i)
-            })
-            .sorted_by(|(l_stake, l_info), (r_stake, r_info)| {
-                if r_stake == l_stake {
-                    peers[*r_info].id.cmp(&peers[*l_info].id)
-                } else {
-                    r_stake.cmp(&l_stake)
-                }
-            })
-            .collect();
-
-        stakes_and_index
-    }
-
-    fn stake_weighted_shuffle(
-        stakes_and_index: &[(u64, usize)],
-        seed: [u8; 32],
-    ) -> Vec<(u64, usize)> {
-        let stake_weights = stakes_and_index.iter().map(|(w, _)| *w).collect();
-
-        let shuffle = weighted_shuffle(stake_weights, seed);
-
-        shuffle.iter().map(|x| stakes_and_index[*x]).collect()
+        let ids: Vec<_> = peers.iter().map(|peer| peer.id).collect();
+        sorted_stakes_with_index(&ids, stakes)
     }
 
     // Return sorted_retransmit_peers(including self) and their stakes
@@ -568,7 +633,7 @@ impl ClusterInfo {
         stakes_and_index: &[(u64, usize)],
         seed: [u8; 32],
     ) -> (usize, Vec<(u64, usize)>) {
-        let shuffled_stakes_and_index = ClusterInfo::stake_weighted_shuffle(stakes_and_index, seed);
+        let shuffled_stakes_and_index = stake_weighted_shuffle(stakes_and_index, seed);
         let mut self_index = 0;
         shuffled_stakes_and_index
             .iter()
@@ -808,24 +873,58 @@ impl ClusterInfo {
         Ok(())
     }
 
+    /// Encode a repair request, in the compact wire format if enabled with
+    /// `set_compact_repair()` and bincode otherwise
+    fn repair_request_bytes(&self, req: &Protocol) -> Result<Vec<u8>> {
+        if self.compact_repair {
+            Ok(repair_to_bytes(req)?)
+        } else {
+            Ok(serialize(req)?)
+        }
+    }
+
     pub fn window_index_request_bytes(&self, slot: Slot, shred_index: u64) -> Result<Vec<u8>> {
         let req = Protocol::RequestWindowIndex(self.my_data().clone(), slot, shred_index);
-        let out = serialize(&req)?;
+        let out = self.repair_request_bytes(&req)?;
         Ok(out)
     }
 
     fn window_highest_index_request_bytes(&self, slot: Slot, shred_index: u64) -> Result<Vec<u8>> {
         let req = Protocol::RequestHighestWindowIndex(self.my_data().clone(), slot, shred_index);
-        let out = serialize(&req)?;
+        let out = self.repair_request_bytes(&req)?;
         Ok(out)
     }
 
     fn orphan_bytes(&self, slot: Slot) -> Result<Vec<u8>> {
         let req = Protocol::RequestOrphan(self.my_data().clone(), slot);
-        let out = serialize(&req)?;
+        let out = self.repair_request_bytes(&req)?;
         Ok(out)
     }
 
+    fn ancestor_hashes_bytes(&self, slot: Slot) -> Result<Vec<u8>> {
+        let req = Protocol::RequestAncestorHashes(self.my_data().clone(), slot);
+        let out = self.repair_request_bytes(&req)?;
+        Ok(out)
+    }
+
+    /// Take the `AncestorHashes` responses received since the last call
+    pub fn take_ancestor_hashes(&mut self) -> HashMap<Slot, (SocketAddr, Vec<(Slot, Hash)>)> {
+        std::mem::replace(&mut self.ancestor_hashes, HashMap::new())
+    }
+
+    /// The identity of each repair peer and the address repair requests are sent to
+    pub fn repair_peer_addrs(&self) -> Vec<(Pubkey, SocketAddr)> {
+        self.repair_peers()
+            .iter()
+            .map(|peer| (peer.id, peer.gossip))
+            .collect()
+    }
+
+    /// The scores of the peers shreds are requested from
+    pub fn repair_peer_scores(&self) -> Arc<Mutex<RepairPeers>> {
+        self.repair_peer_scores.clone()
+    }
+
     pub fn repair_request(&self, repair_request: &RepairType) -> Result<(SocketAddr, Vec<u8>)> {
         // find a peer that appears to be accepting replication, as indicated
         //  by a valid tvu port location
@@ -861,6 +960,13 @@ impl ClusterInfo {
                 datapoint_debug!("cluster_info-repair_orphan", ("repair-orphan", *slot, i64));
                 Ok(self.orphan_bytes(*slot)?)
             }
+            RepairType::AncestorHashes(slot) => {
+                datapoint_debug!(
+                    "cluster_info-repair_ancestor_hashes",
+                    ("repair-ancestor-hashes", *slot, i64)
+                );
+                Ok(self.ancestor_hashes_bytes(*slot)?)
+            }
         }
     }
     // If the network entrypoint hasn't been discovered yet, add it to the crds table
@@ -913,28 +1019,13 @@ impl ClusterInfo {
     /// each Vec is no larger than `PROTOCOL_PAYLOAD_SIZE`
     /// Note: some messages cannot be contained within that size so in the worst case this returns
     /// N nested Vecs with 1 item each.
-    fn split_gossip_messages(mut msgs: Vec<CrdsValue>) -> Vec<Vec<CrdsValue>> {
-        let mut messages = vec![];
-        while !msgs.is_empty() {
-            let mut payload = vec![];
-            let mut size = serialized_size(&payload).expect("Couldn't check size");
-            while let Some(msg) = msgs.pop() {
-                let msg_size = msg.size();
-                if size + msg_size > MAX_PROTOCOL_PAYLOAD_SIZE as u64 {
-                    if msg_size < MAX_PROTOCOL_PAYLOAD_SIZE as u64 {
-                        msgs.push(msg);
-                    } else {
-                        debug!(
-                            "dropping message larger than the maximum payload size {:?}",
-                            msg
-                        );
-                    }
-                    break;
-                }
-                size += msg_size;
-                payload.push(msg);
-            }
-            messages.push(payload);
+    fn split_gossip_messages(msgs: Vec<CrdsValue>) -> Vec<Vec<CrdsValue>> {
+        let (messages, skipped) = split_into_packet_payloads(msgs, MAX_PROTOCOL_PAYLOAD_SIZE);
+        if !skipped.is_empty() {
+            debug!(
+                "dropping {} messages larger than the maximum payload size",
+                skipped.len()
+            );
         }
         messages
     }
@@ -1162,9 +1253,76 @@ impl ClusterInfo {
         res
     }
 
+    /// Answer with the hashes of `slot` and its ancestors, in the compact wire format only if
+    /// the request came in it, so nodes that only decode bincode get bincode back
+    fn run_ancestor_hashes(
+        from: &ContactInfo,
+        bank_forks: Option<&Arc<RwLock<BankForks>>>,
+        self_id: &Pubkey,
+        slot: Slot,
+        compact: bool,
+    ) -> Packets {
+        let bank = bank_forks.and_then(|bank_forks| bank_forks.read().unwrap().get(slot).cloned());
+        match bank {
+            // only answer once the slot is replayed, the hash of an active bank isn't final
+            Some(bank) if bank.is_frozen() => {
+                let hashes = bank
+                    .parents()
+                    .into_iter()
+                    .take(MAX_ANCESTOR_HASHES - 1)
+                    .map(|parent| (parent.slot(), parent.hash()));
+                let hashes = std::iter::once((slot, bank.hash())).chain(hashes).collect();
+                let response = Protocol::AncestorHashes(*self_id, slot, hashes);
+                if !compact {
+                    return Packets::new(vec![Packet::from_data(&from.gossip, response)]);
+                }
+                let mut packet = Packet::default();
+                match encode_repair(&response, &mut packet.data) {
+                    Ok(size) => {
+                        packet.meta.size = size;
+                        packet.meta.set_addr(&from.gossip);
+                        Packets::new(vec![packet])
+                    }
+                    Err(e) => {
+                        warn!("unable to encode ancestor hashes of slot {}: {:?}", slot, e);
+                        Packets::default()
+                    }
+                }
+            }
+            _ => Packets::default(),
+        }
+    }
+
+    fn handle_ancestor_hashes(
+        me: &Arc<RwLock<Self>>,
+        from_addr: &SocketAddr,
+        slot: Slot,
+        hashes: Vec<(Slot, Hash)>,
+    ) {
+        let mut me = me.write().unwrap();
+        if hashes.len() > MAX_ANCESTOR_HASHES
+            || me.ancestor_hashes.len() >= MAX_PENDING_ANCESTOR_HASHES
+        {
+            inc_new_counter_debug!("cluster_info-ancestor_hashes-dropped", 1);
+            return;
+        }
+        me.ancestor_hashes.insert(slot, (*from_addr, hashes));
+    }
+
+    /// Gossip messages come bincode encoded, repair messages either bincode encoded or, from
+    /// nodes with compact repair enabled, in the compact wire format
+    pub(crate) fn deserialize_protocol(data: &[u8]) -> Option<Protocol> {
+        if wire_format::is_compact(data) {
+            wire_format::decode_repair(data).ok()
+        } else {
+            limited_deserialize(data).ok()
+        }
+    }
+
     fn handle_packets(
         me: &Arc<RwLock<Self>>,
         blocktree: Option<&Arc<Blocktree>>,
+        bank_forks: Option<&Arc<RwLock<BankForks>>>,
         stakes: &HashMap<Pubkey, u64>,
         packets: Packets,
         response_sender: &PacketSender,
@@ -1173,7 +1331,9 @@ impl ClusterInfo {
         let mut gossip_pull_data: Vec<PullData> = vec![];
         packets.packets.iter().for_each(|packet| {
             let from_addr = packet.meta.addr();
-            limited_deserialize(&packet.data[..packet.meta.size])
+            let data = &packet.data[..packet.meta.size];
+            let compact = wire_format::is_compact(data);
+            Self::deserialize_protocol(data)
                 .into_iter()
                 .for_each(|request| match request {
                     Protocol::PullRequest(filter, caller) => {
@@ -1248,9 +1408,13 @@ impl ClusterInfo {
                             inc_new_counter_debug!("cluster_info-gossip_prune_msg_verify_fail", 1);
                         }
                     }
+                    Protocol::AncestorHashes(_, slot, hashes) => {
+                        Self::handle_ancestor_hashes(me, &from_addr, slot, hashes)
+                    }
                     _ => {
-                        let _ignore_disconnect = response_sender
-                            .send(Self::handle_repair(me, &from_addr, blocktree, request));
+                        let _ignore_disconnect = response_sender.send(Self::handle_repair(
+                            me, &from_addr, blocktree, bank_forks, request, compact,
+                        ));
                     }
                 })
         });
@@ -1372,6 +1536,7 @@ impl ClusterInfo {
             Protocol::RequestWindowIndex(ref from, _, _) => from,
             Protocol::RequestHighestWindowIndex(ref from, _, _) => from,
             Protocol::RequestOrphan(ref from, _) => from,
+            Protocol::RequestAncestorHashes(ref from, _) => from,
             _ => panic!("Not a repair request"),
         }
     }
@@ -1380,7 +1545,9 @@ impl ClusterInfo {
         me: &Arc<RwLock<Self>>,
         from_addr: &SocketAddr,
         blocktree: Option<&Arc<Blocktree>>,
+        bank_forks: Option<&Arc<RwLock<BankForks>>>,
         request: Protocol,
+        compact: bool,
     ) -> Packets {
         let now = Instant::now();
 
@@ -1442,6 +1609,13 @@ impl ClusterInfo {
                         "RequestOrphan",
                     )
                 }
+                Protocol::RequestAncestorHashes(from, slot) => {
+                    inc_new_counter_debug!("cluster_info-request-ancestor-hashes", 1);
+                    (
+                        Self::run_ancestor_hashes(from, bank_forks, &self_id, *slot, compact),
+                        "RequestAncestorHashes",
+                    )
+                }
                 _ => panic!("Not a repair request"),
             }
         };
@@ -1470,7 +1644,7 @@ impl ClusterInfo {
             None => HashMap::new(),
         };
 
-        Self::handle_packets(obj, blocktree, &stakes, reqs, response_sender);
+        Self::handle_packets(obj, blocktree, bank_forks, &stakes, reqs, response_sender);
         Ok(())
     }
     pub fn listen(
@@ -1621,7 +1795,7 @@ impl Node {
         let tvu = UdpSocket::bind("127.0.0.1:0").unwrap();
         let tvu_forwards = UdpSocket::bind("127.0.0.1:0").unwrap();
         let storage = UdpSocket::bind("127.0.0.1:0").unwrap();
-        let empty = "0.0.0.0:0".parse().unwrap();
+        let empty = ContactInfo::unspecified_addr();
         let repair = UdpSocket::bind("127.0.0.1:0").unwrap();
 
         let broadcast = UdpSocket::bind("0.0.0.0:0").unwrap();
@@ -1705,43 +1879,82 @@ impl Node {
     fn get_gossip_port(
         gossip_addr: &SocketAddr,
         port_range: PortRange,
+        bind_ip: IpAddr,
     ) -> (u16, (UdpSocket, TcpListener)) {
         if gossip_addr.port() != 0 {
             (
                 gossip_addr.port(),
-                bind_common(gossip_addr.port(), false).unwrap_or_else(|e| {
+                bind_common_with_ip(bind_ip, gossip_addr.port(), false).unwrap_or_else(|e| {
                     panic!("gossip_addr bind_to port {}: {}", gossip_addr.port(), e)
                 }),
             )
         } else {
-            bind_common_in_range(port_range).expect("Failed to bind")
+            bind_common_in_range_with_ip(bind_ip, port_range).expect("Failed to bind")
         }
     }
-    fn bind(port_range: PortRange) -> (u16, UdpSocket) {
-        bind_in_range(port_range).expect("Failed to bind")
+    fn bind(bind_ip: IpAddr, port_range: PortRange) -> (u16, UdpSocket) {
+        bind_in_range_with_ip(bind_ip, port_range).expect("Failed to bind")
     }
     pub fn new_with_external_ip(
         pubkey: &Pubkey,
         gossip_addr: &SocketAddr,
         port_range: PortRange,
     ) -> Node {
-        let (gossip_port, (gossip, ip_echo)) = Self::get_gossip_port(gossip_addr, port_range);
+        Self::new_with_external_ip_and_tvu_receive_sockets(
+            pubkey,
+            gossip_addr,
+            port_range,
+            DEFAULT_TVU_RECEIVE_SOCKETS,
+        )
+    }
+    /// Like `new_with_external_ip`, but binds `num_tvu_receive_sockets` sockets to each of the
+    /// tvu and tvu_forwards ports with SO_REUSEPORT, so the kernel spreads incoming shreds over
+    /// that many receive queues, each drained by its own receiver thread.
+    pub fn new_with_external_ip_and_tvu_receive_sockets(
+        pubkey: &Pubkey,
+        gossip_addr: &SocketAddr,
+        port_range: PortRange,
+        num_tvu_receive_sockets: usize,
+    ) -> Node {
+        Self::new_with_bind_ip(
+            pubkey,
+            gossip_addr,
+            port_range,
+            num_tvu_receive_sockets,
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        )
+    }
+    // Binds every socket to `bind_ip` and advertises them at `gossip_addr`'s ip
+    fn new_with_bind_ip(
+        pubkey: &Pubkey,
+        gossip_addr: &SocketAddr,
+        port_range: PortRange,
+        num_tvu_receive_sockets: usize,
+        bind_ip: IpAddr,
+    ) -> Node {
+        let (gossip_port, (gossip, ip_echo)) =
+            Self::get_gossip_port(gossip_addr, port_range, bind_ip);
 
-        let (tvu_port, tvu_sockets) = multi_bind_in_range(port_range, 8).expect("tvu multi_bind");
+        let num_tvu_receive_sockets = num_tvu_receive_sockets.max(1);
+        let (tvu_port, tvu_sockets) =
+            multi_bind_in_range_with_ip(bind_ip, port_range, num_tvu_receive_sockets)
+                .expect("tvu multi_bind");
 
         let (tvu_forwards_port, tvu_forwards_sockets) =
-            multi_bind_in_range(port_range, 8).expect("tvu_forwards multi_bind");
+            multi_bind_in_range_with_ip(bind_ip, port_range, num_tvu_receive_sockets)
+                .expect("tvu_forwards multi_bind");
 
-        let (tpu_port, tpu_sockets) = multi_bind_in_range(port_range, 32).expect("tpu multi_bind");
+        let (tpu_port, tpu_sockets) =
+            multi_bind_in_range_with_ip(bind_ip, port_range, 32).expect("tpu multi_bind");
 
         let (tpu_forwards_port, tpu_forwards_sockets) =
-            multi_bind_in_range(port_range, 8).expect("tpu_forwards multi_bind");
+            multi_bind_in_range_with_ip(bind_ip, port_range, 8).expect("tpu_forwards multi_bind");
 
         let (_, retransmit_sockets) =
-            multi_bind_in_range(port_range, 8).expect("retransmit multi_bind");
+            multi_bind_in_range_with_ip(bind_ip, port_range, 8).expect("retransmit multi_bind");
 
-        let (repair_port, repair) = Self::bind(port_range);
-        let (_, broadcast) = Self::bind(port_range);
+        let (repair_port, repair) = Self::bind(bind_ip, port_range);
+        let (_, broadcast) = Self::bind(bind_ip, port_range);
 
         let info = ContactInfo::new(
             pubkey,
@@ -1774,13 +1987,32 @@ impl Node {
             },
         }
     }
+    /// An archiver node advertised at `gossip_addr`, its sockets are dual-stack if
+    /// `gossip_addr` is an IPv6 address
     pub fn new_archiver_with_external_ip(
         pubkey: &Pubkey,
         gossip_addr: &SocketAddr,
         port_range: PortRange,
     ) -> Node {
-        let mut new = Self::new_with_external_ip(pubkey, gossip_addr, port_range);
-        let (storage_port, storage_socket) = Self::bind(port_range);
+        let bind_ip = unspecified_ip_for(&gossip_addr.ip());
+        Self::new_archiver_with_bind_ip(pubkey, gossip_addr, port_range, bind_ip)
+    }
+    /// Like `new_archiver_with_external_ip`, but binds every socket to `bind_ip`, to pick the
+    /// interface of a multi-homed host
+    pub fn new_archiver_with_bind_ip(
+        pubkey: &Pubkey,
+        gossip_addr: &SocketAddr,
+        port_range: PortRange,
+        bind_ip: IpAddr,
+    ) -> Node {
+        let mut new = Self::new_with_bind_ip(
+            pubkey,
+            gossip_addr,
+            port_range,
+            DEFAULT_TVU_RECEIVE_SOCKETS,
+            bind_ip,
+        );
+        let (storage_port, storage_socket) = Self::bind(bind_ip, port_range);
 
         new.info.storage_addr = SocketAddr::new(gossip_addr.ip(), storage_port);
         new.sockets.storage = Some(storage_socket);
@@ -2103,6 +2335,82 @@ mod tests {
         Blocktree::destroy(&ledger_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn run_ancestor_hashes() {
+        use crate::genesis_utils::{create_genesis_config, GenesisConfigInfo};
+        use solana_runtime::bank::Bank;
+
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let mut bank_forks = BankForks::new(0, Bank::new(&genesis_config));
+        let bank0 = bank_forks[0].clone();
+        let bank1 = bank_forks.insert(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        bank1.freeze();
+        bank_forks.insert(Bank::new_from_parent(&bank1, &Pubkey::default(), 2));
+        let bank_forks = Arc::new(RwLock::new(bank_forks));
+        let from = ContactInfo::new_localhost(&Pubkey::new_rand(), timestamp());
+        let self_id = Pubkey::new_rand();
+
+        // slots that are unknown or not replayed yet aren't answered
+        for slot in &[2, 3] {
+            assert!(ClusterInfo::run_ancestor_hashes(
+                &from,
+                Some(&bank_forks),
+                &self_id,
+                *slot,
+                true
+            )
+            .is_empty());
+        }
+        assert!(ClusterInfo::run_ancestor_hashes(&from, None, &self_id, 1, true).is_empty());
+
+        // a bincode request gets a bincode response
+        let rv = ClusterInfo::run_ancestor_hashes(&from, Some(&bank_forks), &self_id, 1, false);
+        assert_eq!(rv.packets.len(), 1);
+        let packet = &rv.packets[0];
+        assert!(!wire_format::is_compact(&packet.data[..packet.meta.size]));
+        match limited_deserialize(&packet.data[..packet.meta.size]).unwrap() {
+            Protocol::AncestorHashes(_, slot, hashes) => {
+                assert_eq!(slot, 1);
+                assert_eq!(hashes, vec![(1, bank1.hash()), (0, bank0.hash())]);
+            }
+            _ => panic!("expected an AncestorHashes response"),
+        }
+
+        let rv = ClusterInfo::run_ancestor_hashes(&from, Some(&bank_forks), &self_id, 1, true);
+        assert_eq!(rv.packets.len(), 1);
+        let packet = &rv.packets[0];
+        assert_eq!(packet.meta.addr(), from.gossip);
+        let expected = vec![(1, bank1.hash()), (0, bank0.hash())];
+        match wire_format::decode_repair(&packet.data[..packet.meta.size]).unwrap() {
+            Protocol::AncestorHashes(id, slot, hashes) => {
+                assert_eq!(id, self_id);
+                assert_eq!(slot, 1);
+                assert_eq!(hashes, expected);
+            }
+            _ => panic!("expected an AncestorHashes response"),
+        }
+
+        // responses are kept until taken, oversized ones are dropped
+        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+            from.clone(),
+        )));
+        ClusterInfo::handle_ancestor_hashes(&cluster_info, &from.gossip, 1, expected.clone());
+        ClusterInfo::handle_ancestor_hashes(
+            &cluster_info,
+            &from.gossip,
+            2,
+            vec![(0, Hash::default()); MAX_ANCESTOR_HASHES + 1],
+        );
+        let responses = cluster_info.write().unwrap().take_ancestor_hashes();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[&1], (from.gossip, expected));
+        assert!(cluster_info
+            .write()
+            .unwrap()
+            .take_ancestor_hashes()
+            .is_empty());
+    }
+
     fn assert_in_range(x: u16, range: (u16, u16)) {
         assert!(x >= range.0);
         assert!(x < range.1);
@@ -2162,6 +2470,37 @@ mod tests {
         assert_eq!(node.sockets.gossip.local_addr().unwrap().port(), port);
     }
 
+    #[test]
+    fn new_with_external_ip_and_tvu_receive_sockets_test() {
+        let ip = Ipv4Addr::from(0);
+        let node = Node::new_with_external_ip_and_tvu_receive_sockets(
+            &Pubkey::new_rand(),
+            &socketaddr!(ip, 0),
+            VALIDATOR_PORT_RANGE,
+            3,
+        );
+        check_node_sockets(&node, IpAddr::V4(ip), VALIDATOR_PORT_RANGE);
+        if !cfg!(windows) {
+            assert_eq!(node.sockets.tvu.len(), 3);
+            assert_eq!(node.sockets.tvu_forwards.len(), 3);
+        }
+        let port = node.sockets.tvu[0].local_addr().unwrap().port();
+        assert!(node
+            .sockets
+            .tvu
+            .iter()
+            .all(|socket| socket.local_addr().unwrap().port() == port));
+        assert_eq!(node.info.tvu.port(), port);
+
+        let node = Node::new_with_external_ip_and_tvu_receive_sockets(
+            &Pubkey::new_rand(),
+            &socketaddr!(ip, 0),
+            VALIDATOR_PORT_RANGE,
+            0,
+        );
+        assert_eq!(node.sockets.tvu.len(), 1);
+    }
+
     #[test]
     fn new_archiver_external_ip_test() {
         let ip = Ipv4Addr::from(0);
@@ -2179,6 +2518,43 @@ mod tests {
         check_sockets(&node.sockets.tvu, ip, VALIDATOR_PORT_RANGE);
     }
 
+    #[test]
+    fn new_archiver_ipv6_test() {
+        // IPv6 may be disabled on the test host
+        if bind_in_range_with_ip("::".parse().unwrap(), VALIDATOR_PORT_RANGE).is_err() {
+            return;
+        }
+        let gossip_ip: IpAddr = "2001:db8::1".parse().unwrap();
+        let node = Node::new_archiver_with_external_ip(
+            &Pubkey::new_rand(),
+            &SocketAddr::new(gossip_ip, 0),
+            VALIDATOR_PORT_RANGE,
+        );
+
+        // sockets are dual-stack, advertised at the IPv6 address
+        let ip = unspecified_ip_for(&gossip_ip);
+        check_socket(&node.sockets.storage.unwrap(), ip, VALIDATOR_PORT_RANGE);
+        check_socket(&node.sockets.gossip, ip, VALIDATOR_PORT_RANGE);
+        check_sockets(&node.sockets.tvu, ip, VALIDATOR_PORT_RANGE);
+        assert_eq!(node.info.gossip.ip(), gossip_ip);
+        assert_eq!(node.info.storage_addr.ip(), gossip_ip);
+
+        let bind_ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let node = Node::new_archiver_with_bind_ip(
+            &Pubkey::new_rand(),
+            &socketaddr!(Ipv4Addr::new(10, 0, 0, 1), 0),
+            VALIDATOR_PORT_RANGE,
+            bind_ip,
+        );
+        check_socket(
+            &node.sockets.storage.unwrap(),
+            bind_ip,
+            VALIDATOR_PORT_RANGE,
+        );
+        check_socket(&node.sockets.repair, bind_ip, VALIDATOR_PORT_RANGE);
+        check_sockets(&node.sockets.tvu, bind_ip, VALIDATOR_PORT_RANGE);
+    }
+
     //test that all cluster_info objects only generate signed messages
     //when constructed with keypairs
     #[test]
@@ -2378,6 +2754,45 @@ mod tests {
         assert_eq!(max_ts, new_max_ts);
     }
 
+    #[test]
+    fn test_storage_peers() {
+        let keypair = Arc::new(Keypair::new());
+        let mut cluster_info = ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), timestamp()),
+            keypair,
+        );
+        // advertising myself doesn't make me my own peer
+        cluster_info.push_archiver_info(0);
+        assert!(cluster_info.storage_peers().is_empty());
+
+        // a node with a storage address alone is not an archiver
+        let validator = ContactInfo::new_localhost(&Pubkey::new_rand(), timestamp());
+        cluster_info.insert_info(validator);
+        assert!(cluster_info.storage_peers().is_empty());
+
+        let archiver_keypair = Keypair::new();
+        let archiver = ContactInfo::new_localhost(&archiver_keypair.pubkey(), timestamp());
+        cluster_info.insert_info(archiver.clone());
+        let value = CrdsValue::new_signed(
+            CrdsData::ArchiverInfo(ArchiverInfo::new(
+                archiver.id,
+                archiver.storage_addr,
+                2,
+                timestamp(),
+            )),
+            &archiver_keypair,
+        );
+        cluster_info.gossip.crds.insert(value, timestamp()).unwrap();
+        assert_eq!(cluster_info.storage_peers(), vec![archiver.clone()]);
+        assert_eq!(
+            cluster_info
+                .get_archiver_info_for_node(&archiver.id)
+                .unwrap()
+                .num_segments,
+            2
+        );
+    }
+
     #[test]
     fn test_add_entrypoint() {
         let node_keypair = Arc::new(Keypair::new());
@@ -2491,7 +2906,7 @@ mod tests {
         // no tvu
         let id3 = Pubkey::new(&[3u8; 32]);
         let mut contact_info = ContactInfo::new_localhost(&id3, timestamp());
-        contact_info.tvu = "0.0.0.0:0".parse().unwrap();
+        contact_info.tvu = ContactInfo::unspecified_addr();
         cluster_info.insert_info(contact_info);
         stakes.insert(id3, 10);
 
@@ -2601,6 +3016,24 @@ mod tests {
             max_protocol_size
                 > serialized_size(&Protocol::RequestOrphan(ContactInfo::default(), 0)).unwrap()
         );
+        assert!(
+            max_protocol_size
+                > serialized_size(&Protocol::RequestAncestorHashes(ContactInfo::default(), 0))
+                    .unwrap()
+        );
+        // and that a full ancestor hashes response fits in a packet
+        assert!(
+            serialized_size(&Protocol::AncestorHashes(
+                Pubkey::default(),
+                0,
+                vec![(0, Hash::default()); MAX_ANCESTOR_HASHES]
+            ))
+            .unwrap()
+                <= PACKET_DATA_SIZE as u64
+        );
+        // the same goes for the compact encodings repairs are sent with
+        assert!(max_protocol_size > wire_format::MAX_REPAIR_REQUEST_SIZE as u64);
+        assert!(wire_format::MAX_ANCESTOR_HASHES_SIZE <= PACKET_DATA_SIZE);
         // finally assert the header size estimation is correct
         assert_eq!(MAX_PROTOCOL_HEADER_SIZE, max_protocol_size);
     }
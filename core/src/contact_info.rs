@@ -1,5 +1,4 @@
 use solana_sdk::pubkey::Pubkey;
-#[cfg(test)]
 use solana_sdk::rpc_port;
 #[cfg(test)]
 use solana_sdk::signature::{Keypair, KeypairUtil};
@@ -31,6 +30,10 @@ pub struct ContactInfo {
     pub rpc_pubsub: SocketAddr,
     /// latest wallclock picked
     pub wallclock: u64,
+    /// version of the shreds this node produces and accepts, derived from genesis
+    pub shred_version: u16,
+    /// wallclock this node instance started at, a restarted node picks a newer one
+    pub outset: u64,
 }
 
 impl Ord for ContactInfo {
@@ -84,6 +87,8 @@ impl Default for ContactInfo {
             rpc: socketaddr_any!(),
             rpc_pubsub: socketaddr_any!(),
             wallclock: 0,
+            shred_version: 0,
+            outset: 0,
         }
     }
 }
@@ -115,6 +120,8 @@ impl ContactInfo {
             rpc,
             rpc_pubsub,
             wallclock: now,
+            shred_version: 0,
+            outset: now,
         }
     }
 
@@ -156,33 +163,10 @@ impl ContactInfo {
 
     #[cfg(test)]
     pub(crate) fn new_with_pubkey_socketaddr(pubkey: &Pubkey, bind_addr: &SocketAddr) -> Self {
-        fn next_port(addr: &SocketAddr, nxt: u16) -> SocketAddr {
-            let mut nxt_addr = *addr;
-            nxt_addr.set_port(addr.port() + nxt);
-            nxt_addr
-        }
-
-        let tpu_addr = *bind_addr;
-        let gossip_addr = next_port(&bind_addr, 1);
-        let tvu_addr = next_port(&bind_addr, 2);
-        let tpu_forwards_addr = next_port(&bind_addr, 3);
-        let tvu_forwards_addr = next_port(&bind_addr, 4);
-        let repair = next_port(&bind_addr, 5);
-        let rpc_addr = SocketAddr::new(bind_addr.ip(), rpc_port::DEFAULT_RPC_PORT);
-        let rpc_pubsub_addr = SocketAddr::new(bind_addr.ip(), rpc_port::DEFAULT_RPC_PUBSUB_PORT);
-        Self::new(
-            pubkey,
-            gossip_addr,
-            tvu_addr,
-            tvu_forwards_addr,
-            repair,
-            tpu_addr,
-            tpu_forwards_addr,
-            "0.0.0.0:0".parse().unwrap(),
-            rpc_addr,
-            rpc_pubsub_addr,
-            timestamp(),
-        )
+        ContactInfoBuilder::new(pubkey)
+            .ports_from_base(bind_addr)
+            .wallclock(timestamp())
+            .build()
     }
 
     #[cfg(test)]
@@ -193,20 +177,20 @@ impl ContactInfo {
 
     // Construct a ContactInfo that's only usable for gossip
     pub fn new_gossip_entry_point(gossip_addr: &SocketAddr) -> Self {
-        let daddr: SocketAddr = socketaddr!("0.0.0.0:0");
-        Self::new(
-            &Pubkey::default(),
-            *gossip_addr,
-            daddr,
-            daddr,
-            daddr,
-            daddr,
-            daddr,
-            daddr,
-            daddr,
-            daddr,
-            timestamp(),
-        )
+        ContactInfoBuilder::new(&Pubkey::default())
+            .gossip(*gossip_addr)
+            .wallclock(timestamp())
+            .build()
+    }
+
+    /// The address advertised for a service the node does not provide
+    pub fn unspecified_addr() -> SocketAddr {
+        socketaddr_any!()
+    }
+
+    /// true if `addr` is the unspecified address, i.e. the service is not advertised
+    pub fn is_unspecified(addr: &SocketAddr) -> bool {
+        addr.ip().is_unspecified() && addr.port() == 0
     }
 
     fn is_valid_ip(addr: IpAddr) -> bool {
@@ -222,6 +206,47 @@ impl ContactInfo {
         (addr.port() != 0) && Self::is_valid_ip(addr.ip())
     }
 
+    /// a peer that shreds can be sent to
+    pub fn is_valid_tvu_peer(&self) -> bool {
+        Self::is_valid_address(&self.tvu)
+    }
+
+    /// a peer that shreds can be retransmitted to, on either of its tvu ports
+    pub fn is_valid_retransmit_peer(&self) -> bool {
+        self.is_valid_tvu_peer() && Self::is_valid_address(&self.tvu_forwards)
+    }
+
+    /// a peer that repairs can be requested from
+    pub fn is_valid_repair_peer(&self) -> bool {
+        self.is_valid_tvu_peer() && Self::is_valid_address(&self.gossip)
+    }
+
+    fn addrs(&self) -> [(&'static str, &SocketAddr); 9] {
+        [
+            ("gossip", &self.gossip),
+            ("tvu", &self.tvu),
+            ("tvu_forwards", &self.tvu_forwards),
+            ("repair", &self.repair),
+            ("tpu", &self.tpu),
+            ("tpu_forwards", &self.tpu_forwards),
+            ("storage_addr", &self.storage_addr),
+            ("rpc", &self.rpc),
+            ("rpc_pubsub", &self.rpc_pubsub),
+        ]
+    }
+
+    /// Checks that the gossip address is valid and that every other address is either
+    /// unspecified or valid, so the node never advertises an address peers cannot reach
+    pub fn validate(&self) -> Result<(), ContactInfoError> {
+        for &(name, addr) in self.addrs().iter() {
+            let required = name == "gossip";
+            if (required || !Self::is_unspecified(addr)) && !Self::is_valid_address(addr) {
+                return Err(ContactInfoError::UnroutableAddress(name, *addr));
+            }
+        }
+        Ok(())
+    }
+
     pub fn client_facing_addr(&self) -> (SocketAddr, SocketAddr) {
         (self.rpc, self.tpu)
     }
@@ -235,6 +260,114 @@ impl ContactInfo {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum ContactInfoError {
+    /// the named address is advertised but cannot be reached by peers
+    UnroutableAddress(&'static str, SocketAddr),
+}
+
+/// Builds a `ContactInfo`, every address not set is left unspecified
+pub struct ContactInfoBuilder {
+    info: ContactInfo,
+}
+
+impl ContactInfoBuilder {
+    pub fn new(id: &Pubkey) -> Self {
+        Self {
+            info: ContactInfo {
+                id: *id,
+                ..ContactInfo::default()
+            },
+        }
+    }
+
+    /// Derives the gossip, tvu, tvu_forwards, repair, tpu and tpu_forwards ports from
+    /// consecutive ports starting at `base`, which becomes the tpu address, and uses the
+    /// default rpc ports on the same ip
+    pub fn ports_from_base(mut self, base: &SocketAddr) -> Self {
+        let next_port = |offset: u16| {
+            base.port()
+                .checked_add(offset)
+                .map(|port| SocketAddr::new(base.ip(), port))
+                .unwrap_or_else(ContactInfo::unspecified_addr)
+        };
+        self.info.tpu = next_port(0);
+        self.info.gossip = next_port(1);
+        self.info.tvu = next_port(2);
+        self.info.tpu_forwards = next_port(3);
+        self.info.tvu_forwards = next_port(4);
+        self.info.repair = next_port(5);
+        self.info.rpc = SocketAddr::new(base.ip(), rpc_port::DEFAULT_RPC_PORT);
+        self.info.rpc_pubsub = SocketAddr::new(base.ip(), rpc_port::DEFAULT_RPC_PUBSUB_PORT);
+        self
+    }
+
+    pub fn gossip(mut self, addr: SocketAddr) -> Self {
+        self.info.gossip = addr;
+        self
+    }
+
+    pub fn tvu(mut self, addr: SocketAddr) -> Self {
+        self.info.tvu = addr;
+        self
+    }
+
+    pub fn tvu_forwards(mut self, addr: SocketAddr) -> Self {
+        self.info.tvu_forwards = addr;
+        self
+    }
+
+    pub fn repair(mut self, addr: SocketAddr) -> Self {
+        self.info.repair = addr;
+        self
+    }
+
+    pub fn tpu(mut self, addr: SocketAddr) -> Self {
+        self.info.tpu = addr;
+        self
+    }
+
+    pub fn tpu_forwards(mut self, addr: SocketAddr) -> Self {
+        self.info.tpu_forwards = addr;
+        self
+    }
+
+    pub fn storage_addr(mut self, addr: SocketAddr) -> Self {
+        self.info.storage_addr = addr;
+        self
+    }
+
+    pub fn rpc(mut self, addr: SocketAddr) -> Self {
+        self.info.rpc = addr;
+        self
+    }
+
+    pub fn rpc_pubsub(mut self, addr: SocketAddr) -> Self {
+        self.info.rpc_pubsub = addr;
+        self
+    }
+
+    pub fn wallclock(mut self, wallclock: u64) -> Self {
+        self.info.wallclock = wallclock;
+        self
+    }
+
+    pub fn shred_version(mut self, shred_version: u16) -> Self {
+        self.info.shred_version = shred_version;
+        self
+    }
+
+    pub fn build(self) -> ContactInfo {
+        self.info
+    }
+
+    /// Like `build`, but fails if an advertised address is unroutable, see `ContactInfo::validate`
+    pub fn build_validated(self) -> Result<ContactInfo, ContactInfoError> {
+        self.info.validate()?;
+        Ok(self.info)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -324,4 +457,78 @@ mod tests {
         ci.rpc = socketaddr!("127.0.0.1:234");
         assert!(ci.valid_client_facing_addr().is_some());
     }
+
+    #[test]
+    fn test_builder_ports_from_base() {
+        let pubkey = Pubkey::new_rand();
+        let base = socketaddr!("127.0.0.1:8000");
+        let ci = ContactInfoBuilder::new(&pubkey)
+            .ports_from_base(&base)
+            .shred_version(2)
+            .build();
+        assert_eq!(ci.id, pubkey);
+        assert_eq!(ci.tpu, base);
+        assert_eq!(ci.gossip.port(), 8001);
+        assert_eq!(ci.tvu.port(), 8002);
+        assert_eq!(ci.tpu_forwards.port(), 8003);
+        assert_eq!(ci.tvu_forwards.port(), 8004);
+        assert_eq!(ci.repair.port(), 8005);
+        assert_eq!(ci.rpc.port(), rpc_port::DEFAULT_RPC_PORT);
+        assert!(ContactInfo::is_unspecified(&ci.storage_addr));
+        assert_eq!(ci.shred_version, 2);
+        assert_eq!(ci.validate(), Ok(()));
+
+        let ci = ContactInfoBuilder::new(&pubkey)
+            .ports_from_base(&socketaddr!("127.0.0.1:65534"))
+            .build();
+        assert_eq!(ci.gossip.port(), 65535);
+        assert!(ContactInfo::is_unspecified(&ci.tvu));
+    }
+
+    #[test]
+    fn test_builder_validation() {
+        let pubkey = Pubkey::new_rand();
+        let gossip = socketaddr!("127.0.0.1:8001");
+        assert!(ContactInfoBuilder::new(&pubkey)
+            .gossip(gossip)
+            .build_validated()
+            .is_ok());
+        assert_eq!(
+            ContactInfoBuilder::new(&pubkey).build_validated(),
+            Err(ContactInfoError::UnroutableAddress(
+                "gossip",
+                ContactInfo::unspecified_addr()
+            ))
+        );
+        let unroutable = socketaddr!(0, 8002);
+        assert_eq!(
+            ContactInfoBuilder::new(&pubkey)
+                .gossip(gossip)
+                .tvu(unroutable)
+                .build_validated(),
+            Err(ContactInfoError::UnroutableAddress("tvu", unroutable))
+        );
+        let multicast = socketaddr!("224.0.1.255:1000");
+        assert_eq!(
+            ContactInfoBuilder::new(&pubkey)
+                .gossip(gossip)
+                .rpc(multicast)
+                .build_validated(),
+            Err(ContactInfoError::UnroutableAddress("rpc", multicast))
+        );
+    }
+
+    #[test]
+    fn test_peer_helpers() {
+        let mut ci = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
+        assert!(ci.is_valid_tvu_peer());
+        assert!(ci.is_valid_retransmit_peer());
+        assert!(ci.is_valid_repair_peer());
+        ci.tvu_forwards = ContactInfo::unspecified_addr();
+        assert!(ci.is_valid_tvu_peer());
+        assert!(!ci.is_valid_retransmit_peer());
+        ci.tvu = ContactInfo::unspecified_addr();
+        assert!(!ci.is_valid_tvu_peer());
+        assert!(!ci.is_valid_repair_peer());
+    }
 }
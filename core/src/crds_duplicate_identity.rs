@@ -0,0 +1,238 @@
+//! Duplicate node identity detection
+//! Two nodes started with the same identity keypair but different addresses keep overwriting
+//! each other's ContactInfo in the crds table, so the cluster splits its traffic between them
+//! without either node noticing.  Every address a pubkey is gossiped with is recorded here, and
+//! a pubkey is reported as duplicated once two of its addresses are seen alternating.  A node
+//! that restarts at a new address stops advertising the old one and picks a newer outset, so the
+//! wallclock ranges of the two instances don't overlap and the move isn't reported, even when it
+//! later comes back to an address it used before.
+
+use crate::contact_info::ContactInfo;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+
+/// One address a pubkey has been gossiped with
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdentitySighting {
+    /// The latest ContactInfo seen with this gossip address
+    pub contact_info: ContactInfo,
+    /// Oldest wallclock seen from the latest instance at this gossip address
+    pub first_wallclock: u64,
+    /// Newest wallclock seen from the latest instance at this gossip address
+    pub last_wallclock: u64,
+    /// Local timestamp this gossip address was last seen at
+    pub last_seen: u64,
+}
+
+impl IdentitySighting {
+    fn new(contact_info: &ContactInfo, now: u64) -> Self {
+        Self {
+            contact_info: contact_info.clone(),
+            first_wallclock: contact_info.wallclock,
+            last_wallclock: contact_info.wallclock,
+            last_seen: now,
+        }
+    }
+
+    fn update(&mut self, contact_info: &ContactInfo, now: u64) {
+        if contact_info.outset > self.contact_info.outset {
+            // the node restarted, the wallclocks of the instance it replaced no longer count
+            *self = Self::new(contact_info, now.max(self.last_seen));
+            return;
+        }
+        self.last_seen = self.last_seen.max(now);
+        if contact_info.outset < self.contact_info.outset {
+            // a stale value of an instance that has since restarted
+            return;
+        }
+        if contact_info.wallclock > self.last_wallclock {
+            self.contact_info = contact_info.clone();
+            self.last_wallclock = contact_info.wallclock;
+        }
+        self.first_wallclock = self.first_wallclock.min(contact_info.wallclock);
+    }
+
+    /// Two different instances advertised their addresses while the other one was still in use
+    fn overlaps(&self, other: &Self) -> bool {
+        self.contact_info.outset != other.contact_info.outset
+            && self.last_wallclock > other.first_wallclock
+            && other.last_wallclock > self.first_wallclock
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DuplicateIdentities {
+    sightings: HashMap<Pubkey, HashMap<SocketAddr, IdentitySighting>>,
+    duplicates: HashSet<Pubkey>,
+}
+
+impl DuplicateIdentities {
+    /// Record a gossiped ContactInfo, returns true if it shows a new conflicting address for its
+    /// pubkey
+    pub fn observe(&mut self, contact_info: &ContactInfo, now: u64) -> bool {
+        if !ContactInfo::is_valid_address(&contact_info.gossip) {
+            return false;
+        }
+        let sightings = self.sightings.entry(contact_info.id).or_default();
+        let is_new_address = !sightings.contains_key(&contact_info.gossip);
+        let sighting = sightings
+            .entry(contact_info.gossip)
+            .and_modify(|sighting| sighting.update(contact_info, now))
+            .or_insert_with(|| IdentitySighting::new(contact_info, now))
+            .clone();
+        let conflict = sightings
+            .iter()
+            .any(|(addr, other)| *addr != contact_info.gossip && other.overlaps(&sighting));
+        if !conflict {
+            return false;
+        }
+        let is_new_duplicate = self.duplicates.insert(contact_info.id);
+        is_new_duplicate || is_new_address
+    }
+
+    pub fn is_duplicate(&self, pubkey: &Pubkey) -> bool {
+        self.duplicates.contains(pubkey)
+    }
+
+    /// Every address seen for each duplicated pubkey, newest first
+    pub fn duplicates(&self) -> Vec<(Pubkey, Vec<IdentitySighting>)> {
+        self.duplicates
+            .iter()
+            .filter_map(|pubkey| {
+                let mut sightings: Vec<_> = self.sightings.get(pubkey)?.values().cloned().collect();
+                sightings.sort_by(|a, b| b.last_wallclock.cmp(&a.last_wallclock));
+                Some((*pubkey, sightings))
+            })
+            .collect()
+    }
+
+    /// Forget the addresses not seen since `min_ts`, a pubkey left with a single address is no
+    /// longer reported
+    pub fn purge(&mut self, min_ts: u64) {
+        self.sightings.retain(|_, sightings| {
+            sightings.retain(|_, sighting| sighting.last_seen > min_ts);
+            !sightings.is_empty()
+        });
+        let sightings = &self.sightings;
+        self.duplicates.retain(|pubkey| {
+            sightings
+                .get(pubkey)
+                .map(|sightings| sightings.len() > 1)
+                .unwrap_or(false)
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn contact_info(id: &Pubkey, gossip: &str, outset: u64, wallclock: u64) -> ContactInfo {
+        let mut contact_info = ContactInfo::new_localhost(id, wallclock);
+        contact_info.gossip = gossip.parse().unwrap();
+        contact_info.outset = outset;
+        contact_info
+    }
+
+    #[test]
+    fn test_duplicate_identity_alternating() {
+        let mut duplicates = DuplicateIdentities::default();
+        let id = Pubkey::new_rand();
+        assert!(!duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 1), 1));
+        assert!(!duplicates.observe(&contact_info(&id, "10.0.0.2:8001", 2, 2), 2));
+        assert!(!duplicates.is_duplicate(&id));
+
+        // the first address comes back while the second is still in use
+        assert!(duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 3), 3));
+        assert!(duplicates.is_duplicate(&id));
+        // already reported
+        assert!(!duplicates.observe(&contact_info(&id, "10.0.0.2:8001", 2, 4), 4));
+
+        let reported = duplicates.duplicates();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].0, id);
+        let addrs: Vec<_> = reported[0]
+            .1
+            .iter()
+            .map(|sighting| sighting.contact_info.gossip.to_string())
+            .collect();
+        assert_eq!(addrs, vec!["10.0.0.2:8001", "10.0.0.1:8001"]);
+        assert_eq!(reported[0].1[1].first_wallclock, 1);
+        assert_eq!(reported[0].1[1].last_wallclock, 3);
+
+        // a third node reuses the identity
+        assert!(duplicates.observe(&contact_info(&id, "10.0.0.3:8001", 0, 2), 5));
+    }
+
+    #[test]
+    fn test_duplicate_identity_moved_node() {
+        let mut duplicates = DuplicateIdentities::default();
+        let id = Pubkey::new_rand();
+        duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 1), 1);
+        duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 2), 2);
+        duplicates.observe(&contact_info(&id, "10.0.0.2:8001", 3, 3), 3);
+        // stale values of the old address still arrive over pull responses
+        duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 2), 4);
+        duplicates.observe(&contact_info(&id, "10.0.0.2:8001", 3, 4), 5);
+        assert!(!duplicates.is_duplicate(&id));
+        assert!(duplicates.duplicates().is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_identity_restarted_node() {
+        let mut duplicates = DuplicateIdentities::default();
+        let id = Pubkey::new_rand();
+        duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 1), 1);
+        duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 5), 5);
+        // restarted with a new ip
+        assert!(!duplicates.observe(&contact_info(&id, "10.0.0.2:8001", 6, 6), 6));
+        duplicates.observe(&contact_info(&id, "10.0.0.2:8001", 6, 10), 10);
+        // and restarted again with the first one
+        assert!(!duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 11, 11), 11));
+        assert_eq!(duplicates.sightings[&id].len(), 2);
+        assert_eq!(
+            duplicates.sightings[&id][&"10.0.0.1:8001".parse::<SocketAddr>().unwrap()]
+                .first_wallclock,
+            11
+        );
+
+        // stale values of the earlier instances still arrive over pull responses
+        assert!(!duplicates.observe(&contact_info(&id, "10.0.0.2:8001", 6, 9), 12));
+        assert!(!duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 4), 13));
+        assert!(!duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 11, 14), 14));
+        assert!(!duplicates.is_duplicate(&id));
+
+        // an instance that started before the latest one is still advertising its address
+        assert!(duplicates.observe(&contact_info(&id, "10.0.0.2:8001", 6, 15), 15));
+    }
+
+    #[test]
+    fn test_duplicate_identity_invalid_address() {
+        let mut duplicates = DuplicateIdentities::default();
+        let id = Pubkey::new_rand();
+        duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 1), 1);
+        assert!(!duplicates.observe(&contact_info(&id, "0.0.0.0:0", 2, 2), 2));
+        duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 3), 3);
+        assert!(!duplicates.is_duplicate(&id));
+    }
+
+    #[test]
+    fn test_duplicate_identity_purge() {
+        let mut duplicates = DuplicateIdentities::default();
+        let id = Pubkey::new_rand();
+        duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 1), 1);
+        duplicates.observe(&contact_info(&id, "10.0.0.2:8001", 2, 2), 2);
+        duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 3), 3);
+        assert!(duplicates.is_duplicate(&id));
+
+        // the second node went away
+        duplicates.observe(&contact_info(&id, "10.0.0.1:8001", 1, 10), 10);
+        duplicates.purge(5);
+        assert!(!duplicates.is_duplicate(&id));
+        assert_eq!(duplicates.sightings[&id].len(), 1);
+
+        duplicates.purge(10);
+        assert!(duplicates.sightings.is_empty());
+    }
+}
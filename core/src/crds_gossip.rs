@@ -4,6 +4,7 @@
 //! packet::PACKET_DATA_SIZE size.
 
 use crate::crds::{Crds, VersionedCrdsValue};
+use crate::crds_duplicate_identity::DuplicateIdentities;
 use crate::crds_gossip_error::CrdsGossipError;
 use crate::crds_gossip_pull::{CrdsFilter, CrdsGossipPull};
 use crate::crds_gossip_push::{CrdsGossipPush, CRDS_GOSSIP_NUM_ACTIVE};
@@ -20,6 +21,7 @@ pub struct CrdsGossip {
     pub id: Pubkey,
     pub push: CrdsGossipPush,
     pub pull: CrdsGossipPull,
+    pub duplicate_identities: DuplicateIdentities,
 }
 
 impl Default for CrdsGossip {
@@ -29,6 +31,7 @@ impl Default for CrdsGossip {
             id: Pubkey::default(),
             push: CrdsGossipPush::default(),
             pull: CrdsGossipPull::default(),
+            duplicate_identities: DuplicateIdentities::default(),
         }
     }
 }
@@ -45,6 +48,7 @@ impl CrdsGossip {
         values: Vec<CrdsValue>,
         now: u64,
     ) -> Vec<VersionedCrdsValue> {
+        self.observe_contact_infos(&values, now);
         values
             .into_iter()
             .filter_map(|val| {
@@ -159,9 +163,21 @@ impl CrdsGossip {
         response: Vec<CrdsValue>,
         now: u64,
     ) -> usize {
+        self.observe_contact_infos(&response, now);
         self.pull
             .process_pull_response(&mut self.crds, from, response, now)
     }
+
+    /// check gossiped ContactInfos for identities claimed from more than one address, rejected
+    /// values count too since a duplicate's older values lose to the newer ones
+    fn observe_contact_infos(&mut self, values: &[CrdsValue], now: u64) {
+        for contact_info in values.iter().filter_map(CrdsValue::contact_info) {
+            if self.duplicate_identities.observe(contact_info, now) {
+                report_duplicate_identity(&self.duplicate_identities, &contact_info.id);
+            }
+        }
+    }
+
     pub fn purge(&mut self, now: u64) {
         if now > self.push.msg_timeout {
             let min = now - self.push.msg_timeout;
@@ -171,15 +187,61 @@ impl CrdsGossip {
             let min = now - 5 * self.push.msg_timeout;
             self.push.purge_old_received_cache(min);
         }
-        if now > self.pull.crds_timeout {
-            let min = now - self.pull.crds_timeout;
-            self.pull.purge_active(&mut self.crds, &self.id, min);
+        let purged = self.pull.purge_active(&mut self.crds, &self.id, now);
+        if !purged.is_empty() {
+            report_purged(&purged);
         }
-        if now > 5 * self.pull.crds_timeout {
-            let min = now - 5 * self.pull.crds_timeout;
+        let purged_values_timeout = 5 * self.pull.crds_timeouts.max();
+        if now > purged_values_timeout {
+            let min = now - purged_values_timeout;
             self.pull.purge_purged(min);
         }
+        if now > self.pull.crds_timeouts.contact_info {
+            let min = now - self.pull.crds_timeouts.contact_info;
+            self.duplicate_identities.purge(min);
+        }
+    }
+}
+
+fn report_duplicate_identity(duplicate_identities: &DuplicateIdentities, pubkey: &Pubkey) {
+    let addrs: Vec<_> = duplicate_identities
+        .duplicates()
+        .into_iter()
+        .filter(|(duplicate, _)| duplicate == pubkey)
+        .flat_map(|(_, sightings)| sightings)
+        .map(|sighting| sighting.contact_info.gossip.to_string())
+        .collect();
+    warn!(
+        "identity {} is claimed by {} nodes: {}",
+        pubkey,
+        addrs.len(),
+        addrs.join(", ")
+    );
+    datapoint_warn!(
+        "crds_gossip-duplicate_identity",
+        ("pubkey", pubkey.to_string(), String),
+        ("num_addrs", addrs.len(), i64),
+        ("addrs", addrs.join(","), String),
+    );
+}
+
+fn report_purged(purged: &[CrdsValueLabel]) {
+    let (mut contact_info, mut vote, mut epoch_slots, mut archiver_info, mut storage_turn) =
+        (0, 0, 0, 0, 0);
+    for label in purged {
+        match label {
+            CrdsValueLabel::ContactInfo(_) => contact_info += 1,
+            CrdsValueLabel::Vote(_, _) => vote += 1,
+            CrdsValueLabel::EpochSlots(_) => epoch_slots += 1,
+            CrdsValueLabel::ArchiverInfo(_) => archiver_info += 1,
+            CrdsValueLabel::StorageTurn(_) => storage_turn += 1,
+        }
     }
+    inc_new_counter_info!("crds_gossip-purged-contact_info", contact_info);
+    inc_new_counter_info!("crds_gossip-purged-vote", vote);
+    inc_new_counter_info!("crds_gossip-purged-epoch_slots", epoch_slots);
+    inc_new_counter_info!("crds_gossip-purged-archiver_info", archiver_info);
+    inc_new_counter_info!("crds_gossip-purged-storage_turn", storage_turn);
 }
 
 /// Computes a normalized(log of actual stake) stake
@@ -240,4 +302,56 @@ mod test {
         res = crds_gossip.process_prune_msg(&ci.id, &id, &[prune_pubkey], now, timeout);
         assert_eq!(res.err(), Some(CrdsGossipError::PruneMessageTimeout));
     }
+
+    #[test]
+    fn test_duplicate_identity() {
+        let mut crds_gossip = CrdsGossip::default();
+        let from = Pubkey::new_rand();
+        let id = Pubkey::new_rand();
+        let contact_info = |gossip: &str, outset, wallclock| {
+            let mut ci = ContactInfo::new_localhost(&id, wallclock);
+            ci.gossip = gossip.parse().unwrap();
+            ci.outset = outset;
+            CrdsValue::new_unsigned(CrdsData::ContactInfo(ci))
+        };
+        crds_gossip.process_push_message(&from, vec![contact_info("10.0.0.1:8001", 1, 1)], 1);
+        crds_gossip.process_push_message(&from, vec![contact_info("10.0.0.2:8001", 2, 2)], 2);
+        assert!(!crds_gossip.duplicate_identities.is_duplicate(&id));
+
+        crds_gossip.process_push_message(&from, vec![contact_info("10.0.0.2:8001", 2, 4)], 3);
+
+        // the value loses to the newer one in crds but still shows the first node is alive
+        crds_gossip.process_pull_response(&from, vec![contact_info("10.0.0.1:8001", 1, 3)], 5);
+        let label = CrdsValueLabel::ContactInfo(id);
+        assert_eq!(crds_gossip.crds.lookup(&label).unwrap().wallclock(), 4);
+        assert!(crds_gossip.duplicate_identities.is_duplicate(&id));
+
+        crds_gossip.purge(5 + crds_gossip.pull.crds_timeouts.contact_info);
+        assert!(!crds_gossip.duplicate_identities.is_duplicate(&id));
+    }
+
+    #[test]
+    fn test_duplicate_identity_restart() {
+        let mut crds_gossip = CrdsGossip::default();
+        let from = Pubkey::new_rand();
+        let id = Pubkey::new_rand();
+        // a restarted node advertises a new ip, picking a new outset with its first wallclock
+        let contact_info = |gossip: &str, outset, wallclock| {
+            let mut ci = ContactInfo::new_localhost(&id, outset);
+            ci.gossip = gossip.parse().unwrap();
+            ci.wallclock = wallclock;
+            CrdsValue::new_unsigned(CrdsData::ContactInfo(ci))
+        };
+        crds_gossip.process_push_message(&from, vec![contact_info("10.0.0.1:8001", 1, 1)], 1);
+        crds_gossip.process_push_message(&from, vec![contact_info("10.0.0.1:8001", 1, 3)], 3);
+        crds_gossip.process_push_message(&from, vec![contact_info("10.0.0.2:8001", 4, 4)], 4);
+        crds_gossip.process_push_message(&from, vec![contact_info("10.0.0.2:8001", 4, 6)], 6);
+        crds_gossip.process_push_message(&from, vec![contact_info("10.0.0.1:8001", 7, 7)], 7);
+        // a stale value from before the restarts
+        crds_gossip.process_pull_response(&from, vec![contact_info("10.0.0.1:8001", 1, 2)], 8);
+
+        let label = CrdsValueLabel::ContactInfo(id);
+        assert_eq!(crds_gossip.crds.lookup(&label).unwrap().wallclock(), 7);
+        assert!(!crds_gossip.duplicate_identities.is_duplicate(&id));
+    }
 }
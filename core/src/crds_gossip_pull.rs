@@ -8,6 +8,9 @@
 //! Bloom filters have a false positive rate.  Each requests uses a different bloom filter
 //! with random hash functions.  So each subsequent request will have a different distribution
 //! of false positives.
+//!
+//! Large tables are split into shards by the mask bits of the value hashes, each with its own
+//! filter, and the false positive rate is tightened as the table grows.
 
 use crate::contact_info::ContactInfo;
 use crate::crds::Crds;
@@ -26,7 +29,11 @@ use std::collections::VecDeque;
 
 pub const CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS: u64 = 15000;
 pub const FALSE_RATE: f64 = 0.1f64;
+pub const MIN_FALSE_RATE: f64 = 0.01f64;
 pub const KEYS: f64 = 8f64;
+/// Expected number of values a pull request misses due to bloom filter false positives.
+/// The false rate is lowered as the table grows so this stays roughly constant.
+pub const CRDS_GOSSIP_PULL_TARGET_FALSE_POSITIVES: f64 = 16f64;
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
 pub struct CrdsFilter {
@@ -37,36 +44,24 @@ pub struct CrdsFilter {
 
 impl CrdsFilter {
     pub fn new_rand(num_items: usize, max_bytes: usize) -> Self {
-        let max_bits = (max_bytes * 8) as f64;
-        let max_items = Self::max_items(max_bits, FALSE_RATE, KEYS);
-        let mask_bits = Self::mask_bits(num_items as f64, max_items as f64);
-        let filter = Bloom::random(max_items as usize, FALSE_RATE, max_bits as usize);
-        let seed: u64 = rand::thread_rng().gen_range(0, 2u64.pow(mask_bits));
-        let mask = Self::compute_mask(seed, mask_bits);
-        CrdsFilter {
-            filter,
-            mask,
-            mask_bits,
-        }
+        let params = CrdsFilterParams::new(num_items, max_bytes);
+        let seed: u64 = rand::thread_rng().gen_range(0, 2u64.pow(params.mask_bits));
+        params.new_filter(seed)
     }
     // generates a vec of filters that together hold a complete set of Hashes
+    // the filter at index `i` holds the shard returned by `shard_index` for `i`
     pub fn new_complete_set(num_items: usize, max_bytes: usize) -> Vec<Self> {
-        let max_bits = (max_bytes * 8) as f64;
-        let max_items = Self::max_items(max_bits, FALSE_RATE, KEYS);
-        let mask_bits = Self::mask_bits(num_items as f64, max_items as f64);
+        let params = CrdsFilterParams::new(num_items, max_bytes);
         // for each possible mask combination, generate a new filter.
-        let mut filters = vec![];
-        for seed in 0..2u64.pow(mask_bits) {
-            let filter = Bloom::random(max_items as usize, FALSE_RATE, max_bits as usize);
-            let mask = Self::compute_mask(seed, mask_bits);
-            let filter = CrdsFilter {
-                filter,
-                mask,
-                mask_bits,
-            };
-            filters.push(filter)
-        }
-        filters
+        (0..2u64.pow(params.mask_bits))
+            .map(|seed| params.new_filter(seed))
+            .collect()
+    }
+    /// false positive rate targeted for a table of `num_items` values
+    pub fn false_rate(num_items: usize) -> f64 {
+        (CRDS_GOSSIP_PULL_TARGET_FALSE_POSITIVES / num_items.max(1) as f64)
+            .min(FALSE_RATE)
+            .max(MIN_FALSE_RATE)
     }
     fn compute_mask(seed: u64, mask_bits: u32) -> u64 {
         assert!(seed <= 2u64.pow(mask_bits));
@@ -91,6 +86,13 @@ impl CrdsFilter {
         }
         accum
     }
+    /// index of the shard holding `item` when the hash space is split by `mask_bits` bits, the
+    /// highest bits of `hash_as_u64`, which `test_mask` compares against the mask
+    pub fn shard_index(item: &Hash, mask_bits: u32) -> usize {
+        Self::hash_as_u64(item)
+            .checked_shr(64 - mask_bits)
+            .unwrap_or(0) as usize
+    }
     pub fn test_mask(&self, item: &Hash) -> bool {
         // only consider the highest mask_bits bits from the hash and set the rest to 1.
         let ones = (!0u64).checked_shr(self.mask_bits).unwrap_or(!0u64);
@@ -110,13 +112,104 @@ impl CrdsFilter {
     }
 }
 
+/// Sizing shared by every shard of a set of filters
+struct CrdsFilterParams {
+    false_rate: f64,
+    num_keys: usize,
+    max_bits: usize,
+    mask_bits: u32,
+    shard_items: usize,
+}
+
+impl CrdsFilterParams {
+    fn new(num_items: usize, max_bytes: usize) -> Self {
+        let false_rate = CrdsFilter::false_rate(num_items);
+        let num_keys = (-false_rate.log2()).ceil().min(KEYS).max(1f64) as usize;
+        // the keys are serialized with the bits, so they come out of the same byte budget
+        let max_words = (max_bytes.saturating_sub(num_keys * 8) / 8).max(1);
+        let max_bits = max_words * 64;
+        let max_items = CrdsFilter::max_items(max_bits as f64, false_rate, num_keys as f64);
+        let mask_bits = CrdsFilter::mask_bits(num_items as f64, max_items);
+        let shard_items = (num_items as f64 / 2f64.powi(mask_bits as i32)).ceil() as usize;
+        CrdsFilterParams {
+            false_rate,
+            num_keys,
+            max_bits,
+            mask_bits,
+            shard_items,
+        }
+    }
+    fn new_filter(&self, seed: u64) -> CrdsFilter {
+        // size each shard for the items it is expected to hold rather than the full budget
+        let num_bits = Bloom::<Hash>::num_bits(self.shard_items.max(1) as f64, self.false_rate);
+        let num_bits = cmp::max(1, cmp::min(num_bits as usize, self.max_bits));
+        let mut rng = rand::thread_rng();
+        let keys = (0..self.num_keys).map(|_| rng.gen()).collect();
+        CrdsFilter {
+            filter: Bloom::new(num_bits, keys),
+            mask: CrdsFilter::compute_mask(seed, self.mask_bits),
+            mask_bits: self.mask_bits,
+        }
+    }
+}
+
+/// How long values of each type are kept after they were last updated, in milliseconds.
+/// Values are pushed again every `CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS / 2`, shorter timeouts
+/// purge values still in use.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrdsTimeouts {
+    pub contact_info: u64,
+    pub vote: u64,
+    pub epoch_slots: u64,
+    pub archiver_info: u64,
+    pub storage_turn: u64,
+}
+
+impl Default for CrdsTimeouts {
+    fn default() -> Self {
+        Self {
+            contact_info: CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS,
+            vote: CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS,
+            epoch_slots: CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS,
+            archiver_info: CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS,
+            storage_turn: CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS,
+        }
+    }
+}
+
+impl CrdsTimeouts {
+    pub fn get(&self, label: &CrdsValueLabel) -> u64 {
+        match label {
+            CrdsValueLabel::ContactInfo(_) => self.contact_info,
+            CrdsValueLabel::Vote(_, _) => self.vote,
+            CrdsValueLabel::EpochSlots(_) => self.epoch_slots,
+            CrdsValueLabel::ArchiverInfo(_) => self.archiver_info,
+            CrdsValueLabel::StorageTurn(_) => self.storage_turn,
+        }
+    }
+
+    /// The longest timeout, hashes of purged values are remembered for a multiple of it
+    pub fn max(&self) -> u64 {
+        *[
+            self.contact_info,
+            self.vote,
+            self.epoch_slots,
+            self.archiver_info,
+            self.storage_turn,
+        ]
+        .iter()
+        .max()
+        .unwrap()
+    }
+}
+
 #[derive(Clone)]
 pub struct CrdsGossipPull {
     /// timestamp of last request
     pub pull_request_time: HashMap<Pubkey, u64>,
     /// hash and insert time
     purged_values: VecDeque<(Hash, u64)>,
-    pub crds_timeout: u64,
+    pub crds_timeouts: CrdsTimeouts,
 }
 
 impl Default for CrdsGossipPull {
@@ -124,7 +217,7 @@ impl Default for CrdsGossipPull {
         Self {
             purged_values: VecDeque::new(),
             pull_request_time: HashMap::new(),
-            crds_timeout: CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS,
+            crds_timeouts: CrdsTimeouts::default(),
         }
     }
 }
@@ -237,13 +330,14 @@ impl CrdsGossipPull {
             crds.table.values().count() + self.purged_values.len(),
         );
         let mut filters = CrdsFilter::new_complete_set(num, bloom_size);
-        for v in crds.table.values() {
-            filters
-                .iter_mut()
-                .for_each(|filter| filter.add(&v.value_hash));
-        }
-        for (value_hash, _insert_timestamp) in &self.purged_values {
-            filters.iter_mut().for_each(|filter| filter.add(value_hash));
+        let mask_bits = filters[0].mask_bits;
+        let hashes = crds
+            .table
+            .values()
+            .map(|v| &v.value_hash)
+            .chain(self.purged_values.iter().map(|(value_hash, _)| value_hash));
+        for value_hash in hashes {
+            filters[CrdsFilter::shard_index(value_hash, mask_bits)].add(value_hash);
         }
         filters
     }
@@ -263,13 +357,29 @@ impl CrdsGossipPull {
         }
         ret
     }
-    /// Purge values from the crds that are older then `active_timeout`
+    /// Purge values from the crds that are older then the timeout of their type at `now`,
+    /// returns the labels of the purged values
     /// The value_hash of an active item is put into self.purged_values queue
-    pub fn purge_active(&mut self, crds: &mut Crds, self_id: &Pubkey, min_ts: u64) {
-        let old = crds.find_old_labels(min_ts);
+    pub fn purge_active(
+        &mut self,
+        crds: &mut Crds,
+        self_id: &Pubkey,
+        now: u64,
+    ) -> Vec<CrdsValueLabel> {
+        let crds_timeouts = &self.crds_timeouts;
+        let old: Vec<_> = crds
+            .table
+            .iter()
+            .filter(|(label, val)| {
+                label.pubkey() != *self_id
+                    && now
+                        .checked_sub(crds_timeouts.get(label))
+                        .map_or(false, |min_ts| val.local_timestamp <= min_ts)
+            })
+            .map(|(label, _)| label.clone())
+            .collect();
         let mut purged: VecDeque<_> = old
             .iter()
-            .filter(|label| label.pubkey() != *self_id)
             .filter_map(|label| {
                 let rv = crds
                     .lookup_versioned(label)
@@ -279,6 +389,7 @@ impl CrdsGossipPull {
             })
             .collect();
         self.purged_values.append(&mut purged);
+        old
     }
     /// Purge values from the `self.purged_values` queue that are older then purge_timeout
     pub fn purge_purged(&mut self, min_ts: u64) {
@@ -294,10 +405,11 @@ impl CrdsGossipPull {
 mod test {
     use super::*;
     use crate::contact_info::ContactInfo;
-    use crate::crds_value::CrdsData;
+    use crate::crds_value::{CrdsData, EpochSlots};
     use itertools::Itertools;
     use solana_sdk::hash::hash;
     use solana_sdk::packet::PACKET_DATA_SIZE;
+    use std::collections::BTreeSet;
 
     #[test]
     fn test_new_pull_with_stakes() {
@@ -551,7 +663,11 @@ mod test {
         assert_eq!(node_crds.lookup(&node_label).unwrap().label(), node_label);
 
         // purge
-        node.purge_active(&mut node_crds, &node_pubkey, 1);
+        node.purge_active(
+            &mut node_crds,
+            &node_pubkey,
+            node.crds_timeouts.contact_info + 1,
+        );
 
         //verify self is still valid after purge
         assert_eq!(node_crds.lookup(&node_label).unwrap().label(), node_label);
@@ -571,6 +687,39 @@ mod test {
         assert_eq!(node.purged_values.len(), 0);
     }
     #[test]
+    fn test_gossip_purge_by_type() {
+        let mut node_crds = Crds::default();
+        let contact_info = CrdsValue::new_unsigned(CrdsData::ContactInfo(
+            ContactInfo::new_localhost(&Pubkey::new_rand(), 0),
+        ));
+        let epoch_slots = CrdsValue::new_unsigned(CrdsData::EpochSlots(EpochSlots::new(
+            Pubkey::new_rand(),
+            0,
+            BTreeSet::new(),
+            0,
+        )));
+        node_crds.insert(contact_info.clone(), 0).unwrap();
+        node_crds.insert(epoch_slots.clone(), 0).unwrap();
+
+        let mut node = CrdsGossipPull::default();
+        node.crds_timeouts.contact_info = 3000;
+        node.crds_timeouts.epoch_slots = 1000;
+        assert_eq!(node.crds_timeouts.max(), CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS);
+        let self_id = Pubkey::new_rand();
+
+        assert!(node.purge_active(&mut node_crds, &self_id, 999).is_empty());
+        assert_eq!(
+            node.purge_active(&mut node_crds, &self_id, 1000),
+            vec![epoch_slots.label()]
+        );
+        assert!(node_crds.lookup(&contact_info.label()).is_some());
+        assert_eq!(
+            node.purge_active(&mut node_crds, &self_id, 3000),
+            vec![contact_info.label()]
+        );
+        assert_eq!(node.purged_values.len(), 2);
+    }
+    #[test]
     fn test_crds_filter_mask() {
         let filter = CrdsFilter::new_rand(1, 128);
         assert_eq!(filter.mask, !0x0);
@@ -633,6 +782,73 @@ mod test {
         assert!(filter.contains(&h));
     }
     #[test]
+    fn test_crds_filter_shard_index() {
+        let filters = CrdsFilter::new_complete_set(10000, PACKET_DATA_SIZE);
+        let mask_bits = filters[0].mask_bits;
+        assert!(mask_bits > 0);
+        assert_eq!(filters.len(), 1 << mask_bits);
+        let mut h = Hash::default();
+        for _ in 0..100 {
+            h = hash(h.as_ref());
+            let index = CrdsFilter::shard_index(&h, mask_bits);
+            assert!(filters[index].test_mask(&h));
+            let matches = filters.iter().filter(|f| f.test_mask(&h)).count();
+            assert_eq!(matches, 1);
+        }
+        assert_eq!(CrdsFilter::shard_index(&h, 0), 0);
+
+        // the first 8 bytes of the hash are read little-endian
+        let mut bytes = [0u8; 32];
+        bytes[7] = 0b1100_0000;
+        let h = Hash::new(&bytes);
+        assert_eq!(CrdsFilter::shard_index(&h, 1), 1);
+        assert_eq!(CrdsFilter::shard_index(&h, 2), 3);
+        bytes[7] = 0;
+        bytes[0] = 0xff;
+        assert_eq!(CrdsFilter::shard_index(&Hash::new(&bytes), 8), 0);
+    }
+    #[test]
+    fn test_crds_filter_false_rate() {
+        assert_eq!(CrdsFilter::false_rate(0), FALSE_RATE);
+        assert_eq!(CrdsFilter::false_rate(100), FALSE_RATE);
+        let rate = CrdsFilter::false_rate(1000);
+        assert!(rate < FALSE_RATE && rate > MIN_FALSE_RATE);
+        assert_eq!(CrdsFilter::false_rate(1_000_000), MIN_FALSE_RATE);
+    }
+    #[test]
+    fn test_crds_filter_size() {
+        for num_items in &[1, 100, 1000, 10000, 100_000] {
+            let filters = CrdsFilter::new_complete_set(*num_items, PACKET_DATA_SIZE);
+            let default_size = bincode::serialized_size(&CrdsFilter::default()).unwrap();
+            for filter in &filters {
+                let size = bincode::serialized_size(filter).unwrap() - default_size;
+                assert!(size <= PACKET_DATA_SIZE as u64);
+            }
+        }
+        // small tables only pay for the bits they need
+        let small = CrdsFilter::new_rand(10, PACKET_DATA_SIZE);
+        let large = CrdsFilter::new_rand(100_000, PACKET_DATA_SIZE);
+        assert!(small.filter.bits.len() < large.filter.bits.len());
+    }
+    #[test]
+    fn test_build_crds_filters_shards() {
+        let mut crds = Crds::default();
+        let node = CrdsGossipPull::default();
+        for _ in 0..1000 {
+            let entry = CrdsValue::new_unsigned(CrdsData::ContactInfo(ContactInfo::new_localhost(
+                &Pubkey::new_rand(),
+                0,
+            )));
+            crds.insert(entry, 0).unwrap();
+        }
+        let filters = node.build_crds_filters(&crds, 128);
+        assert!(filters.len() > 1);
+        for v in crds.table.values() {
+            let index = CrdsFilter::shard_index(&v.value_hash, filters[0].mask_bits);
+            assert!(filters[index].filter.contains(&v.value_hash));
+        }
+    }
+    #[test]
     fn test_mask() {
         for i in 0..16 {
             run_test_mask(i);
@@ -13,13 +13,12 @@ use crate::crds::{Crds, VersionedCrdsValue};
 use crate::crds_gossip::{get_stake, get_weight, CRDS_GOSSIP_DEFAULT_BLOOM_ITEMS};
 use crate::crds_gossip_error::CrdsGossipError;
 use crate::crds_value::{CrdsValue, CrdsValueLabel};
-use crate::weighted_shuffle::weighted_shuffle;
+use crate::weighted_shuffle::{random_seed, weighted_shuffle};
 use bincode::serialized_size;
 use indexmap::map::IndexMap;
 use itertools::Itertools;
 use rand;
 use rand::seq::SliceRandom;
-use rand::{thread_rng, RngCore};
 use solana_runtime::bloom::Bloom;
 use solana_sdk::hash::Hash;
 use solana_sdk::packet::PACKET_DATA_SIZE;
@@ -102,11 +101,9 @@ impl CrdsGossipPush {
             .filter(|(_, s)| *s > 0)
             .collect();
 
-        let mut seed = [0; 32];
-        seed[0..8].copy_from_slice(&thread_rng().next_u64().to_le_bytes());
         let shuffle = weighted_shuffle(
             staked_peers.iter().map(|(_, stake)| *stake).collect_vec(),
-            seed,
+            random_seed(),
         );
 
         let mut keep = HashSet::new();
@@ -240,11 +237,9 @@ impl CrdsGossipPush {
             return;
         }
 
-        let mut seed = [0; 32];
-        seed[0..8].copy_from_slice(&thread_rng().next_u64().to_le_bytes());
         let mut shuffle = weighted_shuffle(
             options.iter().map(|weighted| weighted.0).collect_vec(),
-            seed,
+            random_seed(),
         )
         .into_iter();
 
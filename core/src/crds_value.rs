@@ -1,5 +1,7 @@
 use crate::contact_info::ContactInfo;
 use bincode::{serialize, serialized_size};
+use solana_sdk::clock::Slot;
+use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, Signable, Signature};
 use solana_sdk::transaction::Transaction;
@@ -8,9 +10,12 @@ use std::borrow::Cow;
 use std::collections::BTreeSet;
 use std::collections::HashSet;
 use std::fmt;
+use std::net::SocketAddr;
 
 pub type VoteIndex = u8;
 pub const MAX_VOTES: VoteIndex = 32;
+/// Version of the archiver storage protocol advertised in `ArchiverInfo`
+pub const ARCHIVER_PROTOCOL_VERSION: u16 = 1;
 
 /// CrdsValue that is replicated across the cluster
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -58,6 +63,10 @@ pub enum CrdsData {
     Vote(VoteIndex, Vote),
     /// * Merge Strategy - Latest wallclock is picked
     EpochSlots(EpochSlots),
+    /// * Merge Strategy - Latest wallclock is picked
+    ArchiverInfo(ArchiverInfo),
+    /// * Merge Strategy - Latest wallclock is picked
+    StorageTurn(StorageTurn),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -79,6 +88,51 @@ impl EpochSlots {
     }
 }
 
+/// Advertises that a node is an archiver and how to reach its storage service
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArchiverInfo {
+    pub from: Pubkey,
+    /// address serving storage requests
+    pub storage_addr: SocketAddr,
+    /// number of segments the archiver holds
+    pub num_segments: u64,
+    pub protocol_version: u16,
+    pub wallclock: u64,
+}
+
+impl ArchiverInfo {
+    pub fn new(from: Pubkey, storage_addr: SocketAddr, num_segments: u64, wallclock: u64) -> Self {
+        Self {
+            from,
+            storage_addr,
+            num_segments,
+            protocol_version: ARCHIVER_PROTOCOL_VERSION,
+            wallclock,
+        }
+    }
+}
+
+/// The storage turn a validator sees committed on chain at its latest root, so archivers can
+/// learn turns from gossip instead of polling RPC nodes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StorageTurn {
+    pub from: Pubkey,
+    pub blockhash: Hash,
+    pub slot: Slot,
+    pub wallclock: u64,
+}
+
+impl StorageTurn {
+    pub fn new(from: Pubkey, blockhash: Hash, slot: Slot, wallclock: u64) -> Self {
+        Self {
+            from,
+            blockhash,
+            slot,
+            wallclock,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Vote {
     pub from: Pubkey,
@@ -103,6 +157,8 @@ pub enum CrdsValueLabel {
     ContactInfo(Pubkey),
     Vote(VoteIndex, Pubkey),
     EpochSlots(Pubkey),
+    ArchiverInfo(Pubkey),
+    StorageTurn(Pubkey),
 }
 
 impl fmt::Display for CrdsValueLabel {
@@ -111,6 +167,8 @@ impl fmt::Display for CrdsValueLabel {
             CrdsValueLabel::ContactInfo(_) => write!(f, "ContactInfo({})", self.pubkey()),
             CrdsValueLabel::Vote(ix, _) => write!(f, "Vote({}, {})", ix, self.pubkey()),
             CrdsValueLabel::EpochSlots(_) => write!(f, "EpochSlots({})", self.pubkey()),
+            CrdsValueLabel::ArchiverInfo(_) => write!(f, "ArchiverInfo({})", self.pubkey()),
+            CrdsValueLabel::StorageTurn(_) => write!(f, "StorageTurn({})", self.pubkey()),
         }
     }
 }
@@ -121,6 +179,8 @@ impl CrdsValueLabel {
             CrdsValueLabel::ContactInfo(p) => *p,
             CrdsValueLabel::Vote(_, p) => *p,
             CrdsValueLabel::EpochSlots(p) => *p,
+            CrdsValueLabel::ArchiverInfo(p) => *p,
+            CrdsValueLabel::StorageTurn(p) => *p,
         }
     }
 }
@@ -146,6 +206,8 @@ impl CrdsValue {
             CrdsData::ContactInfo(contact_info) => contact_info.wallclock,
             CrdsData::Vote(_, vote) => vote.wallclock,
             CrdsData::EpochSlots(vote) => vote.wallclock,
+            CrdsData::ArchiverInfo(archiver_info) => archiver_info.wallclock,
+            CrdsData::StorageTurn(turn) => turn.wallclock,
         }
     }
     pub fn pubkey(&self) -> Pubkey {
@@ -153,6 +215,8 @@ impl CrdsValue {
             CrdsData::ContactInfo(contact_info) => contact_info.id,
             CrdsData::Vote(_, vote) => vote.from,
             CrdsData::EpochSlots(slots) => slots.from,
+            CrdsData::ArchiverInfo(archiver_info) => archiver_info.from,
+            CrdsData::StorageTurn(turn) => turn.from,
         }
     }
     pub fn label(&self) -> CrdsValueLabel {
@@ -160,6 +224,8 @@ impl CrdsValue {
             CrdsData::ContactInfo(_) => CrdsValueLabel::ContactInfo(self.pubkey()),
             CrdsData::Vote(ix, _) => CrdsValueLabel::Vote(*ix, self.pubkey()),
             CrdsData::EpochSlots(_) => CrdsValueLabel::EpochSlots(self.pubkey()),
+            CrdsData::ArchiverInfo(_) => CrdsValueLabel::ArchiverInfo(self.pubkey()),
+            CrdsData::StorageTurn(_) => CrdsValueLabel::StorageTurn(self.pubkey()),
         }
     }
     pub fn contact_info(&self) -> Option<&ContactInfo> {
@@ -188,11 +254,27 @@ impl CrdsValue {
             _ => None,
         }
     }
+
+    pub fn archiver_info(&self) -> Option<&ArchiverInfo> {
+        match &self.data {
+            CrdsData::ArchiverInfo(archiver_info) => Some(archiver_info),
+            _ => None,
+        }
+    }
+
+    pub fn storage_turn(&self) -> Option<&StorageTurn> {
+        match &self.data {
+            CrdsData::StorageTurn(turn) => Some(turn),
+            _ => None,
+        }
+    }
     /// Return all the possible labels for a record identified by Pubkey.
     pub fn record_labels(key: &Pubkey) -> Vec<CrdsValueLabel> {
         let mut labels = vec![
             CrdsValueLabel::ContactInfo(*key),
             CrdsValueLabel::EpochSlots(*key),
+            CrdsValueLabel::ArchiverInfo(*key),
+            CrdsValueLabel::StorageTurn(*key),
         ];
         labels.extend((0..MAX_VOTES).map(|ix| CrdsValueLabel::Vote(ix, *key)));
         labels
@@ -242,13 +324,15 @@ mod test {
 
     #[test]
     fn test_labels() {
-        let mut hits = [false; 2 + MAX_VOTES as usize];
+        let mut hits = [false; 4 + MAX_VOTES as usize];
         // this method should cover all the possible labels
         for v in &CrdsValue::record_labels(&Pubkey::default()) {
             match v {
                 CrdsValueLabel::ContactInfo(_) => hits[0] = true,
                 CrdsValueLabel::EpochSlots(_) => hits[1] = true,
-                CrdsValueLabel::Vote(ix, _) => hits[*ix as usize + 2] = true,
+                CrdsValueLabel::ArchiverInfo(_) => hits[2] = true,
+                CrdsValueLabel::StorageTurn(_) => hits[3] = true,
+                CrdsValueLabel::Vote(ix, _) => hits[*ix as usize + 4] = true,
             }
         }
         assert!(hits.iter().all(|x| *x));
@@ -277,6 +361,26 @@ mod test {
         assert_eq!(v.wallclock(), 0);
         let key = v.clone().epoch_slots().unwrap().from;
         assert_eq!(v.label(), CrdsValueLabel::EpochSlots(key));
+
+        let v = CrdsValue::new_unsigned(CrdsData::ArchiverInfo(ArchiverInfo::new(
+            Pubkey::default(),
+            socketaddr_any!(),
+            0,
+            0,
+        )));
+        assert_eq!(v.wallclock(), 0);
+        let key = v.clone().archiver_info().unwrap().from;
+        assert_eq!(v.label(), CrdsValueLabel::ArchiverInfo(key));
+
+        let v = CrdsValue::new_unsigned(CrdsData::StorageTurn(StorageTurn::new(
+            Pubkey::default(),
+            Hash::default(),
+            0,
+            0,
+        )));
+        assert_eq!(v.wallclock(), 0);
+        let key = v.clone().storage_turn().unwrap().from;
+        assert_eq!(v.label(), CrdsValueLabel::StorageTurn(key));
     }
 
     #[test]
@@ -301,6 +405,20 @@ mod test {
             timestamp(),
         )));
         verify_signatures(&mut v, &keypair, &wrong_keypair);
+        v = CrdsValue::new_unsigned(CrdsData::ArchiverInfo(ArchiverInfo::new(
+            keypair.pubkey(),
+            socketaddr!("127.0.0.1:1234"),
+            1,
+            timestamp(),
+        )));
+        verify_signatures(&mut v, &keypair, &wrong_keypair);
+        v = CrdsValue::new_unsigned(CrdsData::StorageTurn(StorageTurn::new(
+            keypair.pubkey(),
+            Hash::default(),
+            8,
+            timestamp(),
+        )));
+        verify_signatures(&mut v, &keypair, &wrong_keypair);
     }
 
     #[test]
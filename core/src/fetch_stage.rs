@@ -5,6 +5,7 @@ use crate::packet::PacketsRecycler;
 use crate::poh_recorder::PohRecorder;
 use crate::result::{Error, Result};
 use crate::streamer::{self, PacketReceiver, PacketSender};
+use crate::transaction_trace::TraceStage;
 use solana_metrics::{inc_new_counter_debug, inc_new_counter_info};
 use solana_perf::recycler::Recycler;
 use solana_sdk::clock::DEFAULT_TICKS_PER_SLOT;
@@ -95,23 +96,25 @@ impl FetchStage {
         let recycler: PacketsRecycler = Recycler::warmed(1000, 1024);
 
         let tpu_threads = sockets.into_iter().map(|socket| {
-            streamer::receiver(
+            streamer::traced_receiver(
                 socket,
                 &exit,
                 sender.clone(),
                 recycler.clone(),
                 "fetch_stage",
+                Some(TraceStage::Ingest),
             )
         });
 
         let (forward_sender, forward_receiver) = channel();
         let tpu_forwards_threads = tpu_forwards_sockets.into_iter().map(|socket| {
-            streamer::receiver(
+            streamer::traced_receiver(
                 socket,
                 &exit,
                 forward_sender.clone(),
                 recycler.clone(),
                 "fetch_forward_stage",
+                Some(TraceStage::Ingest),
             )
         });
 
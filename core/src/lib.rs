@@ -15,12 +15,17 @@ pub mod shred_fetch_stage;
 #[macro_use]
 pub mod contact_info;
 pub mod archiver;
+pub mod archiver_client;
+pub mod archiver_proof_log;
+pub mod archiver_segment_transfer;
+pub mod archiver_storage;
 pub mod blockstream;
 pub mod blockstream_service;
 pub mod cluster_info;
 pub mod cluster_info_repair_listener;
 pub mod consensus;
 pub mod crds;
+pub mod crds_duplicate_identity;
 pub mod crds_gossip;
 pub mod crds_gossip_error;
 pub mod crds_gossip_pull;
@@ -32,11 +37,15 @@ pub mod genesis_utils;
 pub mod gossip_service;
 pub mod ledger_cleanup_service;
 pub mod local_vote_signer_service;
+pub mod object_store;
 pub mod packet;
+pub mod packet_fuzz;
 pub mod partition_cfg;
 pub mod poh_recorder;
 pub mod poh_service;
+pub mod preflight;
 pub mod recvmmsg;
+pub mod repair_peers;
 pub mod repair_service;
 pub mod replay_stage;
 pub mod result;
@@ -46,18 +55,25 @@ pub mod rpc_pubsub;
 pub mod rpc_pubsub_service;
 pub mod rpc_service;
 pub mod rpc_subscriptions;
+pub mod sender_stake_stage;
 pub mod sendmmsg;
 pub mod sigverify;
 pub mod sigverify_shreds;
 pub mod sigverify_stage;
 pub mod snapshot_packager_service;
+pub mod snapshot_uploader_service;
 pub mod storage_stage;
 pub mod streamer;
+pub mod thread_affinity;
+pub mod thread_registry;
 pub mod tpu;
+pub mod transaction_trace;
 pub mod tvu;
 pub mod validator;
+pub mod validator_plugin;
 pub mod weighted_shuffle;
 pub mod window_service;
+pub mod wire_format;
 
 #[macro_use]
 extern crate solana_budget_program;
@@ -0,0 +1,101 @@
+//! The `object_store` module is a minimal client for S3-compatible object storage, used by
+//! archivers to keep their segment and by validators to upload snapshots and rooted slots.
+
+use reqwest::{
+    header::{CONTENT_LENGTH, RANGE},
+    Client, StatusCode,
+};
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read},
+    path::Path,
+};
+
+fn to_io_error(err: reqwest::Error) -> io::Error {
+    io::Error::new(ErrorKind::Other, err)
+}
+
+/// Reads and writes objects under a bucket URL. Requests aren't signed, the bucket has to
+/// accept them from this node, e.g. through a bucket policy or a signing proxy.
+pub struct ObjectStore {
+    url: String,
+    client: Client,
+}
+
+impl ObjectStore {
+    /// `url` is the bucket, and optionally a key prefix, the objects are stored under,
+    /// e.g. `https://s3.amazonaws.com/my-bucket/validator-1`
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            client: Client::new(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn object_url(&self, name: &str) -> String {
+        format!("{}/{}", self.url, name)
+    }
+
+    /// Replace object `name` with `body`
+    pub fn put<T: Into<reqwest::Body>>(&self, name: &str, body: T) -> io::Result<()> {
+        self.client
+            .put(&self.object_url(name))
+            .body(body)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map_err(to_io_error)?;
+        Ok(())
+    }
+
+    /// Replace object `name` with the contents of the file at `path`
+    pub fn put_file(&self, name: &str, path: &Path) -> io::Result<()> {
+        self.put(name, File::open(path)?)
+    }
+
+    /// Size of object `name` in bytes
+    pub fn size(&self, name: &str) -> io::Result<u64> {
+        let response = self
+            .client
+            .head(&self.object_url(name))
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map_err(to_io_error)?;
+        response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok())
+            .and_then(|length| length.parse().ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("no content length for {}", name),
+                )
+            })
+    }
+
+    /// Fill `buf` with the bytes of object `name` starting at `offset`
+    pub fn read_at(&self, name: &str, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let range = format!("bytes={}-{}", offset, offset + buf.len() as u64 - 1);
+        let mut response = self
+            .client
+            .get(&self.object_url(name))
+            .header(RANGE, range)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map_err(to_io_error)?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("ranged read of {} not supported", name),
+            ));
+        }
+        response.read_exact(buf)
+    }
+}
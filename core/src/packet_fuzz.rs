@@ -0,0 +1,292 @@
+//! The `packet_fuzz` module exposes the decoding of untrusted UDP packets to fuzzers. Every
+//! `FuzzTarget` runs a buffer through the same parsing its service applies to a received packet,
+//! and `PacketMutator` derives new inputs from a corpus deterministically from a seed so that any
+//! failure can be replayed.
+
+use crate::{
+    archiver::ArchiverRequest,
+    cluster_info::{ClusterInfo, Protocol, PruneData},
+    contact_info::ContactInfo,
+    crds_gossip_pull::CrdsFilter,
+    crds_value::{CrdsData, CrdsValue},
+    packet::{limited_deserialize, Packet, PACKET_DATA_SIZE},
+    wire_format::repair_to_bytes,
+};
+use bincode::serialize;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use solana_ledger::{blocktree::verify_shred_slots, shred::Shred};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, KeypairUtil, Signable, Signature},
+    timing::timestamp,
+};
+
+/// Byte values that tend to land on boundaries of lengths, enum tags and flags
+const INTERESTING_BYTES: [u8; 6] = [0, 1, 0x7f, 0x80, 0xfe, 0xff];
+
+/// Values written over bincode length prefixes and slot numbers
+const INTERESTING_U64S: [u64; 5] = [
+    0,
+    1,
+    PACKET_DATA_SIZE as u64,
+    std::u32::MAX as u64 + 1,
+    std::u64::MAX,
+];
+
+/// Most bytes a single mutation inserts
+const MAX_INSERTED_BYTES: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FuzzTarget {
+    /// Requests received on the archiver's storage socket
+    ArchiverRequest,
+    /// Gossip pull, push and prune messages
+    Gossip,
+    /// Window repair requests received on the gossip socket
+    Repair,
+    /// Shreds received on the tvu, tvu forwards and repair sockets
+    Shred,
+}
+
+impl FuzzTarget {
+    pub fn all() -> &'static [FuzzTarget] {
+        &[
+            FuzzTarget::ArchiverRequest,
+            FuzzTarget::Gossip,
+            FuzzTarget::Repair,
+            FuzzTarget::Shred,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FuzzTarget::ArchiverRequest => "archiver-request",
+            FuzzTarget::Gossip => "gossip",
+            FuzzTarget::Repair => "repair",
+            FuzzTarget::Shred => "shred",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all()
+            .iter()
+            .find(|target| target.name() == name)
+            .cloned()
+    }
+
+    /// Well formed packets of this kind to start a corpus from
+    pub fn seed_corpus(self) -> Vec<Vec<u8>> {
+        match self {
+            FuzzTarget::ArchiverRequest => {
+                let from = socketaddr!("127.0.0.1:1234");
+                let requests = vec![
+                    ArchiverRequest::GetSlotHeight(from),
+                    ArchiverRequest::GetStatus(from),
+                    ArchiverRequest::GetSegmentManifest(from),
+                    ArchiverRequest::GetShredRange {
+                        from,
+                        slot: 1,
+                        start_index: 0,
+                        count: 64,
+                    },
+                ];
+                // both the bincode and the compact encodings are accepted
+                requests
+                    .iter()
+                    .map(ArchiverRequest::to_bytes)
+                    .chain(
+                        requests
+                            .iter()
+                            .map(|request| request.to_compact_bytes().unwrap()),
+                    )
+                    .collect()
+            }
+            FuzzTarget::Gossip => {
+                let keypair = Keypair::new();
+                let value = CrdsValue::new_signed(
+                    CrdsData::ContactInfo(ContactInfo::new_localhost(
+                        &keypair.pubkey(),
+                        timestamp(),
+                    )),
+                    &keypair,
+                );
+                let mut prune_data = PruneData {
+                    pubkey: keypair.pubkey(),
+                    prunes: vec![Pubkey::new_rand()],
+                    signature: Signature::default(),
+                    destination: Pubkey::new_rand(),
+                    wallclock: timestamp(),
+                };
+                prune_data.sign(&keypair);
+                vec![
+                    Protocol::PullRequest(CrdsFilter::default(), value.clone()),
+                    Protocol::PullResponse(keypair.pubkey(), vec![value.clone()]),
+                    Protocol::PushMessage(keypair.pubkey(), vec![value]),
+                    Protocol::PruneMessage(keypair.pubkey(), prune_data),
+                ]
+                .iter()
+                .map(|protocol| serialize(protocol).unwrap())
+                .collect()
+            }
+            FuzzTarget::Repair => {
+                let contact_info = ContactInfo::new_localhost(&Pubkey::new_rand(), timestamp());
+                let requests = vec![
+                    Protocol::RequestWindowIndex(contact_info.clone(), 1, 2),
+                    Protocol::RequestHighestWindowIndex(contact_info.clone(), 1, 2),
+                    Protocol::RequestOrphan(contact_info.clone(), 1),
+                    Protocol::RequestAncestorHashes(contact_info, 1),
+                ];
+                requests
+                    .iter()
+                    .map(|protocol| repair_to_bytes(protocol).unwrap())
+                    .chain(requests.iter().map(|protocol| serialize(protocol).unwrap()))
+                    .collect()
+            }
+            FuzzTarget::Shred => {
+                let data = [1, 2, 3];
+                vec![
+                    Shred::new_from_data(2, 0, 1, Some(&data), true, true, 0, 0).payload,
+                    Shred::new_empty_data_shred().payload,
+                ]
+            }
+        }
+    }
+
+    /// Parse `data` the way the target's service parses a received packet, returning whether
+    /// the packet was accepted for further processing
+    pub fn run(self, data: &[u8]) -> bool {
+        let mut packet = Packet::default();
+        let size = data.len().min(PACKET_DATA_SIZE);
+        packet.data[..size].copy_from_slice(&data[..size]);
+        packet.meta.size = size;
+
+        match self {
+            FuzzTarget::ArchiverRequest => {
+                ArchiverRequest::decode(&packet.data[..packet.meta.size]).is_ok()
+            }
+            FuzzTarget::Gossip => {
+                match limited_deserialize(&packet.data[..packet.meta.size]) {
+                    Ok(Protocol::PullRequest(_, caller)) => {
+                        caller.verify() && caller.contact_info().is_some()
+                    }
+                    Ok(Protocol::PullResponse(_, data)) | Ok(Protocol::PushMessage(_, data)) => {
+                        // every value is verified and labeled before it is inserted into crds
+                        data.iter()
+                            .filter(|value| value.verify())
+                            .map(|value| value.label())
+                            .count()
+                            > 0
+                    }
+                    Ok(Protocol::PruneMessage(from, data)) => from == data.pubkey && data.verify(),
+                    _ => false,
+                }
+            }
+            FuzzTarget::Repair => {
+                match ClusterInfo::deserialize_protocol(&packet.data[..packet.meta.size]) {
+                    Some(Protocol::RequestWindowIndex(from, _, _))
+                    | Some(Protocol::RequestHighestWindowIndex(from, _, _))
+                    | Some(Protocol::RequestOrphan(from, _))
+                    | Some(Protocol::RequestAncestorHashes(from, _)) => {
+                        ContactInfo::is_valid_address(&from.repair)
+                    }
+                    _ => false,
+                }
+            }
+            // the window service parses the whole packet buffer, not just the received bytes
+            FuzzTarget::Shred => match Shred::new_from_serialized_shred(packet.data.to_vec()) {
+                Ok(shred) => verify_shred_slots(shred.slot(), shred.parent(), 0),
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+/// Derives fuzzer inputs from corpus entries with small random edits. The same seed always
+/// produces the same sequence of inputs.
+pub struct PacketMutator {
+    rng: ChaChaRng,
+}
+
+impl PacketMutator {
+    pub fn new(seed: u64) -> Self {
+        let mut rng_seed = [0u8; 32];
+        rng_seed[..8].copy_from_slice(&seed.to_le_bytes());
+        Self {
+            rng: ChaChaRng::from_seed(rng_seed),
+        }
+    }
+
+    /// A copy of `input` with a few edits applied, never larger than a packet
+    pub fn mutate(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut data = input.to_vec();
+        let num_edits = self.rng.gen_range(1, 5);
+        for _ in 0..num_edits {
+            match self.rng.gen_range(0, 5) {
+                0 if !data.is_empty() => {
+                    let index = self.rng.gen_range(0, data.len());
+                    data[index] ^= 1 << self.rng.gen_range(0, 8);
+                }
+                1 if !data.is_empty() => {
+                    let index = self.rng.gen_range(0, data.len());
+                    data[index] = INTERESTING_BYTES[self.rng.gen_range(0, INTERESTING_BYTES.len())];
+                }
+                2 if data.len() >= 8 => {
+                    let index = self.rng.gen_range(0, data.len() - 7);
+                    let value = INTERESTING_U64S[self.rng.gen_range(0, INTERESTING_U64S.len())];
+                    data[index..index + 8].copy_from_slice(&value.to_le_bytes());
+                }
+                3 if !data.is_empty() => {
+                    let len = self.rng.gen_range(0, data.len());
+                    data.truncate(len);
+                }
+                _ => {
+                    let index = self.rng.gen_range(0, data.len() + 1);
+                    let len = self.rng.gen_range(1, MAX_INSERTED_BYTES + 1);
+                    let bytes: Vec<u8> = (0..len).map(|_| self.rng.gen()).collect();
+                    data.splice(index..index, bytes);
+                }
+            }
+        }
+        data.truncate(PACKET_DATA_SIZE);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_corpus_is_accepted() {
+        for target in FuzzTarget::all() {
+            assert_eq!(FuzzTarget::from_name(target.name()), Some(*target));
+            for input in target.seed_corpus() {
+                assert!(target.run(&input), "{} rejected its seed", target.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_mutated_packets() {
+        let mut mutator = PacketMutator::new(0);
+        for target in FuzzTarget::all() {
+            let corpus = target.seed_corpus();
+            for i in 0..1000 {
+                let input = mutator.mutate(&corpus[i % corpus.len()]);
+                assert!(input.len() <= PACKET_DATA_SIZE);
+                target.run(&input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mutator_is_deterministic() {
+        let input: Vec<u8> = (0..64).collect();
+        let mut mutator0 = PacketMutator::new(7);
+        let mut mutator1 = PacketMutator::new(7);
+        for _ in 0..100 {
+            assert_eq!(mutator0.mutate(&input), mutator1.mutate(&input));
+        }
+    }
+}
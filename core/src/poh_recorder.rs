@@ -11,6 +11,7 @@
 //! * recorded entry must be >= WorkingBank::min_tick_height && entry must be < WorkingBank::max_tick_height
 //!
 use crate::result::{Error, Result};
+use crate::transaction_trace::{self, TraceStage};
 use solana_ledger::blocktree::Blocktree;
 use solana_ledger::entry::Entry;
 use solana_ledger::leader_schedule_cache::LeaderScheduleCache;
@@ -40,6 +41,16 @@ pub enum PohRecorderError {
 
 pub type WorkingBankEntry = (Arc<Bank>, (Entry, u64));
 
+/// Sent to `PohRecorder::subscribe_leader_bank_notifications()` subscribers when this node's
+/// leader slots start and end
+#[derive(Clone)]
+pub enum LeaderBankNotification {
+    /// Entries are now recorded into the bank of this node's leader slot
+    Started(Arc<Bank>),
+    /// The leader slot of the bank ended, either completed or reset away from
+    Stopped(Arc<Bank>),
+}
+
 #[derive(Clone)]
 pub struct WorkingBank {
     pub bank: Arc<Bank>,
@@ -64,12 +75,14 @@ pub struct PohRecorder {
     leader_schedule_cache: Arc<LeaderScheduleCache>,
     poh_config: Arc<PohConfig>,
     ticks_per_slot: u64,
+    leader_bank_notification_senders: Vec<Sender<LeaderBankNotification>>,
 }
 
 impl PohRecorder {
     fn clear_bank(&mut self) {
         if let Some(working_bank) = self.working_bank.take() {
             let bank = working_bank.bank;
+            self.notify_leader_bank(LeaderBankNotification::Stopped(bank.clone()));
             let next_leader_slot = self.leader_schedule_cache.next_leader_slot(
                 &self.id,
                 bank.slot(),
@@ -88,6 +101,20 @@ impl PohRecorder {
         }
     }
 
+    /// Returns a receiver notified with the working bank whenever this node starts or stops
+    /// being leader, for services that would otherwise poll `has_bank()`
+    pub fn subscribe_leader_bank_notifications(&mut self) -> Receiver<LeaderBankNotification> {
+        let (sender, receiver) = channel();
+        self.leader_bank_notification_senders.push(sender);
+        receiver
+    }
+
+    // Subscribers that dropped their receiver are forgotten
+    fn notify_leader_bank(&mut self, notification: LeaderBankNotification) {
+        self.leader_bank_notification_senders
+            .retain(|sender| sender.send(notification.clone()).is_ok());
+    }
+
     pub fn would_be_leader(&self, within_next_n_ticks: u64) -> bool {
         self.has_bank()
             || self
@@ -226,6 +253,7 @@ impl PohRecorder {
     pub fn set_working_bank(&mut self, working_bank: WorkingBank) {
         trace!("new working bank");
         assert_eq!(working_bank.bank.ticks_per_slot(), self.ticks_per_slot());
+        self.notify_leader_bank(LeaderBankNotification::Started(working_bank.bank.clone()));
         self.working_bank = Some(working_bank);
         let _ = self.flush_cache(false);
     }
@@ -377,6 +405,7 @@ impl PohRecorder {
                     timing::duration_as_us(&now.elapsed()) as usize
                 );
                 if let Some(poh_entry) = res {
+                    transaction_trace::record_transactions(TraceStage::PohRecord, &transactions);
                     let entry = Entry {
                         num_hashes: poh_entry.num_hashes,
                         hash: poh_entry.hash,
@@ -431,6 +460,7 @@ impl PohRecorder {
                 leader_schedule_cache: leader_schedule_cache.clone(),
                 ticks_per_slot,
                 poh_config: poh_config.clone(),
+                leader_bank_notification_senders: vec![],
             },
             receiver,
         )
@@ -1036,6 +1066,53 @@ mod tests {
         Blocktree::destroy(&ledger_path).unwrap();
     }
 
+    #[test]
+    fn test_leader_bank_notifications() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blocktree =
+                Blocktree::open(&ledger_path).expect("Expected to be able to open database ledger");
+            let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(2);
+            let bank = Arc::new(Bank::new(&genesis_config));
+            let (mut poh_recorder, _entry_receiver) = PohRecorder::new(
+                0,
+                Hash::default(),
+                0,
+                None,
+                bank.ticks_per_slot(),
+                &Pubkey::default(),
+                &Arc::new(blocktree),
+                &Arc::new(LeaderScheduleCache::new_from_bank(&bank)),
+                &Arc::new(PohConfig::default()),
+            );
+            let receiver = poh_recorder.subscribe_leader_bank_notifications();
+            let dropped_receiver = poh_recorder.subscribe_leader_bank_notifications();
+            drop(dropped_receiver);
+
+            poh_recorder.set_bank(&bank);
+            match receiver.try_recv() {
+                Ok(LeaderBankNotification::Started(started)) => {
+                    assert!(Arc::ptr_eq(&started, &bank))
+                }
+                _ => panic!("expected a started notification"),
+            }
+            assert_eq!(poh_recorder.leader_bank_notification_senders.len(), 1);
+
+            poh_recorder.reset(hash(b"hello"), 0, None);
+            match receiver.try_recv() {
+                Ok(LeaderBankNotification::Stopped(stopped)) => {
+                    assert!(Arc::ptr_eq(&stopped, &bank))
+                }
+                _ => panic!("expected a stopped notification"),
+            }
+
+            // resetting without a working bank isn't a leader status change
+            poh_recorder.reset(hash(b"hello"), 0, None);
+            assert!(receiver.try_recv().is_err());
+        }
+        Blocktree::destroy(&ledger_path).unwrap();
+    }
+
     #[test]
     fn test_poh_recorder_reset_start_slot() {
         solana_logger::setup();
@@ -1,6 +1,7 @@
 //! The `poh_service` module implements a service that records the passing of
 //! "ticks", a measure of time in the PoH stream
 use crate::poh_recorder::PohRecorder;
+use crate::thread_affinity::{self, ThreadRole};
 use core_affinity;
 use solana_sdk::poh_config::PohConfig;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -43,9 +44,11 @@ impl PohService {
                 } else {
                     // PoH service runs in a tight loop, generating hashes as fast as possible.
                     // Let's dedicate one of the CPU cores to this thread so that it can gain
-                    // from cache performance.
-                    if let Some(cores) = core_affinity::get_core_ids() {
-                        core_affinity::set_for_current(cores[0]);
+                    // from cache performance, unless the thread layout picks its cores.
+                    if !thread_affinity::apply(ThreadRole::Poh) {
+                        if let Some(cores) = core_affinity::get_core_ids() {
+                            core_affinity::set_for_current(cores[0]);
+                        }
                     }
                     Self::tick_producer(poh_recorder, &poh_exit_);
                 }
@@ -0,0 +1,359 @@
+//! The `preflight` module checks that the host can run a validator before it starts: the clock,
+//! resource limits, network buffers, the ledger disk, the GPU and the reachability of its ports.
+
+use log::*;
+use solana_measure::measure::Measure;
+use std::{
+    fmt,
+    io::Write,
+    net::{SocketAddr, TcpListener, UdpSocket},
+    path::{Path, PathBuf},
+};
+
+/// Bytes written to the ledger path to measure its throughput
+const DISK_PROBE_BYTES: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreflightOutcome {
+    Pass(String),
+    /// The validator can run, but may perform poorly
+    Warn(String),
+    /// The validator can't run reliably, it refuses to start unless failures are ignored
+    Fail(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreflightCheck {
+    pub name: &'static str,
+    pub outcome: PreflightOutcome,
+}
+
+/// The outcome of every check, in the order they ran
+#[derive(Debug, Default)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    pub fn add(&mut self, name: &'static str, outcome: PreflightOutcome) {
+        match &outcome {
+            PreflightOutcome::Pass(detail) => info!("preflight {}: {}", name, detail),
+            PreflightOutcome::Warn(detail) => warn!("preflight {}: {}", name, detail),
+            PreflightOutcome::Fail(detail) => error!("preflight {}: {}", name, detail),
+        }
+        self.checks.push(PreflightCheck { name, outcome });
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &PreflightCheck> {
+        self.checks.iter().filter(|check| match check.outcome {
+            PreflightOutcome::Fail(_) => true,
+            _ => false,
+        })
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.failures().next().is_some()
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for check in &self.checks {
+            let (status, detail) = match &check.outcome {
+                PreflightOutcome::Pass(detail) => ("PASS", detail),
+                PreflightOutcome::Warn(detail) => ("WARN", detail),
+                PreflightOutcome::Fail(detail) => ("FAIL", detail),
+            };
+            writeln!(f, "{:<4}  {:<20}  {}", status, check.name, detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Thresholds for the host checks, see `check_host`
+#[derive(Debug, Clone)]
+pub struct PreflightConfig {
+    pub ledger_path: PathBuf,
+    /// Whether the validator was asked to use CUDA
+    pub cuda: bool,
+    /// Open file descriptors RocksDB wants, fewer is a warning
+    pub min_open_files: u64,
+    /// Socket buffer size the kernel should allow, smaller is a warning
+    pub min_udp_buffer_bytes: u64,
+    /// Free space on the ledger path, less is a failure
+    pub min_free_disk_bytes: u64,
+    /// Write throughput of the ledger path, slower is a warning
+    pub min_disk_bytes_per_sec: u64,
+}
+
+impl PreflightConfig {
+    pub fn new(ledger_path: &Path, cuda: bool) -> Self {
+        Self {
+            ledger_path: ledger_path.to_path_buf(),
+            cuda,
+            min_open_files: 65000,
+            min_udp_buffer_bytes: 128 * 1024 * 1024,
+            min_free_disk_bytes: 1024 * 1024 * 1024,
+            min_disk_bytes_per_sec: 100 * 1024 * 1024,
+        }
+    }
+}
+
+/// Run the checks that only need the local host
+pub fn check_host(config: &PreflightConfig) -> PreflightReport {
+    let mut report = PreflightReport::default();
+    report.add("clock sync", check_clock_sync());
+    report.add("open files", check_open_files(config.min_open_files));
+    report.add(
+        "udp buffers",
+        check_udp_buffers(config.min_udp_buffer_bytes),
+    );
+    report.add(
+        "disk space",
+        check_disk_space(&config.ledger_path, config.min_free_disk_bytes),
+    );
+    report.add(
+        "disk throughput",
+        check_disk_throughput(&config.ledger_path, config.min_disk_bytes_per_sec),
+    );
+    report.add("gpu", check_gpu(config.cuda, gpu_present()));
+    report
+}
+
+/// Add whether the machine at `ip_echo_server_addr` can reach the given ports to `report`
+pub fn check_ports(
+    report: &mut PreflightReport,
+    ip_echo_server_addr: &SocketAddr,
+    tcp_listeners: Vec<(u16, TcpListener)>,
+    udp_sockets: &[&UdpSocket],
+) {
+    let outcome = match solana_net_utils::check_reachable_ports(
+        ip_echo_server_addr,
+        tcp_listeners,
+        udp_sockets,
+    ) {
+        Ok(()) => PreflightOutcome::Pass(format!("reachable from {}", ip_echo_server_addr)),
+        Err(err) => PreflightOutcome::Fail(err),
+    };
+    report.add("port reachability", outcome);
+}
+
+#[cfg(target_os = "linux")]
+fn check_clock_sync() -> PreflightOutcome {
+    let mut timex: libc::timex = unsafe { std::mem::zeroed() };
+    match unsafe { libc::adjtimex(&mut timex) } {
+        -1 => PreflightOutcome::Warn(format!(
+            "unable to query the clock: {}",
+            std::io::Error::last_os_error()
+        )),
+        libc::TIME_ERROR => {
+            PreflightOutcome::Warn("the system clock is not synchronized, run NTP".to_string())
+        }
+        _ => PreflightOutcome::Pass(format!(
+            "synchronized, estimated error {}us",
+            timex.esterror
+        )),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn check_clock_sync() -> PreflightOutcome {
+    PreflightOutcome::Pass("not checked on this platform".to_string())
+}
+
+#[cfg(unix)]
+fn check_open_files(min_open_files: u64) -> PreflightOutcome {
+    let mut nofile = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut nofile) } != 0 {
+        return PreflightOutcome::Warn(format!(
+            "getrlimit(RLIMIT_NOFILE) failed: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    // the ledger raises the soft limit up to the hard limit when it's opened
+    let max_open_files = nofile.rlim_max as u64;
+    if max_open_files < min_open_files {
+        PreflightOutcome::Warn(format!(
+            "the open file limit is {}, the ledger may run out below {}",
+            max_open_files, min_open_files
+        ))
+    } else {
+        PreflightOutcome::Pass(format!("up to {} open files", max_open_files))
+    }
+}
+
+#[cfg(not(unix))]
+fn check_open_files(_min_open_files: u64) -> PreflightOutcome {
+    PreflightOutcome::Pass("not checked on this platform".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn check_udp_buffers(min_udp_buffer_bytes: u64) -> PreflightOutcome {
+    let read_sysctl = |name: &str| -> Result<u64, String> {
+        let path = Path::new("/proc/sys/net/core").join(name);
+        std::fs::read_to_string(&path)
+            .map_err(|err| format!("unable to read {:?}: {}", path, err))?
+            .trim()
+            .parse()
+            .map_err(|err| format!("unable to parse {:?}: {}", path, err))
+    };
+    let mut small_buffers = vec![];
+    for name in &["rmem_max", "wmem_max"] {
+        match read_sysctl(name) {
+            Ok(bytes) if bytes < min_udp_buffer_bytes => {
+                small_buffers.push(format!("net.core.{}={}", name, bytes))
+            }
+            Ok(_) => (),
+            Err(err) => return PreflightOutcome::Warn(err),
+        }
+    }
+    if small_buffers.is_empty() {
+        PreflightOutcome::Pass(format!("at least {} bytes", min_udp_buffer_bytes))
+    } else {
+        PreflightOutcome::Warn(format!(
+            "{} is below {} bytes, packets may be dropped",
+            small_buffers.join(", "),
+            min_udp_buffer_bytes
+        ))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn check_udp_buffers(_min_udp_buffer_bytes: u64) -> PreflightOutcome {
+    PreflightOutcome::Pass("not checked on this platform".to_string())
+}
+
+/// The ledger path may not exist until the genesis is downloaded, the disk it will be on is
+/// checked instead
+fn existing_ancestor(path: &Path) -> &Path {
+    path.ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+#[cfg(unix)]
+fn check_disk_space(ledger_path: &Path, min_free_disk_bytes: u64) -> PreflightOutcome {
+    match nix::sys::statvfs::statvfs(existing_ancestor(ledger_path)) {
+        Ok(stat) => {
+            let free_bytes = stat.blocks_available() as u64 * stat.fragment_size() as u64;
+            if free_bytes < min_free_disk_bytes {
+                PreflightOutcome::Fail(format!(
+                    "{} bytes free in {:?}, at least {} are needed",
+                    free_bytes, ledger_path, min_free_disk_bytes
+                ))
+            } else {
+                PreflightOutcome::Pass(format!("{} bytes free", free_bytes))
+            }
+        }
+        Err(err) => PreflightOutcome::Warn(format!("unable to stat {:?}: {}", ledger_path, err)),
+    }
+}
+
+#[cfg(not(unix))]
+fn check_disk_space(_ledger_path: &Path, _min_free_disk_bytes: u64) -> PreflightOutcome {
+    PreflightOutcome::Pass("not checked on this platform".to_string())
+}
+
+fn check_disk_throughput(ledger_path: &Path, min_disk_bytes_per_sec: u64) -> PreflightOutcome {
+    let write_probe = || -> std::io::Result<u64> {
+        let mut file = tempfile::tempfile_in(existing_ancestor(ledger_path))?;
+        let chunk = vec![0xa5u8; 1024 * 1024];
+        let mut measure = Measure::start("disk_probe");
+        for _ in 0..DISK_PROBE_BYTES / chunk.len() {
+            file.write_all(&chunk)?;
+        }
+        file.sync_all()?;
+        measure.stop();
+        Ok(DISK_PROBE_BYTES as u64 * 1000 / measure.as_ms().max(1))
+    };
+    match write_probe() {
+        Ok(bytes_per_sec) if bytes_per_sec < min_disk_bytes_per_sec => {
+            PreflightOutcome::Warn(format!(
+                "{:?} writes {} bytes/s, slower than {} bytes/s",
+                ledger_path, bytes_per_sec, min_disk_bytes_per_sec
+            ))
+        }
+        Ok(bytes_per_sec) => PreflightOutcome::Pass(format!("{} bytes/s", bytes_per_sec)),
+        Err(err) => {
+            PreflightOutcome::Fail(format!("unable to write to {:?}: {}", ledger_path, err))
+        }
+    }
+}
+
+/// Whether the host has an NVIDIA GPU with its driver loaded
+fn gpu_present() -> bool {
+    Path::new("/dev/nvidia0").exists()
+}
+
+fn check_gpu(cuda: bool, gpu_present: bool) -> PreflightOutcome {
+    match (cuda, gpu_present) {
+        (true, true) => PreflightOutcome::Pass("CUDA requested and a GPU is present".to_string()),
+        (true, false) => PreflightOutcome::Fail("CUDA requested but no GPU was found".to_string()),
+        (false, true) => PreflightOutcome::Warn(
+            "a GPU is present but CUDA wasn't requested, signatures are verified on the CPU"
+                .to_string(),
+        ),
+        (false, false) => PreflightOutcome::Pass("CPU only".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_gpu() {
+        assert_matches!(check_gpu(true, true), PreflightOutcome::Pass(_));
+        assert_matches!(check_gpu(true, false), PreflightOutcome::Fail(_));
+        assert_matches!(check_gpu(false, true), PreflightOutcome::Warn(_));
+        assert_matches!(check_gpu(false, false), PreflightOutcome::Pass(_));
+    }
+
+    #[test]
+    fn test_preflight_report() {
+        let mut report = PreflightReport::default();
+        report.add(
+            "clock sync",
+            PreflightOutcome::Pass("synchronized".to_string()),
+        );
+        report.add("udp buffers", PreflightOutcome::Warn("small".to_string()));
+        assert!(!report.has_failures());
+
+        report.add("disk space", PreflightOutcome::Fail("full".to_string()));
+        assert!(report.has_failures());
+        assert_eq!(
+            report
+                .failures()
+                .map(|check| check.name)
+                .collect::<Vec<_>>(),
+            vec!["disk space"]
+        );
+        assert_eq!(
+            report.to_string(),
+            "PASS  clock sync            synchronized\n\
+             WARN  udp buffers           small\n\
+             FAIL  disk space            full\n"
+        );
+    }
+
+    #[test]
+    fn test_check_disk() {
+        let ledger_path = tempfile::tempdir().unwrap();
+        assert_matches!(
+            check_disk_throughput(ledger_path.path(), 0),
+            PreflightOutcome::Pass(_)
+        );
+        assert_matches!(
+            check_disk_throughput(&ledger_path.path().join("missing"), 0),
+            PreflightOutcome::Pass(_)
+        );
+        #[cfg(unix)]
+        assert_matches!(
+            check_disk_space(ledger_path.path(), std::u64::MAX),
+            PreflightOutcome::Fail(_)
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! The `repair_peers` module scores the peers shreds are requested from, by how often and how
+//! fast they answer and by their stake. The repair service and archiver downloads share one
+//! `RepairPeers` through `ClusterInfo`, so what one learns about a peer the other uses too.
+
+use crate::weighted_shuffle::weighted_best;
+use rand::{thread_rng, Rng};
+use solana_metrics::datapoint_info;
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+// Requests older than this many unanswered ones to the same peer are considered lost
+const MAX_OUTSTANDING_REQUESTS_PER_PEER: usize = 1024;
+const REPAIR_PEERS_REPORT_MS: u64 = 10_000;
+// Peers nothing was requested from for this long are forgotten
+const REPAIR_PEER_IDLE_MS: u64 = 600_000;
+// Score added for a peer holding all of the stake
+const MAX_STAKE_SCORE: u64 = 100;
+
+#[derive(Debug)]
+struct RepairPeerStats {
+    requests: u64,
+    responses: u64,
+    total_latency_ms: u64,
+    // send times of requests that haven't been answered yet, oldest first
+    outstanding: VecDeque<Instant>,
+    last_request: Instant,
+}
+
+impl Default for RepairPeerStats {
+    fn default() -> Self {
+        Self {
+            requests: 0,
+            responses: 0,
+            total_latency_ms: 0,
+            outstanding: VecDeque::new(),
+            last_request: Instant::now(),
+        }
+    }
+}
+
+impl RepairPeerStats {
+    fn avg_latency_ms(&self) -> u64 {
+        self.total_latency_ms / self.responses.max(1)
+    }
+
+    // Responsiveness of the peer, requests that were never answered lower it over time and
+    // slow answers scale it down. Unknown peers start at half weight so they still get tried.
+    fn score(&self) -> u64 {
+        let success = 1_000 * (self.responses + 1) / (self.requests + 2);
+        1 + success * 1_000 / (1_000 + self.avg_latency_ms())
+    }
+}
+
+/// Success rates and latencies of the peers shreds were requested from, keyed by identity.
+/// Responses are attributed to a peer by the address its requests were sent to.
+pub struct RepairPeers {
+    peers: HashMap<Pubkey, RepairPeerStats>,
+    addrs: HashMap<SocketAddr, Pubkey>,
+    stakes: HashMap<Pubkey, u64>,
+    total_stake: u64,
+    last_report: Instant,
+}
+
+impl Default for RepairPeers {
+    fn default() -> Self {
+        Self {
+            peers: HashMap::new(),
+            addrs: HashMap::new(),
+            stakes: HashMap::new(),
+            total_stake: 0,
+            last_report: Instant::now(),
+        }
+    }
+}
+
+impl RepairPeers {
+    pub fn record_request(&mut self, peer: Pubkey, addr: SocketAddr) {
+        self.addrs.insert(addr, peer);
+        let stats = self.peers.entry(peer).or_default();
+        let now = Instant::now();
+        stats.requests += 1;
+        stats.last_request = now;
+        stats.outstanding.push_back(now);
+        if stats.outstanding.len() > MAX_OUTSTANDING_REQUESTS_PER_PEER {
+            stats.outstanding.pop_front();
+        }
+    }
+
+    /// Record a response from `addr`, ignored unless a request was sent to `addr`
+    pub fn record_response(&mut self, addr: &SocketAddr) {
+        let peer = match self.addrs.get(addr) {
+            Some(peer) => peer,
+            None => return,
+        };
+        if let Some(stats) = self.peers.get_mut(peer) {
+            // a single request can be answered by several shreds
+            if let Some(sent) = stats.outstanding.pop_front() {
+                stats.responses += 1;
+                stats.total_latency_ms += sent.elapsed().as_millis() as u64;
+            }
+        }
+    }
+
+    /// Replace the stakes peers are weighted by
+    pub fn set_stakes(&mut self, stakes: HashMap<Pubkey, u64>) {
+        self.total_stake = stakes.values().sum();
+        self.stakes = stakes;
+    }
+
+    /// Score of `peer`, higher is better
+    pub fn score(&self, peer: &Pubkey) -> u64 {
+        let score = self
+            .peers
+            .get(peer)
+            .map(RepairPeerStats::score)
+            .unwrap_or_else(|| RepairPeerStats::default().score());
+        let stake = self.stakes.get(peer).cloned().unwrap_or(0);
+        let stake_score = if self.total_stake == 0 {
+            0
+        } else {
+            (u128::from(MAX_STAKE_SCORE) * u128::from(stake) / u128::from(self.total_stake)) as u64
+        };
+        score + stake_score
+    }
+
+    /// Pick one of `peers` to request shreds from, favoring the best scored ones
+    pub fn select_peer(&self, peers: &[(Pubkey, SocketAddr)]) -> Option<(Pubkey, SocketAddr)> {
+        if peers.is_empty() {
+            return None;
+        }
+        let weights: Vec<_> = peers
+            .iter()
+            .enumerate()
+            .map(|(i, (peer, _))| (self.score(peer), i))
+            .collect();
+        Some(peers[weighted_best(&weights, thread_rng().gen())])
+    }
+
+    /// Forget the peers nothing was requested from in a while
+    pub fn evict_idle(&mut self) {
+        self.evict_idle_at(Instant::now());
+    }
+
+    fn evict_idle_at(&mut self, now: Instant) {
+        let idle = Duration::from_millis(REPAIR_PEER_IDLE_MS);
+        self.peers
+            .retain(|_, stats| now.saturating_duration_since(stats.last_request) < idle);
+        let peers = &self.peers;
+        self.addrs.retain(|_, peer| peers.contains_key(peer));
+    }
+
+    pub fn maybe_report(&mut self) {
+        if self.last_report.elapsed() < Duration::from_millis(REPAIR_PEERS_REPORT_MS) {
+            return;
+        }
+        self.last_report = Instant::now();
+        for (peer, stats) in &self.peers {
+            datapoint_info!(
+                "repair_peers-peer_stats",
+                ("peer", peer.to_string(), String),
+                ("requests", stats.requests, i64),
+                ("responses", stats.responses, i64),
+                ("avg_latency_ms", stats.avg_latency_ms(), i64),
+                ("score", self.score(peer), i64)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_peers() {
+        let responsive = (Pubkey::new_rand(), "127.0.0.1:1234".parse().unwrap());
+        let silent = (Pubkey::new_rand(), "127.0.0.1:1235".parse().unwrap());
+        let mut repair_peers = RepairPeers::default();
+        assert_eq!(repair_peers.select_peer(&[]), None);
+
+        for _ in 0..10 {
+            repair_peers.record_request(responsive.0, responsive.1);
+            repair_peers.record_request(silent.0, silent.1);
+            repair_peers.record_response(&responsive.1);
+        }
+        // responses beyond the outstanding requests, or from unknown peers, don't count
+        repair_peers.record_response(&responsive.1);
+        repair_peers.record_response(&"127.0.0.1:1236".parse().unwrap());
+        assert_eq!(repair_peers.peers[&responsive.0].responses, 10);
+        assert_eq!(repair_peers.peers.len(), 2);
+        assert!(repair_peers.score(&responsive.0) > 10 * repair_peers.score(&silent.0));
+
+        let selected = (0..100)
+            .filter(|_| repair_peers.select_peer(&[responsive, silent]) == Some(responsive))
+            .count();
+        assert!(selected > 50);
+
+        // stake raises the score
+        let unknown = Pubkey::new_rand();
+        let score = repair_peers.score(&unknown);
+        repair_peers.set_stakes(vec![(unknown, 3), (silent.0, 1)].into_iter().collect());
+        assert_eq!(
+            repair_peers.score(&unknown),
+            score + MAX_STAKE_SCORE * 3 / 4
+        );
+
+        repair_peers.evict_idle();
+        assert_eq!(repair_peers.peers.len(), 2);
+        let idle = Instant::now() + Duration::from_millis(REPAIR_PEER_IDLE_MS);
+        repair_peers.record_request(responsive.0, responsive.1);
+        repair_peers
+            .peers
+            .get_mut(&responsive.0)
+            .unwrap()
+            .last_request = idle;
+        repair_peers.evict_idle_at(idle);
+        assert!(!repair_peers.peers.contains_key(&silent.0));
+        assert!(!repair_peers.addrs.contains_key(&silent.1));
+        assert!(repair_peers.peers.contains_key(&responsive.0));
+    }
+
+    #[test]
+    fn test_latency_lowers_score() {
+        let fast = RepairPeerStats {
+            requests: 10,
+            responses: 10,
+            total_latency_ms: 100,
+            ..RepairPeerStats::default()
+        };
+        let slow = RepairPeerStats {
+            requests: 10,
+            responses: 10,
+            total_latency_ms: 10_000,
+            ..RepairPeerStats::default()
+        };
+        assert!(fast.score() > slow.score());
+    }
+}
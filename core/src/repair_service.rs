@@ -2,30 +2,38 @@
 //! regularly finds missing shreds in the ledger and sends repair requests for those shreds
 use crate::{
     cluster_info::ClusterInfo, cluster_info_repair_listener::ClusterInfoRepairListener,
-    result::Result,
+    repair_peers::RepairPeers, result::Result,
 };
+use rand::{seq::SliceRandom, thread_rng};
 use solana_ledger::{
     bank_forks::BankForks,
     blocktree::{Blocktree, CompletedSlotsReceiver, SlotMeta},
+    staking_utils,
 };
-use solana_sdk::{clock::Slot, epoch_schedule::EpochSchedule, pubkey::Pubkey};
+use solana_sdk::{clock::Slot, epoch_schedule::EpochSchedule, hash::Hash, pubkey::Pubkey};
 use std::{
-    collections::BTreeSet,
-    net::UdpSocket,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    net::{SocketAddr, UdpSocket},
     ops::Bound::{Excluded, Unbounded},
     sync::atomic::{AtomicBool, Ordering},
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
     thread::sleep,
     thread::{self, Builder, JoinHandle},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 pub const MAX_REPAIR_LENGTH: usize = 512;
 pub const REPAIR_MS: u64 = 100;
 pub const MAX_ORPHANS: usize = 5;
+// The stakes repair peers are weighted by are refreshed this often
+const REPAIR_PEER_STAKES_REFRESH_MS: u64 = 10_000;
+// Dead slots whose ancestor hashes are requested at a time, lowest first
+const MAX_DEAD_SLOT_REQUESTS: usize = 4;
+// Unanswered ancestor hashes requests are sent again, to another peer, after this long
+const ANCESTOR_HASHES_RETRY_MS: u64 = 5_000;
 
 pub enum RepairStrategy {
-    RepairRange(RepairSlotRange),
+    RepairRange(Arc<RwLock<RepairSlotRange>>),
     RepairAll {
         bank_forks: Arc<RwLock<BankForks>>,
         completed_slots_receiver: CompletedSlotsReceiver,
@@ -38,18 +46,78 @@ pub enum RepairType {
     Orphan(u64),
     HighestShred(u64, u64),
     Shred(u64, u64),
+    AncestorHashes(u64),
 }
 
 pub struct RepairSlotRange {
     pub start: u64,
     pub end: u64,
+    // disjoint, non-adjacent inclusive ranges of slots known to be full, keyed by first slot
+    completed: BTreeMap<Slot, Slot>,
 }
 
 impl Default for RepairSlotRange {
     fn default() -> Self {
+        RepairSlotRange::new(0, std::u64::MAX)
+    }
+}
+
+impl RepairSlotRange {
+    pub fn new(start: Slot, end: Slot) -> Self {
         RepairSlotRange {
-            start: 0,
-            end: std::u64::MAX,
+            start,
+            end,
+            completed: BTreeMap::new(),
+        }
+    }
+
+    /// Move the active range, slots already marked complete stay complete
+    pub fn set_range(&mut self, start: Slot, end: Slot) {
+        self.start = start;
+        self.end = end;
+    }
+
+    /// Mark the slots `start..=end` as full so that repair skips them
+    pub fn mark_complete(&mut self, start: Slot, end: Slot) {
+        if start > end {
+            return;
+        }
+        // merge with every completed range that overlaps or touches this one
+        let merged: Vec<_> = self
+            .completed
+            .range(..=end.saturating_add(1))
+            .rev()
+            .take_while(|(_, range_end)| range_end.saturating_add(1) >= start)
+            .map(|(range_start, range_end)| (*range_start, *range_end))
+            .collect();
+        let mut start = start;
+        let mut end = end;
+        for (range_start, range_end) in merged {
+            self.completed.remove(&range_start);
+            start = start.min(range_start);
+            end = end.max(range_end);
+        }
+        self.completed.insert(start, end);
+    }
+
+    pub fn is_complete(&self, slot: Slot) -> bool {
+        self.completed
+            .range(..=slot)
+            .next_back()
+            .map_or(false, |(_, range_end)| slot <= *range_end)
+    }
+
+    /// True once every slot of the active range has been marked complete
+    pub fn is_range_complete(&self) -> bool {
+        self.first_incomplete(self.start)
+            .map_or(true, |slot| slot > self.end)
+    }
+
+    // The first slot >= `slot` that hasn't been marked complete
+    fn first_incomplete(&self, slot: Slot) -> Option<Slot> {
+        match self.completed.range(..=slot).next_back() {
+            Some((_, range_end)) if slot <= *range_end => range_end.checked_add(1),
+            _ => Some(slot),
         }
     }
 }
@@ -66,6 +134,8 @@ impl RepairService {
         repair_socket: Arc<UdpSocket>,
         cluster_info: Arc<RwLock<ClusterInfo>>,
         repair_strategy: RepairStrategy,
+        window_backpressure: Arc<AtomicBool>,
+        repair_peers: Arc<Mutex<RepairPeers>>,
     ) -> Self {
         let cluster_info_repair_listener = match repair_strategy {
             RepairStrategy::RepairAll {
@@ -89,6 +159,8 @@ impl RepairService {
                     &repair_socket,
                     &cluster_info,
                     repair_strategy,
+                    &window_backpressure,
+                    &repair_peers,
                 )
             })
             .unwrap();
@@ -105,10 +177,15 @@ impl RepairService {
         repair_socket: &Arc<UdpSocket>,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         repair_strategy: RepairStrategy,
+        window_backpressure: &AtomicBool,
+        repair_peers: &Mutex<RepairPeers>,
     ) {
         let mut epoch_slots: BTreeSet<u64> = BTreeSet::new();
+        // peer and send time of the outstanding ancestor hashes request of each dead slot
+        let mut ancestor_hashes_requests: HashMap<Slot, (SocketAddr, Instant)> = HashMap::new();
         let id = cluster_info.read().unwrap().id();
         let mut current_root = 0;
+        let mut stakes_refreshed: Option<Instant> = None;
         if let RepairStrategy::RepairAll {
             ref epoch_schedule, ..
         } = repair_strategy
@@ -135,7 +212,7 @@ impl RepairService {
                         Self::generate_repairs_in_range(
                             blocktree,
                             MAX_REPAIR_LENGTH,
-                            repair_slot_range,
+                            &mut repair_slot_range.write().unwrap(),
                         )
                     }
 
@@ -157,42 +234,181 @@ impl RepairService {
                 }
             };
 
-            if let Ok(repairs) = repairs {
+            if window_backpressure.load(Ordering::Relaxed) {
+                // the window can't absorb the shreds it already has, don't ask for more
+                inc_new_counter_debug!("repair_service-backpressure_skipped", 1);
+            } else if let Ok(repairs) = repairs {
+                let peers = cluster_info.read().unwrap().repair_peer_addrs();
+                let mut repair_peers = repair_peers.lock().unwrap();
+                repair_peers.evict_idle();
                 let reqs: Vec<_> = repairs
                     .into_iter()
                     .filter_map(|repair_request| {
+                        let to = repair_peers.select_peer(&peers)?;
                         cluster_info
                             .read()
                             .unwrap()
-                            .repair_request(&repair_request)
-                            .map(|result| (result, repair_request))
+                            .map_repair_request(&repair_request)
+                            .map(|req| (to, req))
                             .ok()
                     })
                     .collect();
 
-                for ((to, req), _) in reqs {
-                    repair_socket.send_to(&req, to).unwrap_or_else(|e| {
-                        info!("{} repair req send_to({}) error {:?}", id, to, e);
-                        0
-                    });
+                for ((peer, to), req) in reqs {
+                    match repair_socket.send_to(&req, to) {
+                        Ok(_) => repair_peers.record_request(peer, to),
+                        Err(e) => info!("{} repair req send_to({}) error {:?}", id, to, e),
+                    }
                 }
+                repair_peers.maybe_report();
+            }
+            if let RepairStrategy::RepairAll { ref bank_forks, .. } = repair_strategy {
+                let refresh = stakes_refreshed
+                    .map(|refreshed| {
+                        refreshed.elapsed() >= Duration::from_millis(REPAIR_PEER_STAKES_REFRESH_MS)
+                    })
+                    .unwrap_or(true);
+                if refresh {
+                    let stakes =
+                        staking_utils::staked_nodes(&bank_forks.read().unwrap().working_bank());
+                    repair_peers.lock().unwrap().set_stakes(stakes);
+                    stakes_refreshed = Some(Instant::now());
+                }
+                Self::repair_dead_slots(
+                    blocktree,
+                    bank_forks,
+                    repair_socket,
+                    cluster_info,
+                    &mut ancestor_hashes_requests,
+                );
             }
             sleep(Duration::from_millis(REPAIR_MS));
         }
     }
 
-    // Generate repairs for all slots `x` in the repair_range.start <= x <= repair_range.end
+    /// Ask peers for the ancestor hashes of the lowest dead slots, and clear the dead slots
+    /// whose answers show this node diverged from the peer on the slot itself, so it's repaired
+    /// and replayed again
+    fn repair_dead_slots(
+        blocktree: &Blocktree,
+        bank_forks: &RwLock<BankForks>,
+        repair_socket: &UdpSocket,
+        cluster_info: &RwLock<ClusterInfo>,
+        outstanding: &mut HashMap<Slot, (SocketAddr, Instant)>,
+    ) {
+        let responses = cluster_info.write().unwrap().take_ancestor_hashes();
+        for (slot, (from_addr, ancestor_hashes)) in responses {
+            // responses aren't signed, only take the answer of the peer that was asked
+            if outstanding.get(&slot).map(|(addr, _)| *addr) != Some(from_addr) {
+                inc_new_counter_debug!("repair_service-ancestor_hashes_unexpected", 1);
+                continue;
+            }
+            outstanding.remove(&slot);
+            Self::resolve_dead_slot(blocktree, bank_forks, slot, &ancestor_hashes);
+        }
+
+        let root = blocktree.last_root();
+        let dead_slots: Vec<_> = match blocktree.dead_slots_iterator(root + 1) {
+            Ok(dead_slots) => dead_slots.take(MAX_DEAD_SLOT_REQUESTS).collect(),
+            Err(e) => {
+                warn!("unable to look up dead slots: {:?}", e);
+                return;
+            }
+        };
+        outstanding.retain(|slot, _| dead_slots.contains(slot));
+        let peers = cluster_info.read().unwrap().repair_peer_addrs();
+        for slot in dead_slots {
+            if let Some((_, sent)) = outstanding.get(&slot) {
+                if sent.elapsed() < Duration::from_millis(ANCESTOR_HASHES_RETRY_MS) {
+                    continue;
+                }
+            }
+            let to = match peers.choose(&mut thread_rng()) {
+                Some((_, to)) => *to,
+                None => return,
+            };
+            let req = match cluster_info
+                .read()
+                .unwrap()
+                .map_repair_request(&RepairType::AncestorHashes(slot))
+            {
+                Ok(req) => req,
+                Err(_) => continue,
+            };
+            match repair_socket.send_to(&req, to) {
+                Ok(_) => {
+                    outstanding.insert(slot, (to, Instant::now()));
+                }
+                Err(e) => info!("ancestor hashes req send_to({}) error {:?}", to, e),
+            }
+        }
+    }
+
+    fn resolve_dead_slot(
+        blocktree: &Blocktree,
+        bank_forks: &RwLock<BankForks>,
+        dead_slot: Slot,
+        ancestor_hashes: &[(Slot, Hash)],
+    ) {
+        let bank_forks = bank_forks.read().unwrap();
+        let local_hash = |slot| {
+            bank_forks
+                .get(slot)
+                .filter(|bank| bank.is_frozen())
+                .map(|bank| bank.hash())
+        };
+        match first_divergent_slot(ancestor_hashes, bank_forks.root(), &local_hash) {
+            Some(slot) if local_hash(slot).is_some() => {
+                // the whole fork above `slot` was replayed on a different state, clearing the
+                // dead slot won't fix it
+                warn!(
+                    "dead slot {} descends from slot {}, which was replayed to a different hash than the cluster's",
+                    dead_slot, slot
+                );
+                datapoint_error!(
+                    "repair_service-divergent_fork",
+                    ("dead_slot", dead_slot, i64),
+                    ("divergent_slot", slot, i64)
+                );
+            }
+            Some(slot) => {
+                // the peer replayed a different version of the dead slot, or the same version
+                // on top of a different parent, download it again
+                info!(
+                    "clearing dead slot {} to repair it again, diverged at slot {}",
+                    dead_slot, slot
+                );
+                if let Err(e) = blocktree.clear_dead_slot(dead_slot) {
+                    warn!("unable to clear dead slot {}: {:?}", dead_slot, e);
+                    return;
+                }
+                datapoint_info!(
+                    "repair_service-dead_slot_cleared",
+                    ("dead_slot", dead_slot, i64),
+                    ("divergent_slot", slot, i64)
+                );
+            }
+            None => debug!("peer agrees with the ancestors of dead slot {}", dead_slot),
+        }
+    }
+
+    // Generate repairs for all slots `x` in the repair_range.start <= x <= repair_range.end,
+    // skipping slots already marked complete and marking any newly full ones
     pub fn generate_repairs_in_range(
         blocktree: &Blocktree,
         max_repairs: usize,
-        repair_range: &RepairSlotRange,
+        repair_range: &mut RepairSlotRange,
     ) -> Result<(Vec<RepairType>)> {
         // Slot height and shred indexes for shreds we want to repair
         let mut repairs: Vec<RepairType> = vec![];
-        for slot in repair_range.start..=repair_range.end {
-            if repairs.len() >= max_repairs {
+        let mut next_slot = repair_range.first_incomplete(repair_range.start);
+        while let Some(slot) = next_slot {
+            if slot > repair_range.end || repairs.len() >= max_repairs {
                 break;
             }
+            next_slot = slot
+                .checked_add(1)
+                .and_then(|slot| repair_range.first_incomplete(slot));
 
             let meta = blocktree
                 .meta(slot)
@@ -201,6 +417,10 @@ impl RepairService {
                     slot,
                     ..SlotMeta::default()
                 });
+            if meta.is_full() {
+                repair_range.mark_complete(slot, slot);
+                continue;
+            }
 
             let new_repairs = Self::generate_repairs_for_slot(
                 blocktree,
@@ -226,8 +446,15 @@ impl RepairService {
         // TODO: Incorporate gossip to determine priorities for repair?
 
         // Try to resolve orphans in blocktree
-        let mut orphans = blocktree.get_orphans(Some(MAX_ORPHANS));
-        orphans.retain(|x| *x > root);
+        let orphan_heads = blocktree.orphan_heads(root, MAX_ORPHANS);
+        if let Some(oldest) = orphan_heads.iter().map(|(_, age)| *age).max() {
+            datapoint_debug!(
+                "repair_service-orphans",
+                ("num_orphans", orphan_heads.len() as i64, i64),
+                ("oldest_orphan_age_ms", oldest.as_millis() as i64, i64),
+            );
+        }
+        let orphans: Vec<_> = orphan_heads.into_iter().map(|(slot, _)| slot).collect();
 
         Self::generate_repairs_for_orphans(&orphans[..], &mut repairs);
         Ok(repairs)
@@ -386,6 +613,27 @@ impl RepairService {
     }
 }
 
+/// The lowest slot at or above `root` in `ancestor_hashes`, the hashes a peer replayed a slot and
+/// its ancestors to, that `local_hash` doesn't report the same hash for
+pub fn first_divergent_slot<F>(
+    ancestor_hashes: &[(Slot, Hash)],
+    root: Slot,
+    local_hash: F,
+) -> Option<Slot>
+where
+    F: Fn(Slot) -> Option<Hash>,
+{
+    let mut ancestor_hashes: Vec<_> = ancestor_hashes
+        .iter()
+        .filter(|(slot, _)| *slot >= root)
+        .collect();
+    ancestor_hashes.sort_by_key(|(slot, _)| *slot);
+    ancestor_hashes
+        .into_iter()
+        .find(|(slot, hash)| local_hash(*slot) != Some(*hash))
+        .map(|(slot, _)| *slot)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -401,6 +649,32 @@ mod test {
     use std::sync::mpsc::channel;
     use std::thread::Builder;
 
+    #[test]
+    fn test_first_divergent_slot() {
+        let hash = |slot: Slot| Hash::new(&[slot as u8; 32]);
+        let local_hashes: HashMap<_, _> = (2..=4).map(|slot| (slot, hash(slot))).collect();
+        let local_hash = |slot| local_hashes.get(&slot).cloned();
+
+        // the peer replayed dead slot 5 on the same ancestors, slots below the root are ignored
+        let ancestor_hashes: Vec<_> = (0..=5).rev().map(|slot| (slot, hash(slot))).collect();
+        assert_eq!(
+            first_divergent_slot(&ancestor_hashes, 2, &local_hash),
+            Some(5)
+        );
+        assert_eq!(
+            first_divergent_slot(&ancestor_hashes[1..], 2, &local_hash),
+            None
+        );
+
+        // the peer replayed slot 3 to a different hash
+        let mut ancestor_hashes = ancestor_hashes;
+        ancestor_hashes[2].1 = Hash::default();
+        assert_eq!(
+            first_divergent_slot(&ancestor_hashes, 2, &local_hash),
+            Some(3)
+        );
+    }
+
     #[test]
     pub fn test_repair_orphan() {
         let blocktree_path = get_tmp_ledger_path!();
@@ -560,7 +834,7 @@ mod test {
                         RepairService::generate_repairs_in_range(
                             &blocktree,
                             std::usize::MAX,
-                            &repair_slot_range
+                            &mut repair_slot_range
                         )
                         .unwrap(),
                         expected
@@ -571,6 +845,66 @@ mod test {
         Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    pub fn test_repair_slot_range_mark_complete() {
+        let mut repair_slot_range = RepairSlotRange::new(0, 20);
+        assert!(!repair_slot_range.is_complete(0));
+
+        repair_slot_range.mark_complete(5, 7);
+        repair_slot_range.mark_complete(10, 12);
+        assert!(!repair_slot_range.is_complete(4));
+        assert!(repair_slot_range.is_complete(5));
+        assert!(repair_slot_range.is_complete(7));
+        assert!(!repair_slot_range.is_complete(8));
+        assert_eq!(repair_slot_range.first_incomplete(6), Some(8));
+        assert_eq!(repair_slot_range.first_incomplete(9), Some(9));
+
+        // adjacent and overlapping ranges are merged
+        repair_slot_range.mark_complete(8, 9);
+        repair_slot_range.mark_complete(11, 15);
+        assert_eq!(
+            repair_slot_range.completed.iter().collect::<Vec<_>>(),
+            vec![(&5, &15)]
+        );
+        assert!(!repair_slot_range.is_range_complete());
+
+        repair_slot_range.set_range(6, 14);
+        assert!(repair_slot_range.is_range_complete());
+
+        repair_slot_range.mark_complete(0, std::u64::MAX);
+        assert_eq!(repair_slot_range.first_incomplete(3), None);
+    }
+
+    #[test]
+    pub fn test_repair_range_skips_complete() {
+        let blocktree_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&blocktree_path).unwrap();
+
+            // slot 1 is full
+            let (shreds, _) = make_slot_entries(1, 0, 1);
+            blocktree.insert_shreds(shreds, None, false).unwrap();
+
+            let mut repair_slot_range = RepairSlotRange::new(0, 3);
+            repair_slot_range.mark_complete(2, 2);
+            assert_eq!(
+                RepairService::generate_repairs_in_range(
+                    &blocktree,
+                    std::usize::MAX,
+                    &mut repair_slot_range
+                )
+                .unwrap(),
+                vec![
+                    RepairType::HighestShred(0, 0),
+                    RepairType::HighestShred(3, 0)
+                ]
+            );
+            // the full slot was noticed and won't be looked up again
+            assert!(repair_slot_range.is_complete(1));
+        }
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     pub fn test_repair_range_highest() {
         let blocktree_path = get_tmp_ledger_path!();
@@ -605,7 +939,7 @@ mod test {
                 RepairService::generate_repairs_in_range(
                     &blocktree,
                     std::usize::MAX,
-                    &repair_slot_range
+                    &mut repair_slot_range
                 )
                 .unwrap(),
                 expected
@@ -8,6 +8,7 @@ use crate::consensus::{StakeLockout, Tower};
 use crate::poh_recorder::PohRecorder;
 use crate::result::{Error, Result};
 use crate::rpc_subscriptions::RpcSubscriptions;
+use crate::validator_plugin::{SlotStatus, ValidatorPluginManager};
 use solana_ledger::{
     bank_forks::BankForks,
     block_error::BlockError,
@@ -178,6 +179,7 @@ impl ReplayStage {
         slot_full_senders: Vec<Sender<(u64, Pubkey)>>,
         snapshot_package_sender: Option<SnapshotPackageSender>,
         block_commitment_cache: Arc<RwLock<BlockCommitmentCache>>,
+        plugins: ValidatorPluginManager,
     ) -> (Self, Receiver<Vec<Arc<Bank>>>)
     where
         T: 'static + KeypairUtil + Send + Sync,
@@ -227,6 +229,7 @@ impl ReplayStage {
                         &my_pubkey,
                         &mut progress,
                         &slot_full_senders,
+                        &plugins,
                     );
 
                     let ancestors = Arc::new(bank_forks.read().unwrap().ancestors());
@@ -264,6 +267,7 @@ impl ReplayStage {
                                 total_staked,
                                 &lockouts_sender,
                                 &snapshot_package_sender,
+                                &plugins,
                             )?;
                         }
                         if let Some(bank) = reset_bank {
@@ -529,6 +533,7 @@ impl ReplayStage {
         total_staked: u64,
         lockouts_sender: &Sender<CommitmentAggregationData>,
         snapshot_package_sender: &Option<SnapshotPackageSender>,
+        plugins: &ValidatorPluginManager,
     ) -> Result<()>
     where
         T: 'static + KeypairUtil + Send + Sync,
@@ -557,6 +562,13 @@ impl ReplayStage {
             blocktree
                 .set_roots(&rooted_slots)
                 .expect("Ledger set roots failed");
+            for rooted_bank in &rooted_banks {
+                plugins.notify_slot_status(
+                    rooted_bank.slot(),
+                    rooted_bank.parent().map(|parent| parent.slot()),
+                    SlotStatus::Rooted,
+                );
+            }
             bank_forks
                 .write()
                 .unwrap()
@@ -636,6 +648,7 @@ impl ReplayStage {
         my_pubkey: &Pubkey,
         progress: &mut HashMap<u64, ForkProgress>,
         slot_full_senders: &[Sender<(u64, Pubkey)>],
+        plugins: &ValidatorPluginManager,
     ) -> bool {
         let mut did_complete_bank = false;
         let mut tx_count = 0;
@@ -645,7 +658,18 @@ impl ReplayStage {
         for bank_slot in &active_banks {
             // If the fork was marked as dead, don't replay it
             if progress.get(bank_slot).map(|p| p.is_dead).unwrap_or(false) {
-                debug!("bank_slot {:?} is marked dead", *bank_slot);
+                if blocktree.is_dead(*bank_slot) {
+                    debug!("bank_slot {:?} is marked dead", *bank_slot);
+                } else {
+                    // Repair cleared the slot to download it again, drop the bank so the slot is
+                    // replayed from its parent
+                    info!(
+                        "bank_slot {} was cleared for repair, dropping its bank",
+                        bank_slot
+                    );
+                    bank_forks.write().unwrap().remove_active_bank(*bank_slot);
+                    progress.remove(bank_slot);
+                }
                 continue;
             }
 
@@ -669,7 +693,13 @@ impl ReplayStage {
                         .report_stats(bank_progress.num_entries, bank_progress.num_shreds);
                 }
                 did_complete_bank = true;
-                Self::process_completed_bank(my_pubkey, bank, slot_full_senders);
+                Self::process_completed_bank(
+                    my_pubkey,
+                    bank,
+                    blocktree,
+                    slot_full_senders,
+                    plugins,
+                );
             } else {
                 trace!(
                     "bank {} not completed tick_height: {}, max_tick_height: {}",
@@ -996,10 +1026,13 @@ impl ReplayStage {
     fn process_completed_bank(
         my_pubkey: &Pubkey,
         bank: Arc<Bank>,
+        blocktree: &Blocktree,
         slot_full_senders: &[Sender<(u64, Pubkey)>],
+        plugins: &ValidatorPluginManager,
     ) {
         bank.freeze();
         info!("bank frozen {}", bank.slot());
+        plugins.notify_bank_frozen(&bank, blocktree);
         slot_full_senders.iter().for_each(|sender| {
             if let Err(e) = sender.send((bank.slot(), *bank.collector_id())) {
                 trace!("{} slot_full alert failed: {:?}", my_pubkey, e);
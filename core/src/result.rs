@@ -2,11 +2,12 @@
 
 use crate::cluster_info;
 use crate::poh_recorder;
+use crate::wire_format;
 use solana_ledger::block_error;
 use solana_ledger::blocktree;
 use solana_ledger::snapshot_utils;
-use solana_sdk::transaction;
-use std::any::Any;
+use solana_sdk::{clock::Slot, pubkey::Pubkey, transaction};
+use std::{any::Any, fmt, net::SocketAddr, time::Duration};
 
 #[derive(Debug)]
 pub enum Error {
@@ -30,13 +31,171 @@ pub enum Error {
     BlocktreeError(blocktree::BlocktreeError),
     FsExtra(fs_extra::error::Error),
     SnapshotError(snapshot_utils::SnapshotError),
+    RpcError(RpcError),
+    GossipError(GossipError),
+    StorageError(StorageError),
+    SetupError(SetupError),
+    ShutdownError(ShutdownError),
+    WireError(wire_format::WireError),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "solana error")
+/// A request to a cluster node's RPC service failed
+#[derive(Debug)]
+pub enum RpcError {
+    /// No node advertising an RPC address was found in gossip
+    NoPeers,
+    /// The request could not be completed
+    RequestFailed { request: String, reason: String },
+    /// The node answered with a response that could not be understood
+    InvalidResponse { request: String, reason: String },
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::NoPeers => write!(f, "no RPC peers found"),
+            RpcError::RequestFailed { request, reason } => {
+                write!(f, "RPC request {} failed: {}", request, reason)
+            }
+            RpcError::InvalidResponse { request, reason } => {
+                write!(f, "invalid response to RPC request {}: {}", request, reason)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GossipError {
+    /// The cluster could not be discovered through the entrypoint
+    DiscoveryFailed {
+        entrypoint: SocketAddr,
+        reason: String,
+    },
+}
+
+impl fmt::Display for GossipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GossipError::DiscoveryFailed { entrypoint, reason } => write!(
+                f,
+                "unable to discover the cluster from {}: {}",
+                entrypoint, reason
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// The account has no lamports to pay for storage transactions
+    AccountNotFunded(Pubkey),
+    /// Creating the storage account failed
+    AccountCreationFailed { account: Pubkey, reason: String },
+    /// Not every slot of the segment could be downloaded
+    SegmentIncomplete {
+        start_slot: Slot,
+        slots_per_segment: u64,
+    },
+    /// The saved archiver state doesn't match the storage account, cluster or segment on disk
+    StateMismatch(String),
+    /// Evicting every proven segment doesn't free enough space for `required` more bytes
+    QuotaExceeded {
+        quota: u64,
+        used: u64,
+        required: u64,
+    },
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::AccountNotFunded(account) => {
+                write!(f, "account {} has no balance", account)
+            }
+            StorageError::AccountCreationFailed { account, reason } => write!(
+                f,
+                "unable to create storage account {}: {}",
+                account, reason
+            ),
+            StorageError::SegmentIncomplete {
+                start_slot,
+                slots_per_segment,
+            } => write!(
+                f,
+                "unable to download the full segment of {} slots starting at {}",
+                slots_per_segment, start_slot
+            ),
+            StorageError::StateMismatch(reason) => {
+                write!(f, "saved archiver state can't be resumed: {}", reason)
+            }
+            StorageError::QuotaExceeded {
+                quota,
+                used,
+                required,
+            } => write!(
+                f,
+                "storage quota of {} bytes exceeded: {} bytes in use, {} more required",
+                quota, used, required
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SetupError {
+    /// The ledger at the given path could not be opened
+    OpenLedger(blocktree::BlocktreeError),
+    /// Exit was signalled before setup completed
+    Exited,
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupError::OpenLedger(err) => write!(f, "unable to open the ledger: {:?}", err),
+            SetupError::Exited => write!(f, "exit signalled during setup"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ShutdownError {
+    /// The named stages panicked, with their panic messages
+    StagesPanicked(Vec<(String, String)>),
+    /// Stages were still running when the shutdown deadline passed
+    DeadlineExceeded(Duration),
+}
+
+impl fmt::Display for ShutdownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShutdownError::StagesPanicked(stages) => {
+                write!(f, "stages panicked:")?;
+                for (stage, message) in stages {
+                    write!(f, " {} ({})", stage, message)?;
+                }
+                Ok(())
+            }
+            ShutdownError::DeadlineExceeded(deadline) => {
+                write!(f, "shutdown took longer than {:?}", deadline)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::IO(err) => write!(f, "{}", err),
+            Error::RpcError(err) => write!(f, "{}", err),
+            Error::GossipError(err) => write!(f, "{}", err),
+            Error::StorageError(err) => write!(f, "{}", err),
+            Error::SetupError(err) => write!(f, "{}", err),
+            Error::ShutdownError(err) => write!(f, "{}", err),
+            _ => write!(f, "solana error: {:?}", self),
+        }
     }
 }
 
@@ -137,12 +296,44 @@ impl std::convert::From<snapshot_utils::SnapshotError> for Error {
         Error::SnapshotError(e)
     }
 }
+impl std::convert::From<RpcError> for Error {
+    fn from(e: RpcError) -> Error {
+        Error::RpcError(e)
+    }
+}
+impl std::convert::From<GossipError> for Error {
+    fn from(e: GossipError) -> Error {
+        Error::GossipError(e)
+    }
+}
+impl std::convert::From<StorageError> for Error {
+    fn from(e: StorageError) -> Error {
+        Error::StorageError(e)
+    }
+}
+impl std::convert::From<SetupError> for Error {
+    fn from(e: SetupError) -> Error {
+        Error::SetupError(e)
+    }
+}
+impl std::convert::From<ShutdownError> for Error {
+    fn from(e: ShutdownError) -> Error {
+        Error::ShutdownError(e)
+    }
+}
+impl std::convert::From<wire_format::WireError> for Error {
+    fn from(e: wire_format::WireError) -> Error {
+        Error::WireError(e)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use crate::result::Error;
     use crate::result::Result;
+    use crate::result::{RpcError, StorageError};
     use serde_json;
+    use solana_sdk::pubkey::Pubkey;
     use std::io;
     use std::io::Write;
     use std::net::SocketAddr;
@@ -185,6 +376,23 @@ mod tests {
         assert_matches!(join_error(), Err(Error::JoinError(_)));
         let ioe = io::Error::new(io::ErrorKind::NotFound, "hi");
         assert_matches!(Error::from(ioe), Error::IO(_));
+        assert_matches!(Error::from(RpcError::NoPeers), Error::RpcError(_));
+    }
+    #[test]
+    fn display_test() {
+        let account = Pubkey::new_rand();
+        assert_eq!(
+            Error::from(StorageError::AccountNotFunded(account)).to_string(),
+            format!("account {} has no balance", account)
+        );
+        assert_eq!(
+            Error::from(RpcError::RequestFailed {
+                request: "GetSlotsPerSegment".to_string(),
+                reason: "timed out".to_string(),
+            })
+            .to_string(),
+            "RPC request GetSlotsPerSegment failed: timed out"
+        );
     }
     #[test]
     fn fmt_test() {
@@ -7,17 +7,17 @@ use crate::{
     repair_service::RepairStrategy,
     result::{Error, Result},
     streamer::PacketReceiver,
-    window_service::{should_retransmit_and_persist, WindowService},
+    window_service::{should_retransmit_and_persist, WindowService, DEFAULT_MAX_FUTURE_SHREDS},
 };
 use crossbeam_channel::Receiver as CrossbeamReceiver;
 use solana_ledger::{
     bank_forks::BankForks,
     blocktree::{Blocktree, CompletedSlotsReceiver},
     leader_schedule_cache::LeaderScheduleCache,
-    staking_utils,
 };
 use solana_measure::measure::Measure;
 use solana_metrics::inc_new_counter_error;
+use solana_runtime::epoch_stakes::EpochStakes;
 use solana_sdk::epoch_schedule::EpochSchedule;
 use std::{
     cmp,
@@ -61,11 +61,11 @@ fn retransmit(
     let r_bank = bank_forks.read().unwrap().working_bank();
     let bank_epoch = r_bank.get_leader_schedule_epoch(r_bank.slot());
     let mut peers_len = 0;
-    let stakes = staking_utils::staked_nodes_at_epoch(&r_bank, bank_epoch);
+    let epoch_stakes = r_bank.epoch_stakes(bank_epoch);
     let (peers, stakes_and_index) = cluster_info
         .read()
         .unwrap()
-        .sorted_retransmit_peers_and_stakes(stakes.as_ref());
+        .sorted_retransmit_peers_and_stakes(epoch_stakes.as_ref().map(EpochStakes::node_stakes));
     let me = cluster_info.read().unwrap().my_data().clone();
     let mut discard_total = 0;
     let mut repair_total = 0;
@@ -241,6 +241,7 @@ impl RetransmitStage {
             exit,
             repair_strategy,
             &leader_schedule_cache.clone(),
+            DEFAULT_MAX_FUTURE_SHREDS,
             move |id, shred, working_bank, last_root| {
                 let is_connected = cfg
                     .as_ref()
@@ -12,15 +12,20 @@ use bincode::serialize;
 use jsonrpc_core::{Error, Metadata, Result};
 use jsonrpc_derive::rpc;
 use solana_client::rpc_request::{
-    Response, RpcConfirmedBlock, RpcContactInfo, RpcEpochInfo, RpcResponseContext, RpcVersionInfo,
-    RpcVoteAccountInfo, RpcVoteAccountStatus,
+    Response, RpcAccountLockContention, RpcAccountReward, RpcConfirmedBlock, RpcContactInfo,
+    RpcDuplicateIdentity, RpcDuplicateIdentityNode, RpcEpochInfo, RpcEpochRewards, RpcFees,
+    RpcGossipStats, RpcResponseContext, RpcSegmentConfig, RpcStakeAccount, RpcStakeDelegation,
+    RpcStakeMeta, RpcVersionInfo, RpcVoteAccountInfo, RpcVoteAccountStatus,
 };
 use solana_drone::drone::request_airdrop_transaction;
 use solana_ledger::{bank_forks::BankForks, blocktree::Blocktree};
-use solana_runtime::bank::Bank;
+use solana_runtime::{
+    bank::{Bank, MAX_REPORTED_CONTENDED_ACCOUNTS},
+    storage_utils,
+};
 use solana_sdk::{
     account::Account,
-    clock::Slot,
+    clock::{Epoch, Slot},
     commitment_config::{CommitmentConfig, CommitmentLevel},
     epoch_schedule::EpochSchedule,
     fee_calculator::FeeCalculator,
@@ -28,10 +33,14 @@ use solana_sdk::{
     inflation::Inflation,
     pubkey::Pubkey,
     signature::Signature,
-    transaction::{self, Transaction},
+    sysvar::{self, stake_history::StakeHistory, Sysvar},
+    transaction::{self, SanitizedTransaction, Transaction},
 };
+use solana_stake_api::stake_state::{Meta as StakeMeta, StakeState};
 use solana_vote_api::vote_state::{VoteState, MAX_LOCKOUT_HISTORY};
 use std::{
+    collections::HashMap,
+    convert::TryFrom,
     net::{SocketAddr, UdpSocket},
     sync::{Arc, RwLock},
     thread::sleep,
@@ -40,6 +49,66 @@ use std::{
 
 type RpcResponse<T> = Result<Response<T>>;
 
+/// A vote account whose last vote is more than this many slots behind the bank is reported as
+/// delinquent by `getVoteAccounts`
+pub const DELINQUENT_VALIDATOR_SLOT_DISTANCE: u64 = MAX_LOCKOUT_HISTORY as u64;
+
+fn is_delinquent(last_vote: Slot, current_slot: Slot) -> bool {
+    if current_slot >= DELINQUENT_VALIDATOR_SLOT_DISTANCE {
+        last_vote <= current_slot - DELINQUENT_VALIDATOR_SLOT_DISTANCE
+    } else {
+        last_vote == 0
+    }
+}
+
+/// Parse a stake account for `getStakeAccount`, with the delegation broken down as of `epoch`.
+/// None if `account` isn't a stake account.
+fn parse_stake_account(
+    account: &Account,
+    epoch: Epoch,
+    stake_history: Option<&StakeHistory>,
+) -> Option<RpcStakeAccount> {
+    if account.owner != solana_stake_api::id() {
+        return None;
+    }
+    let rpc_meta = |meta: &StakeMeta| RpcStakeMeta {
+        rent_exempt_reserve: meta.rent_exempt_reserve,
+        staker: meta.authorized.staker.to_string(),
+        withdrawer: meta.authorized.withdrawer.to_string(),
+        lockup_slot: meta.lockup.slot,
+        custodian: meta.lockup.custodian.to_string(),
+    };
+    let (state, meta, delegation) = match StakeState::from(account)? {
+        StakeState::Uninitialized => ("uninitialized", None, None),
+        StakeState::Initialized(meta) => ("initialized", Some(rpc_meta(&meta)), None),
+        StakeState::Stake(meta, stake) => {
+            let (active_stake, activating_stake, deactivating_stake) =
+                stake.stake_activating_and_deactivating(epoch, stake_history);
+            let delegation = RpcStakeDelegation {
+                voter_pubkey: stake.voter_pubkey.to_string(),
+                stake: stake.stake,
+                activation_epoch: stake.activation_epoch,
+                deactivation_epoch: if stake.deactivation_epoch == std::u64::MAX {
+                    None
+                } else {
+                    Some(stake.deactivation_epoch)
+                },
+                active_stake,
+                activating_stake,
+                deactivating_stake,
+            };
+            ("delegated", Some(rpc_meta(&meta)), Some(delegation))
+        }
+        StakeState::RewardsPool => ("rewardsPool", None, None),
+    };
+    Some(RpcStakeAccount {
+        state: state.to_string(),
+        lamports: account.lamports,
+        meta,
+        delegation,
+    })
+}
+
 fn new_response<T>(bank: &Bank, value: T) -> RpcResponse<T> {
     let context = RpcResponseContext { slot: bank.slot() };
     Ok(Response { context, value })
@@ -115,6 +184,43 @@ impl JsonRpcRequestProcessor {
         }
     }
 
+    pub fn get_stake_account(
+        &self,
+        pubkey: Result<Pubkey>,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<Option<RpcStakeAccount>> {
+        let bank = &*self.bank(commitment);
+        match pubkey {
+            Ok(key) => {
+                let stake_history = bank
+                    .get_account(&sysvar::stake_history::id())
+                    .and_then(|account| StakeHistory::from_account(&account));
+                let stake_account = bank.get_account(&key).and_then(|account| {
+                    parse_stake_account(&account, bank.epoch(), stake_history.as_ref())
+                });
+                new_response(bank, stake_account)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_account_lock_contention(
+        &self,
+        limit: Option<usize>,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<Vec<RpcAccountLockContention>> {
+        let bank = &*self.bank(commitment);
+        let contention = bank
+            .account_lock_contention(limit.unwrap_or(MAX_REPORTED_CONTENDED_ACCOUNTS))
+            .into_iter()
+            .map(|(pubkey, count)| RpcAccountLockContention {
+                pubkey: pubkey.to_string(),
+                count,
+            })
+            .collect();
+        new_response(bank, contention)
+    }
+
     pub fn get_minimum_balance_for_rent_exemption(
         &self,
         data_len: usize,
@@ -142,6 +248,57 @@ impl JsonRpcRequestProcessor {
         Ok(self.bank(commitment).inflation())
     }
 
+    pub fn get_epoch_rewards(
+        &self,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Option<RpcEpochRewards>> {
+        let bank = self.bank(commitment);
+        let epoch_rewards = match bank.epoch_rewards() {
+            Some(epoch_rewards) => epoch_rewards,
+            None => return Ok(None),
+        };
+
+        let mut vote_rewards: HashMap<Pubkey, u64> = HashMap::new();
+        let mut stake_accounts = vec![];
+        for (stake_pubkey, voter_pubkey, voter_rewards, staker_rewards) in
+            bank.redeemable_stake_rewards(epoch_rewards.validator_point_value)
+        {
+            *vote_rewards.entry(voter_pubkey).or_insert(0) += voter_rewards;
+            stake_accounts.push(RpcAccountReward {
+                pubkey: stake_pubkey.to_string(),
+                lamports: staker_rewards,
+            });
+        }
+        let vote_accounts = vote_rewards
+            .into_iter()
+            .map(|(pubkey, lamports)| RpcAccountReward {
+                pubkey: pubkey.to_string(),
+                lamports,
+            })
+            .collect();
+        let storage_accounts = epoch_rewards
+            .storage_account_points
+            .iter()
+            .map(|(pubkey, points)| RpcAccountReward {
+                pubkey: pubkey.to_string(),
+                lamports: (*points as f64 * epoch_rewards.storage_point_value) as u64,
+            })
+            .collect();
+
+        Ok(Some(RpcEpochRewards {
+            epoch: epoch_rewards.epoch,
+            validator_rewards: epoch_rewards.validator_rewards,
+            validator_points: epoch_rewards.validator_points,
+            validator_point_value: epoch_rewards.validator_point_value,
+            storage_rewards: epoch_rewards.storage_rewards,
+            storage_points: epoch_rewards.storage_points,
+            storage_point_value: epoch_rewards.storage_point_value,
+            vote_accounts,
+            stake_accounts,
+            storage_accounts,
+        }))
+    }
+
     pub fn get_epoch_schedule(&self) -> Result<EpochSchedule> {
         // Since epoch schedule data comes from the genesis config, any commitment level should be
         // fine
@@ -169,6 +326,22 @@ impl JsonRpcRequestProcessor {
         new_response(bank, (blockhash.to_string(), fee_calculator))
     }
 
+    fn get_fees(&self, commitment: Option<CommitmentConfig>) -> RpcResponse<RpcFees> {
+        let bank = &*self.bank(commitment);
+        let (blockhash, fee_calculator) = bank.confirmed_last_blockhash();
+        let last_valid_slot = bank
+            .get_blockhash_last_valid_slot(&blockhash)
+            .expect("bank blockhash queue should contain blockhash");
+        new_response(
+            bank,
+            RpcFees {
+                blockhash: blockhash.to_string(),
+                fee_calculator,
+                last_valid_slot,
+            },
+        )
+    }
+
     pub fn confirm_transaction(
         &self,
         signature: Result<Signature>,
@@ -204,6 +377,14 @@ impl JsonRpcRequestProcessor {
             .get_signature_confirmation_status(&signature)
     }
 
+    pub fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+        commitment: Option<CommitmentConfig>,
+    ) -> Vec<Option<transaction::Result<()>>> {
+        self.bank(commitment).get_signature_statuses(signatures)
+    }
+
     fn get_slot(&self, commitment: Option<CommitmentConfig>) -> Result<u64> {
         Ok(self.bank(commitment).slot())
     }
@@ -255,11 +436,7 @@ impl JsonRpcRequestProcessor {
                 }
             })
             .partition(|vote_account_info| {
-                if bank.slot() >= MAX_LOCKOUT_HISTORY as u64 {
-                    vote_account_info.last_vote > bank.slot() - MAX_LOCKOUT_HISTORY as u64
-                } else {
-                    vote_account_info.last_vote > 0
-                }
+                !is_delinquent(vote_account_info.last_vote, bank.slot())
             });
         Ok(RpcVoteAccountStatus {
             current: current_vote_accounts,
@@ -271,23 +448,47 @@ impl JsonRpcRequestProcessor {
         Ok(self.storage_state.get_storage_turn_rate())
     }
 
-    fn get_storage_turn(&self) -> Result<(String, u64)> {
-        Ok((
-            self.storage_state.get_storage_blockhash().to_string(),
-            self.storage_state.get_slot(),
-        ))
+    fn get_storage_turn(&self, commitment: Option<CommitmentConfig>) -> Result<(String, u64)> {
+        let (turn_blockhash, turn_slot) =
+            storage_utils::committed_storage_turn(&self.bank(commitment)).unwrap_or_default();
+        Ok((turn_blockhash.to_string(), turn_slot))
     }
 
     fn get_slots_per_segment(&self, commitment: Option<CommitmentConfig>) -> Result<u64> {
         Ok(self.bank(commitment).slots_per_segment())
     }
 
+    fn get_segment_config(&self, commitment: Option<CommitmentConfig>) -> Result<RpcSegmentConfig> {
+        let bank = self.bank(commitment);
+        Ok(RpcSegmentConfig {
+            slots_per_segment: bank.slots_per_segment(),
+            epoch: bank.epoch(),
+        })
+    }
+
     fn get_storage_pubkeys_for_slot(&self, slot: Slot) -> Result<Vec<Pubkey>> {
         Ok(self
             .storage_state
             .get_pubkeys_for_slot(slot, &self.bank_forks))
     }
 
+    fn get_segment_leaders(
+        &self,
+        slot: Slot,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Vec<String>> {
+        let bank = self.bank(commitment);
+        let slots_per_segment = bank.slots_per_segment();
+        let segment_start = slot - slot % slots_per_segment;
+        (segment_start..segment_start.saturating_add(slots_per_segment))
+            .map(|slot| {
+                solana_ledger::leader_schedule_utils::slot_leader_at(slot, &bank)
+                    .map(|leader| leader.to_string())
+                    .ok_or_else(Error::invalid_request)
+            })
+            .collect()
+    }
+
     pub fn validator_exit(&self) -> Result<bool> {
         if self.config.enable_validator_exit {
             warn!("validator_exit request...");
@@ -309,6 +510,15 @@ impl JsonRpcRequestProcessor {
     pub fn get_confirmed_block(&self, slot: Slot) -> Result<Option<RpcConfirmedBlock>> {
         Ok(self.blocktree.get_confirmed_block(slot).ok())
     }
+
+    pub fn simulate_transaction(
+        &self,
+        tx: Transaction,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<transaction::Result<()>> {
+        let bank = &*self.bank(commitment);
+        new_response(bank, bank.simulate_transaction(tx))
+    }
 }
 
 fn get_tpu_addr(cluster_info: &Arc<RwLock<ClusterInfo>>) -> Result<SocketAddr> {
@@ -324,6 +534,25 @@ fn verify_signature(input: &str) -> Result<Signature> {
     input.parse().map_err(|_e| Error::invalid_request())
 }
 
+fn deserialize_transaction(method: &str, data: &[u8]) -> Result<Transaction> {
+    if data.len() >= PACKET_DATA_SIZE {
+        info!(
+            "{}: transaction too large: {} bytes (max: {} bytes)",
+            method,
+            data.len(),
+            PACKET_DATA_SIZE
+        );
+        return Err(Error::invalid_request());
+    }
+    bincode::config()
+        .limit(PACKET_DATA_SIZE as u64)
+        .deserialize(data)
+        .map_err(|err| {
+            info!("{}: deserialize error: {:?}", method, err);
+            Error::invalid_request()
+        })
+}
+
 #[derive(Clone)]
 pub struct Meta {
     pub request_processor: Arc<RwLock<JsonRpcRequestProcessor>>,
@@ -352,6 +581,14 @@ pub trait RpcSol {
         commitment: Option<CommitmentConfig>,
     ) -> RpcResponse<Option<Account>>;
 
+    #[rpc(meta, name = "getAccountLockContention")]
+    fn get_account_lock_contention(
+        &self,
+        meta: Self::Metadata,
+        limit: Option<usize>,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<Vec<RpcAccountLockContention>>;
+
     #[rpc(meta, name = "getProgramAccounts")]
     fn get_program_accounts(
         &self,
@@ -375,6 +612,13 @@ pub trait RpcSol {
         commitment: Option<CommitmentConfig>,
     ) -> Result<Inflation>;
 
+    #[rpc(meta, name = "getEpochRewards")]
+    fn get_epoch_rewards(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Option<RpcEpochRewards>>;
+
     #[rpc(meta, name = "getEpochSchedule")]
     fn get_epoch_schedule(&self, meta: Self::Metadata) -> Result<EpochSchedule>;
 
@@ -389,6 +633,9 @@ pub trait RpcSol {
     #[rpc(meta, name = "getClusterNodes")]
     fn get_cluster_nodes(&self, meta: Self::Metadata) -> Result<Vec<RpcContactInfo>>;
 
+    #[rpc(meta, name = "getGossipStats")]
+    fn get_gossip_stats(&self, meta: Self::Metadata) -> Result<RpcGossipStats>;
+
     #[rpc(meta, name = "getEpochInfo")]
     fn get_epoch_info(
         &self,
@@ -420,6 +667,13 @@ pub trait RpcSol {
         commitment: Option<CommitmentConfig>,
     ) -> RpcResponse<(String, FeeCalculator)>;
 
+    #[rpc(meta, name = "getFees")]
+    fn get_fees(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<RpcFees>;
+
     #[rpc(meta, name = "getSignatureStatus")]
     fn get_signature_status(
         &self,
@@ -428,6 +682,14 @@ pub trait RpcSol {
         commitment: Option<CommitmentConfig>,
     ) -> Result<Option<transaction::Result<()>>>;
 
+    #[rpc(meta, name = "getSignatureStatuses")]
+    fn get_signature_statuses(
+        &self,
+        meta: Self::Metadata,
+        signature_strs: Vec<String>,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Vec<Option<transaction::Result<()>>>>;
+
     #[rpc(meta, name = "getSlot")]
     fn get_slot(&self, meta: Self::Metadata, commitment: Option<CommitmentConfig>) -> Result<u64>;
 
@@ -457,6 +719,14 @@ pub trait RpcSol {
     #[rpc(meta, name = "sendTransaction")]
     fn send_transaction(&self, meta: Self::Metadata, data: Vec<u8>) -> Result<String>;
 
+    #[rpc(meta, name = "simulateTransaction")]
+    fn simulate_transaction(
+        &self,
+        meta: Self::Metadata,
+        data: Vec<u8>,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<transaction::Result<()>>;
+
     #[rpc(meta, name = "getSlotLeader")]
     fn get_slot_leader(
         &self,
@@ -464,6 +734,14 @@ pub trait RpcSol {
         commitment: Option<CommitmentConfig>,
     ) -> Result<String>;
 
+    #[rpc(meta, name = "getStakeAccount")]
+    fn get_stake_account(
+        &self,
+        meta: Self::Metadata,
+        pubkey_str: String,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<Option<RpcStakeAccount>>;
+
     #[rpc(meta, name = "getVoteAccounts")]
     fn get_vote_accounts(
         &self,
@@ -475,7 +753,11 @@ pub trait RpcSol {
     fn get_storage_turn_rate(&self, meta: Self::Metadata) -> Result<u64>;
 
     #[rpc(meta, name = "getStorageTurn")]
-    fn get_storage_turn(&self, meta: Self::Metadata) -> Result<(String, u64)>;
+    fn get_storage_turn(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<(String, u64)>;
 
     #[rpc(meta, name = "getSlotsPerSegment")]
     fn get_slots_per_segment(
@@ -484,9 +766,24 @@ pub trait RpcSol {
         commitment: Option<CommitmentConfig>,
     ) -> Result<u64>;
 
+    #[rpc(meta, name = "getSegmentConfig")]
+    fn get_segment_config(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcSegmentConfig>;
+
     #[rpc(meta, name = "getStoragePubkeysForSlot")]
     fn get_storage_pubkeys_for_slot(&self, meta: Self::Metadata, slot: u64) -> Result<Vec<Pubkey>>;
 
+    #[rpc(meta, name = "getSegmentLeaders")]
+    fn get_segment_leaders(
+        &self,
+        meta: Self::Metadata,
+        slot: Slot,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Vec<String>>;
+
     #[rpc(meta, name = "validatorExit")]
     fn validator_exit(&self, meta: Self::Metadata) -> Result<bool>;
 
@@ -510,7 +807,15 @@ pub trait RpcSol {
     fn get_version(&self, meta: Self::Metadata) -> Result<RpcVersionInfo>;
 
     #[rpc(meta, name = "setLogFilter")]
-    fn set_log_filter(&self, _meta: Self::Metadata, filter: String) -> Result<()>;
+    fn set_log_filter(
+        &self,
+        _meta: Self::Metadata,
+        filter: String,
+        duration_secs: Option<u64>,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "getLogFilter")]
+    fn get_log_filter(&self, _meta: Self::Metadata) -> Result<String>;
 
     #[rpc(meta, name = "getRpcConfirmedBlock")]
     fn get_confirmed_block(
@@ -552,6 +857,18 @@ impl RpcSol for RpcSolImpl {
             .get_account_info(pubkey, commitment)
     }
 
+    fn get_account_lock_contention(
+        &self,
+        meta: Self::Metadata,
+        limit: Option<usize>,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<Vec<RpcAccountLockContention>> {
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_account_lock_contention(limit, commitment)
+    }
+
     fn get_minimum_balance_for_rent_exemption(
         &self,
         meta: Self::Metadata,
@@ -599,6 +916,18 @@ impl RpcSol for RpcSolImpl {
             .unwrap())
     }
 
+    fn get_epoch_rewards(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Option<RpcEpochRewards>> {
+        debug!("get_epoch_rewards rpc request received");
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_epoch_rewards(commitment)
+    }
+
     fn get_epoch_schedule(&self, meta: Self::Metadata) -> Result<EpochSchedule> {
         debug!("get_epoch_schedule rpc request received");
         Ok(meta
@@ -625,6 +954,7 @@ impl RpcSol for RpcSolImpl {
 
     fn get_cluster_nodes(&self, meta: Self::Metadata) -> Result<Vec<RpcContactInfo>> {
         let cluster_info = meta.cluster_info.read().unwrap();
+        let my_pubkey = cluster_info.id();
         fn valid_address_or_none(addr: &SocketAddr) -> Option<SocketAddr> {
             if ContactInfo::is_valid_address(addr) {
                 Some(*addr)
@@ -640,8 +970,21 @@ impl RpcSol for RpcSolImpl {
                     Some(RpcContactInfo {
                         pubkey: contact_info.id.to_string(),
                         gossip: Some(contact_info.gossip),
+                        tvu: valid_address_or_none(&contact_info.tvu),
+                        tvu_forwards: valid_address_or_none(&contact_info.tvu_forwards),
+                        repair: valid_address_or_none(&contact_info.repair),
                         tpu: valid_address_or_none(&contact_info.tpu),
+                        tpu_forwards: valid_address_or_none(&contact_info.tpu_forwards),
+                        storage: valid_address_or_none(&contact_info.storage_addr),
                         rpc: valid_address_or_none(&contact_info.rpc),
+                        rpc_pubsub: valid_address_or_none(&contact_info.rpc_pubsub),
+                        shred_version: contact_info.shred_version,
+                        // gossip doesn't carry software versions, so only ours is known
+                        version: if contact_info.id == my_pubkey {
+                            Some(solana_clap_utils::version!().to_string())
+                        } else {
+                            None
+                        },
                     })
                 } else {
                     None // Exclude spy nodes
@@ -650,6 +993,32 @@ impl RpcSol for RpcSolImpl {
             .collect())
     }
 
+    fn get_gossip_stats(&self, meta: Self::Metadata) -> Result<RpcGossipStats> {
+        let cluster_info = meta.cluster_info.read().unwrap();
+        let mut duplicate_identities: Vec<_> = cluster_info
+            .duplicate_identities()
+            .into_iter()
+            .map(|(pubkey, sightings)| RpcDuplicateIdentity {
+                pubkey: pubkey.to_string(),
+                nodes: sightings
+                    .into_iter()
+                    .map(|sighting| RpcDuplicateIdentityNode {
+                        gossip: sighting.contact_info.gossip,
+                        tpu: sighting.contact_info.tpu,
+                        tvu: sighting.contact_info.tvu,
+                        first_wallclock: sighting.first_wallclock,
+                        last_wallclock: sighting.last_wallclock,
+                    })
+                    .collect(),
+            })
+            .collect();
+        duplicate_identities.sort_by(|a, b| a.pubkey.cmp(&b.pubkey));
+        Ok(RpcGossipStats {
+            num_nodes: cluster_info.all_peers().len(),
+            duplicate_identities,
+        })
+    }
+
     fn get_epoch_info(
         &self,
         meta: Self::Metadata,
@@ -715,6 +1084,15 @@ impl RpcSol for RpcSolImpl {
             .get_recent_blockhash(commitment)
     }
 
+    fn get_fees(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<RpcFees> {
+        debug!("get_fees rpc request received");
+        meta.request_processor.read().unwrap().get_fees(commitment)
+    }
+
     fn get_signature_status(
         &self,
         meta: Self::Metadata,
@@ -725,6 +1103,27 @@ impl RpcSol for RpcSolImpl {
             .map(|res| res.map(|x| x.1))
     }
 
+    fn get_signature_statuses(
+        &self,
+        meta: Self::Metadata,
+        signature_strs: Vec<String>,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Vec<Option<transaction::Result<()>>>> {
+        debug!(
+            "get_signature_statuses rpc request received: {:?}",
+            signature_strs.len()
+        );
+        let signatures = signature_strs
+            .iter()
+            .map(|signature_str| verify_signature(signature_str))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(meta
+            .request_processor
+            .read()
+            .unwrap()
+            .get_signature_statuses(&signatures, commitment))
+    }
+
     fn get_slot(&self, meta: Self::Metadata, commitment: Option<CommitmentConfig>) -> Result<u64> {
         meta.request_processor.read().unwrap().get_slot(commitment)
     }
@@ -858,21 +1257,16 @@ impl RpcSol for RpcSolImpl {
     }
 
     fn send_transaction(&self, meta: Self::Metadata, data: Vec<u8>) -> Result<String> {
-        if data.len() >= PACKET_DATA_SIZE {
-            info!(
-                "send_transaction: transaction too large: {} bytes (max: {} bytes)",
-                data.len(),
-                PACKET_DATA_SIZE
-            );
-            return Err(Error::invalid_request());
-        }
-        let tx: Transaction = bincode::config()
-            .limit(PACKET_DATA_SIZE as u64)
-            .deserialize(&data)
-            .map_err(|err| {
-                info!("send_transaction: deserialize error: {:?}", err);
-                Error::invalid_request()
-            })?;
+        let tx = deserialize_transaction("send_transaction", &data)?;
+        let tx = SanitizedTransaction::try_from(tx).map_err(|err| {
+            info!("send_transaction: sanitize error: {:?}", err);
+            Error::invalid_request()
+        })?;
+        let signature = tx
+            .signatures
+            .first()
+            .ok_or_else(Error::invalid_request)?
+            .to_string();
 
         let transactions_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
         let tpu_addr = get_tpu_addr(&meta.cluster_info)?;
@@ -883,7 +1277,6 @@ impl RpcSol for RpcSolImpl {
                 info!("send_transaction: send_to error: {:?}", err);
                 Error::internal_error()
             })?;
-        let signature = tx.signatures[0].to_string();
         trace!(
             "send_transaction: sent {} bytes, signature={}",
             data.len(),
@@ -892,6 +1285,19 @@ impl RpcSol for RpcSolImpl {
         Ok(signature)
     }
 
+    fn simulate_transaction(
+        &self,
+        meta: Self::Metadata,
+        data: Vec<u8>,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<transaction::Result<()>> {
+        let tx = deserialize_transaction("simulate_transaction", &data)?;
+        meta.request_processor
+            .read()
+            .unwrap()
+            .simulate_transaction(tx, commitment)
+    }
+
     fn get_slot_leader(
         &self,
         meta: Self::Metadata,
@@ -903,6 +1309,20 @@ impl RpcSol for RpcSolImpl {
             .get_slot_leader(commitment)
     }
 
+    fn get_stake_account(
+        &self,
+        meta: Self::Metadata,
+        pubkey_str: String,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<Option<RpcStakeAccount>> {
+        debug!("get_stake_account rpc request received: {:?}", pubkey_str);
+        let pubkey = verify_pubkey(pubkey_str);
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_stake_account(pubkey, commitment)
+    }
+
     fn get_vote_accounts(
         &self,
         meta: Self::Metadata,
@@ -921,8 +1341,15 @@ impl RpcSol for RpcSolImpl {
             .get_storage_turn_rate()
     }
 
-    fn get_storage_turn(&self, meta: Self::Metadata) -> Result<(String, u64)> {
-        meta.request_processor.read().unwrap().get_storage_turn()
+    fn get_storage_turn(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<(String, u64)> {
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_storage_turn(commitment)
     }
 
     fn get_slots_per_segment(
@@ -936,6 +1363,17 @@ impl RpcSol for RpcSolImpl {
             .get_slots_per_segment(commitment)
     }
 
+    fn get_segment_config(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcSegmentConfig> {
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_segment_config(commitment)
+    }
+
     fn get_storage_pubkeys_for_slot(
         &self,
         meta: Self::Metadata,
@@ -947,6 +1385,18 @@ impl RpcSol for RpcSolImpl {
             .get_storage_pubkeys_for_slot(slot)
     }
 
+    fn get_segment_leaders(
+        &self,
+        meta: Self::Metadata,
+        slot: Slot,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Vec<String>> {
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_segment_leaders(slot, commitment)
+    }
+
     fn validator_exit(&self, meta: Self::Metadata) -> Result<bool> {
         meta.request_processor.read().unwrap().validator_exit()
     }
@@ -957,11 +1407,24 @@ impl RpcSol for RpcSolImpl {
         })
     }
 
-    fn set_log_filter(&self, _meta: Self::Metadata, filter: String) -> Result<()> {
-        solana_logger::setup_with_filter(&filter);
+    fn set_log_filter(
+        &self,
+        _meta: Self::Metadata,
+        filter: String,
+        duration_secs: Option<u64>,
+    ) -> Result<()> {
+        let filter = match duration_secs {
+            Some(secs) => solana_logger::update_filter_for(&filter, Duration::from_secs(secs)),
+            None => solana_logger::update_filter(&filter),
+        };
+        info!("set_log_filter: {}", filter);
         Ok(())
     }
 
+    fn get_log_filter(&self, _meta: Self::Metadata) -> Result<String> {
+        Ok(solana_logger::get_filter())
+    }
+
     fn get_confirmed_block(
         &self,
         meta: Self::Metadata,
@@ -979,16 +1442,19 @@ pub mod tests {
     use super::*;
     use crate::{
         contact_info::ContactInfo,
+        crds_value::{CrdsData, CrdsValue},
         genesis_utils::{create_genesis_config, GenesisConfigInfo},
     };
     use jsonrpc_core::{MetaIoHandler, Output, Response, Value};
     use solana_ledger::get_tmp_ledger_path;
     use solana_sdk::{
+        clock::MAX_PROCESSING_AGE,
         fee_calculator::DEFAULT_BURN_PERCENT,
         hash::{hash, Hash},
         instruction::InstructionError,
         signature::{Keypair, KeypairUtil},
         system_transaction,
+        timing::timestamp,
         transaction::TransactionError,
     };
     use std::{
@@ -1128,6 +1594,90 @@ pub mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_simulate_transaction() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler {
+            io,
+            meta,
+            bank,
+            blockhash,
+            alice,
+            ..
+        } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let tx = system_transaction::transfer(&alice, &bob_pubkey, 1, blockhash);
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"simulateTransaction","params":[{:?}]}}"#,
+            serialize(&tx).unwrap()
+        );
+        let res = io.handle_request_sync(&req, meta.clone());
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": {
+                "context":{"slot":0},
+                "value":{"Ok":null},
+                },
+            "id": 1,
+        });
+        let result = serde_json::from_str::<Value>(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+        assert_eq!(bank.get_balance(&bob_pubkey), 20);
+
+        let mut unsigned_tx = tx;
+        unsigned_tx.signatures.clear();
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"simulateTransaction","params":[{:?}]}}"#,
+            serialize(&unsigned_tx).unwrap()
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": {
+                "context":{"slot":0},
+                "value":{"Err":"SanitizeFailure"},
+                },
+            "id": 1,
+        });
+        let result = serde_json::from_str::<Value>(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_rpc_get_account_lock_contention() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler {
+            io,
+            meta,
+            bank,
+            blockhash,
+            alice,
+            ..
+        } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let txs = vec![
+            system_transaction::transfer(&alice, &bob_pubkey, 1, blockhash),
+            system_transaction::transfer(&alice, &Pubkey::new_rand(), 1, blockhash),
+        ];
+        drop(bank.prepare_batch(&txs, None));
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getAccountLockContention","params":[1]}"#;
+        let res = io.handle_request_sync(&req, meta);
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": {
+                "context":{"slot":0},
+                "value":[{"pubkey": alice.pubkey().to_string(), "count": 1}],
+                },
+            "id": 1,
+        });
+        let result = serde_json::from_str::<Value>(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
     #[test]
     fn test_rpc_get_cluster_nodes() {
         let bob_pubkey = Pubkey::new_rand();
@@ -1144,7 +1694,7 @@ pub mod tests {
             .expect("actual response deserialization");
 
         let expected = format!(
-            r#"{{"jsonrpc":"2.0","result":[{{"pubkey": "{}", "gossip": "127.0.0.1:1235", "tpu": "127.0.0.1:1234", "rpc": "127.0.0.1:8899"}}],"id":1}}"#,
+            r#"{{"jsonrpc":"2.0","result":[{{"pubkey": "{}", "gossip": "127.0.0.1:1235", "tvu": "127.0.0.1:1236", "tvuForwards": "127.0.0.1:1238", "repair": "127.0.0.1:1239", "tpu": "127.0.0.1:1234", "tpuForwards": "127.0.0.1:1237", "storage": null, "rpc": "127.0.0.1:8899", "rpcPubsub": "127.0.0.1:8900", "shredVersion": 0, "version": null}}],"id":1}}"#,
             leader_pubkey,
         );
 
@@ -1153,6 +1703,104 @@ pub mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_get_cluster_nodes_reports_own_version() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let my_pubkey = Pubkey::new_rand();
+        let mut contact_info = ContactInfo::new_localhost(&my_pubkey, 0);
+        contact_info.shred_version = 42;
+        let meta = Meta {
+            cluster_info: Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+                contact_info,
+            ))),
+            ..meta
+        };
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getClusterNodes"}"#;
+        let res = io.handle_request_sync(&req, meta);
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let nodes: Vec<RpcContactInfo> = serde_json::from_value(result["result"].clone()).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].pubkey, my_pubkey.to_string());
+        assert_eq!(nodes[0].shred_version, 42);
+        assert_eq!(nodes[0].tvu, Some(socketaddr!("127.0.0.1:1236")));
+        assert_eq!(
+            nodes[0].version,
+            Some(solana_clap_utils::version!().to_string())
+        );
+    }
+
+    #[test]
+    fn test_rpc_get_gossip_stats() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getGossipStats"}"#;
+        let res = io.handle_request_sync(&req, meta.clone());
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": {"numNodes": 1, "duplicateIdentities": []},
+            "id": 1,
+        });
+        let result = serde_json::from_str::<Value>(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+
+        // two nodes gossiping the same identity from different addresses
+        let from = Pubkey::new_rand();
+        let values = vec![
+            (1, "10.0.0.1:8001"),
+            (2, "10.0.0.2:8001"),
+            (3, "10.0.0.1:8001"),
+        ]
+        .into_iter()
+        .map(|(wallclock, gossip)| {
+            let mut contact_info = ContactInfo::new_localhost(&bob_pubkey, wallclock);
+            contact_info.gossip = gossip.parse().unwrap();
+            CrdsValue::new_unsigned(CrdsData::ContactInfo(contact_info))
+        })
+        .collect();
+        meta.cluster_info
+            .write()
+            .unwrap()
+            .gossip
+            .process_pull_response(&from, values, timestamp());
+
+        let res = io.handle_request_sync(&req, meta);
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": {
+                "numNodes": 2,
+                "duplicateIdentities": [{
+                    "pubkey": bob_pubkey.to_string(),
+                    "nodes": [
+                        {
+                            "gossip": "10.0.0.1:8001",
+                            "tpu": "127.0.0.1:1234",
+                            "tvu": "127.0.0.1:1236",
+                            "firstWallclock": 1,
+                            "lastWallclock": 3,
+                        },
+                        {
+                            "gossip": "10.0.0.2:8001",
+                            "tpu": "127.0.0.1:1234",
+                            "tvu": "127.0.0.1:1236",
+                            "firstWallclock": 2,
+                            "lastWallclock": 2,
+                        },
+                    ],
+                }],
+            },
+            "id": 1,
+        });
+        let result = serde_json::from_str::<Value>(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
     #[test]
     fn test_rpc_get_slot_leader() {
         let bob_pubkey = Pubkey::new_rand();
@@ -1173,6 +1821,42 @@ pub mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_get_segment_leaders() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler {
+            io,
+            meta,
+            bank,
+            leader_pubkey,
+            ..
+        } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let slots_per_segment = bank.slots_per_segment();
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getSegmentLeaders","params":[{}]}}"#,
+            slots_per_segment + 1
+        );
+        let res = io.handle_request_sync(&req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let leaders: Vec<String> = serde_json::from_value(result["result"].clone()).unwrap();
+        assert_eq!(leaders.len() as u64, slots_per_segment);
+        assert!(leaders
+            .iter()
+            .all(|leader| *leader == leader_pubkey.to_string()));
+
+        // the leader schedule isn't known that far ahead
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getSegmentLeaders","params":[{}]}}"#,
+            1_000_000_000
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert!(result.get("error").is_some());
+    }
+
     #[test]
     fn test_rpc_get_tx_count() {
         let bob_pubkey = Pubkey::new_rand();
@@ -1213,6 +1897,43 @@ pub mod tests {
         assert!(supply >= TEST_MINT_LAMPORTS);
     }
 
+    #[test]
+    fn test_is_delinquent() {
+        assert!(is_delinquent(0, 0));
+        assert!(!is_delinquent(1, 1));
+        assert!(!is_delinquent(1, DELINQUENT_VALIDATOR_SLOT_DISTANCE));
+        assert!(is_delinquent(1, DELINQUENT_VALIDATOR_SLOT_DISTANCE + 1));
+        assert!(!is_delinquent(2, DELINQUENT_VALIDATOR_SLOT_DISTANCE + 1));
+    }
+
+    #[test]
+    fn test_rpc_get_vote_accounts() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler {
+            io,
+            meta,
+            leader_pubkey,
+            ..
+        } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let req = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"getVoteAccounts"}}"#);
+        let res = io.handle_request_sync(&req, meta);
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let vote_account_status: RpcVoteAccountStatus =
+            serde_json::from_value(result["result"].clone()).unwrap();
+
+        // the bootstrap leader hasn't voted yet
+        assert!(vote_account_status.current.is_empty());
+        assert_eq!(vote_account_status.delinquent.len(), 1);
+        let vote_account_info = &vote_account_status.delinquent[0];
+        assert_eq!(vote_account_info.node_pubkey, leader_pubkey.to_string());
+        assert_eq!(vote_account_info.last_vote, 0);
+        assert_eq!(vote_account_info.root_slot, 0);
+        assert!(vote_account_info.activated_stake > 0);
+        assert!(vote_account_info.epoch_vote_account);
+    }
+
     #[test]
     fn test_rpc_get_minimum_balance_for_rent_exemption() {
         let bob_pubkey = Pubkey::new_rand();
@@ -1266,6 +1987,61 @@ pub mod tests {
         assert_eq!(inflation, bank.inflation());
     }
 
+    #[test]
+    fn test_rpc_get_epoch_rewards() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler { io, meta, bank, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+        assert!(bank.epoch_rewards().is_none());
+
+        // no epoch boundary crossed yet
+        let req = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"getEpochRewards"}}"#);
+        let rep = io.handle_request_sync(&req, meta);
+        let expected = r#"{"jsonrpc":"2.0","result":null,"id":1}"#;
+        let expected: Response =
+            serde_json::from_str(&expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&rep.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_rpc_request_processor_get_epoch_rewards() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let validator_exit = create_validator_exit(&exit);
+        let (bank_forks, _alice) = new_bank_forks();
+        let bank = bank_forks.read().unwrap().working_bank();
+        let bank1 = bank_forks.write().unwrap().insert(Bank::new_from_parent(
+            &bank,
+            &Pubkey::default(),
+            bank.get_slots_in_epoch(bank.epoch()) + 1,
+        ));
+        let block_commitment_cache = Arc::new(RwLock::new(BlockCommitmentCache::default()));
+        let ledger_path = get_tmp_ledger_path!();
+        let blocktree = Blocktree::open(&ledger_path).unwrap();
+        let request_processor = JsonRpcRequestProcessor::new(
+            JsonRpcConfig::default(),
+            bank_forks,
+            block_commitment_cache,
+            Arc::new(blocktree),
+            StorageState::default(),
+            &validator_exit,
+        );
+
+        let epoch_rewards = request_processor
+            .get_epoch_rewards(Some(CommitmentConfig::recent()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(epoch_rewards.epoch, bank.epoch());
+        assert_eq!(
+            epoch_rewards.validator_point_value,
+            bank1.epoch_rewards().unwrap().validator_point_value
+        );
+        assert_eq!(
+            epoch_rewards.storage_accounts.len(),
+            bank1.epoch_rewards().unwrap().storage_account_points.len()
+        );
+    }
+
     #[test]
     fn test_rpc_get_epoch_schedule() {
         let bob_pubkey = Pubkey::new_rand();
@@ -1319,6 +2095,117 @@ pub mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_get_stake_account() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler { io, meta, bank, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let staker = Pubkey::new_rand();
+        let voter_pubkey = Pubkey::new_rand();
+        let stake_pubkey = Pubkey::new_rand();
+        let stake_state = StakeState::Stake(
+            StakeMeta::auto(&staker),
+            solana_stake_api::stake_state::Stake {
+                voter_pubkey,
+                stake: 42,
+                activation_epoch: std::u64::MAX,
+                ..solana_stake_api::stake_state::Stake::default()
+            },
+        );
+        let stake_account = Account::new_data_with_space(
+            50,
+            &stake_state,
+            std::mem::size_of::<StakeState>(),
+            &solana_stake_api::id(),
+        )
+        .unwrap();
+        bank.store_account(&stake_pubkey, &stake_account);
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getStakeAccount","params":["{}"]}}"#,
+            stake_pubkey
+        );
+        let res = io.handle_request_sync(&req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let stake_account: RpcStakeAccount =
+            serde_json::from_value(result["result"]["value"].clone()).unwrap();
+        assert_eq!(stake_account.state, "delegated");
+        assert_eq!(stake_account.lamports, 50);
+        assert_eq!(stake_account.meta.unwrap().staker, staker.to_string());
+        assert_eq!(
+            stake_account.delegation,
+            Some(RpcStakeDelegation {
+                voter_pubkey: voter_pubkey.to_string(),
+                stake: 42,
+                activation_epoch: std::u64::MAX,
+                deactivation_epoch: None,
+                active_stake: 42,
+                activating_stake: 0,
+                deactivating_stake: 0,
+            })
+        );
+
+        // accounts the stake program doesn't own aren't stake accounts
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getStakeAccount","params":["{}"]}}"#,
+            bob_pubkey
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(result["result"]["value"], Value::Null);
+    }
+
+    #[test]
+    fn test_parse_stake_account() {
+        let meta = StakeMeta::auto(&Pubkey::new_rand());
+        let stake = solana_stake_api::stake_state::Stake {
+            voter_pubkey: Pubkey::new_rand(),
+            stake: 42,
+            activation_epoch: std::u64::MAX,
+            deactivation_epoch: 3,
+            ..solana_stake_api::stake_state::Stake::default()
+        };
+        let new_stake_account = |stake_state: &StakeState| {
+            Account::new_data_with_space(
+                50,
+                stake_state,
+                std::mem::size_of::<StakeState>(),
+                &solana_stake_api::id(),
+            )
+            .unwrap()
+        };
+
+        let account = new_stake_account(&StakeState::Initialized(meta));
+        let stake_account = parse_stake_account(&account, 0, None).unwrap();
+        assert_eq!(stake_account.state, "initialized");
+        assert_eq!(
+            stake_account.meta.unwrap().rent_exempt_reserve,
+            meta.rent_exempt_reserve
+        );
+        assert_eq!(stake_account.delegation, None);
+
+        // a deactivating stake, e.g. the split-off part of a partial deactivation
+        let account = new_stake_account(&StakeState::Stake(meta, stake));
+        let delegation = parse_stake_account(&account, 3, None)
+            .unwrap()
+            .delegation
+            .unwrap();
+        assert_eq!(delegation.deactivation_epoch, Some(3));
+        assert_eq!(delegation.active_stake, 42);
+        assert_eq!(delegation.deactivating_stake, 42);
+
+        let account = new_stake_account(&StakeState::Uninitialized);
+        let stake_account = parse_stake_account(&account, 0, None).unwrap();
+        assert_eq!(stake_account.state, "uninitialized");
+        assert_eq!(stake_account.meta, None);
+
+        let mut account = new_stake_account(&StakeState::Initialized(meta));
+        account.owner = Pubkey::new_rand();
+        assert_eq!(parse_stake_account(&account, 0, None), None);
+    }
+
     #[test]
     fn test_rpc_get_program_accounts() {
         let bob = Keypair::new();
@@ -1462,6 +2349,59 @@ pub mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_get_signature_statuses() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler {
+            io,
+            meta,
+            blockhash,
+            alice,
+            ..
+        } = start_rpc_handler_with_tx(&bob_pubkey);
+        let confirmed_tx = system_transaction::transfer(&alice, &bob_pubkey, 20, blockhash);
+        let unprocessed_tx = system_transaction::transfer(&alice, &bob_pubkey, 10, blockhash);
+        let failed_tx = system_transaction::transfer(&alice, &alice.pubkey(), 20, blockhash);
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getSignatureStatuses","params":[["{}","{}","{}"]]}}"#,
+            confirmed_tx.signatures[0], unprocessed_tx.signatures[0], failed_tx.signatures[0]
+        );
+        let res = io.handle_request_sync(&req, meta.clone());
+        let expected_res: Vec<Option<transaction::Result<()>>> = vec![
+            Some(Ok(())),
+            None,
+            Some(Err(TransactionError::InstructionError(
+                0,
+                InstructionError::DuplicateAccountIndex,
+            ))),
+        ];
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": expected_res,
+            "id": 1
+        });
+        let expected: Response =
+            serde_json::from_value(expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+
+        // A single malformed signature fails the whole request
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getSignatureStatuses","params":[["{}","garbage"]]}}"#,
+            confirmed_tx.signatures[0]
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let expected =
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid request"},"id":1}"#;
+        let expected: Response =
+            serde_json::from_str(expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
     #[test]
     fn test_rpc_get_recent_blockhash() {
         let bob_pubkey = Pubkey::new_rand();
@@ -1495,6 +2435,70 @@ pub mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_get_fees() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler {
+            io,
+            meta,
+            blockhash,
+            ..
+        } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let req = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"getFees"}}"#);
+        let res = io.handle_request_sync(&req, meta);
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": {
+            "context":{"slot":0},
+            "value":{
+                "blockhash": blockhash.to_string(),
+                "feeCalculator": {
+                    "burnPercent": DEFAULT_BURN_PERCENT,
+                    "lamportsPerSignature": 0,
+                    "maxLamportsPerSignature": 0,
+                    "minLamportsPerSignature": 0,
+                    "targetLamportsPerSignature": 0,
+                    "targetSignaturesPerSlot": 0
+                },
+                "lastValidSlot": MAX_PROCESSING_AGE
+            }},
+            "id": 1
+        });
+        let expected: Response =
+            serde_json::from_value(expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_rpc_get_segment_config() {
+        let bob_pubkey = Pubkey::new_rand();
+        let RpcHandler { io, meta, bank, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getSegmentConfig"}"#;
+        let res = io.handle_request_sync(&req, meta);
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let segment_config: RpcSegmentConfig = if let Response::Single(res) = result {
+            if let Output::Success(res) = res {
+                serde_json::from_value(res.result).unwrap()
+            } else {
+                panic!("Expected success");
+            }
+        } else {
+            panic!("Expected single response");
+        };
+        assert_eq!(
+            segment_config,
+            RpcSegmentConfig {
+                slots_per_segment: bank.slots_per_segment(),
+                epoch: bank.epoch(),
+            }
+        );
+    }
+
     #[test]
     fn test_rpc_fail_request_airdrop() {
         let bob_pubkey = Pubkey::new_rand();
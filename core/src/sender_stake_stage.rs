@@ -0,0 +1,120 @@
+//! The `sender_stake_stage` sits between signature verification and banking. It
+//! annotates each verified packet with the epoch stake of its fee payer so banking
+//! can prioritize and shed load by stake without deserializing the packets again.
+
+use crate::packet::Packets;
+use crossbeam_channel::{
+    Receiver as CrossbeamReceiver, RecvTimeoutError, Sender as CrossbeamSender,
+};
+use solana_ledger::bank_forks::BankForks;
+use solana_metrics::inc_new_counter_info;
+use solana_perf::sigverify::get_packet_fee_payer;
+use solana_runtime::epoch_stakes::EpochStakes;
+use std::sync::{Arc, RwLock};
+use std::thread::{self, Builder, JoinHandle};
+use std::time::Duration;
+
+pub struct SenderStakeStage {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl SenderStakeStage {
+    pub fn new(
+        verified_receiver: CrossbeamReceiver<Vec<Packets>>,
+        annotated_sender: CrossbeamSender<Vec<Packets>>,
+        bank_forks: &Arc<RwLock<BankForks>>,
+    ) -> Self {
+        let bank_forks = bank_forks.clone();
+        let thread_hdl = Builder::new()
+            .name("solana-sender-stake".to_string())
+            .spawn(move || loop {
+                let mut batches = match verified_receiver.recv_timeout(Duration::from_secs(1)) {
+                    Ok(batches) => batches,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                let bank = bank_forks.read().unwrap().working_bank();
+                let epoch_stakes = bank.epoch_stakes(bank.epoch()).unwrap_or_default();
+                let num_staked = annotate_sender_stakes(&mut batches, &epoch_stakes);
+                inc_new_counter_info!("sender_stake_stage-staked_packets", num_staked);
+                if annotated_sender.send(batches).is_err() {
+                    break;
+                }
+            })
+            .unwrap();
+        Self { thread_hdl }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+/// Set `sender_stake` of every packet that passed signature verification, returns the number
+/// of packets sent by staked nodes
+pub fn annotate_sender_stakes(batches: &mut [Packets], epoch_stakes: &EpochStakes) -> usize {
+    let mut num_staked = 0;
+    for packet in batches
+        .iter_mut()
+        .flat_map(|batch| batch.packets.iter_mut())
+    {
+        packet.meta.sender_stake = if packet.meta.discard {
+            None
+        } else {
+            get_packet_fee_payer(packet).map(|fee_payer| epoch_stakes.node_stake(&fee_payer))
+        };
+        if packet.meta.sender_stake.unwrap_or(0) > 0 {
+            num_staked += 1;
+        }
+    }
+    num_staked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{to_packets, Packet};
+    use solana_ledger::genesis_utils::{create_genesis_config_with_leader, GenesisConfigInfo};
+    use solana_runtime::bank::Bank;
+    use solana_sdk::{
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::{Keypair, KeypairUtil},
+        system_transaction,
+    };
+
+    #[test]
+    fn test_annotate_sender_stakes() {
+        let leader_keypair = Keypair::new();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config_with_leader(10_000, &leader_keypair.pubkey(), 100);
+        let bank = Bank::new(&genesis_config);
+        let epoch_stakes = bank.epoch_stakes(bank.epoch()).unwrap();
+        let leader_stake = epoch_stakes.node_stake(&leader_keypair.pubkey());
+        assert!(leader_stake > 0);
+
+        let transfer = |from: &Keypair| {
+            system_transaction::transfer(from, &Pubkey::new_rand(), 1, Hash::default())
+        };
+        let mut batches = to_packets(&[
+            transfer(&leader_keypair),
+            transfer(&mint_keypair),
+            transfer(&leader_keypair),
+        ]);
+        // a stale annotation is overwritten
+        batches[0].packets[1].meta.sender_stake = Some(5);
+        batches[0].packets[2].meta.discard = true;
+        batches[0].packets.push(Packet::default());
+
+        assert_eq!(annotate_sender_stakes(&mut batches, &epoch_stakes), 1);
+        let stakes: Vec<_> = batches[0]
+            .packets
+            .iter()
+            .map(|packet| packet.meta.sender_stake)
+            .collect();
+        assert_eq!(stakes, vec![Some(leader_stake), Some(0), None, None]);
+    }
+}
@@ -1,30 +1,169 @@
 //! The `shred_fetch_stage` pulls shreds from UDP sockets and sends it to a channel.
 
-use crate::packet::{Packet, PacketsRecycler};
+use crate::packet::{Packet, Packets, PacketsRecycler};
 use crate::streamer::{self, PacketReceiver, PacketSender};
+use solana_ledger::shred::{
+    CODING_SHRED, DATA_SHRED, SIZE_OF_COMMON_SHRED_HEADER, SIZE_OF_SIGNATURE,
+};
 use solana_perf::cuda_runtime::PinnedVec;
 use solana_perf::recycler::Recycler;
 use std::net::UdpSocket;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc::channel;
 use std::sync::Arc;
 use std::thread::{self, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+const STATS_REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Why the fetch stage dropped a packet before signature verification
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiscardReason {
+    /// Smaller than the common shred header
+    TooShort,
+    /// The shred type is neither data nor coding
+    InvalidShredType,
+}
+
+/// Packets seen on one kind of shred socket
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShredSocketStats {
+    pub packets: u64,
+    pub bytes: u64,
+    pub discarded_too_short: u64,
+    pub discarded_invalid_shred_type: u64,
+}
+
+impl ShredSocketStats {
+    pub fn discarded(&self) -> u64 {
+        self.discarded_too_short + self.discarded_invalid_shred_type
+    }
+
+    fn since(&self, previous: &Self) -> Self {
+        Self {
+            packets: self.packets - previous.packets,
+            bytes: self.bytes - previous.bytes,
+            discarded_too_short: self.discarded_too_short - previous.discarded_too_short,
+            discarded_invalid_shred_type: self.discarded_invalid_shred_type
+                - previous.discarded_invalid_shred_type,
+        }
+    }
+}
+
+/// Packets seen by the fetch stage since it started, by the socket they arrived on
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShredFetchStats {
+    pub tvu: ShredSocketStats,
+    pub tvu_forwards: ShredSocketStats,
+    pub repair: ShredSocketStats,
+}
+
+#[derive(Default)]
+struct SocketCounters {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+    discarded_too_short: AtomicU64,
+    discarded_invalid_shred_type: AtomicU64,
+}
+
+impl SocketCounters {
+    fn record_discard(&self, reason: DiscardReason) {
+        match reason {
+            DiscardReason::TooShort => &self.discarded_too_short,
+            DiscardReason::InvalidShredType => &self.discarded_invalid_shred_type,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> ShredSocketStats {
+        ShredSocketStats {
+            packets: self.packets.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            discarded_too_short: self.discarded_too_short.load(Ordering::Relaxed),
+            discarded_invalid_shred_type: self.discarded_invalid_shred_type.load(Ordering::Relaxed),
+        }
+    }
+}
 
 pub struct ShredFetchStage {
     thread_hdls: Vec<JoinHandle<()>>,
+    tvu_counters: Arc<SocketCounters>,
+    tvu_forwards_counters: Arc<SocketCounters>,
+    repair_counters: Arc<SocketCounters>,
 }
 
 impl ShredFetchStage {
+    fn discard_reason(packet: &Packet) -> Option<DiscardReason> {
+        if packet.meta.size < SIZE_OF_COMMON_SHRED_HEADER {
+            Some(DiscardReason::TooShort)
+        } else if packet.data[SIZE_OF_SIGNATURE] != DATA_SHRED
+            && packet.data[SIZE_OF_SIGNATURE] != CODING_SHRED
+        {
+            Some(DiscardReason::InvalidShredType)
+        } else {
+            None
+        }
+    }
+
+    // counts the packets in a batch and drops the ones that can't be shreds
+    fn filter_packets(packets: &mut Packets, counters: &SocketCounters) {
+        let mut bytes = 0;
+        let mut num_kept = 0;
+        for i in 0..packets.packets.len() {
+            bytes += packets.packets[i].meta.size;
+            if let Some(reason) = Self::discard_reason(&packets.packets[i]) {
+                counters.record_discard(reason);
+            } else {
+                packets.packets[..].swap(num_kept, i);
+                num_kept += 1;
+            }
+        }
+        counters
+            .packets
+            .fetch_add(packets.packets.len() as u64, Ordering::Relaxed);
+        counters.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        packets.packets.truncate(num_kept);
+    }
+
+    fn report_stats(name: &str, stats: &ShredSocketStats) {
+        datapoint_info!(
+            "shred_fetch_stage",
+            ("socket", name.to_string(), String),
+            ("packets", stats.packets, i64),
+            ("bytes", stats.bytes, i64),
+            ("discarded_too_short", stats.discarded_too_short, i64),
+            (
+                "discarded_invalid_shred_type",
+                stats.discarded_invalid_shred_type,
+                i64
+            )
+        );
+    }
+
     // updates packets received on a channel and sends them on another channel
-    fn modify_packets<F>(recvr: PacketReceiver, sendr: PacketSender, modify: F)
-    where
+    fn modify_packets<F>(
+        recvr: PacketReceiver,
+        sendr: PacketSender,
+        name: &'static str,
+        counters: Arc<SocketCounters>,
+        modify: F,
+    ) where
         F: Fn(&mut Packet),
     {
+        let mut last_report = Instant::now();
+        let mut last_stats = ShredSocketStats::default();
         while let Some(mut p) = recvr.iter().next() {
+            Self::filter_packets(&mut p, &counters);
             p.packets.iter_mut().for_each(|p| modify(p));
-            if sendr.send(p).is_err() {
+            if !p.packets.is_empty() && sendr.send(p).is_err() {
                 break;
             }
+            if last_report.elapsed() >= STATS_REPORT_INTERVAL {
+                let stats = counters.stats();
+                Self::report_stats(name, &stats.since(&last_stats));
+                last_stats = stats;
+                last_report = Instant::now();
+            }
         }
     }
 
@@ -33,6 +172,8 @@ impl ShredFetchStage {
         exit: &Arc<AtomicBool>,
         sender: PacketSender,
         recycler: Recycler<PinnedVec<Packet>>,
+        name: &'static str,
+        counters: Arc<SocketCounters>,
         modify: F,
     ) -> (Vec<JoinHandle<()>>, JoinHandle<()>)
     where
@@ -53,8 +194,8 @@ impl ShredFetchStage {
             .collect();
         let sender = sender.clone();
         let modifier_hdl = Builder::new()
-            .name("solana-tvu-fetch-stage-packet-modifier".to_string())
-            .spawn(|| Self::modify_packets(packet_receiver, sender, modify))
+            .name(format!("solana-tvu-fetch-stage-{}-modifier", name))
+            .spawn(move || Self::modify_packets(packet_receiver, sender, name, counters, modify))
             .unwrap();
         (streamers, modifier_hdl)
     }
@@ -67,22 +208,27 @@ impl ShredFetchStage {
         exit: &Arc<AtomicBool>,
     ) -> Self {
         let recycler: PacketsRecycler = Recycler::warmed(100, 1024);
+        let tvu_counters = Arc::new(SocketCounters::default());
+        let tvu_forwards_counters = Arc::new(SocketCounters::default());
+        let repair_counters = Arc::new(SocketCounters::default());
 
-        let tvu_threads = sockets.into_iter().map(|socket| {
-            streamer::receiver(
-                socket,
-                &exit,
-                sender.clone(),
-                recycler.clone(),
-                "shred_fetch_stage",
-            )
-        });
+        let (tvu_threads, tvu_thread_hdl) = Self::packet_modifier(
+            sockets,
+            &exit,
+            sender.clone(),
+            recycler.clone(),
+            "tvu",
+            tvu_counters.clone(),
+            |_| (),
+        );
 
         let (tvu_forwards_threads, fwd_thread_hdl) = Self::packet_modifier(
             forward_sockets,
             &exit,
             sender.clone(),
             recycler.clone(),
+            "tvu_forwards",
+            tvu_forwards_counters.clone(),
             |p| p.meta.forward = true,
         );
 
@@ -91,17 +237,35 @@ impl ShredFetchStage {
             &exit,
             sender.clone(),
             recycler.clone(),
+            "repair",
+            repair_counters.clone(),
             |p| p.meta.repair = true,
         );
 
         let mut thread_hdls: Vec<_> = tvu_threads
+            .into_iter()
             .chain(tvu_forwards_threads.into_iter())
             .collect();
         thread_hdls.extend(repair_receiver.into_iter());
+        thread_hdls.push(tvu_thread_hdl);
         thread_hdls.push(fwd_thread_hdl);
         thread_hdls.push(repair_handler);
 
-        Self { thread_hdls }
+        Self {
+            thread_hdls,
+            tvu_counters,
+            tvu_forwards_counters,
+            repair_counters,
+        }
+    }
+
+    /// Packets received and discarded on each kind of socket so far
+    pub fn stats(&self) -> ShredFetchStats {
+        ShredFetchStats {
+            tvu: self.tvu_counters.stats(),
+            tvu_forwards: self.tvu_forwards_counters.stats(),
+            repair: self.repair_counters.stats(),
+        }
     }
 
     pub fn join(self) -> thread::Result<()> {
@@ -111,3 +275,41 @@ impl ShredFetchStage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_ledger::shred::Shred;
+
+    #[test]
+    fn test_filter_packets() {
+        let mut packets = Packets::default();
+        packets.packets.resize(3, Packet::default());
+
+        let shred = Shred::new_from_data(1, 2, 1, Some(&[1, 2, 3]), true, true, 0, 0);
+        packets.packets[1].data[..shred.payload.len()].copy_from_slice(&shred.payload);
+        packets.packets[1].meta.size = shred.payload.len();
+        // too short to hold a shred header
+        packets.packets[0].meta.size = SIZE_OF_COMMON_SHRED_HEADER - 1;
+        // an unknown shred type
+        packets.packets[2].meta.size = shred.payload.len();
+
+        let counters = SocketCounters::default();
+        ShredFetchStage::filter_packets(&mut packets, &counters);
+        assert_eq!(packets.packets.len(), 1);
+        assert_eq!(packets.packets[0].data[..], shred.payload[..]);
+
+        let stats = counters.stats();
+        assert_eq!(
+            stats,
+            ShredSocketStats {
+                packets: 3,
+                bytes: (SIZE_OF_COMMON_SHRED_HEADER - 1 + 2 * shred.payload.len()) as u64,
+                discarded_too_short: 1,
+                discarded_invalid_shred_type: 1,
+            }
+        );
+        assert_eq!(stats.discarded(), 2);
+        assert_eq!(stats.since(&stats), ShredSocketStats::default());
+    }
+}
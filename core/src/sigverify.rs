@@ -5,18 +5,79 @@
 //!
 
 use crate::sigverify_stage::SigVerifier;
+use solana_metrics::inc_new_counter_info;
 use solana_perf::cuda_runtime::PinnedVec;
-use solana_perf::packet::Packets;
+use solana_perf::packet::{Packet, Packets};
 use solana_perf::recycler::Recycler;
 use solana_perf::sigverify;
 pub use solana_perf::sigverify::{
     batch_size, ed25519_verify_cpu, ed25519_verify_disabled, init, TxOffset,
 };
+use solana_sdk::hash::{hash, Hash};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Number of packet verification results remembered across batches
+pub const VERIFIED_CACHE_SIZE: usize = 50_000;
+
+/// Least recently used cache of verification results, keyed by the hash of the packet data
+pub struct VerifiedCache {
+    results: HashMap<Hash, (bool, u64)>,
+    // last use -> packet hash, oldest first
+    order: BTreeMap<u64, Hash>,
+    capacity: usize,
+    next_use: u64,
+}
+
+impl VerifiedCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            results: HashMap::new(),
+            order: BTreeMap::new(),
+            capacity,
+            next_use: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    pub fn get(&mut self, packet_hash: &Hash) -> Option<bool> {
+        let next_use = self.next_use;
+        let (verified, last_use) = self.results.get_mut(packet_hash)?;
+        self.order.remove(last_use);
+        self.order.insert(next_use, *packet_hash);
+        *last_use = next_use;
+        self.next_use += 1;
+        Some(*verified)
+    }
+
+    pub fn insert(&mut self, packet_hash: Hash, verified: bool) {
+        if let Some((_, last_use)) = self.results.remove(&packet_hash) {
+            self.order.remove(&last_use);
+        } else if self.results.len() >= self.capacity {
+            let oldest = self.order.keys().next().cloned();
+            if let Some(oldest) = oldest {
+                let evicted = self.order.remove(&oldest).unwrap();
+                self.results.remove(&evicted);
+            }
+        }
+        self.results.insert(packet_hash, (verified, self.next_use));
+        self.order.insert(self.next_use, packet_hash);
+        self.next_use += 1;
+    }
+}
 
 #[derive(Clone)]
 pub struct TransactionSigVerifier {
     recycler: Recycler<TxOffset>,
     recycler_out: Recycler<PinnedVec<u8>>,
+    verified_cache: Arc<Mutex<VerifiedCache>>,
 }
 
 impl Default for TransactionSigVerifier {
@@ -25,18 +86,76 @@ impl Default for TransactionSigVerifier {
         Self {
             recycler: Recycler::warmed(50, 4096),
             recycler_out: Recycler::warmed(50, 4096),
+            verified_cache: Arc::new(Mutex::new(VerifiedCache::new(VERIFIED_CACHE_SIZE))),
         }
     }
 }
 
 impl SigVerifier for TransactionSigVerifier {
     fn verify_batch(&self, mut batch: Vec<Packets>) -> Vec<Packets> {
-        let r = sigverify::ed25519_verify(&batch, &self.recycler, &self.recycler_out);
-        mark_disabled(&mut batch, &r);
+        let packet_hashes: Vec<Vec<Hash>> = batch
+            .iter()
+            .map(|packets| packets.packets.iter().map(packet_hash).collect())
+            .collect();
+
+        // Resolve what we can from the cache, only packets seen for the first time go to
+        // the verifier
+        let mut misses = vec![];
+        {
+            let mut verified_cache = self.verified_cache.lock().unwrap();
+            for (i, packets) in batch.iter_mut().enumerate() {
+                for (j, packet) in packets.packets.iter_mut().enumerate() {
+                    match verified_cache.get(&packet_hashes[i][j]) {
+                        Some(verified) => packet.meta.discard = !verified,
+                        None => misses.push((i, j)),
+                    }
+                }
+            }
+        }
+        let num_packets: usize = packet_hashes.iter().map(Vec::len).sum();
+        inc_new_counter_info!("sigverify-cache_hits", num_packets - misses.len());
+        inc_new_counter_info!("sigverify-cache_misses", misses.len());
+        if misses.is_empty() {
+            return batch;
+        }
+
+        let results = if misses.len() == num_packets {
+            let r = sigverify::ed25519_verify(&batch, &self.recycler, &self.recycler_out);
+            mark_disabled(&mut batch, &r);
+            misses
+                .iter()
+                .map(|(i, j)| (*i, *j, r[*i][*j] != 0))
+                .collect::<Vec<_>>()
+        } else {
+            let uncached = Packets::new(
+                misses
+                    .iter()
+                    .map(|(i, j)| batch[*i].packets[*j].clone())
+                    .collect::<Vec<Packet>>(),
+            );
+            let r = sigverify::ed25519_verify(&[uncached], &self.recycler, &self.recycler_out);
+            misses
+                .iter()
+                .zip(&r[0])
+                .map(|((i, j), verified)| {
+                    batch[*i].packets[*j].meta.discard = *verified == 0;
+                    (*i, *j, *verified != 0)
+                })
+                .collect()
+        };
+
+        let mut verified_cache = self.verified_cache.lock().unwrap();
+        for (i, j, verified) in results {
+            verified_cache.insert(packet_hashes[i][j], verified);
+        }
         batch
     }
 }
 
+fn packet_hash(packet: &Packet) -> Hash {
+    hash(&packet.data[..packet.meta.size])
+}
+
 pub fn mark_disabled(batches: &mut Vec<Packets>, r: &[Vec<u8>]) {
     batches.iter_mut().zip(r).for_each(|(b, v)| {
         b.packets
@@ -49,7 +168,6 @@ pub fn mark_disabled(batches: &mut Vec<Packets>, r: &[Vec<u8>]) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use solana_perf::packet::Packet;
 
     #[test]
     fn test_mark_disabled() {
@@ -61,4 +179,19 @@ mod tests {
         mark_disabled(&mut batches, &[vec![1]]);
         assert_eq!(batches[0].packets[0].meta.discard, false);
     }
+
+    #[test]
+    fn test_verified_cache_eviction() {
+        let mut cache = VerifiedCache::new(2);
+        let hashes: Vec<_> = (0..3u8).map(|i| hash(&[i])).collect();
+        cache.insert(hashes[0], true);
+        cache.insert(hashes[1], false);
+        // touching the oldest entry makes the other one the eviction candidate
+        assert_eq!(cache.get(&hashes[0]), Some(true));
+        cache.insert(hashes[2], true);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&hashes[0]), Some(true));
+        assert_eq!(cache.get(&hashes[1]), None);
+        assert_eq!(cache.get(&hashes[2]), Some(true));
+    }
 }
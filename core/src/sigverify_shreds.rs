@@ -7,6 +7,8 @@ use solana_ledger::leader_schedule_cache::LeaderScheduleCache;
 use solana_ledger::shred::ShredType;
 use solana_ledger::sigverify_shreds::verify_shreds_gpu;
 use solana_perf::recycler_cache::RecyclerCache;
+use solana_sdk::clock::Slot;
+use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
 use std::collections::{HashMap, HashSet};
 use std::mem::size_of;
@@ -71,6 +73,50 @@ impl SigVerifier for ShredSigVerifier {
     }
 }
 
+/// Verifies shreds against the leaders of a single segment, for archivers which don't replay
+/// the banks a `ShredSigVerifier` needs. Shreds of any other slot are discarded, all of them
+/// until the leaders are set. Clones share the leaders.
+#[derive(Clone)]
+pub struct SegmentShredSigVerifier {
+    slot_leaders: Arc<RwLock<HashMap<u64, [u8; 32]>>>,
+    recycler_cache: RecyclerCache,
+}
+
+impl Default for SegmentShredSigVerifier {
+    fn default() -> Self {
+        sigverify::init();
+        let verifier = Self {
+            slot_leaders: Arc::new(RwLock::new(HashMap::new())),
+            recycler_cache: RecyclerCache::warmed(),
+        };
+        verifier.set_segment_leaders(0, &[]);
+        verifier
+    }
+}
+
+impl SegmentShredSigVerifier {
+    /// Replace the leaders, `leaders` are the leaders of consecutive slots starting with
+    /// `start_slot`
+    pub fn set_segment_leaders(&self, start_slot: Slot, leaders: &[Pubkey]) {
+        let mut slot_leaders: HashMap<u64, [u8; 32]> = leaders
+            .iter()
+            .enumerate()
+            .map(|(i, leader)| (start_slot + i as u64, leader.to_bytes()))
+            .collect();
+        slot_leaders.insert(std::u64::MAX, [0u8; 32]);
+        *self.slot_leaders.write().unwrap() = slot_leaders;
+    }
+}
+
+impl SigVerifier for SegmentShredSigVerifier {
+    fn verify_batch(&self, mut batches: Vec<Packets>) -> Vec<Packets> {
+        let slot_leaders = self.slot_leaders.read().unwrap().clone();
+        let r = verify_shreds_gpu(&batches, &slot_leaders, &self.recycler_cache);
+        sigverify::mark_disabled(&mut batches, &r);
+        batches
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -150,4 +196,45 @@ pub mod tests {
         assert_eq!(rv[0].packets[0].meta.discard, false);
         assert_eq!(rv[0].packets[1].meta.discard, true);
     }
+
+    #[test]
+    fn test_segment_sigverify_shreds_verify_batch() {
+        let leader_keypair = Keypair::new();
+        let other_keypair = Keypair::new();
+        let verifier = SegmentShredSigVerifier::default();
+        let segment_verifier = verifier.clone();
+        segment_verifier.set_segment_leaders(8, &[leader_keypair.pubkey(), other_keypair.pubkey()]);
+
+        let mut batch = vec![Packets::default()];
+        batch[0].packets.resize(4, Packet::default());
+        let shreds = [
+            (8, &leader_keypair),
+            (9, &other_keypair),
+            // signed by the leader of another slot
+            (9, &leader_keypair),
+            // outside of the segment
+            (10, &other_keypair),
+        ];
+        for (packet, (slot, keypair)) in batch[0].packets.iter_mut().zip(shreds.iter()) {
+            let mut shred =
+                Shred::new_from_data(*slot, 0xc0de, 1, Some(&[1, 2, 3, 4]), true, true, 0, 0);
+            Shredder::sign_shred(keypair, &mut shred);
+            packet.data[0..shred.payload.len()].copy_from_slice(&shred.payload);
+            packet.meta.size = shred.payload.len();
+        }
+
+        let rv = verifier.verify_batch(batch.clone());
+        let discarded: Vec<_> = rv[0]
+            .packets
+            .iter()
+            .map(|packet| packet.meta.discard)
+            .collect();
+        assert_eq!(discarded, vec![false, false, true, true]);
+
+        // the next segment
+        verifier.set_segment_leaders(10, &[other_keypair.pubkey()]);
+        let rv = verifier.verify_batch(batch);
+        assert!(rv[0].packets[..3].iter().all(|packet| packet.meta.discard));
+        assert_eq!(rv[0].packets[3].meta.discard, false);
+    }
 }
@@ -9,9 +9,11 @@ use crate::packet::Packets;
 use crate::result::{Error, Result};
 use crate::sigverify;
 use crate::streamer::{self, PacketReceiver};
-use crossbeam_channel::Sender as CrossbeamSender;
+use crate::thread_affinity::{self, ThreadRole};
+use crate::transaction_trace::{self, TraceStage};
+use crossbeam_channel::{Sender as CrossbeamSender, TrySendError};
 use solana_measure::measure::Measure;
-use solana_metrics::{datapoint_debug, inc_new_counter_info};
+use solana_metrics::{datapoint_debug, inc_new_counter_info, inc_new_counter_warn};
 use solana_perf::perf_libs;
 use solana_sdk::timing;
 use std::sync::mpsc::{Receiver, RecvTimeoutError};
@@ -80,8 +82,16 @@ impl SigVerifyStage {
         inc_new_counter_info!("sigverify_stage-verified_packets_send", len);
 
         for v in verified_batch {
-            if sendr.send(vec![v]).is_err() {
-                return Err(Error::SendError);
+            transaction_trace::record_packets(TraceStage::SigVerify, &v);
+            // A bounded consumer that has fallen behind sheds load here rather than
+            // letting batches pile up without limit
+            match sendr.try_send(vec![v]) {
+                Ok(()) => (),
+                Err(TrySendError::Full(dropped)) => {
+                    let dropped_len: usize = dropped.iter().map(|p| p.packets.len()).sum();
+                    inc_new_counter_warn!("sigverify_stage-dropped_packets_full", dropped_len);
+                }
+                Err(TrySendError::Disconnected(_)) => return Err(Error::SendError),
             }
         }
 
@@ -120,15 +130,20 @@ impl SigVerifyStage {
         let verifier = verifier.clone();
         Builder::new()
             .name(format!("solana-verifier-{}", id))
-            .spawn(move || loop {
-                if let Err(e) = Self::verifier(&packet_receiver, &verified_sender, id, &verifier) {
-                    match e {
-                        Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => break,
-                        Error::RecvTimeoutError(RecvTimeoutError::Timeout) => (),
-                        Error::SendError => {
-                            break;
+            .spawn(move || {
+                thread_affinity::apply(ThreadRole::Sigverify);
+                loop {
+                    if let Err(e) =
+                        Self::verifier(&packet_receiver, &verified_sender, id, &verifier)
+                    {
+                        match e {
+                            Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => break,
+                            Error::RecvTimeoutError(RecvTimeoutError::Timeout) => (),
+                            Error::SendError => {
+                                break;
+                            }
+                            _ => error!("{:?}", e),
                         }
-                        _ => error!("{:?}", e),
                     }
                 }
             })
@@ -5,24 +5,33 @@ use solana_ledger::snapshot_utils::{self, TAR_ACCOUNTS_DIR, TAR_SNAPSHOTS_DIR};
 use solana_measure::measure::Measure;
 use solana_metrics::datapoint_info;
 use solana_runtime::status_cache::SlotDelta;
+use solana_sdk::clock::Slot;
 use solana_sdk::transaction::Result as TransactionResult;
 use std::fs;
 use std::fs::File;
 use std::io::{BufWriter, Error as IOError, ErrorKind};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::{RecvTimeoutError, Sender};
 use std::sync::Arc;
 use std::thread::{self, Builder, JoinHandle};
 use std::time::Duration;
 use symlink;
 use tempfile::TempDir;
 
+/// Receives the root and path of every snapshot tarball once it's in place
+pub type PackagedSnapshotSender = Sender<(Slot, PathBuf)>;
+
 pub struct SnapshotPackagerService {
     t_snapshot_packager: JoinHandle<()>,
 }
 
 impl SnapshotPackagerService {
-    pub fn new(snapshot_package_receiver: SnapshotPackageReceiver, exit: &Arc<AtomicBool>) -> Self {
+    pub fn new(
+        snapshot_package_receiver: SnapshotPackageReceiver,
+        packaged_snapshot_sender: Option<PackagedSnapshotSender>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
         let exit = exit.clone();
         let t_snapshot_packager = Builder::new()
             .name("solana-snapshot-packager".to_string())
@@ -30,7 +39,7 @@ impl SnapshotPackagerService {
                 if exit.load(Ordering::Relaxed) {
                     break;
                 }
-                if let Err(e) = Self::run(&snapshot_package_receiver) {
+                if let Err(e) = Self::run(&snapshot_package_receiver, &packaged_snapshot_sender) {
                     match e {
                         Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => break,
                         Error::RecvTimeoutError(RecvTimeoutError::Timeout) => (),
@@ -133,13 +142,23 @@ impl SnapshotPackagerService {
         Ok(())
     }
 
-    fn run(snapshot_receiver: &SnapshotPackageReceiver) -> Result<()> {
+    fn run(
+        snapshot_receiver: &SnapshotPackageReceiver,
+        packaged_snapshot_sender: &Option<PackagedSnapshotSender>,
+    ) -> Result<()> {
         let mut snapshot_package = snapshot_receiver.recv_timeout(Duration::from_secs(1))?;
         // Only package the latest
         while let Ok(new_snapshot_package) = snapshot_receiver.try_recv() {
             snapshot_package = new_snapshot_package;
         }
         Self::package_snapshots(&snapshot_package)?;
+        if let Some(packaged_snapshot_sender) = packaged_snapshot_sender {
+            // The receiver may have gone away, that doesn't stop snapshots from being packaged
+            let _ = packaged_snapshot_sender.send((
+                snapshot_package.root,
+                snapshot_package.tar_output_file.clone(),
+            ));
+        }
         Ok(())
     }
 
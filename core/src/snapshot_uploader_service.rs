@@ -0,0 +1,246 @@
+//! The `snapshot_uploader_service` streams packaged snapshots and the entries of rooted slots
+//! to S3-compatible object storage, so a cluster keeps its history even after validators purge
+//! their ledgers. Every object is followed by a JSON manifest with its size and SHA-256 hash,
+//! which is only written once the object is, so an object with a manifest is complete.
+
+use crate::object_store::ObjectStore;
+use crate::result::{Error, Result};
+use solana_ledger::blocktree::Blocktree;
+use solana_ledger::rooted_slot_iterator::RootedSlotIterator;
+use solana_measure::measure::Measure;
+use solana_metrics::datapoint_info;
+use solana_sdk::clock::Slot;
+use solana_sdk::hash::Hasher;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, Builder, JoinHandle};
+use std::time::Duration;
+
+/// Manifest of the most recently uploaded snapshot
+pub const LATEST_SNAPSHOT_MANIFEST: &str = "snapshots/latest.json";
+
+pub fn snapshot_object_name(root: Slot) -> String {
+    format!("snapshots/{}/snapshot.tar.bz2", root)
+}
+
+pub fn slot_object_name(slot: Slot) -> String {
+    format!("slots/{}.bin", slot)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct UploadManifest {
+    /// Name of the uploaded object
+    pub object: String,
+    /// Root of the snapshot, or slot of the entries, in the object
+    pub slot: Slot,
+    /// Size of the object in bytes
+    pub size: u64,
+    /// Base58 SHA-256 hash of the object
+    pub hash: String,
+}
+
+impl UploadManifest {
+    pub fn new<R: Read>(object: &str, slot: Slot, reader: &mut R) -> io::Result<Self> {
+        let mut hasher = Hasher::default();
+        let mut size = 0;
+        let mut buf = [0; 64 * 1024];
+        loop {
+            let len = reader.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            hasher.hash(&buf[..len]);
+            size += len as u64;
+        }
+        Ok(Self {
+            object: object.to_string(),
+            slot,
+            size,
+            hash: hasher.result().to_string(),
+        })
+    }
+
+    /// Name of the manifest of `object`
+    pub fn name(object: &str) -> String {
+        format!("{}.manifest.json", object)
+    }
+}
+
+/// Rooted slots descending from `last_uploaded` that are complete in `blocktree`, in order.
+/// Stops at the first rooted slot still missing shreds.
+pub fn rooted_slots_to_upload(blocktree: &Blocktree, last_uploaded: Slot) -> Result<Vec<Slot>> {
+    Ok(RootedSlotIterator::new(last_uploaded, blocktree)?
+        .skip(1)
+        .take_while(|(_, slot_meta)| slot_meta.is_full())
+        .map(|(slot, _)| slot)
+        .collect())
+}
+
+pub struct SnapshotUploaderService {
+    t_uploader: JoinHandle<()>,
+}
+
+impl SnapshotUploaderService {
+    /// Slots rooted before the service starts aren't uploaded, they're covered by the next
+    /// snapshot
+    pub fn new(
+        object_store: ObjectStore,
+        packaged_snapshot_receiver: Receiver<(Slot, PathBuf)>,
+        blocktree: Arc<Blocktree>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        info!(
+            "Uploading snapshots and rooted slots to {}",
+            object_store.url()
+        );
+        let exit = exit.clone();
+        let t_uploader = Builder::new()
+            .name("solana-snapshot-uploader".to_string())
+            .spawn(move || {
+                let mut last_uploaded = blocktree.last_root();
+                loop {
+                    if exit.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if let Err(e) =
+                        Self::upload_snapshots(&object_store, &packaged_snapshot_receiver)
+                    {
+                        match e {
+                            Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => break,
+                            Error::RecvTimeoutError(RecvTimeoutError::Timeout) => (),
+                            _ => info!("Error from upload_snapshots: {:?}", e),
+                        }
+                    }
+                    if let Err(e) =
+                        Self::upload_rooted_slots(&object_store, &blocktree, &mut last_uploaded)
+                    {
+                        info!("Error from upload_rooted_slots: {:?}", e);
+                    }
+                }
+            })
+            .unwrap();
+        Self { t_uploader }
+    }
+
+    fn upload_snapshots(
+        object_store: &ObjectStore,
+        packaged_snapshot_receiver: &Receiver<(Slot, PathBuf)>,
+    ) -> Result<()> {
+        let (mut root, mut tar_path) =
+            packaged_snapshot_receiver.recv_timeout(Duration::from_secs(1))?;
+        // Only upload the latest
+        while let Ok((new_root, new_tar_path)) = packaged_snapshot_receiver.try_recv() {
+            root = new_root;
+            tar_path = new_tar_path;
+        }
+
+        let mut timer = Measure::start("snapshot_uploader-upload_snapshot");
+        // Keep the tarball open, the packager replaces it by renaming a new one over it
+        let mut tar_file = File::open(&tar_path)?;
+        let object = snapshot_object_name(root);
+        let manifest = UploadManifest::new(&object, root, &mut tar_file)?;
+        tar_file.seek(SeekFrom::Start(0))?;
+        object_store.put(&object, tar_file)?;
+        let manifest = serde_json::to_string(&manifest)?;
+        object_store.put(&UploadManifest::name(&object), manifest.clone())?;
+        object_store.put(LATEST_SNAPSHOT_MANIFEST, manifest)?;
+        timer.stop();
+
+        datapoint_info!(
+            "snapshot-upload",
+            ("slot", root, i64),
+            ("duration_ms", timer.as_ms(), i64)
+        );
+        Ok(())
+    }
+
+    fn upload_rooted_slots(
+        object_store: &ObjectStore,
+        blocktree: &Blocktree,
+        last_uploaded: &mut Slot,
+    ) -> Result<()> {
+        let slots = rooted_slots_to_upload(blocktree, *last_uploaded).map_err(|e| {
+            // The slot was purged before its children were uploaded, pick up at the latest root
+            let last_root = blocktree.last_root();
+            warn!(
+                "Rooted slots {}..{} were not uploaded: {:?}",
+                *last_uploaded, last_root, e
+            );
+            *last_uploaded = last_root;
+            e
+        })?;
+        for slot in slots {
+            let entries = blocktree.get_slot_entries(slot, 0, None)?;
+            let data = bincode::serialize(&entries)?;
+            let object = slot_object_name(slot);
+            let manifest = UploadManifest::new(&object, slot, &mut data.as_slice())?;
+            object_store.put(&object, data)?;
+            object_store.put(
+                &UploadManifest::name(&object),
+                serde_json::to_string(&manifest)?,
+            )?;
+            *last_uploaded = slot;
+        }
+        Ok(())
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.t_uploader.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_ledger::blocktree_processor::fill_blocktree_slot_with_ticks;
+    use solana_ledger::get_tmp_ledger_path;
+    use solana_sdk::hash::{hash, Hash};
+
+    #[test]
+    fn test_upload_manifest() {
+        let data = vec![7u8; 100_000];
+        let manifest = UploadManifest::new("slots/3.bin", 3, &mut data.as_slice()).unwrap();
+        assert_eq!(
+            manifest,
+            UploadManifest {
+                object: "slots/3.bin".to_string(),
+                slot: 3,
+                size: 100_000,
+                hash: hash(&data).to_string(),
+            }
+        );
+        assert_eq!(
+            UploadManifest::name(&manifest.object),
+            "slots/3.bin.manifest.json"
+        );
+    }
+
+    #[test]
+    fn test_rooted_slots_to_upload() {
+        let blocktree_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&blocktree_path).unwrap();
+            let mut last_entry_hash = Hash::default();
+            for slot in 0..=3 {
+                last_entry_hash = fill_blocktree_slot_with_ticks(
+                    &blocktree,
+                    5,
+                    slot,
+                    slot.saturating_sub(1),
+                    last_entry_hash,
+                );
+            }
+            blocktree.set_roots(&[0, 1, 2]).unwrap();
+
+            assert_eq!(rooted_slots_to_upload(&blocktree, 0).unwrap(), vec![1, 2]);
+            assert!(rooted_slots_to_upload(&blocktree, 2).unwrap().is_empty());
+            // slot 3 isn't rooted yet
+            assert!(rooted_slots_to_upload(&blocktree, 3).is_err());
+        }
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+}
@@ -3,15 +3,23 @@
 // to submit its proof for mining to be rewarded.
 
 use crate::{
+    archiver::{sample_file, sampling_offsets},
+    chacha::{
+        chacha_cbc_encrypt_ledger, for_each_segment_chunk, CHACHA_BLOCK_SIZE, CHACHA_CHUNK_SLOTS,
+    },
     chacha_cuda::chacha_cbc_encrypt_file_many_keys,
     cluster_info::ClusterInfo,
     contact_info::ContactInfo,
+    crds_gossip_pull::CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS,
     result::{Error, Result},
+    thread_registry,
+};
+use rand::{thread_rng, Rng};
+use solana_ledger::{bank_forks::BankForks, blocktree::Blocktree, staking_utils};
+use solana_runtime::{
+    bank::Bank,
+    storage_utils::{archiver_accounts, committed_storage_turn},
 };
-use rand::{Rng, SeedableRng};
-use rand_chacha::ChaChaRng;
-use solana_ledger::{bank_forks::BankForks, blocktree::Blocktree};
-use solana_runtime::{bank::Bank, storage_utils::archiver_accounts};
 use solana_sdk::{
     account::Account,
     account_utils::State,
@@ -21,6 +29,7 @@ use solana_sdk::{
     message::Message,
     pubkey::Pubkey,
     signature::{Keypair, KeypairUtil, Signature},
+    timing::timestamp,
     transaction::Transaction,
 };
 use solana_storage_api::{
@@ -30,14 +39,14 @@ use solana_storage_api::{
 };
 use std::{
     cmp,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     io,
     mem::size_of,
     net::UdpSocket,
     sync::atomic::{AtomicBool, Ordering},
     sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
     sync::{Arc, RwLock},
-    thread::{self, sleep, Builder, JoinHandle},
+    thread::{self, sleep, JoinHandle},
     time::{Duration, Instant},
 };
 
@@ -47,6 +56,16 @@ type StorageResults = Vec<Hash>;
 type StorageKeys = Vec<u8>;
 type ArchiverMap = Vec<HashMap<Pubkey, Vec<Proof>>>;
 
+// What the answers in `storage_results` were generated for, they only answer a proof mined
+// over the same segment with the same key and sample offsets
+struct StorageReference {
+    segment: u64,
+    // `storage_keys` as they were before the encryption updated them in place
+    keys: StorageKeys,
+    // the blockhash the sample offsets were derived from as in `archiver::sampling_offsets()`
+    sampling_blockhash: Hash,
+}
+
 #[derive(Default)]
 pub struct StorageStateInner {
     storage_results: StorageResults,
@@ -56,6 +75,10 @@ pub struct StorageStateInner {
     slot: Slot,
     slots_per_segment: u64,
     slots_per_turn: u64,
+    // set once `storage_results` hold the answers for the current turn
+    storage_reference: Option<StorageReference>,
+    // fraction of archiver proofs to check, None for a stake-aware default
+    validation_sampling_rate: Option<f64>,
 }
 
 // Used to track root slots in storage stage
@@ -64,6 +87,9 @@ struct StorageSlots {
     last_root: u64,
     slot_count: u64,
     pending_root_banks: Vec<Arc<Bank>>,
+    // the storage turn last advertised in gossip and when
+    pushed_storage_turn: Option<(Hash, Slot)>,
+    last_storage_turn_push: u64,
 }
 
 #[derive(Clone, Default)]
@@ -81,6 +107,39 @@ pub const SLOTS_PER_TURN_TEST: u64 = 2;
 const NUM_IDENTITIES: usize = 1024;
 pub const NUM_STORAGE_SAMPLES: usize = 4;
 const KEY_SIZE: usize = 64;
+// Without perf libs the answers are encrypted one key at a time, for at most this many of the
+// turn's proofs
+const MAX_CPU_REFERENCE_KEYS: usize = 4;
+/// Fraction of archiver proofs checked by a validator with no stake
+pub const MIN_VALIDATION_SAMPLING_RATE: f64 = 0.1;
+
+/// Proofs are sampled more often by validators with more of the cluster's stake, an
+/// unstaked cluster (e.g. a single development node) checks every proof
+pub fn default_validation_sampling_rate(node_stake: u64, total_stake: u64) -> f64 {
+    if total_stake == 0 {
+        return 1.0;
+    }
+    let stake_fraction = node_stake.min(total_stake) as f64 / total_stake as f64;
+    MIN_VALIDATION_SAMPLING_RATE + (1.0 - MIN_VALIDATION_SAMPLING_RATE) * stake_fraction
+}
+
+#[derive(Debug, PartialEq)]
+enum ProofAudit {
+    Passed,
+    Failed,
+    // no reference answers for the proof's segment, key and samples
+    Unverified,
+}
+
+impl ProofAudit {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ProofAudit::Passed => "pass",
+            ProofAudit::Failed => "fail",
+            ProofAudit::Unverified => "unverified",
+        }
+    }
+}
 
 type InstructionSender = Sender<Instruction>;
 
@@ -108,6 +167,8 @@ impl StorageState {
             slot: 0,
             slots_per_segment,
             storage_blockhash: *hash,
+            storage_reference: None,
+            validation_sampling_rate: None,
         };
 
         StorageState {
@@ -125,6 +186,17 @@ impl StorageState {
         self.state.read().unwrap().storage_results[idx]
     }
 
+    /// Check `rate` (0.0 to 1.0) of the archiver proofs instead of the stake-aware default
+    pub fn set_validation_sampling_rate(&self, rate: Option<f64>) {
+        if let Some(rate) = rate {
+            assert!(
+                rate >= 0.0 && rate <= 1.0,
+                "validation sampling rate must be between 0 and 1"
+            );
+        }
+        self.state.write().unwrap().validation_sampling_rate = rate;
+    }
+
     pub fn get_storage_blockhash(&self) -> Hash {
         self.state.read().unwrap().storage_blockhash
     }
@@ -191,14 +263,18 @@ impl StorageStage {
             let storage_state_inner = storage_state.state.clone();
             let exit = exit.clone();
             let storage_keypair = storage_keypair.clone();
-            Builder::new()
-                .name("solana-storage-mining-verify-stage".to_string())
-                .spawn(move || {
+            let node_id = keypair.pubkey();
+            let cluster_info = cluster_info.clone();
+            thread_registry::spawn(
+                "storage-mining-verify-stage",
+                Some(exit.clone()),
+                move || {
                     let mut current_key = 0;
                     let mut storage_slots = StorageSlots::default();
                     loop {
                         if let Some(ref some_blocktree) = blocktree {
                             if let Err(e) = Self::process_entries(
+                                &node_id,
                                 &storage_keypair,
                                 &storage_state_inner,
                                 &bank_receiver,
@@ -207,6 +283,7 @@ impl StorageStage {
                                 &mut current_key,
                                 slots_per_turn,
                                 &instruction_sender,
+                                &cluster_info,
                             ) {
                                 match e {
                                     Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => {
@@ -221,8 +298,9 @@ impl StorageStage {
                             break;
                         }
                     }
-                })
-                .unwrap()
+                },
+            )
+            .unwrap()
         };
 
         let t_storage_create_accounts = {
@@ -231,47 +309,45 @@ impl StorageStage {
             let keypair = keypair.clone();
             let storage_keypair = storage_keypair.clone();
             let bank_forks = bank_forks.clone();
-            Builder::new()
-                .name("solana-storage-create-accounts".to_string())
-                .spawn(move || {
-                    let transactions_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+            thread_registry::spawn("storage-create-accounts", Some(exit.clone()), move || {
+                let transactions_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
 
-                    {
-                        let working_bank = bank_forks.read().unwrap().working_bank();
-                        let storage_account = working_bank.get_account(&storage_keypair.pubkey());
-                        if storage_account.is_none() {
-                            warn!("Storage account not found: {}", storage_keypair.pubkey());
-                        }
+                {
+                    let working_bank = bank_forks.read().unwrap().working_bank();
+                    let storage_account = working_bank.get_account(&storage_keypair.pubkey());
+                    if storage_account.is_none() {
+                        warn!("Storage account not found: {}", storage_keypair.pubkey());
                     }
+                }
 
-                    loop {
-                        match instruction_receiver.recv_timeout(Duration::from_secs(1)) {
-                            Ok(instruction) => {
-                                Self::send_transaction(
-                                    &bank_forks,
-                                    &cluster_info,
-                                    instruction,
-                                    &keypair,
-                                    &storage_keypair,
-                                    &transactions_socket,
-                                )
-                                .unwrap_or_else(|err| {
-                                    info!("failed to send storage transaction: {:?}", err)
-                                });
-                            }
-                            Err(e) => match e {
-                                RecvTimeoutError::Disconnected => break,
-                                RecvTimeoutError::Timeout => (),
-                            },
-                        };
-
-                        if exit.load(Ordering::Relaxed) {
-                            break;
+                loop {
+                    match instruction_receiver.recv_timeout(Duration::from_secs(1)) {
+                        Ok(instruction) => {
+                            Self::send_transaction(
+                                &bank_forks,
+                                &cluster_info,
+                                instruction,
+                                &keypair,
+                                &storage_keypair,
+                                &transactions_socket,
+                            )
+                            .unwrap_or_else(|err| {
+                                info!("failed to send storage transaction: {:?}", err)
+                            });
                         }
-                        sleep(Duration::from_millis(100));
+                        Err(e) => match e {
+                            RecvTimeoutError::Disconnected => break,
+                            RecvTimeoutError::Timeout => (),
+                        },
+                    };
+
+                    if exit.load(Ordering::Relaxed) {
+                        break;
                     }
-                })
-                .unwrap()
+                    sleep(Duration::from_millis(100));
+                }
+            })
+            .unwrap()
         };
 
         StorageStage {
@@ -375,65 +451,64 @@ impl StorageStage {
         instruction_sender: &InstructionSender,
         total_proofs: usize,
     ) -> Result<()> {
-        let mut seed = [0u8; 32];
-        let signature = storage_keypair.sign(&blockhash.as_ref());
-
-        let ix = storage_instruction::advertise_recent_blockhash(
+        let ix = storage_instruction::commit_storage_turn(
             &storage_keypair.pubkey(),
             blockhash,
             get_segment_from_slot(slot, slots_per_segment),
+            slot,
         );
         instruction_sender.send(ix)?;
 
-        seed.copy_from_slice(&signature.to_bytes()[..32]);
-
-        let mut rng = ChaChaRng::from_seed(seed);
-
         {
             let mut w_state = state.write().unwrap();
             w_state.slot = slot;
             w_state.storage_blockhash = blockhash;
+            w_state.storage_reference = None;
         }
 
         if total_proofs == 0 {
             return Ok(());
         }
 
-        // Regenerate the answers
-        let num_segments = get_segment_from_slot(slot, slots_per_segment) as usize;
-        if num_segments == 0 {
-            info!("Ledger has 0 segments!");
+        // Regenerate the answers to the proofs collected this turn, from the segment and
+        // blockhash most of them were mined with
+        let (segment, sampling_blockhash) = match Self::reference_target(state) {
+            Some(target) => target,
+            None => return Ok(()),
+        };
+        let num_chacha_blocks = for_each_segment_chunk(
+            blocktree,
+            segment * slots_per_segment,
+            slots_per_segment,
+            |_| Ok(()),
+        )? / CHACHA_BLOCK_SIZE;
+        if num_chacha_blocks == 0 {
+            info!(
+                "storage verifying: segment {} is not in the ledger",
+                segment
+            );
             return Ok(());
         }
-        // TODO: what if the validator does not have this segment
-        let segment = signature.to_bytes()[0] as usize % num_segments;
+        // the same offsets the archivers sampled
+        let samples = sampling_offsets(&sampling_blockhash, num_chacha_blocks);
 
         debug!(
-            "storage verifying: segment: {} identities: {}",
-            segment, NUM_IDENTITIES,
+            "storage verifying: segment: {} identities: {} samples: {:?}",
+            segment, NUM_IDENTITIES, samples
         );
 
-        let mut samples = vec![];
-        for _ in 0..NUM_STORAGE_SAMPLES {
-            samples.push(rng.gen_range(0, 10));
-        }
-        debug!("generated samples: {:?}", samples);
-
-        // TODO: cuda required to generate the reference values
-        // but if it is missing, then we need to take care not to
-        // process storage mining results.
-        if solana_perf::perf_libs::api().is_some() {
+        let mut statew = state.write().unwrap();
+        let keys = if solana_perf::perf_libs::api().is_some() {
+            let keys = statew.storage_keys.clone();
             // Lock the keys, since this is the IV memory,
             // it will be updated in-place by the encryption.
             // Should be overwritten by the proof signatures which replace the
             // key values by the time it runs again.
-
-            let mut statew = state.write().unwrap();
-
             match chacha_cbc_encrypt_file_many_keys(
                 blocktree,
-                segment as u64,
-                statew.slots_per_segment,
+                segment,
+                slots_per_segment,
+                CHACHA_CHUNK_SLOTS,
                 &mut statew.storage_keys,
                 &samples,
             ) {
@@ -446,10 +521,66 @@ impl StorageStage {
                     return Err(e.into());
                 }
             }
-        }
+            keys
+        } else {
+            // only the keys with answers are kept in the reference, the other proofs can't be
+            // checked this turn
+            let mut identities: Vec<_> = statew.archiver_map[segment as usize]
+                .values()
+                .flatten()
+                .filter_map(|proof| {
+                    let idx = get_identity_index_from_signature(&proof.signature);
+                    let key = &statew.storage_keys[idx * KEY_SIZE..(idx + 1) * KEY_SIZE];
+                    if proof.blockhash == sampling_blockhash && key == proof.signature.as_ref() {
+                        Some(idx)
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            identities.sort();
+            identities.dedup();
+            identities.truncate(MAX_CPU_REFERENCE_KEYS);
+            let mut keys = vec![0; statew.storage_keys.len()];
+            for idx in identities {
+                let mut ivec = [0; KEY_SIZE];
+                ivec.copy_from_slice(&statew.storage_keys[idx * KEY_SIZE..(idx + 1) * KEY_SIZE]);
+                let encrypted = tempfile::NamedTempFile::new()?;
+                chacha_cbc_encrypt_ledger(
+                    blocktree,
+                    segment * slots_per_segment,
+                    slots_per_segment,
+                    CHACHA_CHUNK_SLOTS,
+                    encrypted.path(),
+                    &ivec,
+                )?;
+                statew.storage_results[idx] = sample_file(encrypted.path(), &samples)?;
+                keys[idx * KEY_SIZE..(idx + 1) * KEY_SIZE].copy_from_slice(&ivec);
+            }
+            keys
+        };
+        statew.storage_reference = Some(StorageReference {
+            segment,
+            keys,
+            sampling_blockhash,
+        });
         Ok(())
     }
 
+    // The segment and blockhash shared by most of the proofs waiting for validation
+    fn reference_target(state: &Arc<RwLock<StorageStateInner>>) -> Option<(u64, Hash)> {
+        let mut counts = BTreeMap::new();
+        for (segment, proof_map) in state.read().unwrap().archiver_map.iter().enumerate() {
+            for proof in proof_map.values().flatten() {
+                *counts.entry((segment as u64, proof.blockhash)).or_insert(0) += 1;
+            }
+        }
+        counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(target, _)| target)
+    }
+
     fn collect_proofs(
         slot: Slot,
         slots_per_segment: u64,
@@ -498,7 +629,9 @@ impl StorageStage {
         proofs_collected
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn process_entries(
+        node_id: &Pubkey,
         storage_keypair: &Arc<Keypair>,
         storage_state: &Arc<RwLock<StorageStateInner>>,
         bank_receiver: &Receiver<Vec<Arc<Bank>>>,
@@ -507,6 +640,7 @@ impl StorageStage {
         current_key_idx: &mut usize,
         slots_per_turn: u64,
         instruction_sender: &InstructionSender,
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
     ) -> Result<()> {
         let timeout = Duration::new(1, 0);
         storage_slots
@@ -516,8 +650,10 @@ impl StorageStage {
             .pending_root_banks
             .sort_unstable_by(|a, b| b.slot().cmp(&a.slot()));
         // check if any rooted slots were missed leading up to this one and bump slot count and process proofs for each missed root
+        let mut latest_root_bank = None;
         while let Some(bank) = storage_slots.pending_root_banks.pop() {
             if bank.slot() > storage_slots.last_root {
+                latest_root_bank = Some(bank.clone());
                 storage_slots.slot_count += 1;
                 storage_slots.last_root = bank.slot();
                 if storage_slots.slot_count % slots_per_turn == 0 {
@@ -538,41 +674,126 @@ impl StorageStage {
                     }
 
                     // TODO un-ignore this result and be sure to drain all pending proofs
+                    // the turn's hash is the bank hash of its root, which the storage program
+                    // checks against SlotHashes
                     let _ignored = Self::process_turn(
                         &storage_keypair,
                         &storage_state,
                         &blocktree,
-                        bank.last_blockhash(),
+                        bank.hash(),
                         bank.slot(),
                         bank.slots_per_segment(),
                         instruction_sender,
                         total_proofs,
                     );
+                    let sampling_rate =
+                        Self::validation_sampling_rate(node_id, &bank, &storage_state);
                     Self::submit_verifications(
                         get_segment_from_slot(bank.slot(), bank.slots_per_segment()),
                         &storage_state,
                         &storage_keypair,
                         instruction_sender,
+                        sampling_rate,
                     )?
                 }
             }
         }
+        if let Some(bank) = latest_root_bank {
+            Self::push_storage_turn(cluster_info, &bank, storage_slots);
+        }
         Ok(())
     }
 
+    /// Advertise the storage turn committed at root `bank` in gossip for archivers, when it
+    /// changed or often enough for peers not to purge it
+    fn push_storage_turn(
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        bank: &Bank,
+        storage_slots: &mut StorageSlots,
+    ) {
+        let (blockhash, turn_slot) = match committed_storage_turn(bank) {
+            Some(turn) => turn,
+            None => return,
+        };
+        let now = timestamp();
+        if storage_slots.pushed_storage_turn == Some((blockhash, turn_slot))
+            && now.saturating_sub(storage_slots.last_storage_turn_push)
+                < CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS / 2
+        {
+            return;
+        }
+        cluster_info
+            .write()
+            .unwrap()
+            .push_storage_turn(blockhash, turn_slot);
+        storage_slots.pushed_storage_turn = Some((blockhash, turn_slot));
+        storage_slots.last_storage_turn_push = now;
+    }
+
+    fn validation_sampling_rate(
+        node_id: &Pubkey,
+        bank: &Bank,
+        storage_state: &Arc<RwLock<StorageStateInner>>,
+    ) -> f64 {
+        if let Some(rate) = storage_state.read().unwrap().validation_sampling_rate {
+            return rate;
+        }
+        let staked_nodes = staking_utils::staked_nodes(bank);
+        let total_stake = staked_nodes.values().sum();
+        let node_stake = staked_nodes.get(node_id).cloned().unwrap_or(0);
+        default_validation_sampling_rate(node_stake, total_stake)
+    }
+
+    // Check a sampled proof against the answers regenerated in `process_turn()`, a proof mined
+    // over another segment, key or samples than the answers can't be checked
+    fn audit_proof(
+        storage_results: Option<(&[Hash], &StorageReference)>,
+        proof: &Proof,
+    ) -> ProofAudit {
+        let (storage_results, reference) = match storage_results {
+            Some(storage_results) => storage_results,
+            None => return ProofAudit::Unverified,
+        };
+        let idx = get_identity_index_from_signature(&proof.signature);
+        let key = &reference.keys[idx * KEY_SIZE..(idx + 1) * KEY_SIZE];
+        if proof.segment_index != reference.segment
+            || key != proof.signature.as_ref()
+            || reference.sampling_blockhash != proof.blockhash
+        {
+            return ProofAudit::Unverified;
+        }
+        if storage_results[idx] == proof.sha_state {
+            ProofAudit::Passed
+        } else {
+            ProofAudit::Failed
+        }
+    }
+
     fn submit_verifications(
         current_segment: u64,
         storage_state: &Arc<RwLock<StorageStateInner>>,
         storage_keypair: &Arc<Keypair>,
         ix_sender: &Sender<Instruction>,
+        sampling_rate: f64,
     ) -> Result<()> {
         // bundle up mining submissions from archivers
         // and submit them in a tx to the leader to get rewarded.
         let mut w_state = storage_state.write().unwrap();
+        let StorageStateInner {
+            archiver_map,
+            storage_results,
+            storage_reference,
+            ..
+        } = &mut *w_state;
+        let storage_results = storage_reference
+            .as_ref()
+            .map(|reference| (storage_results.as_slice(), reference));
         let mut max_proof_mask = 0;
         let proof_mask_limit = storage_instruction::proof_mask_limit();
-        let instructions: Vec<_> = w_state
-            .archiver_map
+        let mut rng = thread_rng();
+        let mut num_proofs = 0;
+        let mut num_sampled = 0;
+        let instructions: Vec<_> = archiver_map
             .iter_mut()
             .enumerate()
             .flat_map(|(_, proof_map)| {
@@ -585,13 +806,30 @@ impl StorageStage {
                                 None
                             } else {
                                 max_proof_mask = cmp::max(max_proof_mask, proofs.len());
-                                Some((
-                                    *id,
-                                    proofs
-                                        .drain(..)
-                                        .map(|_| ProofStatus::Valid)
-                                        .collect::<Vec<_>>(),
-                                ))
+                                let statuses = proofs
+                                    .drain(..)
+                                    .map(|proof| {
+                                        num_proofs += 1;
+                                        if !rng.gen_bool(sampling_rate) {
+                                            // not audited this turn
+                                            return ProofStatus::Valid;
+                                        }
+                                        num_sampled += 1;
+                                        let audit = Self::audit_proof(storage_results, &proof);
+                                        datapoint_info!(
+                                            "storage-proof-audit",
+                                            ("segment", proof.segment_index as i64, i64),
+                                            ("archiver", id.to_string(), String),
+                                            ("result", audit.as_str().to_string(), String)
+                                        );
+                                        if audit == ProofAudit::Failed {
+                                            ProofStatus::NotValid
+                                        } else {
+                                            ProofStatus::Valid
+                                        }
+                                    })
+                                    .collect::<Vec<_>>();
+                                Some((*id, statuses))
                             }
                         } else {
                             None
@@ -619,6 +857,14 @@ impl StorageStage {
             })
             .flatten()
             .collect();
+        if num_proofs > 0 {
+            datapoint_info!(
+                "storage-proof-audit-coverage",
+                ("segment", current_segment as i64, i64),
+                ("proofs", num_proofs as i64, i64),
+                ("sampled", num_sampled as i64, i64)
+            );
+        }
         let res: std::result::Result<_, _> = instructions
             .into_iter()
             .map(|ix| {
@@ -647,8 +893,9 @@ mod tests {
     use super::*;
     use crate::genesis_utils::{create_genesis_config, GenesisConfigInfo};
     use rayon::prelude::*;
+    use solana_ledger::{entry::create_ticks, get_tmp_ledger_path};
     use solana_runtime::bank::Bank;
-    use solana_sdk::hash::Hasher;
+    use solana_sdk::hash::{hash, Hasher};
     use solana_sdk::signature::{Keypair, KeypairUtil};
     use std::cmp::{max, min};
     use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
@@ -688,6 +935,192 @@ mod tests {
         storage_stage.join().unwrap();
     }
 
+    #[test]
+    fn test_default_validation_sampling_rate() {
+        assert_eq!(default_validation_sampling_rate(0, 0), 1.0);
+        assert_eq!(
+            default_validation_sampling_rate(0, 100),
+            MIN_VALIDATION_SAMPLING_RATE
+        );
+        assert_eq!(default_validation_sampling_rate(100, 100), 1.0);
+        assert!(
+            default_validation_sampling_rate(10, 100) < default_validation_sampling_rate(50, 100)
+        );
+    }
+
+    #[test]
+    fn test_audit_proof() {
+        let keypair = Keypair::new();
+        let signature = keypair.sign_message(Hash::default().as_ref());
+        let idx = get_identity_index_from_signature(&signature);
+        let mut storage_results = vec![Hash::default(); NUM_IDENTITIES];
+        storage_results[idx] = hash(&[1u8]);
+        let mut keys = vec![0u8; KEY_SIZE * NUM_IDENTITIES];
+        keys[idx * KEY_SIZE..(idx + 1) * KEY_SIZE].copy_from_slice(signature.as_ref());
+        let blockhash = hash(&[3u8]);
+        let mut reference = StorageReference {
+            segment: 1,
+            keys,
+            sampling_blockhash: blockhash,
+        };
+
+        let mut proof = Proof {
+            signature,
+            blockhash,
+            sha_state: hash(&[1u8]),
+            segment_index: 1,
+        };
+        assert_eq!(
+            StorageStage::audit_proof(Some((storage_results.as_slice(), &reference)), &proof),
+            ProofAudit::Passed
+        );
+        assert_eq!(
+            StorageStage::audit_proof(None, &proof),
+            ProofAudit::Unverified
+        );
+        proof.sha_state = hash(&[2u8]);
+        assert_eq!(
+            StorageStage::audit_proof(Some((storage_results.as_slice(), &reference)), &proof),
+            ProofAudit::Failed
+        );
+
+        // answers generated for another segment, key or samples never fail a proof
+        proof.segment_index = 2;
+        assert_eq!(
+            StorageStage::audit_proof(Some((storage_results.as_slice(), &reference)), &proof),
+            ProofAudit::Unverified
+        );
+        proof.segment_index = 1;
+        proof.blockhash = hash(&[4u8]);
+        assert_eq!(
+            StorageStage::audit_proof(Some((storage_results.as_slice(), &reference)), &proof),
+            ProofAudit::Unverified
+        );
+        proof.blockhash = blockhash;
+        reference.keys[idx * KEY_SIZE] ^= 1;
+        assert_eq!(
+            StorageStage::audit_proof(Some((storage_results.as_slice(), &reference)), &proof),
+            ProofAudit::Unverified
+        );
+    }
+
+    #[test]
+    fn test_process_turn_audits_proofs() {
+        solana_logger::setup();
+        let slots_per_segment = 32;
+        let ledger_path = get_tmp_ledger_path!();
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        blocktree
+            .write_entries(
+                0,
+                0,
+                0,
+                16,
+                Some(0),
+                true,
+                &Arc::new(Keypair::new()),
+                create_ticks(slots_per_segment, 0, Hash::default()),
+                0,
+            )
+            .unwrap();
+
+        // two archivers mining segment 0 with different keys
+        let blockhash = hash(&[1u8]);
+        let good_signature = Keypair::new().sign_message(blockhash.as_ref());
+        let bad_signature = loop {
+            let signature = Keypair::new().sign_message(blockhash.as_ref());
+            if get_identity_index_from_signature(&signature)
+                != get_identity_index_from_signature(&good_signature)
+            {
+                break signature;
+            }
+        };
+
+        // the answer an archiver mines with `good_signature`
+        let encrypted = tempfile::NamedTempFile::new().unwrap();
+        let mut ivec = [0; KEY_SIZE];
+        ivec.copy_from_slice(good_signature.as_ref());
+        let num_encrypted_bytes = chacha_cbc_encrypt_ledger(
+            &blocktree,
+            0,
+            slots_per_segment,
+            CHACHA_CHUNK_SLOTS,
+            encrypted.path(),
+            &ivec,
+        )
+        .unwrap();
+        let offsets = sampling_offsets(&blockhash, num_encrypted_bytes / CHACHA_BLOCK_SIZE);
+        let good_sha_state = sample_file(encrypted.path(), &offsets).unwrap();
+
+        let storage_state =
+            StorageState::new(&Hash::default(), SLOTS_PER_TURN_TEST, slots_per_segment);
+        let passing = Pubkey::new_rand();
+        let failing = Pubkey::new_rand();
+        {
+            let mut state = storage_state.state.write().unwrap();
+            state.archiver_map.resize(1, HashMap::new());
+            for (id, signature, sha_state) in vec![
+                (passing, good_signature, good_sha_state),
+                (failing, bad_signature, hash(&[2u8])),
+            ] {
+                let idx = get_identity_index_from_signature(&signature);
+                state.storage_keys[idx * KEY_SIZE..(idx + 1) * KEY_SIZE]
+                    .copy_from_slice(signature.as_ref());
+                state.archiver_map[0].insert(
+                    id,
+                    vec![Proof {
+                        signature,
+                        blockhash,
+                        sha_state,
+                        segment_index: 0,
+                    }],
+                );
+            }
+        }
+
+        let storage_keypair = Arc::new(Keypair::new());
+        let (ix_sender, ix_receiver) = channel();
+        StorageStage::process_turn(
+            &storage_keypair,
+            &storage_state.state,
+            &blocktree,
+            hash(&[3u8]),
+            slots_per_segment,
+            slots_per_segment,
+            &ix_sender,
+            2,
+        )
+        .unwrap();
+        StorageStage::submit_verifications(
+            1,
+            &storage_state.state,
+            &storage_keypair,
+            &ix_sender,
+            1.0,
+        )
+        .unwrap();
+
+        // the turn's advertisement, then the validations
+        let statuses: HashMap<_, _> = ix_receiver
+            .try_iter()
+            .skip(1)
+            .flat_map(|ix| match bincode::deserialize(&ix.data).unwrap() {
+                storage_instruction::StorageInstruction::ProofValidation { proofs, .. } => ix
+                    .accounts[2..]
+                    .iter()
+                    .map(|meta| meta.pubkey)
+                    .zip(proofs)
+                    .collect::<Vec<_>>(),
+                _ => panic!("not a proof validation"),
+            })
+            .collect();
+        assert_eq!(statuses[&passing], vec![ProofStatus::Valid]);
+        assert_eq!(statuses[&failing], vec![ProofStatus::NotValid]);
+
+        drop(blocktree);
+        Blocktree::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_signature_distribution() {
         // See that signatures have an even-ish distribution..
@@ -1,19 +1,27 @@
 //! The `streamer` module defines a set of services for efficiently pulling data from UDP sockets.
 //!
 
-use crate::packet::{self, send_to, Packets, PacketsRecycler, PACKETS_PER_BATCH};
+use crate::packet::{
+    self, limited_deserialize, send_to, Packets, PacketsRecycler, PACKETS_PER_BATCH,
+};
 use crate::recvmmsg::NUM_RCVMMSGS;
 use crate::result::{Error, Result};
+use crate::thread_affinity::{self, ThreadRole};
+use crate::thread_registry;
+use crate::transaction_trace::{self, TraceStage};
+use serde::de::DeserializeOwned;
 use solana_sdk::timing::duration_as_ms;
-use std::net::UdpSocket;
+use std::net::{SocketAddr, UdpSocket};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::sync::Arc;
-use std::thread::{Builder, JoinHandle};
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
 
 pub type PacketReceiver = Receiver<Packets>;
 pub type PacketSender = Sender<Packets>;
+pub type TypedReceiver<T> = Receiver<Vec<(T, SocketAddr)>>;
+pub type TypedSender<T> = Sender<Vec<(T, SocketAddr)>>;
 
 fn recv_loop(
     sock: &UdpSocket,
@@ -21,6 +29,7 @@ fn recv_loop(
     channel: &PacketSender,
     recycler: &PacketsRecycler,
     name: &'static str,
+    trace_stage: Option<TraceStage>,
 ) -> Result<()> {
     let mut recv_count = 0;
     let mut call_count = 0;
@@ -40,6 +49,9 @@ fn recv_loop(
                 }
                 recv_count += len;
                 call_count += 1;
+                if let Some(trace_stage) = trace_stage {
+                    transaction_trace::record_packets(trace_stage, &msgs);
+                }
                 channel.send(msgs)?;
                 break;
             }
@@ -66,18 +78,37 @@ pub fn receiver(
     packet_sender: PacketSender,
     recycler: PacketsRecycler,
     name: &'static str,
+) -> JoinHandle<()> {
+    traced_receiver(sock, exit, packet_sender, recycler, name, None)
+}
+
+/// A `receiver` that records the sampled transactions it receives as reaching `trace_stage`,
+/// see `transaction_trace`
+pub fn traced_receiver(
+    sock: Arc<UdpSocket>,
+    exit: &Arc<AtomicBool>,
+    packet_sender: PacketSender,
+    recycler: PacketsRecycler,
+    name: &'static str,
+    trace_stage: Option<TraceStage>,
 ) -> JoinHandle<()> {
     let res = sock.set_read_timeout(Some(Duration::new(1, 0)));
     if res.is_err() {
         panic!("streamer::receiver set_read_timeout error");
     }
     let exit = exit.clone();
-    Builder::new()
-        .name("solana-receiver".to_string())
-        .spawn(move || {
-            let _ = recv_loop(&sock, exit, &packet_sender, &recycler.clone(), name);
-        })
-        .unwrap()
+    thread_registry::spawn("receiver", Some(exit.clone()), move || {
+        thread_affinity::apply(ThreadRole::Network);
+        let _ = recv_loop(
+            &sock,
+            exit,
+            &packet_sender,
+            &recycler.clone(),
+            name,
+            trace_stage,
+        );
+    })
+    .unwrap()
 }
 
 fn recv_send(sock: &UdpSocket, r: &PacketReceiver) -> Result<()> {
@@ -107,10 +138,106 @@ pub fn recv_batch(recvr: &PacketReceiver, max_batch: usize) -> Result<(Vec<Packe
     Ok((batch, len, duration_as_ms(&recv_start.elapsed())))
 }
 
+fn typed_recv_loop<T, E, F>(
+    packet_receiver: &PacketReceiver,
+    exit: &Arc<AtomicBool>,
+    sender: &TypedSender<T>,
+    name: &'static str,
+    decode: F,
+) -> Result<()>
+where
+    E: std::fmt::Debug,
+    F: Fn(&[u8]) -> std::result::Result<T, E>,
+{
+    let mut deserialized = 0;
+    let mut errors = 0;
+    let mut now = Instant::now();
+    loop {
+        if exit.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let packets = match packet_receiver.recv_timeout(Duration::new(1, 0)) {
+            Ok(packets) => packets,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        };
+        let mut msgs = Vec::with_capacity(packets.packets.len());
+        for packet in &packets.packets {
+            match decode(&packet.data[..packet.meta.size]) {
+                Ok(msg) => msgs.push((msg, packet.meta.addr())),
+                Err(e) => {
+                    trace!(
+                        "{} invalid packet from {}: {:?}",
+                        name,
+                        packet.meta.addr(),
+                        e
+                    );
+                    errors += 1;
+                }
+            }
+        }
+        deserialized += msgs.len();
+        if !msgs.is_empty() {
+            sender.send(msgs)?;
+        }
+        if now.elapsed().as_secs() > 1 {
+            datapoint_debug!(
+                name,
+                ("deserialized", deserialized as i64, i64),
+                ("deserialize_errors", errors as i64, i64),
+            );
+            deserialized = 0;
+            errors = 0;
+            now = Instant::now();
+        }
+    }
+}
+
+/// Deserializes every packet from `packet_receiver` into a `T`, with the same size limit
+/// as `limited_deserialize`, and forwards each batch as `(T, sender address)` pairs. Packets
+/// that fail to deserialize are dropped and counted under the `name` datapoint.
+pub fn typed_receiver<T>(
+    packet_receiver: PacketReceiver,
+    exit: &Arc<AtomicBool>,
+    sender: TypedSender<T>,
+    name: &'static str,
+) -> JoinHandle<()>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    decoding_receiver(packet_receiver, exit, sender, name, |data: &[u8]| {
+        limited_deserialize::<T>(data)
+    })
+}
+
+/// Like `typed_receiver`, but packets are decoded with `decode` instead of bincode
+pub fn decoding_receiver<T, E, F>(
+    packet_receiver: PacketReceiver,
+    exit: &Arc<AtomicBool>,
+    sender: TypedSender<T>,
+    name: &'static str,
+    decode: F,
+) -> JoinHandle<()>
+where
+    T: Send + 'static,
+    E: std::fmt::Debug,
+    F: Fn(&[u8]) -> std::result::Result<T, E> + Send + 'static,
+{
+    let exit = exit.clone();
+    thread_registry::spawn(
+        &format!("typed-receiver-{}", name),
+        Some(exit.clone()),
+        move || {
+            let _ = typed_recv_loop(&packet_receiver, &exit, &sender, name, decode);
+        },
+    )
+    .unwrap()
+}
+
 pub fn responder(name: &'static str, sock: Arc<UdpSocket>, r: PacketReceiver) -> JoinHandle<()> {
-    Builder::new()
-        .name(format!("solana-responder-{}", name))
-        .spawn(move || loop {
+    thread_registry::spawn(&format!("responder-{}", name), None, move || {
+        thread_affinity::apply(ThreadRole::Network);
+        loop {
             if let Err(e) = recv_send(&sock, &r) {
                 match e {
                     Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => break,
@@ -118,8 +245,9 @@ pub fn responder(name: &'static str, sock: Arc<UdpSocket>, r: PacketReceiver) ->
                     _ => warn!("{} responder error: {:?}", name, e),
                 }
             }
-        })
-        .unwrap()
+        }
+    })
+    .unwrap()
 }
 
 #[cfg(test)]
@@ -191,4 +319,27 @@ mod test {
         t_receiver.join().expect("join");
         t_responder.join().expect("join");
     }
+
+    #[test]
+    fn streamer_typed_receiver_test() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (s_packets, r_packets) = channel();
+        let (s_typed, r_typed) = channel();
+        let t_typed = typed_receiver::<u64>(r_packets, &exit, s_typed, "test");
+
+        let addr = socketaddr!("127.0.0.1:1234");
+        let mut garbage = Packet::default();
+        garbage.meta.size = 1;
+        let msgs = Packets::new(vec![
+            Packet::from_data(&addr, 7u64),
+            garbage,
+            Packet::from_data(&addr, 42u64),
+        ]);
+        s_packets.send(msgs).expect("send");
+
+        let received = r_typed.recv_timeout(Duration::new(1, 0)).expect("recv");
+        assert_eq!(received, vec![(7, addr), (42, addr)]);
+        exit.store(true, Ordering::Relaxed);
+        t_typed.join().expect("join");
+    }
 }
@@ -0,0 +1,303 @@
+//! The `thread_affinity` module pins the validator's latency sensitive threads to configured
+//! cores and raises the priority of the PoH thread, so OS scheduling jitter on a busy machine
+//! doesn't make ticks drift. The layout is set once at startup, each thread applies the part
+//! for its role when it starts, and what was applied can be reported.
+
+use lazy_static::lazy_static;
+use solana_metrics::datapoint_info;
+use std::{
+    io::{self, ErrorKind},
+    sync::RwLock,
+    thread,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThreadRole {
+    /// The PoH tick producer
+    Poh,
+    /// Transaction signature verification
+    Sigverify,
+    /// Banking stage transaction processing
+    Banking,
+    /// Packet receivers and responders
+    Network,
+}
+
+impl ThreadRole {
+    pub fn name(self) -> &'static str {
+        match self {
+            ThreadRole::Poh => "poh",
+            ThreadRole::Sigverify => "sigverify",
+            ThreadRole::Banking => "banking",
+            ThreadRole::Network => "network",
+        }
+    }
+}
+
+/// Cores each thread role may run on, an empty list leaves the role to the OS scheduler
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThreadAffinityConfig {
+    /// Without any, the PoH tick producer is pinned to the first core
+    pub poh_cores: Vec<usize>,
+    pub sigverify_cores: Vec<usize>,
+    pub banking_cores: Vec<usize>,
+    pub network_cores: Vec<usize>,
+    /// Nice level of the PoH tick producer, negative levels raise its priority and need
+    /// CAP_SYS_NICE
+    pub poh_nice: Option<i32>,
+}
+
+impl ThreadAffinityConfig {
+    pub fn cores(&self, role: ThreadRole) -> &[usize] {
+        match role {
+            ThreadRole::Poh => &self.poh_cores,
+            ThreadRole::Sigverify => &self.sigverify_cores,
+            ThreadRole::Banking => &self.banking_cores,
+            ThreadRole::Network => &self.network_cores,
+        }
+    }
+
+    pub fn nice(&self, role: ThreadRole) -> Option<i32> {
+        match role {
+            ThreadRole::Poh => self.poh_nice,
+            _ => None,
+        }
+    }
+}
+
+/// The layout one thread applied
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppliedAffinity {
+    pub thread: String,
+    pub role: ThreadRole,
+    pub cores: Vec<usize>,
+    pub nice: Option<i32>,
+    /// Why the cores or nice level couldn't be applied
+    pub error: Option<String>,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<ThreadAffinityConfig> = RwLock::new(ThreadAffinityConfig::default());
+    static ref APPLIED: RwLock<Vec<AppliedAffinity>> = RwLock::new(vec![]);
+}
+
+/// Set the layout threads started from now on apply
+pub fn set_config(config: ThreadAffinityConfig) {
+    *CONFIG.write().unwrap() = config;
+    APPLIED.write().unwrap().clear();
+}
+
+pub fn config() -> ThreadAffinityConfig {
+    CONFIG.read().unwrap().clone()
+}
+
+/// Pin the calling thread to the cores configured for `role` and set its nice level, nothing
+/// is done for a role without either. Returns whether cores are configured for `role`.
+pub fn apply(role: ThreadRole) -> bool {
+    let (cores, nice) = {
+        let config = CONFIG.read().unwrap();
+        (config.cores(role).to_vec(), config.nice(role))
+    };
+    if cores.is_empty() && nice.is_none() {
+        return false;
+    }
+
+    let mut errors = vec![];
+    if !cores.is_empty() {
+        if let Err(err) = set_thread_cores(&cores) {
+            errors.push(format!("cores {:?}: {}", cores, err));
+        }
+    }
+    if let Some(nice) = nice {
+        if let Err(err) = set_thread_nice(nice) {
+            errors.push(format!("nice {}: {}", nice, err));
+        }
+    }
+    let thread = thread::current().name().unwrap_or("unnamed").to_string();
+    let error = if errors.is_empty() {
+        info!(
+            "{} thread {} runs on cores {:?} at nice {:?}",
+            role.name(),
+            thread,
+            cores,
+            nice
+        );
+        None
+    } else {
+        let error = errors.join(", ");
+        warn!(
+            "unable to apply the {} thread layout to {}: {}",
+            role.name(),
+            thread,
+            error
+        );
+        Some(error)
+    };
+    let pinned = !cores.is_empty();
+    APPLIED.write().unwrap().push(AppliedAffinity {
+        thread,
+        role,
+        cores,
+        nice,
+        error,
+    });
+    pinned
+}
+
+/// The layout applied by the threads started since the last `set_config()`
+pub fn applied() -> Vec<AppliedAffinity> {
+    APPLIED.read().unwrap().clone()
+}
+
+/// Log the applied layout and submit it as metrics
+pub fn report() {
+    let applied = applied();
+    if applied.is_empty() {
+        return;
+    }
+    info!("thread layout:");
+    for affinity in &applied {
+        info!(
+            "  {:<10} {:<40} cores {:?} nice {:?}{}",
+            affinity.role.name(),
+            affinity.thread,
+            affinity.cores,
+            affinity.nice,
+            affinity
+                .error
+                .as_ref()
+                .map(|error| format!(" FAILED: {}", error))
+                .unwrap_or_default()
+        );
+        datapoint_info!(
+            "thread_affinity-applied",
+            ("role", affinity.role.name().to_string(), String),
+            ("thread", affinity.thread.clone(), String),
+            ("cores", format!("{:?}", affinity.cores), String),
+            ("nice", i64::from(affinity.nice.unwrap_or(0)), i64),
+            ("ok", affinity.error.is_none(), bool)
+        );
+    }
+}
+
+/// Parse a core list such as `0,2-4`
+pub fn parse_core_list(list: &str) -> Result<Vec<usize>, String> {
+    let mut cores = vec![];
+    for part in list.split(',') {
+        let part = part.trim();
+        let parse = |core: &str| {
+            core.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("invalid core {:?} in {:?}", core, list))
+        };
+        let mut range = part.splitn(2, '-');
+        let first = parse(range.next().unwrap())?;
+        let last = match range.next() {
+            Some(last) => parse(last)?,
+            None => first,
+        };
+        if last < first {
+            return Err(format!("invalid core range {:?} in {:?}", part, list));
+        }
+        cores.extend(first..=last);
+    }
+    cores.sort_unstable();
+    cores.dedup();
+    Ok(cores)
+}
+
+/// Clap validator for core lists, see `parse_core_list()`
+pub fn is_core_list(string: String) -> Result<(), String> {
+    parse_core_list(&string).map(|_| ())
+}
+
+#[cfg(target_os = "linux")]
+fn set_thread_cores(cores: &[usize]) -> io::Result<()> {
+    use nix::{
+        sched::{sched_setaffinity, CpuSet},
+        unistd::Pid,
+    };
+    let to_io_error = |err: nix::Error| io::Error::new(ErrorKind::Other, err.to_string());
+    let mut cpu_set = CpuSet::new();
+    for core in cores {
+        cpu_set.set(*core).map_err(to_io_error)?;
+    }
+    // pid 0 is the calling thread
+    sched_setaffinity(Pid::from_raw(0), &cpu_set).map_err(to_io_error)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_thread_cores(cores: &[usize]) -> io::Result<()> {
+    match cores {
+        [core] => {
+            core_affinity::set_for_current(core_affinity::CoreId { id: *core });
+            Ok(())
+        }
+        _ => Err(io::Error::new(
+            ErrorKind::Other,
+            "pinning a thread to several cores is not supported on this platform",
+        )),
+    }
+}
+
+/// Set the nice level of the calling thread only
+#[cfg(target_os = "linux")]
+pub fn set_thread_nice(nice: i32) -> io::Result<()> {
+    // On Linux the nice level is a per-thread attribute, addressed by thread id
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_thread_nice(_nice: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Other,
+        "per-thread nice levels are not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_core_list() {
+        assert_eq!(parse_core_list("3"), Ok(vec![3]));
+        assert_eq!(parse_core_list("4,0-2, 1"), Ok(vec![0, 1, 2, 4]));
+        assert!(parse_core_list("").is_err());
+        assert!(parse_core_list("2-1").is_err());
+        assert!(parse_core_list("a").is_err());
+        assert!(is_core_list("0-1,3".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_apply() {
+        let config = ThreadAffinityConfig {
+            network_cores: vec![0],
+            ..ThreadAffinityConfig::default()
+        };
+        set_config(config.clone());
+        assert_eq!(super::config(), config);
+
+        let t = thread::Builder::new()
+            .name("test-thread-affinity".to_string())
+            .spawn(|| (apply(ThreadRole::Banking), apply(ThreadRole::Network)))
+            .unwrap();
+        assert_eq!(t.join().unwrap(), (false, true));
+        // threads of other tests may apply the layout too
+        let applied: Vec<_> = applied()
+            .into_iter()
+            .filter(|affinity| affinity.thread == "test-thread-affinity")
+            .collect();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].role, ThreadRole::Network);
+        assert_eq!(applied[0].cores, vec![0]);
+        report();
+
+        set_config(ThreadAffinityConfig::default());
+    }
+}
@@ -0,0 +1,170 @@
+//! The `thread_registry` module spawns named service threads and keeps track of them.
+//! A panic in a registered thread is reported through a metric and raises the thread's
+//! exit flag, so the rest of the node shuts down instead of waiting to notice it at join.
+//! A panic hook records where the thread panicked for debugging.
+
+use lazy_static::lazy_static;
+use solana_metrics::inc_new_counter_error;
+use solana_sdk::timing::timestamp;
+use std::{
+    collections::HashMap,
+    io, panic,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Once, RwLock,
+    },
+    thread::{self, Builder, JoinHandle, ThreadId},
+};
+
+const THREAD_NAME_PREFIX: &str = "solana-";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThreadInfo {
+    pub name: String,
+    /// wallclock time in ms when the thread started
+    pub started: u64,
+    pub panicked: bool,
+    /// panic message and location, if the panic hook saw it
+    pub panic_message: Option<String>,
+}
+
+struct RegisteredThread {
+    info: ThreadInfo,
+    exit: Option<Arc<AtomicBool>>,
+}
+
+lazy_static! {
+    static ref REGISTRY: RwLock<HashMap<ThreadId, RegisteredThread>> = RwLock::new(HashMap::new());
+}
+
+// Removes the thread from the registry when it finishes. If it is unwinding from a panic,
+// the entry is kept for debugging and the exit flag is raised instead.
+struct RegistrationGuard;
+
+impl Drop for RegistrationGuard {
+    fn drop(&mut self) {
+        let id = thread::current().id();
+        let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+        if !thread::panicking() {
+            registry.remove(&id);
+        } else if let Some(thread) = registry.get_mut(&id) {
+            thread.info.panicked = true;
+            inc_new_counter_error!("thread_registry-panic", 1, 1);
+            error!("thread {} panicked, signalling exit", thread.info.name);
+            if let Some(exit) = &thread.exit {
+                exit.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Prefixes `name` with `solana-` unless it already is
+pub fn thread_name(name: &str) -> String {
+    if name.starts_with(THREAD_NAME_PREFIX) {
+        name.to_string()
+    } else {
+        format!("{}{}", THREAD_NAME_PREFIX, name)
+    }
+}
+
+fn install_panic_hook() {
+    static SET_HOOK: Once = Once::new();
+    SET_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            default_hook(info);
+            let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+            if let Some(thread) = registry.get_mut(&thread::current().id()) {
+                thread.info.panic_message = Some(info.to_string());
+            }
+        }));
+    });
+}
+
+/// Spawns a thread named `name` and registers it. If the thread panics, `exit` is set.
+pub fn spawn<F, T>(name: &str, exit: Option<Arc<AtomicBool>>, f: F) -> io::Result<JoinHandle<T>>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    install_panic_hook();
+    let name = thread_name(name);
+    Builder::new().name(name.clone()).spawn(move || {
+        REGISTRY.write().unwrap().insert(
+            thread::current().id(),
+            RegisteredThread {
+                info: ThreadInfo {
+                    name,
+                    started: timestamp(),
+                    panicked: false,
+                    panic_message: None,
+                },
+                exit,
+            },
+        );
+        let _guard = RegistrationGuard;
+        f()
+    })
+}
+
+/// Threads currently running, plus any that panicked, sorted by name
+pub fn registered_threads() -> Vec<ThreadInfo> {
+    let mut threads: Vec<_> = REGISTRY
+        .read()
+        .unwrap()
+        .values()
+        .map(|thread| thread.info.clone())
+        .collect();
+    threads.sort_by(|a, b| a.name.cmp(&b.name));
+    threads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn find(name: &str) -> Option<ThreadInfo> {
+        registered_threads()
+            .into_iter()
+            .find(|info| info.name == name)
+    }
+
+    #[test]
+    fn test_thread_name() {
+        assert_eq!(thread_name("archiver"), "solana-archiver");
+        assert_eq!(thread_name("solana-archiver"), "solana-archiver");
+    }
+
+    #[test]
+    fn test_spawn_registers_thread() {
+        let (sender, receiver) = channel();
+        let t = spawn("test-registry-run", None, move || {
+            receiver.recv().unwrap();
+            thread::current().name().map(str::to_string)
+        })
+        .unwrap();
+        while find("solana-test-registry-run").is_none() {
+            thread::yield_now();
+        }
+        assert!(!find("solana-test-registry-run").unwrap().panicked);
+        sender.send(()).unwrap();
+        assert_eq!(
+            t.join().unwrap(),
+            Some("solana-test-registry-run".to_string())
+        );
+        assert!(find("solana-test-registry-run").is_none());
+    }
+
+    #[test]
+    fn test_spawn_panic_sets_exit() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let t = spawn("test-registry-panic", Some(exit.clone()), || {
+            panic!("test panic");
+        })
+        .unwrap();
+        assert!(t.join().is_err());
+        assert!(exit.load(Ordering::Relaxed));
+        assert!(find("solana-test-registry-panic").unwrap().panicked);
+    }
+}
@@ -7,10 +7,11 @@ use crate::cluster_info::ClusterInfo;
 use crate::cluster_info_vote_listener::ClusterInfoVoteListener;
 use crate::fetch_stage::FetchStage;
 use crate::poh_recorder::{PohRecorder, WorkingBankEntry};
+use crate::sender_stake_stage::SenderStakeStage;
 use crate::sigverify::TransactionSigVerifier;
 use crate::sigverify_stage::{DisabledSigVerifier, SigVerifyStage};
 use crossbeam_channel::unbounded;
-use solana_ledger::blocktree::Blocktree;
+use solana_ledger::{bank_forks::BankForks, blocktree::Blocktree};
 use std::net::UdpSocket;
 use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::{channel, Receiver};
@@ -20,6 +21,7 @@ use std::thread;
 pub struct Tpu {
     fetch_stage: FetchStage,
     sigverify_stage: SigVerifyStage,
+    sender_stake_stage: SenderStakeStage,
     banking_stage: BankingStage,
     cluster_info_vote_listener: ClusterInfoVoteListener,
     broadcast_stage: BroadcastStage,
@@ -36,6 +38,7 @@ impl Tpu {
         broadcast_socket: UdpSocket,
         sigverify_disabled: bool,
         blocktree: &Arc<Blocktree>,
+        bank_forks: &Arc<RwLock<BankForks>>,
         broadcast_type: &BroadcastStageType,
         exit: &Arc<AtomicBool>,
         shred_version: u16,
@@ -58,6 +61,10 @@ impl Tpu {
             SigVerifyStage::new(packet_receiver, verified_sender.clone(), verifier)
         };
 
+        let (annotated_sender, annotated_receiver) = unbounded();
+        let sender_stake_stage =
+            SenderStakeStage::new(verified_receiver, annotated_sender, bank_forks);
+
         let (verified_vote_sender, verified_vote_receiver) = unbounded();
         let cluster_info_vote_listener = ClusterInfoVoteListener::new(
             &exit,
@@ -70,7 +77,7 @@ impl Tpu {
         let banking_stage = BankingStage::new(
             &cluster_info,
             poh_recorder,
-            verified_receiver,
+            annotated_receiver,
             verified_vote_receiver,
         );
 
@@ -86,6 +93,7 @@ impl Tpu {
         Self {
             fetch_stage,
             sigverify_stage,
+            sender_stake_stage,
             banking_stage,
             cluster_info_vote_listener,
             broadcast_stage,
@@ -96,6 +104,7 @@ impl Tpu {
         let mut results = vec![];
         results.push(self.fetch_stage.join());
         results.push(self.sigverify_stage.join());
+        results.push(self.sender_stake_stage.join());
         results.push(self.cluster_info_vote_listener.join());
         results.push(self.banking_stage.join());
         let broadcast_result = self.broadcast_stage.join();
@@ -0,0 +1,258 @@
+//! The `transaction_trace` module follows a sample of transactions through this node's TPU.
+//! A transaction is sampled when the hash of its first signature is a multiple of the sample
+//! rate, so every stage agrees on the sample without passing anything along. Each stage a
+//! sampled transaction reaches reports a `transaction-trace` datapoint with the time spent
+//! since the previous stage. Tracing is off until `set_sample_rate` is called.
+
+use lazy_static::lazy_static;
+use solana_ledger::entry::Entry;
+use solana_metrics::datapoint_info;
+use solana_perf::packet::{Packet, Packets};
+use solana_sdk::{signature::Signature, transaction::Transaction};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    mem::size_of,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Most transactions traced at once, no new trace starts while this many are in flight
+const MAX_TRACED_TRANSACTIONS: usize = 4096;
+
+/// Traces that haven't reached broadcast this long after their last stage are dropped
+const TRACE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceStage {
+    /// Received on a TPU socket
+    Ingest,
+    /// Signatures verified
+    SigVerify,
+    /// Executed by the banking stage
+    Banking,
+    /// Recorded into a PoH entry
+    PohRecord,
+    /// Shredded and sent to the cluster, ending the trace
+    Broadcast,
+}
+
+impl TraceStage {
+    fn name(self) -> &'static str {
+        match self {
+            TraceStage::Ingest => "ingest",
+            TraceStage::SigVerify => "sigverify",
+            TraceStage::Banking => "banking",
+            TraceStage::PohRecord => "poh_record",
+            TraceStage::Broadcast => "broadcast",
+        }
+    }
+}
+
+struct Trace {
+    started: Instant,
+    last_stage: TraceStage,
+    last_seen: Instant,
+}
+
+static SAMPLE_RATE: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref TRACES: Mutex<HashMap<Signature, Trace>> = Mutex::new(HashMap::new());
+}
+
+/// Trace one in `sample_rate` transactions, 0 turns tracing off
+pub fn set_sample_rate(sample_rate: u64) {
+    SAMPLE_RATE.store(sample_rate, Ordering::Relaxed);
+    if sample_rate == 0 {
+        TRACES.lock().unwrap().clear();
+    }
+}
+
+fn is_sampled(signature: &Signature, sample_rate: u64) -> bool {
+    let mut hasher = DefaultHasher::new();
+    signature.hash(&mut hasher);
+    sample_rate != 0 && hasher.finish() % sample_rate == 0
+}
+
+// The first signature of the transaction in `packet`, without deserializing the transaction
+fn packet_signature(packet: &Packet) -> Option<Signature> {
+    let signatures_end = 1 + size_of::<Signature>();
+    // A signature count over 127 takes more than one byte, no valid transaction has that many
+    let num_signatures = packet.data[0];
+    if num_signatures == 0 || num_signatures & 0x80 != 0 || packet.meta.size < signatures_end {
+        return None;
+    }
+    Some(Signature::new(&packet.data[1..signatures_end]))
+}
+
+/// Record the sampled transactions of `packets` that weren't discarded as reaching `stage`
+pub fn record_packets(stage: TraceStage, packets: &Packets) {
+    let sample_rate = SAMPLE_RATE.load(Ordering::Relaxed);
+    if sample_rate == 0 {
+        return;
+    }
+    packets
+        .packets
+        .iter()
+        .filter(|packet| !packet.meta.discard)
+        .filter_map(packet_signature)
+        .filter(|signature| is_sampled(signature, sample_rate))
+        .for_each(|signature| {
+            record_signature(stage, &signature, Instant::now());
+        });
+}
+
+/// Record the sampled transactions of `transactions` as reaching `stage`
+pub fn record_transactions(stage: TraceStage, transactions: &[Transaction]) {
+    let sample_rate = SAMPLE_RATE.load(Ordering::Relaxed);
+    if sample_rate == 0 {
+        return;
+    }
+    transactions
+        .iter()
+        .filter_map(|transaction| transaction.signatures.get(0))
+        .filter(|signature| is_sampled(signature, sample_rate))
+        .for_each(|signature| {
+            record_signature(stage, signature, Instant::now());
+        });
+}
+
+/// The first signatures of the sampled transactions in `entries`, to record with
+/// `record_signatures` once the entries are consumed
+pub fn sampled_signatures(entries: &[Entry]) -> Vec<Signature> {
+    let sample_rate = SAMPLE_RATE.load(Ordering::Relaxed);
+    if sample_rate == 0 {
+        return vec![];
+    }
+    entries
+        .iter()
+        .flat_map(|entry| entry.transactions.iter())
+        .filter_map(|transaction| transaction.signatures.get(0))
+        .filter(|signature| is_sampled(signature, sample_rate))
+        .cloned()
+        .collect()
+}
+
+/// Record `signatures`, taken from `sampled_signatures`, as reaching `stage`
+pub fn record_signatures(stage: TraceStage, signatures: &[Signature]) {
+    let now = Instant::now();
+    for signature in signatures {
+        record_signature(stage, signature, now);
+    }
+}
+
+// Returns the stage the transaction was last seen at and the time spent since, if it was
+// traced already
+fn record_signature(
+    stage: TraceStage,
+    signature: &Signature,
+    now: Instant,
+) -> Option<(TraceStage, Duration)> {
+    let mut traces = TRACES.lock().unwrap();
+    if let Some(trace) = traces.get_mut(signature) {
+        let from_stage = trace.last_stage;
+        let span = now.saturating_duration_since(trace.last_seen);
+        datapoint_info!(
+            "transaction-trace",
+            ("signature", signature.to_string(), String),
+            ("from_stage", from_stage.name().to_string(), String),
+            ("stage", stage.name().to_string(), String),
+            ("span_us", span.as_micros() as i64, i64),
+            (
+                "total_us",
+                now.saturating_duration_since(trace.started).as_micros() as i64,
+                i64
+            ),
+        );
+        trace.last_stage = stage;
+        trace.last_seen = now;
+        if stage == TraceStage::Broadcast {
+            traces.remove(signature);
+        }
+        return Some((from_stage, span));
+    }
+
+    // Nothing is left to follow once a transaction is broadcast
+    if stage == TraceStage::Broadcast {
+        return None;
+    }
+    if traces.len() >= MAX_TRACED_TRANSACTIONS {
+        traces.retain(|_, trace| now.saturating_duration_since(trace.last_seen) < TRACE_TIMEOUT);
+        if traces.len() >= MAX_TRACED_TRANSACTIONS {
+            return None;
+        }
+    }
+    traces.insert(
+        *signature,
+        Trace {
+            started: now,
+            last_stage: stage,
+            last_seen: now,
+        },
+    );
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        hash::Hash,
+        signature::{Keypair, KeypairUtil},
+        system_transaction,
+    };
+
+    #[test]
+    fn test_packet_signature() {
+        let keypair = Keypair::new();
+        let tx = system_transaction::transfer(&keypair, &keypair.pubkey(), 1, Hash::default());
+        let mut packet = Packet::default();
+        Packet::populate_packet(&mut packet, None, &tx).unwrap();
+        assert_eq!(packet_signature(&packet), Some(tx.signatures[0]));
+
+        packet.data[0] = 0;
+        assert_eq!(packet_signature(&packet), None);
+        packet.data[0] = 1;
+        packet.meta.size = 10;
+        assert_eq!(packet_signature(&packet), None);
+    }
+
+    #[test]
+    fn test_is_sampled() {
+        let signature = Signature::new(&[7u8; 64]);
+        assert!(!is_sampled(&signature, 0));
+        assert!(is_sampled(&signature, 1));
+    }
+
+    #[test]
+    fn test_record_signature() {
+        let signature = Signature::new(&[42u8; 64]);
+        let start = Instant::now();
+        // A trace doesn't start at broadcast
+        assert_eq!(
+            record_signature(TraceStage::Broadcast, &signature, start),
+            None
+        );
+
+        assert_eq!(
+            record_signature(TraceStage::Ingest, &signature, start),
+            None
+        );
+        let verified = start + Duration::from_millis(5);
+        assert_eq!(
+            record_signature(TraceStage::SigVerify, &signature, verified),
+            Some((TraceStage::Ingest, Duration::from_millis(5)))
+        );
+        let broadcast = verified + Duration::from_millis(3);
+        assert_eq!(
+            record_signature(TraceStage::Broadcast, &signature, broadcast),
+            Some((TraceStage::SigVerify, Duration::from_millis(3)))
+        );
+        // Broadcast ended the trace
+        assert!(!TRACES.lock().unwrap().contains_key(&signature));
+    }
+}
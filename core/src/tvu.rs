@@ -5,6 +5,7 @@ use crate::blockstream_service::BlockstreamService;
 use crate::cluster_info::ClusterInfo;
 use crate::commitment::BlockCommitmentCache;
 use crate::ledger_cleanup_service::LedgerCleanupService;
+use crate::object_store::ObjectStore;
 use crate::partition_cfg::PartitionCfg;
 use crate::poh_recorder::PohRecorder;
 use crate::replay_stage::ReplayStage;
@@ -14,8 +15,10 @@ use crate::shred_fetch_stage::ShredFetchStage;
 use crate::sigverify_shreds::ShredSigVerifier;
 use crate::sigverify_stage::{DisabledSigVerifier, SigVerifyStage};
 use crate::snapshot_packager_service::SnapshotPackagerService;
+use crate::snapshot_uploader_service::SnapshotUploaderService;
 use crate::storage_stage::{StorageStage, StorageState};
-use crossbeam_channel::unbounded;
+use crate::validator_plugin::ValidatorPluginManager;
+use crate::window_service::window_channel;
 use solana_ledger::bank_forks::BankForks;
 use solana_ledger::blocktree::{Blocktree, CompletedSlotsReceiver};
 use solana_ledger::leader_schedule_cache::LeaderScheduleCache;
@@ -37,6 +40,7 @@ pub struct Tvu {
     ledger_cleanup_service: Option<LedgerCleanupService>,
     storage_stage: StorageStage,
     snapshot_packager_service: Option<SnapshotPackagerService>,
+    snapshot_uploader_service: Option<SnapshotUploaderService>,
 }
 
 pub struct Sockets {
@@ -65,6 +69,7 @@ impl Tvu {
         storage_state: &StorageState,
         blockstream_unix_socket: Option<&PathBuf>,
         max_ledger_slots: Option<u64>,
+        snapshot_upload_url: Option<&String>,
         ledger_signal_receiver: Receiver<bool>,
         subscriptions: &Arc<RpcSubscriptions>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
@@ -75,6 +80,7 @@ impl Tvu {
         sigverify_disabled: bool,
         cfg: Option<PartitionCfg>,
         shred_version: u16,
+        plugins: ValidatorPluginManager,
     ) -> Self
     where
         T: 'static + KeypairUtil + Sync + Send,
@@ -106,7 +112,7 @@ impl Tvu {
             &exit,
         );
 
-        let (verified_sender, verified_receiver) = unbounded();
+        let (verified_sender, verified_receiver) = window_channel();
         let sigverify_stage = if !sigverify_disabled {
             SigVerifyStage::new(
                 fetch_receiver,
@@ -138,15 +144,34 @@ impl Tvu {
 
         let (blockstream_slot_sender, blockstream_slot_receiver) = channel();
         let (ledger_cleanup_slot_sender, ledger_cleanup_slot_receiver) = channel();
-        let (snapshot_packager_service, snapshot_package_sender) = {
+        let (snapshot_packager_service, snapshot_package_sender, snapshot_uploader_service) = {
             let snapshot_config = { bank_forks.read().unwrap().snapshot_config().clone() };
             if snapshot_config.is_some() {
+                // Start a snapshot uploading service if there's somewhere to upload to
+                let (packaged_snapshot_sender, snapshot_uploader_service) =
+                    if let Some(snapshot_upload_url) = snapshot_upload_url {
+                        let (sender, receiver) = channel();
+                        let snapshot_uploader_service = SnapshotUploaderService::new(
+                            ObjectStore::new(snapshot_upload_url),
+                            receiver,
+                            blocktree.clone(),
+                            exit,
+                        );
+                        (Some(sender), Some(snapshot_uploader_service))
+                    } else {
+                        (None, None)
+                    };
                 // Start a snapshot packaging service
                 let (sender, receiver) = channel();
-                let snapshot_packager_service = SnapshotPackagerService::new(receiver, exit);
-                (Some(snapshot_packager_service), Some(sender))
+                let snapshot_packager_service =
+                    SnapshotPackagerService::new(receiver, packaged_snapshot_sender, exit);
+                (
+                    Some(snapshot_packager_service),
+                    Some(sender),
+                    snapshot_uploader_service,
+                )
             } else {
-                (None, None)
+                (None, None, None)
             }
         };
 
@@ -165,6 +190,7 @@ impl Tvu {
             vec![blockstream_slot_sender, ledger_cleanup_slot_sender],
             snapshot_package_sender,
             block_commitment_cache,
+            plugins,
         );
 
         let blockstream_service = if let Some(blockstream_unix_socket) = blockstream_unix_socket {
@@ -208,6 +234,7 @@ impl Tvu {
             ledger_cleanup_service,
             storage_stage,
             snapshot_packager_service,
+            snapshot_uploader_service,
         }
     }
 
@@ -226,6 +253,9 @@ impl Tvu {
         if let Some(s) = self.snapshot_packager_service {
             s.join()?;
         }
+        if let Some(s) = self.snapshot_uploader_service {
+            s.join()?;
+        }
         Ok(())
     }
 }
@@ -287,6 +317,7 @@ pub mod tests {
             &StorageState::default(),
             None,
             None,
+            None,
             l_receiver,
             &Arc::new(RpcSubscriptions::default()),
             &poh_recorder,
@@ -297,6 +328,7 @@ pub mod tests {
             false,
             None,
             0,
+            ValidatorPluginManager::default(),
         );
         exit.store(true, Ordering::Relaxed);
         tvu.join().unwrap();
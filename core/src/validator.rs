@@ -5,6 +5,7 @@ use crate::{
     cluster_info::{ClusterInfo, Node},
     commitment::BlockCommitmentCache,
     contact_info::ContactInfo,
+    crds_gossip_pull::CrdsTimeouts,
     gossip_service::{discover_cluster, GossipService},
     partition_cfg::PartitionCfg,
     poh_recorder::PohRecorder,
@@ -15,8 +16,10 @@ use crate::{
     rpc_subscriptions::RpcSubscriptions,
     sigverify,
     storage_stage::StorageState,
+    thread_affinity::{self, ThreadAffinityConfig},
     tpu::Tpu,
     tvu::{Sockets, Tvu},
+    validator_plugin::ValidatorPluginManager,
 };
 use solana_ledger::{
     bank_forks::{BankForks, SnapshotConfig},
@@ -56,12 +59,18 @@ pub struct ValidatorConfig {
     pub voting_disabled: bool,
     pub blockstream_unix_socket: Option<PathBuf>,
     pub storage_slots_per_turn: u64,
+    pub storage_validation_sampling_rate: Option<f64>,
     pub account_paths: Option<String>,
     pub rpc_config: JsonRpcConfig,
     pub snapshot_config: Option<SnapshotConfig>,
     pub max_ledger_slots: Option<u64>,
+    pub snapshot_upload_url: Option<String>,
+    pub crds_timeouts: CrdsTimeouts,
+    pub compact_repair: bool,
     pub broadcast_stage_type: BroadcastStageType,
     pub partition_cfg: Option<PartitionCfg>,
+    pub plugins: ValidatorPluginManager,
+    pub thread_affinity: ThreadAffinityConfig,
 }
 
 impl Default for ValidatorConfig {
@@ -73,12 +82,18 @@ impl Default for ValidatorConfig {
             voting_disabled: false,
             blockstream_unix_socket: None,
             storage_slots_per_turn: DEFAULT_SLOTS_PER_TURN,
+            storage_validation_sampling_rate: None,
             max_ledger_slots: None,
+            snapshot_upload_url: None,
+            crds_timeouts: CrdsTimeouts::default(),
+            compact_repair: false,
             account_paths: None,
             rpc_config: JsonRpcConfig::default(),
             snapshot_config: None,
             broadcast_stage_type: BroadcastStageType::Standard,
             partition_cfg: None,
+            plugins: ValidatorPluginManager::default(),
+            thread_affinity: ThreadAffinityConfig::default(),
         }
     }
 }
@@ -156,6 +171,8 @@ impl Validator {
 
         Self::print_node_info(&node);
 
+        thread_affinity::set_config(config.thread_affinity.clone());
+
         info!("Initializing sigverify, this could take a while...");
         sigverify::init();
         info!("Done.");
@@ -194,16 +211,19 @@ impl Validator {
         let validator_exit = Arc::new(RwLock::new(Some(validator_exit)));
 
         node.info.wallclock = timestamp();
-        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new(
-            node.info.clone(),
-            keypair.clone(),
-        )));
+        node.info.outset = node.info.wallclock;
+        node.info.shred_version = shred_version;
+        let mut cluster_info = ClusterInfo::new(node.info.clone(), keypair.clone());
+        cluster_info.set_crds_timeouts(config.crds_timeouts);
+        cluster_info.set_compact_repair(config.compact_repair);
+        let cluster_info = Arc::new(RwLock::new(cluster_info));
 
         let storage_state = StorageState::new(
             &bank.last_blockhash(),
             config.storage_slots_per_turn,
             bank.slots_per_segment(),
         );
+        storage_state.set_validation_sampling_rate(config.storage_validation_sampling_rate);
 
         let blocktree = Arc::new(blocktree);
 
@@ -340,6 +360,7 @@ impl Validator {
             &storage_state,
             config.blockstream_unix_socket.as_ref(),
             config.max_ledger_slots,
+            config.snapshot_upload_url.as_ref(),
             ledger_signal_receiver,
             &subscriptions,
             &poh_recorder,
@@ -350,6 +371,7 @@ impl Validator {
             config.dev_sigverify_disabled,
             config.partition_cfg.clone(),
             shred_version,
+            config.plugins.clone(),
         );
 
         if config.dev_sigverify_disabled {
@@ -365,11 +387,13 @@ impl Validator {
             node.sockets.broadcast,
             config.dev_sigverify_disabled,
             &blocktree,
+            &bank_forks,
             &config.broadcast_stage_type,
             &exit,
             shred_version,
         );
 
+        thread_affinity::report();
         datapoint_info!("validator-new", ("id", id.to_string(), String));
         Self {
             id,
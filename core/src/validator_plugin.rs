@@ -0,0 +1,179 @@
+//! The `validator_plugin` module lets compiled-in plugins follow the state changes made by
+//! the replay stage (account writes, slot status and transactions) instead of polling RPC.
+
+use solana_ledger::blocktree::Blocktree;
+use solana_runtime::bank::Bank;
+use solana_sdk::{
+    account::Account, clock::Slot, pubkey::Pubkey, transaction::Result, transaction::Transaction,
+};
+use std::{fmt, sync::Arc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotStatus {
+    /// The bank for the slot was replayed and frozen
+    Frozen,
+    /// The slot was rooted
+    Rooted,
+}
+
+/// Receives the replay stage's notifications. Calls are made from the replay thread, so
+/// implementations should hand any slow work off to their own threads.
+pub trait ValidatorPlugin: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// An account was written in `slot`, called once per account with its final state
+    /// when the slot's bank is frozen
+    fn notify_account_update(&self, _slot: Slot, _pubkey: &Pubkey, _account: &Account) {}
+
+    fn notify_slot_status(&self, _slot: Slot, _parent: Option<Slot>, _status: SlotStatus) {}
+
+    /// A transaction was executed in `slot` with the given result
+    fn notify_transaction(&self, _slot: Slot, _transaction: &Transaction, _result: &Result<()>) {}
+}
+
+/// The set of plugins a validator was configured with
+#[derive(Clone, Default)]
+pub struct ValidatorPluginManager {
+    plugins: Vec<Arc<dyn ValidatorPlugin>>,
+}
+
+impl fmt::Debug for ValidatorPluginManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.plugins.iter().map(|plugin| plugin.name()))
+            .finish()
+    }
+}
+
+impl ValidatorPluginManager {
+    pub fn add_plugin(&mut self, plugin: Arc<dyn ValidatorPlugin>) {
+        info!("validator plugin loaded: {}", plugin.name());
+        self.plugins.push(plugin);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Notify the accounts, transactions and status of a newly frozen bank
+    pub fn notify_bank_frozen(&self, bank: &Bank, blocktree: &Blocktree) {
+        if self.is_empty() {
+            return;
+        }
+        let slot = bank.slot();
+
+        for (pubkey, account) in bank.get_all_accounts_modified_since_parent() {
+            for plugin in &self.plugins {
+                plugin.notify_account_update(slot, &pubkey, &account);
+            }
+        }
+
+        match blocktree.get_slot_entries(slot, 0, None) {
+            Ok(entries) => {
+                for transaction in entries.iter().flat_map(|entry| &entry.transactions) {
+                    if let Some(result) = bank.get_signature_status(&transaction.signatures[0]) {
+                        for plugin in &self.plugins {
+                            plugin.notify_transaction(slot, transaction, &result);
+                        }
+                    }
+                }
+            }
+            Err(err) => warn!(
+                "plugins: unable to load entries for slot {}: {:?}",
+                slot, err
+            ),
+        }
+
+        self.notify_slot_status(
+            slot,
+            bank.parent().map(|parent| parent.slot()),
+            SlotStatus::Frozen,
+        );
+    }
+
+    pub fn notify_slot_status(&self, slot: Slot, parent: Option<Slot>, status: SlotStatus) {
+        for plugin in &self.plugins {
+            plugin.notify_slot_status(slot, parent, status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesis_utils::{create_genesis_config, GenesisConfigInfo};
+    use solana_ledger::get_tmp_ledger_path;
+    use solana_sdk::{signature::KeypairUtil, system_transaction};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct TestPlugin {
+        accounts: Mutex<Vec<(Slot, Pubkey, u64)>>,
+        slots: Mutex<Vec<(Slot, Option<Slot>, SlotStatus)>>,
+        transactions: Mutex<Vec<(Slot, Result<()>)>>,
+    }
+
+    impl ValidatorPlugin for TestPlugin {
+        fn name(&self) -> &str {
+            "test"
+        }
+        fn notify_account_update(&self, slot: Slot, pubkey: &Pubkey, account: &Account) {
+            self.accounts
+                .lock()
+                .unwrap()
+                .push((slot, *pubkey, account.lamports));
+        }
+        fn notify_slot_status(&self, slot: Slot, parent: Option<Slot>, status: SlotStatus) {
+            self.slots.lock().unwrap().push((slot, parent, status));
+        }
+        fn notify_transaction(&self, slot: Slot, _transaction: &Transaction, result: &Result<()>) {
+            self.transactions
+                .lock()
+                .unwrap()
+                .push((slot, result.clone()));
+        }
+    }
+
+    #[test]
+    fn test_notify_bank_frozen() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000);
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        let to = Pubkey::new_rand();
+        let tx = system_transaction::transfer(&mint_keypair, &to, 10, bank1.last_blockhash());
+        bank1.process_transaction(&tx).unwrap();
+
+        let ledger_path = get_tmp_ledger_path!();
+        let blocktree = Blocktree::open(&ledger_path).unwrap();
+
+        let plugin = Arc::new(TestPlugin::default());
+        let mut plugins = ValidatorPluginManager::default();
+        assert!(plugins.is_empty());
+        plugins.add_plugin(plugin.clone());
+        plugins.notify_bank_frozen(&bank1, &blocktree);
+
+        let accounts = plugin.accounts.lock().unwrap();
+        assert!(accounts.contains(&(1, to, 10)));
+        assert!(accounts
+            .iter()
+            .any(|(slot, pubkey, _)| *slot == 1 && *pubkey == mint_keypair.pubkey()));
+        // no entries were written to the ledger for this bank
+        assert!(plugin.transactions.lock().unwrap().is_empty());
+        assert_eq!(
+            *plugin.slots.lock().unwrap(),
+            vec![(1, Some(0), SlotStatus::Frozen)]
+        );
+
+        plugins.notify_slot_status(1, Some(0), SlotStatus::Rooted);
+        assert_eq!(
+            plugin.slots.lock().unwrap().last(),
+            Some(&(1, Some(0), SlotStatus::Rooted))
+        );
+        drop(blocktree);
+        Blocktree::destroy(&ledger_path).unwrap();
+    }
+}
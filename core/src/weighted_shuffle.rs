@@ -1,12 +1,23 @@
-//! The `weighted_shuffle` module provides an iterator over shuffled weights.
+//! The `weighted_shuffle` module provides an iterator over shuffled weights, and the stake
+//! weighted peer ordering built on it that retransmit, gossip and archivers share.
 
 use itertools::Itertools;
 use num_traits::{FromPrimitive, ToPrimitive};
-use rand::{Rng, SeedableRng};
+use rand::{thread_rng, Rng, SeedableRng};
 use rand_chacha::ChaChaRng;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
 use std::iter;
 use std::ops::Div;
 
+/// A seed for a shuffle that doesn't need to be reproduced by other nodes
+pub fn random_seed() -> [u8; 32] {
+    let mut seed = [0; 32];
+    thread_rng().fill(&mut seed);
+    seed
+}
+
 /// Returns a list of indexes shuffled based on the input weights
 /// Note - The sum of all weights must not exceed `u64::MAX`
 pub fn weighted_shuffle<T>(weights: Vec<T>, seed: [u8; 32]) -> Vec<usize>
@@ -62,6 +73,42 @@ pub fn weighted_best(weights_and_indexes: &[(u64, usize)], seed: [u8; 32]) -> us
     best_index
 }
 
+/// Pairs the stake of each of `ids` with its index, sorted by descending stake and then by
+/// descending pubkey so that every node orders the same peers the same way. Missing stakes count
+/// as 1 since a weight of 0 can't be shuffled.
+pub fn sorted_stakes_with_index<S: BuildHasher>(
+    ids: &[Pubkey],
+    stakes: Option<&HashMap<Pubkey, u64, S>>,
+) -> Vec<(u64, usize)> {
+    ids.iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let stake = 1.max(stakes.map_or(1, |stakes| *stakes.get(id).unwrap_or(&1)));
+            (stake, i)
+        })
+        .sorted_by(|(l_stake, l_index), (r_stake, r_index)| {
+            if r_stake == l_stake {
+                ids[*r_index].cmp(&ids[*l_index])
+            } else {
+                r_stake.cmp(&l_stake)
+            }
+        })
+        .collect()
+}
+
+/// Shuffles the output of `sorted_stakes_with_index()` by stake, the same seed always gives the
+/// same order
+pub fn stake_weighted_shuffle(
+    stakes_and_index: &[(u64, usize)],
+    seed: [u8; 32],
+) -> Vec<(u64, usize)> {
+    let stake_weights = stakes_and_index.iter().map(|(w, _)| *w).collect();
+
+    let shuffle = weighted_shuffle(stake_weights, seed);
+
+    shuffle.iter().map(|x| stakes_and_index[*x]).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,4 +178,71 @@ mod tests {
         let best_index = weighted_best(&weights_and_indexes, [0x5b; 32]);
         assert_eq!(best_index, 2);
     }
+
+    #[test]
+    fn test_sorted_stakes_with_index() {
+        let ids: Vec<_> = (0..4).map(|_| Pubkey::new_rand()).collect();
+        let mut stakes = HashMap::new();
+        stakes.insert(ids[1], 10);
+        stakes.insert(ids[2], 0);
+        stakes.insert(ids[3], 10);
+
+        let sorted = sorted_stakes_with_index(&ids, Some(&stakes));
+        // ties are broken by pubkey, descending
+        let (high, low) = if ids[1] > ids[3] { (1, 3) } else { (3, 1) };
+        assert_eq!(sorted[..2], [(10, high), (10, low)]);
+        // a stake of 0 and a missing stake both count as 1
+        let (high, low) = if ids[0] > ids[2] { (0, 2) } else { (2, 0) };
+        assert_eq!(sorted[2..], [(1, high), (1, low)]);
+
+        let unstaked =
+            sorted_stakes_with_index::<std::collections::hash_map::RandomState>(&ids, None);
+        assert!(unstaked.iter().all(|(stake, _)| *stake == 1));
+    }
+
+    #[test]
+    fn test_stake_weighted_shuffle_properties() {
+        let mut rng = ChaChaRng::from_seed([0x3c; 32]);
+        for _ in 0..100 {
+            let num_peers = rng.gen_range(1, 50);
+            let ids: Vec<_> = (0..num_peers).map(|_| Pubkey::new_rand()).collect();
+            let stakes: HashMap<_, _> = ids
+                .iter()
+                .map(|id| (*id, rng.gen_range(0, 1_000_000)))
+                .collect();
+            let stakes_and_index = sorted_stakes_with_index(&ids, Some(&stakes));
+            let mut seed = [0; 32];
+            rng.fill(&mut seed);
+
+            // a permutation of the input
+            let shuffled = stake_weighted_shuffle(&stakes_and_index, seed);
+            let mut sorted_indexes: Vec<_> = shuffled.iter().map(|(_, index)| *index).collect();
+            sorted_indexes.sort();
+            assert_eq!(sorted_indexes, (0..num_peers).collect::<Vec<_>>());
+            assert!(shuffled
+                .iter()
+                .all(|(stake, index)| *stake == 1.max(stakes[&ids[*index]])));
+
+            // determined by the seed
+            assert_eq!(stake_weighted_shuffle(&stakes_and_index, seed), shuffled);
+        }
+    }
+
+    #[test]
+    fn test_stake_weighted_shuffle_favors_stake() {
+        let stakes_and_index = vec![(1_000, 0), (1, 1)];
+        let heavy_first = (0..1000u16)
+            .filter(|i| {
+                let mut seed = [0; 32];
+                seed[..2].copy_from_slice(&i.to_le_bytes());
+                stake_weighted_shuffle(&stakes_and_index, seed)[0].1 == 0
+            })
+            .count();
+        assert!(heavy_first > 900);
+    }
+
+    #[test]
+    fn test_random_seed() {
+        assert_ne!(random_seed(), random_seed());
+    }
 }
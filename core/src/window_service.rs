@@ -3,27 +3,75 @@
 //!
 use crate::cluster_info::ClusterInfo;
 use crate::packet::Packets;
+use crate::repair_peers::RepairPeers;
 use crate::repair_service::{RepairService, RepairStrategy};
 use crate::result::{Error, Result};
 use crate::streamer::PacketSender;
-use crossbeam_channel::{Receiver as CrossbeamReceiver, RecvTimeoutError};
+use crossbeam_channel::{
+    bounded, Receiver as CrossbeamReceiver, RecvTimeoutError, Sender as CrossbeamSender,
+};
 use rayon::iter::IntoParallelRefMutIterator;
 use rayon::iter::ParallelIterator;
 use rayon::ThreadPool;
 use solana_ledger::blocktree::{self, Blocktree};
 use solana_ledger::leader_schedule_cache::LeaderScheduleCache;
 use solana_ledger::shred::Shred;
-use solana_metrics::{inc_new_counter_debug, inc_new_counter_error};
+use solana_metrics::{datapoint_debug, inc_new_counter_debug, inc_new_counter_error};
 use solana_rayon_threadlimit::get_thread_count;
 use solana_runtime::bank::Bank;
+use solana_sdk::clock::Slot;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::timing::duration_as_ms;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::net::UdpSocket;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread::{self, Builder, JoinHandle};
 use std::time::{Duration, Instant};
 
+/// Capacity, in packet batches, of the channel feeding verified shreds to the window
+pub const MAX_WINDOW_QUEUED_BATCHES: usize = 1024;
+// Backpressure is raised once this many batches are queued and cleared again once the
+// window has drained below the low watermark
+const BACKPRESSURE_HIGH_WATERMARK: usize = MAX_WINDOW_QUEUED_BATCHES * 3 / 4;
+const BACKPRESSURE_LOW_WATERMARK: usize = MAX_WINDOW_QUEUED_BATCHES / 4;
+
+/// Default number of shreds held back for slots whose leader isn't known yet
+pub const DEFAULT_MAX_FUTURE_SHREDS: usize = 4096;
+// Held back shreds whose leader is still unknown this long after they arrived are dropped
+const FUTURE_SHRED_MAX_AGE: Duration = Duration::from_secs(10);
+
+/// Create the bounded channel used to feed a `WindowService`, senders should drop
+/// batches rather than block when it is full
+pub fn window_channel() -> (
+    CrossbeamSender<Vec<Packets>>,
+    CrossbeamReceiver<Vec<Packets>>,
+) {
+    bounded(MAX_WINDOW_QUEUED_BATCHES)
+}
+
+// Update the backpressure signal from the current queue depth, returns whether it is raised
+fn update_backpressure(backpressure: &AtomicBool, queued_batches: usize) -> bool {
+    let was_raised = backpressure.load(Ordering::Relaxed);
+    let raised = if was_raised {
+        queued_batches > BACKPRESSURE_LOW_WATERMARK
+    } else {
+        queued_batches >= BACKPRESSURE_HIGH_WATERMARK
+    };
+    if raised != was_raised {
+        backpressure.store(raised, Ordering::Relaxed);
+        if raised {
+            warn!(
+                "window falling behind, {} batches queued: pausing repair",
+                queued_batches
+            );
+        } else {
+            info!("window caught up, resuming repair");
+        }
+    }
+    raised
+}
+
 fn verify_shred_slot(shred: &Shred, root: u64) -> bool {
     if shred.is_data() {
         // Only data shreds have parent information
@@ -67,6 +115,119 @@ pub fn should_retransmit_and_persist(
     }
 }
 
+// A shred rejected only because its slot is ahead of the known leader schedule, it may be
+// accepted once the working bank reaches the slot's epoch
+fn is_future_shred(shred: &Shred, leader_schedule_cache: &LeaderScheduleCache, root: u64) -> bool {
+    shred.slot() > root
+        && leader_schedule_cache
+            .slot_leader_at(shred.slot(), None)
+            .is_none()
+}
+
+/// Shreds for slots whose leader isn't known yet, held back instead of being dropped so they
+/// don't have to be repaired again once the leader schedule catches up. The oldest shreds are
+/// evicted when full.
+struct FutureShreds {
+    shreds: VecDeque<(Instant, Shred)>,
+    max_shreds: usize,
+    // shreds accepted after being held back, since the last report
+    reclaimed: usize,
+    // shreds dropped after being held back, since the last report
+    expired: usize,
+}
+
+impl FutureShreds {
+    fn new(max_shreds: usize) -> Self {
+        Self {
+            shreds: VecDeque::new(),
+            max_shreds,
+            reclaimed: 0,
+            expired: 0,
+        }
+    }
+
+    fn insert(&mut self, shreds: Vec<Shred>, now: Instant) {
+        if self.max_shreds == 0 {
+            self.expired += shreds.len();
+            return;
+        }
+        for shred in shreds {
+            if self.shreds.len() == self.max_shreds {
+                self.shreds.pop_front();
+                self.expired += 1;
+            }
+            self.shreds.push_back((now, shred));
+        }
+    }
+
+    /// Take out the held back shreds `accept` now lets through, shreds that are no longer
+    /// future shreds or that are older than `FUTURE_SHRED_MAX_AGE` are dropped
+    fn reclaim<F, G>(&mut self, now: Instant, accept: F, is_future: G) -> Vec<Shred>
+    where
+        F: Fn(&Shred) -> bool,
+        G: Fn(&Shred) -> bool,
+    {
+        let mut reclaimed = vec![];
+        let mut held = VecDeque::with_capacity(self.shreds.len());
+        for (received, shred) in self.shreds.drain(..) {
+            if accept(&shred) {
+                reclaimed.push(shred);
+            } else if is_future(&shred)
+                && now.saturating_duration_since(received) < FUTURE_SHRED_MAX_AGE
+            {
+                held.push_back((received, shred));
+            } else {
+                self.expired += 1;
+            }
+        }
+        self.shreds = held;
+        self.reclaimed += reclaimed.len();
+        reclaimed
+    }
+
+    fn report(&mut self) {
+        if self.reclaimed > 0 || self.expired > 0 {
+            datapoint_debug!(
+                "recv-window-future-shreds",
+                ("held", self.shreds.len(), i64),
+                ("reclaimed", self.reclaimed, i64),
+                ("expired", self.expired, i64),
+            );
+        }
+        self.reclaimed = 0;
+        self.expired = 0;
+    }
+}
+
+/// Reports, per slot, how many shreds were recovered from erasure coding and how many
+/// arrived as repair responses
+fn report_slot_recovery(
+    num_recovered_by_slot: &HashMap<Slot, usize>,
+    num_repaired_by_slot: &HashMap<Slot, usize>,
+) {
+    let slots: HashSet<_> = num_recovered_by_slot
+        .keys()
+        .chain(num_repaired_by_slot.keys())
+        .collect();
+    for slot in slots {
+        datapoint_debug!(
+            "recv-window-slot-recovery",
+            ("slot", *slot as i64, i64),
+            (
+                "erasure_recovered",
+                *num_recovered_by_slot.get(slot).unwrap_or(&0) as i64,
+                i64
+            ),
+            (
+                "repaired",
+                *num_repaired_by_slot.get(slot).unwrap_or(&0) as i64,
+                i64
+            ),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn recv_window<F>(
     blocktree: &Arc<Blocktree>,
     my_pubkey: &Pubkey,
@@ -75,12 +236,22 @@ fn recv_window<F>(
     shred_filter: F,
     thread_pool: &ThreadPool,
     leader_schedule_cache: &Arc<LeaderScheduleCache>,
+    backpressure: &AtomicBool,
+    repair_peers: &Mutex<RepairPeers>,
+    future_shreds: &mut FutureShreds,
 ) -> Result<()>
 where
     F: Fn(&Shred, u64) -> bool + Sync,
 {
     let timer = Duration::from_millis(200);
     let mut packets = verified_receiver.recv_timeout(timer)?;
+    let queued_batches = verified_receiver.len();
+    let backpressure_raised = update_backpressure(backpressure, queued_batches);
+    datapoint_debug!(
+        "recv-window-queue",
+        ("queued_batches", queued_batches, i64),
+        ("backpressure", backpressure_raised, bool)
+    );
     let mut total_packets: usize = packets.iter().map(|p| p.packets.len()).sum();
 
     while let Ok(mut more_packets) = verified_receiver.try_recv() {
@@ -93,7 +264,9 @@ where
     inc_new_counter_debug!("streamer-recv_window-recv", total_packets);
 
     let last_root = blocktree.last_root();
-    let shreds: Vec<_> = thread_pool.install(|| {
+    // each shred with whether it's accepted, rejected shreds are only kept if they're
+    // future shreds
+    let filtered: Vec<_> = thread_pool.install(|| {
         packets
             .par_iter_mut()
             .flat_map(|packets| {
@@ -110,10 +283,14 @@ where
                             if shred_filter(&shred, last_root) {
                                 packet.meta.slot = shred.slot();
                                 packet.meta.seed = shred.seed();
-                                Some(shred)
+                                Some((shred, true))
                             } else {
                                 packet.meta.discard = true;
-                                None
+                                if is_future_shred(&shred, leader_schedule_cache, last_root) {
+                                    Some((shred, false))
+                                } else {
+                                    None
+                                }
                             }
                         } else {
                             packet.meta.discard = true;
@@ -124,9 +301,40 @@ where
             })
             .collect()
     });
+    let (shreds, new_future_shreds): (Vec<_>, Vec<_>) =
+        filtered.into_iter().partition(|(_, accepted)| *accepted);
+    let mut shreds: Vec<_> = shreds.into_iter().map(|(shred, _)| shred).collect();
 
     trace!("{:?} shreds from packets", shreds.len());
 
+    // Held back shreds aren't retransmitted, their packets were discarded when they arrived
+    shreds.extend(future_shreds.reclaim(
+        now,
+        |shred| shred_filter(shred, last_root),
+        |shred| is_future_shred(shred, leader_schedule_cache, last_root),
+    ));
+    future_shreds.insert(
+        new_future_shreds
+            .into_iter()
+            .map(|(shred, _)| shred)
+            .collect(),
+        now,
+    );
+    future_shreds.report();
+
+    let mut num_repaired_by_slot = HashMap::new();
+    {
+        let mut repair_peers = repair_peers.lock().unwrap();
+        packets
+            .iter()
+            .flat_map(|packets| packets.packets.iter())
+            .filter(|packet| packet.meta.repair && !packet.meta.discard)
+            .for_each(|packet| {
+                repair_peers.record_response(&packet.meta.addr());
+                *num_repaired_by_slot.entry(packet.meta.slot).or_insert(0) += 1;
+            });
+    }
+
     trace!("{} num total shreds received: {}", my_pubkey, total_packets);
 
     for packets in packets.into_iter() {
@@ -139,6 +347,10 @@ where
     let blocktree_insert_metrics =
         blocktree.insert_shreds(shreds, Some(leader_schedule_cache), false)?;
     blocktree_insert_metrics.report_metrics("recv-window-insert-shreds");
+    report_slot_recovery(
+        &blocktree_insert_metrics.num_recovered_by_slot,
+        &num_repaired_by_slot,
+    );
 
     trace!(
         "Elapsed processing time in recv_window(): {}",
@@ -182,6 +394,7 @@ impl WindowService {
         exit: &Arc<AtomicBool>,
         repair_strategy: RepairStrategy,
         leader_schedule_cache: &Arc<LeaderScheduleCache>,
+        max_future_shreds: usize,
         shred_filter: F,
     ) -> WindowService
     where
@@ -196,12 +409,16 @@ impl WindowService {
             RepairStrategy::RepairAll { ref bank_forks, .. } => Some(bank_forks.clone()),
         };
 
+        let backpressure = Arc::new(AtomicBool::new(false));
+        let repair_peers = cluster_info.read().unwrap().repair_peer_scores();
         let repair_service = RepairService::new(
             blocktree.clone(),
             exit.clone(),
             repair_socket,
             cluster_info.clone(),
             repair_strategy,
+            backpressure.clone(),
+            repair_peers.clone(),
         );
         let exit = exit.clone();
         let shred_filter = Arc::new(shred_filter);
@@ -218,6 +435,7 @@ impl WindowService {
                     .num_threads(get_thread_count())
                     .build()
                     .unwrap();
+                let mut future_shreds = FutureShreds::new(max_future_shreds);
                 loop {
                     if exit.load(Ordering::Relaxed) {
                         break;
@@ -240,6 +458,9 @@ impl WindowService {
                         },
                         &thread_pool,
                         &leader_schedule_cache,
+                        &backpressure,
+                        &repair_peers,
+                        &mut future_shreds,
                     ) {
                         match e {
                             Error::CrossbeamRecvTimeoutError(RecvTimeoutError::Disconnected) => break,
@@ -283,7 +504,6 @@ mod test {
         packet::{Packet, Packets},
         repair_service::RepairSlotRange,
     };
-    use crossbeam_channel::unbounded;
     use rand::thread_rng;
     use solana_ledger::shred::DataShredHeader;
     use solana_ledger::{
@@ -411,6 +631,60 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_future_shreds() {
+        let leader_keypair = Arc::new(Keypair::new());
+        let bank = Arc::new(Bank::new(
+            &create_genesis_config_with_leader(100, &leader_keypair.pubkey(), 10).genesis_config,
+        ));
+        let cache = LeaderScheduleCache::new_from_bank(&bank);
+        let future_slot = MINIMUM_SLOTS_PER_EPOCH as u64 * 3;
+        let shreds = local_entries_to_shred(
+            &create_ticks(200, 0, Hash::default()),
+            future_slot,
+            future_slot - 1,
+            &leader_keypair,
+        );
+        assert!(shreds.len() > 2);
+        assert!(is_future_shred(&shreds[0], &cache, 0));
+        assert!(!is_future_shred(&shreds[0], &cache, future_slot));
+        let current_shreds = local_entries_to_shred(&[Entry::default()], 1, 0, &leader_keypair);
+        assert!(!is_future_shred(&current_shreds[0], &cache, 0));
+
+        // the oldest shreds are evicted when full
+        let now = Instant::now();
+        let mut future_shreds = FutureShreds::new(2);
+        future_shreds.insert(shreds.clone(), now);
+        assert_eq!(future_shreds.shreds.len(), 2);
+        assert_eq!(future_shreds.expired, shreds.len() - 2);
+        assert_eq!(future_shreds.shreds[1].1, shreds[shreds.len() - 1]);
+        future_shreds.report();
+
+        // still future shreds are held back until they expire
+        assert!(future_shreds.reclaim(now, |_| false, |_| true).is_empty());
+        assert_eq!(future_shreds.shreds.len(), 2);
+        assert!(future_shreds
+            .reclaim(now + FUTURE_SHRED_MAX_AGE, |_| false, |_| true)
+            .is_empty());
+        assert!(future_shreds.shreds.is_empty());
+        assert_eq!(future_shreds.expired, 2);
+
+        // accepted shreds are reclaimed
+        future_shreds.insert(shreds[..2].to_vec(), now);
+        assert_eq!(
+            future_shreds.reclaim(now, |_| true, |_| true),
+            shreds[..2].to_vec()
+        );
+        assert_eq!(future_shreds.reclaimed, 2);
+        assert!(future_shreds.shreds.is_empty());
+
+        // nothing is held back with a size of 0
+        let mut future_shreds = FutureShreds::new(0);
+        future_shreds.insert(shreds.clone(), now);
+        assert!(future_shreds.shreds.is_empty());
+        assert_eq!(future_shreds.expired, shreds.len());
+    }
+
     fn make_test_window(
         verified_receiver: CrossbeamReceiver<Vec<Packets>>,
         exit: Arc<AtomicBool>,
@@ -432,16 +706,43 @@ mod test {
             retransmit_sender,
             repair_sock,
             &exit,
-            RepairStrategy::RepairRange(RepairSlotRange { start: 0, end: 0 }),
+            RepairStrategy::RepairRange(Arc::new(RwLock::new(RepairSlotRange::new(0, 0)))),
             &Arc::new(LeaderScheduleCache::default()),
+            DEFAULT_MAX_FUTURE_SHREDS,
             |_, _, _, _| true,
         );
         window
     }
 
+    #[test]
+    fn test_update_backpressure() {
+        let backpressure = AtomicBool::new(false);
+        assert!(!update_backpressure(&backpressure, 0));
+        assert!(!update_backpressure(
+            &backpressure,
+            BACKPRESSURE_HIGH_WATERMARK - 1
+        ));
+        assert!(update_backpressure(
+            &backpressure,
+            BACKPRESSURE_HIGH_WATERMARK
+        ));
+        assert!(backpressure.load(Ordering::Relaxed));
+
+        // stays raised until the queue drains below the low watermark
+        assert!(update_backpressure(
+            &backpressure,
+            BACKPRESSURE_LOW_WATERMARK + 1
+        ));
+        assert!(!update_backpressure(
+            &backpressure,
+            BACKPRESSURE_LOW_WATERMARK
+        ));
+        assert!(!backpressure.load(Ordering::Relaxed));
+    }
+
     #[test]
     fn test_recv_window() {
-        let (packet_sender, packet_receiver) = unbounded();
+        let (packet_sender, packet_receiver) = window_channel();
         let exit = Arc::new(AtomicBool::new(false));
         let window = make_test_window(packet_receiver, exit.clone());
         // send 5 slots worth of data to the window
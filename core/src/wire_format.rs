@@ -0,0 +1,612 @@
+//! The `wire_format` module defines fixed-layout binary encodings for the high-rate control
+//! messages: repair requests, ancestor hashes responses and `ArchiverRequest`s. They are
+//! written straight into packet buffers without bincode, and the layout below is the whole
+//! specification of the format.
+//!
+//! Every message starts with a 3 byte header: `WIRE_MAGIC`, `WIRE_VERSION` and the message
+//! kind. Integers are little-endian. A socket address takes 19 bytes: the address family (4 or
+//! 6), 16 bytes of ip with ipv4 addresses zero-padded, then the port. There is no room for the
+//! flowinfo and scope id of an ipv6 address, so addresses with either set can't be encoded and
+//! decoded ipv6 addresses have both zero. A `ContactInfo` is its id,
+//! its nine addresses in declaration order, its wallclock, its shred version and its outset.
+//!
+//! `WIRE_MAGIC` never starts a bincode `Protocol` message, whose first byte is a small enum
+//! tag, so the gossip socket tells the two encodings apart by the first byte.
+
+use crate::{
+    archiver::ArchiverRequest,
+    cluster_info::{Protocol, MAX_ANCESTOR_HASHES},
+    contact_info::ContactInfo,
+};
+use solana_sdk::{hash::Hash, pubkey::Pubkey};
+use std::{
+    mem::size_of,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+pub const WIRE_MAGIC: u8 = 0xf7;
+pub const WIRE_VERSION: u8 = 2;
+
+const HEADER_SIZE: usize = 3;
+const SOCKET_ADDR_SIZE: usize = 1 + 16 + 2;
+const CONTACT_INFO_SIZE: usize =
+    size_of::<Pubkey>() + 9 * SOCKET_ADDR_SIZE + 2 * size_of::<u64>() + size_of::<u16>();
+const SLOT_HASH_SIZE: usize = size_of::<u64>() + size_of::<Hash>();
+
+const KIND_REQUEST_WINDOW_INDEX: u8 = 1;
+const KIND_REQUEST_HIGHEST_WINDOW_INDEX: u8 = 2;
+const KIND_REQUEST_ORPHAN: u8 = 3;
+const KIND_REQUEST_ANCESTOR_HASHES: u8 = 4;
+const KIND_ANCESTOR_HASHES: u8 = 5;
+const KIND_ARCHIVER_GET_SLOT_HEIGHT: u8 = 16;
+const KIND_ARCHIVER_GET_STATUS: u8 = 17;
+const KIND_ARCHIVER_GET_SEGMENT_MANIFEST: u8 = 18;
+const KIND_ARCHIVER_GET_SHRED_RANGE: u8 = 19;
+
+/// Size of the largest repair request
+pub const MAX_REPAIR_REQUEST_SIZE: usize = HEADER_SIZE + CONTACT_INFO_SIZE + 2 * size_of::<u64>();
+/// Size of an ancestor hashes response carrying `MAX_ANCESTOR_HASHES` hashes
+pub const MAX_ANCESTOR_HASHES_SIZE: usize = HEADER_SIZE
+    + size_of::<Pubkey>()
+    + size_of::<u64>()
+    + size_of::<u16>()
+    + MAX_ANCESTOR_HASHES * SLOT_HASH_SIZE;
+
+#[derive(Debug, PartialEq)]
+pub enum WireError {
+    /// The message doesn't fit the output buffer
+    BufferTooSmall,
+    /// The input ended in the middle of a message
+    Truncated,
+    BadMagic(u8),
+    UnsupportedVersion(u8),
+    UnknownKind(u8),
+    BadAddressFamily(u8),
+    /// An ipv6 address with a flowinfo or scope id, which the format has no room for
+    UnsupportedAddress(SocketAddr),
+    TooManyHashes(usize),
+    /// Bytes left over after a complete message
+    TrailingBytes(usize),
+    /// The message has no compact encoding, gossip messages stay bincode encoded
+    NotCompact,
+}
+
+pub type Result<T> = std::result::Result<T, WireError>;
+
+/// Whether `data` starts like a compact message rather than a bincode one
+pub fn is_compact(data: &[u8]) -> bool {
+    data.first() == Some(&WIRE_MAGIC)
+}
+
+struct WireWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> WireWriter<'a> {
+    fn new(buf: &'a mut [u8], kind: u8) -> Result<Self> {
+        let mut writer = Self { buf, pos: 0 };
+        writer.put_bytes(&[WIRE_MAGIC, WIRE_VERSION, kind])?;
+        Ok(writer)
+    }
+
+    fn put_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let end = self.pos + bytes.len();
+        if end > self.buf.len() {
+            return Err(WireError::BufferTooSmall);
+        }
+        self.buf[self.pos..end].copy_from_slice(bytes);
+        self.pos = end;
+        Ok(())
+    }
+
+    fn put_u16(&mut self, value: u16) -> Result<()> {
+        self.put_bytes(&value.to_le_bytes())
+    }
+
+    fn put_u64(&mut self, value: u64) -> Result<()> {
+        self.put_bytes(&value.to_le_bytes())
+    }
+
+    fn put_addr(&mut self, addr: &SocketAddr) -> Result<()> {
+        let mut ip = [0u8; 16];
+        let family = match addr {
+            SocketAddr::V4(addr) => {
+                ip[..4].copy_from_slice(&addr.ip().octets());
+                4
+            }
+            SocketAddr::V6(addr) => {
+                if addr.flowinfo() != 0 || addr.scope_id() != 0 {
+                    return Err(WireError::UnsupportedAddress(SocketAddr::V6(*addr)));
+                }
+                ip.copy_from_slice(&addr.ip().octets());
+                6
+            }
+        };
+        self.put_bytes(&[family])?;
+        self.put_bytes(&ip)?;
+        self.put_u16(addr.port())
+    }
+
+    fn put_contact_info(&mut self, info: &ContactInfo) -> Result<()> {
+        self.put_bytes(info.id.as_ref())?;
+        for addr in &[
+            info.gossip,
+            info.tvu,
+            info.tvu_forwards,
+            info.repair,
+            info.tpu,
+            info.tpu_forwards,
+            info.storage_addr,
+            info.rpc,
+            info.rpc_pubsub,
+        ] {
+            self.put_addr(addr)?;
+        }
+        self.put_u64(info.wallclock)?;
+        self.put_u16(info.shred_version)?;
+        self.put_u64(info.outset)
+    }
+}
+
+struct WireReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> WireReader<'a> {
+    /// Check the header and return the reader along with the message kind
+    fn new(data: &'a [u8]) -> Result<(Self, u8)> {
+        let mut reader = Self { data, pos: 0 };
+        let header = reader.get_bytes(HEADER_SIZE)?;
+        if header[0] != WIRE_MAGIC {
+            return Err(WireError::BadMagic(header[0]));
+        }
+        if header[1] != WIRE_VERSION {
+            return Err(WireError::UnsupportedVersion(header[1]));
+        }
+        let kind = header[2];
+        Ok((reader, kind))
+    }
+
+    fn get_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos + len;
+        if end > self.data.len() {
+            return Err(WireError::Truncated);
+        }
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn get_u8(&mut self) -> Result<u8> {
+        Ok(self.get_bytes(1)?[0])
+    }
+
+    fn get_u16(&mut self) -> Result<u16> {
+        let mut bytes = [0u8; 2];
+        bytes.copy_from_slice(self.get_bytes(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn get_u64(&mut self) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.get_bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn get_pubkey(&mut self) -> Result<Pubkey> {
+        Ok(Pubkey::new(self.get_bytes(size_of::<Pubkey>())?))
+    }
+
+    fn get_hash(&mut self) -> Result<Hash> {
+        Ok(Hash::new(self.get_bytes(size_of::<Hash>())?))
+    }
+
+    fn get_addr(&mut self) -> Result<SocketAddr> {
+        let family = self.get_u8()?;
+        let ip = self.get_bytes(16)?;
+        let ip = match family {
+            4 => IpAddr::V4(Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3])),
+            6 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(ip);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => return Err(WireError::BadAddressFamily(family)),
+        };
+        Ok(SocketAddr::new(ip, self.get_u16()?))
+    }
+
+    fn get_contact_info(&mut self) -> Result<ContactInfo> {
+        Ok(ContactInfo {
+            id: self.get_pubkey()?,
+            gossip: self.get_addr()?,
+            tvu: self.get_addr()?,
+            tvu_forwards: self.get_addr()?,
+            repair: self.get_addr()?,
+            tpu: self.get_addr()?,
+            tpu_forwards: self.get_addr()?,
+            storage_addr: self.get_addr()?,
+            rpc: self.get_addr()?,
+            rpc_pubsub: self.get_addr()?,
+            wallclock: self.get_u64()?,
+            shred_version: self.get_u16()?,
+            outset: self.get_u64()?,
+        })
+    }
+
+    fn finish<T>(self, message: T) -> Result<T> {
+        let trailing = self.data.len() - self.pos;
+        if trailing > 0 {
+            return Err(WireError::TrailingBytes(trailing));
+        }
+        Ok(message)
+    }
+}
+
+/// Write the compact encoding of a repair request or ancestor hashes response to the front of
+/// `buf`, returning its size
+pub(crate) fn encode_repair(protocol: &Protocol, buf: &mut [u8]) -> Result<usize> {
+    let mut writer = match protocol {
+        Protocol::RequestWindowIndex(from, slot, shred_index)
+        | Protocol::RequestHighestWindowIndex(from, slot, shred_index) => {
+            let kind = match protocol {
+                Protocol::RequestWindowIndex(..) => KIND_REQUEST_WINDOW_INDEX,
+                _ => KIND_REQUEST_HIGHEST_WINDOW_INDEX,
+            };
+            let mut writer = WireWriter::new(buf, kind)?;
+            writer.put_contact_info(from)?;
+            writer.put_u64(*slot)?;
+            writer.put_u64(*shred_index)?;
+            writer
+        }
+        Protocol::RequestOrphan(from, slot) | Protocol::RequestAncestorHashes(from, slot) => {
+            let kind = match protocol {
+                Protocol::RequestOrphan(..) => KIND_REQUEST_ORPHAN,
+                _ => KIND_REQUEST_ANCESTOR_HASHES,
+            };
+            let mut writer = WireWriter::new(buf, kind)?;
+            writer.put_contact_info(from)?;
+            writer.put_u64(*slot)?;
+            writer
+        }
+        Protocol::AncestorHashes(from, slot, hashes) => {
+            if hashes.len() > MAX_ANCESTOR_HASHES {
+                return Err(WireError::TooManyHashes(hashes.len()));
+            }
+            let mut writer = WireWriter::new(buf, KIND_ANCESTOR_HASHES)?;
+            writer.put_bytes(from.as_ref())?;
+            writer.put_u64(*slot)?;
+            writer.put_u16(hashes.len() as u16)?;
+            for (slot, hash) in hashes {
+                writer.put_u64(*slot)?;
+                writer.put_bytes(hash.as_ref())?;
+            }
+            writer
+        }
+        _ => return Err(WireError::NotCompact),
+    };
+    Ok(writer.pos)
+}
+
+pub(crate) fn repair_to_bytes(protocol: &Protocol) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; MAX_REPAIR_REQUEST_SIZE.max(MAX_ANCESTOR_HASHES_SIZE)];
+    let size = encode_repair(protocol, &mut buf)?;
+    buf.truncate(size);
+    Ok(buf)
+}
+
+pub(crate) fn decode_repair(data: &[u8]) -> Result<Protocol> {
+    let (mut reader, kind) = WireReader::new(data)?;
+    let protocol = match kind {
+        KIND_REQUEST_WINDOW_INDEX => Protocol::RequestWindowIndex(
+            reader.get_contact_info()?,
+            reader.get_u64()?,
+            reader.get_u64()?,
+        ),
+        KIND_REQUEST_HIGHEST_WINDOW_INDEX => Protocol::RequestHighestWindowIndex(
+            reader.get_contact_info()?,
+            reader.get_u64()?,
+            reader.get_u64()?,
+        ),
+        KIND_REQUEST_ORPHAN => {
+            Protocol::RequestOrphan(reader.get_contact_info()?, reader.get_u64()?)
+        }
+        KIND_REQUEST_ANCESTOR_HASHES => {
+            Protocol::RequestAncestorHashes(reader.get_contact_info()?, reader.get_u64()?)
+        }
+        KIND_ANCESTOR_HASHES => {
+            let from = reader.get_pubkey()?;
+            let slot = reader.get_u64()?;
+            let num_hashes = reader.get_u16()? as usize;
+            if num_hashes > MAX_ANCESTOR_HASHES {
+                return Err(WireError::TooManyHashes(num_hashes));
+            }
+            let hashes = (0..num_hashes)
+                .map(|_| Ok((reader.get_u64()?, reader.get_hash()?)))
+                .collect::<Result<_>>()?;
+            Protocol::AncestorHashes(from, slot, hashes)
+        }
+        _ => return Err(WireError::UnknownKind(kind)),
+    };
+    reader.finish(protocol)
+}
+
+/// Write the compact encoding of `request` to the front of `buf`, returning its size
+pub fn encode_archiver_request(request: &ArchiverRequest, buf: &mut [u8]) -> Result<usize> {
+    let writer = match request {
+        ArchiverRequest::GetSlotHeight(from)
+        | ArchiverRequest::GetStatus(from)
+        | ArchiverRequest::GetSegmentManifest(from) => {
+            let kind = match request {
+                ArchiverRequest::GetSlotHeight(_) => KIND_ARCHIVER_GET_SLOT_HEIGHT,
+                ArchiverRequest::GetStatus(_) => KIND_ARCHIVER_GET_STATUS,
+                _ => KIND_ARCHIVER_GET_SEGMENT_MANIFEST,
+            };
+            let mut writer = WireWriter::new(buf, kind)?;
+            writer.put_addr(from)?;
+            writer
+        }
+        ArchiverRequest::GetShredRange {
+            from,
+            slot,
+            start_index,
+            count,
+        } => {
+            let mut writer = WireWriter::new(buf, KIND_ARCHIVER_GET_SHRED_RANGE)?;
+            writer.put_addr(from)?;
+            writer.put_u64(*slot)?;
+            writer.put_u64(*start_index)?;
+            writer.put_u64(*count)?;
+            writer
+        }
+    };
+    Ok(writer.pos)
+}
+
+pub fn archiver_request_to_bytes(request: &ArchiverRequest) -> Result<Vec<u8>> {
+    let mut buf = [0u8; HEADER_SIZE + SOCKET_ADDR_SIZE + 3 * size_of::<u64>()];
+    let size = encode_archiver_request(request, &mut buf)?;
+    Ok(buf[..size].to_vec())
+}
+
+pub fn decode_archiver_request(data: &[u8]) -> Result<ArchiverRequest> {
+    let (mut reader, kind) = WireReader::new(data)?;
+    let request = match kind {
+        KIND_ARCHIVER_GET_SLOT_HEIGHT => ArchiverRequest::GetSlotHeight(reader.get_addr()?),
+        KIND_ARCHIVER_GET_STATUS => ArchiverRequest::GetStatus(reader.get_addr()?),
+        KIND_ARCHIVER_GET_SEGMENT_MANIFEST => {
+            ArchiverRequest::GetSegmentManifest(reader.get_addr()?)
+        }
+        KIND_ARCHIVER_GET_SHRED_RANGE => ArchiverRequest::GetShredRange {
+            from: reader.get_addr()?,
+            slot: reader.get_u64()?,
+            start_index: reader.get_u64()?,
+            count: reader.get_u64()?,
+        },
+        _ => return Err(WireError::UnknownKind(kind)),
+    };
+    reader.finish(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cluster_info::PruneData,
+        crds_gossip_pull::CrdsFilter,
+        crds_value::{CrdsData, CrdsValue},
+    };
+    use bincode::serialize;
+    use solana_sdk::timing::timestamp;
+    use std::net::SocketAddrV6;
+
+    fn contact_infos() -> Vec<ContactInfo> {
+        let mut ipv6 = ContactInfo::new_localhost(&Pubkey::new_rand(), timestamp());
+        ipv6.gossip = "[2001:db8::1]:8001".parse().unwrap();
+        ipv6.repair = "[::1]:65535".parse().unwrap();
+        ipv6.shred_version = std::u16::MAX;
+        vec![
+            ContactInfo::default(),
+            ContactInfo::new_localhost(&Pubkey::new_rand(), timestamp()),
+            ipv6,
+        ]
+    }
+
+    fn repair_messages() -> Vec<Protocol> {
+        let mut messages = vec![];
+        for info in contact_infos() {
+            for &(slot, index) in &[(0, 0), (1, 2), (std::u64::MAX, std::u64::MAX)] {
+                messages.push(Protocol::RequestWindowIndex(info.clone(), slot, index));
+                messages.push(Protocol::RequestHighestWindowIndex(
+                    info.clone(),
+                    slot,
+                    index,
+                ));
+                messages.push(Protocol::RequestOrphan(info.clone(), slot));
+                messages.push(Protocol::RequestAncestorHashes(info.clone(), slot));
+            }
+        }
+        for num_hashes in &[0, 1, MAX_ANCESTOR_HASHES] {
+            let hashes = (0..*num_hashes)
+                .map(|i| (i as u64, Hash::new(&[i as u8; 32])))
+                .collect();
+            messages.push(Protocol::AncestorHashes(Pubkey::new_rand(), 7, hashes));
+        }
+        messages
+    }
+
+    fn archiver_requests() -> Vec<ArchiverRequest> {
+        let mut requests = vec![];
+        for from in &["127.0.0.1:1234", "[2001:db8::2]:0", "0.0.0.0:65535"] {
+            let from: SocketAddr = from.parse().unwrap();
+            requests.push(ArchiverRequest::GetSlotHeight(from));
+            requests.push(ArchiverRequest::GetStatus(from));
+            requests.push(ArchiverRequest::GetSegmentManifest(from));
+            requests.push(ArchiverRequest::GetShredRange {
+                from,
+                slot: std::u64::MAX,
+                start_index: 1,
+                count: 64,
+            });
+        }
+        requests
+    }
+
+    fn assert_same_protocol(left: &Protocol, right: &Protocol) {
+        // Protocol isn't PartialEq, its bincode encoding is compared instead
+        assert_eq!(serialize(left).unwrap(), serialize(right).unwrap());
+    }
+
+    #[test]
+    fn test_repair_round_trip() {
+        for message in repair_messages() {
+            let bytes = repair_to_bytes(&message).unwrap();
+            assert!(is_compact(&bytes));
+            assert!(bytes.len() <= MAX_REPAIR_REQUEST_SIZE.max(MAX_ANCESTOR_HASHES_SIZE));
+            assert_same_protocol(&decode_repair(&bytes).unwrap(), &message);
+
+            // every strict prefix is rejected, as is any extra byte
+            for len in 0..bytes.len() {
+                assert!(decode_repair(&bytes[..len]).is_err());
+            }
+            let mut extended = bytes.clone();
+            extended.push(0);
+            assert_eq!(
+                decode_repair(&extended).err(),
+                Some(WireError::TrailingBytes(1))
+            );
+
+            // encoding into a buffer that's too small fails instead of truncating
+            let mut small = vec![0u8; bytes.len() - 1];
+            assert_eq!(
+                encode_repair(&message, &mut small),
+                Err(WireError::BufferTooSmall)
+            );
+        }
+    }
+
+    #[test]
+    fn test_repair_sizes() {
+        let info = ContactInfo::default();
+        assert_eq!(
+            repair_to_bytes(&Protocol::RequestWindowIndex(info.clone(), 0, 0))
+                .unwrap()
+                .len(),
+            MAX_REPAIR_REQUEST_SIZE
+        );
+        assert_eq!(
+            repair_to_bytes(&Protocol::RequestOrphan(info, 0))
+                .unwrap()
+                .len(),
+            MAX_REPAIR_REQUEST_SIZE - size_of::<u64>()
+        );
+        let hashes = vec![(0, Hash::default()); MAX_ANCESTOR_HASHES];
+        assert_eq!(
+            repair_to_bytes(&Protocol::AncestorHashes(Pubkey::default(), 0, hashes))
+                .unwrap()
+                .len(),
+            MAX_ANCESTOR_HASHES_SIZE
+        );
+    }
+
+    #[test]
+    fn test_repair_rejects() {
+        let hashes = vec![(0, Hash::default()); MAX_ANCESTOR_HASHES + 1];
+        assert_eq!(
+            repair_to_bytes(&Protocol::AncestorHashes(Pubkey::default(), 0, hashes)).err(),
+            Some(WireError::TooManyHashes(MAX_ANCESTOR_HASHES + 1))
+        );
+        let prune = Protocol::PruneMessage(Pubkey::default(), PruneData::default());
+        assert_eq!(repair_to_bytes(&prune).err(), Some(WireError::NotCompact));
+
+        // ipv6 flowinfo and scope ids would be lost, so they aren't encoded
+        let mut info = ContactInfo::default();
+        for &(flowinfo, scope_id) in &[(1, 0), (0, 1)] {
+            let addr = SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::LOCALHOST,
+                8001,
+                flowinfo,
+                scope_id,
+            ));
+            info.repair = addr;
+            assert_eq!(
+                repair_to_bytes(&Protocol::RequestOrphan(info.clone(), 1)).err(),
+                Some(WireError::UnsupportedAddress(addr))
+            );
+            assert_eq!(
+                archiver_request_to_bytes(&ArchiverRequest::GetStatus(addr)).err(),
+                Some(WireError::UnsupportedAddress(addr))
+            );
+        }
+
+        // bincode messages are never mistaken for compact ones
+        let pull = Protocol::PullRequest(
+            CrdsFilter::default(),
+            CrdsValue::new_unsigned(CrdsData::ContactInfo(ContactInfo::default())),
+        );
+        for message in repair_messages().iter().chain(&[prune, pull]) {
+            assert!(!is_compact(&serialize(message).unwrap()));
+        }
+
+        let mut bytes =
+            repair_to_bytes(&Protocol::RequestOrphan(ContactInfo::default(), 1)).unwrap();
+        bytes[1] = WIRE_VERSION + 1;
+        assert_eq!(
+            decode_repair(&bytes).err(),
+            Some(WireError::UnsupportedVersion(WIRE_VERSION + 1))
+        );
+        bytes[1] = WIRE_VERSION;
+        bytes[2] = KIND_ARCHIVER_GET_STATUS;
+        assert_eq!(
+            decode_repair(&bytes).err(),
+            Some(WireError::UnknownKind(KIND_ARCHIVER_GET_STATUS))
+        );
+        bytes[2] = KIND_REQUEST_ORPHAN;
+        // the family of the gossip address
+        bytes[HEADER_SIZE + size_of::<Pubkey>()] = 5;
+        assert_eq!(
+            decode_repair(&bytes).err(),
+            Some(WireError::BadAddressFamily(5))
+        );
+        bytes[0] = 0;
+        assert_eq!(decode_repair(&bytes).err(), Some(WireError::BadMagic(0)));
+
+        let mut bytes =
+            repair_to_bytes(&Protocol::AncestorHashes(Pubkey::default(), 0, vec![])).unwrap();
+        let count = bytes.len() - size_of::<u16>();
+        bytes[count..].copy_from_slice(&(MAX_ANCESTOR_HASHES as u16 + 1).to_le_bytes());
+        assert_eq!(
+            decode_repair(&bytes).err(),
+            Some(WireError::TooManyHashes(MAX_ANCESTOR_HASHES + 1))
+        );
+    }
+
+    #[test]
+    fn test_archiver_request_round_trip() {
+        for request in archiver_requests() {
+            let bytes = archiver_request_to_bytes(&request).unwrap();
+            assert!(is_compact(&bytes));
+            let decoded = decode_archiver_request(&bytes).unwrap();
+            assert_eq!(serialize(&decoded).unwrap(), serialize(&request).unwrap());
+
+            for len in 0..bytes.len() {
+                assert!(decode_archiver_request(&bytes[..len]).is_err());
+            }
+            let mut extended = bytes.clone();
+            extended.push(0);
+            assert_eq!(
+                decode_archiver_request(&extended).err(),
+                Some(WireError::TrailingBytes(1))
+            );
+            // repair and archiver kinds don't overlap
+            assert!(decode_repair(&bytes).is_err());
+        }
+        let bytes = repair_to_bytes(&Protocol::RequestOrphan(ContactInfo::default(), 1)).unwrap();
+        assert_eq!(
+            decode_archiver_request(&bytes).err(),
+            Some(WireError::UnknownKind(KIND_REQUEST_ORPHAN))
+        );
+    }
+}
@@ -299,7 +299,7 @@ mod tests {
         // correctly construct the earlier snapshots because the SnapshotPackage's on the
         // channel hold hard links to these deleted snapshots. We verify this is the case below.
         let exit = Arc::new(AtomicBool::new(false));
-        let snapshot_packager_service = SnapshotPackagerService::new(receiver, &exit);
+        let snapshot_packager_service = SnapshotPackagerService::new(receiver, None, &exit);
 
         // Close the channel so that the package service will exit after reading all the
         // packages off the channel
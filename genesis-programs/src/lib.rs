@@ -21,7 +21,9 @@ extern crate solana_vest_program;
 extern crate solana_vote_program;
 
 use log::*;
-use solana_runtime::bank::{Bank, EnteredEpochCallback};
+use solana_runtime::bank::{
+    Bank, EnteredEpochCallback, DEFAULT_MAX_ACCOUNTS_DATA_GROWTH_PER_BLOCK,
+};
 
 pub fn get_inflation(operating_mode: OperatingMode, epoch: Epoch) -> Option<Inflation> {
     match operating_mode {
@@ -99,6 +101,46 @@ pub fn get_programs(operating_mode: OperatingMode, epoch: Epoch) -> Option<Vec<(
     }
 }
 
+/// The limit on account data growth per block in effect at `epoch`, None while it's unlimited
+pub fn get_max_accounts_data_growth(operating_mode: OperatingMode, epoch: Epoch) -> Option<u64> {
+    let activation_epoch = match operating_mode {
+        OperatingMode::Development => 0,
+        // The epoch of std::u64::MAX is a placeholder and is expected to be reduced in a future
+        // hard fork.
+        OperatingMode::SoftLaunch => std::u64::MAX,
+    };
+    if epoch >= activation_epoch {
+        Some(DEFAULT_MAX_ACCOUNTS_DATA_GROWTH_PER_BLOCK)
+    } else {
+        None
+    }
+}
+
+/// The versions of the native programs `get_programs()` registers, for genesis manifests.  The
+/// move loader isn't a dependency of this crate, so its version isn't known here
+pub fn get_program_versions() -> Vec<(String, String)> {
+    vec![
+        (solana_system_program().0, solana_runtime::VERSION),
+        (
+            solana_bpf_loader_program!().0,
+            solana_bpf_loader_program::VERSION,
+        ),
+        (solana_budget_program!().0, solana_budget_program::VERSION),
+        (solana_config_program!().0, solana_config_program::VERSION),
+        (
+            solana_exchange_program!().0,
+            solana_exchange_program::VERSION,
+        ),
+        (solana_stake_program!().0, solana_stake_program::VERSION),
+        (solana_storage_program!().0, solana_storage_program::VERSION),
+        (solana_vest_program!().0, solana_vest_program::VERSION),
+        (solana_vote_program!().0, solana_vote_program::VERSION),
+    ]
+    .into_iter()
+    .map(|(name, version)| (name, version.to_string()))
+    .collect()
+}
+
 pub fn get_entered_epoch_callback(operating_mode: OperatingMode) -> EnteredEpochCallback {
     Box::new(move |bank: &mut Bank| {
         info!(
@@ -109,6 +151,11 @@ pub fn get_entered_epoch_callback(operating_mode: OperatingMode) -> EnteredEpoch
         if let Some(inflation) = get_inflation(operating_mode, bank.epoch()) {
             bank.set_inflation(inflation);
         }
+        if let Some(max_accounts_data_growth) =
+            get_max_accounts_data_growth(operating_mode, bank.epoch())
+        {
+            bank.set_max_accounts_data_growth(max_accounts_data_growth);
+        }
         if let Some(new_programs) = get_programs(operating_mode, bank.epoch()) {
             for (name, program_id) in new_programs.iter() {
                 info!("Registering {} at {}", name, program_id);
@@ -130,6 +177,19 @@ mod tests {
         assert!(ids.into_iter().all(move |id| unique.insert(id)));
     }
 
+    #[test]
+    fn test_program_versions() {
+        let versions: HashSet<_> = get_program_versions()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        let (move_loader, _) = solana_move_loader_program();
+        assert!(get_programs(OperatingMode::Development, 0)
+            .unwrap()
+            .into_iter()
+            .all(|(name, _)| name == move_loader || versions.contains(&name)));
+    }
+
     #[test]
     fn test_development_inflation() {
         assert_eq!(
@@ -148,6 +208,26 @@ mod tests {
         assert_eq!(get_programs(OperatingMode::Development, 1), None);
     }
 
+    #[test]
+    fn test_max_accounts_data_growth() {
+        assert_eq!(
+            get_max_accounts_data_growth(OperatingMode::Development, 0),
+            Some(DEFAULT_MAX_ACCOUNTS_DATA_GROWTH_PER_BLOCK)
+        );
+        assert_eq!(
+            get_max_accounts_data_growth(OperatingMode::Development, 1),
+            Some(DEFAULT_MAX_ACCOUNTS_DATA_GROWTH_PER_BLOCK)
+        );
+        assert_eq!(
+            get_max_accounts_data_growth(OperatingMode::SoftLaunch, 0),
+            None
+        );
+        assert_eq!(
+            get_max_accounts_data_growth(OperatingMode::SoftLaunch, std::u64::MAX),
+            Some(DEFAULT_MAX_ACCOUNTS_DATA_GROWTH_PER_BLOCK)
+        );
+    }
+
     #[test]
     fn test_softlaunch_inflation() {
         assert_eq!(
@@ -12,7 +12,7 @@ use solana_sdk::{
     clock,
     epoch_schedule::EpochSchedule,
     fee_calculator::FeeCalculator,
-    genesis_config::{GenesisConfig, OperatingMode},
+    genesis_config::{GenesisConfig, GenesisManifest, OperatingMode},
     native_token::sol_to_lamports,
     poh_config::PohConfig,
     pubkey::{read_pubkey_file, Pubkey},
@@ -442,6 +442,11 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     solana_stake_api::add_genesis_accounts(&mut genesis_config);
 
     create_new_ledger(&ledger_path, &genesis_config)?;
+    GenesisManifest::new(
+        &genesis_config,
+        &solana_genesis_programs::get_program_versions(),
+    )
+    .write(&ledger_path)?;
     Ok(())
 }
 
@@ -4,8 +4,9 @@ use clap::{
     crate_description, crate_name, values_t_or_exit, App, AppSettings, Arg, ArgMatches, SubCommand,
 };
 use num_cpus;
+use solana_clap_utils::{input_parsers::pubkey_of, input_validators::is_pubkey_or_keypair};
 use solana_sdk::{
-    pubkey::write_pubkey_file,
+    pubkey::{write_pubkey_file, Pubkey},
     signature::{
         keypair_from_seed, read_keypair, read_keypair_file, write_keypair, write_keypair_file,
         Keypair, KeypairUtil,
@@ -14,6 +15,7 @@ use solana_sdk::{
 use std::{
     collections::HashSet,
     error,
+    io::{self, Write},
     path::Path,
     process::exit,
     sync::{
@@ -49,6 +51,36 @@ fn output_keypair(
     Ok(())
 }
 
+fn read_keypair_infile(infile: &str) -> Result<Keypair, Box<dyn error::Error>> {
+    if infile == "-" {
+        let mut stdin = std::io::stdin();
+        read_keypair(&mut stdin)
+    } else {
+        read_keypair_file(infile)
+    }
+}
+
+// Sign a fresh message so a stale signature can't pass for the keypair
+fn keypair_signs_for_pubkey(keypair: &Keypair, pubkey: &Pubkey) -> bool {
+    let message = Keypair::new().pubkey();
+    let signature = keypair.sign_message(message.as_ref());
+    signature.verify(pubkey.as_ref(), message.as_ref())
+}
+
+// Show the recovered pubkey and ask before it gets written anywhere
+fn confirm_recovered_pubkey(pubkey: &Pubkey) {
+    eprint!("Recovered pubkey `{}`. Continue? (y/n): ", pubkey);
+    io::stderr().flush().unwrap();
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .expect("read confirmation");
+    if answer.trim().to_lowercase() != "y" {
+        eprintln!("Exiting");
+        exit(1);
+    }
+}
+
 fn main() -> Result<(), Box<dyn error::Error>> {
     let matches = App::new(crate_name!())
         .about(crate_description!())
@@ -141,6 +173,27 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                         .help("Overwrite the output file if it exists"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Verify a keypair can sign and verify a message.")
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("pubkey")
+                        .index(1)
+                        .value_name("BASE58_PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey_or_keypair)
+                        .help("Public key"),
+                )
+                .arg(
+                    Arg::with_name("keypair")
+                        .index(2)
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .help("Path to keypair file"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("recover")
                 .about("Recover keypair from mnemonic phrase")
@@ -158,6 +211,11 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                         .short("f")
                         .long("force")
                         .help("Overwrite the output file if it exists"),
+                )
+                .arg(
+                    Arg::with_name("skip_confirmation")
+                        .long("skip-confirmation")
+                        .help("Write the recovered keypair without asking to confirm its pubkey"),
                 ),
         )
         .get_matches();
@@ -171,12 +229,7 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                 path.extend(&[".config", "solana", "id.json"]);
                 path.to_str().unwrap()
             };
-            let keypair = if infile == "-" {
-                let mut stdin = std::io::stdin();
-                read_keypair(&mut stdin)?
-            } else {
-                read_keypair_file(infile)?
-            };
+            let keypair = read_keypair_infile(infile)?;
 
             if matches.is_present("outfile") {
                 let outfile = matches.value_of("outfile").unwrap();
@@ -233,8 +286,29 @@ fn main() -> Result<(), Box<dyn error::Error>> {
             let seed = Seed::new(&mnemonic, NO_PASSPHRASE);
             let keypair = keypair_from_seed(seed.as_bytes())?;
 
+            if !matches.is_present("skip_confirmation") {
+                confirm_recovered_pubkey(&keypair.pubkey());
+            }
             output_keypair(&keypair, &outfile, "recovered")?;
         }
+        ("verify", Some(matches)) => {
+            let mut path = dirs::home_dir().expect("home directory");
+            let infile = if matches.is_present("keypair") {
+                matches.value_of("keypair").unwrap()
+            } else {
+                path.extend(&[".config", "solana", "id.json"]);
+                path.to_str().unwrap()
+            };
+            let keypair = read_keypair_infile(infile)?;
+            let pubkey = pubkey_of(matches, "pubkey").unwrap();
+
+            if keypair_signs_for_pubkey(&keypair, &pubkey) {
+                println!("Verification for public key: {}: Success", pubkey);
+            } else {
+                println!("Verification for public key: {}: Failed", pubkey);
+                exit(1);
+            }
+        }
         ("grind", Some(matches)) => {
             let ignore_case = matches.is_present("ignore_case");
             let includes = if matches.is_present("includes") {
@@ -319,3 +393,15 @@ fn main() -> Result<(), Box<dyn error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keypair_signs_for_pubkey() {
+        let keypair = Keypair::new();
+        assert!(keypair_signs_for_pubkey(&keypair, &keypair.pubkey()));
+        assert!(!keypair_signs_for_pubkey(&keypair, &Pubkey::new_rand()));
+    }
+}
@@ -1,5 +1,10 @@
 use clap::{
-    crate_description, crate_name, value_t, value_t_or_exit, values_t_or_exit, App, Arg, SubCommand,
+    crate_description, crate_name, value_t, value_t_or_exit, values_t_or_exit, App, Arg,
+    ArgMatches, SubCommand,
+};
+use solana_core::{
+    archiver::{sample_file, sampling_offsets},
+    chacha::{chacha_cbc_encrypt_ledger, export_segment, CHACHA_BLOCK_SIZE, CHACHA_CHUNK_SLOTS},
 };
 use solana_ledger::{
     bank_forks::{BankForks, SnapshotConfig},
@@ -9,8 +14,9 @@ use solana_ledger::{
     rooted_slot_iterator::RootedSlotIterator,
 };
 use solana_sdk::{
-    clock::Slot, genesis_config::GenesisConfig, instruction_processor_utils::limited_deserialize,
-    native_token::lamports_to_sol, pubkey::Pubkey,
+    clock::Slot, genesis_config::GenesisConfig, hash::Hash,
+    instruction_processor_utils::limited_deserialize, native_token::lamports_to_sol,
+    pubkey::Pubkey, signature::Signature,
 };
 use solana_vote_api::vote_state::VoteState;
 use std::{
@@ -21,6 +27,7 @@ use std::{
     path::{Path, PathBuf},
     process::{exit, Command, Stdio},
     str::FromStr,
+    sync::Arc,
 };
 
 #[derive(PartialEq)]
@@ -389,10 +396,34 @@ fn graph_forks(
 }
 
 #[allow(clippy::cognitive_complexity)]
+// The first slot and length of the segment selected by `--slot` and `--slots-per-segment`
+fn segment_bounds(args_matches: &ArgMatches, genesis_config: &GenesisConfig) -> (Slot, u64) {
+    let slot = value_t_or_exit!(args_matches, "slot", Slot);
+    let slots_per_segment = value_t!(args_matches, "slots_per_segment", u64)
+        .unwrap_or(genesis_config.slots_per_segment);
+    if slots_per_segment == 0 {
+        eprintln!("--slots-per-segment must be greater than 0");
+        exit(1);
+    }
+    (slot - slot % slots_per_segment, slots_per_segment)
+}
+
 fn main() {
     const DEFAULT_ROOT_COUNT: &str = "1";
     solana_logger::setup_with_filter("solana=info");
 
+    let segment_slot_arg = Arg::with_name("slot")
+        .long("slot")
+        .value_name("SLOT")
+        .takes_value(true)
+        .required(true)
+        .help("A slot in the segment, the segment starts at the preceding multiple of --slots-per-segment");
+    let slots_per_segment_arg = Arg::with_name("slots_per_segment")
+        .long("slots-per-segment")
+        .value_name("NUM")
+        .takes_value(true)
+        .help("Slots in a storage segment [default: the value in the ledger's genesis config]");
+
     let starting_slot_arg = Arg::with_name("starting_slot")
         .long("starting-slot")
         .value_name("NUM")
@@ -526,6 +557,65 @@ fn main() {
                     .help("Number of roots in the output"),
             )
         )
+        .subcommand(
+            SubCommand::with_name("export-segment")
+            .about("Write the segment data an archiver encrypts, before encryption")
+            .arg(&segment_slot_arg)
+            .arg(&slots_per_segment_arg)
+            .arg(
+                Arg::with_name("output_file")
+                    .long("output")
+                    .value_name("FILENAME")
+                    .takes_value(true)
+                    .required(true)
+                    .help("File to write the segment to"),
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("verify-proof")
+            .about("Recompute the mining hash of a storage proof the way an archiver does")
+            .arg(&segment_slot_arg)
+            .arg(&slots_per_segment_arg)
+            .arg(
+                Arg::with_name("signature")
+                    .long("signature")
+                    .value_name("SIGNATURE")
+                    .takes_value(true)
+                    .required(true)
+                    .help("The archiver's storage signature, used to encrypt the segment"),
+            )
+            .arg(
+                Arg::with_name("blockhash")
+                    .long("blockhash")
+                    .value_name("HASH")
+                    .takes_value(true)
+                    .required_unless("offsets")
+                    .conflicts_with("offsets")
+                    .help("Storage blockhash to derive the sampling offsets from"),
+            )
+            .arg(
+                Arg::with_name("offsets")
+                    .long("offsets")
+                    .value_name("OFFSETS")
+                    .takes_value(true)
+                    .multiple(true)
+                    .help("Offsets of the 32 byte samples to hash"),
+            )
+            .arg(
+                Arg::with_name("encrypted_segment")
+                    .long("encrypted-segment")
+                    .value_name("FILENAME")
+                    .takes_value(true)
+                    .help("Sample this encrypted segment instead of encrypting the one in the ledger"),
+            )
+            .arg(
+                Arg::with_name("sha_state")
+                    .long("sha-state")
+                    .value_name("HASH")
+                    .takes_value(true)
+                    .help("Mining hash from the proof, exit with an error if it doesn't match"),
+            )
+        )
         .get_matches();
 
     let ledger_path = PathBuf::from(value_t_or_exit!(matches, "ledger", String));
@@ -719,6 +809,88 @@ fn main() {
                     }
                 });
         }
+        ("export-segment", Some(args_matches)) => {
+            let (start_slot, slots_per_segment) = segment_bounds(args_matches, &genesis_config);
+            let output_file = value_t_or_exit!(args_matches, "output_file", String);
+            match export_segment(
+                &Arc::new(blocktree),
+                start_slot,
+                slots_per_segment,
+                Path::new(&output_file),
+            ) {
+                Ok(size) => println!(
+                    "Wrote {} bytes of segment at slot {} to {}",
+                    size, start_slot, output_file
+                ),
+                Err(err) => {
+                    eprintln!("Unable to write {}: {}", output_file, err);
+                    exit(1);
+                }
+            }
+        }
+        ("verify-proof", Some(args_matches)) => {
+            let (start_slot, slots_per_segment) = segment_bounds(args_matches, &genesis_config);
+            let signature = value_t_or_exit!(args_matches, "signature", Signature);
+
+            let (segment_path, remove_segment) =
+                if let Some(path) = args_matches.value_of("encrypted_segment") {
+                    (PathBuf::from(path), false)
+                } else {
+                    let path = std::env::temp_dir()
+                        .join(format!("ledger-tool-segment-{}-{}", start_slot, signature));
+                    let mut ivec = [0u8; CHACHA_BLOCK_SIZE];
+                    ivec.copy_from_slice(signature.as_ref());
+                    if let Err(err) = chacha_cbc_encrypt_ledger(
+                        &Arc::new(blocktree),
+                        start_slot,
+                        slots_per_segment,
+                        CHACHA_CHUNK_SLOTS,
+                        &path,
+                        &ivec,
+                    ) {
+                        eprintln!("Failed to encrypt segment at slot {}: {}", start_slot, err);
+                        exit(1);
+                    }
+                    (path, true)
+                };
+            let num_chacha_blocks = std::fs::metadata(&segment_path)
+                .map(|metadata| metadata.len() as usize / CHACHA_BLOCK_SIZE)
+                .unwrap_or_else(|err| {
+                    eprintln!("Unable to read {:?}: {}", segment_path, err);
+                    exit(1);
+                });
+
+            let offsets = if args_matches.is_present("offsets") {
+                values_t_or_exit!(args_matches, "offsets", u64)
+            } else {
+                let blockhash = value_t_or_exit!(args_matches, "blockhash", Hash);
+                sampling_offsets(&blockhash, num_chacha_blocks)
+            };
+            let sha_state = sample_file(&segment_path, &offsets);
+            if remove_segment {
+                let _ignored = std::fs::remove_file(&segment_path);
+            }
+
+            println!("Segment slot: {}", start_slot);
+            println!("Chacha blocks: {}", num_chacha_blocks);
+            println!("Offsets: {:?}", offsets);
+            match sha_state {
+                Ok(sha_state) => {
+                    println!("Mining hash: {}", sha_state);
+                    if let Some(expected) = args_matches.value_of("sha_state") {
+                        if expected != sha_state.to_string() {
+                            eprintln!("Mining hash does not match {}", expected);
+                            exit(1);
+                        }
+                        println!("Ok");
+                    }
+                }
+                Err(err) => {
+                    eprintln!("Unable to sample segment: {}", err);
+                    exit(1);
+                }
+            }
+        }
         ("bounds", _) => match blocktree.slot_meta_iterator(0) {
             Ok(metas) => {
                 println!("Collecting Ledger information...");
@@ -1,6 +1,8 @@
 use assert_cmd::prelude::*;
 use solana_ledger::create_new_tmp_ledger;
 use solana_ledger::genesis_utils::create_genesis_config;
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::Signature;
 use std::process::Command;
 use std::process::Output;
 
@@ -46,3 +48,56 @@ fn nominal() {
     assert!(output.status.success());
     assert_eq!(count_newlines(&output.stdout), ticks + 1);
 }
+
+#[test]
+fn segment() {
+    let genesis_config = create_genesis_config(100).genesis_config;
+    let (ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_config);
+    let ledger_path = ledger_path.to_str().unwrap();
+
+    let segment_path = format!("{}/segment", ledger_path);
+    let output = run_ledger_tool(&[
+        "-l",
+        &ledger_path,
+        "export-segment",
+        "--slot",
+        "0",
+        "--output",
+        &segment_path,
+    ]);
+    assert!(output.status.success());
+    assert!(std::fs::metadata(&segment_path).unwrap().len() > 0);
+
+    let signature = Signature::default().to_string();
+    let verify_proof = |extra_args: &[&str]| {
+        let mut args: Vec<&str> = vec![
+            "-l",
+            ledger_path,
+            "verify-proof",
+            "--slot",
+            "0",
+            "--signature",
+            signature.as_str(),
+            "--offsets",
+            "0",
+            "1",
+        ];
+        args.extend_from_slice(extra_args);
+        run_ledger_tool(&args)
+    };
+    let output = verify_proof(&[]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let sha_state = stdout
+        .lines()
+        .find(|line| line.starts_with("Mining hash: "))
+        .unwrap()
+        .trim_start_matches("Mining hash: ")
+        .to_string();
+
+    assert!(verify_proof(&["--sha-state", &sha_state]).status.success());
+    let wrong_sha_state = Hash::default().to_string();
+    assert!(!verify_proof(&["--sha-state", &wrong_sha_state])
+        .status
+        .success());
+}
@@ -0,0 +1,129 @@
+use crate::blocktree::*;
+use solana_sdk::clock::Slot;
+
+/// Walks the parent links from a slot back towards the root of the ledger, stopping at the first
+/// slot whose `SlotMeta` or parent is unknown
+pub struct AncestorIterator<'a> {
+    current: Option<Slot>,
+    blocktree: &'a Blocktree,
+}
+
+impl<'a> AncestorIterator<'a> {
+    /// Iterate over the ancestors of `start_slot`, not including `start_slot` itself
+    pub fn new(start_slot: Slot, blocktree: &'a Blocktree) -> Self {
+        let current = Self::parent(start_slot, blocktree);
+        Self { current, blocktree }
+    }
+
+    /// Iterate over `start_slot` followed by its ancestors
+    pub fn new_inclusive(start_slot: Slot, blocktree: &'a Blocktree) -> Self {
+        Self {
+            current: blocktree
+                .meta(start_slot)
+                .expect("Database failure, couldnt fetch SlotMeta")
+                .map(|_| start_slot),
+            blocktree,
+        }
+    }
+
+    fn parent(slot: Slot, blocktree: &Blocktree) -> Option<Slot> {
+        // slot 0 is its own parent
+        if slot == 0 {
+            return None;
+        }
+        blocktree
+            .meta(slot)
+            .expect("Database failure, couldnt fetch SlotMeta")
+            .filter(|slot_meta| slot_meta.is_parent_set() && slot_meta.parent_slot < slot)
+            .map(|slot_meta| slot_meta.parent_slot)
+    }
+}
+
+impl<'a> Iterator for AncestorIterator<'a> {
+    type Item = Slot;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.current;
+        current.map(|slot| {
+            self.current = Self::parent(slot, self.blocktree);
+            slot
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{blocktree_processor::fill_blocktree_slot_with_ticks, get_tmp_ledger_path};
+    use solana_sdk::hash::Hash;
+
+    #[test]
+    fn test_ancestor_iterator() {
+        let blocktree_path = get_tmp_ledger_path!();
+        let blocktree = Blocktree::open(&blocktree_path).unwrap();
+        blocktree.set_roots(&[0]).unwrap();
+        let ticks_per_slot = 5;
+
+        /*
+            Build a blocktree in the ledger with the following fork structure:
+
+                 slot 0
+                   |
+                 slot 1
+                 /   \
+            slot 2   |
+               /     |
+            slot 3   |
+                     |
+                   slot 4
+
+        */
+
+        // Fork 1, ending at slot 3
+        let last_entry_hash = Hash::default();
+        let fork_point = 1;
+        let mut fork_hash = Hash::default();
+        for slot in 0..=3 {
+            let parent = {
+                if slot == 0 {
+                    0
+                } else {
+                    slot - 1
+                }
+            };
+            let last_entry_hash = fill_blocktree_slot_with_ticks(
+                &blocktree,
+                ticks_per_slot,
+                slot,
+                parent,
+                last_entry_hash,
+            );
+
+            if slot == fork_point {
+                fork_hash = last_entry_hash;
+            }
+        }
+
+        // Fork 2, ending at slot 4
+        let _ =
+            fill_blocktree_slot_with_ticks(&blocktree, ticks_per_slot, 4, fork_point, fork_hash);
+
+        let ancestors: Vec<_> = AncestorIterator::new(3, &blocktree).collect();
+        assert_eq!(ancestors, vec![2, 1, 0]);
+
+        let ancestors: Vec<_> = AncestorIterator::new(4, &blocktree).collect();
+        assert_eq!(ancestors, vec![1, 0]);
+
+        let ancestors: Vec<_> = AncestorIterator::new_inclusive(4, &blocktree).collect();
+        assert_eq!(ancestors, vec![4, 1, 0]);
+
+        assert_eq!(AncestorIterator::new(0, &blocktree).next(), None);
+
+        // a slot the ledger knows nothing about has no ancestors
+        assert_eq!(AncestorIterator::new(5, &blocktree).next(), None);
+        assert_eq!(AncestorIterator::new_inclusive(5, &blocktree).next(), None);
+
+        drop(blocktree);
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+}
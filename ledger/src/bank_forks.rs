@@ -165,6 +165,20 @@ impl BankForks {
         bank
     }
 
+    /// Drop the bank of `slot` if it isn't frozen yet, so it can be created again from its
+    /// parent, e.g. to replay a slot that was repaired anew
+    pub fn remove_active_bank(&mut self, slot: Slot) -> Option<Arc<Bank>> {
+        if self.banks.get(&slot)?.is_frozen() {
+            return None;
+        }
+        let bank = self.banks.remove(&slot)?;
+        if Arc::ptr_eq(&bank, &self.working_bank) {
+            // an active bank has no children, so its parent is still in the forks
+            self.working_bank = bank.parent().expect("active bank without a parent");
+        }
+        Some(bank)
+    }
+
     pub fn working_bank(&self) -> Arc<Bank> {
         self.working_bank.clone()
     }
@@ -374,4 +388,17 @@ mod tests {
         bank_forks.insert(child_bank);
         assert_eq!(bank_forks.active_banks(), vec![1]);
     }
+
+    #[test]
+    fn test_bank_forks_remove_active_bank() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let bank = Bank::new(&genesis_config);
+        let mut bank_forks = BankForks::new(0, bank);
+        let child_bank = Bank::new_from_parent(&bank_forks[0u64], &Pubkey::default(), 1);
+        bank_forks.insert(child_bank);
+        assert!(bank_forks.remove_active_bank(0).is_none());
+        assert_eq!(bank_forks.remove_active_bank(1).unwrap().slot(), 1);
+        assert!(bank_forks.get(1).is_none());
+        assert_eq!(bank_forks.working_bank().slot(), 0);
+    }
 }
@@ -30,7 +30,7 @@ pub fn load(
         if tar.exists() {
             info!("Loading snapshot package: {:?}", tar);
             // Fail hard here if snapshot fails to load, don't silently continue
-            let deserialized_bank = snapshot_utils::bank_from_archive(
+            let mut deserialized_bank = snapshot_utils::bank_from_archive(
                 account_paths
                     .clone()
                     .expect("Account paths not present when booting from snapshot"),
@@ -38,6 +38,17 @@ pub fn load(
                 &tar,
             )
             .expect("Load from snapshot failed");
+            // Builtin entrypoints aren't part of the snapshot, install them again
+            deserialized_bank.register_native_programs(&process_options.native_programs)?;
+            // Neither is the account data growth limit
+            if let Some(max_accounts_data_growth) =
+                solana_genesis_programs::get_max_accounts_data_growth(
+                    genesis_config.operating_mode,
+                    deserialized_bank.epoch(),
+                )
+            {
+                deserialized_bank.set_max_accounts_data_growth(max_accounts_data_growth);
+            }
 
             return blocktree_processor::process_blocktree_from_root(
                 genesis_config,
@@ -2,6 +2,7 @@
 //! Proof of History ledger as well as iterative read, append write, and random
 //! access read to a persistent file-based ledger.
 use crate::{
+    ancestor_iterator::AncestorIterator,
     blocktree_db::{
         columns as cf, Column, Database, IteratorDirection, IteratorMode, LedgerColumn, WriteBatch,
     },
@@ -13,7 +14,7 @@ use crate::{
 };
 pub use crate::{
     blocktree_db::{BlocktreeError, Result},
-    blocktree_meta::SlotMeta,
+    blocktree_meta::{DownloadManifest, SlotMeta},
 };
 use bincode::deserialize;
 use log::*;
@@ -37,14 +38,16 @@ use solana_sdk::{
 use std::{
     cell::RefCell,
     cmp,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs,
+    ops::Bound,
     path::{Path, PathBuf},
     rc::Rc,
     sync::{
         mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
         Arc, Mutex, RwLock,
     },
+    time::{Duration, Instant},
 };
 
 pub const BLOCKTREE_DIRECTORY: &str = "rocksdb";
@@ -70,7 +73,10 @@ pub struct Blocktree {
     data_shred_cf: LedgerColumn<cf::ShredData>,
     code_shred_cf: LedgerColumn<cf::ShredCode>,
     transaction_status_cf: LedgerColumn<cf::TransactionStatus>,
+    download_manifest_cf: LedgerColumn<cf::DownloadManifest>,
     last_root: Arc<RwLock<u64>>,
+    // mirrors the orphans column, with when each slot was first seen as an orphan
+    orphan_heads: Arc<RwLock<BTreeMap<Slot, Instant>>>,
     insert_shreds_lock: Arc<Mutex<()>>,
     pub new_shreds_signals: Vec<SyncSender<bool>>,
     pub completed_slots_senders: Vec<SyncSender<Vec<u64>>>,
@@ -102,9 +108,55 @@ pub struct BlocktreeInsertionMetrics {
     pub total_elapsed: u64,
     pub num_inserted: u64,
     pub num_recovered: usize,
+    /// number of data shreds recovered from erasure coding and inserted, per slot
+    pub num_recovered_by_slot: HashMap<Slot, usize>,
     pub index_meta_time: u64,
 }
 
+/// Walks the data shreds of the slots in `[start_slot, end_slot]` in (slot, index) order,
+/// lending each payload straight out of the database iterator so it can be written to a socket
+/// or file without an intermediate copy
+pub struct SlotRangeShredIterator<'a> {
+    db_iterator: DBRawIterator<'a>,
+    end_slot: Slot,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> SlotRangeShredIterator<'a> {
+    /// Returns the next shred's (slot, index) and serialized payload. The payload borrows from
+    /// the iterator and is only valid until the following call.
+    pub fn next_shred(&mut self) -> Option<((Slot, u64), &[u8])> {
+        if self.done {
+            return None;
+        }
+        if self.started {
+            self.db_iterator.next();
+        } else {
+            self.started = true;
+        }
+        if !self.db_iterator.valid() {
+            self.done = true;
+            return None;
+        }
+        // The slices point into buffers rocksdb owns and only stay valid until the iterator
+        // moves. They are returned borrowing `self` mutably, so the caller has to drop them
+        // before the next call can move it.
+        let (key, payload) = unsafe {
+            (
+                self.db_iterator.key_inner()?,
+                self.db_iterator.value_inner()?,
+            )
+        };
+        let (slot, index) = <cf::ShredData as Column>::index(key);
+        if slot > self.end_slot {
+            self.done = true;
+            return None;
+        }
+        Some(((slot, index), payload))
+    }
+}
+
 impl SlotMetaWorkingSetEntry {
     fn new(new_slot_meta: Rc<RefCell<SlotMeta>>, old_slot_meta: Option<SlotMeta>) -> Self {
         Self {
@@ -174,6 +226,7 @@ impl Blocktree {
         let data_shred_cf = db.column();
         let code_shred_cf = db.column();
         let transaction_status_cf = db.column();
+        let download_manifest_cf = db.column();
 
         let db = Arc::new(db);
 
@@ -185,6 +238,14 @@ impl Blocktree {
             .unwrap_or(0);
         let last_root = Arc::new(RwLock::new(max_root));
 
+        // Orphans already in the ledger are aged from the time it's opened
+        let now = Instant::now();
+        let orphan_heads = db
+            .iter::<cf::Orphans>(IteratorMode::Start)?
+            .map(|(slot, _)| (slot, now))
+            .collect();
+        let orphan_heads = Arc::new(RwLock::new(orphan_heads));
+
         info!("{:?} {}", blocktree_path, measure);
         Ok(Blocktree {
             db,
@@ -196,10 +257,12 @@ impl Blocktree {
             data_shred_cf,
             code_shred_cf,
             transaction_status_cf,
+            download_manifest_cf,
             new_shreds_signals: vec![],
             completed_slots_senders: vec![],
             insert_shreds_lock: Arc::new(Mutex::new(())),
             last_root,
+            orphan_heads,
         })
     }
 
@@ -303,6 +366,10 @@ impl Blocktree {
                 .dead_slots_cf
                 .delete_slot(&mut write_batch, from_slot, batch_end)
                 .unwrap_or(false)
+            && self
+                .download_manifest_cf
+                .delete_slot(&mut write_batch, from_slot, batch_end)
+                .unwrap_or(false)
             && self
                 .db
                 .column::<cf::Root>()
@@ -316,6 +383,14 @@ impl Blocktree {
             );
             return Err(e);
         }
+        let mut orphan_heads = self.orphan_heads.write().unwrap();
+        let purged: Vec<_> = orphan_heads
+            .range(from_slot.unwrap()..=batch_end.unwrap())
+            .map(|(slot, _)| *slot)
+            .collect();
+        for slot in purged {
+            orphan_heads.remove(&slot);
+        }
         Ok(end)
     }
 
@@ -343,6 +418,29 @@ impl Blocktree {
         }))
     }
 
+    /// Like `slot_meta_iterator()`, but from `slot` down to the lowest slot in the ledger
+    pub fn reverse_slot_meta_iterator<'a>(
+        &'a self,
+        slot: Slot,
+    ) -> Result<impl Iterator<Item = (u64, SlotMeta)> + 'a> {
+        let meta_iter = self
+            .db
+            .iter::<cf::SlotMeta>(IteratorMode::From(slot, IteratorDirection::Reverse))?;
+        Ok(meta_iter.map(|(slot, slot_meta_bytes)| {
+            (
+                slot,
+                deserialize(&slot_meta_bytes)
+                    .unwrap_or_else(|_| panic!("Could not deserialize SlotMeta for slot {}", slot)),
+            )
+        }))
+    }
+
+    /// The slots `slot` descends from, following the parent links from its parent back to the
+    /// root of the ledger
+    pub fn ancestor_iterator(&self, slot: Slot) -> AncestorIterator {
+        AncestorIterator::new(slot, self)
+    }
+
     pub fn slot_data_iterator<'a>(
         &'a self,
         slot: Slot,
@@ -353,6 +451,57 @@ impl Blocktree {
         Ok(slot_iterator.take_while(move |((shred_slot, _), _)| *shred_slot == slot))
     }
 
+    pub fn slot_range_shred_iterator(
+        &self,
+        start_slot: Slot,
+        end_slot: Slot,
+    ) -> Result<SlotRangeShredIterator> {
+        let mut db_iterator = self
+            .db
+            .raw_iterator_cf(self.db.cf_handle::<cf::ShredData>())?;
+        db_iterator.seek(&<cf::ShredData as Column>::key((start_slot, 0)));
+        Ok(SlotRangeShredIterator {
+            db_iterator,
+            end_slot,
+            started: false,
+            done: start_slot > end_slot,
+        })
+    }
+
+    /// Adds the stored erasure meta of every erasure set that just received data shreds, so a
+    /// set is tried for recovery as soon as enough shreds are present, even when all of its
+    /// coding shreds arrived in earlier batches
+    fn get_erasure_metas_for_data_shreds(
+        &self,
+        just_inserted_data_shreds: &HashMap<(u64, u64), Shred>,
+        erasure_metas: &mut HashMap<(u64, u64), ErasureMeta>,
+    ) -> Result<()> {
+        for &(slot, index) in just_inserted_data_shreds.keys() {
+            let covered = erasure_metas.iter().any(|(&(meta_slot, _), erasure_meta)| {
+                meta_slot == slot
+                    && erasure_meta.start_index() <= index
+                    && index < erasure_meta.end_indexes().0
+            });
+            if covered {
+                continue;
+            }
+            let mut meta_iter = self.db.iter::<cf::ErasureMeta>(IteratorMode::From(
+                (slot, index),
+                IteratorDirection::Reverse,
+            ))?;
+            if let Some(((meta_slot, set_index), erasure_meta_bytes)) = meta_iter.next() {
+                if meta_slot != slot {
+                    continue;
+                }
+                let erasure_meta: ErasureMeta = deserialize(&erasure_meta_bytes)?;
+                if index < erasure_meta.end_indexes().0 {
+                    erasure_metas.insert((slot, set_index), erasure_meta);
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn try_shred_recovery(
         db: &Database,
         erasure_metas: &HashMap<(u64, u64), ErasureMeta>,
@@ -516,7 +665,9 @@ impl Blocktree {
         let insert_shreds_elapsed = start.as_us();
         let mut start = Measure::start("Shred recovery");
         let mut num_recovered = 0;
+        let mut num_recovered_by_slot = HashMap::new();
         if let Some(leader_schedule_cache) = leader_schedule {
+            self.get_erasure_metas_for_data_shreds(&just_inserted_data_shreds, &mut erasure_metas)?;
             let recovered_data = Self::try_shred_recovery(
                 &db,
                 &erasure_metas,
@@ -527,9 +678,10 @@ impl Blocktree {
 
             num_recovered = recovered_data.len();
             recovered_data.into_iter().for_each(|shred| {
-                if let Some(leader) = leader_schedule_cache.slot_leader_at(shred.slot(), None) {
-                    if shred.verify(&leader) {
-                        self.check_insert_data_shred(
+                let slot = shred.slot();
+                if let Some(leader) = leader_schedule_cache.slot_leader_at(slot, None) {
+                    if shred.verify(&leader)
+                        && self.check_insert_data_shred(
                             shred,
                             &mut index_working_set,
                             &mut slot_meta_working_set,
@@ -537,7 +689,9 @@ impl Blocktree {
                             &mut just_inserted_data_shreds,
                             &mut index_meta_time,
                             is_trusted,
-                        );
+                        )
+                    {
+                        *num_recovered_by_slot.entry(slot).or_insert(0) += 1;
                     }
                 }
             });
@@ -560,7 +714,8 @@ impl Blocktree {
         let mut start = Measure::start("Shred recovery");
         // Handle chaining for the members of the slot_meta_working_set that were inserted into,
         // drop the others
-        handle_chaining(&self.db, &mut write_batch, &mut slot_meta_working_set)?;
+        let orphan_updates =
+            handle_chaining(&self.db, &mut write_batch, &mut slot_meta_working_set)?;
         start.stop();
         let chaining_elapsed = start.as_us();
 
@@ -585,6 +740,7 @@ impl Blocktree {
 
         let mut start = Measure::start("Write Batch");
         self.db.write(write_batch)?;
+        self.update_orphan_heads(orphan_updates);
         start.stop();
         let write_batch_elapsed = start.as_us();
 
@@ -608,6 +764,7 @@ impl Blocktree {
             write_batch_elapsed,
             num_inserted,
             num_recovered,
+            num_recovered_by_slot,
             index_meta_time,
         })
     }
@@ -1368,6 +1525,91 @@ impl Blocktree {
         self.dead_slots_cf.put(slot, &true)
     }
 
+    pub fn download_manifest(&self, start_slot: Slot) -> Result<Option<DownloadManifest>> {
+        self.download_manifest_cf.get(start_slot)
+    }
+
+    /// The manifest of the most recent segment download, by start slot
+    pub fn latest_download_manifest(&self) -> Result<Option<DownloadManifest>> {
+        Ok(self
+            .db
+            .iter::<cf::DownloadManifest>(IteratorMode::End)?
+            .next()
+            .map(|(_, manifest_bytes)| deserialize(&manifest_bytes))
+            .transpose()?)
+    }
+
+    pub fn put_download_manifest(&self, manifest: &DownloadManifest) -> Result<()> {
+        self.download_manifest_cf.put(manifest.start_slot, manifest)
+    }
+
+    pub fn remove_download_manifest(&self, start_slot: Slot) -> Result<()> {
+        let mut write_batch = self.db.batch()?;
+        write_batch.delete::<cf::DownloadManifest>(start_slot)?;
+        self.db.write(write_batch)
+    }
+
+    /// Dead slots at or above `slot`, in order
+    pub fn dead_slots_iterator<'a>(
+        &'a self,
+        slot: Slot,
+    ) -> Result<impl Iterator<Item = Slot> + 'a> {
+        let dead_slots_iter = self
+            .db
+            .iter::<cf::DeadSlots>(IteratorMode::From(slot, IteratorDirection::Forward))?;
+        Ok(dead_slots_iter.map(|(slot, _)| slot))
+    }
+
+    /// Delete the shreds of dead `slot` and clear its dead flag so repair downloads the slot
+    /// again. Unlike `purge_slots()`, the slot keeps its parent and children in the chain.
+    pub fn clear_dead_slot(&self, slot: Slot) -> Result<()> {
+        let mut write_batch = self.db.batch()?;
+        self.data_shred_cf
+            .delete_slot(&mut write_batch, Some(slot), Some(slot))?;
+        self.code_shred_cf
+            .delete_slot(&mut write_batch, Some(slot), Some(slot))?;
+        self.erasure_meta_cf
+            .delete_slot(&mut write_batch, Some(slot), Some(slot))?;
+        self.index_cf
+            .delete_slot(&mut write_batch, Some(slot), Some(slot))?;
+        write_batch.delete::<cf::DeadSlots>(slot)?;
+        if let Some(meta) = self.meta(slot)? {
+            let mut cleared_meta = SlotMeta::new(slot, meta.parent_slot);
+            cleared_meta.next_slots = meta.next_slots;
+            write_batch.put::<cf::SlotMeta>(slot, &cleared_meta)?;
+        }
+        self.db.write(write_batch)
+    }
+
+    /// The heads of detached chains of slots greater than `after_slot`, lowest slot first,
+    /// along with how long each has been an orphan. Orphans already in the ledger when it was
+    /// opened are aged from then.
+    pub fn orphan_heads(&self, after_slot: Slot, max: usize) -> Vec<(Slot, Duration)> {
+        let now = Instant::now();
+        self.orphan_heads
+            .read()
+            .unwrap()
+            .range((Bound::Excluded(after_slot), Bound::Unbounded))
+            .take(max)
+            .map(|(slot, since)| (*slot, now.duration_since(*since)))
+            .collect()
+    }
+
+    fn update_orphan_heads(&self, orphan_updates: HashMap<Slot, bool>) {
+        if orphan_updates.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let mut orphan_heads = self.orphan_heads.write().unwrap();
+        for (slot, is_orphan) in orphan_updates {
+            if is_orphan {
+                orphan_heads.entry(slot).or_insert(now);
+            } else {
+                orphan_heads.remove(&slot);
+            }
+        }
+    }
+
     pub fn get_orphans(&self, max: Option<usize>) -> Vec<u64> {
         let mut results = vec![];
 
@@ -1652,17 +1894,26 @@ fn find_slot_meta_in_cached_state<'a>(
 }
 
 // Chaining based on latest discussion here: https://github.com/solana-labs/solana/pull/2253
+// Returns the slots added to (true) or removed from (false) the orphans column
 fn handle_chaining(
     db: &Database,
     write_batch: &mut WriteBatch,
     working_set: &mut HashMap<u64, SlotMetaWorkingSetEntry>,
-) -> Result<()> {
+) -> Result<HashMap<u64, bool>> {
     // Handle chaining for all the SlotMetas that were inserted into
     working_set.retain(|_, entry| entry.did_insert_occur);
     let mut new_chained_slots = HashMap::new();
+    let mut orphan_updates = HashMap::new();
     let working_set_slots: Vec<_> = working_set.keys().collect();
     for slot in working_set_slots {
-        handle_chaining_for_slot(db, write_batch, working_set, &mut new_chained_slots, *slot)?;
+        handle_chaining_for_slot(
+            db,
+            write_batch,
+            working_set,
+            &mut new_chained_slots,
+            &mut orphan_updates,
+            *slot,
+        )?;
     }
 
     // Write all the newly changed slots in new_chained_slots to the write_batch
@@ -1670,7 +1921,7 @@ fn handle_chaining(
         let meta: &SlotMeta = &RefCell::borrow(&*meta);
         write_batch.put::<cf::SlotMeta>(*slot, meta)?;
     }
-    Ok(())
+    Ok(orphan_updates)
 }
 
 fn handle_chaining_for_slot(
@@ -1678,6 +1929,7 @@ fn handle_chaining_for_slot(
     write_batch: &mut WriteBatch,
     working_set: &HashMap<u64, SlotMetaWorkingSetEntry>,
     new_chained_slots: &mut HashMap<u64, Rc<RefCell<SlotMeta>>>,
+    orphan_updates: &mut HashMap<u64, bool>,
     slot: Slot,
 ) -> Result<()> {
     let slot_meta_entry = working_set
@@ -1713,6 +1965,7 @@ fn handle_chaining_for_slot(
                 // column family
                 if is_orphan(&RefCell::borrow(&*prev_slot_meta)) {
                     write_batch.put::<cf::Orphans>(prev_slot, &true)?;
+                    orphan_updates.insert(prev_slot, true);
                 }
             }
         }
@@ -1720,6 +1973,7 @@ fn handle_chaining_for_slot(
         // At this point this slot has received a parent, so it's no longer an orphan
         if was_orphan_slot {
             write_batch.delete::<cf::Orphans>(slot)?;
+            orphan_updates.insert(slot, false);
         }
     }
 
@@ -2029,11 +2283,15 @@ pub mod tests {
     use super::*;
     use crate::{
         entry::{next_entry, next_entry_mut},
-        genesis_utils::{create_genesis_config, GenesisConfigInfo},
+        genesis_utils::{
+            create_genesis_config, create_genesis_config_with_leader, GenesisConfigInfo,
+            BOOTSTRAP_LEADER_LAMPORTS,
+        },
         shred::{max_ticks_per_n_shreds, DataShredHeader},
     };
     use itertools::Itertools;
     use rand::{seq::SliceRandom, thread_rng};
+    use solana_runtime::bank::Bank;
     use solana_sdk::{
         hash::{self, Hash},
         instruction::CompiledInstruction,
@@ -3263,6 +3521,51 @@ pub mod tests {
         Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_orphan_heads() {
+        let blocktree_path = get_tmp_ledger_path!();
+        let orphan_slots = |blocktree: &Blocktree, after_slot, max| {
+            blocktree
+                .orphan_heads(after_slot, max)
+                .into_iter()
+                .map(|(slot, _)| slot)
+                .collect_vec()
+        };
+        {
+            let blocktree = Blocktree::open(&blocktree_path).unwrap();
+            assert!(blocktree.orphan_heads(0, 10).is_empty());
+
+            // Slot 2 chains to missing slot 1, slot 6 to missing slot 5
+            let (shreds, _) = make_slot_entries(2, 1, 1);
+            blocktree.insert_shreds(shreds, None, false).unwrap();
+            let (shreds, _) = make_slot_entries(6, 5, 1);
+            blocktree.insert_shreds(shreds, None, false).unwrap();
+            assert_eq!(orphan_slots(&blocktree, 0, 10), vec![1, 5]);
+            assert_eq!(orphan_slots(&blocktree, 0, 1), vec![1]);
+            assert_eq!(orphan_slots(&blocktree, 1, 10), vec![5]);
+            let ages = blocktree.orphan_heads(0, 10);
+            assert!(ages[0].1 >= ages[1].1);
+
+            // Slot 1 arrives and chains to slot 0, which becomes the orphan
+            let (shreds, _) = make_slot_entries(1, 0, 1);
+            blocktree.insert_shreds(shreds, None, false).unwrap();
+            assert_eq!(orphan_slots(&blocktree, 0, 10), vec![5]);
+            assert_eq!(blocktree.get_orphans(None), vec![0, 5]);
+        }
+        {
+            // Orphans are loaded from the ledger when it's reopened
+            let blocktree = Blocktree::open(&blocktree_path).unwrap();
+            assert_eq!(orphan_slots(&blocktree, 0, 10), vec![5]);
+            let (shreds, _) = make_slot_entries(9, 8, 1);
+            blocktree.insert_shreds(shreds, None, false).unwrap();
+            assert_eq!(orphan_slots(&blocktree, 0, 10), vec![5, 8]);
+
+            blocktree.purge_slots(5, Some(6));
+            assert_eq!(orphan_slots(&blocktree, 0, 10), vec![8]);
+        }
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
     fn test_insert_data_shreds_slots(name: &str, should_bulk_write: bool) {
         let blocktree_path = get_ledger_path_from_name(name);
         {
@@ -3763,6 +4066,55 @@ pub mod tests {
         Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_recovery_triggered_by_data_shreds() {
+        let leader_keypair = Arc::new(Keypair::new());
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config_with_leader(
+            100,
+            &leader_keypair.pubkey(),
+            BOOTSTRAP_LEADER_LAMPORTS,
+        );
+        let bank = Bank::new(&genesis_config);
+        let leader_schedule_cache = Arc::new(LeaderScheduleCache::new_from_bank(&bank));
+
+        let slot = 1;
+        let entries = create_ticks(max_ticks_per_n_shreds(4), 0, Hash::default());
+        let shredder = Shredder::new(slot, 0, 1.0, leader_keypair, 0, 0).unwrap();
+        let (data_shreds, coding_shreds, _) = shredder.entries_to_shreds(&entries, true, 0);
+        let num_data = data_shreds.len();
+        assert_eq!(coding_shreds.len(), num_data);
+
+        let blocktree_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&blocktree_path).unwrap();
+            // Half of the coding shreds alone are not enough to recover
+            let metrics = blocktree
+                .insert_shreds(
+                    coding_shreds[..num_data / 2].to_vec(),
+                    Some(&leader_schedule_cache),
+                    false,
+                )
+                .unwrap();
+            assert_eq!(metrics.num_recovered, 0);
+            assert!(!blocktree.is_full(slot));
+
+            // The data shreds reach the threshold, recovery runs without any new coding shreds
+            let metrics = blocktree
+                .insert_shreds(
+                    data_shreds[..num_data - num_data / 2].to_vec(),
+                    Some(&leader_schedule_cache),
+                    false,
+                )
+                .unwrap();
+            assert_eq!(
+                metrics.num_recovered_by_slot.get(&slot),
+                Some(&(num_data / 2))
+            );
+            assert!(blocktree.is_full(slot));
+        }
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     pub fn test_insert_multiple_is_last() {
         let (shreds, _) = make_slot_entries(0, 0, 20);
@@ -3821,6 +4173,46 @@ pub mod tests {
         Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_slot_range_shred_iterator() {
+        let blocktree_path = get_tmp_ledger_path!();
+        let blocktree = Blocktree::open(&blocktree_path).unwrap();
+        let slots = vec![2, 4, 8, 12];
+        for (slot_shreds, _) in make_chaining_slot_entries(&slots, 10) {
+            blocktree.insert_shreds(slot_shreds, None, false).unwrap();
+        }
+
+        let collect = |start_slot, end_slot| {
+            let mut shred_iter = blocktree
+                .slot_range_shred_iterator(start_slot, end_slot)
+                .unwrap();
+            let mut result = vec![];
+            while let Some((key, payload)) = shred_iter.next_shred() {
+                result.push((key, payload.to_vec()));
+            }
+            assert!(shred_iter.next_shred().is_none());
+            result
+        };
+
+        // Payloads match the stored shreds, in order, for every slot in the range
+        let expected: Vec<_> = [4, 8]
+            .iter()
+            .flat_map(|slot| blocktree.slot_data_iterator(*slot).unwrap())
+            .map(|(key, payload)| (key, payload.to_vec()))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(collect(3, 11), expected);
+        assert_eq!(collect(4, 8), expected);
+
+        // Empty and inverted ranges yield nothing
+        assert!(collect(5, 7).is_empty());
+        assert!(collect(8, 4).is_empty());
+        assert_eq!(collect(12, std::u64::MAX).len(), collect(12, 12).len());
+
+        drop(blocktree);
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_set_roots() {
         let blocktree_path = get_tmp_ledger_path!();
@@ -3876,6 +4268,90 @@ pub mod tests {
         Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_clear_dead_slot() {
+        let blocktree_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&blocktree_path).unwrap();
+            let (shreds, _) = make_many_slot_entries(0, 3, 5);
+            blocktree.insert_shreds(shreds, None, false).unwrap();
+            blocktree.set_dead_slot(1).unwrap();
+            blocktree.set_dead_slot(2).unwrap();
+            assert_eq!(
+                blocktree
+                    .dead_slots_iterator(0)
+                    .unwrap()
+                    .collect::<Vec<_>>(),
+                vec![1, 2]
+            );
+
+            blocktree.clear_dead_slot(1).unwrap();
+            assert!(!blocktree.is_dead(1));
+            assert_eq!(
+                blocktree
+                    .dead_slots_iterator(0)
+                    .unwrap()
+                    .collect::<Vec<_>>(),
+                vec![2]
+            );
+            let meta = blocktree.meta(1).unwrap().unwrap();
+            assert!(!meta.is_full());
+            assert_eq!(meta.parent_slot, 0);
+            assert_eq!(meta.next_slots, vec![2]);
+            assert!(blocktree.get_data_shred(1, 0).unwrap().is_none());
+
+            // the rest of the chain is untouched
+            assert!(blocktree.meta(2).unwrap().unwrap().is_full());
+            assert!(blocktree.get_data_shred(0, 0).unwrap().is_some());
+        }
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
+    #[test]
+    fn test_download_manifest() {
+        let blocktree_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&blocktree_path).unwrap();
+            assert_eq!(blocktree.latest_download_manifest().unwrap(), None);
+
+            let mut manifest = DownloadManifest::new(8, 4);
+            assert_eq!(manifest.end_slot(), 12);
+            manifest.set_slot_complete(9);
+            manifest.set_slot_complete(12);
+            assert!(manifest.is_slot_complete(9));
+            assert!(!manifest.is_slot_complete(12));
+            assert_eq!(manifest.first_incomplete_slot(), Some(8));
+            blocktree.put_download_manifest(&manifest).unwrap();
+            blocktree
+                .put_download_manifest(&DownloadManifest::new(0, 4))
+                .unwrap();
+            assert_eq!(
+                blocktree.download_manifest(8).unwrap(),
+                Some(manifest.clone())
+            );
+            assert_eq!(
+                blocktree.latest_download_manifest().unwrap(),
+                Some(manifest)
+            );
+
+            for slot in 8..12 {
+                let mut manifest = blocktree.download_manifest(8).unwrap().unwrap();
+                manifest.set_slot_complete(slot);
+                blocktree.put_download_manifest(&manifest).unwrap();
+            }
+            let manifest = blocktree.download_manifest(8).unwrap().unwrap();
+            assert!(manifest.is_complete());
+            assert_eq!(manifest.num_complete(), 4);
+
+            blocktree.remove_download_manifest(8).unwrap();
+            assert_eq!(blocktree.download_manifest(8).unwrap(), None);
+            // purging a segment's slots drops its manifest too
+            blocktree.purge_slots(0, Some(4));
+            assert_eq!(blocktree.latest_download_manifest().unwrap(), None);
+        }
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_purge_slots() {
         let blocktree_path = get_tmp_ledger_path!();
@@ -3950,6 +4426,39 @@ pub mod tests {
         Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_reverse_slot_meta_iterator() {
+        let blocktree_path = get_tmp_ledger_path!();
+        let blocktree = Blocktree::open(&blocktree_path).unwrap();
+
+        let (shreds, _) = make_many_slot_entries(0, 5, 1);
+        blocktree.insert_shreds(shreds, None, false).unwrap();
+
+        let slots: Vec<_> = blocktree
+            .reverse_slot_meta_iterator(3)
+            .unwrap()
+            .map(|(slot, meta)| {
+                assert_eq!(meta.slot, slot);
+                slot
+            })
+            .collect();
+        assert_eq!(slots, vec![3, 2, 1, 0]);
+
+        // starting past the last slot begins at the last slot
+        let slots: Vec<_> = blocktree
+            .reverse_slot_meta_iterator(10)
+            .unwrap()
+            .map(|(slot, _)| slot)
+            .collect();
+        assert_eq!(slots, vec![4, 3, 2, 1, 0]);
+
+        let ancestors: Vec<_> = blocktree.ancestor_iterator(4).collect();
+        assert_eq!(ancestors, vec![3, 2, 1, 0]);
+
+        drop(blocktree);
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_get_completed_data_ranges() {
         let completed_data_end_indexes = vec![2, 4, 9, 11];
@@ -34,6 +34,8 @@ const DATA_SHRED_CF: &str = "data_shred";
 const CODE_SHRED_CF: &str = "code_shred";
 /// Column family for Transaction Status
 const TRANSACTION_STATUS_CF: &str = "transaction_status";
+/// Column family for archiver segment download progress
+const DOWNLOAD_MANIFEST_CF: &str = "download_manifest";
 
 #[derive(Debug)]
 pub enum BlocktreeError {
@@ -114,6 +116,10 @@ pub mod columns {
     #[derive(Debug)]
     /// The transaction status column
     pub struct TransactionStatus;
+
+    #[derive(Debug)]
+    /// The segment download manifest column
+    pub struct DownloadManifest;
 }
 
 #[derive(Debug)]
@@ -122,8 +128,8 @@ struct Rocks(rocksdb::DB);
 impl Rocks {
     fn open(path: &Path) -> Result<Rocks> {
         use columns::{
-            DeadSlots, ErasureMeta, Index, Orphans, Root, ShredCode, ShredData, SlotMeta,
-            TransactionStatus,
+            DeadSlots, DownloadManifest, ErasureMeta, Index, Orphans, Root, ShredCode, ShredData,
+            SlotMeta, TransactionStatus,
         };
 
         fs::create_dir_all(&path)?;
@@ -146,6 +152,8 @@ impl Rocks {
             ColumnFamilyDescriptor::new(ShredCode::NAME, get_cf_options());
         let transaction_status_cf_descriptor =
             ColumnFamilyDescriptor::new(TransactionStatus::NAME, get_cf_options());
+        let download_manifest_cf_descriptor =
+            ColumnFamilyDescriptor::new(DownloadManifest::NAME, get_cf_options());
 
         let cfs = vec![
             meta_cf_descriptor,
@@ -157,6 +165,7 @@ impl Rocks {
             shred_data_cf_descriptor,
             shred_code_cf_descriptor,
             transaction_status_cf_descriptor,
+            download_manifest_cf_descriptor,
         ];
 
         // Open the database
@@ -167,8 +176,8 @@ impl Rocks {
 
     fn columns(&self) -> Vec<&'static str> {
         use columns::{
-            DeadSlots, ErasureMeta, Index, Orphans, Root, ShredCode, ShredData, SlotMeta,
-            TransactionStatus,
+            DeadSlots, DownloadManifest, ErasureMeta, Index, Orphans, Root, ShredCode, ShredData,
+            SlotMeta, TransactionStatus,
         };
 
         vec![
@@ -181,6 +190,7 @@ impl Rocks {
             ShredData::NAME,
             ShredCode::NAME,
             TransactionStatus::NAME,
+            DownloadManifest::NAME,
         ]
     }
 
@@ -287,6 +297,33 @@ impl Column for columns::TransactionStatus {
     }
 }
 
+impl Column for columns::DownloadManifest {
+    const NAME: &'static str = DOWNLOAD_MANIFEST_CF;
+    type Index = u64;
+
+    fn key(start_slot: Slot) -> Vec<u8> {
+        let mut key = vec![0; 8];
+        BigEndian::write_u64(&mut key[..], start_slot);
+        key
+    }
+
+    fn index(key: &[u8]) -> u64 {
+        BigEndian::read_u64(&key[..8])
+    }
+
+    fn slot(index: Self::Index) -> Slot {
+        index
+    }
+
+    fn as_index(slot: Slot) -> Self::Index {
+        slot
+    }
+}
+
+impl TypedColumn for columns::DownloadManifest {
+    type Type = blocktree_meta::DownloadManifest;
+}
+
 impl Column for columns::ShredCode {
     const NAME: &'static str = CODE_SHRED_CF;
     type Index = (u64, u64);
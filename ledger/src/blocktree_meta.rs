@@ -65,6 +65,16 @@ pub struct ErasureMeta {
     pub config: ErasureConfig,
 }
 
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+/// Progress of an archiver segment download, so a restarted download can skip the slots that
+/// were already complete
+pub struct DownloadManifest {
+    /// First slot of the segment being downloaded
+    pub start_slot: Slot,
+    /// Whether each slot of the segment, starting at `start_slot`, is complete
+    completed: Vec<bool>,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ErasureMetaStatus {
     CanRecover,
@@ -251,6 +261,61 @@ impl ErasureMeta {
     }
 }
 
+impl DownloadManifest {
+    pub fn new(start_slot: Slot, slots_per_segment: u64) -> Self {
+        DownloadManifest {
+            start_slot,
+            completed: vec![false; slots_per_segment as usize],
+        }
+    }
+
+    pub fn slots_per_segment(&self) -> u64 {
+        self.completed.len() as u64
+    }
+
+    /// One past the last slot of the segment
+    pub fn end_slot(&self) -> Slot {
+        self.start_slot + self.slots_per_segment()
+    }
+
+    pub fn is_slot_complete(&self, slot: Slot) -> bool {
+        slot >= self.start_slot
+            && self
+                .completed
+                .get((slot - self.start_slot) as usize)
+                .cloned()
+                .unwrap_or(false)
+    }
+
+    /// Mark `slot` complete, slots outside the segment are ignored
+    pub fn set_slot_complete(&mut self, slot: Slot) {
+        if slot >= self.start_slot {
+            if let Some(completed) = self.completed.get_mut((slot - self.start_slot) as usize) {
+                *completed = true;
+            }
+        }
+    }
+
+    /// The first slot of the segment that isn't complete yet, `None` once all of them are
+    pub fn first_incomplete_slot(&self) -> Option<Slot> {
+        self.completed
+            .iter()
+            .position(|completed| !completed)
+            .map(|offset| self.start_slot + offset as u64)
+    }
+
+    pub fn num_complete(&self) -> u64 {
+        self.completed
+            .iter()
+            .filter(|completed| **completed)
+            .count() as u64
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.first_incomplete_slot().is_none()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
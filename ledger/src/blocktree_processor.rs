@@ -12,7 +12,12 @@ use rand::{seq::SliceRandom, thread_rng};
 use rayon::{prelude::*, ThreadPool};
 use solana_metrics::{datapoint, datapoint_error, inc_new_counter_debug};
 use solana_rayon_threadlimit::get_thread_count;
-use solana_runtime::{bank::Bank, transaction_batch::TransactionBatch};
+use solana_runtime::{
+    accounts::TransactionLoadResult,
+    bank::Bank,
+    native_program_registry::{NativeProgramError, NativeProgramRegistry},
+    transaction_batch::TransactionBatch,
+};
 use solana_sdk::{
     clock::{Slot, MAX_RECENT_BLOCKHASHES},
     genesis_config::GenesisConfig,
@@ -34,6 +39,16 @@ thread_local!(static PAR_THREAD_POOL: RefCell<ThreadPool> = RefCell::new(rayon::
                     .unwrap())
 );
 
+/// A batch executed by `Bank::load_and_execute_transactions`, waiting to be committed
+type ExecutedBatch = (
+    Vec<Result<TransactionLoadResult>>,
+    Vec<Result<()>>,
+    Vec<i64>,
+    Vec<usize>,
+    u64,
+    u64,
+);
+
 fn first_err(results: &[Result<()>]) -> Result<()> {
     for r in results {
         if r.is_err() {
@@ -43,10 +58,17 @@ fn first_err(results: &[Result<()>]) -> Result<()> {
     Ok(())
 }
 
-fn execute_batch(batch: &TransactionBatch) -> Result<()> {
-    let results = batch
-        .bank()
-        .load_execute_and_commit_transactions(batch, MAX_RECENT_BLOCKHASHES);
+fn commit_batch(batch: &TransactionBatch, executed: ExecutedBatch) -> Result<()> {
+    let (mut loaded_accounts, executed, data_deltas, _, tx_count, signature_count) = executed;
+    let results = batch.bank().commit_transactions(
+        batch.transactions(),
+        batch.iteration_order(),
+        &mut loaded_accounts,
+        &executed,
+        &data_deltas,
+        tx_count,
+        signature_count,
+    );
 
     let mut first_err = None;
     for (result, transaction) in results.iter().zip(batch.transactions()) {
@@ -77,12 +99,32 @@ fn execute_batches(
     entry_callback: Option<&ProcessCallback>,
 ) -> Result<()> {
     inc_new_counter_debug!("bank-par_execute_entries-count", batches.len());
+    let mut executed_batches: Vec<ExecutedBatch> = PAR_THREAD_POOL.with(|thread_pool| {
+        thread_pool.borrow().install(|| {
+            batches
+                .into_par_iter()
+                .map(|batch| bank.load_and_execute_transactions(batch, MAX_RECENT_BLOCKHASHES))
+                .collect()
+        })
+    });
+
+    // Whether a transaction fits in the block's account data growth limit depends on the
+    // transactions before it, so reserve the growth in entry order like the leader did
+    for (batch, (_, executed, data_deltas, _, tx_count, _)) in
+        batches.iter().zip(executed_batches.iter_mut())
+    {
+        let (_, failed) =
+            bank.reserve_accounts_data_growth(batch.iteration_order(), executed, data_deltas);
+        *tx_count -= failed;
+    }
+
     let results: Vec<Result<()>> = PAR_THREAD_POOL.with(|thread_pool| {
         thread_pool.borrow().install(|| {
             batches
                 .into_par_iter()
-                .map(|batch| {
-                    let result = execute_batch(batch);
+                .zip(executed_batches.into_par_iter())
+                .map(|(batch, executed)| {
+                    let result = commit_batch(batch, executed);
                     if let Some(entry_callback) = entry_callback {
                         entry_callback(bank);
                     }
@@ -193,6 +235,7 @@ pub enum BlocktreeProcessorError {
     FailedToLoadMeta,
     InvalidBlock(BlockError),
     InvalidTransaction,
+    InvalidNativePrograms(NativeProgramError),
 }
 
 impl From<BlockError> for BlocktreeProcessorError {
@@ -201,6 +244,12 @@ impl From<BlockError> for BlocktreeProcessorError {
     }
 }
 
+impl From<NativeProgramError> for BlocktreeProcessorError {
+    fn from(native_program_error: NativeProgramError) -> Self {
+        BlocktreeProcessorError::InvalidNativePrograms(native_program_error)
+    }
+}
+
 /// Callback for accessing bank state while processing the blocktree
 pub type ProcessCallback = Arc<dyn Fn(&Bank) -> () + Sync + Send>;
 
@@ -211,6 +260,8 @@ pub struct ProcessOptions {
     pub dev_halt_at_slot: Option<Slot>,
     pub entry_callback: Option<ProcessCallback>,
     pub override_num_threads: Option<usize>,
+    // Native programs to install in the root bank, whether it starts from genesis or a snapshot
+    pub native_programs: NativeProgramRegistry,
 }
 
 pub fn process_blocktree(
@@ -229,7 +280,14 @@ pub fn process_blocktree(
     }
 
     // Setup bank for slot 0
-    let bank0 = Arc::new(Bank::new_with_paths(&genesis_config, account_paths));
+    let mut bank0 = Bank::new_with_paths(&genesis_config, account_paths);
+    bank0.register_native_programs(&opts.native_programs)?;
+    if let Some(max_accounts_data_growth) =
+        solana_genesis_programs::get_max_accounts_data_growth(genesis_config.operating_mode, 0)
+    {
+        bank0.set_max_accounts_data_growth(max_accounts_data_growth);
+    }
+    let bank0 = Arc::new(bank0);
     info!("processing ledger for bank 0...");
     process_bank_0(&bank0, blocktree, &opts)?;
     process_blocktree_from_root(genesis_config, blocktree, bank0, &opts)
@@ -44,6 +44,7 @@
 use reed_solomon_erasure::galois_8::Field;
 use reed_solomon_erasure::ReedSolomon;
 use serde::{Deserialize, Serialize};
+use solana_perf::perf_libs;
 
 //TODO(sakridge) pick these values
 /// Number of data shreds
@@ -122,6 +123,61 @@ impl Session {
     }
 }
 
+/// Create the coding blocks of several erasure sets in one batch on the GPU, overwriting `parity`.
+/// Returns `false`, leaving `parity` untouched, if perf-libs with erasure support isn't loaded or
+/// the encode fails, in which case the sets must be encoded with a `Session` each.
+/// Every data and parity block must be the same size.
+pub fn encode_many_gpu(data: &[Vec<&[u8]>], parity: &mut [Vec<&mut [u8]>]) -> bool {
+    let encode_many = match perf_libs::api().and_then(|api| api.reed_solomon_encode_many.as_ref()) {
+        Some(encode_many) => encode_many,
+        None => return false,
+    };
+    assert_eq!(data.len(), parity.len());
+    let block_len = match data.first().and_then(|set| set.first()) {
+        Some(block) => block.len(),
+        None => return true,
+    };
+    assert!(data.iter().flatten().all(|block| block.len() == block_len));
+    assert!(parity
+        .iter()
+        .flatten()
+        .all(|block| block.len() == block_len));
+
+    let num_data: Vec<u32> = data.iter().map(|set| set.len() as u32).collect();
+    let num_parity: Vec<u32> = parity.iter().map(|set| set.len() as u32).collect();
+    let data_buffer: Vec<u8> = data
+        .iter()
+        .flatten()
+        .flat_map(|block| block.iter())
+        .cloned()
+        .collect();
+    let mut parity_buffer = vec![0u8; num_parity.iter().sum::<u32>() as usize * block_len];
+
+    let res = unsafe {
+        (encode_many)(
+            data_buffer.as_ptr(),
+            parity_buffer.as_mut_ptr(),
+            block_len as u32,
+            num_data.as_ptr(),
+            num_parity.as_ptr(),
+            data.len() as u32,
+            1,
+        )
+    };
+    if res != 0 {
+        inc_new_counter_debug!("erasure-gpu_encode_failures", 1);
+        return false;
+    }
+    for (block, encoded) in parity
+        .iter_mut()
+        .flatten()
+        .zip(parity_buffer.chunks(block_len))
+    {
+        block.copy_from_slice(encoded);
+    }
+    true
+}
+
 impl Default for Session {
     fn default() -> Session {
         Session::new(NUM_DATA, NUM_CODING).unwrap()
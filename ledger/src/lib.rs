@@ -1,3 +1,4 @@
+pub mod ancestor_iterator;
 pub mod bank_forks;
 pub mod bank_forks_utils;
 pub mod block_error;
@@ -1,7 +1,7 @@
 //! The `shred` module defines data structures and methods to pull MTU sized data frames from the network.
 use crate::{
     entry::{create_ticks, Entry},
-    erasure::Session,
+    erasure::{self, Session},
 };
 use core::cell::RefCell;
 use rayon::{
@@ -20,7 +20,7 @@ use solana_sdk::{
     signature::{Keypair, KeypairUtil, Signature},
 };
 use std::mem::size_of;
-use std::{sync::Arc, time::Instant};
+use std::{fmt, sync::Arc, time::Instant};
 
 /// The following constants are computed by hand, and hardcoded.
 /// `test_shred_constants` ensures that the values are correct.
@@ -46,6 +46,9 @@ pub const DATA_SHRED: u8 = 0b1010_0101;
 pub const CODING_SHRED: u8 = 0b0101_1010;
 
 pub const MAX_DATA_SHREDS_PER_FEC_BLOCK: u32 = 32;
+
+/// Below this many FEC sets in a batch the GPU transfers cost more than encoding on the CPU
+pub const MIN_FEC_SETS_FOR_GPU_CODING: usize = 4;
 pub const RECOMMENDED_FEC_RATE: f32 = 1.0;
 
 pub const SHRED_TICK_REFERENCE_MASK: u8 = 0b0011_1111;
@@ -57,6 +60,8 @@ pub enum ShredError {
     InvalidShredType,
     InvalidFecRate(f32), // FEC rate must be more than 0.0 and less than 1.0
     SlotTooLow { slot: Slot, parent_slot: Slot }, // "Current slot must be > Parent slot, but the difference must not be > u16::MAX
+    InvalidParentOffset { slot: Slot, parent_offset: u16 }, // parent_offset must not be > slot
+    InvalidPayloadSize(usize),                    // payload is too short to hold the shred headers
     Serialize(std::boxed::Box<bincode::ErrorKind>),
 }
 
@@ -110,13 +115,17 @@ pub struct Shred {
 }
 
 impl Shred {
-    fn deserialize_obj<'de, T>(index: &mut usize, size: usize, buf: &'de [u8]) -> bincode::Result<T>
+    fn deserialize_obj<'de, T>(index: &mut usize, size: usize, buf: &'de [u8]) -> Result<T>
     where
         T: Deserialize<'de>,
     {
+        let end = *index + size;
+        if end > buf.len() {
+            return Err(ShredError::InvalidPayloadSize(buf.len()));
+        }
         let ret = bincode::config()
             .limit(PACKET_DATA_SIZE as u64)
-            .deserialize(&buf[*index..*index + size])?;
+            .deserialize(&buf[*index..end])?;
         *index += size;
         Ok(ret)
     }
@@ -211,6 +220,12 @@ impl Shred {
         } else if common_header.shred_type == ShredType(DATA_SHRED) {
             let data_header: DataShredHeader =
                 Self::deserialize_obj(&mut start, SIZE_OF_DATA_SHRED_HEADER, &payload)?;
+            if u64::from(data_header.parent_offset) > common_header.slot {
+                return Err(ShredError::InvalidParentOffset {
+                    slot: common_header.slot,
+                    parent_offset: data_header.parent_offset,
+                });
+            }
             Self {
                 common_header,
                 data_header,
@@ -388,7 +403,6 @@ impl Shred {
     }
 }
 
-#[derive(Debug)]
 pub struct Shredder {
     slot: Slot,
     parent_slot: Slot,
@@ -399,6 +413,21 @@ pub struct Shredder {
     reference_tick: u8,
 }
 
+// `Keypair`'s own `Debug` prints its secret key
+impl fmt::Debug for Shredder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shredder")
+            .field("slot", &self.slot)
+            .field("parent_slot", &self.parent_slot)
+            .field("version", &self.version)
+            .field("fec_rate", &self.fec_rate)
+            .field("keypair", &self.keypair.pubkey())
+            .field("signing_coding_time", &self.signing_coding_time)
+            .field("reference_tick", &self.reference_tick)
+            .finish()
+    }
+}
+
 impl Shredder {
     pub fn new(
         slot: Slot,
@@ -479,20 +508,35 @@ impl Shredder {
         let gen_data_time = now.elapsed().as_millis();
 
         let now = Instant::now();
-        // 2) Generate coding shreds
-        let mut coding_shreds: Vec<_> = PAR_THREAD_POOL.with(|thread_pool| {
-            thread_pool.borrow().install(|| {
-                data_shreds
-                    .par_chunks(MAX_DATA_SHREDS_PER_FEC_BLOCK as usize)
-                    .flat_map(|shred_data_batch| {
-                        Shredder::generate_coding_shreds(
-                            self.slot,
-                            self.fec_rate,
-                            shred_data_batch,
-                            self.version,
-                        )
-                    })
-                    .collect()
+        // 2) Generate coding shreds, batching the FEC sets on the GPU when there are enough of them
+        let num_fec_sets = (data_shreds.len() + MAX_DATA_SHREDS_PER_FEC_BLOCK as usize - 1)
+            / MAX_DATA_SHREDS_PER_FEC_BLOCK as usize;
+        let gpu_coding_shreds = if num_fec_sets >= MIN_FEC_SETS_FOR_GPU_CODING {
+            Shredder::generate_coding_shreds_gpu(
+                self.slot,
+                self.fec_rate,
+                &data_shreds,
+                self.version,
+            )
+        } else {
+            None
+        };
+        let gpu_coding = gpu_coding_shreds.is_some();
+        let mut coding_shreds: Vec<_> = gpu_coding_shreds.unwrap_or_else(|| {
+            PAR_THREAD_POOL.with(|thread_pool| {
+                thread_pool.borrow().install(|| {
+                    data_shreds
+                        .par_chunks(MAX_DATA_SHREDS_PER_FEC_BLOCK as usize)
+                        .flat_map(|shred_data_batch| {
+                            Shredder::generate_coding_shreds(
+                                self.slot,
+                                self.fec_rate,
+                                shred_data_batch,
+                                self.version,
+                            )
+                        })
+                        .collect()
+                })
             })
         });
         let gen_coding_time = now.elapsed().as_millis();
@@ -516,6 +560,7 @@ impl Shredder {
             ("serializing", serialize_time as i64, i64),
             ("gen_data", gen_data_time as i64, i64),
             ("gen_coding", gen_coding_time as i64, i64),
+            ("gpu_coding", gpu_coding, bool),
             ("sign_coding", sign_coding_time as i64, i64),
         );
 
@@ -570,16 +615,107 @@ impl Shredder {
                 Session::new(num_data, num_coding).expect("Failed to create erasure session");
             let start_index = data_shred_batch[0].common_header.index;
 
-            // All information after coding shred field in a data shred is encoded
-            let valid_data_len = PACKET_DATA_SIZE - SIZE_OF_DATA_SHRED_IGNORED_TAIL;
-            let data_ptrs: Vec<_> = data_shred_batch
-                .iter()
-                .map(|data| &data.payload[..valid_data_len])
+            let data_ptrs = Self::coding_data_blocks(data_shred_batch);
+            let mut coding_shreds =
+                Self::empty_coding_payloads(slot, start_index, num_data, num_coding, version);
+            let mut coding_ptrs = Self::coding_parity_blocks(&mut coding_shreds);
+
+            // Create coding blocks
+            session
+                .encode(&data_ptrs, coding_ptrs.as_mut_slice())
+                .expect("Failed in erasure encode");
+
+            Self::coding_shreds_from_payloads(
+                slot,
+                start_index,
+                num_data,
+                num_coding,
+                version,
+                coding_shreds,
+            )
+        } else {
+            vec![]
+        }
+    }
+
+    /// Generates the coding shreds for every FEC set of `data_shreds` in one batch on the GPU.
+    /// Returns `None` if the GPU can't encode them, the sets must then go through
+    /// `generate_coding_shreds()` instead.
+    pub fn generate_coding_shreds_gpu(
+        slot: Slot,
+        fec_rate: f32,
+        data_shreds: &[Shred],
+        version: u16,
+    ) -> Option<Vec<Shred>> {
+        if fec_rate == 0.0 {
+            return Some(vec![]);
+        }
+        let sets: Vec<_> = data_shreds
+            .chunks(MAX_DATA_SHREDS_PER_FEC_BLOCK as usize)
+            .map(|data_shred_batch| {
+                let num_data = data_shred_batch.len();
+                let num_coding = Self::calculate_num_coding_shreds(num_data as f32, fec_rate);
+                let start_index = data_shred_batch[0].common_header.index;
+                (data_shred_batch, num_data, num_coding, start_index)
+            })
+            .collect();
+
+        let data_ptrs: Vec<_> = sets
+            .iter()
+            .map(|(data_shred_batch, _, _, _)| Self::coding_data_blocks(data_shred_batch))
+            .collect();
+        let mut coding_shreds: Vec<_> = sets
+            .iter()
+            .map(|(_, num_data, num_coding, start_index)| {
+                Self::empty_coding_payloads(slot, *start_index, *num_data, *num_coding, version)
+            })
+            .collect();
+        {
+            let mut coding_ptrs: Vec<_> = coding_shreds
+                .iter_mut()
+                .map(|payloads| Self::coding_parity_blocks(payloads))
                 .collect();
+            if !erasure::encode_many_gpu(&data_ptrs, &mut coding_ptrs) {
+                return None;
+            }
+        }
+
+        Some(
+            sets.into_iter()
+                .zip(coding_shreds)
+                .flat_map(|((_, num_data, num_coding, start_index), payloads)| {
+                    Self::coding_shreds_from_payloads(
+                        slot,
+                        start_index,
+                        num_data,
+                        num_coding,
+                        version,
+                        payloads,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    // All information after coding shred field in a data shred is encoded
+    fn coding_data_blocks(data_shred_batch: &[Shred]) -> Vec<&[u8]> {
+        let valid_data_len = PACKET_DATA_SIZE - SIZE_OF_DATA_SHRED_IGNORED_TAIL;
+        data_shred_batch
+            .iter()
+            .map(|data| &data.payload[..valid_data_len])
+            .collect()
+    }
 
-            // Create empty coding shreds, with correctly populated headers
-            let mut coding_shreds = Vec::with_capacity(num_coding);
-            (0..num_coding).for_each(|i| {
+    // Create empty coding shreds, with correctly populated headers
+    fn empty_coding_payloads(
+        slot: Slot,
+        start_index: u32,
+        num_data: usize,
+        num_coding: usize,
+        version: u16,
+    ) -> Vec<Vec<u8>> {
+        (0..num_coding)
+            .map(|i| {
                 let (header, coding_header) = Self::new_coding_shred_header(
                     slot,
                     start_index + i as u32,
@@ -588,47 +724,49 @@ impl Shredder {
                     i,
                     version,
                 );
-                let shred =
-                    Shred::new_empty_from_header(header, DataShredHeader::default(), coding_header);
-                coding_shreds.push(shred.payload);
-            });
-
-            // Grab pointers for the coding blocks
-            let coding_block_offset = SIZE_OF_COMMON_SHRED_HEADER + SIZE_OF_CODING_SHRED_HEADER;
-            let mut coding_ptrs: Vec<_> = coding_shreds
-                .iter_mut()
-                .map(|buffer| &mut buffer[coding_block_offset..])
-                .collect();
+                Shred::new_empty_from_header(header, DataShredHeader::default(), coding_header)
+                    .payload
+            })
+            .collect()
+    }
 
-            // Create coding blocks
-            session
-                .encode(&data_ptrs, coding_ptrs.as_mut_slice())
-                .expect("Failed in erasure encode");
+    // Grab pointers for the coding blocks
+    fn coding_parity_blocks(coding_shreds: &mut [Vec<u8>]) -> Vec<&mut [u8]> {
+        let coding_block_offset = SIZE_OF_COMMON_SHRED_HEADER + SIZE_OF_CODING_SHRED_HEADER;
+        coding_shreds
+            .iter_mut()
+            .map(|buffer| &mut buffer[coding_block_offset..])
+            .collect()
+    }
 
-            // append to the shred list
-            coding_shreds
-                .into_iter()
-                .enumerate()
-                .map(|(i, payload)| {
-                    let (common_header, coding_header) = Self::new_coding_shred_header(
-                        slot,
-                        start_index + i as u32,
-                        num_data,
-                        num_coding,
-                        i,
-                        version,
-                    );
-                    Shred {
-                        common_header,
-                        data_header: DataShredHeader::default(),
-                        coding_header,
-                        payload,
-                    }
-                })
-                .collect()
-        } else {
-            vec![]
-        }
+    fn coding_shreds_from_payloads(
+        slot: Slot,
+        start_index: u32,
+        num_data: usize,
+        num_coding: usize,
+        version: u16,
+        coding_shreds: Vec<Vec<u8>>,
+    ) -> Vec<Shred> {
+        coding_shreds
+            .into_iter()
+            .enumerate()
+            .map(|(i, payload)| {
+                let (common_header, coding_header) = Self::new_coding_shred_header(
+                    slot,
+                    start_index + i as u32,
+                    num_data,
+                    num_coding,
+                    i,
+                    version,
+                );
+                Shred {
+                    common_header,
+                    data_header: DataShredHeader::default(),
+                    coding_header,
+                    payload,
+                }
+            })
+            .collect()
     }
 
     fn calculate_num_coding_shreds(num_data_shreds: f32, fec_rate: f32) -> usize {
@@ -801,6 +939,100 @@ impl Shredder {
     }
 }
 
+/// Default number of serialized entry bytes `StreamingShredder` buffers before emitting shreds,
+/// sized so that each flush fills one FEC block of data shreds.
+pub const STREAMING_SHREDDER_FLUSH_BYTES: usize =
+    MAX_DATA_SHREDS_PER_FEC_BLOCK as usize * SIZE_OF_DATA_SHRED_PAYLOAD;
+
+/// Converts entries into shreds as they are produced instead of shredding a whole batch at
+/// once. Entries are buffered until they fill a FEC block, at which point the data and coding
+/// shreds for the buffered entries are emitted so they can be transmitted right away.
+#[derive(Debug)]
+pub struct StreamingShredder {
+    shredder: Shredder,
+    entries: Vec<Entry>,
+    buffered_bytes: usize,
+    flush_bytes: usize,
+    next_shred_index: u32,
+}
+
+impl StreamingShredder {
+    pub fn new(shredder: Shredder, next_shred_index: u32) -> Self {
+        Self {
+            shredder,
+            entries: vec![],
+            buffered_bytes: size_of::<u64>(),
+            flush_bytes: STREAMING_SHREDDER_FLUSH_BYTES,
+            next_shred_index,
+        }
+    }
+
+    /// Overrides the number of buffered bytes that triggers a flush
+    pub fn flush_bytes(mut self, flush_bytes: usize) -> Self {
+        self.flush_bytes = flush_bytes.max(SIZE_OF_DATA_SHRED_PAYLOAD);
+        self
+    }
+
+    pub fn slot(&self) -> Slot {
+        self.shredder.slot
+    }
+
+    pub fn parent_slot(&self) -> Slot {
+        self.shredder.parent_slot
+    }
+
+    /// Index of the next data shred this shredder will emit
+    pub fn next_shred_index(&self) -> u32 {
+        self.next_shred_index
+    }
+
+    pub fn num_buffered_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn set_reference_tick(&mut self, reference_tick: u8) {
+        self.shredder.reference_tick = reference_tick;
+    }
+
+    /// Buffers `entry`, returning the data and coding shreds of any FEC block it completed
+    pub fn push(&mut self, entry: Entry) -> (Vec<Shred>, Vec<Shred>) {
+        let entry_bytes =
+            bincode::serialized_size(&entry).expect("Expect to serialize entry") as usize;
+        let shreds =
+            if !self.entries.is_empty() && self.buffered_bytes + entry_bytes > self.flush_bytes {
+                self.flush()
+            } else {
+                (vec![], vec![])
+            };
+        self.entries.push(entry);
+        self.buffered_bytes += entry_bytes;
+        shreds
+    }
+
+    /// Shreds all buffered entries, leaving the slot open for more entries
+    pub fn flush(&mut self) -> (Vec<Shred>, Vec<Shred>) {
+        if self.entries.is_empty() {
+            return (vec![], vec![]);
+        }
+        self.shred_buffered(false)
+    }
+
+    /// Shreds all buffered entries and marks the last data shred as the last in the slot
+    pub fn finish(mut self) -> (Vec<Shred>, Vec<Shred>) {
+        self.shred_buffered(true)
+    }
+
+    fn shred_buffered(&mut self, is_last_in_slot: bool) -> (Vec<Shred>, Vec<Shred>) {
+        let (data_shreds, coding_shreds, next_shred_index) =
+            self.shredder
+                .entries_to_shreds(&self.entries, is_last_in_slot, self.next_shred_index);
+        self.entries.clear();
+        self.buffered_bytes = size_of::<u64>();
+        self.next_shred_index = next_shred_index;
+        (data_shreds, coding_shreds)
+    }
+}
+
 pub fn max_ticks_per_n_shreds(num_shreds: u64) -> u64 {
     let ticks = create_ticks(1, 0, Hash::default());
     max_entries_per_n_shred(&ticks[0], num_shreds)
@@ -974,6 +1206,65 @@ pub mod tests {
         assert_eq!(entries, deshred_entries);
     }
 
+    #[test]
+    fn test_streaming_shredder() {
+        let keypair = Arc::new(Keypair::new());
+        let slot = 0x1234_5678_9abc_def0;
+        let parent_slot = slot - 5;
+        let shredder = Shredder::new(slot, parent_slot, 1.0, keypair.clone(), 0, 0)
+            .expect("Failed in creating shredder");
+        let start_index = 7;
+        let mut streaming_shredder = StreamingShredder::new(shredder, start_index);
+
+        let mut last_hash = hash(&[0u8]);
+        let entries: Vec<_> = (0..200)
+            .map(|_| {
+                let keypair0 = Keypair::new();
+                let keypair1 = Keypair::new();
+                let tx0 =
+                    system_transaction::transfer(&keypair0, &keypair1.pubkey(), 1, Hash::default());
+                let entry = Entry::new(&last_hash, 1, vec![tx0]);
+                last_hash = entry.hash;
+                entry
+            })
+            .collect();
+
+        let mut batches = vec![];
+        for entry in entries.iter().cloned() {
+            let (data_shreds, coding_shreds) = streaming_shredder.push(entry);
+            if !data_shreds.is_empty() {
+                assert!(data_shreds.len() <= MAX_DATA_SHREDS_PER_FEC_BLOCK as usize);
+                assert_eq!(coding_shreds.len(), data_shreds.len());
+                batches.push(data_shreds);
+            }
+        }
+        // Several FEC blocks were emitted before the slot finished
+        assert!(batches.len() > 1);
+        assert!(streaming_shredder.num_buffered_entries() > 0);
+        let (data_shreds, _) = streaming_shredder.finish();
+        batches.push(data_shreds);
+
+        let mut next_index = start_index;
+        let mut deshred_entries = vec![];
+        for (i, batch) in batches.iter().enumerate() {
+            for shred in batch {
+                assert_eq!(shred.index(), next_index);
+                assert_eq!(shred.slot(), slot);
+                assert_eq!(shred.parent(), parent_slot);
+                assert!(shred.verify(&keypair.pubkey()));
+                next_index += 1;
+            }
+            let last = batch.last().unwrap();
+            assert!(last.data_complete());
+            assert_eq!(last.last_in_slot(), i == batches.len() - 1);
+
+            let payload = Shredder::deshred(batch).unwrap();
+            let batch_entries: Vec<Entry> = bincode::deserialize(&payload).unwrap();
+            deshred_entries.extend(batch_entries);
+        }
+        assert_eq!(entries, deshred_entries);
+    }
+
     #[test]
     fn test_deserialize_shred_payload() {
         let keypair = Arc::new(Keypair::new());
@@ -1000,6 +1291,28 @@ pub mod tests {
         assert_eq!(deserialized_shred, *data_shreds.last().unwrap());
     }
 
+    #[test]
+    fn test_deserialize_invalid_shred_payload() {
+        let shred = Shred::new_from_data(1, 0, 1, None, false, false, 0, 0);
+        match Shred::new_from_serialized_shred(
+            shred.payload[..SIZE_OF_COMMON_SHRED_HEADER].to_vec(),
+        ) {
+            Err(ShredError::InvalidPayloadSize(size)) => {
+                assert_eq!(size, SIZE_OF_COMMON_SHRED_HEADER)
+            }
+            _ => panic!("expected a truncated payload to be rejected"),
+        }
+
+        let shred = Shred::new_from_data(1, 0, 2, None, false, false, 0, 0);
+        match Shred::new_from_serialized_shred(shred.payload) {
+            Err(ShredError::InvalidParentOffset {
+                slot: 1,
+                parent_offset: 2,
+            }) => (),
+            _ => panic!("expected a parent before slot 0 to be rejected"),
+        }
+    }
+
     #[test]
     fn test_shred_reference_tick() {
         let keypair = Arc::new(Keypair::new());
@@ -1361,6 +1674,57 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_generate_coding_shreds_gpu() {
+        let keypair = Arc::new(Keypair::new());
+        let slot = 1;
+        let fec_rate = 0.25;
+        let shredder = Shredder::new(slot, 0, fec_rate, keypair.clone(), 0, 0)
+            .expect("Failed in creating shredder");
+
+        // enough data for the GPU batch, with a partial last FEC set
+        let num_data_shreds = MAX_DATA_SHREDS_PER_FEC_BLOCK as usize * MIN_FEC_SETS_FOR_GPU_CODING
+            + MAX_DATA_SHREDS_PER_FEC_BLOCK as usize / 2;
+        let keypair0 = Keypair::new();
+        let keypair1 = Keypair::new();
+        let tx0 = system_transaction::transfer(&keypair0, &keypair1.pubkey(), 1, Hash::default());
+        let entry = Entry::new(&Hash::default(), 1, vec![tx0]);
+        let num_entries = max_entries_per_n_shred(&entry, num_data_shreds as u64);
+        let entries: Vec<_> = (0..num_entries)
+            .map(|_| {
+                let keypair0 = Keypair::new();
+                let keypair1 = Keypair::new();
+                let tx0 =
+                    system_transaction::transfer(&keypair0, &keypair1.pubkey(), 1, Hash::default());
+                Entry::new(&Hash::default(), 1, vec![tx0])
+            })
+            .collect();
+        let (data_shreds, coding_shreds, _) = shredder.entries_to_shreds(&entries, true, 0);
+
+        let cpu_coding_shreds: Vec<_> = data_shreds
+            .chunks(MAX_DATA_SHREDS_PER_FEC_BLOCK as usize)
+            .flat_map(|batch| Shredder::generate_coding_shreds(slot, fec_rate, batch, 0))
+            .collect();
+
+        // whichever path entries_to_shreds took, it must match the CPU encoding
+        let mut signed_cpu_coding_shreds = cpu_coding_shreds.clone();
+        signed_cpu_coding_shreds
+            .iter_mut()
+            .for_each(|shred| Shredder::sign_shred(&keypair, shred));
+        assert_eq!(coding_shreds, signed_cpu_coding_shreds);
+
+        // without perf-libs there is nothing to compare against
+        if let Some(gpu_coding_shreds) =
+            Shredder::generate_coding_shreds_gpu(slot, fec_rate, &data_shreds, 0)
+        {
+            assert_eq!(gpu_coding_shreds, cpu_coding_shreds);
+        }
+        assert_eq!(
+            Shredder::generate_coding_shreds_gpu(slot, 0.0, &data_shreds, 0),
+            Some(vec![])
+        );
+    }
+
     #[test]
     fn test_multi_fec_block_coding() {
         let keypair = Arc::new(Keypair::new());
@@ -285,11 +285,12 @@ where
     info!("Loading from {:?}", &root_paths.snapshot_file_path);
     let file = File::open(&root_paths.snapshot_file_path)?;
     let mut stream = BufReader::new(file);
-    let bank: Bank = deserialize_from(&mut stream)?;
+    let mut bank: Bank = deserialize_from(&mut stream)?;
 
     // Rebuild accounts
     bank.rc
         .accounts_from_stream(&mut stream, local_account_paths, append_vecs_path)?;
+    bank.restore_accounts_data_len();
 
     // Rebuild status cache
     let status_cache_path = unpacked_snapshots_dir.join(SNAPSHOT_STATUS_CACHE_FILE_NAME);
@@ -34,8 +34,8 @@ pub fn staked_nodes(bank: &Bank) -> HashMap<Pubkey, u64> {
 /// At the specified epoch, collect the delegate account balance and vote states for delegates
 /// that have non-zero balance in any of their managed staking accounts
 pub fn staked_nodes_at_epoch(bank: &Bank, epoch: Epoch) -> Option<HashMap<Pubkey, u64>> {
-    bank.epoch_vote_accounts(epoch)
-        .map(|vote_accounts| to_staked_nodes(to_vote_states(vote_accounts.iter())))
+    bank.epoch_stakes(epoch)
+        .map(|epoch_stakes| epoch_stakes.node_stakes().clone())
 }
 
 // input (vote_pubkey, (stake, vote_account)) => (stake, vote_state)
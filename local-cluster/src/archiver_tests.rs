@@ -0,0 +1,170 @@
+//! End-to-end archiver helpers
+//!
+//! The archivers of a `LocalCluster` report their progress as `ArchiverEvent`s, these helpers
+//! wait on them and check what the archivers end up storing.
+use crate::{
+    cluster::Cluster,
+    local_cluster::{ArchiverInfo, LocalCluster},
+};
+use log::*;
+use solana_core::{
+    archiver::{Archiver, ArchiverEvent},
+    repair_peers::RepairPeers,
+};
+use solana_ledger::{blocktree::Blocktree, get_tmp_ledger_path};
+use solana_sdk::{clock::Slot, pubkey::Pubkey};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Wait up to `timeout` for an event of the archiver `is_expected` accepts, the events before
+/// it are consumed. Returns `None` on timeout.
+pub fn wait_for_archiver_event<F>(
+    archiver_info: &ArchiverInfo,
+    timeout: Duration,
+    is_expected: F,
+) -> Option<ArchiverEvent>
+where
+    F: Fn(&ArchiverEvent) -> bool,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        let event = archiver_info.events.recv_timeout(deadline - now).ok()?;
+        if let ArchiverEvent::Error(err) = &event {
+            warn!("archiver error: {}", err);
+        }
+        if is_expected(&event) {
+            return Some(event);
+        }
+    }
+}
+
+/// Wait for every archiver of `cluster` to get a mining proof confirmed. Returns the first slot
+/// of the segment each archiver proved, by archiver pubkey.
+pub fn wait_for_archiver_proofs(
+    cluster: &LocalCluster,
+    timeout: Duration,
+) -> HashMap<Pubkey, Slot> {
+    cluster
+        .archiver_infos
+        .iter()
+        .map(|(pubkey, archiver_info)| {
+            let event = wait_for_archiver_event(archiver_info, timeout, |event| match event {
+                ArchiverEvent::ProofSubmitted { .. } => true,
+                _ => false,
+            });
+            match event {
+                Some(ArchiverEvent::ProofSubmitted { slot, signature }) => {
+                    info!("archiver {} proved segment {}: {}", pubkey, slot, signature);
+                    (*pubkey, slot)
+                }
+                _ => panic!("archiver {} submitted no proof in {:?}", pubkey, timeout),
+            }
+        })
+        .collect()
+}
+
+/// Wait for every archiver of `cluster` to claim storage rewards. Returns the lamports each
+/// archiver estimated for its claim, by archiver pubkey.
+pub fn wait_for_archiver_rewards(
+    cluster: &LocalCluster,
+    timeout: Duration,
+) -> HashMap<Pubkey, u64> {
+    cluster
+        .archiver_infos
+        .iter()
+        .map(|(pubkey, archiver_info)| {
+            let event = wait_for_archiver_event(archiver_info, timeout, |event| match event {
+                ArchiverEvent::RewardClaimed { .. } => true,
+                _ => false,
+            });
+            match event {
+                Some(ArchiverEvent::RewardClaimed {
+                    estimated_lamports, ..
+                }) => {
+                    info!(
+                        "archiver {} claimed {} lamports",
+                        pubkey, estimated_lamports
+                    );
+                    (*pubkey, estimated_lamports)
+                }
+                _ => panic!("archiver {} claimed no rewards in {:?}", pubkey, timeout),
+            }
+        })
+        .collect()
+}
+
+/// Exit and restart the validator `validator_pubkey` as soon as the archiver `archiver_pubkey`
+/// downloads the first slot of its segment, so the rest of the download has to ride out the
+/// restart. Returns the first slot of the segment being downloaded.
+pub fn restart_validator_mid_download(
+    cluster: &mut LocalCluster,
+    archiver_pubkey: &Pubkey,
+    validator_pubkey: &Pubkey,
+    timeout: Duration,
+) -> Slot {
+    let slot = {
+        let archiver_info = &cluster.archiver_infos[archiver_pubkey];
+        match wait_for_archiver_event(archiver_info, timeout, |event| match event {
+            ArchiverEvent::DownloadProgress { .. } => true,
+            _ => false,
+        }) {
+            Some(ArchiverEvent::DownloadProgress {
+                slot,
+                slots_downloaded,
+                slots_per_segment,
+            }) => {
+                info!(
+                    "restarting {} with {}/{} slots of segment {} downloaded",
+                    validator_pubkey, slots_downloaded, slots_per_segment, slot
+                );
+                slot
+            }
+            _ => panic!(
+                "archiver {} downloaded nothing in {:?}",
+                archiver_pubkey, timeout
+            ),
+        }
+    };
+    let validator_config = cluster.validator_infos[validator_pubkey].config.clone();
+    cluster.exit_restart_node(validator_pubkey, validator_config);
+    slot
+}
+
+/// Download the segment the archiver serves into a scratch blocktree and assert every slot of
+/// it is complete. Returns the first slot of the segment.
+pub fn verify_archiver_segment(archiver_info: &ArchiverInfo, slots_per_segment: u64) -> Slot {
+    let ledger_path = get_tmp_ledger_path!();
+    let start_slot = {
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let repair_peers = Mutex::new(RepairPeers::default());
+        let start_slot = Archiver::download_from_archiver(
+            &archiver_info.contact_info,
+            &blocktree,
+            &repair_peers,
+        )
+        .unwrap_or_else(|err| {
+            panic!(
+                "unable to download from archiver {}: {:?}",
+                archiver_info.contact_info.id, err
+            )
+        });
+        for slot in start_slot..start_slot + slots_per_segment {
+            assert!(
+                blocktree.is_full(slot),
+                "slot {} of segment {} is incomplete",
+                slot,
+                start_slot
+            );
+        }
+        start_slot
+    };
+    Blocktree::destroy(&ledger_path).unwrap();
+    start_slot
+}
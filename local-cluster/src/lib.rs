@@ -1,3 +1,4 @@
+pub mod archiver_tests;
 pub mod cluster;
 pub mod cluster_tests;
 pub mod local_cluster;
@@ -1,8 +1,9 @@
 use crate::cluster::{Cluster, ClusterValidatorInfo, ValidatorInfo};
+use crossbeam_channel::{unbounded, Receiver};
 use log::*;
 use solana_client::thin_client::{create_client, ThinClient};
 use solana_core::{
-    archiver::Archiver,
+    archiver::{Archiver, ArchiverBuilder, ArchiverEvent, RedemptionPolicy},
     cluster_info::{Node, VALIDATOR_PORT_RANGE},
     contact_info::ContactInfo,
     genesis_utils::{create_genesis_config_with_leader, GenesisConfigInfo},
@@ -46,13 +47,23 @@ use std::{
 pub struct ArchiverInfo {
     pub archiver_storage_pubkey: Pubkey,
     pub ledger_path: PathBuf,
+    pub contact_info: ContactInfo,
+    /// Progress of the archiver, see `archiver_tests` for helpers waiting on it
+    pub events: Receiver<ArchiverEvent>,
 }
 
 impl ArchiverInfo {
-    fn new(storage_pubkey: Pubkey, ledger_path: PathBuf) -> Self {
+    fn new(
+        storage_pubkey: Pubkey,
+        ledger_path: PathBuf,
+        contact_info: ContactInfo,
+        events: Receiver<ArchiverEvent>,
+    ) -> Self {
         Self {
             archiver_storage_pubkey: storage_pubkey,
             ledger_path,
+            contact_info,
+            events,
         }
     }
 }
@@ -77,6 +88,8 @@ pub struct ClusterConfig {
     pub native_instruction_processors: Vec<(String, Pubkey)>,
     pub operating_mode: OperatingMode,
     pub poh_config: PohConfig,
+    /// When the archivers claim their storage rewards
+    pub archiver_redemption_policy: RedemptionPolicy,
 }
 
 impl Default for ClusterConfig {
@@ -94,6 +107,7 @@ impl Default for ClusterConfig {
             native_instruction_processors: vec![],
             operating_mode: OperatingMode::Development,
             poh_config: PohConfig::default(),
+            archiver_redemption_policy: RedemptionPolicy::default(),
         }
     }
 }
@@ -255,7 +269,7 @@ impl LocalCluster {
         .unwrap();
 
         for _ in 0..config.num_archivers {
-            cluster.add_archiver();
+            cluster.add_archiver(&config.archiver_redemption_policy);
         }
 
         discover_cluster(
@@ -280,7 +294,7 @@ impl LocalCluster {
         }
 
         while let Some(archiver) = self.archivers.pop() {
-            archiver.close();
+            archiver.close().unwrap();
         }
     }
 
@@ -362,7 +376,8 @@ impl LocalCluster {
         }
     }
 
-    fn add_archiver(&mut self) {
+    /// Start another archiver against the cluster, returns its pubkey
+    pub fn add_archiver(&mut self, redemption_policy: &RedemptionPolicy) -> Pubkey {
         let archiver_keypair = Arc::new(Keypair::new());
         let archiver_pubkey = archiver_keypair.pubkey();
         let storage_keypair = Arc::new(Keypair::new());
@@ -383,22 +398,33 @@ impl LocalCluster {
 
         Self::setup_storage_account(&client, &storage_keypair, &archiver_keypair, true).unwrap();
 
+        let archiver_contact_info = archiver_node.info.clone();
         let (archiver_ledger_path, _blockhash) = create_new_tmp_ledger!(&self.genesis_config);
-        let archiver = Archiver::new(
+        let (event_sender, events) = unbounded();
+        let archiver = ArchiverBuilder::new(
             &archiver_ledger_path,
             archiver_node,
             self.entry_point_info.clone(),
             archiver_keypair,
             storage_keypair,
-            CommitmentConfig::recent(),
         )
+        .client_commitment(CommitmentConfig::recent())
+        .redemption_policy(redemption_policy.clone())
+        .event_sender(event_sender)
+        .build()
         .unwrap_or_else(|err| panic!("Archiver::new() failed: {:?}", err));
 
         self.archivers.push(archiver);
         self.archiver_infos.insert(
             archiver_pubkey,
-            ArchiverInfo::new(storage_pubkey, archiver_ledger_path),
+            ArchiverInfo::new(
+                storage_pubkey,
+                archiver_ledger_path,
+                archiver_contact_info,
+                events,
+            ),
         );
+        archiver_pubkey
     }
 
     fn close(&mut self) {
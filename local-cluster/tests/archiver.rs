@@ -2,15 +2,21 @@ use log::*;
 use serial_test_derive::serial;
 use solana_client::thin_client::create_client;
 use solana_core::{
-    archiver::Archiver,
-    cluster_info::{ClusterInfo, Node, VALIDATOR_PORT_RANGE},
+    archiver::{Archiver, RedemptionPolicy, StorageFundingConfig},
+    cluster_info::{Node, VALIDATOR_PORT_RANGE},
     contact_info::ContactInfo,
     gossip_service::discover_cluster,
+    repair_peers::RepairPeers,
+    result::{Error, GossipError},
     storage_stage::SLOTS_PER_TURN_TEST,
     validator::ValidatorConfig,
 };
 use solana_ledger::{blocktree::Blocktree, create_new_tmp_ledger, get_tmp_ledger_path};
-use solana_local_cluster::local_cluster::{ClusterConfig, LocalCluster};
+use solana_local_cluster::{
+    archiver_tests,
+    cluster::Cluster,
+    local_cluster::{ClusterConfig, LocalCluster},
+};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
     genesis_config::create_genesis_config,
@@ -18,9 +24,12 @@ use solana_sdk::{
 };
 use std::{
     fs::remove_dir_all,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
+const ARCHIVER_TIMEOUT: Duration = Duration::from_secs(180);
+
 /// Start the cluster with the given configuration and wait till the archivers are discovered
 /// Then download shreds from one of them.
 fn run_archiver_startup_basic(num_nodes: usize, num_archivers: usize) {
@@ -58,13 +67,14 @@ fn run_archiver_startup_basic(num_nodes: usize, num_archivers: usize) {
     }
     assert_eq!(archiver_count, num_archivers);
 
-    let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
-        cluster_nodes[0].clone(),
-    )));
     let path = get_tmp_ledger_path!();
     let blocktree = Arc::new(Blocktree::open(&path).unwrap());
-    Archiver::download_from_archiver(&cluster_info, &archiver_info, &blocktree, slots_per_segment)
-        .unwrap();
+    let repair_peers = Mutex::new(RepairPeers::default());
+    let start_slot =
+        Archiver::download_from_archiver(&archiver_info, &blocktree, &repair_peers).unwrap();
+    for slot in start_slot..start_slot + slots_per_segment {
+        assert!(blocktree.is_full(slot));
+    }
 }
 
 #[test]
@@ -79,6 +89,78 @@ fn test_archiver_startup_2_nodes() {
     run_archiver_startup_basic(2, 1);
 }
 
+#[test]
+#[serial]
+fn test_archiver_proofs_and_rewards() {
+    solana_logger::setup();
+    let num_nodes = 1;
+    let num_archivers = 2;
+    let slots_per_segment = 8;
+    let mut validator_config = ValidatorConfig::default();
+    validator_config.storage_slots_per_turn = SLOTS_PER_TURN_TEST;
+    let config = ClusterConfig {
+        validator_configs: vec![validator_config; num_nodes],
+        num_archivers,
+        node_stakes: vec![100; num_nodes],
+        cluster_lamports: 10_000,
+        slots_per_segment,
+        ..ClusterConfig::default()
+    };
+    let cluster = LocalCluster::new(&config);
+
+    let proofs = archiver_tests::wait_for_archiver_proofs(&cluster, ARCHIVER_TIMEOUT);
+    assert_eq!(proofs.len(), num_archivers);
+    archiver_tests::wait_for_archiver_rewards(&cluster, ARCHIVER_TIMEOUT);
+    for (pubkey, archiver_info) in &cluster.archiver_infos {
+        assert_eq!(
+            archiver_tests::verify_archiver_segment(archiver_info, slots_per_segment),
+            proofs[pubkey]
+        );
+    }
+}
+
+#[test]
+#[serial]
+fn test_archiver_validator_restart_mid_download() {
+    solana_logger::setup();
+    let num_nodes = 2;
+    let slots_per_segment = 8;
+    let mut validator_config = ValidatorConfig::default();
+    validator_config.storage_slots_per_turn = SLOTS_PER_TURN_TEST;
+    let config = ClusterConfig {
+        validator_configs: vec![validator_config; num_nodes],
+        num_archivers: 1,
+        node_stakes: vec![100; num_nodes],
+        cluster_lamports: 10_000,
+        slots_per_segment,
+        ..ClusterConfig::default()
+    };
+    let mut cluster = LocalCluster::new(&config);
+
+    // restarting the entry point would move it, restart the other validator
+    let validator_pubkey = cluster
+        .get_node_pubkeys()
+        .into_iter()
+        .find(|pubkey| *pubkey != cluster.entry_point_info.id)
+        .unwrap();
+    let archiver_pubkey = *cluster.archiver_infos.keys().next().unwrap();
+    archiver_tests::restart_validator_mid_download(
+        &mut cluster,
+        &archiver_pubkey,
+        &validator_pubkey,
+        ARCHIVER_TIMEOUT,
+    );
+
+    let proofs = archiver_tests::wait_for_archiver_proofs(&cluster, ARCHIVER_TIMEOUT);
+    assert_eq!(
+        archiver_tests::verify_archiver_segment(
+            &cluster.archiver_infos[&archiver_pubkey],
+            slots_per_segment
+        ),
+        proofs[&archiver_pubkey]
+    );
+}
+
 #[test]
 #[serial]
 fn test_archiver_startup_leader_hang() {
@@ -108,9 +190,17 @@ fn test_archiver_startup_leader_hang() {
             archiver_keypair,
             storage_keypair,
             CommitmentConfig::recent(),
+            StorageFundingConfig::default(),
+            RedemptionPolicy::default(),
         );
 
-        assert!(archiver_res.is_err());
+        match archiver_res {
+            Err(Error::GossipError(GossipError::DiscoveryFailed { entrypoint, .. })) => {
+                assert_eq!(entrypoint, fake_gossip)
+            }
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("archiver started without a cluster"),
+        }
     }
 
     let _ignored = Blocktree::destroy(&leader_ledger_path);
@@ -144,6 +234,8 @@ fn test_archiver_startup_ledger_hang() {
         bad_keys,
         storage_keypair,
         CommitmentConfig::recent(),
+        StorageFundingConfig::default(),
+        RedemptionPolicy::default(),
     );
 
     assert!(archiver_res.is_err());
@@ -1,6 +1,6 @@
 use log::*;
 use serial_test_derive::serial;
-use solana_client::thin_client::create_client;
+use solana_client::{rpc_client::RpcClient, thin_client::create_client};
 use solana_core::{
     broadcast_stage::BroadcastStageType,
     consensus::VOTE_THRESHOLD_DEPTH,
@@ -29,10 +29,13 @@ use std::{
     fs,
     path::{Path, PathBuf},
     thread::sleep,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tempfile::TempDir;
 
+// How long a test waits for a node to reach a slot
+const WAIT_FOR_SLOT_TIMEOUT: Duration = Duration::from_secs(300);
+
 #[test]
 #[serial]
 fn test_ledger_cleanup_service() {
@@ -553,20 +556,18 @@ fn test_snapshots_blocktree_floor() {
         .into_iter()
         .find(|x| *x != cluster.entry_point_info.id)
         .unwrap();
-    let validator_client = cluster.get_validator_client(&validator_id).unwrap();
-    let mut current_slot = 0;
+    let validator_client =
+        RpcClient::new_socket(cluster.validator_infos[&validator_id].info.contact_info.rpc);
 
     // Let this validator run a while with repair
     let target_slot = slot_floor + 40;
-    while current_slot <= target_slot {
-        trace!("current_slot: {}", current_slot);
-        if let Ok(slot) = validator_client.get_slot_with_commitment(CommitmentConfig::recent()) {
-            current_slot = slot;
-        } else {
-            continue;
-        }
-        sleep(Duration::from_secs(1));
-    }
+    validator_client
+        .wait_for_slot_with_commitment(
+            target_slot + 1,
+            Instant::now() + WAIT_FOR_SLOT_TIMEOUT,
+            CommitmentConfig::recent(),
+        )
+        .unwrap();
 
     // Check the validator ledger doesn't contain any slots < slot_floor
     cluster.close_preserve_ledgers();
@@ -672,6 +673,20 @@ fn test_fake_shreds_broadcast_leader() {
     test_faulty_node(BroadcastStageType::BroadcastFakeShreds);
 }
 
+#[test]
+#[allow(unused_attributes)]
+#[ignore]
+fn test_fail_slot_broadcast_leader() {
+    test_faulty_node(BroadcastStageType::FailSlot);
+}
+
+#[test]
+#[allow(unused_attributes)]
+#[ignore]
+fn test_duplicate_shreds_broadcast_leader() {
+    test_faulty_node(BroadcastStageType::BroadcastDuplicateShreds);
+}
+
 fn test_faulty_node(faulty_node_type: BroadcastStageType) {
     solana_logger::setup();
     let num_nodes = 4;
@@ -740,18 +755,14 @@ fn test_no_voting() {
         ..ClusterConfig::default()
     };
     let mut cluster = LocalCluster::new(&config);
-    let client = cluster
-        .get_validator_client(&cluster.entry_point_info.id)
+    let client = RpcClient::new_socket(cluster.entry_point_info.rpc);
+    client
+        .wait_for_slot_with_commitment(
+            4 * VOTE_THRESHOLD_DEPTH as u64 + 1,
+            Instant::now() + WAIT_FOR_SLOT_TIMEOUT,
+            CommitmentConfig::recent(),
+        )
         .unwrap();
-    loop {
-        let last_slot = client
-            .get_slot_with_commitment(CommitmentConfig::recent())
-            .expect("Couldn't get slot");
-        if last_slot > 4 * VOTE_THRESHOLD_DEPTH as u64 {
-            break;
-        }
-        sleep(Duration::from_secs(1));
-    }
 
     cluster.close_preserve_ledgers();
     let leader_pubkey = cluster.entry_point_info.id;
@@ -846,20 +857,18 @@ fn run_repairman_catchup(num_repairmen: u64) {
         .unwrap();
 
     // Wait for repairman protocol to catch this validator up
-    let repairee_client = cluster.get_validator_client(&repairee_id).unwrap();
-    let mut current_slot = 0;
+    let repairee_client =
+        RpcClient::new_socket(cluster.validator_infos[&repairee_id].info.contact_info.rpc);
 
     // Make sure this validator can get repaired past the first few warmup epochs
     let target_slot = (num_warmup_epochs) * num_slots_per_epoch + 1;
-    while current_slot <= target_slot {
-        trace!("current_slot: {}", current_slot);
-        if let Ok(slot) = repairee_client.get_slot_with_commitment(CommitmentConfig::recent()) {
-            current_slot = slot;
-        } else {
-            continue;
-        }
-        sleep(Duration::from_secs(1));
-    }
+    repairee_client
+        .wait_for_slot_with_commitment(
+            target_slot + 1,
+            Instant::now() + WAIT_FOR_SLOT_TIMEOUT,
+            CommitmentConfig::recent(),
+        )
+        .unwrap();
 }
 
 fn wait_for_next_snapshot<P: AsRef<Path>>(cluster: &LocalCluster, tar: P) {
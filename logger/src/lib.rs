@@ -2,10 +2,14 @@
 
 use lazy_static::lazy_static;
 use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
 
 lazy_static! {
     static ref LOGGER: Arc<RwLock<env_logger::Logger>> =
         { Arc::new(RwLock::new(env_logger::Logger::from_default_env())) };
+    // The active filter string, and a counter bumped every time it changes
+    static ref FILTER: RwLock<(String, u64)> = RwLock::new((String::new(), 0));
 }
 
 struct LoggerShim {}
@@ -22,20 +26,124 @@ impl log::Log for LoggerShim {
     fn flush(&self) {}
 }
 
+fn replace_logger(logger: env_logger::Logger, filter: String) -> u64 {
+    let max_level = logger.filter();
+    log::set_max_level(max_level);
+    let mut rw = LOGGER.write().unwrap();
+    std::mem::replace(&mut *rw, logger);
+    let _ = log::set_boxed_logger(Box::new(LoggerShim {}));
+
+    let mut current = FILTER.write().unwrap();
+    current.0 = filter;
+    current.1 += 1;
+    current.1
+}
+
+fn apply_filter(filter: &str) -> u64 {
+    let logger = env_logger::Builder::new()
+        .parse_filters(filter)
+        .format_timestamp_nanos()
+        .build();
+    replace_logger(logger, filter.to_string())
+}
+
 // Configures logging with a specific filter.
 // May be called at any time to re-configure the log filter
 pub fn setup_with_filter(filter: &str) {
     let logger = env_logger::Builder::from_env(env_logger::Env::new().default_filter_or(filter))
         .format_timestamp_nanos()
         .build();
-    let max_level = logger.filter();
-    log::set_max_level(max_level);
-    let mut rw = LOGGER.write().unwrap();
-    std::mem::replace(&mut *rw, logger);
-    let _ = log::set_boxed_logger(Box::new(LoggerShim {}));
+    let filter = std::env::var(env_logger::DEFAULT_FILTER_ENV).unwrap_or_else(|_| filter.into());
+    replace_logger(logger, filter);
 }
 
 // Configures logging with the default filter ("error")
 pub fn setup() {
     setup_with_filter("error");
 }
+
+// Returns the filter currently in effect
+pub fn get_filter() -> String {
+    FILTER.read().unwrap().0.clone()
+}
+
+// Merges `directives` into the current filter and returns the new filter.
+// A directive for a module replaces only that module's directive, and a bare level
+// replaces only the global level, e.g. "solana_core::archiver=trace" leaves
+// everything else as it was.
+pub fn update_filter(directives: &str) -> String {
+    let filter = merge_filters(&get_filter(), directives);
+    apply_filter(&filter);
+    filter
+}
+
+// Like `update_filter`, but reverts to the previous filter after `duration`, unless
+// the filter has been changed again in the meantime
+pub fn update_filter_for(directives: &str, duration: Duration) -> String {
+    let previous = get_filter();
+    let filter = merge_filters(&previous, directives);
+    let generation = apply_filter(&filter);
+    thread::Builder::new()
+        .name("solana-logger-revert".to_string())
+        .spawn(move || {
+            thread::sleep(duration);
+            if FILTER.read().unwrap().1 == generation {
+                apply_filter(&previous);
+            }
+        })
+        .unwrap();
+    filter
+}
+
+fn directive_module(directive: &str) -> &str {
+    directive
+        .find('=')
+        .map(|index| directive[..index].trim())
+        .unwrap_or("")
+}
+
+// Overlays the comma separated `directives` onto `filter`, keyed by module
+pub fn merge_filters(filter: &str, directives: &str) -> String {
+    let mut merged: Vec<&str> = filter
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .collect();
+    for directive in directives.split(',').map(str::trim) {
+        if directive.is_empty() {
+            continue;
+        }
+        let module = directive_module(directive);
+        match merged
+            .iter()
+            .position(|existing| directive_module(existing) == module)
+        {
+            Some(index) => merged[index] = directive,
+            None => merged.push(directive),
+        }
+    }
+    merged.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_filters() {
+        assert_eq!(merge_filters("", "info"), "info");
+        assert_eq!(merge_filters("error", "warn"), "warn");
+        assert_eq!(
+            merge_filters("error", "solana_core::archiver=trace"),
+            "error,solana_core::archiver=trace"
+        );
+        assert_eq!(
+            merge_filters(
+                "info,solana_core::archiver=trace,solana_runtime=debug",
+                "solana_core::archiver=info, warn"
+            ),
+            "warn,solana_core::archiver=info,solana_runtime=debug"
+        );
+        assert_eq!(merge_filters("info,", ""), "info");
+    }
+}
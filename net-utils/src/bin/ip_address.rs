@@ -8,18 +8,24 @@ fn main() {
             Arg::with_name("host_port")
                 .index(1)
                 .required(true)
-                .help("Host:port to connect to"),
+                .multiple(true)
+                .help("Host:port to connect to, a majority of the given servers must agree"),
         )
         .get_matches();
 
-    let host_port = matches.value_of("host_port").unwrap();
-    let addr = solana_net_utils::parse_host_port(host_port)
-        .unwrap_or_else(|_| panic!("failed to parse {}", host_port));
+    let addrs: Vec<_> = matches
+        .values_of("host_port")
+        .unwrap()
+        .map(|host_port| {
+            solana_net_utils::parse_host_port(host_port)
+                .unwrap_or_else(|_| panic!("failed to parse {}", host_port))
+        })
+        .collect();
 
-    match solana_net_utils::get_public_ip_addr(&addr) {
+    match solana_net_utils::get_public_ip_addr_with_quorum(&addrs) {
         Ok(ip) => println!("{}", ip),
         Err(err) => {
-            eprintln!("{}: {}", addr, err);
+            eprintln!("{}", err);
             std::process::exit(1)
         }
     }
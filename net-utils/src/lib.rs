@@ -2,10 +2,14 @@
 use log::*;
 use rand::{thread_rng, Rng};
 use socket2::{Domain, SockAddr, Socket, Type};
+use std::collections::HashMap;
+use std::fmt;
 use std::io::{self, Read, Write};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::net::{
+    IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket,
+};
 use std::sync::mpsc::channel;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 mod ip_echo_server;
 use ip_echo_server::IpEchoServerMessage;
@@ -54,6 +58,142 @@ pub fn get_public_ip_addr(ip_echo_server_addr: &SocketAddr) -> Result<IpAddr, St
     ip_echo_server_request(ip_echo_server_addr, IpEchoServerMessage::default())
 }
 
+/// Longest to wait on all the ip_echo_servers together, covers the connect and read timeouts
+/// of a single request
+const PUBLIC_IP_ADDR_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(16);
+
+/// Reasons the public IP address couldn't be determined from a set of ip_echo_servers
+#[derive(Debug, PartialEq)]
+pub enum PublicIpAddrError {
+    /// No ip_echo_server answered, this machine can't reach any of them
+    Unreachable(Vec<(SocketAddr, String)>),
+
+    /// The ip_echo_servers saw different addresses, this machine is likely behind a NAT that
+    /// doesn't map its connections to a single public address
+    Inconsistent {
+        responses: Vec<(SocketAddr, IpAddr)>,
+        quorum: usize,
+    },
+
+    /// The ip_echo_servers that answered agree, but too few of them answered
+    NoQuorum {
+        responses: Vec<(SocketAddr, IpAddr)>,
+        errors: Vec<(SocketAddr, String)>,
+        quorum: usize,
+    },
+}
+
+impl fmt::Display for PublicIpAddrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PublicIpAddrError::Unreachable(errors) => {
+                write!(f, "no ip echo server is reachable: {:?}", errors)
+            }
+            PublicIpAddrError::Inconsistent { responses, quorum } => write!(
+                f,
+                "ip echo servers disagree on the public address, check for NAT (quorum {}): {:?}",
+                quorum, responses
+            ),
+            PublicIpAddrError::NoQuorum {
+                responses,
+                errors,
+                quorum,
+            } => write!(
+                f,
+                "only {} of the {} ip echo servers needed responded: {:?}, errors: {:?}",
+                responses.len(),
+                quorum,
+                responses,
+                errors
+            ),
+        }
+    }
+}
+
+/// Determine the public IP address of this machine by asking all the given ip_echo_servers
+/// at once.  A majority of them must report the same address for it to be returned
+pub fn get_public_ip_addr_with_quorum(
+    ip_echo_server_addrs: &[SocketAddr],
+) -> Result<IpAddr, PublicIpAddrError> {
+    let mut unique_addrs = vec![];
+    for ip_echo_server_addr in ip_echo_server_addrs {
+        if !unique_addrs.contains(ip_echo_server_addr) {
+            unique_addrs.push(*ip_echo_server_addr);
+        }
+    }
+    let ip_echo_server_addrs = &unique_addrs;
+
+    let (sender, receiver) = channel();
+    for ip_echo_server_addr in ip_echo_server_addrs {
+        let sender = sender.clone();
+        let ip_echo_server_addr = *ip_echo_server_addr;
+        std::thread::spawn(move || {
+            let result = get_public_ip_addr(&ip_echo_server_addr);
+            let _ = sender.send((ip_echo_server_addr, result));
+        });
+    }
+    drop(sender);
+
+    let start = Instant::now();
+    let mut results = HashMap::new();
+    while results.len() < ip_echo_server_addrs.len() {
+        let elapsed = start.elapsed();
+        if elapsed >= PUBLIC_IP_ADDR_DISCOVERY_TIMEOUT {
+            break;
+        }
+        match receiver.recv_timeout(PUBLIC_IP_ADDR_DISCOVERY_TIMEOUT - elapsed) {
+            Ok((ip_echo_server_addr, result)) => {
+                results.insert(ip_echo_server_addr, result);
+            }
+            Err(_) => break,
+        }
+    }
+    let results = ip_echo_server_addrs
+        .iter()
+        .map(|ip_echo_server_addr| {
+            let result = results
+                .remove(ip_echo_server_addr)
+                .unwrap_or_else(|| Err("timed out".to_string()));
+            (*ip_echo_server_addr, result)
+        })
+        .collect();
+    tally_public_ip_addrs(results, ip_echo_server_addrs.len() / 2 + 1)
+}
+
+fn tally_public_ip_addrs(
+    results: Vec<(SocketAddr, Result<IpAddr, String>)>,
+    quorum: usize,
+) -> Result<IpAddr, PublicIpAddrError> {
+    let mut responses = vec![];
+    let mut errors = vec![];
+    for (ip_echo_server_addr, result) in results {
+        match result {
+            Ok(ip_addr) => responses.push((ip_echo_server_addr, ip_addr)),
+            Err(err) => errors.push((ip_echo_server_addr, err)),
+        }
+    }
+    if responses.is_empty() {
+        return Err(PublicIpAddrError::Unreachable(errors));
+    }
+
+    let mut votes: HashMap<IpAddr, usize> = HashMap::new();
+    for (_, ip_addr) in &responses {
+        *votes.entry(*ip_addr).or_insert(0) += 1;
+    }
+    if let Some((ip_addr, _)) = votes.iter().find(|(_, count)| **count >= quorum) {
+        return Ok(*ip_addr);
+    }
+    if votes.len() > 1 {
+        Err(PublicIpAddrError::Inconsistent { responses, quorum })
+    } else {
+        Err(PublicIpAddrError::NoQuorum {
+            responses,
+            errors,
+            quorum,
+        })
+    }
+}
+
 // Aborts the process if any of the provided TCP/UDP ports are not reachable by the machine at
 // `ip_echo_server_addr`
 pub fn verify_reachable_ports(
@@ -61,6 +201,19 @@ pub fn verify_reachable_ports(
     tcp_listeners: Vec<(u16, TcpListener)>,
     udp_sockets: &[&UdpSocket],
 ) {
+    check_reachable_ports(ip_echo_server_addr, tcp_listeners, udp_sockets).unwrap_or_else(|err| {
+        error!("{}", err);
+        std::process::exit(1);
+    });
+}
+
+// Returns an error naming the first of the provided TCP/UDP ports that is not reachable by the
+// machine at `ip_echo_server_addr`
+pub fn check_reachable_ports(
+    ip_echo_server_addr: &SocketAddr,
+    tcp_listeners: Vec<(u16, TcpListener)>,
+    udp_sockets: &[&UdpSocket],
+) -> Result<(), String> {
     let udp: Vec<(_, _)> = udp_sockets
         .iter()
         .map(|udp_socket| {
@@ -95,13 +248,12 @@ pub fn verify_reachable_ports(
         });
         receiver
             .recv_timeout(Duration::from_secs(5))
-            .unwrap_or_else(|err| {
-                error!(
+            .map_err(|err| {
+                format!(
                     "Received no response at tcp/{}, check your port configuration: {}",
                     port, err
-                );
-                std::process::exit(1);
-            });
+                )
+            })?;
         info!("tdp/{} is reachable", port);
     }
 
@@ -116,15 +268,15 @@ pub fn verify_reachable_ports(
         });
         receiver
             .recv_timeout(Duration::from_secs(5))
-            .unwrap_or_else(|err| {
-                error!(
+            .map_err(|err| {
+                format!(
                     "Received no response at udp/{}, check your port configuration: {}",
                     port, err
-                );
-                std::process::exit(1);
-            });
+                )
+            })?;
         info!("udp/{} is reachable", port);
     }
+    Ok(())
 }
 
 pub fn parse_port_or_addr(optstr: Option<&str>, default_addr: SocketAddr) -> SocketAddr {
@@ -193,19 +345,41 @@ pub fn is_host_port(string: String) -> Result<(), String> {
     Ok(())
 }
 
+const UNSPECIFIED_IPV4: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+
+/// The unspecified address of `ip`'s family, sockets bound to the IPv6 one are dual-stack and
+/// also reach IPv4 peers
+pub fn unspecified_ip_for(ip: &IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => UNSPECIFIED_IPV4,
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+// A socket of `ip`'s family, IPv6 sockets accept IPv4 traffic too
+fn new_socket(ip: &IpAddr, socket_type: Type) -> io::Result<Socket> {
+    match ip {
+        IpAddr::V4(_) => Socket::new(Domain::ipv4(), socket_type, None),
+        IpAddr::V6(_) => {
+            let sock = Socket::new(Domain::ipv6(), socket_type, None)?;
+            sock.set_only_v6(false)?;
+            Ok(sock)
+        }
+    }
+}
+
 #[cfg(windows)]
-fn udp_socket(_reuseaddr: bool) -> io::Result<Socket> {
-    let sock = Socket::new(Domain::ipv4(), Type::dgram(), None)?;
-    Ok(sock)
+fn udp_socket(ip: &IpAddr, _reuseaddr: bool) -> io::Result<Socket> {
+    new_socket(ip, Type::dgram())
 }
 
 #[cfg(not(windows))]
-fn udp_socket(reuseaddr: bool) -> io::Result<Socket> {
+fn udp_socket(ip: &IpAddr, reuseaddr: bool) -> io::Result<Socket> {
     use nix::sys::socket::setsockopt;
     use nix::sys::socket::sockopt::{ReuseAddr, ReusePort};
     use std::os::unix::io::AsRawFd;
 
-    let sock = Socket::new(Domain::ipv4(), Type::dgram(), None)?;
+    let sock = new_socket(ip, Type::dgram())?;
     let sock_fd = sock.as_raw_fd();
 
     if reuseaddr {
@@ -219,11 +393,19 @@ fn udp_socket(reuseaddr: bool) -> io::Result<Socket> {
 
 // Find a port in the given range that is available for both TCP and UDP
 pub fn bind_common_in_range(range: PortRange) -> io::Result<(u16, (UdpSocket, TcpListener))> {
+    bind_common_in_range_with_ip(UNSPECIFIED_IPV4, range)
+}
+
+/// Like `bind_common_in_range`, but binds to `ip` instead of the IPv4 unspecified address
+pub fn bind_common_in_range_with_ip(
+    ip: IpAddr,
+    range: PortRange,
+) -> io::Result<(u16, (UdpSocket, TcpListener))> {
     let (start, end) = range;
     let mut tries_left = end - start;
     let mut rand_port = thread_rng().gen_range(start, end);
     loop {
-        match bind_common(rand_port, false) {
+        match bind_common_with_ip(ip, rand_port, false) {
             Ok((sock, listener)) => {
                 break Result::Ok((sock.local_addr().unwrap().port(), (sock, listener)));
             }
@@ -242,13 +424,18 @@ pub fn bind_common_in_range(range: PortRange) -> io::Result<(u16, (UdpSocket, Tc
 }
 
 pub fn bind_in_range(range: PortRange) -> io::Result<(u16, UdpSocket)> {
-    let sock = udp_socket(false)?;
+    bind_in_range_with_ip(UNSPECIFIED_IPV4, range)
+}
+
+/// Like `bind_in_range`, but binds to `ip` instead of the IPv4 unspecified address
+pub fn bind_in_range_with_ip(ip: IpAddr, range: PortRange) -> io::Result<(u16, UdpSocket)> {
+    let sock = udp_socket(&ip, false)?;
 
     let (start, end) = range;
     let mut tries_left = end - start;
     let mut rand_port = thread_rng().gen_range(start, end);
     loop {
-        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), rand_port);
+        let addr = SocketAddr::new(ip, rand_port);
 
         match sock.bind(&SockAddr::from(addr)) {
             Ok(_) => {
@@ -270,7 +457,16 @@ pub fn bind_in_range(range: PortRange) -> io::Result<(u16, UdpSocket)> {
 }
 
 // binds many sockets to the same port in a range
-pub fn multi_bind_in_range(range: PortRange, mut num: usize) -> io::Result<(u16, Vec<UdpSocket>)> {
+pub fn multi_bind_in_range(range: PortRange, num: usize) -> io::Result<(u16, Vec<UdpSocket>)> {
+    multi_bind_in_range_with_ip(UNSPECIFIED_IPV4, range, num)
+}
+
+/// Like `multi_bind_in_range`, but binds to `ip` instead of the IPv4 unspecified address
+pub fn multi_bind_in_range_with_ip(
+    ip: IpAddr,
+    range: PortRange,
+    mut num: usize,
+) -> io::Result<(u16, Vec<UdpSocket>)> {
     if cfg!(windows) && num != 1 {
         // See https://github.com/solana-labs/solana/issues/4607
         warn!(
@@ -282,20 +478,25 @@ pub fn multi_bind_in_range(range: PortRange, mut num: usize) -> io::Result<(u16,
     let mut sockets = Vec::with_capacity(num);
 
     let port = {
-        let (port, _) = bind_in_range(range)?;
+        let (port, _) = bind_in_range_with_ip(ip, range)?;
         port
     }; // drop the probe, port should be available... briefly.
 
     for _ in 0..num {
-        sockets.push(bind_to(port, true)?);
+        sockets.push(bind_to_with_ip(ip, port, true)?);
     }
     Ok((port, sockets))
 }
 
 pub fn bind_to(port: u16, reuseaddr: bool) -> io::Result<UdpSocket> {
-    let sock = udp_socket(reuseaddr)?;
+    bind_to_with_ip(UNSPECIFIED_IPV4, port, reuseaddr)
+}
 
-    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port);
+/// Like `bind_to`, but binds to `ip` instead of the IPv4 unspecified address
+pub fn bind_to_with_ip(ip: IpAddr, port: u16, reuseaddr: bool) -> io::Result<UdpSocket> {
+    let sock = udp_socket(&ip, reuseaddr)?;
+
+    let addr = SocketAddr::new(ip, port);
 
     match sock.bind(&SockAddr::from(addr)) {
         Ok(_) => Result::Ok(sock.into_udp_socket()),
@@ -305,12 +506,21 @@ pub fn bind_to(port: u16, reuseaddr: bool) -> io::Result<UdpSocket> {
 
 // binds both a UdpSocket and a TcpListener
 pub fn bind_common(port: u16, reuseaddr: bool) -> io::Result<(UdpSocket, TcpListener)> {
-    let sock = udp_socket(reuseaddr)?;
+    bind_common_with_ip(UNSPECIFIED_IPV4, port, reuseaddr)
+}
+
+/// Like `bind_common`, but binds to `ip` instead of the IPv4 unspecified address
+pub fn bind_common_with_ip(
+    ip: IpAddr,
+    port: u16,
+    reuseaddr: bool,
+) -> io::Result<(UdpSocket, TcpListener)> {
+    let sock = udp_socket(&ip, reuseaddr)?;
 
-    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port);
+    let addr = SocketAddr::new(ip, port);
     let sock_addr = SockAddr::from(addr);
     match sock.bind(&sock_addr) {
-        Ok(_) => match TcpListener::bind(&addr) {
+        Ok(_) => match tcp_listener(&addr) {
             Ok(listener) => Result::Ok((sock.into_udp_socket(), listener)),
             Err(err) => Err(err),
         },
@@ -318,6 +528,19 @@ pub fn bind_common(port: u16, reuseaddr: bool) -> io::Result<(UdpSocket, TcpList
     }
 }
 
+fn tcp_listener(addr: &SocketAddr) -> io::Result<TcpListener> {
+    if addr.is_ipv4() {
+        return TcpListener::bind(addr);
+    }
+    // std leaves IPV6_V6ONLY to the system default, set it so the listener is dual-stack too
+    let sock = new_socket(&addr.ip(), Type::stream())?;
+    #[cfg(not(windows))]
+    sock.set_reuse_address(true)?;
+    sock.bind(&SockAddr::from(*addr))?;
+    sock.listen(128)?;
+    Ok(sock.into_tcp_listener())
+}
+
 pub fn find_available_port_in_range(range: PortRange) -> io::Result<u16> {
     let (start, end) = range;
     let mut tries_left = end - start;
@@ -406,6 +629,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_bind_dual_stack() {
+        let (port, socket) =
+            match bind_in_range_with_ip(IpAddr::V6(Ipv6Addr::UNSPECIFIED), (2200, 2300)) {
+                Ok(bound) => bound,
+                // IPv6 may be disabled on the test host
+                Err(_) => return,
+            };
+        assert!(socket.local_addr().unwrap().is_ipv6());
+        socket
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        // IPv4 peers reach the dual-stack socket and get answered
+        let (_, peer) = bind_in_range((2300, 2400)).unwrap();
+        peer.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        peer.send_to(&[1], ("127.0.0.1", port)).unwrap();
+        let mut buf = [0; 1];
+        let (_, from) = socket.recv_from(&mut buf).unwrap();
+        assert_eq!(buf, [1]);
+        socket.send_to(&[2], from).unwrap();
+        peer.recv_from(&mut buf).unwrap();
+        assert_eq!(buf, [2]);
+
+        assert_eq!(
+            unspecified_ip_for(&"::1".parse().unwrap()),
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        );
+        assert_eq!(
+            unspecified_ip_for(&"127.0.0.1".parse().unwrap()),
+            UNSPECIFIED_IPV4
+        );
+    }
+
     #[test]
     #[should_panic]
     fn test_bind_in_range_nil() {
@@ -430,6 +687,54 @@ mod tests {
         bind_common_in_range((port, port + 1)).unwrap_err();
     }
 
+    #[test]
+    fn test_tally_public_ip_addrs() {
+        let server = |i| SocketAddr::from(([10, 0, 0, i], 8001));
+        let ip_addr = |i| IpAddr::V4(Ipv4Addr::new(1, 2, 3, i));
+        let err = || Err("connection refused".to_string());
+
+        assert_eq!(
+            tally_public_ip_addrs(
+                vec![
+                    (server(1), Ok(ip_addr(1))),
+                    (server(2), Ok(ip_addr(1))),
+                    (server(3), err())
+                ],
+                2
+            ),
+            Ok(ip_addr(1))
+        );
+        assert_eq!(
+            tally_public_ip_addrs(vec![(server(1), err()), (server(2), err())], 2),
+            Err(PublicIpAddrError::Unreachable(vec![
+                (server(1), "connection refused".to_string()),
+                (server(2), "connection refused".to_string()),
+            ]))
+        );
+        assert_eq!(
+            tally_public_ip_addrs(
+                vec![
+                    (server(1), Ok(ip_addr(1))),
+                    (server(2), Ok(ip_addr(2))),
+                    (server(3), err())
+                ],
+                2
+            ),
+            Err(PublicIpAddrError::Inconsistent {
+                responses: vec![(server(1), ip_addr(1)), (server(2), ip_addr(2))],
+                quorum: 2,
+            })
+        );
+        assert_eq!(
+            tally_public_ip_addrs(vec![(server(1), Ok(ip_addr(1))), (server(2), err())], 2),
+            Err(PublicIpAddrError::NoQuorum {
+                responses: vec![(server(1), ip_addr(1))],
+                errors: vec![(server(2), "connection refused".to_string())],
+                quorum: 2,
+            })
+        );
+    }
+
     #[test]
     fn test_get_public_ip_addr() {
         let (_server_port, (server_udp_socket, server_tcp_listener)) =
@@ -441,6 +746,10 @@ mod tests {
 
         let ip_echo_server_addr = server_udp_socket.local_addr().unwrap();
         get_public_ip_addr(&ip_echo_server_addr).unwrap();
+        assert_eq!(
+            get_public_ip_addr_with_quorum(&[ip_echo_server_addr]).unwrap(),
+            get_public_ip_addr(&ip_echo_server_addr).unwrap()
+        );
 
         verify_reachable_ports(
             &ip_echo_server_addr,
@@ -0,0 +1,178 @@
+use clap::{crate_description, crate_name, crate_version, value_t_or_exit, App, Arg};
+use log::*;
+use solana_core::packet_fuzz::{FuzzTarget, PacketMutator};
+use solana_sdk::hash::hash;
+use std::{
+    collections::HashSet,
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+/// Inputs are stored under the hash of their contents, so the same input is never kept twice
+fn input_path(dir: &Path, input: &[u8]) -> PathBuf {
+    dir.join(hash(input).to_string())
+}
+
+fn save_input(dir: &Path, input: &[u8]) -> io::Result<bool> {
+    let path = input_path(dir, input);
+    if path.exists() {
+        return Ok(false);
+    }
+    fs::write(path, input)?;
+    Ok(true)
+}
+
+/// Load every file in `dir`, sorted by name so runs over the same corpus are reproducible
+fn load_corpus(dir: &Path) -> io::Result<Vec<Vec<u8>>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    paths.iter().map(fs::read).collect()
+}
+
+/// Run `input` through `target`, returning `None` if it panicked
+fn run_input(target: FuzzTarget, input: &[u8]) -> Option<bool> {
+    panic::catch_unwind(AssertUnwindSafe(|| target.run(input))).ok()
+}
+
+fn main() {
+    solana_logger::setup_with_filter("solana=info");
+
+    let target_names: Vec<_> = FuzzTarget::all()
+        .iter()
+        .map(|target| target.name())
+        .collect();
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .arg(
+            Arg::with_name("target")
+                .index(1)
+                .value_name("TARGET")
+                .takes_value(true)
+                .required(true)
+                .possible_values(&target_names)
+                .help("Deserialization path to fuzz"),
+        )
+        .arg(
+            Arg::with_name("corpus")
+                .long("corpus")
+                .value_name("DIR")
+                .takes_value(true)
+                .required(true)
+                .help("Corpus directory, populated with seed packets when empty"),
+        )
+        .arg(
+            Arg::with_name("crashes")
+                .long("crashes")
+                .value_name("DIR")
+                .takes_value(true)
+                .help("Directory to save inputs that panic [default: <corpus>/crashes]"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("NUMBER")
+                .takes_value(true)
+                .default_value("0")
+                .help("Seed for the input mutations, reusing a seed replays the same run"),
+        )
+        .arg(
+            Arg::with_name("iterations")
+                .long("iterations")
+                .value_name("NUMBER")
+                .takes_value(true)
+                .default_value("100000")
+                .help("Number of mutated inputs to run"),
+        )
+        .arg(
+            Arg::with_name("replay")
+                .long("replay")
+                .takes_value(false)
+                .help("Only run the existing corpus and crashes, without mutating them"),
+        )
+        .get_matches();
+
+    let target = FuzzTarget::from_name(matches.value_of("target").unwrap()).unwrap();
+    let corpus_dir = PathBuf::from(matches.value_of("corpus").unwrap());
+    let crashes_dir = matches
+        .value_of("crashes")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| corpus_dir.join("crashes"));
+    let seed = value_t_or_exit!(matches, "seed", u64);
+    let iterations = value_t_or_exit!(matches, "iterations", usize);
+
+    fs::create_dir_all(&corpus_dir).expect("create corpus directory");
+    fs::create_dir_all(&crashes_dir).expect("create crashes directory");
+
+    let mut corpus = load_corpus(&corpus_dir).expect("load corpus");
+    if corpus.is_empty() {
+        for input in target.seed_corpus() {
+            save_input(&corpus_dir, &input).expect("save seed input");
+        }
+        corpus = load_corpus(&corpus_dir).expect("load corpus");
+        info!(
+            "seeded {} with {} {} packets",
+            corpus_dir.display(),
+            corpus.len(),
+            target.name()
+        );
+    }
+
+    // Keep panics quiet while fuzzing, crashing inputs are reported by path instead
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut crashes = 0;
+    let previous_crashes = load_corpus(&crashes_dir).expect("load crashes");
+    for input in corpus.iter().chain(previous_crashes.iter()) {
+        if run_input(target, input).is_none() {
+            crashes += 1;
+            error!("panic on {}", input_path(&crashes_dir, input).display());
+        }
+    }
+
+    if !matches.is_present("replay") {
+        let mut mutator = PacketMutator::new(seed);
+        let mut seen: HashSet<_> = corpus.iter().map(|input| hash(input)).collect();
+        let mut accepted = 0;
+        for i in 0..iterations {
+            let input = mutator.mutate(&corpus[i % corpus.len()]);
+            match run_input(target, &input) {
+                Some(true) => {
+                    // inputs that still parse are kept as starting points for later runs
+                    if seen.insert(hash(&input)) {
+                        save_input(&corpus_dir, &input).expect("save corpus input");
+                        accepted += 1;
+                    }
+                }
+                Some(false) => (),
+                None => {
+                    if save_input(&crashes_dir, &input).expect("save crashing input") {
+                        crashes += 1;
+                        error!(
+                            "iteration {} panicked, saved {}",
+                            i,
+                            input_path(&crashes_dir, &input).display()
+                        );
+                    }
+                }
+            }
+        }
+        info!(
+            "{} {} inputs from seed {}: {} added to the corpus, {} crashes",
+            iterations,
+            target.name(),
+            seed,
+            accepted,
+            crashes
+        );
+    }
+
+    if crashes > 0 {
+        exit(1);
+    }
+}
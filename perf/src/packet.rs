@@ -3,6 +3,7 @@ use crate::{
     cuda_runtime::PinnedVec,
     recycler::{Recycler, Reset},
 };
+use bincode::serialized_size;
 use serde::Serialize;
 pub use solana_sdk::packet::{Meta, Packet, PACKET_DATA_SIZE};
 use std::{mem, net::SocketAddr};
@@ -100,14 +101,67 @@ pub fn to_packets<T: Serialize>(xs: &[T]) -> Vec<Packets> {
 }
 
 pub fn to_packets_with_destination<T: Serialize>(dests_and_data: &[(SocketAddr, T)]) -> Packets {
+    let (out, skipped) = to_packets_with_destination_reporting_skipped(dests_and_data);
+    if !skipped.is_empty() {
+        error!(
+            "Couldn't write {} of {} items to packets. Data skipped.",
+            skipped.len(),
+            dests_and_data.len()
+        );
+    }
+    out
+}
+
+/// Serializes each item into its own packet, leaving out any item that does not fit
+/// within `PACKET_DATA_SIZE`. Returns the packets along with the indices of the skipped items.
+pub fn to_packets_with_destination_reporting_skipped<T: Serialize>(
+    dests_and_data: &[(SocketAddr, T)],
+) -> (Packets, Vec<usize>) {
     let mut out = Packets::default();
-    out.packets.resize(dests_and_data.len(), Packet::default());
-    for (dest_and_data, o) in dests_and_data.iter().zip(out.packets.iter_mut()) {
-        if let Err(e) = Packet::populate_packet(o, Some(&dest_and_data.0), &dest_and_data.1) {
-            error!("Couldn't write to packet {:?}. Data skipped.", e);
+    out.packets.reserve(dests_and_data.len());
+    let mut skipped = vec![];
+    for (i, (dest, data)) in dests_and_data.iter().enumerate() {
+        let mut packet = Packet::default();
+        match Packet::populate_packet(&mut packet, Some(dest), data) {
+            Ok(()) => out.packets.push(packet),
+            Err(e) => {
+                debug!("Couldn't write item {} to packet {:?}", i, e);
+                skipped.push(i);
+            }
         }
     }
-    out
+    (out, skipped)
+}
+
+/// Splits `items` into groups whose serialized `Vec<T>` is no larger than `max_size` bytes,
+/// preserving order. Items that cannot fit on their own are left out and their indices
+/// are returned alongside the groups.
+pub fn split_into_packet_payloads<T: Serialize>(
+    items: Vec<T>,
+    max_size: u64,
+) -> (Vec<Vec<T>>, Vec<usize>) {
+    let empty_size = serialized_size(&Vec::<T>::new()).expect("serialized size");
+    let mut payloads = vec![];
+    let mut skipped = vec![];
+    let mut payload = vec![];
+    let mut size = empty_size;
+    for (i, item) in items.into_iter().enumerate() {
+        let item_size = serialized_size(&item).expect("serialized size");
+        if empty_size + item_size > max_size {
+            skipped.push(i);
+            continue;
+        }
+        if size + item_size > max_size {
+            payloads.push(mem::replace(&mut payload, vec![]));
+            size = empty_size;
+        }
+        size += item_size;
+        payload.push(item);
+    }
+    if !payload.is_empty() {
+        payloads.push(payload);
+    }
+    (payloads, skipped)
 }
 
 pub fn limited_deserialize<T>(data: &[u8]) -> bincode::Result<T>
@@ -153,4 +207,52 @@ mod tests {
         assert_eq!(rv[0].packets.len(), NUM_PACKETS);
         assert_eq!(rv[1].packets.len(), 1);
     }
+
+    #[test]
+    fn test_to_packets_with_destination_reporting_skipped() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 8001));
+        let small = vec![0u8; 16];
+        let large = vec![0u8; PACKET_DATA_SIZE];
+        let items = vec![
+            (addr, small.clone()),
+            (addr, large.clone()),
+            (addr, small.clone()),
+            (addr, large),
+        ];
+        let (packets, skipped) = to_packets_with_destination_reporting_skipped(&items);
+        assert_eq!(skipped, vec![1, 3]);
+        assert_eq!(packets.packets.len(), 2);
+        for packet in packets.packets.iter() {
+            assert_eq!(packet.meta.addr(), addr);
+            assert_eq!(packet.meta.size, serialized_size(&small).unwrap() as usize);
+        }
+
+        let packets = to_packets_with_destination(&items);
+        assert_eq!(packets.packets.len(), 2);
+    }
+
+    #[test]
+    fn test_split_into_packet_payloads() {
+        let max_size = PACKET_DATA_SIZE as u64;
+        let item = vec![1u8; 100];
+        let item_size = serialized_size(&item).unwrap();
+        let empty_size = serialized_size(&Vec::<Vec<u8>>::new()).unwrap();
+        let per_payload = ((max_size - empty_size) / item_size) as usize;
+
+        let mut items = vec![item.clone(); per_payload * 2 + 1];
+        items.insert(3, vec![0u8; PACKET_DATA_SIZE]);
+        let (payloads, skipped) = split_into_packet_payloads(items, max_size);
+        assert_eq!(skipped, vec![3]);
+        assert_eq!(payloads.len(), 3);
+        assert_eq!(payloads[0].len(), per_payload);
+        assert_eq!(payloads[1].len(), per_payload);
+        assert_eq!(payloads[2].len(), 1);
+        for payload in &payloads {
+            assert!(serialized_size(payload).unwrap() <= max_size);
+        }
+
+        let (payloads, skipped) = split_into_packet_payloads(Vec::<Vec<u8>>::new(), max_size);
+        assert!(payloads.is_empty());
+        assert!(skipped.is_empty());
+    }
 }
@@ -87,6 +87,41 @@ pub struct Api<'a> {
         ) -> c_int,
     >,
 
+    // Missing from older perf-libs releases, callers fall back to the CPU when it isn't loaded
+    #[allow(clippy::type_complexity)]
+    pub reed_solomon_encode_many: Option<
+        Symbol<
+            'a,
+            unsafe extern "C" fn(
+                data: *const u8,        //data blocks of all the sets, back to back
+                parity: *mut u8,        //parity blocks of all the sets, back to back
+                block_len: u32,         //size of every data and parity block
+                num_data: *const u32,   //number of data blocks in each set
+                num_parity: *const u32, //number of parity blocks in each set
+                num_sets: u32,
+                use_non_default_stream: u8,
+            ) -> c_int,
+        >,
+    >,
+
+    // Missing from older perf-libs releases, callers fall back to the CPU when it isn't loaded
+    #[allow(clippy::type_complexity)]
+    pub chacha_cbc_encrypt_many: Option<
+        Symbol<
+            'a,
+            unsafe extern "C" fn(
+                input: *const u8,       //pieces of all the streams, back to back
+                output: *mut u8,        //ciphertext, laid out like input
+                piece_lens: *const u32, //size of every piece, each one chacha_cbc_encrypt() call
+                num_pieces: *const u32, //number of pieces in each stream
+                num_streams: u32,
+                keys: *const u8, //one key per stream
+                ivecs: *mut u8,  //one ivec per stream, chained across its pieces
+                use_non_default_stream: u8,
+            ) -> c_int,
+        >,
+    >,
+
     pub cuda_host_register:
         Symbol<'a, unsafe extern "C" fn(ptr: *mut c_void, size: usize, flags: c_uint) -> c_int>,
 
@@ -190,6 +190,17 @@ fn get_packet_offsets(packet: &Packet, current_offset: u32) -> PacketOffsets {
     }
 }
 
+/// The fee payer of the transaction in `packet`, the first account key of its message
+pub fn get_packet_fee_payer(packet: &Packet) -> Option<Pubkey> {
+    let packet_offsets = do_get_packet_offsets(packet, 0).ok()?;
+    let pubkey_start = packet_offsets.pubkey_start as usize;
+    let pubkey_end = pubkey_start + size_of::<Pubkey>();
+    if pubkey_end > packet.meta.size {
+        return None;
+    }
+    Some(Pubkey::new(&packet.data[pubkey_start..pubkey_end]))
+}
+
 pub fn generate_offsets(
     batches: &[Packets],
     recycler: &Recycler<TxOffset>,
@@ -494,6 +505,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_packet_fee_payer() {
+        let tx = test_tx();
+        let mut packet = sigverify::make_packet_from_transaction(tx.clone());
+        assert_eq!(
+            sigverify::get_packet_fee_payer(&packet),
+            Some(tx.message.account_keys[0])
+        );
+
+        packet.meta.size = 2;
+        assert_eq!(sigverify::get_packet_fee_payer(&packet), None);
+    }
+
     #[test]
     fn test_small_packet() {
         let tx = test_tx();
@@ -126,12 +126,12 @@ mod bpf {
                 let account_metas = vec![
                     AccountMeta::new(mint_keypair.pubkey(), true),
                     AccountMeta::new(Keypair::new().pubkey(), false),
-                    AccountMeta::new(clock::id(), false),
-                    AccountMeta::new(fees::id(), false),
-                    AccountMeta::new(rewards::id(), false),
-                    AccountMeta::new(slot_hashes::id(), false),
-                    AccountMeta::new(stake_history::id(), false),
-                    AccountMeta::new(rent::id(), false),
+                    AccountMeta::new_readonly(clock::id(), false),
+                    AccountMeta::new_readonly(fees::id(), false),
+                    AccountMeta::new_readonly(rewards::id(), false),
+                    AccountMeta::new_readonly(slot_hashes::id(), false),
+                    AccountMeta::new_readonly(stake_history::id(), false),
+                    AccountMeta::new_readonly(rent::id(), false),
                 ];
                 let instruction = Instruction::new(program_id, &1u8, account_metas);
                 let result = bank_client.send_instruction(&mint_keypair, instruction);
@@ -8,5 +8,8 @@ macro_rules! solana_bpf_loader_program {
     };
 }
 
+/// Version of this program, recorded in genesis manifests
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 use solana_bpf_loader_api::process_instruction;
 solana_sdk::solana_entrypoint!(process_instruction);
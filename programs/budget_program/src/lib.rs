@@ -5,5 +5,8 @@ macro_rules! solana_budget_program {
     };
 }
 
+/// Version of this program, recorded in genesis manifests
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 use solana_budget_api::budget_processor::process_instruction;
 solana_sdk::solana_entrypoint!(process_instruction);
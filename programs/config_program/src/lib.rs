@@ -4,6 +4,10 @@ macro_rules! solana_config_program {
         ("solana_config_program".to_string(), solana_config_api::id())
     };
 }
+
+/// Version of this program, recorded in genesis manifests
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 use solana_config_api::config_processor::process_instruction;
 
 solana_sdk::solana_entrypoint!(process_instruction);
@@ -7,6 +7,10 @@ macro_rules! solana_exchange_program {
         )
     };
 }
+
+/// Version of this program, recorded in genesis manifests
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 use solana_exchange_api::exchange_processor::process_instruction;
 
 solana_sdk::solana_entrypoint!(process_instruction);
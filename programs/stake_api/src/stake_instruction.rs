@@ -24,6 +24,7 @@ pub enum StakeError {
     AlreadyDeactivated,
     TooSoonToRedelegate,
     InsufficientStake,
+    MergeMismatch,
 }
 impl<E> DecodeError<E> for StakeError {
     fn type_of() -> &'static str {
@@ -38,6 +39,10 @@ impl std::fmt::Display for StakeError {
             StakeError::AlreadyDeactivated => write!(f, "stake already deactivated"),
             StakeError::TooSoonToRedelegate => write!(f, "one re-delegation permitted per epoch"),
             StakeError::InsufficientStake => write!(f, "split amount is more than is staked"),
+            StakeError::MergeMismatch => write!(
+                f,
+                "stake accounts have different authorities, lockups or delegations"
+            ),
         }
     }
 }
@@ -126,6 +131,33 @@ pub enum StakeInstruction {
     ///    1 - Syscall Account that carries epoch
     ///
     Deactivate,
+
+    /// Merge all lamports and stake of one stake account into another,
+    ///   leaving the source stake account Uninitialized and empty.
+    ///   Requires Authorized::staker signature, which must be shared
+    ///   by both accounts along with their lockup.
+    ///
+    /// Delegated stakes can only be merged if they're delegated to the same
+    ///   vote account, have observed the same credits and are both fully
+    ///   active; an undelegated source may be merged into any stake.
+    ///
+    /// Expects 4 Accounts:
+    ///    0 - Destination StakeAccount
+    ///    1 - Source StakeAccount, drained by the merge
+    ///    2 - Syscall Account that carries epoch
+    ///    3 - StakeHistory sysvar that carries stake warmup/cooldown history
+    ///
+    Merge,
+
+    /// Replace the Lockup of a stake account
+    ///    requires Lockup::custodian signature while the current lockup
+    ///    is in force, Authorized::withdrawer signature once it has expired
+    ///
+    /// Expects 2 Accounts:
+    ///    0 - StakeAccount to be updated with the new Lockup
+    ///    1 - Syscall Account that carries slot
+    ///
+    SetLockup(Lockup),
 }
 
 pub fn initialize(stake_pubkey: &Pubkey, authorized: &Authorized, lockup: &Lockup) -> Instruction {
@@ -163,10 +195,28 @@ pub fn split(
     authorized_pubkey: &Pubkey,
     lamports: u64,
     split_stake_pubkey: &Pubkey,
+) -> Vec<Instruction> {
+    split_from(
+        stake_pubkey,
+        stake_pubkey,
+        authorized_pubkey,
+        lamports,
+        split_stake_pubkey,
+    )
+}
+
+/// Like `split()`, but `from_pubkey` signs for the creation of the split stake
+///  account, for when the stake account's own keypair isn't at hand
+pub fn split_from(
+    from_pubkey: &Pubkey,
+    stake_pubkey: &Pubkey,
+    authorized_pubkey: &Pubkey,
+    lamports: u64,
+    split_stake_pubkey: &Pubkey,
 ) -> Vec<Instruction> {
     vec![
         system_instruction::create_account(
-            stake_pubkey,
+            from_pubkey,
             split_stake_pubkey,
             0, // creates an ephemeral, uninitialized Stake
             std::mem::size_of::<StakeState>() as u64,
@@ -183,6 +233,21 @@ pub fn split(
     ]
 }
 
+pub fn merge(
+    destination_stake_pubkey: &Pubkey,
+    source_stake_pubkey: &Pubkey,
+    authorized_pubkey: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*destination_stake_pubkey, false),
+        AccountMeta::new(*source_stake_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+    ]
+    .with_signer(authorized_pubkey);
+    Instruction::new(id(), &StakeInstruction::Merge, account_metas)
+}
+
 pub fn create_stake_account(
     from_pubkey: &Pubkey,
     stake_pubkey: &Pubkey,
@@ -280,6 +345,39 @@ pub fn deactivate_stake(stake_pubkey: &Pubkey, authorized_pubkey: &Pubkey) -> In
     Instruction::new(id(), &StakeInstruction::Deactivate, account_metas)
 }
 
+pub fn set_lockup(
+    stake_pubkey: &Pubkey,
+    lockup: &Lockup,
+    authorized_pubkey: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*stake_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ]
+    .with_signer(authorized_pubkey);
+    Instruction::new(id(), &StakeInstruction::SetLockup(*lockup), account_metas)
+}
+
+/// Deactivate only `lamports` of a stake by splitting them off into
+///  `split_stake_pubkey` and deactivating that, leaving the rest in effect
+pub fn deactivate_stake_partial(
+    from_pubkey: &Pubkey,
+    stake_pubkey: &Pubkey,
+    authorized_pubkey: &Pubkey,
+    lamports: u64,
+    split_stake_pubkey: &Pubkey,
+) -> Vec<Instruction> {
+    let mut instructions = split_from(
+        from_pubkey,
+        stake_pubkey,
+        authorized_pubkey,
+        lamports,
+        split_stake_pubkey,
+    );
+    instructions.push(deactivate_stake(split_stake_pubkey, authorized_pubkey));
+    instructions
+}
+
 pub fn process_instruction(
     _program_id: &Pubkey,
     keyed_accounts: &mut [KeyedAccount],
@@ -344,6 +442,20 @@ pub fn process_instruction(
             &Clock::from_keyed_account(next_keyed_account(keyed_accounts)?)?,
             &signers,
         ),
+        StakeInstruction::Merge => {
+            let source_stake = &mut next_keyed_account(keyed_accounts)?;
+            me.merge(
+                source_stake,
+                &Clock::from_keyed_account(next_keyed_account(keyed_accounts)?)?,
+                &StakeHistory::from_keyed_account(next_keyed_account(keyed_accounts)?)?,
+                &signers,
+            )
+        }
+        StakeInstruction::SetLockup(lockup) => me.set_lockup(
+            &lockup,
+            &Clock::from_keyed_account(next_keyed_account(keyed_accounts)?)?,
+            &signers,
+        ),
     }
 }
 
@@ -440,6 +552,22 @@ mod tests {
             process_instruction(&deactivate_stake(&Pubkey::default(), &Pubkey::default())),
             Err(InstructionError::InvalidAccountData),
         );
+        assert_eq!(
+            process_instruction(&merge(
+                &Pubkey::default(),
+                &Pubkey::new_rand(),
+                &Pubkey::default()
+            )),
+            Err(InstructionError::InvalidAccountData),
+        );
+        assert_eq!(
+            process_instruction(&set_lockup(
+                &Pubkey::default(),
+                &Lockup::default(),
+                &Pubkey::default()
+            )),
+            Err(InstructionError::InvalidAccountData),
+        );
     }
 
     #[test]
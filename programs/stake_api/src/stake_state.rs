@@ -188,7 +188,8 @@ impl Stake {
         self.stake_activating_and_deactivating(epoch, history).0
     }
 
-    fn stake_activating_and_deactivating(
+    /// effective, activating and deactivating portions of the stake at the given epoch
+    pub fn stake_activating_and_deactivating(
         &self,
         epoch: Epoch,
         history: Option<&StakeHistory>,
@@ -304,7 +305,7 @@ impl Stake {
     ///   * staker_rewards to be distributed
     ///   * new value for credits_observed in the stake
     //  returns None if there's no payout or if any deserved payout is < 1 lamport
-    fn calculate_rewards(
+    pub fn calculate_rewards(
         &self,
         point_value: f64,
         vote_state: &VoteState,
@@ -414,6 +415,27 @@ impl Stake {
         }
     }
 
+    /// true if the whole stake is in effect at `epoch`, i.e. it's neither
+    ///  warming up nor cooling down
+    fn is_fully_active(&self, epoch: Epoch, history: Option<&StakeHistory>) -> bool {
+        self.deactivation_epoch == std::u64::MAX
+            && self.stake_activating_and_deactivating(epoch, history) == (self.stake, 0, 0)
+    }
+
+    fn merge(&mut self, other: &Self, epoch: Epoch, history: Option<&StakeHistory>) -> bool {
+        // only stakes that earn identically can be combined without
+        //  disturbing warmup/cooldown or rewards accounting
+        if self.voter_pubkey != other.voter_pubkey
+            || self.credits_observed != other.credits_observed
+            || !self.is_fully_active(epoch, history)
+            || !other.is_fully_active(epoch, history)
+        {
+            return false;
+        }
+        self.stake += other.stake;
+        true
+    }
+
     fn deactivate(&mut self, epoch: Epoch) -> Result<(), StakeError> {
         if self.deactivation_epoch != std::u64::MAX {
             Err(StakeError::AlreadyDeactivated)
@@ -462,6 +484,13 @@ pub trait StakeAccount {
         split_stake: &mut KeyedAccount,
         signers: &HashSet<Pubkey>,
     ) -> Result<(), InstructionError>;
+    fn merge(
+        &mut self,
+        source_stake: &mut KeyedAccount,
+        clock: &sysvar::clock::Clock,
+        stake_history: &sysvar::stake_history::StakeHistory,
+        signers: &HashSet<Pubkey>,
+    ) -> Result<(), InstructionError>;
     fn withdraw(
         &mut self,
         lamports: u64,
@@ -470,6 +499,12 @@ pub trait StakeAccount {
         stake_history: &sysvar::stake_history::StakeHistory,
         signers: &HashSet<Pubkey>,
     ) -> Result<(), InstructionError>;
+    fn set_lockup(
+        &mut self,
+        lockup: &Lockup,
+        clock: &sysvar::clock::Clock,
+        signers: &HashSet<Pubkey>,
+    ) -> Result<(), InstructionError>;
 }
 
 impl<'a> StakeAccount for KeyedAccount<'a> {
@@ -676,6 +711,54 @@ impl<'a> StakeAccount for KeyedAccount<'a> {
         }
     }
 
+    fn merge(
+        &mut self,
+        source: &mut KeyedAccount,
+        clock: &sysvar::clock::Clock,
+        stake_history: &sysvar::stake_history::StakeHistory,
+        signers: &HashSet<Pubkey>,
+    ) -> Result<(), InstructionError> {
+        fn meta_and_stake(state: StakeState) -> Result<(Meta, Option<Stake>), InstructionError> {
+            match state {
+                StakeState::Initialized(meta) => Ok((meta, None)),
+                StakeState::Stake(meta, stake) => Ok((meta, Some(stake))),
+                _ => Err(InstructionError::InvalidAccountData),
+            }
+        }
+        let (meta, stake) = meta_and_stake(self.state()?)?;
+        let (source_meta, source_stake) = meta_and_stake(source.state()?)?;
+
+        if self.unsigned_key() == source.unsigned_key() {
+            return Err(InstructionError::InvalidArgument);
+        }
+        meta.authorized.check(signers, StakeAuthorize::Staker)?;
+
+        // the merged account must be no easier to move or withdraw than either source
+        if meta.authorized != source_meta.authorized || meta.lockup != source_meta.lockup {
+            return Err(StakeError::MergeMismatch.into());
+        }
+
+        let state = match (stake, source_stake) {
+            // undelegated lamports join the destination as unstaked lamports
+            (None, None) => StakeState::Initialized(meta),
+            (Some(stake), None) => StakeState::Stake(meta, stake),
+            (Some(mut stake), Some(source_stake)) => {
+                if !stake.merge(&source_stake, clock.epoch, Some(stake_history)) {
+                    return Err(StakeError::MergeMismatch.into());
+                }
+                StakeState::Stake(meta, stake)
+            }
+            (None, Some(_)) => return Err(StakeError::MergeMismatch.into()),
+        };
+
+        self.set_state(&state)?;
+        source.set_state(&StakeState::Uninitialized)?;
+
+        self.account.lamports += source.account.lamports;
+        source.account.lamports = 0;
+        Ok(())
+    }
+
     fn withdraw(
         &mut self,
         lamports: u64,
@@ -737,6 +820,35 @@ impl<'a> StakeAccount for KeyedAccount<'a> {
         to.account.lamports += lamports;
         Ok(())
     }
+
+    fn set_lockup(
+        &mut self,
+        lockup: &Lockup,
+        clock: &sysvar::clock::Clock,
+        signers: &HashSet<Pubkey>,
+    ) -> Result<(), InstructionError> {
+        let (mut meta, stake) = match self.state()? {
+            StakeState::Stake(meta, stake) => (meta, Some(stake)),
+            StakeState::Initialized(meta) => (meta, None),
+            _ => return Err(InstructionError::InvalidAccountData),
+        };
+
+        // while the lockup is in force only the custodian may change it,
+        //  afterwards it's up to the withdrawer
+        if meta.lockup.slot > clock.slot {
+            if !signers.contains(&meta.lockup.custodian) {
+                return Err(InstructionError::MissingRequiredSignature);
+            }
+        } else {
+            meta.authorized.check(signers, StakeAuthorize::Withdrawer)?;
+        }
+        meta.lockup = *lockup;
+
+        match stake {
+            Some(stake) => self.set_state(&StakeState::Stake(meta, stake)),
+            None => self.set_state(&StakeState::Initialized(meta)),
+        }
+    }
 }
 
 // utility function, used by runtime::Stakes, tests
@@ -1728,6 +1840,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_set_lockup() {
+        let stake_pubkey = Pubkey::new_rand();
+        let custodian = Pubkey::new_rand();
+        let mut stake_account = Account::new_data_with_space(
+            100,
+            &StakeState::Initialized(Meta {
+                lockup: Lockup { slot: 1, custodian },
+                ..Meta::auto(&stake_pubkey)
+            }),
+            std::mem::size_of::<StakeState>(),
+            &id(),
+        )
+        .expect("stake_account");
+        let mut stake_keyed_account = KeyedAccount::new(&stake_pubkey, true, &mut stake_account);
+
+        let mut clock = sysvar::clock::Clock::default();
+        let new_lockup = Lockup {
+            slot: 10,
+            custodian: Pubkey::new_rand(),
+        };
+
+        // lockup is still in force, the withdrawer can't change it
+        let withdrawer_signers = vec![stake_pubkey].into_iter().collect();
+        assert_eq!(
+            stake_keyed_account.set_lockup(&new_lockup, &clock, &withdrawer_signers),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+
+        // ...but the custodian can
+        let custodian_signers = vec![custodian].into_iter().collect();
+        assert_eq!(
+            stake_keyed_account.set_lockup(&new_lockup, &clock, &custodian_signers),
+            Ok(())
+        );
+        assert_eq!(
+            StakeState::from(&stake_keyed_account.account)
+                .and_then(|state| match state {
+                    StakeState::Initialized(meta) => Some(meta.lockup),
+                    _ => None,
+                })
+                .unwrap(),
+            new_lockup
+        );
+
+        // once the lockup has expired it's the withdrawer's to change
+        clock.slot = 10;
+        assert_eq!(
+            stake_keyed_account.set_lockup(&Lockup::default(), &clock, &custodian_signers),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            stake_keyed_account.set_lockup(&Lockup::default(), &clock, &withdrawer_signers),
+            Ok(())
+        );
+    }
+
     #[test]
     fn test_stake_state_calculate_rewards() {
         let mut vote_state = VoteState::default();
@@ -2475,4 +2644,229 @@ mod tests {
             Ok(())
         );
     }
+
+    #[test]
+    fn test_merge() {
+        let authority_pubkey = Pubkey::new_rand();
+        let signers = vec![authority_pubkey].into_iter().collect();
+        let stake_pubkey = Pubkey::new_rand();
+        let source_stake_pubkey = Pubkey::new_rand();
+        let stake_lamports = 42;
+        let meta = Meta::auto(&authority_pubkey);
+
+        let clock = sysvar::clock::Clock {
+            epoch: 1,
+            ..sysvar::clock::Clock::default()
+        };
+        let stake_history = StakeHistory::default();
+
+        for (state, source_state, expected) in &[
+            (
+                StakeState::Initialized(meta),
+                StakeState::Initialized(meta),
+                StakeState::Initialized(meta),
+            ),
+            (
+                StakeState::Stake(
+                    meta,
+                    Stake {
+                        stake: stake_lamports,
+                        ..Stake::default()
+                    },
+                ),
+                StakeState::Initialized(meta),
+                StakeState::Stake(
+                    meta,
+                    Stake {
+                        stake: stake_lamports,
+                        ..Stake::default()
+                    },
+                ),
+            ),
+            (
+                StakeState::Stake(
+                    meta,
+                    Stake {
+                        stake: stake_lamports,
+                        ..Stake::default()
+                    },
+                ),
+                StakeState::Stake(
+                    meta,
+                    Stake {
+                        stake: stake_lamports,
+                        ..Stake::default()
+                    },
+                ),
+                StakeState::Stake(
+                    meta,
+                    Stake {
+                        stake: stake_lamports * 2,
+                        ..Stake::default()
+                    },
+                ),
+            ),
+        ] {
+            let mut stake_account = Account::new_data_with_space(
+                stake_lamports,
+                state,
+                std::mem::size_of::<StakeState>(),
+                &id(),
+            )
+            .expect("stake_account");
+            let mut stake_keyed_account =
+                KeyedAccount::new(&stake_pubkey, false, &mut stake_account);
+
+            let mut source_stake_account = Account::new_data_with_space(
+                stake_lamports,
+                source_state,
+                std::mem::size_of::<StakeState>(),
+                &id(),
+            )
+            .expect("source_stake_account");
+            let mut source_stake_keyed_account =
+                KeyedAccount::new(&source_stake_pubkey, false, &mut source_stake_account);
+
+            // unsigned fails
+            assert_eq!(
+                stake_keyed_account.merge(
+                    &mut source_stake_keyed_account,
+                    &clock,
+                    &stake_history,
+                    &HashSet::new()
+                ),
+                Err(InstructionError::MissingRequiredSignature)
+            );
+
+            assert_eq!(
+                stake_keyed_account.merge(
+                    &mut source_stake_keyed_account,
+                    &clock,
+                    &stake_history,
+                    &signers
+                ),
+                Ok(())
+            );
+            // no lamport leakage
+            assert_eq!(stake_keyed_account.account.lamports, stake_lamports * 2);
+            assert_eq!(source_stake_keyed_account.account.lamports, 0);
+            assert_eq!(stake_keyed_account.state(), Ok(*expected));
+            assert_eq!(
+                source_stake_keyed_account.state(),
+                Ok(StakeState::Uninitialized)
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_mismatch() {
+        let authority_pubkey = Pubkey::new_rand();
+        let signers = vec![authority_pubkey].into_iter().collect();
+        let stake_pubkey = Pubkey::new_rand();
+        let source_stake_pubkey = Pubkey::new_rand();
+        let stake_lamports = 42;
+        let meta = Meta::auto(&authority_pubkey);
+        let stake = Stake {
+            stake: stake_lamports,
+            ..Stake::default()
+        };
+
+        let clock = sysvar::clock::Clock {
+            epoch: 1,
+            ..sysvar::clock::Clock::default()
+        };
+        let stake_history = StakeHistory::default();
+
+        for (state, source_state) in &[
+            // delegated stake can't be merged into an undelegated one
+            (
+                StakeState::Initialized(meta),
+                StakeState::Stake(meta, stake),
+            ),
+            // different authorities
+            (
+                StakeState::Initialized(meta),
+                StakeState::Initialized(Meta {
+                    authorized: Authorized::auto(&Pubkey::new_rand()),
+                    ..meta
+                }),
+            ),
+            // different lockups
+            (
+                StakeState::Initialized(meta),
+                StakeState::Initialized(Meta {
+                    lockup: Lockup {
+                        slot: 1,
+                        custodian: Pubkey::default(),
+                    },
+                    ..meta
+                }),
+            ),
+            // different voters
+            (
+                StakeState::Stake(meta, stake),
+                StakeState::Stake(
+                    meta,
+                    Stake {
+                        voter_pubkey: Pubkey::new_rand(),
+                        ..stake
+                    },
+                ),
+            ),
+            // still activating
+            (
+                StakeState::Stake(meta, stake),
+                StakeState::Stake(
+                    meta,
+                    Stake {
+                        activation_epoch: clock.epoch,
+                        ..stake
+                    },
+                ),
+            ),
+            // deactivating
+            (
+                StakeState::Stake(meta, stake),
+                StakeState::Stake(
+                    meta,
+                    Stake {
+                        deactivation_epoch: clock.epoch,
+                        ..stake
+                    },
+                ),
+            ),
+        ] {
+            let mut stake_account = Account::new_data_with_space(
+                stake_lamports,
+                state,
+                std::mem::size_of::<StakeState>(),
+                &id(),
+            )
+            .expect("stake_account");
+            let mut stake_keyed_account =
+                KeyedAccount::new(&stake_pubkey, false, &mut stake_account);
+
+            let mut source_stake_account = Account::new_data_with_space(
+                stake_lamports,
+                source_state,
+                std::mem::size_of::<StakeState>(),
+                &id(),
+            )
+            .expect("source_stake_account");
+            let mut source_stake_keyed_account =
+                KeyedAccount::new(&source_stake_pubkey, false, &mut source_stake_account);
+
+            assert_eq!(
+                stake_keyed_account.merge(
+                    &mut source_stake_keyed_account,
+                    &clock,
+                    &stake_history,
+                    &signers
+                ),
+                Err(StakeError::MergeMismatch.into())
+            );
+            assert_eq!(stake_keyed_account.account.lamports, stake_lamports);
+            assert_eq!(source_stake_keyed_account.account.lamports, stake_lamports);
+        }
+    }
 }
@@ -5,5 +5,8 @@ macro_rules! solana_stake_program {
     };
 }
 
+/// Version of this program, recorded in genesis manifests
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 use solana_stake_api::stake_instruction::process_instruction;
 solana_sdk::solana_entrypoint!(process_instruction);
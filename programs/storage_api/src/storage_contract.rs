@@ -5,11 +5,12 @@ use serde_derive::{Deserialize, Serialize};
 use solana_sdk::{
     account::{Account, KeyedAccount},
     account_utils::State,
-    clock::Epoch,
+    clock::{Epoch, Slot},
     hash::Hash,
     instruction::InstructionError,
     pubkey::Pubkey,
     signature::Signature,
+    slot_hashes::SlotHashes,
     sysvar,
 };
 use std::collections::BTreeMap;
@@ -47,6 +48,7 @@ pub enum StorageError {
     RewardPoolDepleted,
     InvalidOwner,
     ProofLimitReached,
+    InvalidTurnSlot,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -104,6 +106,21 @@ pub enum StorageContract {
     },
 
     RewardsPool,
+
+    /// `ValidatorStorage` of a validator committing its storage turns, a validator account
+    /// moves to it with its first `CommitStorageTurn` and back with an
+    /// `AdvertiseStorageRecentBlockhash`
+    CommittedValidatorStorage {
+        owner: Pubkey,
+        // Most recently committed segment
+        segment: u64,
+        // Bank hash of the most recently committed storage turn
+        hash: Hash,
+        // Slot of the storage turn `hash` was committed for
+        turn_slot: Slot,
+        lockout_validations: BTreeMap<u64, BTreeMap<Pubkey, Vec<ProofStatus>>>,
+        credits: Credits,
+    },
 }
 
 // utility function, used by Bank, tests, genesis
@@ -244,34 +261,95 @@ impl<'a> StorageAccount<'a> {
         segment: u64,
         clock: sysvar::clock::Clock,
     ) -> Result<(), InstructionError> {
-        let mut storage_contract = &mut self.account.state()?;
-        if let StorageContract::ValidatorStorage {
-            segment: state_segment,
-            hash: state_hash,
-            lockout_validations,
-            credits,
-            ..
-        } = &mut storage_contract
-        {
-            debug!("advertise new segment: {} orig: {}", segment, clock.segment);
-            if segment < *state_segment || segment > clock.segment {
-                return Err(InstructionError::CustomError(
-                    StorageError::InvalidSegment as u32,
-                ));
-            }
+        self.advertise(hash, segment, None, clock)
+    }
 
-            *state_segment = segment;
-            *state_hash = hash;
+    /// Advertise `hash` as the bank hash of the storage turn at `turn_slot`
+    pub fn commit_storage_turn(
+        &mut self,
+        hash: Hash,
+        segment: u64,
+        turn_slot: Slot,
+        clock: sysvar::clock::Clock,
+        slot_hashes: &SlotHashes,
+    ) -> Result<(), InstructionError> {
+        let state_turn_slot = match self.account.state()? {
+            StorageContract::ValidatorStorage { .. } => 0,
+            StorageContract::CommittedValidatorStorage { turn_slot, .. } => turn_slot,
+            _ => return Err(InstructionError::InvalidArgument),
+        };
+        // turns are committed in order and never ahead of the cluster
+        if turn_slot < state_turn_slot || turn_slot > clock.slot {
+            return Err(InstructionError::CustomError(
+                StorageError::InvalidTurnSlot as u32,
+            ));
+        }
+        // the turn's hash is the bank hash of its slot
+        if slot_hashes.get(&turn_slot) != Some(&hash) {
+            return Err(InstructionError::CustomError(
+                StorageError::InvalidBlockhash as u32,
+            ));
+        }
+        self.advertise(hash, segment, Some(turn_slot), clock)
+    }
 
-            // storage epoch updated, move the lockout_validations to credits
-            let (_num_valid, total_validations) = count_valid_proofs(&lockout_validations);
-            lockout_validations.clear();
-            credits.update_epoch(clock.epoch);
-            credits.current_epoch += total_validations;
-            self.account.set_state(storage_contract)
-        } else {
-            Err(InstructionError::InvalidArgument)
+    // Record `hash` as the validator's storage blockhash for `segment`, along with the slot of
+    // its turn if it was committed
+    fn advertise(
+        &mut self,
+        hash: Hash,
+        segment: u64,
+        turn_slot: Option<Slot>,
+        clock: sysvar::clock::Clock,
+    ) -> Result<(), InstructionError> {
+        let (owner, state_segment, mut lockout_validations, mut credits) =
+            match self.account.state()? {
+                StorageContract::ValidatorStorage {
+                    owner,
+                    segment,
+                    lockout_validations,
+                    credits,
+                    ..
+                }
+                | StorageContract::CommittedValidatorStorage {
+                    owner,
+                    segment,
+                    lockout_validations,
+                    credits,
+                    ..
+                } => (owner, segment, lockout_validations, credits),
+                _ => return Err(InstructionError::InvalidArgument),
+            };
+        debug!("advertise new segment: {} orig: {}", segment, clock.segment);
+        if segment < state_segment || segment > clock.segment {
+            return Err(InstructionError::CustomError(
+                StorageError::InvalidSegment as u32,
+            ));
         }
+
+        // storage epoch updated, move the lockout_validations to credits
+        let (_num_valid, total_validations) = count_valid_proofs(&lockout_validations);
+        lockout_validations.clear();
+        credits.update_epoch(clock.epoch);
+        credits.current_epoch += total_validations;
+        let storage_contract = match turn_slot {
+            None => StorageContract::ValidatorStorage {
+                owner,
+                segment,
+                hash,
+                lockout_validations,
+                credits,
+            },
+            Some(turn_slot) => StorageContract::CommittedValidatorStorage {
+                owner,
+                segment,
+                hash,
+                turn_slot,
+                lockout_validations,
+                credits,
+            },
+        };
+        self.account.set_state(&storage_contract)
     }
 
     pub fn proof_validation(
@@ -287,6 +365,11 @@ impl<'a> StorageAccount<'a> {
             segment: state_segment,
             lockout_validations,
             ..
+        }
+        | StorageContract::CommittedValidatorStorage {
+            segment: state_segment,
+            lockout_validations,
+            ..
         } = &mut storage_contract
         {
             if segment_index > *state_segment {
@@ -377,6 +460,11 @@ impl<'a> StorageAccount<'a> {
             owner: account_owner,
             credits,
             ..
+        }
+        | StorageContract::CommittedValidatorStorage {
+            owner: account_owner,
+            credits,
+            ..
         } = &mut storage_contract
         {
             if owner.id != *account_owner {
@@ -643,4 +731,77 @@ mod tests {
         // check that the owner's balance increases
         assert_eq!(owner.account.lamports, 101);
     }
+
+    #[test]
+    fn test_commit_storage_turn() {
+        let mut account = Account::new(1, STORAGE_ACCOUNT_SPACE as usize, &id());
+        let mut storage_account = StorageAccount::new(Pubkey::default(), &mut account);
+        storage_account
+            .initialize_storage(Pubkey::default(), StorageAccountType::Validator)
+            .unwrap();
+        let clock = || sysvar::clock::Clock {
+            slot: 20,
+            segment: 2,
+            ..sysvar::clock::Clock::default()
+        };
+        let hash = Hash::new(&[1; 32]);
+        let slot_hashes = SlotHashes::new(&[(8, Hash::new(&[2; 32])), (16, hash), (21, hash)]);
+
+        // can't commit a turn the cluster hasn't reached
+        assert_eq!(
+            storage_account.commit_storage_turn(hash, 2, 21, clock(), &slot_hashes),
+            Err(InstructionError::CustomError(
+                StorageError::InvalidTurnSlot as u32
+            ))
+        );
+        // or with a hash the turn's bank doesn't have
+        assert_eq!(
+            storage_account.commit_storage_turn(Hash::new(&[3; 32]), 2, 16, clock(), &slot_hashes),
+            Err(InstructionError::CustomError(
+                StorageError::InvalidBlockhash as u32
+            ))
+        );
+        assert_eq!(
+            storage_account.commit_storage_turn(hash, 2, 12, clock(), &slot_hashes),
+            Err(InstructionError::CustomError(
+                StorageError::InvalidBlockhash as u32
+            ))
+        );
+        assert_eq!(
+            storage_account.commit_storage_turn(hash, 2, 16, clock(), &slot_hashes),
+            Ok(())
+        );
+        // or go back to an earlier one
+        assert_eq!(
+            storage_account.commit_storage_turn(Hash::new(&[2; 32]), 2, 8, clock(), &slot_hashes),
+            Err(InstructionError::CustomError(
+                StorageError::InvalidTurnSlot as u32
+            ))
+        );
+        if let Ok(StorageContract::CommittedValidatorStorage {
+            hash: state_hash,
+            turn_slot,
+            ..
+        }) = storage_account.account.state()
+        {
+            assert_eq!(state_hash, hash);
+            assert_eq!(turn_slot, 16);
+        } else {
+            panic!("Wrong contract type");
+        }
+
+        // a plain advertisement moves the account back to `ValidatorStorage`
+        assert_eq!(
+            storage_account.advertise_storage_recent_blockhash(hash, 2, clock()),
+            Ok(())
+        );
+        match storage_account.account.state() {
+            Ok(StorageContract::ValidatorStorage {
+                hash: state_hash, ..
+            }) => {
+                assert_eq!(state_hash, hash)
+            }
+            _ => panic!("Wrong contract type"),
+        }
+    }
 }
@@ -6,7 +6,7 @@ use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
 use solana_sdk::system_instruction;
-use solana_sdk::sysvar::{clock, rewards};
+use solana_sdk::sysvar::{clock, rewards, slot_hashes};
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
 pub enum StorageAccountType {
@@ -50,6 +50,20 @@ pub enum StorageInstruction {
         /// A Vec of proof masks per keyed archiver account loaded by the instruction
         proofs: Vec<Vec<ProofStatus>>,
     },
+    /// Commit the blockhash of a storage turn, archivers sample their segments with the
+    /// blockhash most validators committed
+    ///
+    /// Expects 3 Accounts:
+    ///    0 - Validator storage account, must sign
+    ///    1 - Clock Syscall to check the segment and slot against
+    ///    2 - SlotHashes Syscall to check `hash` against
+    CommitStorageTurn {
+        /// The bank hash of `turn_slot`
+        hash: Hash,
+        segment: u64,
+        /// The slot of the storage turn `hash` belongs to
+        turn_slot: u64,
+    },
 }
 
 fn get_ratios() -> (u64, u64) {
@@ -111,6 +125,50 @@ pub fn create_storage_account(
     ]
 }
 
+/// Seed of the storage account an archiver derives for the segment at `segment_index`
+pub fn segment_storage_seed(segment_index: u64) -> String {
+    format!("segment-{}", segment_index)
+}
+
+/// Address of the storage account `archiver_pubkey` uses for the segment at `segment_index`,
+/// derived so an archiver storing many segments doesn't need a keypair per storage account
+pub fn segment_storage_pubkey(archiver_pubkey: &Pubkey, segment_index: u64) -> Pubkey {
+    Pubkey::create_with_seed(archiver_pubkey, &segment_storage_seed(segment_index), &id())
+        .expect("segment seeds are shorter than MAX_SEED_LEN")
+}
+
+/// Create and initialize the storage account of `archiver_pubkey` for the segment at
+/// `segment_index`, owned by `archiver_pubkey`, returns its address along with the
+/// instructions. `archiver_pubkey` must sign, as the base of the derived address.
+pub fn create_segment_storage_account(
+    from_pubkey: &Pubkey,
+    archiver_pubkey: &Pubkey,
+    segment_index: u64,
+    lamports: u64,
+) -> (Pubkey, Vec<Instruction>) {
+    let storage_pubkey = segment_storage_pubkey(archiver_pubkey, segment_index);
+    let instructions = vec![
+        system_instruction::create_account_with_seed(
+            from_pubkey,
+            &storage_pubkey,
+            archiver_pubkey,
+            &segment_storage_seed(segment_index),
+            lamports,
+            STORAGE_ACCOUNT_SPACE,
+            &id(),
+        ),
+        Instruction::new(
+            id(),
+            &StorageInstruction::InitializeStorage {
+                owner: *archiver_pubkey,
+                account_type: StorageAccountType::Archiver,
+            },
+            vec![AccountMeta::new(storage_pubkey, false)],
+        ),
+    ];
+    (storage_pubkey, instructions)
+}
+
 pub fn mining_proof(
     storage_pubkey: &Pubkey,
     sha_state: Hash,
@@ -126,7 +184,7 @@ pub fn mining_proof(
     };
     let account_metas = vec![
         AccountMeta::new(*storage_pubkey, true),
-        AccountMeta::new(clock::id(), false),
+        AccountMeta::new_readonly(clock::id(), false),
     ];
     Instruction::new(id(), &storage_instruction, account_metas)
 }
@@ -142,7 +200,26 @@ pub fn advertise_recent_blockhash(
     };
     let account_metas = vec![
         AccountMeta::new(*storage_pubkey, true),
-        AccountMeta::new(clock::id(), false),
+        AccountMeta::new_readonly(clock::id(), false),
+    ];
+    Instruction::new(id(), &storage_instruction, account_metas)
+}
+
+pub fn commit_storage_turn(
+    storage_pubkey: &Pubkey,
+    storage_hash: Hash,
+    segment: u64,
+    turn_slot: u64,
+) -> Instruction {
+    let storage_instruction = StorageInstruction::CommitStorageTurn {
+        hash: storage_hash,
+        segment,
+        turn_slot,
+    };
+    let account_metas = vec![
+        AccountMeta::new(*storage_pubkey, true),
+        AccountMeta::new_readonly(clock::id(), false),
+        AccountMeta::new_readonly(slot_hashes::id(), false),
     ];
     Instruction::new(id(), &storage_instruction, account_metas)
 }
@@ -154,7 +231,7 @@ pub fn proof_validation(
 ) -> Instruction {
     let mut account_metas = vec![
         AccountMeta::new(*storage_pubkey, true),
-        AccountMeta::new(clock::id(), false),
+        AccountMeta::new_readonly(clock::id(), false),
     ];
     let mut proofs = vec![];
     checked_proofs.into_iter().for_each(|(id, p)| {
@@ -169,8 +246,8 @@ pub fn claim_reward(owner_pubkey: &Pubkey, storage_pubkey: &Pubkey) -> Instructi
     let storage_instruction = StorageInstruction::ClaimStorageReward;
     let account_metas = vec![
         AccountMeta::new(*storage_pubkey, false),
-        AccountMeta::new(clock::id(), false),
-        AccountMeta::new(rewards::id(), false),
+        AccountMeta::new_readonly(clock::id(), false),
+        AccountMeta::new_readonly(rewards::id(), false),
         AccountMeta::new(rewards_pools::random_id(), false),
         AccountMeta::new(*owner_pubkey, false),
     ];
@@ -186,4 +263,25 @@ mod tests {
         // check that if there's 50 proof per account, only 1 account can fit in a single tx
         assert_eq!(validation_account_limit(50), 1);
     }
+
+    #[test]
+    fn test_segment_storage_pubkey() {
+        let archiver_pubkey = Pubkey::new_rand();
+        let storage_pubkey = segment_storage_pubkey(&archiver_pubkey, 3);
+        assert_eq!(storage_pubkey, segment_storage_pubkey(&archiver_pubkey, 3));
+        assert_ne!(storage_pubkey, segment_storage_pubkey(&archiver_pubkey, 4));
+        assert_ne!(
+            storage_pubkey,
+            segment_storage_pubkey(&Pubkey::new_rand(), 3)
+        );
+        // The longest segment index still makes a valid seed
+        segment_storage_pubkey(&archiver_pubkey, std::u64::MAX);
+
+        let from_pubkey = Pubkey::new_rand();
+        let (pubkey, instructions) =
+            create_segment_storage_account(&from_pubkey, &archiver_pubkey, 3, 1);
+        assert_eq!(pubkey, storage_pubkey);
+        assert_eq!(instructions[0].accounts[1].pubkey, storage_pubkey);
+        assert_eq!(instructions[1].accounts[0].pubkey, storage_pubkey);
+    }
 }
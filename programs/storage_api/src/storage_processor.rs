@@ -7,7 +7,7 @@ use solana_sdk::{
     instruction::InstructionError,
     instruction_processor_utils::limited_deserialize,
     pubkey::Pubkey,
-    sysvar::{clock::Clock, rewards::Rewards, Sysvar},
+    sysvar::{clock::Clock, rewards::Rewards, slot_hashes::SlotHashes, Sysvar},
 };
 
 pub fn process_instruction(
@@ -92,5 +92,18 @@ pub fn process_instruction(
                 .collect();
             storage_account.proof_validation(&me_id, clock, segment, proofs, &mut rest)
         }
+        StorageInstruction::CommitStorageTurn {
+            hash,
+            segment,
+            turn_slot,
+        } => {
+            if me_unsigned || rest.len() != 2 {
+                // This instruction must be signed by `me`
+                return Err(InstructionError::InvalidArgument);
+            }
+            let clock = Clock::from_keyed_account(&rest[0])?;
+            let slot_hashes = SlotHashes::from_keyed_account(&rest[1])?;
+            storage_account.commit_storage_turn(hash, segment, turn_slot, clock, &slot_hashes)
+        }
     }
 }
@@ -8,5 +8,8 @@ macro_rules! solana_storage_program {
     };
 }
 
+/// Version of this program, recorded in genesis manifests
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 use solana_storage_api::storage_processor::process_instruction;
 solana_sdk::solana_entrypoint!(process_instruction);
@@ -11,7 +11,7 @@ use solana_sdk::{
     account_utils::State,
     client::SyncClient,
     clock::{get_segment_from_slot, DEFAULT_SLOTS_PER_SEGMENT, DEFAULT_TICKS_PER_SLOT},
-    hash::{hash, Hash},
+    hash::Hash,
     instruction::{Instruction, InstructionError},
     message::Message,
     pubkey::Pubkey,
@@ -22,6 +22,7 @@ use solana_sdk::{
         rewards::{self, Rewards},
         Sysvar,
     },
+    transaction::Transaction,
 };
 use solana_storage_api::{
     id,
@@ -35,6 +36,12 @@ use std::sync::Arc;
 
 const TICKS_IN_SEGMENT: u64 = DEFAULT_SLOTS_PER_SEGMENT * DEFAULT_TICKS_PER_SLOT;
 
+// Commit the storage turn at the parent of `bank`, the latest slot its SlotHashes knows
+fn commit_parent_turn(storage_pubkey: &Pubkey, bank: &Bank, segment: u64) -> Instruction {
+    let parent = bank.parent().unwrap();
+    storage_instruction::commit_storage_turn(storage_pubkey, parent.hash(), segment, parent.slot())
+}
+
 fn test_instruction(
     ix: &Instruction,
     program_accounts: &mut [Account],
@@ -113,6 +120,48 @@ fn test_account_owner() {
     }
 }
 
+#[test]
+fn test_segment_storage_account() {
+    let archiver_keypair = Keypair::new();
+    let archiver_pubkey = archiver_keypair.pubkey();
+
+    let GenesisConfigInfo {
+        genesis_config,
+        mint_keypair,
+        ..
+    } = create_genesis_config(1000);
+    let mut bank = Bank::new(&genesis_config);
+    bank.add_instruction_processor(id(), process_instruction);
+    let bank = Arc::new(bank);
+    let bank_client = BankClient::new_shared(&bank);
+
+    let (storage_pubkey, instructions) = storage_instruction::create_segment_storage_account(
+        &mint_keypair.pubkey(),
+        &archiver_pubkey,
+        2,
+        1,
+    );
+    assert_eq!(
+        storage_pubkey,
+        storage_instruction::segment_storage_pubkey(&archiver_pubkey, 2)
+    );
+    bank_client
+        .send_message(
+            &[&mint_keypair, &archiver_keypair],
+            Message::new(instructions),
+        )
+        .expect("failed to create account");
+    let account = bank
+        .get_account(&storage_pubkey)
+        .expect("account not found");
+    let storage_contract = account.state().expect("couldn't unpack account data");
+    if let StorageContract::ArchiverStorage { owner, .. } = storage_contract {
+        assert_eq!(owner, archiver_pubkey);
+    } else {
+        assert!(false, "wrong account type found")
+    }
+}
+
 #[test]
 fn test_proof_bounds() {
     let account_owner = Pubkey::new_rand();
@@ -307,11 +356,7 @@ fn test_validate_mining() {
 
     // advertise for storage segment 1
     let message = Message::new_with_payer(
-        vec![storage_instruction::advertise_recent_blockhash(
-            &validator_storage_id,
-            Hash::default(),
-            1,
-        )],
+        vec![commit_parent_turn(&validator_storage_id, &bank, 1)],
         Some(&mint_pubkey),
     );
     assert_matches!(
@@ -341,14 +386,6 @@ fn test_validate_mining() {
                 0,
             ));
     }
-    let message = Message::new_with_payer(
-        vec![storage_instruction::advertise_recent_blockhash(
-            &validator_storage_id,
-            Hash::default(),
-            2,
-        )],
-        Some(&mint_pubkey),
-    );
 
     // move banks into the next segment
     let proof_segment = get_segment_from_slot(bank.slot(), bank.slots_per_segment());
@@ -359,6 +396,11 @@ fn test_validate_mining() {
     ));
     let bank_client = BankClient::new_shared(&bank);
 
+    let message = Message::new_with_payer(
+        vec![commit_parent_turn(&validator_storage_id, &bank, 2)],
+        Some(&mint_pubkey),
+    );
+
     assert_matches!(
         bank_client.send_message(&[&mint_keypair, &validator_storage_keypair], message),
         Ok(_)
@@ -378,15 +420,6 @@ fn test_validate_mining() {
         Ok(_)
     );
 
-    let message = Message::new_with_payer(
-        vec![storage_instruction::advertise_recent_blockhash(
-            &validator_storage_id,
-            Hash::default(),
-            3,
-        )],
-        Some(&mint_pubkey),
-    );
-
     // move banks into the next segment
     let bank = Arc::new(Bank::new_from_parent(
         &bank,
@@ -395,6 +428,11 @@ fn test_validate_mining() {
     ));
     let bank_client = BankClient::new_shared(&bank);
 
+    let message = Message::new_with_payer(
+        vec![commit_parent_turn(&validator_storage_id, &bank, 3)],
+        Some(&mint_pubkey),
+    );
+
     assert_matches!(
         bank_client.send_message(&[&mint_keypair, &validator_storage_keypair], message),
         Ok(_)
@@ -508,7 +546,8 @@ fn get_storage_segment<C: SyncClient>(client: &C, account: &Pubkey) -> u64 {
             let contract = deserialize(&storage_system_account_data);
             if let Ok(contract) = contract {
                 match contract {
-                    StorageContract::ValidatorStorage { segment, .. } => {
+                    StorageContract::ValidatorStorage { segment, .. }
+                    | StorageContract::CommittedValidatorStorage { segment, .. } => {
                         return segment;
                     }
                     _ => info!("error in reading segment"),
@@ -552,7 +591,8 @@ fn get_storage_blockhash<C: SyncClient>(client: &C, account: &Pubkey) -> Hash {
         let contract = deserialize(&storage_system_account_data);
         if let Ok(contract) = contract {
             match contract {
-                StorageContract::ValidatorStorage { hash, .. } => {
+                StorageContract::ValidatorStorage { hash, .. }
+                | StorageContract::CommittedValidatorStorage { hash, .. } => {
                     return hash;
                 }
                 _ => (),
@@ -586,12 +626,10 @@ fn test_bank_storage() {
         &Pubkey::new_rand(),
         DEFAULT_SLOTS_PER_SEGMENT * 2,
     );
+    let advertise_ix = commit_parent_turn(&validator_pubkey, &bank, 1);
+    let storage_blockhash = bank.parent().unwrap().hash();
     let bank_client = BankClient::new(bank);
 
-    let x = 42;
-    let x2 = x * 2;
-    let storage_blockhash = hash(&[x2]);
-
     let message = Message::new(storage_instruction::create_storage_account(
         &mint_pubkey,
         &Pubkey::default(),
@@ -614,14 +652,7 @@ fn test_bank_storage() {
         .send_message(&[&mint_keypair, &validator_keypair], message)
         .unwrap();
 
-    let message = Message::new_with_payer(
-        vec![storage_instruction::advertise_recent_blockhash(
-            &validator_pubkey,
-            storage_blockhash,
-            1,
-        )],
-        Some(&mint_pubkey),
-    );
+    let message = Message::new_with_payer(vec![advertise_ix], Some(&mint_pubkey));
 
     assert_matches!(
         bank_client.send_message(&[&mint_keypair, &validator_keypair], message),
@@ -650,3 +681,84 @@ fn test_bank_storage() {
         storage_blockhash
     );
 }
+
+#[test]
+fn test_mining_proofs_share_clock_lock() {
+    let GenesisConfigInfo {
+        mut genesis_config,
+        mint_keypair,
+        ..
+    } = create_genesis_config(1000);
+    genesis_config
+        .native_instruction_processors
+        .push(solana_storage_program::solana_storage_program!());
+    let bank = Bank::new(&genesis_config);
+    let bank = Arc::new(Bank::new_from_parent(
+        &Arc::new(bank),
+        &Pubkey::new_rand(),
+        DEFAULT_SLOTS_PER_SEGMENT * 2,
+    ));
+    let bank_client = BankClient::new_shared(&bank);
+
+    let num_archivers = 8;
+    let archiver_keypairs: Vec<_> = (0..num_archivers).map(|_| Keypair::new()).collect();
+    let payer_keypairs: Vec<_> = (0..num_archivers).map(|_| Keypair::new()).collect();
+    init_storage_accounts(
+        &Pubkey::new_rand(),
+        &bank_client,
+        &mint_keypair,
+        &[],
+        &archiver_keypairs.iter().collect::<Vec<_>>(),
+        1,
+    );
+    for payer_keypair in &payer_keypairs {
+        bank.transfer(10, &mint_keypair, &payer_keypair.pubkey())
+            .unwrap();
+    }
+
+    let blockhash = bank.last_blockhash();
+    let mining_proof = |archiver_keypair: &Keypair, payer_keypair: &Keypair, writable: bool| {
+        let mut instruction = storage_instruction::mining_proof(
+            &archiver_keypair.pubkey(),
+            Hash::new(Pubkey::new_rand().as_ref()),
+            0,
+            Signature::default(),
+            blockhash,
+        );
+        assert_eq!(instruction.accounts[1].pubkey, clock::id());
+        instruction.accounts[1].is_writable = writable;
+        let message = Message::new_with_payer(vec![instruction], Some(&payer_keypair.pubkey()));
+        Transaction::new(&[payer_keypair, archiver_keypair], message, blockhash)
+    };
+
+    // a writable clock serializes every proof
+    let txs: Vec<_> = archiver_keypairs
+        .iter()
+        .zip(&payer_keypairs)
+        .map(|(archiver_keypair, payer_keypair)| {
+            mining_proof(archiver_keypair, payer_keypair, true)
+        })
+        .collect();
+    assert_eq!(Bank::conflict_free_batch_ranges(&txs).len(), num_archivers);
+
+    // the proofs only read the clock, so they're all locked and executed as one batch
+    let txs: Vec<_> = archiver_keypairs
+        .iter()
+        .zip(&payer_keypairs)
+        .map(|(archiver_keypair, payer_keypair)| {
+            mining_proof(archiver_keypair, payer_keypair, false)
+        })
+        .collect();
+    assert_eq!(
+        Bank::conflict_free_batch_ranges(&txs),
+        vec![0..num_archivers]
+    );
+    {
+        let batch = bank.prepare_batch(&txs, None);
+        assert!(batch.lock_results().iter().all(|result| result.is_ok()));
+    }
+    assert!(bank
+        .process_transactions(&txs)
+        .iter()
+        .all(|result| result.is_ok()));
+}
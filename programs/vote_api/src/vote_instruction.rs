@@ -17,6 +17,7 @@ use solana_sdk::{
     system_instruction,
     sysvar::{self, clock::Clock, slot_hashes::SlotHashes, Sysvar},
 };
+use std::iter::Peekable;
 
 /// Reasons the stake might have had an error
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromPrimitive, ToPrimitive)]
@@ -54,20 +55,30 @@ pub enum VoteInstruction {
     InitializeAccount(VoteInit),
 
     /// Authorize a voter to send signed votes or a withdrawer
-    ///  to withdraw
+    ///  to withdraw, cancelling a pending rotation of the same signer
+    ///
+    /// The Clock sysvar may follow the vote account, it is required
+    ///  by accounts with room for rotations
     Authorize(Pubkey, VoteAuthorize),
 
     /// A Vote instruction with recent votes
     Vote(Vote),
 
     /// Withdraw some amount of funds
+    ///
+    /// The Clock sysvar may follow the recipient, it is required
+    ///  by accounts with room for rotations
     Withdraw(u64),
+
+    /// Rotate the authorized voter or withdrawer at the start of the next epoch,
+    ///  keeping a record of the change in the vote account
+    RotateAuthorized(Pubkey, VoteAuthorize),
 }
 
 fn initialize_account(vote_pubkey: &Pubkey, vote_init: &VoteInit) -> Instruction {
     let account_metas = vec![
         AccountMeta::new(*vote_pubkey, false),
-        AccountMeta::new(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
     ];
     Instruction::new(
         id(),
@@ -82,7 +93,7 @@ pub fn create_account(
     vote_init: &VoteInit,
     lamports: u64,
 ) -> Vec<Instruction> {
-    let space = VoteState::size_of() as u64;
+    let space = VoteState::account_size() as u64;
     let create_ix =
         system_instruction::create_account(from_pubkey, vote_pubkey, lamports, space, &id());
     let init_ix = initialize_account(vote_pubkey, vote_init);
@@ -104,6 +115,45 @@ pub fn authorize(
     )
 }
 
+/// `authorize()` passing the Clock, which accounts with room for rotations require
+pub fn authorize_with_clock(
+    vote_pubkey: &Pubkey,
+    authorized_pubkey: &Pubkey, // currently authorized
+    new_authorized_pubkey: &Pubkey,
+    vote_authorize: VoteAuthorize,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*vote_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ]
+    .with_signer(authorized_pubkey);
+
+    Instruction::new(
+        id(),
+        &VoteInstruction::Authorize(*new_authorized_pubkey, vote_authorize),
+        account_metas,
+    )
+}
+
+pub fn rotate_authorized(
+    vote_pubkey: &Pubkey,
+    authorized_pubkey: &Pubkey, // currently authorized
+    new_authorized_pubkey: &Pubkey,
+    vote_authorize: VoteAuthorize,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*vote_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ]
+    .with_signer(authorized_pubkey);
+
+    Instruction::new(
+        id(),
+        &VoteInstruction::RotateAuthorized(*new_authorized_pubkey, vote_authorize),
+        account_metas,
+    )
+}
+
 pub fn vote(vote_pubkey: &Pubkey, authorized_voter_pubkey: &Pubkey, vote: Vote) -> Instruction {
     let account_metas = vec![
         AccountMeta::new(*vote_pubkey, false),
@@ -130,6 +180,38 @@ pub fn withdraw(
     Instruction::new(id(), &VoteInstruction::Withdraw(lamports), account_metas)
 }
 
+/// `withdraw()` passing the Clock, which accounts with room for rotations require
+pub fn withdraw_with_clock(
+    vote_pubkey: &Pubkey,
+    withdrawer_pubkey: &Pubkey,
+    lamports: u64,
+    to_pubkey: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*vote_pubkey, false),
+        AccountMeta::new(*to_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ]
+    .with_signer(withdrawer_pubkey);
+
+    Instruction::new(id(), &VoteInstruction::Withdraw(lamports), account_metas)
+}
+
+// The Clock an Authorize or Withdraw instruction passes for an account with room for rotations
+fn next_optional_clock<'a, 'b: 'a, I>(
+    keyed_accounts: &mut Peekable<I>,
+) -> Result<Option<Clock>, InstructionError>
+where
+    I: Iterator<Item = &'a mut KeyedAccount<'b>>,
+{
+    match keyed_accounts.peek() {
+        Some(keyed_account) if sysvar::clock::check_id(keyed_account.unsigned_key()) => {
+            Clock::from_keyed_account(next_keyed_account(keyed_accounts)?).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
 pub fn process_instruction(
     _program_id: &Pubkey,
     keyed_accounts: &mut [KeyedAccount],
@@ -142,7 +224,7 @@ pub fn process_instruction(
 
     let signers = get_signers(keyed_accounts);
 
-    let keyed_accounts = &mut keyed_accounts.iter_mut();
+    let keyed_accounts = &mut keyed_accounts.iter_mut().peekable();
     let me = &mut next_keyed_account(keyed_accounts)?;
 
     match limited_deserialize(data)? {
@@ -150,9 +232,13 @@ pub fn process_instruction(
             sysvar::rent::verify_rent_exemption(me, next_keyed_account(keyed_accounts)?)?;
             vote_state::initialize_account(me, &vote_init)
         }
-        VoteInstruction::Authorize(voter_pubkey, vote_authorize) => {
-            vote_state::authorize(me, &voter_pubkey, vote_authorize, &signers)
-        }
+        VoteInstruction::Authorize(voter_pubkey, vote_authorize) => vote_state::authorize(
+            me,
+            &voter_pubkey,
+            vote_authorize,
+            next_optional_clock(keyed_accounts)?.as_ref(),
+            &signers,
+        ),
         VoteInstruction::Vote(vote) => {
             datapoint_debug!("vote-native", ("count", 1, i64));
             vote_state::process_vote(
@@ -165,7 +251,22 @@ pub fn process_instruction(
         }
         VoteInstruction::Withdraw(lamports) => {
             let to = next_keyed_account(keyed_accounts)?;
-            vote_state::withdraw(me, lamports, to, &signers)
+            vote_state::withdraw(
+                me,
+                lamports,
+                to,
+                next_optional_clock(keyed_accounts)?.as_ref(),
+                &signers,
+            )
+        }
+        VoteInstruction::RotateAuthorized(authorized_pubkey, vote_authorize) => {
+            vote_state::rotate_authorized(
+                me,
+                &authorized_pubkey,
+                vote_authorize,
+                &Clock::from_keyed_account(next_keyed_account(keyed_accounts)?)?,
+                &signers,
+            )
         }
     }
 }
@@ -245,6 +346,24 @@ mod tests {
             )),
             Err(InstructionError::InvalidAccountData),
         );
+        assert_eq!(
+            process_instruction(&authorize_with_clock(
+                &Pubkey::default(),
+                &Pubkey::default(),
+                &Pubkey::default(),
+                VoteAuthorize::Voter,
+            )),
+            Err(InstructionError::InvalidAccountData),
+        );
+        assert_eq!(
+            process_instruction(&rotate_authorized(
+                &Pubkey::default(),
+                &Pubkey::default(),
+                &Pubkey::default(),
+                VoteAuthorize::Withdrawer,
+            )),
+            Err(InstructionError::InvalidAccountData),
+        );
         assert_eq!(
             process_instruction(&withdraw(
                 &Pubkey::default(),
@@ -254,12 +373,21 @@ mod tests {
             )),
             Err(InstructionError::InvalidAccountData),
         );
+        assert_eq!(
+            process_instruction(&withdraw_with_clock(
+                &Pubkey::default(),
+                &Pubkey::default(),
+                0,
+                &Pubkey::default()
+            )),
+            Err(InstructionError::InvalidAccountData),
+        );
     }
 
     #[test]
     fn test_minimum_balance() {
         let rent = solana_sdk::rent::Rent::default();
-        let minimum_balance = rent.minimum_balance(VoteState::size_of());
+        let minimum_balance = rent.minimum_balance(VoteState::account_size());
         // vote state cheaper than "my $0.02" ;)
         assert!(minimum_balance as f64 / 10f64.powf(9.0) < 0.02)
     }
@@ -25,6 +25,9 @@ pub const INITIAL_LOCKOUT: usize = 2;
 //  smaller numbers makes
 pub const MAX_EPOCH_CREDITS_HISTORY: usize = 64;
 
+// Maximum number of authorized voter and withdrawer rotations to keep around
+pub const MAX_AUTHORIZED_HISTORY: usize = 32;
+
 #[derive(Serialize, Default, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct Vote {
     /// A stack of votes starting with the oldest vote
@@ -82,6 +85,19 @@ pub enum VoteAuthorize {
     Withdrawer,
 }
 
+/// A change of the authorized voter or withdrawer that has taken effect
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AuthorizedRotation {
+    /// which signer was replaced
+    pub vote_authorize: VoteAuthorize,
+    /// the signer before the rotation
+    pub prior: Pubkey,
+    /// the signer after the rotation
+    pub authorized: Pubkey,
+    /// the first epoch `authorized` signed for
+    pub epoch: Epoch,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct VoteState {
     /// the node that votes in this account
@@ -110,6 +126,19 @@ pub struct VoteState {
     epoch_credits: Vec<(Epoch, u64, u64)>,
 }
 
+/// Rotations of the authorized voter and withdrawer, kept in the vote account after the
+///  VoteState's `size_of()` bytes so the VoteState layout of existing accounts doesn't change.
+///  Only accounts created with `VoteState::account_size()` have room for them.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct AuthorizedRotations {
+    /// rotations waiting for their epoch, each tuple is (new signer, first epoch it signs for)
+    pending_authorized_voter: Option<(Pubkey, Epoch)>,
+    pending_authorized_withdrawer: Option<(Pubkey, Epoch)>,
+
+    /// rotations that have taken effect, oldest first
+    authorized_history: Vec<AuthorizedRotation>,
+}
+
 impl VoteState {
     pub fn new(vote_init: &VoteInit) -> Self {
         Self {
@@ -131,6 +160,11 @@ impl VoteState {
         serialized_size(&vote_state).unwrap() as usize
     }
 
+    /// Space for a new vote account, the VoteState followed by its AuthorizedRotations
+    pub fn account_size() -> usize {
+        Self::size_of() + AuthorizedRotations::size_of()
+    }
+
     // utility function, used by Stakes, tests
     pub fn from(account: &Account) -> Option<VoteState> {
         Self::deserialize(&account.data).ok()
@@ -336,30 +370,215 @@ impl VoteState {
     }
 }
 
+impl AuthorizedRotations {
+    pub fn size_of() -> usize {
+        // Upper limit on the size of the rotations, with both pending and a full history
+        let rotations = Self {
+            pending_authorized_voter: Some((Pubkey::default(), std::u64::MAX)),
+            pending_authorized_withdrawer: Some((Pubkey::default(), std::u64::MAX)),
+            authorized_history: vec![
+                AuthorizedRotation {
+                    vote_authorize: VoteAuthorize::Voter,
+                    prior: Pubkey::default(),
+                    authorized: Pubkey::default(),
+                    epoch: std::u64::MAX,
+                };
+                MAX_AUTHORIZED_HISTORY
+            ],
+        };
+        serialized_size(&rotations).unwrap() as usize
+    }
+
+    /// The rotations kept in `account`, None if the account has no room for them
+    pub fn from(account: &Account) -> Option<AuthorizedRotations> {
+        account
+            .data
+            .get(VoteState::size_of()..)
+            .filter(|data| data.len() >= Self::size_of())
+            .and_then(|data| deserialize(data).ok())
+    }
+
+    pub fn to(&self, account: &mut Account) -> Result<(), InstructionError> {
+        let data = account
+            .data
+            .get_mut(VoteState::size_of()..)
+            .filter(|data| data.len() >= Self::size_of())
+            .ok_or(InstructionError::AccountDataTooSmall)?;
+        serialize_into(data, self).map_err(|_| InstructionError::GenericError)
+    }
+
+    /// The rotation of the voter or withdrawer waiting to take effect, as
+    ///  (new signer, first epoch it signs for)
+    pub fn pending_authorized(&self, vote_authorize: VoteAuthorize) -> Option<&(Pubkey, Epoch)> {
+        match vote_authorize {
+            VoteAuthorize::Voter => self.pending_authorized_voter.as_ref(),
+            VoteAuthorize::Withdrawer => self.pending_authorized_withdrawer.as_ref(),
+        }
+    }
+
+    /// Past rotations of the voter and withdrawer, oldest first
+    pub fn authorized_history(&self) -> impl Iterator<Item = &AuthorizedRotation> {
+        self.authorized_history.iter()
+    }
+
+    /// Schedule `authorized` to replace the voter or withdrawer from `epoch` on, replacing any
+    ///  rotation of the same signer that hasn't taken effect yet
+    pub fn rotate(&mut self, authorized: &Pubkey, vote_authorize: VoteAuthorize, epoch: Epoch) {
+        let pending = Some((*authorized, epoch));
+        match vote_authorize {
+            VoteAuthorize::Voter => self.pending_authorized_voter = pending,
+            VoteAuthorize::Withdrawer => self.pending_authorized_withdrawer = pending,
+        }
+    }
+
+    /// Make the rotations of `vote_state` due by `epoch` take effect
+    pub fn apply(&mut self, vote_state: &mut VoteState, epoch: Epoch) {
+        let voter = self
+            .pending_authorized_voter
+            .filter(|(_, start)| *start <= epoch);
+        if let Some((authorized, start)) = voter {
+            self.pending_authorized_voter = None;
+            self.record_rotation(
+                VoteAuthorize::Voter,
+                vote_state.authorized_voter,
+                authorized,
+                start,
+            );
+            vote_state.authorized_voter = authorized;
+        }
+        let withdrawer = self
+            .pending_authorized_withdrawer
+            .filter(|(_, start)| *start <= epoch);
+        if let Some((authorized, start)) = withdrawer {
+            self.pending_authorized_withdrawer = None;
+            self.record_rotation(
+                VoteAuthorize::Withdrawer,
+                vote_state.authorized_withdrawer,
+                authorized,
+                start,
+            );
+            vote_state.authorized_withdrawer = authorized;
+        }
+    }
+
+    /// Record an immediate change of signer made in `epoch`, it replaces any pending rotation
+    ///  of the same signer
+    pub fn authorize(
+        &mut self,
+        prior: &Pubkey,
+        authorized: &Pubkey,
+        vote_authorize: VoteAuthorize,
+        epoch: Epoch,
+    ) {
+        match vote_authorize {
+            VoteAuthorize::Voter => self.pending_authorized_voter = None,
+            VoteAuthorize::Withdrawer => self.pending_authorized_withdrawer = None,
+        }
+        self.record_rotation(vote_authorize, *prior, *authorized, epoch);
+    }
+
+    fn record_rotation(
+        &mut self,
+        vote_authorize: VoteAuthorize,
+        prior: Pubkey,
+        authorized: Pubkey,
+        epoch: Epoch,
+    ) {
+        self.authorized_history.push(AuthorizedRotation {
+            vote_authorize,
+            prior,
+            authorized,
+            epoch,
+        });
+        if self.authorized_history.len() > MAX_AUTHORIZED_HISTORY {
+            self.authorized_history.remove(0);
+        }
+    }
+}
+
+// Load the vote state with the rotations due by `epoch` applied, the rotations are None for an
+//  account without room for them. Only those accounts can be loaded without an epoch, as
+//  Authorize and Withdraw instructions built before rotations existed don't pass the clock.
+fn get_state_with_rotations(
+    vote_account: &KeyedAccount,
+    epoch: Option<Epoch>,
+) -> Result<(VoteState, Option<AuthorizedRotations>), InstructionError> {
+    let mut vote_state: VoteState = vote_account.state()?;
+    let mut rotations = AuthorizedRotations::from(&vote_account.account);
+    if let Some(rotations) = rotations.as_mut() {
+        let epoch = epoch.ok_or(InstructionError::NotEnoughAccountKeys)?;
+        rotations.apply(&mut vote_state, epoch);
+    }
+    Ok((vote_state, rotations))
+}
+
+fn set_state_with_rotations(
+    vote_account: &mut KeyedAccount,
+    vote_state: &VoteState,
+    rotations: Option<&AuthorizedRotations>,
+) -> Result<(), InstructionError> {
+    vote_account.set_state(vote_state)?;
+    if let Some(rotations) = rotations {
+        rotations.to(vote_account.account)?;
+    }
+    Ok(())
+}
+
 /// Authorize the given pubkey to withdraw or sign votes. This may be called multiple times,
 /// but will implicitly withdraw authorization from the previously authorized
-/// key
+/// key. A pending rotation of the same signer is cancelled. The `clock` is only optional for
+/// accounts without room for rotations.
 pub fn authorize(
     vote_account: &mut KeyedAccount,
     authorized: &Pubkey,
     vote_authorize: VoteAuthorize,
+    clock: Option<&Clock>,
     signers: &HashSet<Pubkey>,
 ) -> Result<(), InstructionError> {
-    let mut vote_state: VoteState = vote_account.state()?;
+    let epoch = clock.map(|clock| clock.epoch);
+    let (mut vote_state, mut rotations) = get_state_with_rotations(vote_account, epoch)?;
 
     // current authorized signer must say "yay"
-    match vote_authorize {
+    let prior = match vote_authorize {
         VoteAuthorize::Voter => {
             verify_authorized_signer(&vote_state.authorized_voter, signers)?;
-            vote_state.authorized_voter = *authorized;
+            std::mem::replace(&mut vote_state.authorized_voter, *authorized)
         }
         VoteAuthorize::Withdrawer => {
             verify_authorized_signer(&vote_state.authorized_withdrawer, signers)?;
-            vote_state.authorized_withdrawer = *authorized;
+            std::mem::replace(&mut vote_state.authorized_withdrawer, *authorized)
+        }
+    };
+    if let (Some(rotations), Some(epoch)) = (rotations.as_mut(), epoch) {
+        rotations.authorize(&prior, authorized, vote_authorize, epoch);
+    }
+
+    set_state_with_rotations(vote_account, &vote_state, rotations.as_ref())
+}
+
+/// Rotate the voter or withdrawer to `authorized` at the start of the next epoch. Until then
+/// the current signer stays authorized, and may replace the pending rotation with another.
+/// Accounts created before rotations existed have no room for them and must use `authorize`.
+pub fn rotate_authorized(
+    vote_account: &mut KeyedAccount,
+    authorized: &Pubkey,
+    vote_authorize: VoteAuthorize,
+    clock: &Clock,
+    signers: &HashSet<Pubkey>,
+) -> Result<(), InstructionError> {
+    let (vote_state, rotations) = get_state_with_rotations(vote_account, Some(clock.epoch))?;
+    let mut rotations = rotations.ok_or(InstructionError::AccountDataTooSmall)?;
+
+    // current authorized signer must say "yay"
+    match vote_authorize {
+        VoteAuthorize::Voter => verify_authorized_signer(&vote_state.authorized_voter, signers)?,
+        VoteAuthorize::Withdrawer => {
+            verify_authorized_signer(&vote_state.authorized_withdrawer, signers)?
         }
     }
+    rotations.rotate(authorized, vote_authorize, clock.epoch + 1);
 
-    vote_account.set_state(&vote_state)
+    set_state_with_rotations(vote_account, &vote_state, Some(&rotations))
 }
 
 fn verify_authorized_signer(
@@ -373,20 +592,24 @@ fn verify_authorized_signer(
     }
 }
 
-/// Withdraw funds from the vote account
+/// Withdraw funds from the vote account, the `clock` is only optional for accounts without room
+/// for rotations
 pub fn withdraw(
     vote_account: &mut KeyedAccount,
     lamports: u64,
     to_account: &mut KeyedAccount,
+    clock: Option<&Clock>,
     signers: &HashSet<Pubkey>,
 ) -> Result<(), InstructionError> {
-    let vote_state: VoteState = vote_account.state()?;
+    let (vote_state, rotations) =
+        get_state_with_rotations(vote_account, clock.map(|clock| clock.epoch))?;
 
     verify_authorized_signer(&vote_state.authorized_withdrawer, signers)?;
 
     if vote_account.account.lamports < lamports {
         return Err(InstructionError::InsufficientFunds);
     }
+    set_state_with_rotations(vote_account, &vote_state, rotations.as_ref())?;
     vote_account.account.lamports -= lamports;
     to_account.account.lamports += lamports;
     Ok(())
@@ -414,7 +637,7 @@ pub fn process_vote(
     vote: &Vote,
     signers: &HashSet<Pubkey>,
 ) -> Result<(), InstructionError> {
-    let mut vote_state: VoteState = vote_account.state()?;
+    let (mut vote_state, rotations) = get_state_with_rotations(vote_account, Some(clock.epoch))?;
 
     if vote_state.authorized_voter == Pubkey::default() {
         return Err(InstructionError::UninitializedAccount);
@@ -423,7 +646,7 @@ pub fn process_vote(
     verify_authorized_signer(&vote_state.authorized_voter, signers)?;
 
     vote_state.process_vote(vote, slot_hashes, clock.epoch)?;
-    vote_account.set_state(&vote_state)
+    set_state_with_rotations(vote_account, &vote_state, rotations.as_ref())
 }
 
 // utility function, used by Bank, tests
@@ -433,7 +656,7 @@ pub fn create_account(
     commission: u8,
     lamports: u64,
 ) -> Account {
-    let mut vote_account = Account::new(lamports, VoteState::size_of(), &id());
+    let mut vote_account = Account::new(lamports, VoteState::account_size(), &id());
 
     VoteState::new(&VoteInit {
         node_pubkey: *node_pubkey,
@@ -653,6 +876,7 @@ mod tests {
             &mut keyed_accounts[0],
             &authorized_voter_pubkey,
             VoteAuthorize::Voter,
+            Some(&Clock::default()),
             &signers,
         );
         assert_eq!(res, Err(InstructionError::MissingRequiredSignature));
@@ -663,6 +887,7 @@ mod tests {
             &mut keyed_accounts[0],
             &authorized_voter_pubkey,
             VoteAuthorize::Voter,
+            Some(&Clock::default()),
             &signers,
         );
         assert_eq!(res, Ok(()));
@@ -682,6 +907,7 @@ mod tests {
             &mut keyed_accounts[0],
             &authorized_voter_pubkey,
             VoteAuthorize::Voter,
+            Some(&Clock::default()),
             &signers,
         );
         assert_eq!(res, Ok(()));
@@ -695,6 +921,7 @@ mod tests {
             &mut keyed_accounts[0],
             &authorized_withdrawer_pubkey,
             VoteAuthorize::Withdrawer,
+            Some(&Clock::default()),
             &signers,
         );
         assert_eq!(res, Ok(()));
@@ -710,6 +937,7 @@ mod tests {
             &mut keyed_accounts[0],
             &authorized_withdrawer_pubkey,
             VoteAuthorize::Withdrawer,
+            Some(&Clock::default()),
             &signers,
         );
         assert_eq!(res, Ok(()));
@@ -1074,6 +1302,18 @@ mod tests {
     fn test_vote_state_withdraw() {
         let (vote_pubkey, mut vote_account) = create_test_account();
 
+        // an account with room for rotations needs the Clock to apply them
+        let keyed_accounts = &mut [KeyedAccount::new(&vote_pubkey, true, &mut vote_account)];
+        let signers = get_signers(keyed_accounts);
+        let res = withdraw(
+            &mut keyed_accounts[0],
+            0,
+            &mut KeyedAccount::new(&Pubkey::new_rand(), false, &mut Account::default()),
+            None,
+            &signers,
+        );
+        assert_eq!(res, Err(InstructionError::NotEnoughAccountKeys));
+
         // unsigned request
         let keyed_accounts = &mut [KeyedAccount::new(&vote_pubkey, false, &mut vote_account)];
         let signers = get_signers(keyed_accounts);
@@ -1081,6 +1321,7 @@ mod tests {
             &mut keyed_accounts[0],
             0,
             &mut KeyedAccount::new(&Pubkey::new_rand(), false, &mut Account::default()),
+            Some(&Clock::default()),
             &signers,
         );
         assert_eq!(res, Err(InstructionError::MissingRequiredSignature));
@@ -1092,6 +1333,7 @@ mod tests {
             &mut keyed_accounts[0],
             101,
             &mut KeyedAccount::new(&Pubkey::new_rand(), false, &mut Account::default()),
+            Some(&Clock::default()),
             &signers,
         );
         assert_eq!(res, Err(InstructionError::InsufficientFunds));
@@ -1105,6 +1347,7 @@ mod tests {
             &mut keyed_accounts[0],
             lamports,
             &mut KeyedAccount::new(&Pubkey::new_rand(), false, &mut to_account),
+            Some(&Clock::default()),
             &signers,
         );
         assert_eq!(res, Ok(()));
@@ -1122,6 +1365,7 @@ mod tests {
             &mut keyed_accounts[0],
             &authorized_withdrawer_pubkey,
             VoteAuthorize::Withdrawer,
+            Some(&Clock::default()),
             &signers,
         );
         assert_eq!(res, Ok(()));
@@ -1140,6 +1384,7 @@ mod tests {
             vote_keyed_account,
             lamports,
             withdrawer_keyed_account,
+            Some(&Clock::default()),
             &signers,
         );
         assert_eq!(res, Ok(()));
@@ -1147,6 +1392,285 @@ mod tests {
         assert_eq!(withdrawer_account.lamports, lamports);
     }
 
+    #[test]
+    fn test_vote_state_rotate_authorized() {
+        let (vote_pubkey, mut vote_account) = create_test_account();
+        let clock = Clock {
+            epoch: 1,
+            ..Clock::default()
+        };
+        let new_voter_pubkey = Pubkey::new_rand();
+
+        // unsigned
+        let keyed_accounts = &mut [KeyedAccount::new(&vote_pubkey, false, &mut vote_account)];
+        let signers = get_signers(keyed_accounts);
+        let res = rotate_authorized(
+            &mut keyed_accounts[0],
+            &new_voter_pubkey,
+            VoteAuthorize::Voter,
+            &clock,
+            &signers,
+        );
+        assert_eq!(res, Err(InstructionError::MissingRequiredSignature));
+
+        // signed by the current voter, takes effect next epoch
+        let keyed_accounts = &mut [KeyedAccount::new(&vote_pubkey, true, &mut vote_account)];
+        let signers = get_signers(keyed_accounts);
+        let res = rotate_authorized(
+            &mut keyed_accounts[0],
+            &new_voter_pubkey,
+            VoteAuthorize::Voter,
+            &clock,
+            &signers,
+        );
+        assert_eq!(res, Ok(()));
+        let vote_state: VoteState = vote_account.state().unwrap();
+        assert_eq!(vote_state.authorized_voter, vote_pubkey);
+        let rotations = AuthorizedRotations::from(&vote_account).unwrap();
+        assert_eq!(
+            rotations.pending_authorized(VoteAuthorize::Voter),
+            Some(&(new_voter_pubkey, 2))
+        );
+
+        // the current voter still votes this epoch
+        let vote = Vote::new(vec![1], Hash::default());
+        let res = simulate_process_vote(
+            &vote_pubkey,
+            &mut vote_account,
+            &vote,
+            &[(*vote.slots.last().unwrap(), vote.hash)],
+            1,
+        );
+        assert!(res.is_ok());
+
+        // ...but not once the rotation is due
+        let vote = Vote::new(vec![2], Hash::default());
+        let res = simulate_process_vote(
+            &vote_pubkey,
+            &mut vote_account,
+            &vote,
+            &[(*vote.slots.last().unwrap(), vote.hash)],
+            2,
+        );
+        assert_eq!(res, Err(InstructionError::MissingRequiredSignature));
+
+        // the new voter votes, which applies the rotation and records it
+        let mut new_voter_account = Account::default();
+        let keyed_accounts = &mut [
+            KeyedAccount::new(&vote_pubkey, false, &mut vote_account),
+            KeyedAccount::new(&new_voter_pubkey, true, &mut new_voter_account),
+        ];
+        let signers = get_signers(keyed_accounts);
+        let res = process_vote(
+            &mut keyed_accounts[0],
+            &[(*vote.slots.last().unwrap(), vote.hash)],
+            &Clock {
+                epoch: 2,
+                ..Clock::default()
+            },
+            &vote,
+            &signers,
+        );
+        assert_eq!(res, Ok(()));
+        let vote_state: VoteState = vote_account.state().unwrap();
+        assert_eq!(vote_state.authorized_voter, new_voter_pubkey);
+        let rotations = AuthorizedRotations::from(&vote_account).unwrap();
+        assert_eq!(rotations.pending_authorized(VoteAuthorize::Voter), None);
+        assert_eq!(
+            rotations.authorized_history().collect::<Vec<_>>(),
+            vec![&AuthorizedRotation {
+                vote_authorize: VoteAuthorize::Voter,
+                prior: vote_pubkey,
+                authorized: new_voter_pubkey,
+                epoch: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_vote_state_rotate_withdrawer() {
+        let (vote_pubkey, mut vote_account) = create_test_account();
+        let lamports = vote_account.lamports;
+        let new_withdrawer_pubkey = Pubkey::new_rand();
+
+        let keyed_accounts = &mut [KeyedAccount::new(&vote_pubkey, true, &mut vote_account)];
+        let signers = get_signers(keyed_accounts);
+        let res = rotate_authorized(
+            &mut keyed_accounts[0],
+            &new_withdrawer_pubkey,
+            VoteAuthorize::Withdrawer,
+            &Clock::default(),
+            &signers,
+        );
+        assert_eq!(res, Ok(()));
+
+        // the new withdrawer can't withdraw until the next epoch
+        let mut withdrawer_account = Account::default();
+        let keyed_accounts = &mut [
+            KeyedAccount::new(&vote_pubkey, false, &mut vote_account),
+            KeyedAccount::new(&new_withdrawer_pubkey, true, &mut withdrawer_account),
+        ];
+        let signers = get_signers(keyed_accounts);
+        let keyed_accounts = &mut keyed_accounts.iter_mut();
+        let vote_keyed_account = next_keyed_account(keyed_accounts).unwrap();
+        let withdrawer_keyed_account = next_keyed_account(keyed_accounts).unwrap();
+        let res = withdraw(
+            vote_keyed_account,
+            lamports,
+            withdrawer_keyed_account,
+            Some(&Clock::default()),
+            &signers,
+        );
+        assert_eq!(res, Err(InstructionError::MissingRequiredSignature));
+        let res = withdraw(
+            vote_keyed_account,
+            lamports,
+            withdrawer_keyed_account,
+            Some(&Clock {
+                epoch: 1,
+                ..Clock::default()
+            }),
+            &signers,
+        );
+        assert_eq!(res, Ok(()));
+        assert_eq!(vote_account.lamports, 0);
+        assert_eq!(withdrawer_account.lamports, lamports);
+
+        let vote_state: VoteState = vote_account.state().unwrap();
+        assert_eq!(vote_state.authorized_withdrawer, new_withdrawer_pubkey);
+        let rotations = AuthorizedRotations::from(&vote_account).unwrap();
+        assert_eq!(rotations.authorized_history().count(), 1);
+    }
+
+    #[test]
+    fn test_vote_state_authorize_cancels_rotation() {
+        let (vote_pubkey, mut vote_account) = create_test_account();
+        let clock = Clock {
+            epoch: 3,
+            ..Clock::default()
+        };
+        let rotated_pubkey = Pubkey::new_rand();
+        let authorized_pubkey = Pubkey::new_rand();
+
+        let keyed_accounts = &mut [KeyedAccount::new(&vote_pubkey, true, &mut vote_account)];
+        let signers = get_signers(keyed_accounts);
+        let res = rotate_authorized(
+            &mut keyed_accounts[0],
+            &rotated_pubkey,
+            VoteAuthorize::Voter,
+            &clock,
+            &signers,
+        );
+        assert_eq!(res, Ok(()));
+        let res = authorize(
+            &mut keyed_accounts[0],
+            &authorized_pubkey,
+            VoteAuthorize::Voter,
+            Some(&clock),
+            &signers,
+        );
+        assert_eq!(res, Ok(()));
+
+        let vote_state: VoteState = vote_account.state().unwrap();
+        assert_eq!(vote_state.authorized_voter, authorized_pubkey);
+        let rotations = AuthorizedRotations::from(&vote_account).unwrap();
+        assert_eq!(rotations.pending_authorized(VoteAuthorize::Voter), None);
+        assert_eq!(
+            rotations.authorized_history().collect::<Vec<_>>(),
+            vec![&AuthorizedRotation {
+                vote_authorize: VoteAuthorize::Voter,
+                prior: vote_pubkey,
+                authorized: authorized_pubkey,
+                epoch: 3,
+            }]
+        );
+
+        // the cancelled rotation doesn't take effect later on
+        let vote = Vote::new(vec![1], Hash::default());
+        let res = simulate_process_vote(
+            &authorized_pubkey,
+            &mut vote_account,
+            &vote,
+            &[(*vote.slots.last().unwrap(), vote.hash)],
+            4,
+        );
+        assert!(res.is_ok());
+        let vote_state: VoteState = vote_account.state().unwrap();
+        assert_eq!(vote_state.authorized_voter, authorized_pubkey);
+    }
+
+    #[test]
+    fn test_vote_state_rotate_without_room() {
+        // accounts created before rotations existed only have room for the VoteState
+        let vote_pubkey = Pubkey::new_rand();
+        let mut vote_account = Account::new(100, VoteState::size_of(), &id());
+        VoteState::new(&VoteInit {
+            node_pubkey: Pubkey::new_rand(),
+            authorized_voter: vote_pubkey,
+            authorized_withdrawer: vote_pubkey,
+            commission: 0,
+        })
+        .to(&mut vote_account)
+        .unwrap();
+        assert_eq!(AuthorizedRotations::from(&vote_account), None);
+
+        let keyed_accounts = &mut [KeyedAccount::new(&vote_pubkey, true, &mut vote_account)];
+        let signers = get_signers(keyed_accounts);
+        let res = rotate_authorized(
+            &mut keyed_accounts[0],
+            &Pubkey::new_rand(),
+            VoteAuthorize::Voter,
+            &Clock::default(),
+            &signers,
+        );
+        assert_eq!(res, Err(InstructionError::AccountDataTooSmall));
+
+        // everything else keeps working, without the Clock as before rotations existed
+        let new_voter_pubkey = Pubkey::new_rand();
+        let res = authorize(
+            &mut keyed_accounts[0],
+            &new_voter_pubkey,
+            VoteAuthorize::Voter,
+            None,
+            &signers,
+        );
+        assert_eq!(res, Ok(()));
+        let vote_state: VoteState = vote_account.state().unwrap();
+        assert_eq!(vote_state.authorized_voter, new_voter_pubkey);
+    }
+
+    #[test]
+    fn test_vote_state_authorized_history_capped() {
+        let mut vote_state = VoteState::default();
+        let mut rotations = AuthorizedRotations::default();
+        for epoch in 0..(MAX_AUTHORIZED_HISTORY as u64 + 5) {
+            rotations.rotate(&Pubkey::new_rand(), VoteAuthorize::Voter, epoch);
+            rotations.apply(&mut vote_state, epoch);
+        }
+        assert_eq!(
+            rotations.authorized_history().count(),
+            MAX_AUTHORIZED_HISTORY
+        );
+        assert_eq!(rotations.authorized_history().next().unwrap().epoch, 5);
+        assert!(serialized_size(&rotations).unwrap() as usize <= AuthorizedRotations::size_of());
+    }
+
+    #[test]
+    fn test_vote_state_rotations_after_full_vote_state() {
+        let (_vote_pubkey, mut vote_account) = create_test_account();
+        let mut rotations = AuthorizedRotations::default();
+        rotations.rotate(&Pubkey::new_rand(), VoteAuthorize::Withdrawer, 7);
+        rotations.to(&mut vote_account).unwrap();
+
+        // a VoteState at its largest leaves the rotations alone
+        let mut vote_state: VoteState = vote_account.state().unwrap();
+        vote_state.votes = VecDeque::from(vec![Lockout::default(); MAX_LOCKOUT_HISTORY]);
+        vote_state.root_slot = Some(std::u64::MAX);
+        vote_state.epoch_credits = vec![(0, 0, 0); MAX_EPOCH_CREDITS_HISTORY];
+        vote_state.to(&mut vote_account).unwrap();
+        assert_eq!(AuthorizedRotations::from(&vote_account), Some(rotations));
+    }
+
     #[test]
     fn test_vote_state_epoch_credits() {
         let mut vote_state = VoteState::default();
@@ -5,5 +5,8 @@ macro_rules! solana_vote_program {
     };
 }
 
+/// Version of this program, recorded in genesis manifests
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 use solana_vote_api::vote_instruction::process_instruction;
 solana_sdk::solana_entrypoint!(process_instruction);
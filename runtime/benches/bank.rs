@@ -10,11 +10,12 @@ use solana_sdk::account::KeyedAccount;
 use solana_sdk::client::AsyncClient;
 use solana_sdk::client::SyncClient;
 use solana_sdk::clock::MAX_RECENT_BLOCKHASHES;
-use solana_sdk::genesis_config::create_genesis_config;
+use solana_sdk::genesis_config::{create_genesis_config, GenesisConfig};
 use solana_sdk::instruction::InstructionError;
+use solana_sdk::message::{Message, MessageHeader};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, KeypairUtil};
-use solana_sdk::transaction::Transaction;
+use solana_sdk::transaction::{Transaction, TransactionError};
 use std::sync::Arc;
 use std::thread::sleep;
 use std::time::Duration;
@@ -197,3 +198,59 @@ fn bench_bank_update_recent_blockhashes(bencher: &mut Bencher) {
         bank.update_recent_blockhashes();
     });
 }
+
+// Transactions that each write one of a few shared accounts, so many of them conflict
+fn create_contended_transactions(genesis_config: &GenesisConfig) -> Vec<Transaction> {
+    let shared_keys: Vec<_> = (0..64).map(|_| Pubkey::new_rand()).collect();
+    (0..4096)
+        .map(|i| {
+            let keypair = Keypair::new();
+            let message = Message {
+                header: MessageHeader {
+                    num_required_signatures: 1,
+                    num_readonly_signed_accounts: 0,
+                    num_readonly_unsigned_accounts: 0,
+                },
+                account_keys: vec![keypair.pubkey(), shared_keys[i % shared_keys.len()]],
+                recent_blockhash: genesis_config.hash(),
+                instructions: vec![],
+            };
+            Transaction::new(&[&keypair], message, genesis_config.hash())
+        })
+        .collect()
+}
+
+#[bench]
+fn bench_bank_greedy_lock_loop(bencher: &mut Bencher) {
+    let (genesis_config, _mint_keypair) = create_genesis_config(100);
+    let bank = Bank::new(&genesis_config);
+    let transactions = create_contended_transactions(&genesis_config);
+    bencher.iter(|| {
+        // lock everything, then retry whatever was in use until every transaction got its locks
+        let mut pending = transactions.clone();
+        while !pending.is_empty() {
+            let batch = bank.prepare_batch(&pending, None);
+            let retry: Vec<_> = batch
+                .lock_results()
+                .iter()
+                .zip(pending.iter())
+                .filter(|(result, _)| **result == Err(TransactionError::AccountInUse))
+                .map(|(_, tx)| tx.clone())
+                .collect();
+            drop(batch);
+            pending = retry;
+        }
+    });
+}
+
+#[bench]
+fn bench_bank_prepare_sanitized_batches(bencher: &mut Bencher) {
+    let (genesis_config, _mint_keypair) = create_genesis_config(100);
+    let bank = Bank::new(&genesis_config);
+    let transactions = create_contended_transactions(&genesis_config);
+    bencher.iter(|| {
+        for batch in bank.prepare_sanitized_batches(&transactions) {
+            assert!(batch.lock_results().iter().all(|result| result.is_ok()));
+        }
+    });
+}
@@ -44,12 +44,28 @@ pub struct Accounts {
     readonly_locks: Arc<RwLock<Option<HashMap<Pubkey, ReadonlyLock>>>>,
 }
 
+/// Which accounts a parallel scan returns, accounts without lamports never are
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccountsScanFilter {
+    /// Only accounts owned by this program
+    pub owner: Option<Pubkey>,
+    /// Only versions stored in rooted slots, ignoring the unrooted ancestors
+    pub rooted_only: bool,
+}
+
 // for the load instructions
 pub type TransactionAccounts = Vec<Account>;
 pub type TransactionRents = Vec<u64>;
 pub type TransactionLoaders = Vec<Vec<(Pubkey, Account)>>;
+/// Bytes of data the transaction's writable accounts held as stored, before rent was collected
+pub type TransactionDataLen = usize;
 
-pub type TransactionLoadResult = (TransactionAccounts, TransactionLoaders, TransactionRents);
+pub type TransactionLoadResult = (
+    TransactionAccounts,
+    TransactionLoaders,
+    TransactionRents,
+    TransactionDataLen,
+);
 
 impl Accounts {
     pub fn new(paths: Option<String>) -> Self {
@@ -92,7 +108,7 @@ impl Accounts {
         fee: u64,
         error_counters: &mut ErrorCounters,
         rent_collector: &RentCollector,
-    ) -> Result<(TransactionAccounts, TransactionRents)> {
+    ) -> Result<(TransactionAccounts, TransactionRents, TransactionDataLen)> {
         // Copy all the accounts
         let message = tx.message();
         if tx.signatures.is_empty() && fee != 0 {
@@ -108,13 +124,18 @@ impl Accounts {
             // If a fee can pay for execution then the program will be scheduled
             let mut accounts: TransactionAccounts = Vec::with_capacity(message.account_keys.len());
             let mut rents: TransactionRents = Vec::with_capacity(message.account_keys.len());
-            for key in message
+            let mut data_len: TransactionDataLen = 0;
+            for (i, key) in message
                 .account_keys
                 .iter()
-                .filter(|key| !message.program_ids().contains(key))
+                .enumerate()
+                .filter(|(_, key)| !message.program_ids().contains(key))
             {
                 let (account, rent) = AccountsDB::load(storage, ancestors, accounts_index, key)
                     .and_then(|(mut account, _)| {
+                        if message.is_writable(i) && account.lamports > 0 {
+                            data_len += account.data.len();
+                        }
                         let rent_due = rent_collector.update(&mut account);
                         Some((account, rent_due))
                     })
@@ -135,7 +156,7 @@ impl Accounts {
                 Err(TransactionError::InsufficientFundsForFee)
             } else {
                 accounts[0].lamports -= fee;
-                Ok((accounts, rents))
+                Ok((accounts, rents, data_len))
             }
         }
     }
@@ -236,7 +257,7 @@ impl Accounts {
                         .ok_or(TransactionError::BlockhashNotFound)?;
 
                     let fee = fee_calculator.calculate_fee(tx.message());
-                    let (accounts, rents) = self.load_tx_accounts(
+                    let (accounts, rents, data_len) = self.load_tx_accounts(
                         &storage,
                         ancestors,
                         &accounts_index,
@@ -252,7 +273,7 @@ impl Accounts {
                         tx,
                         error_counters,
                     )?;
-                    Ok((accounts, loaders, rents))
+                    Ok((accounts, loaders, rents, data_len))
                 }
                 (_, Err(e)) => Err(e),
             })
@@ -321,6 +342,12 @@ impl Accounts {
         })
     }
 
+    pub fn load_by_slot(&self, slot: Slot) -> Vec<(Pubkey, Account)> {
+        self.scan_slot(slot, |stored_account| {
+            Some((stored_account.meta.pubkey, stored_account.clone_account()))
+        })
+    }
+
     pub fn verify_hash_internal_state(&self, slot: Slot, ancestors: &HashMap<Slot, usize>) -> bool {
         self.accounts_db.verify_hash_internal_state(slot, ancestors)
     }
@@ -330,17 +357,38 @@ impl Accounts {
         ancestors: &HashMap<Slot, usize>,
         program_id: &Pubkey,
     ) -> Vec<(Pubkey, Account)> {
-        self.accounts_db.scan_accounts(
-            ancestors,
-            |collector: &mut Vec<(Pubkey, Account)>, option| {
-                if let Some(data) = option
-                    .filter(|(_, account, _)| account.owner == *program_id && account.lamports != 0)
-                    .map(|(pubkey, account, _slot)| (*pubkey, account))
-                {
-                    collector.push(data)
-                }
-            },
-        )
+        let filter = AccountsScanFilter {
+            owner: Some(*program_id),
+            ..AccountsScanFilter::default()
+        };
+        self.load_by_filter(ancestors, Slot::max_value(), &filter)
+    }
+
+    /// Load every account visible from `ancestors` and stored no later than `max_slot` that
+    /// `filter` accepts, scanning the index in parallel
+    pub fn load_by_filter(
+        &self,
+        ancestors: &HashMap<Slot, usize>,
+        max_slot: Slot,
+        filter: &AccountsScanFilter,
+    ) -> Vec<(Pubkey, Account)> {
+        let unrooted = HashMap::new();
+        let ancestors = if filter.rooted_only {
+            &unrooted
+        } else {
+            ancestors
+        };
+        self.accounts_db
+            .scan_accounts_parallel(ancestors, max_slot, |_pubkey, account| {
+                account.lamports != 0
+                    && filter
+                        .owner
+                        .map(|owner| account.owner == owner)
+                        .unwrap_or(true)
+            })
+            .into_iter()
+            .map(|(pubkey, account, _slot)| (pubkey, account))
+            .collect()
     }
 
     /// Slow because lock is held for 1 operation instead of many
@@ -398,12 +446,14 @@ impl Accounts {
         locks: &mut HashSet<Pubkey>,
         message: &Message,
         error_counters: &mut ErrorCounters,
+        contention: &mut HashMap<Pubkey, u64>,
     ) -> Result<()> {
         let (writable_keys, readonly_keys) = message.get_account_keys_by_lock_type();
 
         for k in writable_keys.iter() {
             if locks.contains(k) || self.is_locked_readonly(k) {
                 error_counters.account_in_use += 1;
+                *contention.entry(**k).or_insert(0) += 1;
                 debug!("CD Account in use: {:?}", k);
                 return Err(TransactionError::AccountInUse);
             }
@@ -411,6 +461,7 @@ impl Accounts {
         for k in readonly_keys.iter() {
             if locks.contains(k) {
                 error_counters.account_in_use += 1;
+                *contention.entry(**k).or_insert(0) += 1;
                 debug!("CO Account in use: {:?}", k);
                 return Err(TransactionError::AccountInUse);
             }
@@ -440,8 +491,9 @@ impl Accounts {
     fn unlock_account(&self, tx: &Transaction, result: &Result<()>, locks: &mut HashSet<Pubkey>) {
         let (writable_keys, readonly_keys) = &tx.message().get_account_keys_by_lock_type();
         match result {
-            Err(TransactionError::AccountInUse) => (),
-            _ => {
+            // Only transactions whose lock succeeded hold locks to release
+            Err(_) => (),
+            Ok(()) => {
                 for k in writable_keys {
                     locks.remove(k);
                 }
@@ -470,14 +522,52 @@ impl Accounts {
         txs: &[Transaction],
         txs_iteration_order: Option<&[usize]>,
     ) -> Vec<Result<()>> {
+        self.lock_accounts_with_contention(txs, txs_iteration_order, &mut HashMap::new())
+    }
+
+    /// Lock accounts like `lock_accounts`, counting in `contention` every account that kept a
+    /// transaction from taking its locks
+    #[must_use]
+    pub fn lock_accounts_with_contention(
+        &self,
+        txs: &[Transaction],
+        txs_iteration_order: Option<&[usize]>,
+        contention: &mut HashMap<Pubkey, u64>,
+    ) -> Vec<Result<()>> {
+        self.lock_checked_accounts(txs, txs_iteration_order, contention, |_| Ok(()))
+    }
+
+    /// Like `lock_accounts_with_contention()`, but only the transactions that pass
+    /// `Transaction::sanitize()` take locks. The others keep their sanitize error as their result.
+    pub fn lock_sanitized_accounts_with_contention(
+        &self,
+        txs: &[Transaction],
+        txs_iteration_order: Option<&[usize]>,
+        contention: &mut HashMap<Pubkey, u64>,
+    ) -> Vec<Result<()>> {
+        self.lock_checked_accounts(txs, txs_iteration_order, contention, Transaction::sanitize)
+    }
+
+    fn lock_checked_accounts<F>(
+        &self,
+        txs: &[Transaction],
+        txs_iteration_order: Option<&[usize]>,
+        contention: &mut HashMap<Pubkey, u64>,
+        check: F,
+    ) -> Vec<Result<()>>
+    where
+        F: Fn(&Transaction) -> Result<()>,
+    {
         let mut error_counters = ErrorCounters::default();
         let rv = OrderedIterator::new(txs, txs_iteration_order)
             .map(|tx| {
+                check(tx)?;
                 let message = &tx.message();
                 self.lock_account(
                     &mut self.account_locks.lock().unwrap(),
                     &message,
                     &mut error_counters,
+                    contention,
                 )
             })
             .collect();
@@ -808,11 +898,12 @@ mod tests {
         assert_eq!(error_counters.account_not_found, 0);
         assert_eq!(loaded_accounts.len(), 1);
         match &loaded_accounts[0] {
-            Ok((transaction_accounts, transaction_loaders, _transaction_rents)) => {
+            Ok((transaction_accounts, transaction_loaders, _transaction_rents, data_len)) => {
                 assert_eq!(transaction_accounts.len(), 2);
                 assert_eq!(transaction_accounts[0], accounts[0].1);
                 assert_eq!(transaction_loaders.len(), 1);
                 assert_eq!(transaction_loaders[0].len(), 0);
+                assert_eq!(*data_len, 2);
             }
             Err(e) => Err(e).unwrap(),
         }
@@ -989,7 +1080,7 @@ mod tests {
         assert_eq!(error_counters.account_not_found, 0);
         assert_eq!(loaded_accounts.len(), 1);
         match &loaded_accounts[0] {
-            Ok((transaction_accounts, transaction_loaders, _transaction_rents)) => {
+            Ok((transaction_accounts, transaction_loaders, _transaction_rents, _data_len)) => {
                 assert_eq!(transaction_accounts.len(), 1);
                 assert_eq!(transaction_accounts[0], accounts[0].1);
                 assert_eq!(transaction_loaders.len(), 2);
@@ -1249,6 +1340,44 @@ mod tests {
         assert_eq!(*keypair1_lock.unwrap().lock_count.lock().unwrap(), 0);
     }
 
+    #[test]
+    fn test_accounts_lock_contention() {
+        let keypair0 = Keypair::new();
+        let keypair1 = Keypair::new();
+        let keypair2 = Keypair::new();
+        let accounts = Accounts::new(None);
+
+        let new_tx = |payer: &Keypair, readonly: &Keypair| {
+            let instructions = vec![CompiledInstruction::new(2, &(), vec![0, 1])];
+            let message = Message::new_with_compiled_instructions(
+                1,
+                0,
+                2,
+                vec![payer.pubkey(), readonly.pubkey(), native_loader::id()],
+                Hash::default(),
+                instructions,
+            );
+            Transaction::new(&[payer], message, Hash::default())
+        };
+        let txs = vec![
+            new_tx(&keypair0, &keypair1),
+            new_tx(&keypair0, &keypair2),
+            new_tx(&keypair1, &keypair2),
+            new_tx(&keypair2, &keypair0),
+        ];
+
+        let mut contention = HashMap::new();
+        let results = accounts.lock_accounts_with_contention(&txs, None, &mut contention);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err()); // keypair0 is already locked as writable
+        assert!(results[2].is_err()); // keypair1 is locked as read-only
+        assert!(results[3].is_err()); // keypair0 can't be read while locked as writable
+        assert_eq!(contention.get(&keypair0.pubkey()), Some(&2));
+        assert_eq!(contention.get(&keypair1.pubkey()), Some(&1));
+        assert_eq!(contention.get(&keypair2.pubkey()), None);
+        accounts.unlock_accounts(&txs, None, &results);
+    }
+
     #[test]
     fn test_accounts_locks_multithreaded() {
         let counter = Arc::new(AtomicU64::new(0));
@@ -1368,6 +1497,7 @@ mod tests {
             transaction_accounts0,
             transaction_loaders0,
             transaction_rents0,
+            0,
         ));
 
         let transaction_accounts1 = vec![account1, account2.clone()];
@@ -1377,6 +1507,7 @@ mod tests {
             transaction_accounts1,
             transaction_loaders1,
             transaction_rents1,
+            0,
         ));
 
         let mut loaded = vec![loaded0, loaded1];
@@ -610,6 +610,38 @@ impl AccountsDB {
         collector
     }
 
+    /// Like `scan_accounts()`, but shards the index across the threads of the accounts pool and
+    /// skips versions stored after `max_slot`. Returns every visible account `filter` accepts.
+    pub fn scan_accounts_parallel<F>(
+        &self,
+        ancestors: &HashMap<Slot, usize>,
+        max_slot: Slot,
+        filter: F,
+    ) -> Vec<(Pubkey, Account, Slot)>
+    where
+        F: Fn(&Pubkey, &Account) -> bool + Send + Sync,
+    {
+        let accounts_index = self.accounts_index.read().unwrap();
+        let storage = self.storage.read().unwrap();
+        self.thread_pool.install(|| {
+            accounts_index.par_scan_accounts(ancestors, max_slot, |pubkey, (account_info, slot)| {
+                let account = storage
+                    .0
+                    .get(&slot)
+                    .and_then(|storage_map| storage_map.get(&account_info.id))?
+                    .accounts
+                    .get_account(account_info.offset)?
+                    .0
+                    .clone_account();
+                if filter(pubkey, &account) {
+                    Some((*pubkey, account, slot))
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
     /// Scan a specific slot through all the account storage in parallel with sequential read
     // PERF: Sequentially read each storage entry in parallel
     pub fn scan_account_storage<F, B>(&self, slot_id: Slot, scan_func: F) -> Vec<B>
@@ -680,6 +712,39 @@ impl AccountsDB {
         Self::load(&storage, ancestors, &accounts_index, pubkey)
     }
 
+    /// Data length of a stored account version, read from its meta without copying the data out.
+    /// Accounts without lamports hold no data.
+    fn stored_data_len(storage: &AccountStorage, slot: Slot, info: &AccountInfo) -> Option<usize> {
+        if info.lamports == 0 {
+            return None;
+        }
+        let store = storage.0.get(&slot)?.get(&info.id)?;
+        let (stored_account, _) = store.accounts.get_account(info.offset)?;
+        Some(stored_account.meta.data_len as usize)
+    }
+
+    pub fn load_data_len_slow(&self, ancestors: &HashMap<Slot, usize>, pubkey: &Pubkey) -> usize {
+        let accounts_index = self.accounts_index.read().unwrap();
+        let storage = self.storage.read().unwrap();
+        accounts_index
+            .get(pubkey, ancestors)
+            .and_then(|(lock, index)| {
+                Self::stored_data_len(&storage, lock[index].0, &lock[index].1)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Total bytes of data in the accounts visible from `ancestors`
+    pub fn data_len(&self, ancestors: &HashMap<Slot, usize>) -> u64 {
+        let accounts_index = self.accounts_index.read().unwrap();
+        let storage = self.storage.read().unwrap();
+        let mut data_len = 0;
+        accounts_index.scan_accounts(ancestors, |_pubkey, (account_info, slot)| {
+            data_len += Self::stored_data_len(&storage, slot, account_info).unwrap_or(0) as u64;
+        });
+        data_len
+    }
+
     fn find_storage_candidate(&self, slot_id: Slot) -> Arc<AccountStorageEntry> {
         let mut create_extra = false;
         let stores = self.storage.read().unwrap();
@@ -1707,6 +1772,37 @@ pub mod tests {
         assert_eq!(accounts.len(), 2);
     }
 
+    #[test]
+    fn test_accountsdb_scan_accounts_parallel() {
+        solana_logger::setup();
+        let db = AccountsDB::new(None);
+        let key = Pubkey::default();
+        let key0 = Pubkey::new_rand();
+        let account0 = Account::new(1, 0, &key);
+        db.store(0, &[(&key0, &account0)]);
+
+        let key1 = Pubkey::new_rand();
+        let account1 = Account::new(2, 0, &key);
+        db.store(1, &[(&key1, &account1)]);
+        let account0_1 = Account::new(3, 0, &key);
+        db.store(1, &[(&key0, &account0_1)]);
+
+        let ancestors = vec![(1, 1), (0, 0)].into_iter().collect();
+        let mut accounts = db.scan_accounts_parallel(&ancestors, 1, |_, _| true);
+        accounts.sort_by_key(|(_, account, _)| account.lamports);
+        assert_eq!(
+            accounts,
+            vec![(key1, account1.clone(), 1), (key0, account0_1, 1)]
+        );
+
+        // slot 1 is past the boundary
+        let accounts = db.scan_accounts_parallel(&ancestors, 0, |_, _| true);
+        assert_eq!(accounts, vec![(key0, account0, 0)]);
+
+        let accounts = db.scan_accounts_parallel(&ancestors, 1, |pubkey, _| *pubkey == key1);
+        assert_eq!(accounts, vec![(key1, account1, 1)]);
+    }
+
     #[test]
     fn test_store_large_account() {
         solana_logger::setup();
@@ -1,3 +1,4 @@
+use rayon::prelude::*;
 use solana_sdk::pubkey::Pubkey;
 use std::collections::{HashMap, HashSet};
 use std::sync::{RwLock, RwLockReadGuard};
@@ -29,6 +30,29 @@ impl<T: Clone> AccountsIndex<T> {
         }
     }
 
+    /// call func with every pubkey and index visible from a given set of ancestors and stored no
+    /// later than `max_slot`, sharding the index across the threads of the current rayon pool
+    pub fn par_scan_accounts<F, B>(
+        &self,
+        ancestors: &HashMap<Slot, usize>,
+        max_slot: Slot,
+        func: F,
+    ) -> Vec<B>
+    where
+        F: Fn(&Pubkey, (&T, Slot)) -> Option<B> + Send + Sync,
+        B: Send,
+        T: Send + Sync,
+    {
+        self.account_maps
+            .par_iter()
+            .filter_map(|(pubkey, list)| {
+                let list_r = list.read().unwrap();
+                let index = self.latest_slot_at_or_before(ancestors, max_slot, &list_r)?;
+                func(pubkey, (&list_r[index].1, list_r[index].0))
+            })
+            .collect()
+    }
+
     pub fn purge(&mut self, pubkey: &Pubkey) -> Vec<(Slot, T)> {
         let mut list = self.account_maps.get(&pubkey).unwrap().write().unwrap();
         let reclaims = list
@@ -43,10 +67,23 @@ impl<T: Clone> AccountsIndex<T> {
     // find the latest slot and T in a list for a given ancestor
     // returns index into 'list' if found, None if not.
     fn latest_slot(&self, ancestors: &HashMap<Slot, usize>, list: &[(Slot, T)]) -> Option<usize> {
+        self.latest_slot_at_or_before(ancestors, Slot::max_value(), list)
+    }
+
+    // like latest_slot(), ignoring entries stored after max_slot
+    fn latest_slot_at_or_before(
+        &self,
+        ancestors: &HashMap<Slot, usize>,
+        max_slot: Slot,
+        list: &[(Slot, T)],
+    ) -> Option<usize> {
         let mut max = 0;
         let mut rv = None;
         for (i, (slot, _t)) in list.iter().rev().enumerate() {
-            if *slot >= max && (ancestors.get(slot).is_some() || self.is_root(*slot)) {
+            if *slot >= max
+                && *slot <= max_slot
+                && (ancestors.get(slot).is_some() || self.is_root(*slot))
+            {
                 rv = Some((list.len() - 1) - i);
                 max = *slot;
             }
@@ -262,6 +299,46 @@ mod tests {
         assert_eq!(list[idx], (0, true));
     }
 
+    #[test]
+    fn test_par_scan_accounts() {
+        let key0 = Keypair::new().pubkey();
+        let key1 = Keypair::new().pubkey();
+        let mut index = AccountsIndex::<u64>::default();
+        let mut gc = Vec::new();
+        index.insert(0, &key0, 0, &mut gc);
+        index.insert(0, &key1, 0, &mut gc);
+        index.insert(1, &key0, 1, &mut gc);
+        index.add_root(0);
+        let ancestors = vec![(1, 0)].into_iter().collect();
+
+        let scan = |ancestors, max_slot| {
+            let mut found =
+                index.par_scan_accounts(ancestors, max_slot, |pubkey, (value, slot)| {
+                    Some((*pubkey, *value, slot))
+                });
+            found.sort();
+            found
+        };
+        let mut expected = vec![(key0, 1, 1), (key1, 0, 0)];
+        expected.sort();
+        assert_eq!(scan(&ancestors, 1), expected);
+
+        // the version of slot 1 is past the boundary
+        let mut expected = vec![(key0, 0, 0), (key1, 0, 0)];
+        expected.sort();
+        assert_eq!(scan(&ancestors, 0), expected);
+        assert_eq!(scan(&HashMap::new(), 1), expected);
+
+        let found = index.par_scan_accounts(&ancestors, 1, |pubkey, _| {
+            if *pubkey == key1 {
+                Some(*pubkey)
+            } else {
+                None
+            }
+        });
+        assert_eq!(found, vec![key1]);
+    }
+
     #[test]
     fn test_is_purged() {
         let mut index = AccountsIndex::<bool>::default();
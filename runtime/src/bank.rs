@@ -3,10 +3,12 @@
 //! on behalf of the caller, and a low-level API for when they have
 //! already been signed and verified.
 use crate::{
-    accounts::{Accounts, TransactionLoadResult},
+    accounts::{Accounts, AccountsScanFilter, TransactionLoadResult},
     accounts_db::{AccountStorageEntry, AccountsDBSerialize, AppendVecId, ErrorCounters},
     blockhash_queue::BlockhashQueue,
+    epoch_stakes::EpochStakes,
     message_processor::{MessageProcessor, ProcessInstruction},
+    native_program_registry::{NativeProgram, NativeProgramError, NativeProgramRegistry},
     rent_collector::RentCollector,
     serde_utils::{
         deserialize_atomicbool, deserialize_atomicu64, serialize_atomicbool, serialize_atomicu64,
@@ -25,16 +27,18 @@ use log::*;
 use serde::{Deserialize, Serialize};
 use solana_measure::measure::Measure;
 use solana_metrics::{
-    datapoint_debug, inc_new_counter_debug, inc_new_counter_error, inc_new_counter_info,
+    datapoint_debug, datapoint_info, inc_new_counter_debug, inc_new_counter_error,
+    inc_new_counter_info,
 };
 use solana_sdk::{
     account::Account,
-    clock::{get_segment_from_slot, Epoch, Slot, MAX_RECENT_BLOCKHASHES},
+    clock::{get_segment_from_slot, Epoch, Slot, MAX_PROCESSING_AGE, MAX_RECENT_BLOCKHASHES},
     epoch_schedule::EpochSchedule,
     fee_calculator::FeeCalculator,
     genesis_config::GenesisConfig,
     hash::{hashv, Hash},
     inflation::Inflation,
+    message::Message,
     native_loader,
     pubkey::Pubkey,
     signature::{Keypair, Signature},
@@ -45,15 +49,24 @@ use solana_sdk::{
     transaction::{Result, Transaction, TransactionError},
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::{BufReader, Cursor, Error as IOError, Read},
+    ops::Range,
     path::Path,
     sync::atomic::{AtomicBool, AtomicU64, Ordering},
-    sync::{Arc, RwLock, RwLockReadGuard},
+    sync::mpsc::{channel, Receiver, Sender},
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard},
 };
 
 pub const SECONDS_PER_YEAR: f64 = (365.25 * 24.0 * 60.0 * 60.0);
 
+/// Number of the most contended accounts reported when a bank is frozen
+pub const MAX_REPORTED_CONTENDED_ACCOUNTS: usize = 5;
+
+/// Default limit on how many bytes of account data transactions may add in a single block, once
+/// the limit is activated with `set_max_accounts_data_growth()`
+pub const DEFAULT_MAX_ACCOUNTS_DATA_GROWTH_PER_BLOCK: u64 = 100 * 1024 * 1024;
+
 type BankStatusCache = StatusCache<Result<()>>;
 
 #[derive(Default)]
@@ -153,6 +166,34 @@ impl StatusCacheRc {
 
 pub type EnteredEpochCallback = Box<dyn Fn(&mut Bank) -> () + Sync + Send>;
 
+/// The rewards computed when the cluster crossed into a new epoch
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct EpochRewards {
+    /// The epoch the rewards were computed for
+    pub epoch: Epoch,
+
+    /// Lamports of inflation set aside for validators and their stakers
+    pub validator_rewards: u64,
+
+    /// Stake-weighted vote credits claimed by the epoch
+    pub validator_points: u64,
+
+    /// Lamports paid per validator point
+    pub validator_point_value: f64,
+
+    /// Lamports of inflation set aside for storage accounts
+    pub storage_rewards: u64,
+
+    /// Storage credits claimed by the epoch
+    pub storage_points: u64,
+
+    /// Lamports paid per storage point
+    pub storage_point_value: f64,
+
+    /// The storage credits each storage account claimed
+    pub storage_account_points: HashMap<Pubkey, u64>,
+}
+
 /// Manager for the state of all accounts and programs after processing its entries.
 #[derive(Default, Deserialize, Serialize)]
 pub struct Bank {
@@ -195,6 +236,19 @@ pub struct Bank {
     #[serde(deserialize_with = "deserialize_atomicu64")]
     capitalization: AtomicU64,
 
+    /// Total bytes of account data, recomputed from the accounts on snapshot restore
+    #[serde(skip)]
+    accounts_data_len: AtomicU64,
+
+    /// Bytes of account data added by transactions in this slot
+    #[serde(skip)]
+    accounts_data_growth: AtomicU64,
+
+    /// Transactions that would grow account data past this many bytes in this slot fail,
+    /// unlimited until activated by the `EnteredEpochCallback`
+    #[serde(skip)]
+    max_accounts_data_growth: u64,
+
     // Bank max_tick_height
     max_tick_height: u64,
 
@@ -249,6 +303,17 @@ pub struct Bank {
     ///   a leader schedule calculation boundary
     epoch_stakes: HashMap<Epoch, Stakes>,
 
+    /// snapshots of `epoch_stakes` handed out by `epoch_stakes()`, built on first use
+    #[serde(skip)]
+    epoch_stakes_snapshots: RwLock<HashMap<Epoch, EpochStakes>>,
+
+    /// Notified with the new snapshot whenever a bank saves off stakes for a new epoch
+    #[serde(skip)]
+    epoch_stakes_subscribers: Arc<Mutex<Vec<Sender<EpochStakes>>>>,
+
+    /// rewards computed at the most recent epoch boundary
+    epoch_rewards: Option<EpochRewards>,
+
     /// A boolean reflecting whether any entries were recorded into the PoH
     /// stream for the slot == self.slot
     #[serde(serialize_with = "serialize_atomicbool")]
@@ -266,6 +331,10 @@ pub struct Bank {
     /// Last time when the cluster info vote listener has synced with this bank
     #[serde(skip)]
     pub last_vote_sync: AtomicU64,
+
+    /// Number of transactions in this bank that each account's lock kept from being processed
+    #[serde(skip)]
+    account_lock_contention: Mutex<HashMap<Pubkey, u64>>,
 }
 
 impl Default for BlockhashQueue {
@@ -282,6 +351,7 @@ impl Bank {
     pub fn new_with_paths(genesis_config: &GenesisConfig, paths: Option<String>) -> Self {
         let mut bank = Self::default();
         bank.ancestors.insert(bank.slot(), 0);
+        bank.max_accounts_data_growth = std::u64::MAX;
         bank.rc.accounts = Arc::new(Accounts::new(paths));
         bank.process_genesis_config(genesis_config);
         // genesis needs stakes for all epochs up to the epoch implied by
@@ -341,10 +411,18 @@ impl Bank {
                 parent.signature_count() as usize,
             ),
             capitalization: AtomicU64::new(parent.capitalization()),
+            accounts_data_len: AtomicU64::new(parent.accounts_data_len()),
+            accounts_data_growth: AtomicU64::new(0),
+            max_accounts_data_growth: parent.max_accounts_data_growth,
             inflation: parent.inflation.clone(),
             transaction_count: AtomicU64::new(parent.transaction_count()),
             stakes: RwLock::new(parent.stakes.read().unwrap().clone_with_epoch(epoch)),
             epoch_stakes: parent.epoch_stakes.clone(),
+            epoch_stakes_snapshots: RwLock::new(
+                parent.epoch_stakes_snapshots.read().unwrap().clone(),
+            ),
+            epoch_stakes_subscribers: parent.epoch_stakes_subscribers.clone(),
+            epoch_rewards: parent.epoch_rewards.clone(),
             storage_accounts: RwLock::new(parent.storage_accounts.read().unwrap().clone()),
             parent_hash: parent.hash(),
             collector_id: *collector_id,
@@ -354,9 +432,10 @@ impl Bank {
             is_delta: AtomicBool::new(false),
             tick_height: AtomicU64::new(parent.tick_height.load(Ordering::Relaxed)),
             signature_count: AtomicU64::new(0),
-            message_processor: MessageProcessor::default(),
+            message_processor: MessageProcessor::new_from_parent(&parent.message_processor),
             entered_epoch_callback: parent.entered_epoch_callback.clone(),
             last_vote_sync: AtomicU64::new(parent.last_vote_sync.load(Ordering::Relaxed)),
+            account_lock_contention: Mutex::new(HashMap::new()),
         };
 
         datapoint_debug!(
@@ -381,6 +460,7 @@ impl Bank {
         if new.epoch_stakes.get(&leader_schedule_epoch).is_none() {
             new.epoch_stakes
                 .insert(leader_schedule_epoch, new.stakes.read().unwrap().clone());
+            new.notify_epoch_stakes_subscribers(leader_schedule_epoch);
         }
 
         new.ancestors.insert(new.slot(), 0);
@@ -411,8 +491,9 @@ impl Bank {
             &BankRc::new(account_paths, id, bank.slot()),
             &status_cache_rc,
         );
-        bank.process_genesis_config(genesis_config);
         bank.ancestors.insert(0, 0);
+        bank.max_accounts_data_growth = std::u64::MAX;
+        bank.process_genesis_config(genesis_config);
         bank
     }
 
@@ -531,7 +612,12 @@ impl Bank {
 
         let storage_rewards = (*inflation).storage(year) * self.capitalization() as f64 * period;
 
-        let storage_points = self.storage_accounts.write().unwrap().claim_points();
+        let storage_account_points = self
+            .storage_accounts
+            .write()
+            .unwrap()
+            .claim_points_by_account();
+        let storage_points = storage_account_points.values().sum::<u64>();
 
         let (validator_point_value, storage_point_value) = self.check_point_values(
             validator_rewards / validator_points as f64,
@@ -542,6 +628,17 @@ impl Bank {
             &sysvar::rewards::create_account(1, validator_point_value, storage_point_value),
         );
 
+        self.epoch_rewards = Some(EpochRewards {
+            epoch,
+            validator_rewards: validator_rewards as u64,
+            validator_points,
+            validator_point_value,
+            storage_rewards: storage_rewards as u64,
+            storage_points,
+            storage_point_value,
+            storage_account_points,
+        });
+
         self.capitalization.fetch_add(
             (validator_rewards + storage_rewards) as u64,
             Ordering::Relaxed,
@@ -608,6 +705,35 @@ impl Bank {
     pub fn freeze(&self) {
         if self.set_hash() {
             self.update_slot_hashes();
+            self.report_account_lock_contention();
+        }
+    }
+
+    /// The accounts whose locks kept the most transactions in this bank from being processed,
+    /// with the number of transactions each one held up, most contended first
+    pub fn account_lock_contention(&self, limit: usize) -> Vec<(Pubkey, u64)> {
+        let mut contention: Vec<_> = self
+            .account_lock_contention
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(pubkey, count)| (*pubkey, *count))
+            .collect();
+        contention.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        contention.truncate(limit);
+        contention
+    }
+
+    fn report_account_lock_contention(&self) {
+        let contention = self.account_lock_contention(MAX_REPORTED_CONTENDED_ACCOUNTS);
+        for (rank, (pubkey, count)) in contention.iter().enumerate() {
+            datapoint_info!(
+                "bank-account_lock_contention",
+                ("slot", self.slot(), i64),
+                ("rank", rank, i64),
+                ("pubkey", pubkey.to_string(), String),
+                ("count", *count, i64)
+            );
         }
     }
 
@@ -750,6 +876,16 @@ impl Bank {
         }
     }
 
+    /// The last slot in which a leader will still process transactions that use `blockhash`,
+    /// or None if the blockhash is unknown to this bank
+    pub fn get_blockhash_last_valid_slot(&self, blockhash: &Hash) -> Option<Slot> {
+        let blockhash_queue = self.blockhash_queue.read().unwrap();
+        // one blockhash is registered per slot
+        blockhash_queue
+            .get_hash_age(blockhash)
+            .map(|age| self.slot + (MAX_PROCESSING_AGE as u64).saturating_sub(age))
+    }
+
     /// Forget all signatures. Useful for benchmarking.
     pub fn clear_signatures(&self) {
         self.src.status_cache.write().unwrap().clear_signatures();
@@ -759,6 +895,7 @@ impl Bank {
         match result {
             Ok(_) => true,
             Err(TransactionError::InstructionError(_, _)) => true,
+            Err(TransactionError::MaxAccountsDataGrowthExceeded) => true,
             Err(_) => false,
         }
     }
@@ -822,13 +959,84 @@ impl Bank {
         txs: &'b [Transaction],
         iteration_order: Option<Vec<usize>>,
     ) -> TransactionBatch<'a, 'b> {
-        let results = self
-            .rc
-            .accounts
-            .lock_accounts(txs, iteration_order.as_ref().map(|v| v.as_slice()));
+        let mut contention = HashMap::new();
+        let results = self.rc.accounts.lock_accounts_with_contention(
+            txs,
+            iteration_order.as_ref().map(|v| v.as_slice()),
+            &mut contention,
+        );
+        self.record_lock_contention(contention);
         TransactionBatch::new(results, &self, txs, iteration_order)
     }
 
+    fn prepare_sanitized_batch<'a, 'b>(
+        &'a self,
+        txs: &'b [Transaction],
+    ) -> TransactionBatch<'a, 'b> {
+        let mut contention = HashMap::new();
+        let results =
+            self.rc
+                .accounts
+                .lock_sanitized_accounts_with_contention(txs, None, &mut contention);
+        self.record_lock_contention(contention);
+        TransactionBatch::new(results, &self, txs, None)
+    }
+
+    fn record_lock_contention(&self, contention: HashMap<Pubkey, u64>) {
+        if !contention.is_empty() {
+            let mut account_lock_contention = self.account_lock_contention.lock().unwrap();
+            for (pubkey, count) in contention {
+                *account_lock_contention.entry(pubkey).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// Split `txs` into consecutive runs in which no two transactions lock the same account
+    /// where either lock is writable. The runs keep the order of `txs`, so executing them one
+    /// after the other gives the same result as executing `txs` in order. Transactions that fail
+    /// `Transaction::sanitize()` take no locks, so they never conflict.
+    pub fn conflict_free_batch_ranges(txs: &[Transaction]) -> Vec<Range<usize>> {
+        let mut ranges = vec![];
+        let mut start = 0;
+        let mut write_locks = HashSet::new();
+        let mut read_locks = HashSet::new();
+        for (i, tx) in txs.iter().enumerate() {
+            if tx.sanitize().is_err() {
+                continue;
+            }
+            let (writable_keys, readonly_keys) = tx.message().get_account_keys_by_lock_type();
+            let conflicts = writable_keys
+                .iter()
+                .any(|k| write_locks.contains(*k) || read_locks.contains(*k))
+                || readonly_keys.iter().any(|k| write_locks.contains(*k));
+            if conflicts {
+                ranges.push(start..i);
+                start = i;
+                write_locks.clear();
+                read_locks.clear();
+            }
+            write_locks.extend(writable_keys.into_iter().cloned());
+            read_locks.extend(readonly_keys.into_iter().cloned());
+        }
+        if start < txs.len() {
+            ranges.push(start..txs.len());
+        }
+        ranges
+    }
+
+    /// Lock `txs` as the batches of `conflict_free_batch_ranges()`. A batch's accounts are
+    /// locked when the iterator yields it, so each batch should be executed and dropped before
+    /// the next one is taken. Transactions that fail `Transaction::sanitize()` take no locks and
+    /// carry their sanitize error as their lock result.
+    pub fn prepare_sanitized_batches<'a, 'b>(
+        &'a self,
+        txs: &'b [Transaction],
+    ) -> impl Iterator<Item = TransactionBatch<'a, 'b>> {
+        Self::conflict_free_batch_ranges(txs)
+            .into_iter()
+            .map(move |range| self.prepare_sanitized_batch(&txs[range]))
+    }
+
     pub fn unlock_accounts(&self, batch: &mut TransactionBatch) {
         if batch.needs_unlock {
             batch.needs_unlock = false;
@@ -1010,6 +1218,7 @@ impl Bank {
     ) -> (
         Vec<Result<TransactionLoadResult>>,
         Vec<Result<()>>,
+        Vec<i64>,
         Vec<usize>,
         u64,
         u64,
@@ -1047,15 +1256,28 @@ impl Bank {
 
         let mut execution_time = Measure::start("execution_time");
         let mut signature_count: u64 = 0;
+        // bytes of account data each transaction adds, or removes if negative
+        let mut data_deltas = Vec::with_capacity(txs.len());
         let executed: Vec<Result<()>> = loaded_accounts
             .iter_mut()
             .zip(OrderedIterator::new(txs, batch.iteration_order()))
             .map(|(accs, tx)| match accs {
-                Err(e) => Err(e.clone()),
-                Ok((accounts, loaders, _rents)) => {
+                Err(e) => {
+                    data_deltas.push(0);
+                    Err(e.clone())
+                }
+                Ok((accounts, loaders, _rents, pre_data_len)) => {
                     signature_count += u64::from(tx.message().header.num_required_signatures);
-                    self.message_processor
-                        .process_message(tx.message(), loaders, accounts)
+                    let result =
+                        self.message_processor
+                            .process_message(tx.message(), loaders, accounts);
+                    if result.is_ok() {
+                        let post_data_len = Self::writable_data_len(tx.message(), accounts);
+                        data_deltas.push(post_data_len as i64 - *pre_data_len as i64);
+                    } else {
+                        data_deltas.push(0);
+                    }
+                    result
                 }
             })
             .collect();
@@ -1093,12 +1315,92 @@ impl Bank {
         (
             loaded_accounts,
             executed,
+            data_deltas,
             retryable_txs,
             tx_count,
             signature_count,
         )
     }
 
+    /// Bytes of data in the writable accounts of `message`, the ones a commit stores. Accounts
+    /// without lamports hold no data.
+    fn writable_data_len(message: &Message, accounts: &[Account]) -> usize {
+        accounts
+            .iter()
+            .enumerate()
+            .filter(|(i, account)| message.is_writable(*i) && account.lamports > 0)
+            .map(|(_, account)| account.data.len())
+            .sum()
+    }
+
+    /// Reserves this slot's account data growth for a batch of executed transactions, failing
+    /// the ones that would grow account data past `max_accounts_data_growth` with
+    /// `MaxAccountsDataGrowthExceeded`. Returns the bytes reserved and the number of
+    /// transactions failed.
+    ///
+    /// Whether a transaction fits depends on every transaction before it in the block, so
+    /// batches must be reserved one at a time in ledger order: in entry order when replaying
+    /// and in PoH record order when leading. Execution itself can stay parallel.
+    pub fn reserve_accounts_data_growth(
+        &self,
+        iteration_order: Option<&[usize]>,
+        executed: &mut [Result<()>],
+        data_deltas: &[i64],
+    ) -> (u64, u64) {
+        let mut positions: Vec<usize> = (0..executed.len()).collect();
+        if let Some(iteration_order) = iteration_order {
+            positions.sort_by_key(|position| iteration_order[*position]);
+        }
+        let mut growth = self.accounts_data_growth.load(Ordering::Relaxed);
+        let (mut reserved, mut failed) = (0, 0);
+        for position in positions {
+            if executed[position].is_err() || data_deltas[position] <= 0 {
+                continue;
+            }
+            let delta = data_deltas[position] as u64;
+            if growth.saturating_add(delta) > self.max_accounts_data_growth {
+                executed[position] = Err(TransactionError::MaxAccountsDataGrowthExceeded);
+                failed += 1;
+            } else {
+                growth += delta;
+                reserved += delta;
+            }
+        }
+        self.accounts_data_growth
+            .fetch_add(reserved, Ordering::Relaxed);
+        if failed > 0 {
+            inc_new_counter_error!("bank-process_transactions-max_data_growth", failed as usize);
+        }
+        (reserved, failed)
+    }
+
+    /// Gives back growth reserved for transactions that ended up not being committed
+    pub fn release_accounts_data_growth(&self, reserved: u64) {
+        self.accounts_data_growth
+            .fetch_sub(reserved, Ordering::Relaxed);
+    }
+
+    fn update_accounts_data_len(&self, delta: i64) {
+        if delta >= 0 {
+            self.accounts_data_len
+                .fetch_add(delta as u64, Ordering::Relaxed);
+            return;
+        }
+        let shrink = delta.abs() as u64;
+        let mut current = self.accounts_data_len.load(Ordering::Relaxed);
+        loop {
+            match self.accounts_data_len.compare_exchange(
+                current,
+                current.saturating_sub(shrink),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
     fn filter_program_errors_and_collect_fee(
         &self,
         txs: &[Transaction],
@@ -1117,7 +1419,8 @@ impl Bank {
 
                 let message = tx.message();
                 match *res {
-                    Err(TransactionError::InstructionError(_, _)) => {
+                    Err(TransactionError::InstructionError(_, _))
+                    | Err(TransactionError::MaxAccountsDataGrowthExceeded) => {
                         // credit the transaction fee even in case of InstructionError
                         // necessary to withdraw from account[0] here because previous
                         // work of doing so (in accounts.load()) is ignored by store_account()
@@ -1138,12 +1441,14 @@ impl Bank {
         results
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn commit_transactions(
         &self,
         txs: &[Transaction],
         iteration_order: Option<&[usize]>,
         loaded_accounts: &mut [Result<TransactionLoadResult>],
         executed: &[Result<()>],
+        data_deltas: &[i64],
         tx_count: u64,
         signature_count: u64,
     ) -> Vec<Result<()>> {
@@ -1172,6 +1477,14 @@ impl Bank {
         );
 
         self.update_cached_accounts(txs, iteration_order, executed, loaded_accounts);
+        self.update_accounts_data_len(
+            executed
+                .iter()
+                .zip(data_deltas)
+                .filter(|(result, _)| result.is_ok())
+                .map(|(_, data_delta)| data_delta)
+                .sum(),
+        );
 
         // once committed there is no way to unroll
         write_time.stop();
@@ -1181,21 +1494,26 @@ impl Bank {
     }
 
     /// Process a batch of transactions.
+    /// Batches executed concurrently must reserve their account data growth in ledger order
+    /// instead, see `reserve_accounts_data_growth`.
     #[must_use]
     pub fn load_execute_and_commit_transactions(
         &self,
         batch: &TransactionBatch,
         max_age: usize,
     ) -> Vec<Result<()>> {
-        let (mut loaded_accounts, executed, _, tx_count, signature_count) =
+        let (mut loaded_accounts, mut executed, data_deltas, _, tx_count, signature_count) =
             self.load_and_execute_transactions(batch, max_age);
+        let (_, failed) =
+            self.reserve_accounts_data_growth(batch.iteration_order(), &mut executed, &data_deltas);
 
         self.commit_transactions(
             batch.transactions(),
             batch.iteration_order(),
             &mut loaded_accounts,
             &executed,
-            tx_count,
+            &data_deltas,
+            tx_count - failed,
             signature_count,
         )
     }
@@ -1206,6 +1524,17 @@ impl Bank {
         self.load_execute_and_commit_transactions(&batch, MAX_RECENT_BLOCKHASHES)
     }
 
+    /// Execute `tx` against this bank without committing it, and return its result
+    pub fn simulate_transaction(&self, tx: Transaction) -> Result<()> {
+        let txs = vec![tx];
+        let batch = self
+            .prepare_sanitized_batches(&txs)
+            .next()
+            .expect("a single transaction makes a single batch");
+        let (_, results, ..) = self.load_and_execute_transactions(&batch, MAX_RECENT_BLOCKHASHES);
+        results[0].clone()
+    }
+
     /// Create, sign, and process a Transaction from `keypair` to `to` of
     /// `n` lamports where `blockhash` is the last Entry ID observed by the client.
     pub fn transfer(&self, n: u64, keypair: &Keypair, to: &Pubkey) -> Result<Signature> {
@@ -1238,6 +1567,27 @@ impl Bank {
     }
 
     pub fn store_account(&self, pubkey: &Pubkey, account: &Account) {
+        let old_data_len = self
+            .rc
+            .accounts
+            .accounts_db
+            .load_data_len_slow(&self.ancestors, pubkey);
+        self.store_loaded_account(pubkey, account, old_data_len);
+    }
+
+    /// Bytes of data `account` holds in the accounts db, accounts without lamports hold none
+    fn stored_data_len(account: &Account) -> usize {
+        if account.lamports > 0 {
+            account.data.len()
+        } else {
+            0
+        }
+    }
+
+    /// Store over an account the caller has already loaded, with `old_data_len` its
+    /// `stored_data_len()`, sparing `store_account()`'s lookup of the old data length
+    fn store_loaded_account(&self, pubkey: &Pubkey, account: &Account, old_data_len: usize) {
+        self.update_accounts_data_len(Self::stored_data_len(account) as i64 - old_data_len as i64);
         self.rc.accounts.store_slow(self.slot(), pubkey, account);
 
         if Stakes::is_stake(account) {
@@ -1257,8 +1607,9 @@ impl Bank {
                     return Err(TransactionError::InsufficientFundsForFee);
                 }
 
+                let old_data_len = Self::stored_data_len(&account);
                 account.lamports -= lamports;
-                self.store_account(pubkey, &account);
+                self.store_loaded_account(pubkey, &account, old_data_len);
 
                 Ok(())
             }
@@ -1268,8 +1619,9 @@ impl Bank {
 
     pub fn deposit(&self, pubkey: &Pubkey, lamports: u64) {
         let mut account = self.get_account(pubkey).unwrap_or_default();
+        let old_data_len = Self::stored_data_len(&account);
         account.lamports += lamports;
-        self.store_account(pubkey, &account);
+        self.store_loaded_account(pubkey, &account, old_data_len);
     }
 
     pub fn accounts(&self) -> Arc<Accounts> {
@@ -1281,10 +1633,25 @@ impl Bank {
         self.src.status_cache = status_cache_rc.status_cache.clone()
     }
 
+    /// Recompute the account data size snapshots don't carry once the accounts of a bank
+    /// restored from a snapshot are loaded.  The growth limit isn't carried either and starts
+    /// out unlimited, callers set it again for the bank's epoch.
+    pub fn restore_accounts_data_len(&mut self) {
+        self.accounts_data_len =
+            AtomicU64::new(self.rc.accounts.accounts_db.data_len(&self.ancestors));
+        self.max_accounts_data_growth = std::u64::MAX;
+    }
+
     pub fn set_parent(&mut self, parent: &Arc<Bank>) {
         self.rc.parent = RwLock::new(Some(parent.clone()));
     }
 
+    /// Limit the account data transactions may add in this bank and its descendants,
+    /// e.g. from an `EnteredEpochCallback`
+    pub fn set_max_accounts_data_growth(&mut self, max_accounts_data_growth: u64) {
+        self.max_accounts_data_growth = max_accounts_data_growth;
+    }
+
     pub fn set_inflation(&self, inflation: Inflation) {
         *self.inflation.write().unwrap() = inflation;
     }
@@ -1301,9 +1668,24 @@ impl Bank {
     }
 
     pub fn get_program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+        self.scan_accounts_parallel(&AccountsScanFilter {
+            owner: Some(*program_id),
+            ..AccountsScanFilter::default()
+        })
+    }
+
+    /// Every account of this bank `filter` accepts, the accounts index is sharded across the
+    /// accounts thread pool. With `filter.rooted_only` only versions stored in rooted slots up to
+    /// this bank's slot are visible.
+    pub fn scan_accounts_parallel(&self, filter: &AccountsScanFilter) -> Vec<(Pubkey, Account)> {
+        let max_slot = if filter.rooted_only {
+            self.slot()
+        } else {
+            Slot::max_value()
+        };
         self.rc
             .accounts
-            .load_by_program(&self.ancestors, program_id)
+            .load_by_filter(&self.ancestors, max_slot, filter)
     }
 
     pub fn get_program_accounts_modified_since_parent(
@@ -1315,6 +1697,10 @@ impl Bank {
             .load_by_program_slot(self.slot(), program_id)
     }
 
+    pub fn get_all_accounts_modified_since_parent(&self) -> Vec<(Pubkey, Account)> {
+        self.rc.accounts.load_by_slot(self.slot())
+    }
+
     pub fn get_account_modified_since_parent(&self, pubkey: &Pubkey) -> Option<(Account, Slot)> {
         let just_self: HashMap<u64, usize> = vec![(self.slot(), 0)].into_iter().collect();
         self.rc.accounts.load_slow(&just_self, pubkey)
@@ -1351,6 +1737,39 @@ impl Bank {
             .map(|v| v.1)
     }
 
+    /// Look up a signature in the status cache under the blockhash of its transaction, which
+    /// avoids scanning every cached blockhash as `get_signature_status` has to
+    pub fn get_signature_status_with_blockhash(
+        &self,
+        signature: &Signature,
+        blockhash: &Hash,
+    ) -> Option<Result<()>> {
+        let rcache = self.src.status_cache.read().unwrap();
+        rcache
+            .get_signature_status(signature, blockhash, &self.ancestors)
+            .map(|v| v.1)
+    }
+
+    /// Bulk variant of `get_signature_confirmation_status`, the status cache is locked once
+    /// for all `signatures`
+    pub fn get_signature_confirmation_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> Vec<Option<(usize, Result<()>)>> {
+        let rcache = self.src.status_cache.read().unwrap();
+        signatures
+            .iter()
+            .map(|signature| rcache.get_signature_status_slow(signature, &self.ancestors))
+            .collect()
+    }
+
+    pub fn get_signature_statuses(&self, signatures: &[Signature]) -> Vec<Option<Result<()>>> {
+        self.get_signature_confirmation_statuses(signatures)
+            .into_iter()
+            .map(|status| status.map(|v| v.1))
+            .collect()
+    }
+
     pub fn has_signature(&self, signature: &Signature) -> bool {
         self.get_signature_confirmation_status(signature).is_some()
     }
@@ -1434,6 +1853,20 @@ impl Bank {
         self.capitalization.load(Ordering::Relaxed)
     }
 
+    /// Return the total bytes of account data
+    pub fn accounts_data_len(&self) -> u64 {
+        self.accounts_data_len.load(Ordering::Relaxed)
+    }
+
+    /// Return the bytes of account data added by transactions in this slot
+    pub fn accounts_data_growth(&self) -> u64 {
+        self.accounts_data_growth.load(Ordering::Relaxed)
+    }
+
+    pub fn max_accounts_data_growth(&self) -> u64 {
+        self.max_accounts_data_growth
+    }
+
     /// Return this bank's max_tick_height
     pub fn max_tick_height(&self) -> u64 {
         self.max_tick_height
@@ -1511,12 +1944,56 @@ impl Bank {
         self.stakes.read().unwrap().stake_accounts().clone()
     }
 
+    /// rewards computed when this bank's fork last crossed an epoch boundary
+    pub fn epoch_rewards(&self) -> Option<&EpochRewards> {
+        self.epoch_rewards.as_ref()
+    }
+
+    /// rewards each stake account could redeem at the given point value, as
+    ///   (stake pubkey, voter pubkey, voter rewards, staker rewards)
+    pub fn redeemable_stake_rewards(&self, point_value: f64) -> Vec<(Pubkey, Pubkey, u64, u64)> {
+        self.stakes.read().unwrap().redeemable_rewards(point_value)
+    }
+
     /// vote accounts for the specific epoch along with the stake
     ///   attributed to each account
     pub fn epoch_vote_accounts(&self, epoch: Epoch) -> Option<&HashMap<Pubkey, (u64, Account)>> {
         self.epoch_stakes.get(&epoch).map(Stakes::vote_accounts)
     }
 
+    /// A snapshot of the stakes saved off for the specific epoch, which can be held on
+    ///   to outside the bank
+    pub fn epoch_stakes(&self, epoch: Epoch) -> Option<EpochStakes> {
+        if let Some(epoch_stakes) = self.epoch_stakes_snapshots.read().unwrap().get(&epoch) {
+            return Some(epoch_stakes.clone());
+        }
+        let epoch_stakes = EpochStakes::new(epoch, self.epoch_stakes.get(&epoch)?);
+        self.epoch_stakes_snapshots
+            .write()
+            .unwrap()
+            .insert(epoch, epoch_stakes.clone());
+        Some(epoch_stakes)
+    }
+
+    /// Receive the stakes of each new epoch as banks on any fork save them off.  Banks on
+    ///   different forks may send the same epoch, the most recent one belongs to the fork
+    ///   that crossed the boundary last.
+    pub fn subscribe_epoch_stakes(&self) -> Receiver<EpochStakes> {
+        let (sender, receiver) = channel();
+        self.epoch_stakes_subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    fn notify_epoch_stakes_subscribers(&self, epoch: Epoch) {
+        let mut subscribers = self.epoch_stakes_subscribers.lock().unwrap();
+        if subscribers.is_empty() {
+            return;
+        }
+        if let Some(epoch_stakes) = self.epoch_stakes(epoch) {
+            subscribers.retain(|subscriber| subscriber.send(epoch_stakes.clone()).is_ok());
+        }
+    }
+
     /// given a slot, return the epoch and offset into the epoch this slot falls
     /// e.g. with a fixed number for slots_per_epoch, the calculation is simply:
     ///
@@ -1548,6 +2025,59 @@ impl Bank {
         }
     }
 
+    /// Install the programs of `registry`, failing without installing any of them if one of
+    /// their ids is already taken by an intercepted program or a non-native account, or by a
+    /// different loadable program. Child banks keep the installed programs.
+    ///
+    /// Builtin entrypoints are function pointers and are not saved in snapshots, so a bank
+    /// deserialized from one has to be handed the same registry again before it processes
+    /// transactions. `bank_forks_utils::load` does this with `ProcessOptions::native_programs`.
+    pub fn register_native_programs(
+        &mut self,
+        registry: &NativeProgramRegistry,
+    ) -> std::result::Result<(), NativeProgramError> {
+        for (_, program_id, program) in registry.programs() {
+            if self.message_processor.has_instruction_processor(program_id) {
+                return Err(NativeProgramError::ProgramIdInUse {
+                    program_id: *program_id,
+                    claimed_by: "a builtin program".to_string(),
+                });
+            }
+            if let Some(account) = self.get_account(program_id) {
+                let claimed_by = if account.owner != native_loader::id() {
+                    Some("a non-native account".to_string())
+                } else {
+                    match program {
+                        NativeProgram::Loadable(name) if account.data != name.as_bytes() => {
+                            Some(String::from_utf8_lossy(&account.data).into_owned())
+                        }
+                        _ => None,
+                    }
+                };
+                if let Some(claimed_by) = claimed_by {
+                    return Err(NativeProgramError::ProgramIdInUse {
+                        program_id: *program_id,
+                        claimed_by,
+                    });
+                }
+            }
+        }
+
+        for (_, program_id, program) in registry.programs() {
+            match program {
+                NativeProgram::Loadable(name) => {
+                    if self.get_account(program_id).is_none() {
+                        self.register_native_instruction_processor(name, program_id);
+                    }
+                }
+                NativeProgram::Builtin(process_instruction) => {
+                    self.add_instruction_processor(*program_id, *process_instruction)
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn compare_bank(&self, dbank: &Bank) {
         assert_eq!(self.slot, dbank.slot);
         assert_eq!(self.collector_id, dbank.collector_id);
@@ -1563,6 +2093,11 @@ impl Bank {
             self.is_delta.load(Ordering::Relaxed),
             dbank.is_delta.load(Ordering::Relaxed)
         );
+        assert_eq!(self.accounts_data_len(), dbank.accounts_data_len());
+        assert_eq!(
+            self.max_accounts_data_growth,
+            dbank.max_accounts_data_growth
+        );
 
         let st = self.stakes.read().unwrap();
         let dst = dbank.stakes.read().unwrap();
@@ -1634,7 +2169,7 @@ mod tests {
         poh_config::PohConfig,
         rent::Rent,
         signature::{Keypair, KeypairUtil},
-        system_instruction,
+        system_instruction, system_program,
         sysvar::{fees::Fees, rewards::Rewards},
     };
     use solana_stake_api::stake_state::Stake;
@@ -1801,6 +2336,33 @@ mod tests {
             .map(|account| Rewards::from_account(&account).unwrap())
             .unwrap();
 
+        // verify the computation is kept around for inspection
+        let epoch_rewards = bank1.epoch_rewards().unwrap();
+        assert_eq!(epoch_rewards.epoch, bank.epoch());
+        assert_eq!(epoch_rewards.validator_points, validator_points);
+        assert_eq!(epoch_rewards.storage_points, storage_points);
+        assert_eq!(
+            epoch_rewards.validator_point_value,
+            rewards.validator_point_value
+        );
+        assert_eq!(
+            epoch_rewards.storage_point_value,
+            rewards.storage_point_value
+        );
+        assert_eq!(epoch_rewards.storage_account_points.len(), 2);
+        assert!(bank1
+            .redeemable_stake_rewards(rewards.validator_point_value)
+            .is_empty());
+
+        // once the vote account records epoch 0's credits, the stake can redeem them
+        vote_state.process_slot(MAX_LOCKOUT_HISTORY as u64 + 42, bank1.epoch());
+        vote_state.to(&mut vote_account).unwrap();
+        bank1.store_account(&vote_id, &vote_account);
+        let stake_rewards = bank1.redeemable_stake_rewards(rewards.validator_point_value);
+        assert_eq!(stake_rewards.len(), 1);
+        assert_eq!(stake_rewards[0].0, stake.0);
+        assert_eq!(stake_rewards[0].1, vote_id);
+
         assert!(
             ((rewards.validator_point_value * validator_points as f64
                 + rewards.storage_point_value * storage_points as f64)
@@ -1890,6 +2452,112 @@ mod tests {
         assert_eq!(bank.transaction_count(), 2);
     }
 
+    #[test]
+    fn test_accounts_data_growth_limit() {
+        let (genesis_config, mint_keypair) = create_genesis_config(10_000);
+        let mut bank = Bank::new(&genesis_config);
+        let genesis_data_len = bank.accounts_data_len();
+        // unlimited until activated
+        assert_eq!(bank.max_accounts_data_growth(), std::u64::MAX);
+        bank.set_max_accounts_data_growth(100);
+        let blockhash = genesis_config.hash();
+
+        let key1 = Keypair::new();
+        let tx = system_transaction::create_account(
+            &mint_keypair,
+            &key1,
+            blockhash,
+            1,
+            60,
+            &system_program::id(),
+        );
+        assert_eq!(bank.process_transaction(&tx), Ok(()));
+        assert_eq!(bank.accounts_data_growth(), 60);
+        assert_eq!(bank.accounts_data_len(), genesis_data_len + 60);
+
+        // a second allocation would exceed the limit for this block
+        let key2 = Keypair::new();
+        let tx = system_transaction::create_account(
+            &mint_keypair,
+            &key2,
+            blockhash,
+            1,
+            60,
+            &system_program::id(),
+        );
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(TransactionError::MaxAccountsDataGrowthExceeded)
+        );
+        assert_eq!(bank.get_account(&key2.pubkey()), None);
+        assert_eq!(bank.accounts_data_growth(), 60);
+        assert_eq!(bank.accounts_data_len(), genesis_data_len + 60);
+
+        // the limit applies per block
+        let bank = Arc::new(bank);
+        let bank = Bank::new_from_parent(&bank, &Pubkey::default(), 1);
+        assert_eq!(bank.accounts_data_growth(), 0);
+        assert_eq!(bank.max_accounts_data_growth(), 100);
+        let key3 = Keypair::new();
+        let tx = system_transaction::create_account(
+            &mint_keypair,
+            &key3,
+            blockhash,
+            1,
+            60,
+            &system_program::id(),
+        );
+        assert_eq!(bank.process_transaction(&tx), Ok(()));
+        assert_eq!(bank.accounts_data_len(), genesis_data_len + 120);
+
+        // deleting an account gives its data back
+        bank.store_account(&key1.pubkey(), &Account::default());
+        assert_eq!(bank.accounts_data_len(), genesis_data_len + 60);
+        assert_eq!(
+            bank.accounts_data_len(),
+            bank.rc.accounts.accounts_db.data_len(&bank.ancestors)
+        );
+
+        // so does withdrawing all of its lamports
+        assert_eq!(bank.withdraw(&key3.pubkey(), 1), Ok(()));
+        assert_eq!(bank.accounts_data_len(), genesis_data_len);
+        bank.deposit(&key3.pubkey(), 1);
+        assert_eq!(bank.accounts_data_len(), genesis_data_len);
+        assert_eq!(
+            bank.accounts_data_len(),
+            bank.rc.accounts.accounts_db.data_len(&bank.ancestors)
+        );
+    }
+
+    #[test]
+    fn test_reserve_accounts_data_growth_in_ledger_order() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(10_000);
+        let mut bank = Bank::new(&genesis_config);
+        bank.set_max_accounts_data_growth(100);
+
+        // executed as transactions 2, 0 and 1
+        let iteration_order = vec![2, 0, 1];
+        let mut executed = vec![Ok(()), Ok(()), Ok(())];
+        let data_deltas = vec![30, 60, 60];
+        assert_eq!(
+            bank.reserve_accounts_data_growth(Some(&iteration_order), &mut executed, &data_deltas),
+            (90, 1)
+        );
+        // transaction 1 comes after transaction 0 in the ledger, so it's the one that doesn't fit
+        assert_eq!(
+            executed,
+            vec![
+                Ok(()),
+                Ok(()),
+                Err(TransactionError::MaxAccountsDataGrowthExceeded)
+            ]
+        );
+        assert_eq!(bank.accounts_data_growth(), 90);
+
+        bank.release_accounts_data_growth(90);
+        assert_eq!(bank.accounts_data_growth(), 0);
+    }
+
     #[test]
     fn test_one_source_two_tx_one_batch() {
         let (genesis_config, mint_keypair) = create_genesis_config(1);
@@ -1914,17 +2582,50 @@ mod tests {
         assert_eq!(bank.get_signature_status(&t2.signatures[0]), None);
     }
 
+    #[test]
+    fn test_get_signature_statuses() {
+        let (genesis_config, mint_keypair) = create_genesis_config(10);
+        let key1 = Pubkey::new_rand();
+        let key2 = Pubkey::new_rand();
+        let bank = Bank::new(&genesis_config);
+        let blockhash = genesis_config.hash();
+
+        let t1 = system_transaction::transfer(&mint_keypair, &key1, 1, blockhash);
+        let t2 = system_transaction::transfer(&mint_keypair, &key2, 20, blockhash);
+        let t3 = system_transaction::transfer(&mint_keypair, &key2, 2, blockhash);
+        bank.process_transaction(&t1).unwrap();
+        assert!(bank.process_transaction(&t2).is_err());
+
+        assert_eq!(
+            bank.get_signature_status_with_blockhash(&t1.signatures[0], &blockhash),
+            Some(Ok(()))
+        );
+        assert_eq!(
+            bank.get_signature_status_with_blockhash(&t1.signatures[0], &Hash::default()),
+            None
+        );
+        assert_eq!(
+            bank.get_signature_statuses(&[t1.signatures[0], t2.signatures[0], t3.signatures[0]]),
+            vec![
+                Some(Ok(())),
+                Some(Err(TransactionError::InstructionError(
+                    0,
+                    InstructionError::new_result_with_negative_lamports()
+                ))),
+                None
+            ]
+        );
+    }
+
     #[test]
     fn test_one_tx_two_out_atomic_fail() {
         let (genesis_config, mint_keypair) = create_genesis_config(1);
         let key1 = Pubkey::new_rand();
         let key2 = Pubkey::new_rand();
         let bank = Bank::new(&genesis_config);
-        let instructions =
-            system_instruction::transfer_many(&mint_keypair.pubkey(), &[(key1, 1), (key2, 1)]);
-        let tx = Transaction::new_signed_instructions(
-            &[&mint_keypair],
-            instructions,
+        let tx = system_transaction::transfer_many(
+            &mint_keypair,
+            &[(key1, 1), (key2, 1)],
             genesis_config.hash(),
         );
         assert_eq!(
@@ -1945,11 +2646,9 @@ mod tests {
         let key1 = Pubkey::new_rand();
         let key2 = Pubkey::new_rand();
         let bank = Bank::new(&genesis_config);
-        let instructions =
-            system_instruction::transfer_many(&mint_keypair.pubkey(), &[(key1, 1), (key2, 1)]);
-        let tx = Transaction::new_signed_instructions(
-            &[&mint_keypair],
-            instructions,
+        let tx = system_transaction::transfer_many(
+            &mint_keypair,
+            &[(key1, 1), (key2, 1)],
             genesis_config.hash(),
         );
         bank.process_transaction(&tx).unwrap();
@@ -2364,6 +3063,10 @@ mod tests {
             bank.transfer(1, &mint_keypair, &bob.pubkey()),
             Err(TransactionError::AccountInUse)
         );
+        assert_eq!(
+            bank.account_lock_contention(MAX_REPORTED_CONTENDED_ACCOUNTS),
+            vec![(mint_keypair.pubkey(), 2)]
+        );
 
         drop(lock_result);
 
@@ -2431,6 +3134,151 @@ mod tests {
         assert!(batch2.lock_results()[0].is_ok());
     }
 
+    #[test]
+    fn test_prepare_sanitized_batches() {
+        let (genesis_config, mint_keypair) = create_genesis_config(10);
+        let bank = Bank::new(&genesis_config);
+        let key0 = Keypair::new();
+        let key1 = Keypair::new();
+        let key2 = Pubkey::new_rand();
+        let readonly_key = Pubkey::new_rand();
+
+        let read_only_tx = |keypair: &Keypair| {
+            let message = Message {
+                header: MessageHeader {
+                    num_required_signatures: 1,
+                    num_readonly_signed_accounts: 0,
+                    num_readonly_unsigned_accounts: 1,
+                },
+                account_keys: vec![keypair.pubkey(), readonly_key],
+                recent_blockhash: genesis_config.hash(),
+                instructions: vec![],
+            };
+            Transaction::new(&[keypair], message, genesis_config.hash())
+        };
+        let write_tx = |keypair: &Keypair| {
+            let message = Message {
+                header: MessageHeader {
+                    num_required_signatures: 1,
+                    num_readonly_signed_accounts: 0,
+                    num_readonly_unsigned_accounts: 0,
+                },
+                account_keys: vec![keypair.pubkey(), readonly_key],
+                recent_blockhash: genesis_config.hash(),
+                instructions: vec![],
+            };
+            Transaction::new(&[keypair], message, genesis_config.hash())
+        };
+
+        let txs = vec![
+            system_transaction::transfer(&mint_keypair, &key2, 1, genesis_config.hash()),
+            // shares a read-only account with the next transaction, which doesn't conflict
+            read_only_tx(&key0),
+            read_only_tx(&key1),
+            // the mint is written again
+            system_transaction::transfer(&mint_keypair, &key2, 1, genesis_config.hash()),
+            // writes the account read above, but it's in a later run
+            write_tx(&key0),
+            read_only_tx(&key1),
+        ];
+        assert_eq!(
+            Bank::conflict_free_batch_ranges(&txs),
+            vec![0..3, 3..5, 5..6]
+        );
+        assert!(Bank::conflict_free_batch_ranges(&[]).is_empty());
+
+        let mut num_batches = 0;
+        for batch in bank.prepare_sanitized_batches(&txs) {
+            assert!(batch.lock_results().iter().all(|result| result.is_ok()));
+            num_batches += 1;
+        }
+        assert_eq!(num_batches, 3);
+
+        // every batch was unlocked when it was dropped
+        let batch = bank.prepare_batch(&txs[..1], None);
+        assert!(batch.lock_results()[0].is_ok());
+    }
+
+    #[test]
+    fn test_bank_simulate_transaction() {
+        let (genesis_config, mint_keypair) = create_genesis_config(10);
+        let bank = Bank::new(&genesis_config);
+        let key = Pubkey::new_rand();
+
+        let tx = system_transaction::transfer(&mint_keypair, &key, 1, genesis_config.hash());
+        assert_eq!(bank.simulate_transaction(tx.clone()), Ok(()));
+        // nothing was committed
+        assert_eq!(bank.get_balance(&key), 0);
+        assert_eq!(bank.get_signature_status(&tx.signatures[0]), None);
+
+        let mut unsigned_tx = tx;
+        unsigned_tx.signatures.clear();
+        assert_eq!(
+            bank.simulate_transaction(unsigned_tx),
+            Err(TransactionError::SanitizeFailure)
+        );
+    }
+
+    #[test]
+    fn test_prepare_sanitized_batches_locks_lazily() {
+        let (genesis_config, mint_keypair) = create_genesis_config(10);
+        let bank = Bank::new(&genesis_config);
+        let key0 = Keypair::new();
+        let key1 = Pubkey::new_rand();
+
+        let mut unsigned_tx =
+            system_transaction::transfer(&mint_keypair, &key1, 1, genesis_config.hash());
+        unsigned_tx.signatures.clear();
+        let txs = vec![
+            system_transaction::transfer(&mint_keypair, &key1, 1, genesis_config.hash()),
+            unsigned_tx,
+            system_transaction::transfer(&mint_keypair, &key0.pubkey(), 1, genesis_config.hash()),
+        ];
+        assert_eq!(Bank::conflict_free_batch_ranges(&txs), vec![0..2, 2..3]);
+
+        let mut batches = bank.prepare_sanitized_batches(&txs);
+        let batch0 = batches.next().unwrap();
+        assert_eq!(
+            batch0.lock_results(),
+            &vec![Ok(()), Err(TransactionError::SanitizeFailure)]
+        );
+
+        // the second batch hasn't locked key0 yet
+        let other_txs = vec![system_transaction::transfer(
+            &key0,
+            &Pubkey::new_rand(),
+            1,
+            genesis_config.hash(),
+        )];
+        let other_batch = bank.prepare_batch(&other_txs, None);
+        assert!(other_batch.lock_results()[0].is_ok());
+        drop(other_batch);
+        drop(batch0);
+
+        let batch1 = batches.next().unwrap();
+        assert!(batch1.lock_results()[0].is_ok());
+        drop(batch1);
+        assert!(batches.next().is_none());
+
+        // dropping an unsanitized transaction doesn't release locks held by someone else
+        let held_batch = bank.prepare_batch(&other_txs, None);
+        let mut unsigned_tx = system_transaction::transfer(&key0, &key1, 1, genesis_config.hash());
+        unsigned_tx.signatures.clear();
+        let unsigned_txs = vec![unsigned_tx];
+        for batch in bank.prepare_sanitized_batches(&unsigned_txs) {
+            assert_eq!(
+                batch.lock_results(),
+                &vec![Err(TransactionError::SanitizeFailure)]
+            );
+        }
+        let batch = bank.prepare_batch(&other_txs, None);
+        assert_eq!(
+            batch.lock_results(),
+            &vec![Err(TransactionError::AccountInUse)]
+        );
+        drop(held_batch);
+    }
+
     #[test]
     fn test_bank_invalid_account_index() {
         let (genesis_config, mint_keypair) = create_genesis_config(1);
@@ -2886,6 +3734,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bank_subscribe_epoch_stakes() {
+        let leader_pubkey = Pubkey::new_rand();
+        let mut genesis_config =
+            create_genesis_config_with_leader(5, &leader_pubkey, 3).genesis_config;
+        const SLOTS_PER_EPOCH: u64 = MINIMUM_SLOTS_PER_EPOCH as u64;
+        genesis_config.epoch_schedule =
+            EpochSchedule::custom(SLOTS_PER_EPOCH, SLOTS_PER_EPOCH, false);
+
+        let parent = Arc::new(Bank::new(&genesis_config));
+        let epoch_stakes = parent.epoch_stakes(1).unwrap();
+        assert_eq!(epoch_stakes.epoch(), 1);
+        assert!(epoch_stakes.node_stake(&leader_pubkey) > 0);
+        assert_eq!(
+            epoch_stakes.vote_accounts(),
+            parent.epoch_vote_accounts(1).unwrap()
+        );
+        assert!(parent.epoch_stakes(2).is_none());
+
+        let receiver = parent.subscribe_epoch_stakes();
+
+        // no new epoch saved off, nothing sent
+        let child = Bank::new_from_parent(&parent, &leader_pubkey, 1);
+        assert!(receiver.try_recv().is_err());
+
+        // crossing into epoch 1 saves off the stakes for epoch 2
+        let child = Bank::new_from_parent(&Arc::new(child), &leader_pubkey, SLOTS_PER_EPOCH);
+        let received = receiver.try_recv().unwrap();
+        assert_eq!(received.epoch(), 2);
+        assert_eq!(Some(received), child.epoch_stakes(2));
+        assert!(parent.epoch_stakes(2).is_none());
+
+        // dropped receivers are pruned on the next send
+        drop(receiver);
+        Bank::new_from_parent(&Arc::new(child), &leader_pubkey, SLOTS_PER_EPOCH * 2);
+        assert!(parent.epoch_stakes_subscribers.lock().unwrap().is_empty());
+    }
+
     #[test]
     fn test_zero_signatures() {
         solana_logger::setup();
@@ -3178,6 +4064,7 @@ mod tests {
             .rc
             .accounts_from_stream(&mut reader, dbank_paths, copied_accounts.path())
             .unwrap();
+        dbank.restore_accounts_data_len();
         assert_eq!(dbank.get_balance(&key.pubkey()), 10);
         bank.compare_bank(&dbank);
     }
@@ -3251,6 +4138,57 @@ mod tests {
         assert_eq!(bank3.get_program_accounts(&program_id).len(), 2);
     }
 
+    #[test]
+    fn test_bank_scan_accounts_parallel() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(500);
+        let parent = Arc::new(Bank::new(&genesis_config));
+        let bank0 = Arc::new(new_from_parent(&parent));
+        let program_id = Pubkey::new(&[2; 32]);
+        let pubkey0 = Pubkey::new_rand();
+        let account0 = Account::new(1, 0, &program_id);
+        bank0.store_account(&pubkey0, &account0);
+        bank0.squash();
+
+        let bank1 = Arc::new(new_from_parent(&bank0));
+        let pubkey1 = Pubkey::new_rand();
+        let account1 = Account::new(2, 0, &program_id);
+        bank1.store_account(&pubkey1, &account1);
+        let account0_1 = Account::new(3, 0, &program_id);
+        bank1.store_account(&pubkey0, &account0_1);
+
+        let filter = AccountsScanFilter {
+            owner: Some(program_id),
+            ..AccountsScanFilter::default()
+        };
+        let mut accounts = bank1.scan_accounts_parallel(&filter);
+        accounts.sort_by_key(|(_, account)| account.lamports);
+        assert_eq!(
+            accounts,
+            vec![(pubkey1, account1), (pubkey0, account0_1.clone())]
+        );
+
+        // bank1 isn't rooted yet
+        let rooted = AccountsScanFilter {
+            rooted_only: true,
+            ..filter.clone()
+        };
+        assert_eq!(
+            bank1.scan_accounts_parallel(&rooted),
+            vec![(pubkey0, account0.clone())]
+        );
+        bank1.squash();
+        assert_eq!(bank1.scan_accounts_parallel(&rooted).len(), 2);
+        // versions rooted after bank0 are past its boundary
+        assert_eq!(
+            bank0.scan_accounts_parallel(&rooted),
+            vec![(pubkey0, account0)]
+        );
+
+        let all = bank1.scan_accounts_parallel(&AccountsScanFilter::default());
+        assert!(all.len() > 2);
+        assert!(all.contains(&(pubkey0, account0_1)));
+    }
+
     #[test]
     fn test_status_cache_ancestors() {
         let (genesis_config, _mint_keypair) = create_genesis_config(500);
@@ -3372,6 +4310,150 @@ mod tests {
         // Non-native loader accounts can not be used for instruction processing
         bank.add_instruction_processor(mint_keypair.pubkey(), mock_ix_processor);
     }
+
+    #[test]
+    fn test_register_native_programs() {
+        let (genesis_config, mint_keypair) = create_genesis_config(500);
+        let mut bank = Bank::new(&genesis_config);
+
+        fn mock_vote_processor(
+            _pubkey: &Pubkey,
+            _ka: &mut [KeyedAccount],
+            _data: &[u8],
+        ) -> std::result::Result<(), InstructionError> {
+            Err(InstructionError::CustomError(42))
+        }
+
+        // Conflicting registries install nothing
+        let loadable_id = Pubkey::new_rand();
+        let mut registry = NativeProgramRegistry::default();
+        registry
+            .register_loadable("solana_mock_program", loadable_id)
+            .unwrap();
+        registry
+            .register_builtin("mock", system_program::id(), mock_vote_processor)
+            .unwrap();
+        assert_eq!(
+            bank.register_native_programs(&registry),
+            Err(NativeProgramError::ProgramIdInUse {
+                program_id: system_program::id(),
+                claimed_by: "a builtin program".to_string(),
+            })
+        );
+        assert!(bank.get_account(&loadable_id).is_none());
+
+        let mut registry = NativeProgramRegistry::default();
+        registry
+            .register_builtin("mock", mint_keypair.pubkey(), mock_vote_processor)
+            .unwrap();
+        assert!(bank.register_native_programs(&registry).is_err());
+
+        let mut registry = NativeProgramRegistry::default();
+        registry
+            .register_loadable("solana_mock_program", loadable_id)
+            .unwrap();
+        registry
+            .register_builtin("mock_vote", solana_vote_api::id(), mock_vote_processor)
+            .unwrap();
+        bank.register_native_programs(&registry).unwrap();
+        assert_eq!(
+            bank.get_account(&loadable_id),
+            Some(native_loader::create_loadable_account(
+                "solana_mock_program"
+            ))
+        );
+
+        // A different library can't take over a registered loadable program
+        let mut registry = NativeProgramRegistry::default();
+        registry
+            .register_loadable("solana_other_program", loadable_id)
+            .unwrap();
+        assert_eq!(
+            bank.register_native_programs(&registry),
+            Err(NativeProgramError::ProgramIdInUse {
+                program_id: loadable_id,
+                claimed_by: "solana_mock_program".to_string(),
+            })
+        );
+
+        // Child banks keep intercepting the builtin program
+        let bank = new_from_parent(&Arc::new(bank));
+        let mock_account = Keypair::new();
+        let instructions = vote_instruction::create_account(
+            &mint_keypair.pubkey(),
+            &mock_account.pubkey(),
+            &VoteInit::default(),
+            1,
+        );
+        let transaction = Transaction::new_signed_instructions(
+            &[&mint_keypair, &mock_account],
+            instructions,
+            bank.last_blockhash(),
+        );
+        assert_eq!(
+            bank.process_transaction(&transaction),
+            Err(TransactionError::InstructionError(
+                1,
+                InstructionError::CustomError(42)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_register_native_programs_after_deserialize() {
+        let (genesis_config, _) = create_genesis_config(500);
+        let mut bank = Bank::new(&genesis_config);
+
+        fn mock_processor(
+            _pubkey: &Pubkey,
+            _ka: &mut [KeyedAccount],
+            _data: &[u8],
+        ) -> std::result::Result<(), InstructionError> {
+            Err(InstructionError::CustomError(42))
+        }
+
+        let builtin_id = Pubkey::new_rand();
+        let mut registry = NativeProgramRegistry::default();
+        registry
+            .register_builtin("mock", builtin_id, mock_processor)
+            .unwrap();
+        bank.register_native_programs(&registry).unwrap();
+
+        let len = serialized_size(&bank).unwrap() + serialized_size(&bank.rc).unwrap();
+        let mut buf = vec![0u8; len as usize];
+        let mut writer = Cursor::new(&mut buf[..]);
+        serialize_into(&mut writer, &bank).unwrap();
+        serialize_into(&mut writer, &bank.rc).unwrap();
+
+        let mut rdr = Cursor::new(&buf[..]);
+        let mut dbank: Bank = deserialize_from(&mut rdr).unwrap();
+        let mut reader = BufReader::new(&buf[rdr.position() as usize..]);
+        let (_accounts_dir, dbank_paths) = get_temp_accounts_paths(4).unwrap();
+        dbank.set_bank_rc(
+            &BankRc::new(dbank_paths.clone(), 0, dbank.slot()),
+            &StatusCacheRc::default(),
+        );
+        let copied_accounts = TempDir::new().unwrap();
+        copy_append_vecs(&bank.rc.accounts.accounts_db, copied_accounts.path()).unwrap();
+        dbank
+            .rc
+            .accounts_from_stream(&mut reader, dbank_paths, copied_accounts.path())
+            .unwrap();
+        dbank.restore_accounts_data_len();
+
+        // The builtin's account survives but its entrypoint doesn't
+        assert!(dbank.get_account(&builtin_id).is_some());
+        assert!(!dbank
+            .message_processor
+            .has_instruction_processor(&builtin_id));
+
+        // Registering the same programs again is accepted and restores the entrypoint
+        dbank.register_native_programs(&registry).unwrap();
+        assert!(dbank
+            .message_processor
+            .has_instruction_processor(&builtin_id));
+    }
+
     #[test]
     fn test_recent_blockhashes_sysvar() {
         let (genesis_config, _mint_keypair) = create_genesis_config(500);
@@ -59,6 +59,13 @@ impl BlockhashQueue {
         }
     }
 
+    /// Number of hashes registered since `hash`, if it is still in the queue
+    pub fn get_hash_age(&self, hash: &Hash) -> Option<u64> {
+        self.ages
+            .get(hash)
+            .map(|age| self.hash_height - age.hash_height)
+    }
+
     /// check if hash is valid
     #[cfg(test)]
     pub fn check_hash(&self, hash: Hash) -> bool {
@@ -156,6 +163,20 @@ mod tests {
         assert!(hash_queue.check_hash_age(&last_hash, 0));
     }
 
+    #[test]
+    fn test_get_hash_age() {
+        let mut hash_queue = BlockhashQueue::new(100);
+        let first_hash = hash(&serialize(&0).unwrap());
+        assert_eq!(hash_queue.get_hash_age(&first_hash), None);
+        hash_queue.register_hash(&first_hash, &FeeCalculator::default());
+        assert_eq!(hash_queue.get_hash_age(&first_hash), Some(0));
+        for i in 1..5 {
+            let last_hash = hash(&serialize(&i).unwrap());
+            hash_queue.register_hash(&last_hash, &FeeCalculator::default());
+        }
+        assert_eq!(hash_queue.get_hash_age(&first_hash), Some(4));
+    }
+
     #[test]
     fn test_get_recent_blockhashes() {
         let mut blockhash_queue = BlockhashQueue::new(MAX_RECENT_BLOCKHASHES);
@@ -0,0 +1,93 @@
+//! EpochStakes are snapshots of the stakes a bank captured for an epoch's leader
+//! schedule, cheap to clone and hold on to outside the bank
+use crate::stakes::Stakes;
+use solana_sdk::account::Account;
+use solana_sdk::clock::Epoch;
+use solana_sdk::pubkey::Pubkey;
+use solana_vote_api::vote_state::VoteState;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct EpochStakes {
+    epoch: Epoch,
+
+    /// vote accounts, along with the stake delegated to each
+    vote_accounts: Arc<HashMap<Pubkey, (u64, Account)>>,
+
+    /// stake of each node, summed over the vote accounts naming it
+    node_stakes: Arc<HashMap<Pubkey, u64>>,
+
+    total_stake: u64,
+}
+
+impl EpochStakes {
+    pub fn new(epoch: Epoch, stakes: &Stakes) -> Self {
+        let vote_accounts = stakes.vote_accounts().clone();
+        let mut node_stakes = HashMap::new();
+        for (stake, account) in vote_accounts.values() {
+            if let Some(vote_state) = VoteState::from(account) {
+                *node_stakes.entry(vote_state.node_pubkey).or_insert(0) += *stake;
+            }
+        }
+        let total_stake = vote_accounts.values().map(|(stake, _)| *stake).sum();
+        Self {
+            epoch,
+            vote_accounts: Arc::new(vote_accounts),
+            node_stakes: Arc::new(node_stakes),
+            total_stake,
+        }
+    }
+
+    pub fn epoch(&self) -> Epoch {
+        self.epoch
+    }
+
+    pub fn vote_accounts(&self) -> &HashMap<Pubkey, (u64, Account)> {
+        &self.vote_accounts
+    }
+
+    pub fn node_stakes(&self) -> &HashMap<Pubkey, u64> {
+        &self.node_stakes
+    }
+
+    /// stake of the given node, 0 if it has none
+    pub fn node_stake(&self, node_pubkey: &Pubkey) -> u64 {
+        self.node_stakes.get(node_pubkey).cloned().unwrap_or(0)
+    }
+
+    pub fn total_stake(&self) -> u64 {
+        self.total_stake
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stakes::tests::create_staked_node_accounts;
+
+    #[test]
+    fn test_epoch_stakes() {
+        let mut stakes = Stakes::default();
+        let ((vote_pubkey, vote_account), (stake_pubkey, mut stake_account)) =
+            create_staked_node_accounts(10);
+        stakes.store(&vote_pubkey, &vote_account);
+        stakes.store(&stake_pubkey, &stake_account);
+        let node_pubkey = VoteState::from(&vote_account).unwrap().node_pubkey;
+        let stake = stakes.vote_accounts()[&vote_pubkey].0;
+        assert!(stake > 0);
+
+        let epoch_stakes = EpochStakes::new(3, &stakes);
+        assert_eq!(epoch_stakes.epoch(), 3);
+        assert_eq!(epoch_stakes.total_stake(), stake);
+        assert_eq!(epoch_stakes.node_stake(&node_pubkey), stake);
+        assert_eq!(epoch_stakes.node_stake(&Pubkey::new_rand()), 0);
+        assert_eq!(epoch_stakes.vote_accounts(), stakes.vote_accounts());
+
+        // snapshots don't see later changes to the stakes
+        stake_account.lamports = 0;
+        stakes.store(&stake_pubkey, &stake_account);
+        assert_eq!(epoch_stakes.total_stake(), stake);
+        assert_eq!(EpochStakes::new(3, &stakes).total_stake(), 0);
+    }
+}
@@ -6,10 +6,12 @@ pub mod bank;
 pub mod bank_client;
 mod blockhash_queue;
 pub mod bloom;
+pub mod epoch_stakes;
 pub mod genesis_utils;
 pub mod loader_utils;
 pub mod message_processor;
 mod native_loader;
+pub mod native_program_registry;
 pub mod rent_collector;
 mod serde_utils;
 pub mod stakes;
@@ -19,6 +21,9 @@ mod system_instruction_processor;
 pub mod transaction_batch;
 pub mod transaction_utils;
 
+/// Version of the runtime, and of the system program built into it
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 #[macro_use]
 extern crate solana_metrics;
 
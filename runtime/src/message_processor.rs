@@ -221,6 +221,21 @@ impl MessageProcessor {
             .push((program_id, process_instruction));
     }
 
+    /// Whether instructions for `program_id` are intercepted before the dynamic loader
+    pub fn has_instruction_processor(&self, program_id: &Pubkey) -> bool {
+        self.instruction_processors
+            .iter()
+            .any(|(id, _)| id == program_id)
+    }
+
+    /// A processor for a child bank, intercepting the same programs as this one
+    pub fn new_from_parent(parent: &MessageProcessor) -> Self {
+        Self {
+            instruction_processors: parent.instruction_processors.clone(),
+            symbol_cache: RwLock::new(HashMap::new()),
+        }
+    }
+
     /// Process an instruction
     /// This method calls the instruction's program entrypoint method
     fn process_instruction(
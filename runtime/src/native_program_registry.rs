@@ -0,0 +1,224 @@
+//! The `native_program_registry` module collects native programs to install on top of the
+//! runtime's built-in set, so a fork or a test can add its own entrypoints without editing the
+//! hard-coded program lists. Each program id can only be claimed once.
+
+use crate::message_processor::ProcessInstruction;
+use solana_sdk::{genesis_config::GenesisConfig, pubkey::Pubkey};
+use std::{error, fmt};
+
+/// How the runtime reaches a registered program's entrypoint
+#[derive(Clone)]
+pub enum NativeProgram {
+    /// Loaded by the native loader from the shared library of this name
+    Loadable(String),
+    /// Called directly, ahead of the native loader
+    Builtin(ProcessInstruction),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NativeProgramError {
+    /// The program id is already claimed by the named program
+    ProgramIdInUse {
+        program_id: Pubkey,
+        claimed_by: String,
+    },
+}
+
+impl fmt::Display for NativeProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NativeProgramError::ProgramIdInUse {
+                program_id,
+                claimed_by,
+            } => write!(
+                f,
+                "program id {} is already claimed by {}",
+                program_id, claimed_by
+            ),
+        }
+    }
+}
+
+impl error::Error for NativeProgramError {}
+
+#[derive(Clone, Default)]
+pub struct NativeProgramRegistry {
+    programs: Vec<(String, Pubkey, NativeProgram)>,
+}
+
+impl NativeProgramRegistry {
+    /// Register the shared library `name` as the program at `program_id`
+    pub fn register_loadable(
+        &mut self,
+        name: &str,
+        program_id: Pubkey,
+    ) -> Result<(), NativeProgramError> {
+        self.register(name, program_id, NativeProgram::Loadable(name.to_string()))
+    }
+
+    /// Register `process_instruction` as the program at `program_id`, `name` only identifies it
+    /// in errors and logs
+    pub fn register_builtin(
+        &mut self,
+        name: &str,
+        program_id: Pubkey,
+        process_instruction: ProcessInstruction,
+    ) -> Result<(), NativeProgramError> {
+        self.register(
+            name,
+            program_id,
+            NativeProgram::Builtin(process_instruction),
+        )
+    }
+
+    fn register(
+        &mut self,
+        name: &str,
+        program_id: Pubkey,
+        program: NativeProgram,
+    ) -> Result<(), NativeProgramError> {
+        if let Some((claimed_by, _, _)) = self.programs.iter().find(|(_, id, _)| *id == program_id)
+        {
+            return Err(NativeProgramError::ProgramIdInUse {
+                program_id,
+                claimed_by: claimed_by.clone(),
+            });
+        }
+        self.programs.push((name.to_string(), program_id, program));
+        Ok(())
+    }
+
+    /// The registered programs as `(name, program_id, program)`, in registration order
+    pub fn programs(&self) -> impl Iterator<Item = (&str, &Pubkey, &NativeProgram)> {
+        self.programs
+            .iter()
+            .map(|(name, program_id, program)| (name.as_str(), program_id, program))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    /// Add the loadable programs to `genesis_config`'s native programs, failing without changing
+    /// `genesis_config` if one of their ids is already a genesis program or account. Builtin
+    /// programs have no place in a genesis config, they're installed with
+    /// `Bank::register_native_programs`.
+    pub fn add_to_genesis(
+        &self,
+        genesis_config: &mut GenesisConfig,
+    ) -> Result<(), NativeProgramError> {
+        for (_, program_id, _) in &self.programs {
+            if let Some((claimed_by, _)) = genesis_config
+                .native_instruction_processors
+                .iter()
+                .find(|(_, id)| id == program_id)
+            {
+                return Err(NativeProgramError::ProgramIdInUse {
+                    program_id: *program_id,
+                    claimed_by: claimed_by.clone(),
+                });
+            }
+            if genesis_config
+                .accounts
+                .iter()
+                .any(|(pubkey, _)| pubkey == program_id)
+            {
+                return Err(NativeProgramError::ProgramIdInUse {
+                    program_id: *program_id,
+                    claimed_by: "a genesis account".to_string(),
+                });
+            }
+        }
+        for (_, program_id, program) in &self.programs {
+            if let NativeProgram::Loadable(name) = program {
+                genesis_config
+                    .native_instruction_processors
+                    .push((name.clone(), *program_id));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        account::{Account, KeyedAccount},
+        instruction::InstructionError,
+        system_program::solana_system_program,
+    };
+
+    fn mock_processor(
+        _program_id: &Pubkey,
+        _keyed_accounts: &mut [KeyedAccount],
+        _data: &[u8],
+    ) -> Result<(), InstructionError> {
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_conflicts() {
+        let mut registry = NativeProgramRegistry::default();
+        let program_id = Pubkey::new_rand();
+        registry
+            .register_loadable("solana_mock_program", program_id)
+            .unwrap();
+        assert_eq!(
+            registry.register_builtin("mock", program_id, mock_processor),
+            Err(NativeProgramError::ProgramIdInUse {
+                program_id,
+                claimed_by: "solana_mock_program".to_string()
+            })
+        );
+        registry
+            .register_builtin("mock", Pubkey::new_rand(), mock_processor)
+            .unwrap();
+        assert_eq!(registry.programs().count(), 2);
+    }
+
+    #[test]
+    fn test_add_to_genesis() {
+        let mut genesis_config = GenesisConfig::default();
+        genesis_config
+            .native_instruction_processors
+            .push(solana_system_program());
+        let account_id = Pubkey::new_rand();
+        genesis_config
+            .accounts
+            .push((account_id, Account::new(1, 0, &Pubkey::default())));
+
+        let mut registry = NativeProgramRegistry::default();
+        let loadable_id = Pubkey::new_rand();
+        registry
+            .register_loadable("solana_mock_program", loadable_id)
+            .unwrap();
+        registry
+            .register_builtin("mock", Pubkey::new_rand(), mock_processor)
+            .unwrap();
+        registry.add_to_genesis(&mut genesis_config).unwrap();
+        assert_eq!(
+            genesis_config.native_instruction_processors,
+            vec![
+                solana_system_program(),
+                ("solana_mock_program".to_string(), loadable_id)
+            ]
+        );
+
+        // A second registration of the same programs conflicts and changes nothing
+        assert!(registry.add_to_genesis(&mut genesis_config).is_err());
+        assert_eq!(genesis_config.native_instruction_processors.len(), 2);
+
+        let mut registry = NativeProgramRegistry::default();
+        registry
+            .register_builtin("mock", account_id, mock_processor)
+            .unwrap();
+        assert_eq!(
+            registry.add_to_genesis(&mut genesis_config),
+            Err(NativeProgramError::ProgramIdInUse {
+                program_id: account_id,
+                claimed_by: "a genesis account".to_string()
+            })
+        );
+    }
+}
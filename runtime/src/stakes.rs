@@ -204,6 +204,24 @@ impl Stakes {
         self.points = 0;
         points
     }
+
+    /// rewards each stake account could redeem at the given point value, as
+    ///  (stake pubkey, voter pubkey, voter rewards, staker rewards)
+    pub fn redeemable_rewards(&self, point_value: f64) -> Vec<(Pubkey, Pubkey, u64, u64)> {
+        self.stake_accounts
+            .iter()
+            .filter_map(|(pubkey, stake_account)| {
+                let stake = StakeState::stake_from(stake_account)?;
+                let vote_state = self
+                    .vote_accounts
+                    .get(&stake.voter_pubkey)
+                    .and_then(|(_stake, account)| VoteState::from(account))?;
+                let (voter_rewards, staker_rewards, _credits_observed) =
+                    stake.calculate_rewards(point_value, &vote_state, Some(&self.stake_history))?;
+                Some((*pubkey, stake.voter_pubkey, voter_rewards, staker_rewards))
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,8 @@
 use crate::bank::Bank;
 use solana_sdk::account::Account;
 use solana_sdk::account_utils::State;
+use solana_sdk::clock::Slot;
+use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
 use solana_storage_api::storage_contract::StorageContract;
 use std::collections::{HashMap, HashSet};
@@ -32,7 +34,9 @@ impl StorageAccounts {
                     self.archiver_accounts.insert(*pubkey);
                     self.points.insert(*pubkey, credits.current_epoch);
                 }
-            } else if let StorageContract::ValidatorStorage { credits, .. } = storage_state {
+            } else if let StorageContract::ValidatorStorage { credits, .. }
+            | StorageContract::CommittedValidatorStorage { credits, .. } = storage_state
+            {
                 if account.lamports == 0 {
                     self.validator_accounts.remove(pubkey);
                 } else {
@@ -50,9 +54,12 @@ impl StorageAccounts {
 
     /// "claims" points, resets points to 0
     pub fn claim_points(&mut self) -> u64 {
-        let points = self.points();
-        self.points.clear();
-        points
+        self.claim_points_by_account().values().sum()
+    }
+
+    /// "claims" points, resets points to 0, returning each account's share
+    pub fn claim_points_by_account(&mut self) -> HashMap<Pubkey, u64> {
+        std::mem::replace(&mut self.points, HashMap::new())
     }
 }
 
@@ -78,6 +85,49 @@ pub fn archiver_accounts(bank: &Bank) -> HashMap<Pubkey, Account> {
         .collect()
 }
 
+/// The storage turn committed on chain with the most stake behind it, as weighed by the stakes
+/// of the current epoch.  None until a staked validator has committed a turn
+pub fn committed_storage_turn(bank: &Bank) -> Option<(Hash, Slot)> {
+    let epoch_stakes = bank.epoch_stakes(bank.epoch());
+    let commitments = validator_accounts(bank)
+        .values()
+        .filter_map(|account| match account.state() {
+            Ok(StorageContract::CommittedValidatorStorage {
+                owner,
+                hash,
+                turn_slot,
+                ..
+            }) => {
+                let stake = epoch_stakes
+                    .as_ref()
+                    .map(|epoch_stakes| epoch_stakes.node_stake(&owner))
+                    .unwrap_or(0);
+                Some((hash, turn_slot, stake))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    select_storage_turn(commitments)
+}
+
+/// The storage turn with the most stake behind it out of `(hash, turn slot, stake)`
+/// commitments, one per validator, the latest turn wins a tie.  Unstaked validators aren't
+/// counted, so they can't outvote the cluster by committing from many identities
+pub fn select_storage_turn(
+    commitments: impl IntoIterator<Item = (Hash, Slot, u64)>,
+) -> Option<(Hash, Slot)> {
+    let mut stakes: HashMap<(Hash, Slot), u64> = HashMap::new();
+    for (hash, turn_slot, stake) in commitments {
+        if hash != Hash::default() && stake > 0 {
+            *stakes.entry((hash, turn_slot)).or_insert(0) += stake;
+        }
+    }
+    stakes
+        .into_iter()
+        .max_by_key(|((hash, turn_slot), stake)| (*stake, *turn_slot, *hash))
+        .map(|(turn, _)| turn)
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
@@ -180,11 +230,41 @@ pub(crate) mod tests {
         // check that incremental store increases credits
         assert_eq!(storage_accounts.points(), credits * 2 * 2 + 1);
 
+        let points = storage_accounts.clone().claim_points_by_account();
+        assert_eq!(points.len(), 2);
         assert_eq!(storage_accounts.claim_points(), credits * 2 * 2 + 1);
         // check that once redeemed, the points are gone
         assert_eq!(storage_accounts.claim_points(), 0);
     }
 
+    #[test]
+    fn test_select_storage_turn() {
+        let (hash1, hash2) = (Hash::new(&[1; 32]), Hash::new(&[2; 32]));
+
+        // nothing committed yet
+        assert_eq!(select_storage_turn(vec![(Hash::default(), 0, 10)]), None);
+
+        // the turn with the most stake wins, however many validators commit another
+        let commitments = vec![
+            (hash1, 8, 10),
+            (hash2, 16, 3),
+            (hash2, 16, 3),
+            (hash2, 16, 3),
+        ];
+        assert_eq!(select_storage_turn(commitments), Some((hash1, 8)));
+        let commitments = vec![(hash1, 8, 10), (hash2, 16, 6), (hash2, 16, 6)];
+        assert_eq!(select_storage_turn(commitments), Some((hash2, 16)));
+
+        // unstaked validators don't count
+        let commitments = vec![(hash1, 8, 1), (hash2, 16, 0), (hash2, 16, 0)];
+        assert_eq!(select_storage_turn(commitments), Some((hash1, 8)));
+        assert_eq!(select_storage_turn(vec![(hash2, 16, 0)]), None);
+
+        // the later turn wins a tie
+        let commitments = vec![(hash1, 8, 5), (hash2, 16, 5)];
+        assert_eq!(select_storage_turn(commitments), Some((hash2, 16)));
+    }
+
     pub fn create_storage_accounts_with_credits(
         credits: u64,
     ) -> ((Pubkey, Account), (Pubkey, Account)) {
@@ -7,6 +7,34 @@ use solana_sdk::system_instruction::{SystemError, SystemInstruction};
 use solana_sdk::system_program;
 use solana_sdk::sysvar;
 
+/// Check that `address` was derived from the (signing) `base` account with `seed`
+/// and `program_id`
+fn verify_address_with_seed(
+    address: &Pubkey,
+    base: &KeyedAccount,
+    seed: &str,
+    program_id: &Pubkey,
+) -> Result<(), InstructionError> {
+    let base = match base.signer_key() {
+        Some(base) => base,
+        None => {
+            debug!("WithSeed: base must sign");
+            return Err(InstructionError::MissingRequiredSignature);
+        }
+    };
+
+    let expected = Pubkey::create_with_seed(base, seed, program_id)
+        .map_err(|_| SystemError::MaxSeedLengthExceeded)?;
+    if *address != expected {
+        debug!(
+            "WithSeed: invalid argument; derived {} does not match {}",
+            expected, address
+        );
+        return Err(SystemError::AddressWithSeedMismatch.into());
+    }
+    Ok(())
+}
+
 fn create_system_account(
     from: &mut KeyedAccount,
     to: &mut KeyedAccount,
@@ -14,14 +42,51 @@ fn create_system_account(
     space: u64,
     program_id: &Pubkey,
 ) -> Result<(), InstructionError> {
-    // if lamports == 0, the from account isn't touched
-    if lamports != 0 && from.signer_key().is_none() {
-        debug!("CreateAccount: from must sign");
+    if to.signer_key().is_none() {
+        debug!("CreateAccount: to must sign");
         return Err(InstructionError::MissingRequiredSignature);
     }
 
-    if to.signer_key().is_none() {
-        debug!("CreateAccount: to must sign");
+    create_account(from, to, lamports, space, program_id)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_system_account_with_seed(
+    from: &mut KeyedAccount,
+    to: &mut KeyedAccount,
+    base: Option<&mut KeyedAccount>,
+    base_pubkey: &Pubkey,
+    seed: &str,
+    lamports: u64,
+    space: u64,
+    program_id: &Pubkey,
+) -> Result<(), InstructionError> {
+    {
+        // the base may be the funding account itself, in which case its signature suffices
+        let base = match base {
+            Some(base) => &*base,
+            None => &*from,
+        };
+        if base.unsigned_key() != base_pubkey {
+            debug!("CreateAccountWithSeed: base {} not provided", base_pubkey);
+            return Err(InstructionError::MissingRequiredSignature);
+        }
+        verify_address_with_seed(to.unsigned_key(), base, seed, program_id)?;
+    }
+
+    create_account(from, to, lamports, space, program_id)
+}
+
+fn create_account(
+    from: &mut KeyedAccount,
+    to: &mut KeyedAccount,
+    lamports: u64,
+    space: u64,
+    program_id: &Pubkey,
+) -> Result<(), InstructionError> {
+    // if lamports == 0, the from account isn't touched
+    if lamports != 0 && from.signer_key().is_none() {
+        debug!("CreateAccount: from must sign");
         return Err(InstructionError::MissingRequiredSignature);
     }
 
@@ -50,7 +115,7 @@ fn create_system_account(
         return Err(SystemError::ResultWithNegativeLamports.into());
     }
 
-    assign_account_to_program(to, program_id)?;
+    assign(to, program_id)?;
     from.account.lamports -= lamports;
     to.account.lamports += lamports;
     to.account.data = vec![0; space as usize];
@@ -58,6 +123,34 @@ fn create_system_account(
     Ok(())
 }
 
+fn allocate_with_seed(
+    account: &mut KeyedAccount,
+    base: &KeyedAccount,
+    seed: &str,
+    space: u64,
+    program_id: &Pubkey,
+) -> Result<(), InstructionError> {
+    verify_address_with_seed(account.unsigned_key(), base, seed, program_id)?;
+
+    // only a fresh system account may be allocated
+    if !account.account.data.is_empty() || !system_program::check_id(&account.account.owner) {
+        debug!(
+            "AllocateWithSeed: invalid argument; account {} already in use",
+            account.unsigned_key()
+        );
+        return Err(SystemError::AccountAlreadyInUse.into());
+    }
+
+    if sysvar::is_sysvar_id(&account.unsigned_key()) {
+        debug!("AllocateWithSeed: account id {} invalid", program_id);
+        return Err(SystemError::InvalidAccountId.into());
+    }
+
+    assign(account, program_id)?;
+    account.account.data = vec![0; space as usize];
+    Ok(())
+}
+
 fn assign_account_to_program(
     account: &mut KeyedAccount,
     program_id: &Pubkey,
@@ -67,6 +160,10 @@ fn assign_account_to_program(
         return Err(InstructionError::MissingRequiredSignature);
     }
 
+    assign(account, program_id)
+}
+
+fn assign(account: &mut KeyedAccount, program_id: &Pubkey) -> Result<(), InstructionError> {
     // guard against sysvars being assigned
     if sysvar::check_id(&program_id) {
         debug!("Assign: program id {} invalid", program_id);
@@ -91,6 +188,31 @@ fn transfer_lamports(
         return Err(InstructionError::MissingRequiredSignature);
     }
 
+    move_lamports(from, to, lamports)
+}
+
+fn transfer_lamports_with_seed(
+    from: &mut KeyedAccount,
+    base: &KeyedAccount,
+    from_seed: &str,
+    from_owner: &Pubkey,
+    to: &mut KeyedAccount,
+    lamports: u64,
+) -> Result<(), InstructionError> {
+    if lamports == 0 {
+        return Ok(());
+    }
+
+    verify_address_with_seed(from.unsigned_key(), base, from_seed, from_owner)?;
+
+    move_lamports(from, to, lamports)
+}
+
+fn move_lamports(
+    from: &mut KeyedAccount,
+    to: &mut KeyedAccount,
+    lamports: u64,
+) -> Result<(), InstructionError> {
     if lamports > from.account.lamports {
         debug!(
             "Transfer: insufficient lamports ({}, need {})",
@@ -134,6 +256,46 @@ pub fn process_instruction(
             let to = next_keyed_account(keyed_accounts_iter)?;
             transfer_lamports(from, to, lamports)
         }
+        SystemInstruction::CreateAccountWithSeed {
+            base,
+            seed,
+            lamports,
+            space,
+            program_id,
+        } => {
+            let from = next_keyed_account(keyed_accounts_iter)?;
+            let to = next_keyed_account(keyed_accounts_iter)?;
+            let base_account = keyed_accounts_iter.next();
+            create_system_account_with_seed(
+                from,
+                to,
+                base_account,
+                &base,
+                &seed,
+                lamports,
+                space,
+                &program_id,
+            )
+        }
+        SystemInstruction::AllocateWithSeed {
+            seed,
+            space,
+            program_id,
+        } => {
+            let account = next_keyed_account(keyed_accounts_iter)?;
+            let base = next_keyed_account(keyed_accounts_iter)?;
+            allocate_with_seed(account, base, &seed, space, &program_id)
+        }
+        SystemInstruction::TransferWithSeed {
+            lamports,
+            from_seed,
+            from_owner,
+        } => {
+            let from = next_keyed_account(keyed_accounts_iter)?;
+            let base = next_keyed_account(keyed_accounts_iter)?;
+            let to = next_keyed_account(keyed_accounts_iter)?;
+            transfer_lamports_with_seed(from, base, &from_seed, &from_owner, to, lamports)
+        }
     }
 }
 
@@ -148,6 +310,7 @@ mod tests {
     use solana_sdk::genesis_config::create_genesis_config;
     use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
     use solana_sdk::signature::{Keypair, KeypairUtil};
+    use solana_sdk::system_instruction;
     use solana_sdk::system_program;
     use solana_sdk::transaction::TransactionError;
 
@@ -426,6 +589,242 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_create_account_with_seed() {
+        let new_program_owner = Pubkey::new(&[9; 32]);
+        let from = Pubkey::new_rand();
+        let mut from_account = Account::new(100, 0, &system_program::id());
+        let seed = "shiny pepper";
+        let to = Pubkey::create_with_seed(&from, seed, &new_program_owner).unwrap();
+        let mut to_account = Account::new(0, 0, &Pubkey::default());
+
+        assert_eq!(
+            create_system_account_with_seed(
+                &mut KeyedAccount::new(&from, true, &mut from_account),
+                &mut KeyedAccount::new(&to, false, &mut to_account),
+                None,
+                &from,
+                seed,
+                50,
+                2,
+                &new_program_owner,
+            ),
+            Ok(())
+        );
+        assert_eq!(from_account.lamports, 50);
+        assert_eq!(to_account.lamports, 50);
+        assert_eq!(to_account.owner, new_program_owner);
+        assert_eq!(to_account.data, [0, 0]);
+    }
+
+    #[test]
+    fn test_create_account_with_seed_separate_base() {
+        let new_program_owner = Pubkey::new(&[9; 32]);
+        let from = Pubkey::new_rand();
+        let mut from_account = Account::new(100, 0, &system_program::id());
+        let base = Pubkey::new_rand();
+        let mut base_account = Account::new(0, 0, &system_program::id());
+        let seed = "shiny pepper";
+        let to = Pubkey::create_with_seed(&base, seed, &new_program_owner).unwrap();
+        let mut to_account = Account::new(0, 0, &Pubkey::default());
+        let unchanged_account = to_account.clone();
+
+        // base didn't sign
+        assert_eq!(
+            create_system_account_with_seed(
+                &mut KeyedAccount::new(&from, true, &mut from_account),
+                &mut KeyedAccount::new(&to, false, &mut to_account),
+                Some(&mut KeyedAccount::new(&base, false, &mut base_account)),
+                &base,
+                seed,
+                50,
+                2,
+                &new_program_owner,
+            ),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+        // base not provided
+        assert_eq!(
+            create_system_account_with_seed(
+                &mut KeyedAccount::new(&from, true, &mut from_account),
+                &mut KeyedAccount::new(&to, false, &mut to_account),
+                None,
+                &base,
+                seed,
+                50,
+                2,
+                &new_program_owner,
+            ),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+        // wrong seed
+        assert_eq!(
+            create_system_account_with_seed(
+                &mut KeyedAccount::new(&from, true, &mut from_account),
+                &mut KeyedAccount::new(&to, false, &mut to_account),
+                Some(&mut KeyedAccount::new(&base, true, &mut base_account)),
+                &base,
+                "dull pepper",
+                50,
+                2,
+                &new_program_owner,
+            ),
+            Err(SystemError::AddressWithSeedMismatch.into())
+        );
+        assert_eq!(from_account.lamports, 100);
+        assert_eq!(to_account, unchanged_account);
+
+        assert_eq!(
+            create_system_account_with_seed(
+                &mut KeyedAccount::new(&from, true, &mut from_account),
+                &mut KeyedAccount::new(&to, false, &mut to_account),
+                Some(&mut KeyedAccount::new(&base, true, &mut base_account)),
+                &base,
+                seed,
+                50,
+                2,
+                &new_program_owner,
+            ),
+            Ok(())
+        );
+        assert_eq!(from_account.lamports, 50);
+        assert_eq!(to_account.lamports, 50);
+        assert_eq!(to_account.owner, new_program_owner);
+    }
+
+    #[test]
+    fn test_allocate_with_seed() {
+        let new_program_owner = Pubkey::new(&[9; 32]);
+        let base = Pubkey::new_rand();
+        let mut base_account = Account::new(0, 0, &system_program::id());
+        let seed = "shiny pepper";
+        let address = Pubkey::create_with_seed(&base, seed, &new_program_owner).unwrap();
+        let mut account = Account::new(10, 0, &system_program::id());
+
+        assert_eq!(
+            allocate_with_seed(
+                &mut KeyedAccount::new(&address, false, &mut account),
+                &KeyedAccount::new(&base, false, &mut base_account),
+                seed,
+                2,
+                &new_program_owner,
+            ),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            allocate_with_seed(
+                &mut KeyedAccount::new(&address, false, &mut account),
+                &KeyedAccount::new(&base, true, &mut base_account),
+                seed,
+                2,
+                &new_program_owner,
+            ),
+            Ok(())
+        );
+        assert_eq!(account.lamports, 10);
+        assert_eq!(account.owner, new_program_owner);
+        assert_eq!(account.data, [0, 0]);
+
+        // can't allocate twice
+        assert_eq!(
+            allocate_with_seed(
+                &mut KeyedAccount::new(&address, false, &mut account),
+                &KeyedAccount::new(&base, true, &mut base_account),
+                seed,
+                2,
+                &new_program_owner,
+            ),
+            Err(SystemError::AccountAlreadyInUse.into())
+        );
+    }
+
+    #[test]
+    fn test_transfer_lamports_with_seed() {
+        let base = Pubkey::new_rand();
+        let mut base_account = Account::new(0, 0, &system_program::id());
+        let seed = "shiny pepper";
+        let from = Pubkey::create_with_seed(&base, seed, &system_program::id()).unwrap();
+        let mut from_account = Account::new(100, 0, &system_program::id());
+        let to = Pubkey::new_rand();
+        let mut to_account = Account::new(1, 0, &system_program::id());
+
+        assert_eq!(
+            transfer_lamports_with_seed(
+                &mut KeyedAccount::new(&from, false, &mut from_account),
+                &KeyedAccount::new(&base, true, &mut base_account),
+                "dull pepper",
+                &system_program::id(),
+                &mut KeyedAccount::new(&to, false, &mut to_account),
+                50,
+            ),
+            Err(SystemError::AddressWithSeedMismatch.into())
+        );
+        assert_eq!(
+            transfer_lamports_with_seed(
+                &mut KeyedAccount::new(&from, false, &mut from_account),
+                &KeyedAccount::new(&base, false, &mut base_account),
+                seed,
+                &system_program::id(),
+                &mut KeyedAccount::new(&to, false, &mut to_account),
+                50,
+            ),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            transfer_lamports_with_seed(
+                &mut KeyedAccount::new(&from, false, &mut from_account),
+                &KeyedAccount::new(&base, true, &mut base_account),
+                seed,
+                &system_program::id(),
+                &mut KeyedAccount::new(&to, false, &mut to_account),
+                50,
+            ),
+            Ok(())
+        );
+        assert_eq!(from_account.lamports, 50);
+        assert_eq!(to_account.lamports, 51);
+    }
+
+    #[test]
+    fn test_system_with_seed_transactions() {
+        let (genesis_config, alice_keypair) = create_genesis_config(100);
+        let alice_pubkey = alice_keypair.pubkey();
+        let bob_pubkey = Pubkey::new_rand();
+        let bank = Bank::new(&genesis_config);
+        let bank_client = BankClient::new(bank);
+
+        let seed = "segment 1";
+        let derived_pubkey =
+            Pubkey::create_with_seed(&alice_pubkey, seed, &system_program::id()).unwrap();
+        let instruction = system_instruction::create_account_with_seed(
+            &alice_pubkey,
+            &derived_pubkey,
+            &alice_pubkey,
+            seed,
+            50,
+            0,
+            &system_program::id(),
+        );
+        bank_client
+            .send_instruction(&alice_keypair, instruction)
+            .unwrap();
+        assert_eq!(bank_client.get_balance(&derived_pubkey).unwrap(), 50);
+
+        let instruction = system_instruction::transfer_with_seed(
+            &derived_pubkey,
+            &alice_pubkey,
+            seed,
+            &system_program::id(),
+            &bob_pubkey,
+            10,
+        );
+        bank_client
+            .send_instruction(&alice_keypair, instruction)
+            .unwrap();
+        assert_eq!(bank_client.get_balance(&derived_pubkey).unwrap(), 40);
+        assert_eq!(bank_client.get_balance(&bob_pubkey).unwrap(), 10);
+    }
+
     #[test]
     fn test_process_bogus_instruction() {
         // Attempt to assign with no accounts
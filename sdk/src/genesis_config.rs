@@ -16,12 +16,17 @@ use crate::{
 use bincode::{deserialize, serialize};
 use memmap::Mmap;
 use std::{
+    collections::BTreeMap,
+    fmt,
     fs::{File, OpenOptions},
     io::Write,
     path::{Path, PathBuf},
 };
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// Version of the `genesis_manifest.bin` layout written by `GenesisManifest::write()`
+pub const GENESIS_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum OperatingMode {
     SoftLaunch,  // Cluster features incrementally enabled over time
     Development, // All features (including experimental features) available immediately from genesis
@@ -42,6 +47,198 @@ pub struct GenesisConfig {
     pub operating_mode: OperatingMode,
 }
 
+/// A native program of a genesis config and the version of the crate that provides it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NativeProgramVersion {
+    pub name: String,
+    pub program_id: Pubkey,
+    pub version: String,
+}
+
+/// The cluster parameters of a `GenesisConfig`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GenesisParameters {
+    pub ticks_per_slot: u64,
+    pub slots_per_segment: u64,
+    pub poh_config: PohConfig,
+    pub fee_calculator: FeeCalculator,
+    pub rent: Rent,
+    pub inflation: Inflation,
+    pub epoch_schedule: EpochSchedule,
+    pub operating_mode: OperatingMode,
+}
+
+/// Written next to `genesis.bin`, pins the native programs and cluster parameters the config
+/// was created with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GenesisManifest {
+    pub format_version: u32,
+    pub native_programs: Vec<NativeProgramVersion>,
+    pub parameters: GenesisParameters,
+}
+
+impl GenesisManifest {
+    /// `program_versions` maps native program names to the versions of their crates, programs
+    /// missing from it are recorded as "unknown"
+    pub fn new(genesis_config: &GenesisConfig, program_versions: &[(String, String)]) -> Self {
+        let program_versions: BTreeMap<_, _> = program_versions.iter().cloned().collect();
+        Self {
+            format_version: GENESIS_FORMAT_VERSION,
+            native_programs: genesis_config
+                .native_instruction_processors
+                .iter()
+                .map(|(name, program_id)| NativeProgramVersion {
+                    name: name.clone(),
+                    program_id: *program_id,
+                    version: program_versions
+                        .get(name)
+                        .cloned()
+                        .unwrap_or_else(|| "unknown".to_string()),
+                })
+                .collect(),
+            parameters: genesis_config.parameters(),
+        }
+    }
+
+    fn manifest_filename(ledger_path: &Path) -> PathBuf {
+        Path::new(ledger_path).join("genesis_manifest.bin")
+    }
+
+    /// Load the manifest of the ledger at `ledger_path`, None for ledgers created without one
+    pub fn load(ledger_path: &Path) -> Result<Option<Self>, std::io::Error> {
+        let filename = Self::manifest_filename(ledger_path);
+        if !filename.exists() {
+            return Ok(None);
+        }
+        let data = std::fs::read(&filename)?;
+        let manifest: Self = deserialize(&data).map_err(|err| {
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("Unable to deserialize {:?}: {:?}", filename, err),
+            )
+        })?;
+        if manifest.format_version != GENESIS_FORMAT_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!(
+                    "Unable to deserialize {:?}: unsupported format version {}",
+                    filename, manifest.format_version
+                ),
+            ));
+        }
+        Ok(Some(manifest))
+    }
+
+    pub fn write(&self, ledger_path: &Path) -> Result<(), std::io::Error> {
+        let serialized = serialize(self).map_err(|err| {
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("Unable to serialize: {:?}", err),
+            )
+        })?;
+
+        std::fs::create_dir_all(&ledger_path)?;
+
+        let mut file = File::create(Self::manifest_filename(&ledger_path))?;
+        file.write_all(&serialized)
+    }
+
+    /// Check that `genesis_config` still has the pinned native programs and parameters
+    pub fn verify(&self, genesis_config: &GenesisConfig) -> Result<(), String> {
+        let native_programs: Vec<_> = self
+            .native_programs
+            .iter()
+            .map(|program| (program.name.clone(), program.program_id))
+            .collect();
+        if native_programs != genesis_config.native_instruction_processors {
+            return Err("native programs don't match the genesis manifest".to_string());
+        }
+        if self.parameters != genesis_config.parameters() {
+            return Err("cluster parameters don't match the genesis manifest".to_string());
+        }
+        Ok(())
+    }
+
+    /// The differences in format and native program versions between two manifests
+    pub fn diff(&self, other: &Self) -> Vec<GenesisDiff> {
+        let mut diffs = vec![];
+        diff_field(
+            &mut diffs,
+            "format_version",
+            &self.format_version,
+            &other.format_version,
+        );
+        let versions = |manifest: &Self| -> BTreeMap<String, String> {
+            manifest
+                .native_programs
+                .iter()
+                .map(|program| (program.name.clone(), program.version.clone()))
+                .collect()
+        };
+        diff_keyed(
+            &mut diffs,
+            "native program version",
+            &versions(self),
+            &versions(other),
+        );
+        diffs
+    }
+}
+
+/// One difference between two genesis configs, `left` and `right` are "none" where only one of
+/// them has the field
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenesisDiff {
+    pub field: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for GenesisDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.left, self.right)
+    }
+}
+
+fn diff_field<T: fmt::Debug + PartialEq>(
+    diffs: &mut Vec<GenesisDiff>,
+    field: &str,
+    left: &T,
+    right: &T,
+) {
+    if left != right {
+        diffs.push(GenesisDiff {
+            field: field.to_string(),
+            left: format!("{:?}", left),
+            right: format!("{:?}", right),
+        });
+    }
+}
+
+fn diff_keyed<K: Ord + fmt::Display, V: fmt::Debug + PartialEq>(
+    diffs: &mut Vec<GenesisDiff>,
+    field: &str,
+    left: &BTreeMap<K, V>,
+    right: &BTreeMap<K, V>,
+) {
+    let describe = |value: Option<&V>| {
+        value
+            .map(|value| format!("{:?}", value))
+            .unwrap_or_else(|| "none".to_string())
+    };
+    let keys: std::collections::BTreeSet<_> = left.keys().chain(right.keys()).collect();
+    for key in keys {
+        let (left, right) = (left.get(*key), right.get(*key));
+        if left != right {
+            diffs.push(GenesisDiff {
+                field: format!("{} {}", field, key),
+                left: describe(left),
+                right: describe(right),
+            });
+        }
+    }
+}
+
 // useful for basic tests
 pub fn create_genesis_config(lamports: u64) -> (GenesisConfig, Keypair) {
     let faucet_keypair = Keypair::new();
@@ -92,6 +289,94 @@ impl GenesisConfig {
         hash(&serialized.into_bytes())
     }
 
+    pub fn parameters(&self) -> GenesisParameters {
+        GenesisParameters {
+            ticks_per_slot: self.ticks_per_slot,
+            slots_per_segment: self.slots_per_segment,
+            poh_config: self.poh_config.clone(),
+            fee_calculator: self.fee_calculator.clone(),
+            rent: self.rent,
+            inflation: self.inflation,
+            epoch_schedule: self.epoch_schedule,
+            operating_mode: self.operating_mode,
+        }
+    }
+
+    /// The differences in cluster parameters, native programs, accounts and rewards pools
+    /// between two genesis configs, for tools comparing clusters
+    pub fn diff(&self, other: &Self) -> Vec<GenesisDiff> {
+        let mut diffs = vec![];
+        let (left, right) = (self.parameters(), other.parameters());
+        diff_field(
+            &mut diffs,
+            "ticks_per_slot",
+            &left.ticks_per_slot,
+            &right.ticks_per_slot,
+        );
+        diff_field(
+            &mut diffs,
+            "slots_per_segment",
+            &left.slots_per_segment,
+            &right.slots_per_segment,
+        );
+        diff_field(
+            &mut diffs,
+            "poh_config",
+            &left.poh_config,
+            &right.poh_config,
+        );
+        diff_field(
+            &mut diffs,
+            "fee_calculator",
+            &left.fee_calculator,
+            &right.fee_calculator,
+        );
+        diff_field(&mut diffs, "rent", &left.rent, &right.rent);
+        diff_field(&mut diffs, "inflation", &left.inflation, &right.inflation);
+        diff_field(
+            &mut diffs,
+            "epoch_schedule",
+            &left.epoch_schedule,
+            &right.epoch_schedule,
+        );
+        diff_field(
+            &mut diffs,
+            "operating_mode",
+            &left.operating_mode,
+            &right.operating_mode,
+        );
+
+        let native_programs = |config: &Self| -> BTreeMap<String, Pubkey> {
+            config
+                .native_instruction_processors
+                .iter()
+                .cloned()
+                .collect()
+        };
+        diff_keyed(
+            &mut diffs,
+            "native program",
+            &native_programs(self),
+            &native_programs(other),
+        );
+        let accounts = |accounts: &[(Pubkey, Account)]| -> BTreeMap<Pubkey, Account> {
+            accounts.iter().cloned().collect()
+        };
+        diff_keyed(
+            &mut diffs,
+            "account",
+            &accounts(&self.accounts),
+            &accounts(&other.accounts),
+        );
+        diff_keyed(
+            &mut diffs,
+            "rewards pool",
+            &accounts(&self.rewards_pools),
+            &accounts(&other.rewards_pools),
+        );
+        diffs
+    }
+
     fn genesis_filename(ledger_path: &Path) -> PathBuf {
         Path::new(ledger_path).join("genesis.bin")
     }
@@ -125,6 +410,21 @@ impl GenesisConfig {
         Ok(genesis_config)
     }
 
+    /// Load the genesis config along with its manifest, checking the config against the
+    /// manifest when the ledger has one
+    pub fn load_with_manifest(
+        ledger_path: &Path,
+    ) -> Result<(Self, Option<GenesisManifest>), std::io::Error> {
+        let genesis_config = Self::load(ledger_path)?;
+        let manifest = GenesisManifest::load(ledger_path)?;
+        if let Some(manifest) = &manifest {
+            manifest
+                .verify(&genesis_config)
+                .map_err(|reason| std::io::Error::new(std::io::ErrorKind::Other, reason))?;
+        }
+        Ok((genesis_config, manifest))
+    }
+
     pub fn write(&self, ledger_path: &Path) -> Result<(), std::io::Error> {
         let serialized = serialize(&self).map_err(|err| {
             std::io::Error::new(
@@ -202,4 +502,97 @@ mod tests {
         assert_eq!(config.hash(), loaded_config.hash());
         let _ignored = std::fs::remove_file(&path);
     }
+
+    #[test]
+    fn test_genesis_manifest() {
+        let mut config = GenesisConfig::default();
+        config.add_native_instruction_processor("hi".to_string(), Pubkey::new_rand());
+        config.add_native_instruction_processor("bye".to_string(), Pubkey::new_rand());
+        let path = &make_tmp_path("genesis_manifest");
+        config.write(&path).expect("write");
+
+        // ledgers without a manifest still load
+        let (loaded_config, manifest) = GenesisConfig::load_with_manifest(&path).expect("load");
+        assert_eq!(config.hash(), loaded_config.hash());
+        assert_eq!(manifest, None);
+
+        // the manifest doesn't change `genesis.bin`
+        let manifest = GenesisManifest::new(&config, &[("hi".to_string(), "1.2.3".to_string())]);
+        assert_eq!(manifest.native_programs[0].version, "1.2.3");
+        assert_eq!(manifest.native_programs[1].version, "unknown");
+        manifest.write(&path).expect("write");
+        let genesis_bin = std::fs::read(GenesisConfig::genesis_filename(&path)).unwrap();
+        assert_eq!(genesis_bin, serialize(&config).unwrap());
+        let (_, loaded_manifest) = GenesisConfig::load_with_manifest(&path).expect("load");
+        assert_eq!(loaded_manifest, Some(manifest.clone()));
+
+        // a config that no longer matches its manifest is rejected
+        let mut mismatched = manifest.clone();
+        mismatched.parameters.slots_per_segment += 1;
+        mismatched.write(&path).expect("write");
+        assert!(GenesisConfig::load_with_manifest(&path).is_err());
+        // but still loads without it
+        assert!(GenesisConfig::load(&path).is_ok());
+
+        // so is a manifest format this build doesn't know
+        let mut unknown = manifest;
+        unknown.format_version += 1;
+        unknown.write(&path).expect("write");
+        assert!(GenesisConfig::load_with_manifest(&path).is_err());
+        let _ignored = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_genesis_config_diff() {
+        let program_id = Pubkey::new_rand();
+        let pubkey = Pubkey::new_rand();
+        let mut left = GenesisConfig::default();
+        left.add_native_instruction_processor("hi".to_string(), program_id);
+        left.add_account(pubkey, Account::new(1, 0, &Pubkey::default()));
+        let mut right = left.clone();
+        assert!(left.diff(&right).is_empty());
+        assert!(GenesisManifest::new(&left, &[])
+            .diff(&GenesisManifest::new(&right, &[]))
+            .is_empty());
+
+        right.slots_per_segment += 1;
+        right.accounts.clear();
+        right.add_native_instruction_processor("bye".to_string(), program_id);
+        let diffs = left.diff(&right);
+        assert_eq!(
+            diffs
+                .iter()
+                .map(|diff| diff.field.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                "slots_per_segment".to_string(),
+                "native program bye".to_string(),
+                format!("account {}", pubkey),
+            ]
+        );
+        assert_eq!(diffs[1].left, "none");
+        assert_eq!(diffs[2].right, "none");
+        assert_eq!(
+            diffs[0].to_string(),
+            format!(
+                "slots_per_segment: {} -> {}",
+                left.slots_per_segment, right.slots_per_segment
+            )
+        );
+
+        let versions = [("hi".to_string(), "1.0.0".to_string())];
+        let mut right_manifest = GenesisManifest::new(&right, &versions);
+        right_manifest.native_programs[0].version = "0.0.0".to_string();
+        let diffs = GenesisManifest::new(&left, &versions).diff(&right_manifest);
+        assert_eq!(
+            diffs
+                .iter()
+                .map(|diff| diff.field.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                "native program version bye".to_string(),
+                "native program version hi".to_string(),
+            ]
+        );
+    }
 }
@@ -3,6 +3,7 @@
 use bs58;
 use sha2::{Digest, Sha256};
 use std::convert::TryFrom;
+use std::error;
 use std::fmt;
 use std::mem;
 use std::str::FromStr;
@@ -25,6 +26,13 @@ impl Hasher {
             self.hash(val);
         }
     }
+    /// Start a new hasher whose state already includes `hash`, the incremental
+    /// counterpart of `extend_and_hash()`
+    pub fn new_extended(hash: &Hash) -> Self {
+        let mut hasher = Self::default();
+        hasher.hash(hash.as_ref());
+        hasher
+    }
     pub fn result(self) -> Hash {
         // At the time of this writing, the sha2 library is stuck on an old version
         // of generic_array (0.9.0). Decouple ourselves with a clone to our version.
@@ -32,6 +40,14 @@ impl Hasher {
     }
 }
 
+impl<'a> Extend<&'a [u8]> for Hasher {
+    fn extend<I: IntoIterator<Item = &'a [u8]>>(&mut self, vals: I) {
+        for val in vals {
+            self.hash(val);
+        }
+    }
+}
+
 impl AsRef<[u8]> for Hash {
     fn as_ref(&self) -> &[u8] {
         &self.0[..]
@@ -56,6 +72,14 @@ pub enum ParseHashError {
     Invalid,
 }
 
+impl fmt::Display for ParseHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ParseHashError: {:?}", self)
+    }
+}
+
+impl error::Error for ParseHashError {}
+
 impl FromStr for Hash {
     type Err = ParseHashError;
 
@@ -75,6 +99,23 @@ impl Hash {
     pub fn new(hash_slice: &[u8]) -> Self {
         Hash(<[u8; 32]>::try_from(hash_slice).unwrap())
     }
+
+    /// Return a `Hash` that is distinct from every other one returned by this
+    /// function, for use in tests
+    #[cfg(not(feature = "program"))]
+    pub fn new_unique() -> Self {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static COUNTER: AtomicU64 = AtomicU64::new(1);
+
+        let mut bytes = [0u8; 32];
+        let i = COUNTER.fetch_add(1, Ordering::Relaxed);
+        bytes[..8].copy_from_slice(&i.to_le_bytes());
+        Hash(bytes)
+    }
+
+    pub fn to_bytes(self) -> [u8; 32] {
+        self.0
+    }
 }
 
 /// Return a Sha256 hash for the given data.
@@ -91,9 +132,9 @@ pub fn hash(val: &[u8]) -> Hash {
 
 /// Return the hash of the given hash extended with the given value.
 pub fn extend_and_hash(id: &Hash, val: &[u8]) -> Hash {
-    let mut hash_data = id.as_ref().to_vec();
-    hash_data.extend_from_slice(val);
-    hash(&hash_data)
+    let mut hasher = Hasher::new_extended(id);
+    hasher.hash(val);
+    hasher.result()
 }
 
 #[cfg(test)]
@@ -134,4 +175,46 @@ mod tests {
             Err(ParseHashError::Invalid)
         );
     }
+
+    #[test]
+    fn test_parse_hash_error_display() {
+        let err = "I".parse::<Hash>().unwrap_err();
+        assert_eq!(err.to_string(), "ParseHashError: Invalid");
+    }
+
+    #[test]
+    fn test_hash_serde() {
+        let hash = hash(&[1u8]);
+
+        // JSON carries a `Hash` the way it carries a `Pubkey`, as its bytes
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(
+            json,
+            serde_json::to_string(&crate::pubkey::Pubkey::new(hash.as_ref())).unwrap()
+        );
+        assert_eq!(serde_json::from_str::<Hash>(&json).unwrap(), hash);
+
+        let bytes = bincode::serialize(&hash).unwrap();
+        assert_eq!(bytes, hash.as_ref());
+        assert_eq!(bincode::deserialize::<Hash>(&bytes).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_new_unique() {
+        assert_ne!(Hash::new_unique(), Hash::new_unique());
+    }
+
+    #[test]
+    fn test_hasher_extend() {
+        let id = hash(&[1u8]);
+        let vals: [&[u8]; 2] = [&[2u8], &[3u8, 4u8]];
+
+        let mut hasher = Hasher::new_extended(&id);
+        hasher.extend(vals.iter().cloned());
+        assert_eq!(hasher.result(), hashv(&[id.as_ref(), &[2u8, 3u8, 4u8]]));
+
+        let mut hash_data = id.as_ref().to_vec();
+        hash_data.extend_from_slice(&[5u8]);
+        assert_eq!(extend_and_hash(&id, &[5u8]), hash(&hash_data));
+    }
 }
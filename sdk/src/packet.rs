@@ -22,6 +22,8 @@ pub struct Meta {
     pub v6: bool,
     pub seed: [u8; 32],
     pub slot: Slot,
+    /// Epoch stake of the transaction's fee payer, set after signature verification
+    pub sender_stake: Option<u64>,
 }
 
 #[derive(Clone)]
@@ -1,3 +1,4 @@
+use crate::hash::hashv;
 use std::convert::TryFrom;
 use std::error;
 use std::fmt;
@@ -6,6 +7,9 @@ use std::str::FromStr;
 
 pub use bs58;
 
+/// maximum length of the seed passed to `Pubkey::create_with_seed()`
+pub const MAX_SEED_LEN: usize = 32;
+
 #[repr(transparent)]
 #[derive(Serialize, Deserialize, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Pubkey([u8; 32]);
@@ -24,6 +28,19 @@ impl fmt::Display for ParsePubkeyError {
 
 impl error::Error for ParsePubkeyError {}
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PubkeyError {
+    MaxSeedLengthExceeded,
+}
+
+impl fmt::Display for PubkeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PubkeyError: {:?}", self)
+    }
+}
+
+impl error::Error for PubkeyError {}
+
 impl FromStr for Pubkey {
     type Err = ParsePubkeyError;
 
@@ -47,6 +64,22 @@ impl Pubkey {
         )
     }
 
+    /// Derive an address from `base`, `seed` and the `program_id` that will own it,
+    /// so that `base` can sign for many accounts without holding a keypair for each
+    pub fn create_with_seed(
+        base: &Pubkey,
+        seed: &str,
+        program_id: &Pubkey,
+    ) -> Result<Pubkey, PubkeyError> {
+        if seed.len() > MAX_SEED_LEN {
+            return Err(PubkeyError::MaxSeedLengthExceeded);
+        }
+
+        Ok(Pubkey::new(
+            hashv(&[base.as_ref(), seed.as_ref(), program_id.as_ref()]).as_ref(),
+        ))
+    }
+
     #[cfg(not(feature = "program"))]
     pub fn new_rand() -> Self {
         Self::new(&rand::random::<[u8; 32]>())
@@ -179,6 +212,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_create_with_seed() {
+        let base = Pubkey::new_rand();
+        let program_id = Pubkey::new_rand();
+
+        assert!(Pubkey::create_with_seed(&base, "", &program_id).is_ok());
+        assert_eq!(
+            Pubkey::create_with_seed(
+                &base,
+                std::str::from_utf8(&[127; MAX_SEED_LEN + 1]).unwrap(),
+                &program_id
+            ),
+            Err(PubkeyError::MaxSeedLengthExceeded)
+        );
+        assert!(Pubkey::create_with_seed(
+            &base,
+            std::str::from_utf8(&[0; MAX_SEED_LEN]).unwrap(),
+            &program_id
+        )
+        .is_ok());
+
+        assert_eq!(
+            Pubkey::create_with_seed(&base, "a", &program_id),
+            Pubkey::create_with_seed(&base, "a", &program_id)
+        );
+        assert_ne!(
+            Pubkey::create_with_seed(&base, "a", &program_id),
+            Pubkey::create_with_seed(&base, "b", &program_id)
+        );
+        assert_ne!(
+            Pubkey::create_with_seed(&base, "a", &program_id),
+            Pubkey::create_with_seed(&base, "a", &Pubkey::new_rand())
+        );
+    }
+
     #[test]
     fn test_read_write_pubkey() -> Result<(), Box<dyn error::Error>> {
         let filename = "test_pubkey.json";
@@ -10,12 +10,16 @@ use std::{
     borrow::{Borrow, Cow},
     error, fmt,
     fs::{self, File},
-    io::{Read, Write},
+    io::{self, Read, Write},
     mem,
     path::Path,
+    ptr,
     str::FromStr,
+    sync::atomic::{self, Ordering},
 };
 
+/// The secret key is cleared when a `Keypair` is dropped, but its `Debug` implementation
+/// prints it: types holding a `Keypair` should implement `Debug` by hand rather than derive it
 pub type Keypair = ed25519_dalek::Keypair;
 
 #[repr(transparent)]
@@ -124,11 +128,42 @@ impl KeypairUtil for Keypair {
     }
 }
 
+/// Overwrite `bytes` with zeros, in a way the compiler doesn't optimize away, once a buffer
+/// holding secret key material isn't needed anymore
+pub fn zeroize(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    atomic::compiler_fence(Ordering::SeqCst);
+}
+
+/// Lock the memory holding `keypair` so its secret key is never written to swap. Only useful
+/// for a keypair that won't move anymore, like one behind an `Arc` or a `Box`. The memory
+/// stays locked until the process exits.
+#[cfg(unix)]
+pub fn mlock_keypair(keypair: &Keypair) -> io::Result<()> {
+    let addr = keypair as *const Keypair as *const libc::c_void;
+    if unsafe { libc::mlock(addr, mem::size_of::<Keypair>()) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+pub fn mlock_keypair(_keypair: &Keypair) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "locking keypair memory is not supported on this platform",
+    ))
+}
+
 pub fn read_keypair<R: Read>(reader: &mut R) -> Result<Keypair, Box<dyn error::Error>> {
-    let bytes: Vec<u8> = serde_json::from_reader(reader)?;
+    let mut bytes: Vec<u8> = serde_json::from_reader(reader)?;
     let keypair = Keypair::from_bytes(&bytes)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-    Ok(keypair)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()));
+    zeroize(&mut bytes);
+    Ok(keypair?)
 }
 
 pub fn read_keypair_file(path: &str) -> Result<Keypair, Box<dyn error::Error>> {
@@ -137,20 +172,28 @@ pub fn read_keypair_file(path: &str) -> Result<Keypair, Box<dyn error::Error>> {
     read_keypair(&mut file)
 }
 
+/// Write `keypair` as JSON to `writer`, returning its pubkey
 pub fn write_keypair<W: Write>(
     keypair: &Keypair,
     writer: &mut W,
-) -> Result<String, Box<dyn error::Error>> {
-    let keypair_bytes = keypair.to_bytes();
-    let serialized = serde_json::to_string(&keypair_bytes.to_vec())?;
-    writer.write_all(&serialized.clone().into_bytes())?;
-    Ok(serialized)
+) -> Result<Pubkey, Box<dyn error::Error>> {
+    let mut keypair_bytes = keypair.to_bytes();
+    let mut keypair_vec = keypair_bytes.to_vec();
+    let serialized = serde_json::to_string(&keypair_vec);
+    zeroize(&mut keypair_bytes);
+    zeroize(&mut keypair_vec);
+    let mut serialized = serialized?;
+    let result = writer.write_all(serialized.as_bytes());
+    // zeroes are valid UTF-8, so the string stays well-formed
+    zeroize(unsafe { serialized.as_bytes_mut() });
+    result?;
+    Ok(keypair.pubkey())
 }
 
 pub fn write_keypair_file(
     keypair: &Keypair,
     outfile: &str,
-) -> Result<String, Box<dyn error::Error>> {
+) -> Result<Pubkey, Box<dyn error::Error>> {
     assert!(outfile != "-");
     if let Some(outdir) = Path::new(outfile).parent() {
         fs::create_dir_all(outdir)?;
@@ -170,7 +213,7 @@ pub fn keypair_from_seed(seed: &[u8]) -> Result<Keypair, Box<dyn error::Error>>
     Ok(keypair)
 }
 
-pub fn gen_keypair_file(outfile: &str) -> Result<String, Box<dyn error::Error>> {
+pub fn gen_keypair_file(outfile: &str) -> Result<Pubkey, Box<dyn error::Error>> {
     write_keypair_file(&Keypair::new(), outfile)
 }
 
@@ -190,13 +233,9 @@ mod tests {
     #[test]
     fn test_gen_keypair_file() {
         let outfile = tmp_file_path("test_gen_keypair_file.json");
-        let serialized_keypair = gen_keypair_file(&outfile).unwrap();
-        let keypair_vec: Vec<u8> = serde_json::from_str(&serialized_keypair).unwrap();
+        let pubkey = gen_keypair_file(&outfile).unwrap();
         assert!(Path::new(&outfile).exists());
-        assert_eq!(
-            keypair_vec,
-            read_keypair_file(&outfile).unwrap().to_bytes().to_vec()
-        );
+        assert_eq!(pubkey, read_keypair_file(&outfile).unwrap().pubkey());
         assert_eq!(
             read_keypair_file(&outfile).unwrap().pubkey().as_ref().len(),
             mem::size_of::<Pubkey>()
@@ -214,6 +253,37 @@ mod tests {
         assert!(keypair_from_seed(&too_short_seed).is_err());
     }
 
+    #[test]
+    fn test_zeroize() {
+        let keypair = Keypair::new();
+        let mut bytes = keypair.to_bytes();
+        zeroize(&mut bytes);
+        assert!(bytes.iter().all(|byte| *byte == 0));
+
+        // reading a keypair back doesn't depend on the buffers it cleared
+        let mut serialized = vec![];
+        assert_eq!(
+            write_keypair(&keypair, &mut serialized).unwrap(),
+            keypair.pubkey()
+        );
+        let read_keypair = read_keypair(&mut serialized.as_slice()).unwrap();
+        assert_eq!(
+            read_keypair.to_bytes().to_vec(),
+            keypair.to_bytes().to_vec()
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_mlock_keypair() {
+        let keypair = Box::new(Keypair::new());
+        // locking can be denied by the memlock limit, but never by the keypair itself
+        if let Err(err) = mlock_keypair(&keypair) {
+            assert!(err.raw_os_error().is_some());
+        }
+        assert!(keypair.verify(b"hi", &keypair.sign(b"hi")).is_ok());
+    }
+
     #[test]
     fn test_signature_fromstr() {
         let signature = Keypair::new().sign_message(&[0u8]);
@@ -10,6 +10,8 @@ pub enum SystemError {
     ResultWithNegativeLamports,
     InvalidProgramId,
     InvalidAccountId,
+    AddressWithSeedMismatch,
+    MaxSeedLengthExceeded,
 }
 
 impl<T> DecodeError<T> for SystemError {
@@ -45,6 +47,47 @@ pub enum SystemInstruction {
     /// * Transaction::keys[0] - source
     /// * Transaction::keys[1] - destination
     Transfer { lamports: u64 },
+    /// Create a new account at an address derived from a base pubkey and a seed
+    /// * Transaction::keys[0] - source
+    /// * Transaction::keys[1] - new account key, derived by `Pubkey::create_with_seed()`
+    /// * Transaction::keys[2] - base, must sign unless it is the source
+    /// * base - the base pubkey the new account key was derived from
+    /// * seed - the seed the new account key was derived from
+    /// * lamports - number of lamports to transfer to the new account
+    /// * space - memory to allocate if greater then zero
+    /// * program_id - the program id of the new account
+    CreateAccountWithSeed {
+        base: Pubkey,
+        seed: String,
+        lamports: u64,
+        space: u64,
+        program_id: Pubkey,
+    },
+    /// Allocate space in and assign an account at an address derived from a
+    /// base pubkey and a seed
+    /// * Transaction::keys[0] - account to allocate, derived by `Pubkey::create_with_seed()`
+    /// * Transaction::keys[1] - base, must sign
+    /// * seed - the seed the account key was derived from
+    /// * space - memory to allocate
+    /// * program_id - the program id of the account
+    AllocateWithSeed {
+        seed: String,
+        space: u64,
+        program_id: Pubkey,
+    },
+    /// Transfer lamports from an account at an address derived from a base
+    /// pubkey and a seed
+    /// * Transaction::keys[0] - source, derived by `Pubkey::create_with_seed()`
+    /// * Transaction::keys[1] - base, must sign
+    /// * Transaction::keys[2] - destination
+    /// * lamports - number of lamports to transfer
+    /// * from_seed - the seed the source key was derived from
+    /// * from_owner - the program id the source key was derived with
+    TransferWithSeed {
+        lamports: u64,
+        from_seed: String,
+        from_owner: Pubkey,
+    },
 }
 
 pub fn create_account(
@@ -69,6 +112,57 @@ pub fn create_account(
     )
 }
 
+pub fn create_account_with_seed(
+    from_pubkey: &Pubkey,
+    to_pubkey: &Pubkey, // must match create_with_seed(base, seed, program_id)
+    base: &Pubkey,
+    seed: &str,
+    lamports: u64,
+    space: u64,
+    program_id: &Pubkey,
+) -> Instruction {
+    let mut account_metas = vec![
+        AccountMeta::new(*from_pubkey, true),
+        AccountMeta::new(*to_pubkey, false),
+    ];
+    if base != from_pubkey {
+        account_metas.push(AccountMeta::new_readonly(*base, true));
+    }
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::CreateAccountWithSeed {
+            base: *base,
+            seed: seed.to_string(),
+            lamports,
+            space,
+            program_id: *program_id,
+        },
+        account_metas,
+    )
+}
+
+pub fn allocate_with_seed(
+    address: &Pubkey, // must match create_with_seed(base, seed, program_id)
+    base: &Pubkey,
+    seed: &str,
+    space: u64,
+    program_id: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*address, false),
+        AccountMeta::new_readonly(*base, true),
+    ];
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::AllocateWithSeed {
+            seed: seed.to_string(),
+            space,
+            program_id: *program_id,
+        },
+        account_metas,
+    )
+}
+
 pub fn assign(from_pubkey: &Pubkey, program_id: &Pubkey) -> Instruction {
     let account_metas = vec![AccountMeta::new(*from_pubkey, true)];
     Instruction::new(
@@ -92,6 +186,30 @@ pub fn transfer(from_pubkey: &Pubkey, to_pubkey: &Pubkey, lamports: u64) -> Inst
     )
 }
 
+pub fn transfer_with_seed(
+    from_pubkey: &Pubkey, // must match create_with_seed(base, seed, from_owner)
+    from_base: &Pubkey,
+    from_seed: &str,
+    from_owner: &Pubkey,
+    to_pubkey: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*from_pubkey, false),
+        AccountMeta::new_readonly(*from_base, true),
+        AccountMeta::new(*to_pubkey, false),
+    ];
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::TransferWithSeed {
+            lamports,
+            from_seed: from_seed.to_string(),
+            from_owner: *from_owner,
+        },
+        account_metas,
+    )
+}
+
 /// Create and sign new SystemInstruction::Transfer transaction to many destinations
 pub fn transfer_many(from_pubkey: &Pubkey, to_lamports: &[(Pubkey, u64)]) -> Vec<Instruction> {
     to_lamports
@@ -120,4 +238,40 @@ mod tests {
         assert_eq!(get_keys(&instructions[0]), vec![alice_pubkey, bob_pubkey]);
         assert_eq!(get_keys(&instructions[1]), vec![alice_pubkey, carol_pubkey]);
     }
+
+    #[test]
+    fn test_create_account_with_seed_keys() {
+        let alice_pubkey = Pubkey::new_rand();
+        let base_pubkey = Pubkey::new_rand();
+        let program_id = Pubkey::new_rand();
+        let to_pubkey = Pubkey::create_with_seed(&alice_pubkey, "seed", &program_id).unwrap();
+
+        // base is the funder, no extra signer needed
+        let instruction = create_account_with_seed(
+            &alice_pubkey,
+            &to_pubkey,
+            &alice_pubkey,
+            "seed",
+            1,
+            0,
+            &program_id,
+        );
+        assert_eq!(get_keys(&instruction), vec![alice_pubkey, to_pubkey]);
+
+        let to_pubkey = Pubkey::create_with_seed(&base_pubkey, "seed", &program_id).unwrap();
+        let instruction = create_account_with_seed(
+            &alice_pubkey,
+            &to_pubkey,
+            &base_pubkey,
+            "seed",
+            1,
+            0,
+            &program_id,
+        );
+        assert_eq!(
+            get_keys(&instruction),
+            vec![alice_pubkey, to_pubkey, base_pubkey]
+        );
+        assert!(instruction.accounts[2].is_signer);
+    }
 }
@@ -49,3 +49,154 @@ pub fn transfer(
     let instructions = vec![transfer_instruction];
     Transaction::new_signed_instructions(&[from_keypair], instructions, recent_blockhash)
 }
+
+/// Create and sign new system_instruction::Transfer transaction to many destinations
+pub fn transfer_many(
+    from_keypair: &Keypair,
+    to_lamports: &[(Pubkey, u64)],
+    recent_blockhash: Hash,
+) -> Transaction {
+    let from_pubkey = from_keypair.pubkey();
+    let instructions = system_instruction::transfer_many(&from_pubkey, to_lamports);
+    Transaction::new_signed_instructions(&[from_keypair], instructions, recent_blockhash)
+}
+
+/// Create and sign new SystemInstruction::CreateAccountWithSeed transaction, `base_keypair`
+/// only signs if it differs from `from_keypair`
+#[allow(clippy::too_many_arguments)]
+pub fn create_account_with_seed(
+    from_keypair: &Keypair,
+    to_pubkey: &Pubkey, // must match create_with_seed(base, seed, program_id)
+    base_keypair: &Keypair,
+    seed: &str,
+    recent_blockhash: Hash,
+    lamports: u64,
+    space: u64,
+    program_id: &Pubkey,
+) -> Transaction {
+    let from_pubkey = from_keypair.pubkey();
+    let base_pubkey = base_keypair.pubkey();
+    let create_instruction = system_instruction::create_account_with_seed(
+        &from_pubkey,
+        to_pubkey,
+        &base_pubkey,
+        seed,
+        lamports,
+        space,
+        program_id,
+    );
+    let instructions = vec![create_instruction];
+    if base_pubkey == from_pubkey {
+        Transaction::new_signed_instructions(&[from_keypair], instructions, recent_blockhash)
+    } else {
+        Transaction::new_signed_instructions(
+            &[from_keypair, base_keypair],
+            instructions,
+            recent_blockhash,
+        )
+    }
+}
+
+/// Create and sign new SystemInstruction::AllocateWithSeed transaction, fees are paid by
+/// `base_keypair`
+pub fn allocate_with_seed(
+    base_keypair: &Keypair,
+    address: &Pubkey, // must match create_with_seed(base, seed, program_id)
+    seed: &str,
+    recent_blockhash: Hash,
+    space: u64,
+    program_id: &Pubkey,
+) -> Transaction {
+    let base_pubkey = base_keypair.pubkey();
+    let allocate_instruction =
+        system_instruction::allocate_with_seed(address, &base_pubkey, seed, space, program_id);
+    let instructions = vec![allocate_instruction];
+    Transaction::new_signed_instructions(&[base_keypair], instructions, recent_blockhash)
+}
+
+/// Create and sign new SystemInstruction::TransferWithSeed transaction, fees are paid by
+/// `from_base_keypair`
+pub fn transfer_with_seed(
+    from_base_keypair: &Keypair,
+    from_pubkey: &Pubkey, // must match create_with_seed(base, seed, from_owner)
+    from_seed: &str,
+    from_owner: &Pubkey,
+    to: &Pubkey,
+    lamports: u64,
+    recent_blockhash: Hash,
+) -> Transaction {
+    let from_base_pubkey = from_base_keypair.pubkey();
+    let transfer_instruction = system_instruction::transfer_with_seed(
+        from_pubkey,
+        &from_base_pubkey,
+        from_seed,
+        from_owner,
+        to,
+        lamports,
+    );
+    let instructions = vec![transfer_instruction];
+    Transaction::new_signed_instructions(&[from_base_keypair], instructions, recent_blockhash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system_program;
+
+    #[test]
+    fn test_create_account_with_seed_signers() {
+        let from_keypair = Keypair::new();
+        let base_keypair = Keypair::new();
+        let program_id = Pubkey::new_rand();
+
+        let to_pubkey =
+            Pubkey::create_with_seed(&from_keypair.pubkey(), "seed", &program_id).unwrap();
+        let tx = create_account_with_seed(
+            &from_keypair,
+            &to_pubkey,
+            &from_keypair,
+            "seed",
+            Hash::default(),
+            1,
+            0,
+            &program_id,
+        );
+        assert_eq!(tx.signatures.len(), 1);
+        assert!(tx.is_signed());
+
+        let to_pubkey =
+            Pubkey::create_with_seed(&base_keypair.pubkey(), "seed", &program_id).unwrap();
+        let tx = create_account_with_seed(
+            &from_keypair,
+            &to_pubkey,
+            &base_keypair,
+            "seed",
+            Hash::default(),
+            1,
+            0,
+            &program_id,
+        );
+        assert_eq!(tx.signatures.len(), 2);
+        assert!(tx.is_signed());
+    }
+
+    #[test]
+    fn test_transfer_with_seed_fee_payer() {
+        let base_keypair = Keypair::new();
+        let from_pubkey =
+            Pubkey::create_with_seed(&base_keypair.pubkey(), "seed", &system_program::id())
+                .unwrap();
+        let to = Pubkey::new_rand();
+        let tx = transfer_with_seed(
+            &base_keypair,
+            &from_pubkey,
+            "seed",
+            &system_program::id(),
+            &to,
+            1,
+            Hash::default(),
+        );
+        assert_eq!(tx.message.account_keys[0], base_keypair.pubkey());
+        assert!(tx.is_signed());
+    }
+}
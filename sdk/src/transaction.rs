@@ -7,7 +7,7 @@ use crate::pubkey::Pubkey;
 use crate::short_vec;
 use crate::signature::{KeypairUtil, Signature};
 use bincode::serialize;
-use std::result;
+use std::{convert::TryFrom, ops::Deref, result};
 
 /// Reasons a transaction might be rejected.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -51,6 +51,13 @@ pub enum TransactionError {
 
     /// Transaction contains an invalid account reference
     InvalidAccountIndex,
+
+    /// Transaction would grow account data past the limit for this block
+    MaxAccountsDataGrowthExceeded,
+
+    /// Transaction header counts don't fit its account keys, or its signature count doesn't
+    /// match the number of required signatures
+    SanitizeFailure,
 }
 
 pub type Result<T> = result::Result<T, TransactionError>;
@@ -252,6 +259,36 @@ impl Transaction {
             .all(|signature| *signature != Signature::default())
     }
 
+    /// Check that the transaction is well formed:
+    /// * the header counts fit `account_keys` and the fee payer isn't read-only
+    /// * there is exactly one signature per required signer
+    /// * instructions only reference existing account keys
+    /// * no account key is listed twice
+    pub fn sanitize(&self) -> Result<()> {
+        let header = &self.message.header;
+        let num_required_signatures = header.num_required_signatures as usize;
+        let num_readonly_signed_accounts = header.num_readonly_signed_accounts as usize;
+        let num_readonly_unsigned_accounts = header.num_readonly_unsigned_accounts as usize;
+        let num_account_keys = self.message.account_keys.len();
+        if num_required_signatures + num_readonly_unsigned_accounts > num_account_keys
+            || (num_readonly_signed_accounts > 0
+                && num_readonly_signed_accounts >= num_required_signatures)
+            || self.signatures.len() != num_required_signatures
+        {
+            return Err(TransactionError::SanitizeFailure);
+        }
+        if !self.verify_refs() {
+            return Err(TransactionError::InvalidAccountIndex);
+        }
+        let account_keys = &self.message.account_keys;
+        for (i, key) in account_keys.iter().enumerate() {
+            if account_keys[i + 1..].contains(key) {
+                return Err(TransactionError::AccountLoadedTwice);
+            }
+        }
+        Ok(())
+    }
+
     /// Verify that references in the instructions are valid
     pub fn verify_refs(&self) -> bool {
         let message = self.message();
@@ -269,6 +306,34 @@ impl Transaction {
     }
 }
 
+/// A `Transaction` that passed `Transaction::sanitize()`, for code that must only handle well
+/// formed transactions
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SanitizedTransaction(Transaction);
+
+impl SanitizedTransaction {
+    pub fn into_inner(self) -> Transaction {
+        self.0
+    }
+}
+
+impl TryFrom<Transaction> for SanitizedTransaction {
+    type Error = TransactionError;
+
+    fn try_from(transaction: Transaction) -> Result<Self> {
+        transaction.sanitize()?;
+        Ok(Self(transaction))
+    }
+}
+
+impl Deref for SanitizedTransaction {
+    type Target = Transaction;
+
+    fn deref(&self) -> &Transaction {
+        &self.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -354,6 +419,65 @@ mod tests {
         assert!(!tx.verify_refs());
     }
 
+    #[test]
+    fn test_sanitize() {
+        let key = Keypair::new();
+        let key1 = Pubkey::new_rand();
+        let instructions = vec![CompiledInstruction::new(2, &(), vec![0, 1])];
+        let tx = Transaction::new_with_compiled_instructions(
+            &[&key],
+            &[key1],
+            Hash::default(),
+            vec![Pubkey::new_rand()],
+            instructions,
+        );
+        assert_eq!(tx.sanitize(), Ok(()));
+        let sanitized = SanitizedTransaction::try_from(tx.clone()).unwrap();
+        assert_eq!(sanitized.signatures, tx.signatures);
+        assert_eq!(sanitized.into_inner(), tx);
+
+        let mut tx_missing_signature = tx.clone();
+        tx_missing_signature.signatures.clear();
+        assert_eq!(
+            tx_missing_signature.sanitize(),
+            Err(TransactionError::SanitizeFailure)
+        );
+
+        let mut tx_readonly_payer = tx.clone();
+        tx_readonly_payer
+            .message
+            .header
+            .num_readonly_signed_accounts = 1;
+        assert_eq!(
+            tx_readonly_payer.sanitize(),
+            Err(TransactionError::SanitizeFailure)
+        );
+
+        let mut tx_too_many_readonly = tx.clone();
+        tx_too_many_readonly
+            .message
+            .header
+            .num_readonly_unsigned_accounts = 3;
+        assert_eq!(
+            tx_too_many_readonly.sanitize(),
+            Err(TransactionError::SanitizeFailure)
+        );
+
+        let mut tx_invalid_index = tx.clone();
+        tx_invalid_index.message.instructions[0].accounts[1] = 3;
+        assert_eq!(
+            tx_invalid_index.sanitize(),
+            Err(TransactionError::InvalidAccountIndex)
+        );
+
+        let mut tx_duplicate_key = tx.clone();
+        tx_duplicate_key.message.account_keys[1] = key.pubkey();
+        assert_eq!(
+            SanitizedTransaction::try_from(tx_duplicate_key),
+            Err(TransactionError::AccountLoadedTwice)
+        );
+    }
+
     fn create_sample_transaction() -> Transaction {
         let keypair = Keypair::from_bytes(&[
             48, 83, 2, 1, 1, 48, 5, 6, 3, 43, 101, 112, 4, 34, 4, 32, 255, 101, 36, 24, 124, 23,
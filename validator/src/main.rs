@@ -8,18 +8,22 @@ use solana_clap_utils::{
     input_validators::{is_keypair, is_pubkey_or_keypair},
 };
 use solana_client::rpc_client::RpcClient;
-use solana_core::cluster_info::{Node, VALIDATOR_PORT_RANGE};
+use solana_core::cluster_info::{Node, DEFAULT_TVU_RECEIVE_SOCKETS, VALIDATOR_PORT_RANGE};
 use solana_core::contact_info::ContactInfo;
+use solana_core::crds_gossip_pull::CrdsTimeouts;
 use solana_core::gossip_service::discover;
 use solana_core::ledger_cleanup_service::DEFAULT_MAX_LEDGER_SLOTS;
+use solana_core::preflight::{self, PreflightConfig};
 use solana_core::socketaddr;
+use solana_core::thread_affinity::{self, ThreadAffinityConfig};
+use solana_core::transaction_trace;
 use solana_core::validator::{Validator, ValidatorConfig};
 use solana_ledger::bank_forks::SnapshotConfig;
 use solana_perf::recycler::enable_recycler_warming;
 use solana_sdk::clock::Slot;
 use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::{read_keypair_file, Keypair, KeypairUtil};
+use solana_sdk::signature::{mlock_keypair, read_keypair_file, Keypair, KeypairUtil};
 use std::fs::{self, File};
 use std::io::{self, Read};
 use std::net::{SocketAddr, TcpListener};
@@ -43,12 +47,68 @@ fn port_range_validator(port_range: String) -> Result<(), String> {
     }
 }
 
+fn sampling_rate_validator(rate: String) -> Result<(), String> {
+    match rate.parse::<f64>() {
+        Ok(rate) if rate >= 0.0 && rate <= 1.0 => Ok(()),
+        Ok(_) => Err("Sampling rate must be between 0 and 1".to_string()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+fn trace_sample_rate_validator(rate: String) -> Result<(), String> {
+    match rate.parse::<u64>() {
+        Ok(rate) if rate > 0 => Ok(()),
+        Ok(_) => Err("Trace sample rate must be at least 1".to_string()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+fn poh_nice_validator(nice: String) -> Result<(), String> {
+    match nice.parse::<i32>() {
+        Ok(nice) if nice >= -20 && nice <= 19 => Ok(()),
+        Ok(_) => Err("Nice level must be between -20 and 19".to_string()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+fn receive_sockets_validator(num: String) -> Result<(), String> {
+    match num.parse::<usize>() {
+        Ok(num) if num > 0 => Ok(()),
+        Ok(_) => Err("Number of receive sockets must be at least 1".to_string()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
 fn hash_validator(hash: String) -> Result<(), String> {
     Hash::from_str(&hash)
         .map(|_| ())
         .map_err(|e| format!("{:?}", e))
 }
 
+// Set the timeout of a gossip value type from `TYPE=MILLISECONDS`
+fn set_gossip_timeout(crds_timeouts: &mut CrdsTimeouts, value: &str) -> Result<(), String> {
+    let mut parts = value.splitn(2, '=');
+    let value_type = parts.next().unwrap();
+    let timeout = parts
+        .next()
+        .ok_or_else(|| format!("Expected TYPE=MILLISECONDS, got {}", value))?
+        .parse::<u64>()
+        .map_err(|e| format!("{:?}", e))?;
+    let crds_timeout = match value_type {
+        "contact-info" => &mut crds_timeouts.contact_info,
+        "vote" => &mut crds_timeouts.vote,
+        "epoch-slots" => &mut crds_timeouts.epoch_slots,
+        "archiver-info" => &mut crds_timeouts.archiver_info,
+        _ => return Err(format!("Unknown gossip value type: {}", value_type)),
+    };
+    *crds_timeout = timeout;
+    Ok(())
+}
+
+fn gossip_timeout_validator(value: String) -> Result<(), String> {
+    set_gossip_timeout(&mut CrdsTimeouts::default(), &value)
+}
+
 static TRUCK: Emoji = Emoji("🚚 ", "");
 static SPARKLE: Emoji = Emoji("✨ ", "");
 
@@ -295,6 +355,7 @@ fn initialize_ledger_path(
 pub fn main() {
     let default_dynamic_port_range =
         &format!("{}-{}", VALIDATOR_PORT_RANGE.0, VALIDATOR_PORT_RANGE.1);
+    let default_tvu_receive_sockets = DEFAULT_TVU_RECEIVE_SOCKETS.to_string();
 
     let matches = App::new(crate_name!()).about(crate_description!())
         .version(solana_clap_utils::version!())
@@ -363,6 +424,16 @@ pub fn main() {
                 .validator(solana_net_utils::is_host_port)
                 .help("Rendezvous with the cluster at this entry point"),
         )
+        .arg(
+            Arg::with_name("ip_echo_server")
+                .long("ip-echo-server")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .multiple(true)
+                .requires("entrypoint")
+                .validator(solana_net_utils::is_host_port)
+                .help("Also ask this ip echo server for the public IP address of this node, a majority of the servers and the entry point must agree"),
+        )
         .arg(
             Arg::with_name("no_snapshot_fetch")
                 .long("no-snapshot-fetch")
@@ -377,12 +448,24 @@ pub fn main() {
                 .requires("entrypoint")
                 .help("Do not attempt to fetch a new genesis from the cluster entrypoint, start from a local genesis if present"),
         )
+        .arg(
+            Arg::with_name("lock_keypair_memory")
+                .long("lock-keypair-memory")
+                .takes_value(false)
+                .help("Lock the memory holding the identity, voting and storage keypairs so they are never swapped to disk"),
+        )
         .arg(
             Arg::with_name("no_voting")
                 .long("no-voting")
                 .takes_value(false)
                 .help("Launch node without voting"),
         )
+        .arg(
+            Arg::with_name("compact_repair")
+                .long("compact-repair")
+                .takes_value(false)
+                .help("Send repair requests in the compact wire format, only for clusters where every node decodes it"),
+        )
         .arg(
             Arg::with_name("dev_no_sigverify")
                 .long("dev-no-sigverify")
@@ -396,6 +479,47 @@ pub fn main() {
                 .takes_value(true)
                 .help("Halt the validator when it reaches the given slot"),
         )
+        .arg(
+            Arg::with_name("poh_cores")
+                .long("poh-cores")
+                .value_name("CORES")
+                .takes_value(true)
+                .validator(thread_affinity::is_core_list)
+                .help("Pin the PoH thread to these cores, e.g. 0 or 0,2-3 [default: the first core]"),
+        )
+        .arg(
+            Arg::with_name("sigverify_cores")
+                .long("sigverify-cores")
+                .value_name("CORES")
+                .takes_value(true)
+                .validator(thread_affinity::is_core_list)
+                .help("Pin the signature verification threads to these cores"),
+        )
+        .arg(
+            Arg::with_name("banking_cores")
+                .long("banking-cores")
+                .value_name("CORES")
+                .takes_value(true)
+                .validator(thread_affinity::is_core_list)
+                .help("Pin the banking stage threads to these cores"),
+        )
+        .arg(
+            Arg::with_name("network_cores")
+                .long("network-cores")
+                .value_name("CORES")
+                .takes_value(true)
+                .validator(thread_affinity::is_core_list)
+                .help("Pin the packet receiver and responder threads to these cores"),
+        )
+        .arg(
+            Arg::with_name("poh_nice")
+                .long("poh-nice")
+                .value_name("NICE")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .validator(poh_nice_validator)
+                .help("Run the PoH thread at this nice level, negative levels raise its priority and need CAP_SYS_NICE"),
+        )
         .arg(
             Arg::with_name("rpc_port")
                 .long("rpc-port")
@@ -450,6 +574,15 @@ pub fn main() {
                 .validator(port_range_validator)
                 .help("Range to use for dynamically assigned ports"),
         )
+        .arg(
+            clap::Arg::with_name("tvu_receive_sockets")
+                .long("tvu-receive-sockets")
+                .value_name("NUM")
+                .takes_value(true)
+                .default_value(&default_tvu_receive_sockets)
+                .validator(receive_sockets_validator)
+                .help("Number of SO_REUSEPORT sockets, each with its own receiver thread, to bind to each TVU port"),
+        )
         .arg(
             clap::Arg::with_name("snapshot_interval_slots")
                 .long("snapshot-interval-slots")
@@ -458,6 +591,38 @@ pub fn main() {
                 .default_value("100")
                 .help("Number of slots between generating snapshots, 0 to disable snapshots"),
         )
+        .arg(
+            clap::Arg::with_name("snapshot_upload_url")
+                .long("snapshot-upload-url")
+                .value_name("URL")
+                .takes_value(true)
+                .help("Upload snapshots and the entries of rooted slots, each with an integrity manifest, to this S3-compatible bucket URL"),
+        )
+        .arg(
+            clap::Arg::with_name("gossip_timeout")
+                .long("gossip-timeout")
+                .value_name("TYPE=MILLISECONDS")
+                .takes_value(true)
+                .multiple(true)
+                .validator(gossip_timeout_validator)
+                .help("How long gossip values of TYPE received from other nodes are kept since they were last updated, TYPE is one of contact-info, vote, epoch-slots or archiver-info [default: 15000 for every type]"),
+        )
+        .arg(
+            clap::Arg::with_name("storage_validation_sampling_rate")
+                .long("storage-validation-sampling-rate")
+                .value_name("RATE")
+                .takes_value(true)
+                .validator(sampling_rate_validator)
+                .help("Fraction (0 to 1) of archiver storage proofs to check. Default increases with the node's stake"),
+        )
+        .arg(
+            clap::Arg::with_name("trace_transactions")
+                .long("trace-transactions")
+                .value_name("N")
+                .takes_value(true)
+                .validator(trace_sample_rate_validator)
+                .help("Trace one in N transactions through ingest, signature verification, banking, PoH and broadcast, reporting the time spent between stages as metrics"),
+        )
         .arg(
             clap::Arg::with_name("limit_ledger_size")
                 .long("limit-ledger-size")
@@ -476,6 +641,12 @@ pub fn main() {
                 .takes_value(false)
                 .help("Use CUDA"),
         )
+        .arg(
+            clap::Arg::with_name("ignore_preflight_failures")
+                .long("ignore-preflight-failures")
+                .takes_value(false)
+                .help("Start even when a preflight check of the host fails"),
+        )
         .arg(
             Arg::with_name("expected_genesis_hash")
                 .long("expected-genesis-hash")
@@ -527,6 +698,7 @@ pub fn main() {
     let init_complete_file = matches.value_of("init_complete_file");
     let skip_poh_verify = matches.is_present("skip_poh_verify");
     let cuda = matches.is_present("cuda");
+    let ignore_preflight_failures = matches.is_present("ignore_preflight_failures");
     let no_genesis_fetch = matches.is_present("no_genesis_fetch");
     let no_snapshot_fetch = matches.is_present("no_snapshot_fetch");
     let rpc_port = value_t!(matches, "rpc_port", u16);
@@ -534,6 +706,30 @@ pub fn main() {
     let mut validator_config = ValidatorConfig::default();
     validator_config.dev_sigverify_disabled = matches.is_present("dev_no_sigverify");
     validator_config.dev_halt_at_slot = value_t!(matches, "dev_halt_at_slot", Slot).ok();
+    let core_list = |name| {
+        matches
+            .value_of(name)
+            .map(|list| thread_affinity::parse_core_list(list).unwrap())
+            .unwrap_or_default()
+    };
+    validator_config.thread_affinity = ThreadAffinityConfig {
+        poh_cores: core_list("poh_cores"),
+        sigverify_cores: core_list("sigverify_cores"),
+        banking_cores: core_list("banking_cores"),
+        network_cores: core_list("network_cores"),
+        poh_nice: value_t!(matches, "poh_nice", i32).ok(),
+    };
+    if let Some(gossip_timeouts) = matches.values_of("gossip_timeout") {
+        for gossip_timeout in gossip_timeouts {
+            set_gossip_timeout(&mut validator_config.crds_timeouts, gossip_timeout).unwrap();
+        }
+    }
+    validator_config.storage_validation_sampling_rate =
+        value_t!(matches, "storage_validation_sampling_rate", f64).ok();
+
+    if let Ok(sample_rate) = value_t!(matches, "trace_transactions", u64) {
+        transaction_trace::set_sample_rate(sample_rate);
+    }
 
     validator_config.rpc_config.enable_validator_exit = matches.is_present("enable_rpc_exit");
 
@@ -572,6 +768,9 @@ pub fn main() {
         snapshot_package_output_path: ledger_path.clone(),
     });
 
+    validator_config.snapshot_upload_url =
+        matches.value_of("snapshot_upload_url").map(String::from);
+
     if matches.is_present("limit_ledger_size") {
         validator_config.max_ledger_slots = Some(DEFAULT_MAX_LEDGER_SLOTS);
     }
@@ -634,6 +833,7 @@ pub fn main() {
     if matches.is_present("no_voting") {
         validator_config.voting_disabled = true;
     }
+    validator_config.compact_repair = matches.is_present("compact_repair");
 
     let vote_account = pubkey_of(&matches, "vote_account").unwrap_or_else(|| {
         // Disable voting because normal (=not bootstrapping) validator rejects
@@ -648,6 +848,8 @@ pub fn main() {
     solana_metrics::set_host_id(identity_keypair.pubkey().to_string());
     solana_metrics::set_panic_hook("validator");
 
+    let mut preflight_report = preflight::check_host(&PreflightConfig::new(&ledger_path, cuda));
+
     if cuda {
         solana_perf::perf_libs::init_cuda();
         enable_recycler_warming();
@@ -665,10 +867,17 @@ pub fn main() {
     let cluster_entrypoint = entrypoint.map(|entrypoint| {
         let entrypoint_addr = solana_net_utils::parse_host_port(entrypoint)
             .expect("failed to parse entrypoint address");
-        let ip_addr =
-            solana_net_utils::get_public_ip_addr(&entrypoint_addr).unwrap_or_else(|err| {
+        let mut ip_echo_server_addrs = vec![entrypoint_addr];
+        if let Some(ip_echo_servers) = matches.values_of("ip_echo_server") {
+            ip_echo_server_addrs.extend(ip_echo_servers.map(|ip_echo_server| {
+                solana_net_utils::parse_host_port(ip_echo_server)
+                    .expect("failed to parse ip echo server address")
+            }));
+        }
+        let ip_addr = solana_net_utils::get_public_ip_addr_with_quorum(&ip_echo_server_addrs)
+            .unwrap_or_else(|err| {
                 error!(
-                    "Failed to contact cluster entrypoint {} ({}): {}",
+                    "Failed to determine the public IP address via cluster entrypoint {} ({}): {}",
                     entrypoint, entrypoint_addr, err
                 );
                 exit(1);
@@ -679,8 +888,12 @@ pub fn main() {
     });
 
     let mut tcp_ports = vec![];
-    let mut node =
-        Node::new_with_external_ip(&identity_keypair.pubkey(), &gossip_addr, dynamic_port_range);
+    let mut node = Node::new_with_external_ip_and_tvu_receive_sockets(
+        &identity_keypair.pubkey(),
+        &gossip_addr,
+        dynamic_port_range,
+        value_t_or_exit!(matches, "tvu_receive_sockets", usize),
+    );
     if let Ok(rpc_port) = rpc_port {
         let rpc_pubsub_port = rpc_port + 1;
         node.info.rpc = SocketAddr::new(node.info.gossip.ip(), rpc_port);
@@ -714,12 +927,25 @@ pub fn main() {
             tcp_listeners.push((node.info.gossip.port(), ip_echo));
         }
 
-        solana_net_utils::verify_reachable_ports(
+        preflight::check_ports(
+            &mut preflight_report,
             &cluster_entrypoint.gossip,
             tcp_listeners,
             &udp_sockets,
         );
+    }
 
+    println!("Preflight checks:\n{}", preflight_report);
+    if preflight_report.has_failures() {
+        if ignore_preflight_failures {
+            warn!("Ignoring failed preflight checks");
+        } else {
+            error!("Preflight checks failed, use --ignore-preflight-failures to start anyway");
+            exit(1);
+        }
+    }
+
+    if let Some(ref cluster_entrypoint) = cluster_entrypoint {
         let (rpc_addr, rpc_client) = create_rpc_client(cluster_entrypoint).unwrap_or_else(|err| {
             error!("unable to create rpc client: {}", err);
             std::process::exit(1);
@@ -771,13 +997,28 @@ pub fn main() {
         }
     }
 
+    let identity_keypair = Arc::new(identity_keypair);
+    let voting_keypair = Arc::new(voting_keypair);
+    let storage_keypair = Arc::new(storage_keypair);
+    if matches.is_present("lock_keypair_memory") {
+        for keypair in &[&identity_keypair, &voting_keypair, &storage_keypair] {
+            if let Err(err) = mlock_keypair(keypair) {
+                warn!(
+                    "Unable to lock keypair {} memory: {}",
+                    keypair.pubkey(),
+                    err
+                );
+            }
+        }
+    }
+
     let validator = Validator::new(
         node,
-        &Arc::new(identity_keypair),
+        &identity_keypair,
         &ledger_path,
         &vote_account,
-        &Arc::new(voting_keypair),
-        &Arc::new(storage_keypair),
+        &voting_keypair,
+        &storage_keypair,
         cluster_entrypoint.as_ref(),
         !skip_poh_verify,
         &validator_config,